@@ -1077,6 +1077,11 @@ export function RecordingSettings() {
                     <SelectItem value="whisper-large-v3-turbo">
                       whisper-large-turbo
                     </SelectItem>
+                    <SelectItem value="whisper-large-v3">whisper-large-v3</SelectItem>
+                    <SelectItem value="whisper-distil-small-en">
+                      whisper-distil-small (english)
+                    </SelectItem>
+                    <SelectItem value="whisper-cpp">whisper-cpp</SelectItem>
                   </SelectContent>
                 </Select>
               </div>
@@ -1,8 +1,9 @@
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 use screenpipe_audio::vad_engine::VadSensitivity;
+use screenpipe_audio::whisper::ModelOptions;
 use screenpipe_audio::{
     create_whisper_channel, default_input_device, record_and_transcribe, AudioDevice, AudioInput,
-    AudioStream, AudioTranscriptionEngine,
+    AudioStream, AudioTranscriptionEngine, DeviceTranscriptionOptions,
 };
 use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
@@ -26,6 +27,8 @@ async fn setup_test() -> (
         VadSensitivity::High,
         vec![],
         None,
+        ModelOptions::default(),
+        DeviceTranscriptionOptions::default(),
     )
     .await
     .unwrap();
@@ -0,0 +1,28 @@
+// cargo bench --bench resample_benchmark
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use screenpipe_audio::{resample_with, ResampleQuality};
+
+fn benchmark_resample(c: &mut Criterion) {
+    // a 30s chunk of a 48khz device, resampled to whisper's 16khz
+    let audio: Vec<f32> = (0..48000 * 30)
+        .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin() * 0.5)
+        .collect();
+
+    let mut group = c.benchmark_group("resample_48k_to_16k");
+    group.sample_size(10);
+    for quality in [
+        ResampleQuality::Fast,
+        ResampleQuality::Balanced,
+        ResampleQuality::High,
+    ] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{:?}", quality)),
+            &quality,
+            |b, &quality| b.iter(|| resample_with(black_box(&audio), 48000, 16000, quality)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_resample);
+criterion_main!(benches);
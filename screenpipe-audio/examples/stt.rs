@@ -3,7 +3,7 @@ use screenpipe_audio::pyannote::embedding::EmbeddingExtractor;
 use screenpipe_audio::pyannote::identify::EmbeddingManager;
 use screenpipe_audio::stt::{prepare_segments, stt};
 use screenpipe_audio::vad_engine::{SileroVad, VadEngine};
-use screenpipe_audio::whisper::WhisperModel;
+use screenpipe_audio::whisper::{ModelOptions, WhisperModel};
 use screenpipe_audio::{AudioInput, AudioTranscriptionEngine};
 use screenpipe_core::Language;
 use std::path::PathBuf;
@@ -47,7 +47,11 @@ async fn main() {
     ];
 
     let whisper_model = Arc::new(Mutex::new(
-        WhisperModel::new(&AudioTranscriptionEngine::WhisperLargeV3Turbo).unwrap(),
+        WhisperModel::new(
+            &AudioTranscriptionEngine::WhisperLargeV3Turbo,
+            &ModelOptions::default(),
+        )
+        .unwrap(),
     ));
     let vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>> =
         Arc::new(Mutex::new(Box::new(SileroVad::new().await.unwrap())));
@@ -87,6 +91,7 @@ async fn main() {
                 sample_rate: 44100, // hardcoded based on test data sample rate
                 channels: 1,
                 device: Arc::new(screenpipe_audio::default_input_device().unwrap()),
+                capture_timing: None,
             };
 
             let mut segments = prepare_segments(
@@ -0,0 +1,267 @@
+use anyhow::{anyhow, Context, Result};
+use candle_transformers::models::whisper as m;
+use hf_hub::{api::sync::Api, Repo, RepoType};
+use log::debug;
+use ndarray::{Array2, Axis};
+use ort::{GraphOptimizationLevel, Session};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// wav2vec2 fine-tuned for english character CTC, exported to ONNX
+const ALIGNMENT_REPO: &str = "Xenova/wav2vec2-base-960h";
+const BLANK: &str = "<pad>";
+const WORD_SEPARATOR: &str = "|";
+
+/// Where a word of a transcript was spoken, in seconds from the start of the audio
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WordTiming {
+    pub word: String,
+    pub start: f64,
+    pub end: f64,
+    /// Mean probability of the word's characters where they were placed, 0 for words without any
+    /// character the model knows, which get the time between their neighbours
+    pub confidence: f32,
+}
+
+/// Forced aligner: places the words of an already known transcript in its audio with a wav2vec2
+/// CTC model, precise to a frame (20ms). Meant for segments, not hour long recordings, the whole
+/// audio goes through the model at once
+#[derive(Clone)]
+pub struct Aligner {
+    session: Arc<Session>,
+    vocab: HashMap<char, usize>,
+    blank: usize,
+    separator: usize,
+}
+
+impl Aligner {
+    pub fn new() -> Result<Self> {
+        debug!("Fetching alignment model {}", ALIGNMENT_REPO);
+        let repo = Api::new()?.repo(Repo::with_revision(
+            ALIGNMENT_REPO.to_string(),
+            RepoType::Model,
+            "main".to_string(),
+        ));
+        let model_filename = repo.get("onnx/model.onnx")?;
+        let vocab: HashMap<String, usize> =
+            serde_json::from_str(&std::fs::read_to_string(repo.get("vocab.json")?)?)?;
+
+        let token = |name: &str| {
+            vocab
+                .get(name)
+                .copied()
+                .ok_or_else(|| anyhow!("{} not in the alignment vocabulary", name))
+        };
+        let blank = token(BLANK)?;
+        let separator = token(WORD_SEPARATOR)?;
+        let vocab = vocab
+            .iter()
+            .filter_map(|(token, &id)| {
+                let mut chars = token.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if token != WORD_SEPARATOR => Some((c, id)),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        let session = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .commit_from_file(model_filename)?;
+
+        Ok(Self {
+            session: Arc::new(session),
+            vocab,
+            blank,
+            separator,
+        })
+    }
+
+    /// Word boundaries of `transcript` in 16khz mono `audio`
+    pub fn align(&self, audio: &[f32], transcript: &str) -> Result<Vec<WordTiming>> {
+        let words: Vec<&str> = transcript.split_whitespace().collect();
+        if words.is_empty() || audio.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let emissions = self.emissions(audio)?;
+        let seconds_per_frame =
+            audio.len() as f64 / m::SAMPLE_RATE as f64 / emissions.nrows() as f64;
+
+        // characters of every word, separated like the model was trained on
+        let mut tokens = Vec::new();
+        let mut word_tokens = Vec::with_capacity(words.len());
+        for (i, word) in words.iter().enumerate() {
+            if i > 0 {
+                tokens.push(self.separator);
+            }
+            let start = tokens.len();
+            tokens.extend(
+                word.chars()
+                    .flat_map(char::to_uppercase)
+                    .filter_map(|c| self.vocab.get(&c).copied()),
+            );
+            word_tokens.push(start..tokens.len());
+        }
+
+        let spans = forced_align(&emissions, &tokens, self.blank)
+            .ok_or_else(|| anyhow!("audio too short to align {} characters", tokens.len()))?;
+
+        let mut timings: Vec<WordTiming> = words
+            .iter()
+            .zip(&word_tokens)
+            .map(|(word, range)| {
+                let spans = &spans[range.clone()];
+                match (spans.first(), spans.last()) {
+                    (Some(first), Some(last)) => WordTiming {
+                        word: word.to_string(),
+                        start: first.start as f64 * seconds_per_frame,
+                        end: last.end as f64 * seconds_per_frame,
+                        confidence: spans.iter().map(|span| span.probability).sum::<f32>()
+                            / spans.len() as f32,
+                    },
+                    _ => WordTiming {
+                        word: word.to_string(),
+                        start: f64::NAN,
+                        end: f64::NAN,
+                        confidence: 0.0,
+                    },
+                }
+            })
+            .collect();
+        fill_unaligned(&mut timings, audio.len() as f64 / m::SAMPLE_RATE as f64);
+        Ok(timings)
+    }
+
+    /// Log probabilities of every token for each frame
+    fn emissions(&self, audio: &[f32]) -> Result<Array2<f32>> {
+        // the feature extractor of the model normalizes to zero mean and unit variance
+        let mean = audio.iter().sum::<f32>() / audio.len() as f32;
+        let variance = audio.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / audio.len() as f32;
+        let std = (variance + 1e-7).sqrt();
+        let input =
+            ndarray::Array1::from_iter(audio.iter().map(|s| (s - mean) / std)).insert_axis(Axis(0));
+
+        let outputs = self
+            .session
+            .run(ort::inputs!["input_values" => input.view()]?)?;
+        let logits = outputs
+            .get("logits")
+            .context("alignment model output not found")?
+            .try_extract_tensor::<f32>()?;
+        let logits = logits.index_axis(Axis(0), 0);
+
+        let mut emissions = Array2::zeros((logits.shape()[0], logits.shape()[1]));
+        for (frame, mut row) in logits.outer_iter().zip(emissions.outer_iter_mut()) {
+            let max = frame.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let log_sum = frame.iter().map(|l| (l - max).exp()).sum::<f32>().ln() + max;
+            row.assign(&frame.mapv(|l| l - log_sum));
+        }
+        Ok(emissions)
+    }
+}
+
+/// Frames a token was spoken in
+#[derive(Clone, Debug)]
+struct TokenSpan {
+    start: usize,
+    /// Exclusive
+    end: usize,
+    probability: f32,
+}
+
+/// Viterbi path through the CTC trellis, the most likely frames for each token when they are
+/// spoken in order, with blanks or repetitions of a token in between
+fn forced_align(emissions: &Array2<f32>, tokens: &[usize], blank: usize) -> Option<Vec<TokenSpan>> {
+    let frames = emissions.nrows();
+    if tokens.is_empty() || frames < tokens.len() {
+        return None;
+    }
+    let stay = |t: usize, j: usize| {
+        // before the first token only blanks are emitted
+        let blank = emissions[[t, blank]];
+        if j == 0 {
+            blank
+        } else {
+            blank.max(emissions[[t, tokens[j - 1]]])
+        }
+    };
+
+    // trellis[t][j]: best score after t frames with j tokens emitted
+    let n = tokens.len();
+    let mut trellis = vec![vec![f32::NEG_INFINITY; n + 1]; frames + 1];
+    trellis[0][0] = 0.0;
+    for t in 0..frames {
+        for j in 0..=n {
+            let stayed = trellis[t][j] + stay(t, j);
+            let advanced = if j > 0 {
+                trellis[t][j - 1] + emissions[[t, tokens[j - 1]]]
+            } else {
+                f32::NEG_INFINITY
+            };
+            trellis[t + 1][j] = stayed.max(advanced);
+        }
+    }
+    if trellis[frames][n] == f32::NEG_INFINITY {
+        return None;
+    }
+
+    // walk back, noting the frame each token was entered at
+    let mut starts = vec![0; n];
+    let mut j = n;
+    for t in (0..frames).rev() {
+        if j == 0 {
+            break;
+        }
+        let advanced = trellis[t][j - 1] + emissions[[t, tokens[j - 1]]];
+        let stayed = trellis[t][j] + stay(t, j);
+        if advanced >= stayed {
+            starts[j - 1] = t;
+            j -= 1;
+        }
+    }
+
+    Some(
+        (0..n)
+            .map(|j| {
+                let start = starts[j];
+                // a token lasts while it keeps being the likeliest of it and a blank
+                let mut end = start + 1;
+                let next_start = starts.get(j + 1).copied().unwrap_or(frames);
+                while end < next_start && emissions[[end, tokens[j]]] >= emissions[[end, blank]] {
+                    end += 1;
+                }
+                TokenSpan {
+                    start,
+                    end,
+                    probability: emissions[[start, tokens[j]]].exp(),
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Words without known characters get the gap between the aligned words around them
+fn fill_unaligned(timings: &mut [WordTiming], duration: f64) {
+    let mut i = 0;
+    while i < timings.len() {
+        if !timings[i].start.is_nan() {
+            i += 1;
+            continue;
+        }
+        let run_start = i;
+        while i < timings.len() && timings[i].start.is_nan() {
+            i += 1;
+        }
+        let from = run_start
+            .checked_sub(1)
+            .map_or(0.0, |previous| timings[previous].end);
+        let to = timings.get(i).map_or(duration, |next| next.start);
+        let step = (to - from).max(0.0) / (i - run_start) as f64;
+        for (k, timing) in timings[run_start..i].iter_mut().enumerate() {
+            timing.start = from + step * k as f64;
+            timing.end = from + step * (k + 1) as f64;
+        }
+    }
+}
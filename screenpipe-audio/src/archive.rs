@@ -0,0 +1,336 @@
+use crate::encode::{encode_stream, transcode_stream, AudioCodec};
+use anyhow::{Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Manifest of an archive directory, one `ManifestEntry` per line
+pub const MANIFEST_FILE: &str = "manifest.jsonl";
+
+/// How often `recompress_periodically` looks for files old enough
+const RECOMPRESS_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Held while the manifest is written, its lines appended while it is rewritten would be lost
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// Where and how the rolling audio archive is written
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArchiveConfig {
+    pub dir: PathBuf,
+    /// Time covered by each file of a device, files start at multiples of it
+    pub file_duration: Duration,
+    pub codec: AudioCodec,
+    /// Re-encode files once their audio is old enough, see `recompress_periodically`
+    pub recompress: Option<Recompression>,
+}
+
+/// Re-encoding of archive files that are rarely played back any more to a smaller codec, e.g.
+/// 24kbps opus or flac to 12kbps opus after a month. Transcripts aren't touched
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Recompression {
+    /// Age of all the audio of a file from which it is re-encoded
+    pub after: Duration,
+    pub codec: AudioCodec,
+}
+
+/// Audio of a device in an archive file, a stream decodable on its own
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub device: String,
+    pub file: PathBuf,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Where the stream starts in the file and how many bytes it takes
+    pub offset: u64,
+    pub length: u64,
+    /// The stream was re-encoded by `recompress`, it isn't re-encoded again
+    #[serde(default)]
+    pub recompressed: bool,
+}
+
+struct DeviceFile {
+    path: PathBuf,
+    /// Start of the time slot of the file
+    slot: i64,
+    len: u64,
+}
+
+/// Keeps the audio of each device in files covering `file_duration` each, e.g. an hour, rather
+/// than a file per chunk, with a manifest of the time range and byte range of each chunk in them.
+/// Playing a few minutes back only reads and decodes the chunks covering them
+pub struct AudioArchive {
+    config: ArchiveConfig,
+    files: HashMap<String, DeviceFile>,
+    /// End of the audio archived of each device, the overlap between chunks is archived once
+    archived_until: HashMap<String, DateTime<Utc>>,
+}
+
+impl AudioArchive {
+    pub fn new(config: ArchiveConfig) -> Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        info!(
+            "archiving audio to {} in files of {:?}",
+            config.dir.display(),
+            config.file_duration
+        );
+        Ok(Self {
+            config,
+            files: HashMap::new(),
+            archived_until: HashMap::new(),
+        })
+    }
+
+    /// Appends mono `audio` of `device` captured from `captured_at` to the file of its time slot,
+    /// leaving out what was already archived
+    pub fn append(
+        &mut self,
+        device: &str,
+        audio: &[f32],
+        sample_rate: u32,
+        captured_at: DateTime<Utc>,
+    ) -> Result<Option<ManifestEntry>> {
+        let sample =
+            |n: usize| chrono::Duration::microseconds(n as i64 * 1_000_000 / sample_rate as i64);
+        let skip = match self.archived_until.get(device) {
+            Some(&until) if until > captured_at => {
+                let secs = (until - captured_at).num_microseconds().unwrap_or(i64::MAX) as f64
+                    / 1_000_000.0;
+                ((secs * sample_rate as f64) as usize).min(audio.len())
+            }
+            _ => 0,
+        };
+        let audio = &audio[skip..];
+        if audio.is_empty() {
+            return Ok(None);
+        }
+        let start = captured_at + sample(skip);
+        let end = start + sample(audio.len());
+
+        let slot_secs = self.config.file_duration.as_secs().max(1) as i64;
+        let slot = start.timestamp().div_euclid(slot_secs) * slot_secs;
+        if self
+            .files
+            .get(device)
+            .map_or(true, |file| file.slot != slot)
+        {
+            let slot_start = Utc
+                .timestamp_opt(slot, 0)
+                .single()
+                .context("archive slot out of range")?;
+            let path = self.config.dir.join(format!(
+                "{}_{}.{}",
+                device.replace(['/', '\\'], "_"),
+                slot_start.format("%Y-%m-%d_%H-%M-%S"),
+                self.config.codec.stream_extension()
+            ));
+            // a restart within the slot appends to the file it left
+            let len = std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+            debug!("device: {}, archiving to {}", device, path.display());
+            self.files
+                .insert(device.to_string(), DeviceFile { path, slot, len });
+        }
+        let file = self.files.get_mut(device).expect("file was just opened");
+
+        let stream = encode_stream(
+            bytemuck::cast_slice(audio),
+            sample_rate,
+            1,
+            self.config.codec,
+        )?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&file.path)?
+            .write_all(&stream)?;
+        let entry = ManifestEntry {
+            device: device.to_string(),
+            file: file.path.clone(),
+            start,
+            end,
+            offset: file.len,
+            length: stream.len() as u64,
+            recompressed: false,
+        };
+        file.len += stream.len() as u64;
+        self.archived_until.insert(device.to_string(), end);
+
+        let _lock = MANIFEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.config.dir.join(MANIFEST_FILE))?;
+        writeln!(manifest, "{}", serde_json::to_string(&entry)?)?;
+        Ok(Some(entry))
+    }
+}
+
+/// Entries of the manifest in `dir` overlapping `start`..`end`, of `device` or every device,
+/// oldest first
+pub fn find_entries(
+    dir: &Path,
+    device: Option<&str>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<ManifestEntry>> {
+    let mut entries: Vec<ManifestEntry> = read_manifest(dir)?
+        .into_iter()
+        .filter_map(|(_, entry)| entry)
+        .filter(|entry| {
+            device.map_or(true, |device| entry.device == device)
+                && entry.start < end
+                && entry.end > start
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.start);
+    Ok(entries)
+}
+
+/// Lines of the manifest in `dir` with their entry, None for lines that can't be read
+fn read_manifest(dir: &Path) -> Result<Vec<(String, Option<ManifestEntry>)>> {
+    let manifest = match File::open(dir.join(MANIFEST_FILE)) {
+        Ok(manifest) => manifest,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut lines = Vec::new();
+    for line in BufReader::new(manifest).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // a line cut by a crash is skipped
+        let entry = serde_json::from_str::<ManifestEntry>(&line).ok();
+        if entry.is_none() {
+            debug!("skipping unreadable archive manifest line");
+        }
+        lines.push((line, entry));
+    }
+    Ok(lines)
+}
+
+/// The encoded stream of an entry, e.g. to decode or send as is
+pub fn read_entry(entry: &ManifestEntry) -> Result<Vec<u8>> {
+    let mut file = File::open(&entry.file)?;
+    file.seek(SeekFrom::Start(entry.offset))?;
+    let mut stream = vec![0; entry.length as usize];
+    file.read_exact(&mut stream)?;
+    Ok(stream)
+}
+
+/// Re-encodes the files in `dir` whose audio all ended before `before` to `codec`, pointing their
+/// manifest entries at the new files before deleting the old ones. Returns the bytes reclaimed.
+/// Files that fail are left as they were and tried again next time
+pub fn recompress(dir: &Path, before: DateTime<Utc>, codec: AudioCodec) -> Result<u64> {
+    let mut files: BTreeMap<PathBuf, Vec<ManifestEntry>> = BTreeMap::new();
+    for entry in read_manifest(dir)?
+        .into_iter()
+        .filter_map(|(_, entry)| entry)
+    {
+        files.entry(entry.file.clone()).or_default().push(entry);
+    }
+
+    let mut reclaimed = 0;
+    for (file, mut entries) in files {
+        if entries
+            .iter()
+            .any(|entry| entry.end >= before || entry.recompressed)
+        {
+            continue;
+        }
+        entries.sort_by_key(|entry| entry.offset);
+        match recompress_file(dir, &file, &entries, codec) {
+            Ok(saved) => reclaimed += saved,
+            Err(e) => warn!("failed to recompress {}: {:?}", file.display(), e),
+        }
+    }
+    Ok(reclaimed)
+}
+
+fn recompress_file(
+    dir: &Path,
+    file: &Path,
+    entries: &[ManifestEntry],
+    codec: AudioCodec,
+) -> Result<u64> {
+    let old_len = std::fs::metadata(file)?.len();
+    let target = file.with_extension(format!("recompressed.{}", codec.stream_extension()));
+    let mut out = BufWriter::new(File::create(&target)?);
+    let mut len = 0;
+    let mut recompressed = Vec::new();
+    for entry in entries {
+        let stream = transcode_stream(&read_entry(entry)?, codec)?;
+        out.write_all(&stream)?;
+        recompressed.push(ManifestEntry {
+            file: target.clone(),
+            offset: len,
+            length: stream.len() as u64,
+            recompressed: true,
+            ..entry.clone()
+        });
+        len += stream.len() as u64;
+    }
+    out.into_inner()?.sync_all()?;
+
+    replace_entries(dir, file, &recompressed)?;
+    std::fs::remove_file(file)?;
+    info!(
+        "recompressed {} from {} to {} bytes",
+        file.display(),
+        old_len,
+        len
+    );
+    Ok(old_len.saturating_sub(len))
+}
+
+/// Rewrites the manifest with the entries of `file` replaced by `entries`
+fn replace_entries(dir: &Path, file: &Path, entries: &[ManifestEntry]) -> Result<()> {
+    let _lock = MANIFEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = dir.join(MANIFEST_FILE);
+    let tmp = path.with_extension("jsonl.tmp");
+    let mut manifest = BufWriter::new(File::create(&tmp)?);
+    for (line, entry) in read_manifest(dir)? {
+        if entry.map_or(true, |entry| entry.file != file) {
+            writeln!(manifest, "{}", line)?;
+        }
+    }
+    for entry in entries {
+        writeln!(manifest, "{}", serde_json::to_string(entry)?)?;
+    }
+    manifest.into_inner()?.sync_all()?;
+    std::fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// Runs `recompress` on the archive of `config` every hour, if it has a `Recompression`
+pub async fn recompress_periodically(config: ArchiveConfig) {
+    let Some(recompression) = config.recompress else {
+        return;
+    };
+    let Ok(after) = chrono::Duration::from_std(recompression.after) else {
+        error!("audio archive recompression age out of range");
+        return;
+    };
+    let mut interval = tokio::time::interval(RECOMPRESS_INTERVAL);
+    loop {
+        interval.tick().await;
+        let dir = config.dir.clone();
+        let before = Utc::now() - after;
+        match tokio::task::spawn_blocking(move || recompress(&dir, before, recompression.codec))
+            .await
+        {
+            Ok(Ok(0)) => {}
+            Ok(Ok(reclaimed)) => info!(
+                "recompressing the audio archive reclaimed {} MB",
+                reclaimed / (1024 * 1024)
+            ),
+            Ok(Err(e)) => error!("failed to recompress the audio archive: {:?}", e),
+            Err(e) => error!("audio archive recompression panicked: {}", e),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use crate::whisper::{DetectedLanguage, SegmentConfidence, TextSegment, Transcript};
+use anyhow::{bail, Result};
+use hound::{WavSpec, WavWriter};
+use lazy_static::lazy_static;
+use log::{debug, info};
+use reqwest::Client;
+use screenpipe_core::Language;
+use serde::Deserialize;
+use serde_json::json;
+use std::env;
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    pub(crate) static ref ASSEMBLYAI_API_URL: String = env::var("ASSEMBLYAI_API_URL")
+        .unwrap_or_else(|_| "https://api.assemblyai.com/v2".to_string());
+}
+
+/// How often the transcript is checked while AssemblyAI works on it
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Chunks take a few seconds, a transcript still queued after this is given up on
+const TRANSCRIPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Deserialize)]
+struct UploadResponse {
+    upload_url: String,
+}
+
+#[derive(Deserialize)]
+struct TranscriptResponse {
+    id: String,
+    /// queued, processing, completed or error
+    status: String,
+    error: Option<String>,
+    text: Option<String>,
+    /// 0..1 over the whole transcript
+    confidence: Option<f64>,
+    language_code: Option<String>,
+    language_confidence: Option<f64>,
+    /// Model the transcript was made with, e.g. "best" or "universal"
+    speech_model: Option<String>,
+    utterances: Option<Vec<Utterance>>,
+}
+
+#[derive(Deserialize)]
+struct Utterance {
+    /// "A", "B", ... in the order speakers first talk
+    speaker: String,
+    text: String,
+    /// Milliseconds from the start of the audio
+    start: Option<u64>,
+    end: Option<u64>,
+}
+
+/// Transcribes with AssemblyAI's async API: the audio is uploaded as wav, transcribed with speaker
+/// labels and polled until done. Each speaker turn is a line of the returned text.
+///
+/// A single language is forced, several are the candidates of its language detection. `keywords`
+/// are boosted
+pub async fn transcribe_with_assemblyai(
+    api_key: &str,
+    audio_data: &[f32],
+    device: &str,
+    sample_rate: u32,
+    languages: &[Language],
+    keywords: &[String],
+) -> Result<Transcript> {
+    debug!("starting assemblyai transcription");
+    let client = Client::new();
+
+    let upload: UploadResponse = client
+        .post(format!("{}/upload", *ASSEMBLYAI_API_URL))
+        .header("authorization", api_key)
+        .body(wav_bytes(audio_data, sample_rate)?)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let mut request = json!({
+        "audio_url": upload.upload_url,
+        "speaker_labels": true,
+    });
+    match languages {
+        [language] => request["language_code"] = json!(language.as_lang_code()),
+        languages => {
+            request["language_detection"] = json!(true);
+            if !languages.is_empty() {
+                request["language_detection_options"] = json!({
+                    "expected_languages": languages
+                        .iter()
+                        .map(|language| language.as_lang_code())
+                        .collect::<Vec<_>>(),
+                });
+            }
+        }
+    }
+    if !keywords.is_empty() {
+        request["word_boost"] = json!(keywords);
+    }
+
+    let mut transcript: TranscriptResponse = client
+        .post(format!("{}/transcript", *ASSEMBLYAI_API_URL))
+        .header("authorization", api_key)
+        .json(&request)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let started = Instant::now();
+    loop {
+        match transcript.status.as_str() {
+            "completed" => break,
+            "error" => bail!(
+                "assemblyai transcription failed: {}",
+                transcript.error.unwrap_or_default()
+            ),
+            _ if started.elapsed() > TRANSCRIPT_TIMEOUT => bail!(
+                "assemblyai transcript {} not done after {:?}",
+                transcript.id,
+                TRANSCRIPT_TIMEOUT
+            ),
+            _ => tokio::time::sleep(POLL_INTERVAL).await,
+        }
+        transcript = client
+            .get(format!(
+                "{}/transcript/{}",
+                *ASSEMBLYAI_API_URL, transcript.id
+            ))
+            .header("authorization", api_key)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+    }
+
+    let segments = transcript
+        .utterances
+        .iter()
+        .flatten()
+        .filter_map(|u| {
+            Some(TextSegment {
+                start: u.start? as f64 / 1000.0,
+                end: u.end? as f64 / 1000.0,
+                text: u.text.clone(),
+            })
+        })
+        .collect();
+    let text = match transcript.utterances.as_deref() {
+        Some(utterances) if !utterances.is_empty() => {
+            debug!(
+                "device: {}, {} speaker turns, speakers {:?}",
+                device,
+                utterances.len(),
+                utterances.iter().map(|u| &u.speaker).collect::<Vec<_>>()
+            );
+            utterances
+                .iter()
+                .map(|u| u.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        _ => transcript.text.unwrap_or_default(),
+    };
+    info!(
+        "device: {}, assemblyai transcription successful. length: {} characters",
+        device,
+        text.len()
+    );
+
+    Ok(Transcript {
+        text,
+        confidence: transcript.confidence.map(|confidence| SegmentConfidence {
+            avg_logprob: confidence.max(f64::MIN_POSITIVE).ln(),
+            no_speech_prob: 0.0,
+        }),
+        language: transcript.language_code.map(|code| DetectedLanguage {
+            code,
+            probability: transcript.language_confidence.unwrap_or(1.0) as f32,
+        }),
+        engine: None,
+        segments,
+        model_version: transcript
+            .speech_model
+            .map(|model| format!("assemblyai/{}", model)),
+    })
+}
+
+/// `audio_data` as a mono 32 bit float wav file
+fn wav_bytes(audio_data: &[f32], sample_rate: u32) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = WavWriter::new(&mut cursor, spec)?;
+    for &sample in audio_data {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()?;
+    Ok(cursor.into_inner())
+}
@@ -8,7 +8,7 @@ use rubato::{
 };
 use std::path::PathBuf;
 
-use crate::encode_single_audio;
+use crate::encode::{encode_single_audio, AudioCodec, AudioTags};
 
 pub fn normalize_v2(audio: &[f32]) -> Vec<f32> {
     let rms = (audio.iter().map(|&x| x * x).sum::<f32>() / audio.len() as f32).sqrt();
@@ -110,14 +110,366 @@ pub fn audio_to_mono(audio: &[f32], channels: u16) -> Vec<f32> {
     mono_samples
 }
 
+/// Loudness normalization after EBU R128: audio is brought to a target integrated loudness, and
+/// limited so its true peak stays under a ceiling
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoudnessNormalization {
+    /// Integrated loudness audio is brought to, in LUFS
+    pub target_lufs: f64,
+    /// Ceiling of the true peak after the gain, in dBTP
+    pub true_peak_dbtp: f64,
+    /// Most gain applied, a few words in a minute of silence aren't brought up to the target with
+    /// the noise floor
+    pub max_gain_db: f64,
+}
+
+impl Default for LoudnessNormalization {
+    /// The EBU R128 broadcast target
+    fn default() -> Self {
+        Self {
+            target_lufs: -23.0,
+            true_peak_dbtp: -1.0,
+            max_gain_db: 30.0,
+        }
+    }
+}
+
+/// Second order IIR filter in transposed direct form II
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The two stages of the K-weighting of ITU-R BS.1770 (a high shelf for the head, a high-pass
+/// for the rumble), derived for any sample rate like libebur128 does
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let fs = sample_rate as f64;
+
+    let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+    [shelf, high_pass]
+}
+
+/// Integrated loudness of mono audio in LUFS (ITU-R BS.1770-4): mean square of the K-weighted
+/// signal over 400ms blocks overlapping by 75%, gated at -70 LUFS and 10 LU under the ungated
+/// loudness. None for silence
+pub fn integrated_loudness(audio: &[f32], sample_rate: u32) -> Option<f64> {
+    if audio.is_empty() || sample_rate == 0 {
+        return None;
+    }
+    let mut filters = k_weighting(sample_rate);
+    let squares: Vec<f64> = audio
+        .iter()
+        .map(|&x| {
+            let y = filters
+                .iter_mut()
+                .fold(x as f64, |y, filter| filter.process(y));
+            y * y
+        })
+        .collect();
+
+    // shorter audio is a single block
+    let block = ((sample_rate as f64 * 0.4) as usize).min(squares.len());
+    let step = (block / 4).max(1);
+    let blocks: Vec<f64> = (0..=(squares.len() - block) / step)
+        .map(|i| squares[i * step..i * step + block].iter().sum::<f64>() / block as f64)
+        .collect();
+
+    let loudness = |z: f64| -0.691 + 10.0 * z.log10();
+    let gated_mean = |threshold: f64| {
+        let gated: Vec<f64> = blocks
+            .iter()
+            .copied()
+            .filter(|&z| z > 0.0 && loudness(z) > threshold)
+            .collect();
+        (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+    };
+    let relative_threshold = loudness(gated_mean(-70.0)?) - 10.0;
+    gated_mean(relative_threshold.max(-70.0)).map(loudness)
+}
+
+/// Phases of the 4x oversampling true peaks are measured with, 12 taps each
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+const TRUE_PEAK_TAPS: usize = 12;
+
+/// Highest absolute value of the signal between each sample and the next, the sample included,
+/// estimated by 4x oversampling (ITU-R BS.1770 annex 2)
+fn true_peaks(audio: &[f32]) -> Vec<f32> {
+    let half = TRUE_PEAK_TAPS as isize / 2;
+    // hann windowed sinc, one set of taps per phase between two samples
+    let phases: Vec<Vec<f32>> = (1..TRUE_PEAK_OVERSAMPLING)
+        .map(|phase| {
+            let offset = phase as f64 / TRUE_PEAK_OVERSAMPLING as f64;
+            (0..TRUE_PEAK_TAPS as isize)
+                .map(|tap| {
+                    let t = (tap - half + 1) as f64 - offset;
+                    let sinc = if t == 0.0 {
+                        1.0
+                    } else {
+                        (std::f64::consts::PI * t).sin() / (std::f64::consts::PI * t)
+                    };
+                    let window = 0.5 + 0.5 * (std::f64::consts::PI * t / (half as f64 + 1.0)).cos();
+                    (sinc * window) as f32
+                })
+                .collect()
+        })
+        .collect();
+
+    (0..audio.len())
+        .map(|i| {
+            let sample = |j: isize| {
+                usize::try_from(i as isize + j)
+                    .ok()
+                    .and_then(|j| audio.get(j))
+                    .copied()
+                    .unwrap_or(0.0)
+            };
+            phases
+                .iter()
+                .map(|taps| {
+                    taps.iter()
+                        .enumerate()
+                        .map(|(tap, &weight)| weight * sample(tap as isize - half + 1))
+                        .sum::<f32>()
+                        .abs()
+                })
+                .fold(audio[i].abs(), f32::max)
+        })
+        .collect()
+}
+
+/// Brings mono audio to the integrated loudness of `config`, reducing the gain around peaks that
+/// would go over its true peak ceiling with a limiter looking 5ms ahead. Silence is returned as is
+pub fn normalize_loudness(
+    audio: &[f32],
+    sample_rate: u32,
+    config: &LoudnessNormalization,
+) -> Vec<f32> {
+    let Some(loudness) = integrated_loudness(audio, sample_rate) else {
+        return audio.to_vec();
+    };
+    let gain_db = (config.target_lufs - loudness).min(config.max_gain_db);
+    let gain = 10f64.powf(gain_db / 20.0) as f32;
+    let ceiling = 10f64.powf(config.true_peak_dbtp / 20.0) as f32;
+
+    // gain each sample may take to keep the peaks under the ceiling
+    let needed: Vec<f32> = true_peaks(audio)
+        .into_iter()
+        .map(|peak| (ceiling / (peak * gain)).min(1.0))
+        .collect();
+    // the lowest needed over the lookahead, averaged over it so the gain ramps down before a peak
+    // instead of stepping
+    let lookahead = ((sample_rate as f64 * 0.005) as usize).max(1);
+    let ahead_min: Vec<f32> = (0..needed.len())
+        .map(|i| {
+            needed[i..(i + lookahead).min(needed.len())]
+                .iter()
+                .fold(1.0f32, |min, &g| min.min(g))
+        })
+        .collect();
+    let release = 1.0 - (-1.0 / (sample_rate as f64 * 0.05)).exp() as f32;
+
+    let mut limiter = 1.0f32;
+    let mut sum = 0.0f32;
+    audio
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| {
+            sum += ahead_min[i];
+            if i >= lookahead {
+                sum -= ahead_min[i - lookahead];
+            }
+            let attack = sum / (i + 1).min(lookahead) as f32;
+            limiter = attack.min(limiter + (1.0 - limiter) * release);
+            (x * gain * limiter).clamp(-1.0, 1.0)
+        })
+        .collect()
+}
+
+/// A processing step of an `AudioPipeline`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioStage {
+    /// Subtracts the mean of the audio, the offset cheap mics and sound cards add
+    DcRemoval,
+    /// Second order butterworth high-pass, for the rumble, hum and handling noise under speech
+    HighPass {
+        cutoff_hz: f32,
+    },
+    Loudness(LoudnessNormalization),
+}
+
+impl AudioStage {
+    fn process(&self, audio: &mut Vec<f32>, sample_rate: u32) {
+        match self {
+            AudioStage::DcRemoval => {
+                if audio.is_empty() {
+                    return;
+                }
+                let mean = audio.iter().map(|&x| x as f64).sum::<f64>() / audio.len() as f64;
+                for x in audio.iter_mut() {
+                    *x -= mean as f32;
+                }
+            }
+            AudioStage::HighPass { cutoff_hz } => {
+                // above nyquist it would filter out everything, or be unstable
+                if *cutoff_hz <= 0.0 || *cutoff_hz >= sample_rate as f32 / 2.0 {
+                    return;
+                }
+                let mut filter = high_pass(*cutoff_hz as f64, sample_rate);
+                for x in audio.iter_mut() {
+                    *x = filter.process(*x as f64) as f32;
+                }
+            }
+            AudioStage::Loudness(config) => {
+                *audio = normalize_loudness(audio, sample_rate, config);
+            }
+        }
+    }
+}
+
+/// Butterworth high-pass at `cutoff_hz` (RBJ cookbook)
+fn high_pass(cutoff_hz: f64, sample_rate: u32) -> Biquad {
+    let w0 = 2.0 * std::f64::consts::PI * cutoff_hz / sample_rate as f64;
+    let alpha = w0.sin() / (2.0 * std::f64::consts::FRAC_1_SQRT_2);
+    let cos = w0.cos();
+    let a0 = 1.0 + alpha;
+    Biquad {
+        b: [
+            (1.0 + cos) / 2.0 / a0,
+            -(1.0 + cos) / a0,
+            (1.0 + cos) / 2.0 / a0,
+        ],
+        a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+        z: [0.0; 2],
+    }
+}
+
+/// Stages run on each chunk of mono audio in the order they were added, e.g. DC removal, an 80hz
+/// high-pass, then loudness normalization. Each chunk is processed on its own
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AudioPipeline {
+    stages: Vec<AudioStage>,
+}
+
+impl AudioPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stage(mut self, stage: AudioStage) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    pub fn dc_removal(self) -> Self {
+        self.stage(AudioStage::DcRemoval)
+    }
+
+    pub fn high_pass(self, cutoff_hz: f32) -> Self {
+        self.stage(AudioStage::HighPass { cutoff_hz })
+    }
+
+    pub fn loudness(self, config: LoudnessNormalization) -> Self {
+        self.stage(AudioStage::Loudness(config))
+    }
+
+    pub fn stages(&self) -> &[AudioStage] {
+        &self.stages
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Runs the stages on `audio`, returned as is by an empty pipeline
+    pub fn process(&self, mut audio: Vec<f32>, sample_rate: u32) -> Vec<f32> {
+        for stage in &self.stages {
+            stage.process(&mut audio, sample_rate);
+        }
+        audio
+    }
+}
+
+/// Trade-off between speed and fidelity of `resample_with`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResampleQuality {
+    /// Linear interpolation without anti-aliasing, for VAD frames and other pre-checks that only
+    /// need the rough shape of the signal
+    Fast,
+    /// Short windowed sinc, little aliasing at a fraction of the cost of `High`
+    Balanced,
+    /// Long windowed sinc, for audio that is transcribed or kept
+    #[default]
+    High,
+}
+
+/// Resamples with `ResampleQuality::High`
 pub fn resample(input: &[f32], from_sample_rate: u32, to_sample_rate: u32) -> Result<Vec<f32>> {
+    resample_with(
+        input,
+        from_sample_rate,
+        to_sample_rate,
+        ResampleQuality::High,
+    )
+}
+
+pub fn resample_with(
+    input: &[f32],
+    from_sample_rate: u32,
+    to_sample_rate: u32,
+    quality: ResampleQuality,
+) -> Result<Vec<f32>> {
     debug!("Resampling audio");
-    let params = SincInterpolationParameters {
-        sinc_len: 256,
-        f_cutoff: 0.95,
-        interpolation: SincInterpolationType::Linear,
-        oversampling_factor: 256,
-        window: WindowFunction::BlackmanHarris2,
+    let params = match quality {
+        ResampleQuality::Fast => {
+            return Ok(resample_linear(input, from_sample_rate, to_sample_rate));
+        }
+        ResampleQuality::Balanced => SincInterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.91,
+            interpolation: SincInterpolationType::Linear,
+            oversampling_factor: 128,
+            window: WindowFunction::Hann2,
+        },
+        ResampleQuality::High => SincInterpolationParameters {
+            sinc_len: 256,
+            f_cutoff: 0.95,
+            interpolation: SincInterpolationType::Linear,
+            oversampling_factor: 256,
+            window: WindowFunction::BlackmanHarris2,
+        },
     };
 
     let mut resampler = SincFixedIn::<f32>::new(
@@ -135,17 +487,42 @@ pub fn resample(input: &[f32], from_sample_rate: u32, to_sample_rate: u32) -> Re
     Ok(waves_out.into_iter().next().unwrap())
 }
 
+fn resample_linear(input: &[f32], from_sample_rate: u32, to_sample_rate: u32) -> Vec<f32> {
+    if input.is_empty() || from_sample_rate == to_sample_rate || to_sample_rate == 0 {
+        return input.to_vec();
+    }
+    let step = from_sample_rate as f64 / to_sample_rate as f64;
+    let len = (input.len() as f64 / step).round() as usize;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let fraction = (position - index as f64) as f32;
+            let current = input[index.min(input.len() - 1)];
+            let next = input[(index + 1).min(input.len() - 1)];
+            current + (next - current) * fraction
+        })
+        .collect()
+}
+
 pub fn write_audio_to_file(
     audio: &[f32],
     sample_rate: u32,
     output_path: &PathBuf,
     device: &str,
     skip_encoding: bool,
+    codec: AudioCodec,
+    tags: Option<&AudioTags>,
 ) -> Result<String> {
     let new_file_name = Utc::now().format("%Y-%m-%d_%H-%M-%S").to_string();
     let sanitized_device_name = device.replace(['/', '\\'], "_");
     let file_path = PathBuf::from(output_path)
-        .join(format!("{}_{}.mp4", sanitized_device_name, new_file_name))
+        .join(format!(
+            "{}_{}.{}",
+            sanitized_device_name,
+            new_file_name,
+            codec.extension()
+        ))
         .to_str()
         .expect("Failed to create valid path")
         .to_string();
@@ -157,6 +534,8 @@ pub fn write_audio_to_file(
             sample_rate,
             1,
             &PathBuf::from(file_path),
+            codec,
+            tags,
         )?;
     }
     Ok(file_path_clone)
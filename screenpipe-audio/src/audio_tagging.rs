@@ -0,0 +1,279 @@
+use crate::models::{self, ModelFile};
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use ndarray::Array4;
+use ort::{GraphOptimizationLevel, Session};
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// YAMNet, mobilenet trained on the 521 classes of AudioSet, taking one patch of log mel
+/// spectrogram at a time
+const MODEL_REPO: &str = "qualcomm/YamNet";
+const MODEL_FILE: &str = "YamNet.onnx";
+const CLASS_MAP_URL: &str =
+    "https://raw.githubusercontent.com/tensorflow/models/master/research/audioset/yamnet/yamnet_class_map.csv";
+
+// frontend of YAMNet: 25ms windows every 10ms, 64 mel bands, patches of 0.96s every 0.48s
+const SAMPLE_RATE: f32 = 16000.0;
+const WINDOW: usize = 400;
+const HOP: usize = 160;
+const FFT: usize = 512;
+const MEL_BANDS: usize = 64;
+const MEL_MIN_HZ: f32 = 125.0;
+const MEL_MAX_HZ: f32 = 7500.0;
+const LOG_OFFSET: f32 = 0.001;
+const PATCH_FRAMES: usize = 96;
+const PATCH_HOP: usize = 48;
+
+/// Score from which a chunk is labeled with an event
+pub const LABEL_THRESHOLD: f32 = 0.2;
+
+/// What can be heard in a chunk besides or instead of speech
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioEvent {
+    Speech,
+    Music,
+    Typing,
+    Notification,
+    Silence,
+}
+
+impl AudioEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AudioEvent::Speech => "speech",
+            AudioEvent::Music => "music",
+            AudioEvent::Typing => "typing",
+            AudioEvent::Notification => "notification",
+            AudioEvent::Silence => "silence",
+        }
+    }
+
+    /// The event an AudioSet class is part of, by its display name
+    fn from_class(name: &str) -> Option<Self> {
+        match name {
+            "Speech"
+            | "Child speech, kid speaking"
+            | "Conversation"
+            | "Narration, monologue"
+            | "Male speech, man speaking"
+            | "Female speech, woman speaking" => Some(AudioEvent::Speech),
+            "Music" | "Musical instrument" | "Singing" | "Song" | "Background music"
+            | "Theme music" | "Pop music" | "Electronic music" | "Classical music" => {
+                Some(AudioEvent::Music)
+            }
+            "Typing" | "Computer keyboard" | "Typewriter" => Some(AudioEvent::Typing),
+            "Beep, bleep"
+            | "Ding"
+            | "Chime"
+            | "Ding-dong"
+            | "Ringtone"
+            | "Alarm"
+            | "Bell"
+            | "Telephone bell ringing" => Some(AudioEvent::Notification),
+            "Silence" => Some(AudioEvent::Silence),
+            _ => None,
+        }
+    }
+}
+
+/// An event heard in a chunk, with the highest mean score of its AudioSet classes over the chunk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioLabel {
+    pub event: AudioEvent,
+    pub score: f32,
+}
+
+/// What the whisper channel does with audio event classification
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AudioTagging {
+    #[default]
+    Off,
+    /// Label the results of each chunk with the events heard in it
+    Label,
+    /// Label results, and don't transcribe chunks with music and no speech at all
+    SkipMusic,
+}
+
+/// True for chunks labeled with music but not speech, whose transcription would only be lyrics
+/// or made up text
+pub fn is_music_only(labels: &[AudioLabel]) -> bool {
+    labels.iter().any(|label| label.event == AudioEvent::Music)
+        && !labels.iter().any(|label| label.event == AudioEvent::Speech)
+}
+
+/// Classifies chunks as speech, music, typing, notification sounds or silence with YAMNet
+#[derive(Clone)]
+pub struct AudioTagger {
+    session: Arc<Session>,
+    output: String,
+    /// Event of each class of the model, by index
+    classes: Vec<Option<AudioEvent>>,
+    mel_filters: Arc<Vec<Vec<f32>>>,
+}
+
+impl AudioTagger {
+    /// Loads the model, downloading it first if needed. Blocks, async callers should use
+    /// `spawn_blocking`
+    pub fn new() -> Result<Self> {
+        debug!("loading audio tagging model {}", MODEL_REPO);
+        let model_path =
+            models::get_or_download_with_events(&ModelFile::huggingface(MODEL_REPO, MODEL_FILE))?;
+        let class_map = models::get_or_download_with_events(&ModelFile::new(
+            "yamnet/yamnet_class_map.csv",
+            CLASS_MAP_URL,
+        ))?;
+        // index,mid,display_name with a header, names with commas are quoted
+        let classes = std::fs::read_to_string(class_map)?
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.splitn(3, ',').nth(2))
+            .map(|name| AudioEvent::from_class(name.trim().trim_matches('"')))
+            .collect::<Vec<_>>();
+        if classes.is_empty() {
+            return Err(anyhow!("empty audio tagging class map"));
+        }
+
+        let session = Session::builder()?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .commit_from_file(model_path)?;
+        let output = session
+            .outputs
+            .first()
+            .map(|output| output.name.clone())
+            .context("audio tagging model has no output")?;
+
+        Ok(Self {
+            session: Arc::new(session),
+            output,
+            classes,
+            mel_filters: Arc::new(mel_filters()),
+        })
+    }
+
+    /// Events heard in 16khz mono `audio`, scoring at least `LABEL_THRESHOLD`, most likely first
+    pub fn tag(&self, audio: &[f32]) -> Result<Vec<AudioLabel>> {
+        let spectrogram = self.log_mel_spectrogram(audio);
+        if spectrogram.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // mean score of each class over the patches, short chunks are padded to a single patch
+        let mut sums = vec![0.0f32; self.classes.len()];
+        let mut patches = 0;
+        let mut start = 0;
+        loop {
+            let mut patch =
+                Array4::<f32>::from_elem((1, 1, PATCH_FRAMES, MEL_BANDS), LOG_OFFSET.ln());
+            for (i, frame) in spectrogram
+                .iter()
+                .skip(start)
+                .take(PATCH_FRAMES)
+                .enumerate()
+            {
+                for (j, &value) in frame.iter().enumerate() {
+                    patch[[0, 0, i, j]] = value;
+                }
+            }
+            let outputs = self.session.run(ort::inputs![patch]?)?;
+            let scores = outputs
+                .get(self.output.as_str())
+                .context("audio tagging model output not found")?
+                .try_extract_tensor::<f32>()?;
+            for (sum, score) in sums.iter_mut().zip(scores.iter()) {
+                *sum += score;
+            }
+            patches += 1;
+
+            start += PATCH_HOP;
+            if start + PATCH_FRAMES > spectrogram.len() {
+                break;
+            }
+        }
+
+        let mut labels: Vec<AudioLabel> = Vec::new();
+        for (event, sum) in self.classes.iter().zip(&sums) {
+            let Some(event) = event else {
+                continue;
+            };
+            let score = sum / patches as f32;
+            match labels.iter_mut().find(|label| label.event == *event) {
+                Some(label) => label.score = label.score.max(score),
+                None => labels.push(AudioLabel {
+                    event: *event,
+                    score,
+                }),
+            }
+        }
+        labels.retain(|label| label.score >= LABEL_THRESHOLD);
+        labels.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(labels)
+    }
+
+    /// log(mel + 0.001) of each 10ms frame
+    fn log_mel_spectrogram(&self, audio: &[f32]) -> Vec<Vec<f32>> {
+        if audio.len() < WINDOW {
+            return Vec::new();
+        }
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FFT);
+        let mut input = fft.make_input_vec();
+        let mut spectrum = fft.make_output_vec();
+        // periodic hann window
+        let window: Vec<f32> = (0..WINDOW)
+            .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / WINDOW as f32).cos())
+            .collect();
+
+        (0..=(audio.len() - WINDOW) / HOP)
+            .map(|frame| {
+                let samples = &audio[frame * HOP..frame * HOP + WINDOW];
+                input.fill(0.0);
+                for ((x, &s), &w) in input.iter_mut().zip(samples).zip(&window) {
+                    *x = s * w;
+                }
+                let _ = fft.process(&mut input, &mut spectrum);
+                self.mel_filters
+                    .iter()
+                    .map(|filter| {
+                        let energy: f32 = filter
+                            .iter()
+                            .zip(&spectrum)
+                            .map(|(weight, bin)| weight * bin.norm())
+                            .sum();
+                        (energy + LOG_OFFSET).ln()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Triangular filters of each mel band over the magnitude spectrum, on the HTK mel scale like
+/// tensorflow's `linear_to_mel_weight_matrix` that YAMNet was trained with
+fn mel_filters() -> Vec<Vec<f32>> {
+    let mel = |hz: f32| 1127.0 * (1.0 + hz / 700.0).ln();
+    let bins = FFT / 2 + 1;
+    let (low, high) = (mel(MEL_MIN_HZ), mel(MEL_MAX_HZ));
+    let edges: Vec<f32> = (0..MEL_BANDS + 2)
+        .map(|i| low + (high - low) * i as f32 / (MEL_BANDS + 1) as f32)
+        .collect();
+
+    (0..MEL_BANDS)
+        .map(|band| {
+            let (left, center, right) = (edges[band], edges[band + 1], edges[band + 2]);
+            (0..bins)
+                .map(|bin| {
+                    // the DC bin is left out
+                    if bin == 0 {
+                        return 0.0;
+                    }
+                    let m = mel(bin as f32 * SAMPLE_RATE / FFT as f32);
+                    let rising = (m - left) / (center - left);
+                    let falling = (right - m) / (right - center);
+                    rising.min(falling).max(0.0)
+                })
+                .collect()
+        })
+        .collect()
+}
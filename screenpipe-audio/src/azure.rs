@@ -0,0 +1,238 @@
+use crate::whisper::{DetectedLanguage, SegmentConfidence, TextSegment, Transcript};
+use anyhow::Result;
+use hound::{WavSpec, WavWriter};
+use lazy_static::lazy_static;
+use log::{debug, info};
+use reqwest::multipart::{Form, Part};
+use reqwest::Client;
+use screenpipe_core::Language;
+use serde::Deserialize;
+use serde_json::json;
+use std::env;
+use std::fmt;
+use std::io::Cursor;
+
+lazy_static! {
+    /// `{region}` is replaced by the region of the speech resource
+    pub(crate) static ref AZURE_SPEECH_API_URL: String = env::var("AZURE_SPEECH_API_URL")
+        .unwrap_or_else(|_| {
+            "https://{region}.api.cognitive.microsoft.com/speechtotext/transcriptions:transcribe?api-version=2024-11-15"
+                .to_string()
+        });
+}
+
+/// Speakers told apart within a chunk
+const MAX_SPEAKERS: u32 = 4;
+
+/// Key and region of an Azure speech resource
+#[derive(Clone, PartialEq)]
+pub struct AzureSpeechCredentials {
+    pub key: String,
+    /// e.g. "eastus" or "westeurope"
+    pub region: String,
+}
+
+impl fmt::Debug for AzureSpeechCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AzureSpeechCredentials")
+            .field("key", &"<redacted>")
+            .field("region", &self.region)
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranscribeResponse {
+    #[serde(default)]
+    combined_phrases: Vec<CombinedPhrase>,
+    #[serde(default)]
+    phrases: Vec<Phrase>,
+}
+
+#[derive(Deserialize)]
+struct CombinedPhrase {
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Phrase {
+    /// 1, 2, ... when diarization is on
+    speaker: Option<u32>,
+    text: String,
+    /// e.g. "en-US"
+    locale: Option<String>,
+    /// 0..1
+    confidence: Option<f64>,
+    /// When it was said, from the start of the audio
+    offset_milliseconds: Option<u64>,
+    duration_milliseconds: Option<u64>,
+}
+
+/// Transcribes with Azure's fast transcription API, with diarization. Each speaker turn is a line
+/// of the returned text.
+///
+/// Languages are the candidate locales of its language identification, all of them when none has
+/// a known locale. `keywords` are sent as the phrase list
+pub async fn transcribe_with_azure(
+    credentials: &AzureSpeechCredentials,
+    audio_data: &[f32],
+    device: &str,
+    sample_rate: u32,
+    languages: &[Language],
+    keywords: &[String],
+) -> Result<Transcript> {
+    debug!("starting azure speech transcription");
+
+    let mut definition = json!({
+        "locales": languages.iter().filter_map(locale).collect::<Vec<_>>(),
+        "diarization": {
+            "enabled": true,
+            "maxSpeakers": MAX_SPEAKERS,
+        },
+    });
+    if !keywords.is_empty() {
+        definition["phraseList"] = json!({ "phrases": keywords });
+    }
+
+    let form = Form::new()
+        .part(
+            "audio",
+            Part::bytes(wav_bytes(audio_data, sample_rate)?)
+                .file_name("audio.wav")
+                .mime_str("audio/wav")?,
+        )
+        .text("definition", definition.to_string());
+
+    let response: TranscribeResponse = Client::new()
+        .post(AZURE_SPEECH_API_URL.replace("{region}", &credentials.region))
+        .header("Ocp-Apim-Subscription-Key", &credentials.key)
+        .multipart(form)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let text = if response.phrases.is_empty() {
+        response
+            .combined_phrases
+            .iter()
+            .map(|phrase| phrase.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        speaker_turns(&response.phrases).join("\n")
+    };
+    info!(
+        "device: {}, azure speech transcription successful. length: {} characters",
+        device,
+        text.len()
+    );
+
+    let confidences: Vec<f64> = response
+        .phrases
+        .iter()
+        .filter_map(|phrase| phrase.confidence)
+        .collect();
+    let confidence = (!confidences.is_empty())
+        .then(|| confidences.iter().sum::<f64>() / confidences.len() as f64);
+
+    Ok(Transcript {
+        text,
+        confidence: confidence.map(|confidence| SegmentConfidence {
+            avg_logprob: confidence.max(f64::MIN_POSITIVE).ln(),
+            no_speech_prob: 0.0,
+        }),
+        language: response
+            .phrases
+            .iter()
+            .find_map(|phrase| phrase.locale.as_deref())
+            .map(|locale| DetectedLanguage {
+                code: locale.split('-').next().unwrap_or(locale).to_string(),
+                probability: 1.0,
+            }),
+        engine: None,
+        segments: response
+            .phrases
+            .iter()
+            .filter_map(|phrase| {
+                let offset = phrase.offset_milliseconds? as f64 / 1000.0;
+                Some(TextSegment {
+                    start: offset,
+                    end: offset + phrase.duration_milliseconds? as f64 / 1000.0,
+                    text: phrase.text.clone(),
+                })
+            })
+            .collect(),
+        model_version: None,
+    })
+}
+
+/// Joins consecutive phrases of the same speaker
+fn speaker_turns(phrases: &[Phrase]) -> Vec<String> {
+    let mut turns: Vec<(Option<u32>, String)> = Vec::new();
+    for phrase in phrases {
+        match turns.last_mut() {
+            Some((speaker, text)) if *speaker == phrase.speaker => {
+                text.push(' ');
+                text.push_str(&phrase.text);
+            }
+            _ => turns.push((phrase.speaker, phrase.text.clone())),
+        }
+    }
+    turns.into_iter().map(|(_, text)| text).collect()
+}
+
+/// Azure wants full locales, the most common one of a language is used
+fn locale(language: &Language) -> Option<&'static str> {
+    Some(match language.as_lang_code() {
+        "en" => "en-US",
+        "zh" => "zh-CN",
+        "de" => "de-DE",
+        "es" => "es-ES",
+        "ru" => "ru-RU",
+        "ko" => "ko-KR",
+        "fr" => "fr-FR",
+        "ja" => "ja-JP",
+        "pt" => "pt-BR",
+        "tr" => "tr-TR",
+        "pl" => "pl-PL",
+        "ca" => "ca-ES",
+        "nl" => "nl-NL",
+        "ar" => "ar-SA",
+        "sv" => "sv-SE",
+        "it" => "it-IT",
+        "id" => "id-ID",
+        "hi" => "hi-IN",
+        "fi" => "fi-FI",
+        "he" => "he-IL",
+        "uk" => "uk-UA",
+        "el" => "el-GR",
+        "cs" => "cs-CZ",
+        "ro" => "ro-RO",
+        "da" => "da-DK",
+        "hu" => "hu-HU",
+        "no" => "nb-NO",
+        "th" => "th-TH",
+        _ => return None,
+    })
+}
+
+/// `audio_data` as a mono 16 bit pcm wav file
+fn wav_bytes(audio_data: &[f32], sample_rate: u32) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = WavWriter::new(&mut cursor, spec)?;
+    for &sample in audio_data {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    Ok(cursor.into_inner())
+}
@@ -8,9 +8,11 @@ use screenpipe_audio::list_audio_devices;
 use screenpipe_audio::parse_audio_device;
 use screenpipe_audio::record_and_transcribe;
 use screenpipe_audio::vad_engine::VadSensitivity;
+use screenpipe_audio::whisper::ModelOptions;
 use screenpipe_audio::AudioDevice;
 use screenpipe_audio::AudioStream;
 use screenpipe_audio::AudioTranscriptionEngine;
+use screenpipe_audio::DeviceTranscriptionOptions;
 use screenpipe_audio::VadEngineEnum;
 use screenpipe_core::Language;
 use std::path::PathBuf;
@@ -96,6 +98,8 @@ async fn main() -> Result<()> {
         VadSensitivity::Medium,
         languages,
         None,
+        ModelOptions::default(),
+        DeviceTranscriptionOptions::default(),
     )
     .await?;
 
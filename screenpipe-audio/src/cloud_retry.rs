@@ -0,0 +1,69 @@
+use reqwest::StatusCode;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How often a cloud engine is tried when it can't be reached, rate limits or has an outage,
+/// before the next engine of the chain takes over
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Tries in total, 1 doesn't retry
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for each of the next ones
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait after the failed `attempt`, counted from 0
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+/// Whether the error is worth retrying: the request didn't get through, timed out, was rate
+/// limited or hit a server error
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause.downcast_ref::<reqwest::Error>().is_some_and(|e| {
+            e.is_connect()
+                || e.is_timeout()
+                || e.status().is_some_and(|status| {
+                    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+                })
+        })
+    })
+}
+
+/// Where segments wait while no cloud engine of the chain can be reached
+#[derive(Clone, Debug, PartialEq)]
+pub struct OfflineQueueConfig {
+    pub dir: PathBuf,
+    /// Size of the queued samples above which segments are transcribed by the local model instead
+    pub max_bytes: u64,
+}
+
+/// Error of `transcribe` when every cloud engine failed transiently and an offline queue is
+/// configured, the segment should be queued rather than transcribed locally
+#[derive(Debug)]
+pub struct CloudUnreachable;
+
+impl fmt::Display for CloudUnreachable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no cloud transcription engine reachable")
+    }
+}
+
+impl std::error::Error for CloudUnreachable {}
@@ -1,17 +1,23 @@
 use crate::audio_processing::audio_to_mono;
 use crate::realtime::realtime_stt;
-use crate::AudioInput;
+use crate::speech_gate::{SpeechGate, VadGating};
+use crate::speech_stats;
+use crate::stitching::DEFAULT_CHUNK_OVERLAP;
+use crate::vad_engine::VadSettings;
+use crate::whisper::{ExecutionProvider, ModelOptions};
+use crate::{AudioInput, TranscriptionOptions};
 use anyhow::{anyhow, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::StreamError;
 use lazy_static::lazy_static;
 use log::{debug, error, info, warn};
-use screenpipe_core::Language;
+use screenpipe_core::{capture_pause, clock, Language};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex as StdMutex, MutexGuard, Weak};
+use std::time::{Duration, SystemTime};
 use std::{fmt, thread};
 use tokio::sync::{broadcast, oneshot};
 lazy_static! {
@@ -21,8 +27,15 @@ lazy_static! {
             .unwrap_or_default()
             .as_secs()
     );
+    /// Streams shared with the rest of the process by `AudioStream::shared`, by device
+    static ref LIVE_STREAMS: StdMutex<HashMap<String, Weak<AudioStream>>> =
+        StdMutex::new(HashMap::new());
 }
 
+/// Host of devices standing in for audio files read from disk rather than captured live,
+/// the transcription pipeline doesn't require a running device control for them
+pub const FILE_AUDIO_HOST: &str = "file";
+
 #[derive(Clone, Debug, PartialEq, Default)]
 pub enum AudioTranscriptionEngine {
     Deepgram,
@@ -31,6 +44,69 @@ pub enum AudioTranscriptionEngine {
     #[default]
     WhisperLargeV3Turbo,
     WhisperLargeV3,
+    /// whisper.cpp (GGML) instead of candle, faster CPU inference
+    WhisperCpp,
+    /// CTranslate2 faster-whisper model, only available with the `ctranslate2` feature
+    FasterWhisper,
+    /// Whisper on ONNX Runtime, on the given hardware
+    WhisperOnnx(ExecutionProvider),
+    /// int8 quantized tiny on candle
+    WhisperTinyQ8,
+    /// 5 bit quantized large-v3 on whisper.cpp, about a third of the memory of the full model
+    WhisperLargeV3Q5,
+    /// int8 quantized large-v3-turbo on whisper.cpp
+    WhisperLargeV3TurboQ8,
+    /// English only, about twice as fast as tiny at better accuracy
+    WhisperDistilSmallEn,
+    /// AssemblyAI's cloud transcription, with speaker labels
+    AssemblyAI,
+    /// Azure's cloud speech to text, with diarization
+    AzureSpeech,
+    /// Vosk small models, for very low-end hardware, only available with the `vosk` feature
+    Vosk,
+    /// OpenAI's hosted whisper-1
+    OpenAIWhisper,
+    /// Groq's hosted whisper-large-v3
+    GroqWhisper,
+}
+
+impl AudioTranscriptionEngine {
+    /// Whether the engine transcribes in the cloud, falling back to local whisper when it fails
+    pub fn is_cloud(&self) -> bool {
+        matches!(
+            self,
+            AudioTranscriptionEngine::Deepgram
+                | AudioTranscriptionEngine::AssemblyAI
+                | AudioTranscriptionEngine::AzureSpeech
+                | AudioTranscriptionEngine::OpenAIWhisper
+                | AudioTranscriptionEngine::GroqWhisper
+        )
+    }
+
+    /// Model a local engine transcribes with, recorded next to transcriptions to tell them apart
+    /// once models change. None for cloud engines, whose model is whatever their API reports
+    pub fn model_version(&self) -> Option<&'static str> {
+        let version = match self {
+            AudioTranscriptionEngine::Deepgram
+            | AudioTranscriptionEngine::AssemblyAI
+            | AudioTranscriptionEngine::AzureSpeech
+            | AudioTranscriptionEngine::OpenAIWhisper
+            | AudioTranscriptionEngine::GroqWhisper => return None,
+            AudioTranscriptionEngine::WhisperTiny => "openai/whisper-tiny",
+            AudioTranscriptionEngine::WhisperDistilLargeV3 => "distil-whisper/distil-large-v3",
+            AudioTranscriptionEngine::WhisperLargeV3Turbo => "openai/whisper-large-v3-turbo",
+            AudioTranscriptionEngine::WhisperLargeV3 => "openai/whisper-large-v3",
+            AudioTranscriptionEngine::WhisperCpp => "ggml-large-v3-turbo-q5_0",
+            AudioTranscriptionEngine::FasterWhisper => "deepdml/faster-whisper-large-v3-turbo-ct2",
+            AudioTranscriptionEngine::WhisperOnnx(_) => "onnx-community/whisper-large-v3-turbo",
+            AudioTranscriptionEngine::WhisperTinyQ8 => "lmz/candle-whisper tiny-q80",
+            AudioTranscriptionEngine::WhisperLargeV3Q5 => "ggml-large-v3-q5_0",
+            AudioTranscriptionEngine::WhisperLargeV3TurboQ8 => "ggml-large-v3-turbo-q8_0",
+            AudioTranscriptionEngine::WhisperDistilSmallEn => "distil-whisper/distil-small.en",
+            AudioTranscriptionEngine::Vosk => "vosk-model-small",
+        };
+        Some(version)
+    }
 }
 
 impl fmt::Display for AudioTranscriptionEngine {
@@ -41,6 +117,18 @@ impl fmt::Display for AudioTranscriptionEngine {
             AudioTranscriptionEngine::WhisperDistilLargeV3 => write!(f, "WhisperLarge"),
             AudioTranscriptionEngine::WhisperLargeV3Turbo => write!(f, "WhisperLargeV3Turbo"),
             AudioTranscriptionEngine::WhisperLargeV3 => write!(f, "WhisperLargeV3"),
+            AudioTranscriptionEngine::WhisperCpp => write!(f, "WhisperCpp"),
+            AudioTranscriptionEngine::FasterWhisper => write!(f, "FasterWhisper"),
+            AudioTranscriptionEngine::WhisperOnnx(_) => write!(f, "WhisperOnnx"),
+            AudioTranscriptionEngine::WhisperTinyQ8 => write!(f, "WhisperTinyQ8"),
+            AudioTranscriptionEngine::WhisperLargeV3Q5 => write!(f, "WhisperLargeV3Q5"),
+            AudioTranscriptionEngine::WhisperLargeV3TurboQ8 => write!(f, "WhisperLargeV3TurboQ8"),
+            AudioTranscriptionEngine::WhisperDistilSmallEn => write!(f, "WhisperDistilSmallEn"),
+            AudioTranscriptionEngine::AssemblyAI => write!(f, "AssemblyAI"),
+            AudioTranscriptionEngine::AzureSpeech => write!(f, "AzureSpeech"),
+            AudioTranscriptionEngine::Vosk => write!(f, "Vosk"),
+            AudioTranscriptionEngine::OpenAIWhisper => write!(f, "OpenAIWhisper"),
+            AudioTranscriptionEngine::GroqWhisper => write!(f, "GroqWhisper"),
         }
     }
 }
@@ -57,22 +145,43 @@ pub enum DeviceType {
     Output,
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Serialize, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
 pub struct AudioDevice {
     pub name: String,
     pub device_type: DeviceType,
+    /// cpal host (backend) to open the device with, e.g. "ASIO" or "ScreenCaptureKit".
+    /// None means the platform default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
 }
 
 impl AudioDevice {
     pub fn new(name: String, device_type: DeviceType) -> Self {
-        AudioDevice { name, device_type }
+        AudioDevice {
+            name,
+            device_type,
+            host: None,
+        }
     }
 
+    pub fn with_host(mut self, host: Option<String>) -> Self {
+        self.host = host;
+        self
+    }
+
+    /// Parses "name (input)" or "name (output)", optionally followed by a host in brackets,
+    /// e.g. "Focusrite USB (input) [ASIO]"
     pub fn from_name(name: &str) -> Result<Self> {
         if name.trim().is_empty() {
             return Err(anyhow!("Device name cannot be empty"));
         }
 
+        let name = name.trim();
+        let (name, host) = match name.strip_suffix(']').and_then(|n| n.rsplit_once(" [")) {
+            Some((rest, host)) if !host.trim().is_empty() => (rest, Some(host.trim().to_string())),
+            _ => (name, None),
+        };
+
         let (name, device_type) = if name.to_lowercase().ends_with("(input)") {
             (
                 name.trim_end_matches("(input)").trim().to_string(),
@@ -89,7 +198,7 @@ impl AudioDevice {
             ));
         };
 
-        Ok(AudioDevice::new(name, device_type))
+        Ok(AudioDevice::new(name, device_type).with_host(host))
     }
 }
 
@@ -103,7 +212,54 @@ impl fmt::Display for AudioDevice {
                 DeviceType::Input => "input",
                 DeviceType::Output => "output",
             }
-        )
+        )?;
+        if let Some(host) = &self.host {
+            write!(f, " [{}]", host)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioHost {
+    pub id: String,
+    pub is_default: bool,
+}
+
+/// Lists the cpal hosts (audio backends) compiled in and available on this system,
+/// e.g. CoreAudio + ScreenCaptureKit on macos or WASAPI + ASIO on windows
+pub fn list_audio_hosts() -> Vec<AudioHost> {
+    let default_id = cpal::default_host().id();
+    cpal::available_hosts()
+        .into_iter()
+        .map(|id| AudioHost {
+            id: id.name().to_string(),
+            is_default: id == default_id,
+        })
+        .collect()
+}
+
+fn host_id_from_name(name: &str) -> Result<cpal::HostId> {
+    cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            anyhow!(
+                "audio host {} not available, available hosts: {:?}",
+                name,
+                cpal::available_hosts()
+                    .iter()
+                    .map(|id| id.name())
+                    .collect::<Vec<_>>()
+            )
+        })
+}
+
+/// Returns the cpal host by name, or the default host when none is given
+pub fn get_audio_host(name: Option<&str>) -> Result<cpal::Host> {
+    match name {
+        Some(name) => Ok(cpal::host_from_id(host_id_from_name(name)?)?),
+        None => Ok(cpal::default_host()),
     }
 }
 
@@ -114,12 +270,12 @@ pub fn parse_audio_device(name: &str) -> Result<AudioDevice> {
 pub async fn get_device_and_config(
     audio_device: &AudioDevice,
 ) -> Result<(cpal::Device, cpal::SupportedStreamConfig)> {
-    let host = cpal::default_host();
+    let host = get_audio_host(audio_device.host.as_deref())?;
 
     let is_output_device = audio_device.device_type == DeviceType::Output;
-    let is_display = audio_device.to_string().contains("Display");
+    let is_display = audio_device.name.contains("Display");
 
-    let cpal_audio_device = if audio_device.to_string() == "default" {
+    let cpal_audio_device = if audio_device.name == "default" {
         match audio_device.device_type {
             DeviceType::Input => host.default_input_device(),
             DeviceType::Output => host.default_output_device(),
@@ -130,9 +286,10 @@ pub async fn get_device_and_config(
             DeviceType::Output => host.output_devices()?,
         };
 
+        // only fall back to screen capture kit when the user did not pick a host explicitly
         #[cfg(target_os = "macos")]
         {
-            if is_output_device {
+            if is_output_device && audio_device.host.is_none() {
                 if let Ok(screen_capture_host) = cpal::host_from_id(cpal::HostId::ScreenCaptureKit)
                 {
                     devices = screen_capture_host.input_devices()?;
@@ -142,13 +299,7 @@ pub async fn get_device_and_config(
 
         devices.find(|x| {
             x.name()
-                .map(|y| {
-                    y == audio_device
-                        .to_string()
-                        .replace(" (input)", "")
-                        .replace(" (output)", "")
-                        .trim()
-                })
+                .map(|y| y == audio_device.name.trim())
                 .unwrap_or(false)
         })
     }
@@ -168,16 +319,78 @@ pub async fn record_and_transcribe(
     duration: Duration,
     whisper_sender: crossbeam::channel::Sender<AudioInput>,
     is_running: Arc<AtomicBool>,
+) -> Result<()> {
+    record_and_transcribe_with_options(
+        audio_stream,
+        duration,
+        whisper_sender,
+        is_running,
+        RecordingOptions::default(),
+    )
+    .await
+}
+
+/// How recorded audio is cut into the chunks sent to the whisper channel
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingOptions {
+    /// Only send the speech the VAD hears, each stretch as a chunk of at most the chunk duration.
+    /// None sends every chunk duration of audio, with `overlap`
+    pub vad_gating: Option<VadGating>,
+    /// Audio at the end of each chunk sent again at the start of the next one, so the words cut
+    /// at the seam are heard whole in one of them
+    pub overlap: Duration,
+}
+
+impl Default for RecordingOptions {
+    fn default() -> Self {
+        Self {
+            vad_gating: None,
+            overlap: DEFAULT_CHUNK_OVERLAP,
+        }
+    }
+}
+
+impl RecordingOptions {
+    /// The same options for a device with its own VAD
+    pub fn with_vad(mut self, vad: &VadSettings) -> Self {
+        if let Some(gating) = self.vad_gating.as_mut() {
+            gating.vad = vad.clone();
+        }
+        self
+    }
+}
+
+pub async fn record_and_transcribe_with_options(
+    audio_stream: Arc<AudioStream>,
+    duration: Duration,
+    whisper_sender: crossbeam::channel::Sender<AudioInput>,
+    is_running: Arc<AtomicBool>,
+    options: RecordingOptions,
 ) -> Result<()> {
     while is_running.load(Ordering::Relaxed) {
-        match run_record_and_transcribe(
-            audio_stream.clone(),
-            duration,
-            whisper_sender.clone(),
-            is_running.clone(),
-        )
-        .await
-        {
+        let recording = match &options.vad_gating {
+            Some(gating) => {
+                run_vad_gated_recording(
+                    audio_stream.clone(),
+                    duration,
+                    whisper_sender.clone(),
+                    is_running.clone(),
+                    gating,
+                )
+                .await
+            }
+            None => {
+                run_record_and_transcribe(
+                    audio_stream.clone(),
+                    duration,
+                    whisper_sender.clone(),
+                    is_running.clone(),
+                    options.overlap,
+                )
+                .await
+            }
+        };
+        match recording {
             Ok(_) => {
                 // Normal shutdown
                 break;
@@ -197,11 +410,16 @@ pub async fn record_and_transcribe(
     Ok(())
 }
 
+/// Live captions for a stream: deepgram's websocket when it is the transcription engine,
+/// sliding window whisper otherwise
 pub async fn start_realtime_recording(
     audio_stream: Arc<AudioStream>,
     languages: Vec<Language>,
     is_running: Arc<AtomicBool>,
     deepgram_api_key: Option<String>,
+    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
+    model_options: ModelOptions,
+    transcription_options: TranscriptionOptions,
 ) -> Result<()> {
     while is_running.load(Ordering::Relaxed) {
         match realtime_stt(
@@ -209,6 +427,9 @@ pub async fn start_realtime_recording(
             languages.clone(),
             is_running.clone(),
             deepgram_api_key.clone(),
+            audio_transcription_engine.clone(),
+            model_options.clone(),
+            transcription_options.clone(),
         )
         .await
         {
@@ -236,8 +457,9 @@ async fn run_record_and_transcribe(
     duration: Duration,
     whisper_sender: crossbeam::channel::Sender<AudioInput>,
     is_running: Arc<AtomicBool>,
+    overlap: Duration,
 ) -> Result<()> {
-    let mut receiver = audio_stream.subscribe().await;
+    let mut receiver = audio_stream.subscribe();
 
     info!(
         "starting continuous recording for {} ({}s segments)",
@@ -245,10 +467,10 @@ async fn run_record_and_transcribe(
         duration.as_secs()
     );
 
-    const OVERLAP_SECONDS: usize = 2;
     let mut collected_audio = Vec::new();
+    let mut capture_timing: Option<CaptureTiming> = None;
     let sample_rate = audio_stream.device_config.sample_rate().0 as usize;
-    let overlap_samples = OVERLAP_SECONDS * sample_rate;
+    let overlap_samples = (overlap.as_secs_f64() * sample_rate as f64) as usize;
 
     while is_running.load(Ordering::Relaxed)
         && !audio_stream.is_disconnected.load(Ordering::Relaxed)
@@ -257,14 +479,12 @@ async fn run_record_and_transcribe(
 
         while start_time.elapsed() < duration && is_running.load(Ordering::Relaxed) {
             match tokio::time::timeout(Duration::from_millis(100), receiver.recv()).await {
-                Ok(Ok(chunk)) => {
-                    collected_audio.extend(chunk);
-                    LAST_AUDIO_CAPTURE.store(
-                        std::time::SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap()
-                            .as_secs(),
-                        Ordering::Relaxed,
+                Ok(Ok(block)) => {
+                    collect_block(
+                        &mut collected_audio,
+                        &mut capture_timing,
+                        block,
+                        sample_rate as u32,
                     );
                 }
                 Ok(Err(e)) => {
@@ -276,28 +496,19 @@ async fn run_record_and_transcribe(
         }
 
         if !collected_audio.is_empty() {
-            debug!("sending audio segment to audio model");
-            match whisper_sender.try_send(AudioInput {
-                data: Arc::new(collected_audio.clone()),
-                device: audio_stream.device.clone(),
-                sample_rate: audio_stream.device_config.sample_rate().0,
-                channels: audio_stream.device_config.channels(),
-            }) {
-                Ok(_) => {
-                    debug!("sent audio segment to audio model");
-                    if collected_audio.len() > overlap_samples {
-                        collected_audio =
-                            collected_audio.split_off(collected_audio.len() - overlap_samples);
-                    }
-                }
-                Err(e) => {
-                    if e.is_disconnected() {
-                        error!("whisper channel disconnected, restarting recording process");
-                        return Err(anyhow!("Whisper channel disconnected"));
-                    } else if e.is_full() {
-                        warn!("whisper channel full, dropping audio segment");
-                        tokio::time::sleep(Duration::from_millis(100)).await;
-                    }
+            let sent = send_to_whisper(
+                &whisper_sender,
+                &audio_stream,
+                collected_audio.clone(),
+                capture_timing.clone(),
+            )?;
+            if !sent {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            } else if collected_audio.len() > overlap_samples {
+                let trimmed = collected_audio.len() - overlap_samples;
+                collected_audio = collected_audio.split_off(trimmed);
+                if let Some(timing) = capture_timing.as_mut() {
+                    timing.trim_front(trimmed);
                 }
             }
         }
@@ -307,6 +518,153 @@ async fn run_record_and_transcribe(
     Ok(())
 }
 
+/// Sends only the stretches of speech the VAD hears, as they end. Silence is dropped as it is
+/// classified, keeping the padding that may precede speech
+async fn run_vad_gated_recording(
+    audio_stream: Arc<AudioStream>,
+    duration: Duration,
+    whisper_sender: crossbeam::channel::Sender<AudioInput>,
+    is_running: Arc<AtomicBool>,
+    gating: &VadGating,
+) -> Result<()> {
+    let sample_rate = audio_stream.device_config.sample_rate().0;
+    let mut gate = SpeechGate::create(gating, sample_rate, duration).await?;
+    let mut receiver = audio_stream.subscribe();
+
+    info!(
+        "starting vad gated recording for {} (up to {}s segments)",
+        audio_stream.device.to_string(),
+        duration.as_secs()
+    );
+
+    let device = audio_stream.device.to_string();
+    let mut collected_audio = Vec::new();
+    let mut capture_timing: Option<CaptureTiming> = None;
+    let send_speech = |collected_audio: &[f32],
+                       capture_timing: &Option<CaptureTiming>,
+                       speech: std::ops::Range<usize>| {
+        debug!(
+            "sending {:.1}s of speech to audio model",
+            speech.len() as f64 / sample_rate as f64
+        );
+        let mut timing = capture_timing.clone();
+        if let Some(timing) = timing.as_mut() {
+            timing.trim_front(speech.start);
+        }
+        send_to_whisper(
+            &whisper_sender,
+            &audio_stream,
+            collected_audio[speech].to_vec(),
+            timing,
+        )
+    };
+
+    while is_running.load(Ordering::Relaxed)
+        && !audio_stream.is_disconnected.load(Ordering::Relaxed)
+    {
+        match tokio::time::timeout(Duration::from_millis(100), receiver.recv()).await {
+            Ok(Ok(block)) => {
+                collect_block(
+                    &mut collected_audio,
+                    &mut capture_timing,
+                    block,
+                    sample_rate,
+                );
+            }
+            Ok(Err(e)) => {
+                error!("error receiving audio data: {}", e);
+                return Err(anyhow!("Audio stream error: {}", e));
+            }
+            Err(_) => continue,
+        }
+
+        while let Some(speech) = gate.next_speech(&collected_audio) {
+            send_speech(&collected_audio, &capture_timing, speech)?;
+        }
+        // silence never reaches the whisper channel, it's counted here
+        for (start, is_speech) in gate.take_decisions() {
+            if let Some(at) = capture_timing
+                .as_ref()
+                .and_then(|timing| timing.time_at(start as f64 / sample_rate as f64))
+            {
+                speech_stats::record_frame(&device, at.into(), gate.frame(), is_speech);
+            }
+        }
+        let unneeded = gate.unneeded();
+        if unneeded > 0 {
+            collected_audio.drain(..unneeded);
+            if let Some(timing) = capture_timing.as_mut() {
+                timing.trim_front(unneeded);
+            }
+            gate.trim_front(unneeded);
+        }
+    }
+
+    if let Some(speech) = gate.finish(collected_audio.len()) {
+        send_speech(&collected_audio, &capture_timing, speech)?;
+    }
+
+    info!("stopped recording for {}", audio_stream.device.to_string());
+    Ok(())
+}
+
+/// Appends a block received from the stream to the audio being collected
+fn collect_block(
+    collected_audio: &mut Vec<f32>,
+    capture_timing: &mut Option<CaptureTiming>,
+    block: AudioBlock,
+    sample_rate: u32,
+) {
+    capture_timing
+        .get_or_insert_with(|| {
+            CaptureTiming::new(block.captured_at - block.stream_offset, sample_rate)
+        })
+        .push_block(collected_audio.len(), block.stream_offset);
+    collected_audio.extend(block.data);
+    LAST_AUDIO_CAPTURE.store(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+        Ordering::Relaxed,
+    );
+}
+
+/// Sends a chunk to the whisper channel, false when the channel is full and it was dropped
+fn send_to_whisper(
+    whisper_sender: &crossbeam::channel::Sender<AudioInput>,
+    audio_stream: &AudioStream,
+    data: Vec<f32>,
+    capture_timing: Option<CaptureTiming>,
+) -> Result<bool> {
+    if capture_pause::is_paused() {
+        debug!("capture paused, dropping audio segment");
+        return Ok(true);
+    }
+    debug!("sending audio segment to audio model");
+    match whisper_sender.try_send(AudioInput {
+        data: Arc::new(data),
+        device: audio_stream.device.clone(),
+        sample_rate: audio_stream.device_config.sample_rate().0,
+        // the stream is downmixed to mono as it's captured
+        channels: 1,
+        capture_timing,
+    }) {
+        Ok(_) => {
+            debug!("sent audio segment to audio model");
+            Ok(true)
+        }
+        Err(e) if e.is_disconnected() => {
+            error!("whisper channel disconnected, restarting recording process");
+            Err(anyhow!("Whisper channel disconnected"))
+        }
+        Err(_) => {
+            warn!("whisper channel full, dropping audio segment");
+            Ok(false)
+        }
+    }
+}
+
 pub async fn list_audio_devices() -> Result<Vec<AudioDevice>> {
     let host = cpal::default_host();
     let mut devices = Vec::new();
@@ -370,6 +728,29 @@ pub async fn list_audio_devices() -> Result<Vec<AudioDevice>> {
     Ok(devices)
 }
 
+/// Lists devices of a specific cpal host, tagging each with the host so it is opened through it
+pub async fn list_host_audio_devices(host_name: &str) -> Result<Vec<AudioDevice>> {
+    let host = get_audio_host(Some(host_name))?;
+    let host_id = host.id().name().to_string();
+    let mut devices = Vec::new();
+
+    for device in host.input_devices()? {
+        if let Ok(name) = device.name() {
+            devices
+                .push(AudioDevice::new(name, DeviceType::Input).with_host(Some(host_id.clone())));
+        }
+    }
+
+    for device in host.output_devices()? {
+        if let Ok(name) = device.name() {
+            devices
+                .push(AudioDevice::new(name, DeviceType::Output).with_host(Some(host_id.clone())));
+        }
+    }
+
+    Ok(devices)
+}
+
 pub fn default_input_device() -> Result<AudioDevice> {
     let host = cpal::default_host();
     let device = host
@@ -430,11 +811,178 @@ pub fn trigger_audio_permission() -> Result<()> {
     Ok(())
 }
 
+/// A block of mono samples as delivered by one cpal input callback
+#[derive(Clone, Debug)]
+pub struct AudioBlock {
+    pub data: Vec<f32>,
+    /// Wall clock time of the first sample in the block
+    pub captured_at: SystemTime,
+    /// Offset of the first sample from the first block of the stream, from the device clock (monotonic)
+    pub stream_offset: Duration,
+}
+
+impl AudioBlock {
+    /// Block stamped with the current time, for audio that does not come from a cpal stream
+    pub fn new(data: Vec<f32>) -> Self {
+        Self {
+            data,
+            captured_at: clock::now(),
+            stream_offset: Duration::ZERO,
+        }
+    }
+}
+
+/// Maps positions in an `AudioInput` back to the time they were captured
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CaptureTiming {
+    /// Wall clock time at stream offset zero
+    pub stream_started_at: SystemTime,
+    /// Sample rate the block indices are expressed in
+    pub sample_rate: u32,
+    /// (index of the block's first sample in the input data, block stream offset)
+    pub blocks: Vec<(usize, Duration)>,
+}
+
+impl CaptureTiming {
+    pub fn new(stream_started_at: SystemTime, sample_rate: u32) -> Self {
+        Self {
+            stream_started_at,
+            sample_rate,
+            blocks: Vec::new(),
+        }
+    }
+
+    pub fn push_block(&mut self, sample_index: usize, stream_offset: Duration) {
+        self.blocks.push((sample_index, stream_offset));
+    }
+
+    /// Wall clock time of the sample `secs` seconds into the input data
+    pub fn time_at(&self, secs: f64) -> Option<SystemTime> {
+        let sample_index = (secs.max(0.0) * self.sample_rate as f64) as usize;
+        let (block_index, offset) = self
+            .blocks
+            .iter()
+            .rev()
+            .find(|(i, _)| *i <= sample_index)
+            .or_else(|| self.blocks.first())?;
+        let delta = (sample_index as f64 - *block_index as f64) / self.sample_rate as f64;
+        let at = self.stream_started_at + *offset;
+        Some(if delta >= 0.0 {
+            at + Duration::from_secs_f64(delta)
+        } else {
+            at - Duration::from_secs_f64(-delta)
+        })
+    }
+
+    /// Wall clock time of the first sample in the input data
+    pub fn started_at(&self) -> Option<SystemTime> {
+        self.time_at(0.0)
+    }
+
+    /// Drops the first `n` samples, keeping the timing of the remaining ones
+    pub fn trim_front(&mut self, n: usize) {
+        let first_kept = self.blocks.iter().rposition(|(i, _)| *i <= n);
+        if let Some(pos) = first_kept {
+            self.blocks.drain(..pos);
+            let (index, offset) = self.blocks[0];
+            self.blocks[0] = (
+                n,
+                offset + Duration::from_secs_f64((n - index) as f64 / self.sample_rate as f64),
+            );
+        }
+        for block in self.blocks.iter_mut() {
+            block.0 -= n;
+        }
+    }
+}
+
+/// Stamps cpal callbacks with (wall clock, offset since the first callback) using the device capture clock
+fn block_stamper() -> impl FnMut(&cpal::InputCallbackInfo) -> (SystemTime, Duration) {
+    let mut first: Option<(cpal::StreamInstant, SystemTime)> = None;
+    move |info: &cpal::InputCallbackInfo| {
+        let timestamp = info.timestamp();
+        let (first_capture, started_at) = *first.get_or_insert_with(|| {
+            // the callback runs a bit after the samples were captured
+            let latency = timestamp
+                .callback
+                .duration_since(&timestamp.capture)
+                .unwrap_or_default();
+            (timestamp.capture, clock::now() - latency)
+        });
+        let offset = timestamp
+            .capture
+            .duration_since(&first_capture)
+            .unwrap_or_default();
+        (started_at + offset, offset)
+    }
+}
+
+/// How the cpal input stream is built
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StreamOptions {
+    /// Requested buffer size in frames. Clamped to what the device supports, ignored when the
+    /// device does not report a range. None keeps the host default (often 100ms+ of audio)
+    pub buffer_frames: Option<u32>,
+}
+
+impl StreamOptions {
+    /// ~10ms buffers, for realtime transcription consumers. cpal has no WASAPI exclusive mode,
+    /// so on windows this only shrinks the shared-mode buffer; on macos it sets the CoreAudio
+    /// device buffer frame size
+    pub fn low_latency() -> Self {
+        Self {
+            buffer_frames: Some(LOW_LATENCY_BUFFER_FRAMES),
+        }
+    }
+
+    fn stream_config(&self, config: &cpal::SupportedStreamConfig) -> cpal::StreamConfig {
+        let mut stream_config: cpal::StreamConfig = config.clone().into();
+        if let Some(frames) = self.buffer_frames {
+            match config.buffer_size() {
+                cpal::SupportedBufferSize::Range { min, max } => {
+                    stream_config.buffer_size = cpal::BufferSize::Fixed(frames.clamp(*min, *max));
+                }
+                cpal::SupportedBufferSize::Unknown => {
+                    warn!("device does not report supported buffer sizes, using default buffer");
+                }
+            }
+        }
+        stream_config
+    }
+}
+
+/// The stream capturing `device` shared with `AudioStream::shared`, None when it isn't being
+/// recorded. The device is captured as long as the stream is held, subscribe and let it go
+pub fn live_audio_stream(device: &AudioDevice) -> Option<Arc<AudioStream>> {
+    live_streams()
+        .get(&device.to_string())
+        .and_then(Weak::upgrade)
+        .filter(|stream| !stream.is_disconnected())
+}
+
+/// Every stream shared with `AudioStream::shared` that is still capturing
+pub fn live_audio_streams() -> Vec<Arc<AudioStream>> {
+    let mut streams = live_streams();
+    streams.retain(|_, stream| stream.strong_count() > 0);
+    streams
+        .values()
+        .filter_map(Weak::upgrade)
+        .filter(|stream| !stream.is_disconnected())
+        .collect()
+}
+
+fn live_streams() -> MutexGuard<'static, HashMap<String, Weak<AudioStream>>> {
+    LIVE_STREAMS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 480 frames is 10ms at 48kHz
+const LOW_LATENCY_BUFFER_FRAMES: u32 = 480;
+
 #[derive(Clone)]
 pub struct AudioStream {
     pub device: Arc<AudioDevice>,
     pub device_config: cpal::SupportedStreamConfig,
-    transmitter: Arc<tokio::sync::broadcast::Sender<Vec<f32>>>,
+    transmitter: Arc<tokio::sync::broadcast::Sender<AudioBlock>>,
     stream_control: mpsc::Sender<StreamControl>,
     stream_thread: Option<Arc<tokio::sync::Mutex<Option<thread::JoinHandle<()>>>>>,
     is_disconnected: Arc<AtomicBool>,
@@ -449,10 +997,23 @@ impl AudioStream {
         device: Arc<AudioDevice>,
         is_running: Arc<AtomicBool>,
     ) -> Result<Self> {
-        let (tx, _) = broadcast::channel::<Vec<f32>>(1000);
+        Self::from_device_with_options(device, is_running, StreamOptions::default()).await
+    }
+
+    pub async fn from_device_with_options(
+        device: Arc<AudioDevice>,
+        is_running: Arc<AtomicBool>,
+        options: StreamOptions,
+    ) -> Result<Self> {
+        let (tx, _) = broadcast::channel::<AudioBlock>(1000);
         let tx_clone = tx.clone();
         let (cpal_audio_device, config) = get_device_and_config(&device).await?;
         let channels = config.channels();
+        let stream_config = options.stream_config(&config);
+        debug!(
+            "building input stream for {} with buffer size {:?}",
+            device, stream_config.buffer_size
+        );
 
         let is_running_weak_2 = Arc::downgrade(&is_running);
         let is_disconnected = Arc::new(AtomicBool::new(false));
@@ -491,13 +1052,18 @@ impl AudioStream {
                 }
             };
 
+            let mut stamp = block_stamper();
             let stream = match config.sample_format() {
                 cpal::SampleFormat::F32 => cpal_audio_device
                     .build_input_stream(
-                        &config.into(),
-                        move |data: &[f32], _: &_| {
-                            let mono = audio_to_mono(data, channels);
-                            let _ = tx.send(mono);
+                        &stream_config,
+                        move |data: &[f32], info: &cpal::InputCallbackInfo| {
+                            let (captured_at, stream_offset) = stamp(info);
+                            let _ = tx.send(AudioBlock {
+                                data: audio_to_mono(data, channels),
+                                captured_at,
+                                stream_offset,
+                            });
                         },
                         error_callback,
                         None,
@@ -505,10 +1071,14 @@ impl AudioStream {
                     .expect("Failed to build input stream"),
                 cpal::SampleFormat::I16 => cpal_audio_device
                     .build_input_stream(
-                        &config.into(),
-                        move |data: &[i16], _: &_| {
-                            let mono = audio_to_mono(bytemuck::cast_slice(data), channels);
-                            let _ = tx.send(mono);
+                        &stream_config,
+                        move |data: &[i16], info: &cpal::InputCallbackInfo| {
+                            let (captured_at, stream_offset) = stamp(info);
+                            let _ = tx.send(AudioBlock {
+                                data: audio_to_mono(bytemuck::cast_slice(data), channels),
+                                captured_at,
+                                stream_offset,
+                            });
                         },
                         error_callback,
                         None,
@@ -516,10 +1086,14 @@ impl AudioStream {
                     .expect("Failed to build input stream"),
                 cpal::SampleFormat::I32 => cpal_audio_device
                     .build_input_stream(
-                        &config.into(),
-                        move |data: &[i32], _: &_| {
-                            let mono = audio_to_mono(bytemuck::cast_slice(data), channels);
-                            let _ = tx.send(mono);
+                        &stream_config,
+                        move |data: &[i32], info: &cpal::InputCallbackInfo| {
+                            let (captured_at, stream_offset) = stamp(info);
+                            let _ = tx.send(AudioBlock {
+                                data: audio_to_mono(bytemuck::cast_slice(data), channels),
+                                captured_at,
+                                stream_offset,
+                            });
                         },
                         error_callback,
                         None,
@@ -527,10 +1101,14 @@ impl AudioStream {
                     .expect("Failed to build input stream"),
                 cpal::SampleFormat::I8 => cpal_audio_device
                     .build_input_stream(
-                        &config.into(),
-                        move |data: &[i8], _: &_| {
-                            let mono = audio_to_mono(bytemuck::cast_slice(data), channels);
-                            let _ = tx.send(mono);
+                        &stream_config,
+                        move |data: &[i8], info: &cpal::InputCallbackInfo| {
+                            let (captured_at, stream_offset) = stamp(info);
+                            let _ = tx.send(AudioBlock {
+                                data: audio_to_mono(bytemuck::cast_slice(data), channels),
+                                captured_at,
+                                stream_offset,
+                            });
                         },
                         error_callback,
                         None,
@@ -564,10 +1142,32 @@ impl AudioStream {
         })
     }
 
-    pub async fn subscribe(&self) -> broadcast::Receiver<Vec<f32>> {
+    /// Receives the blocks of mono samples captured from now on, at `sample_rate`. A receiver
+    /// more than 1000 blocks behind misses the oldest ones (`RecvError::Lagged`), and gets
+    /// `RecvError::Closed` once the stream is stopped
+    pub fn subscribe(&self) -> broadcast::Receiver<AudioBlock> {
         self.transmitter.subscribe()
     }
 
+    /// Sample rate of the blocks the stream sends
+    pub fn sample_rate(&self) -> u32 {
+        self.device_config.sample_rate().0
+    }
+
+    /// Whether the device went away, the stream won't send more blocks
+    pub fn is_disconnected(&self) -> bool {
+        self.is_disconnected.load(Ordering::Relaxed)
+    }
+
+    /// Shares the stream with the rest of the process: vision sync, pipes or level meters find
+    /// it with `live_audio_stream` and subscribe to it instead of opening the device a second
+    /// time. It is forgotten once dropped
+    pub fn shared(self) -> Arc<Self> {
+        let stream = Arc::new(self);
+        live_streams().insert(stream.device.to_string(), Arc::downgrade(&stream));
+        stream
+    }
+
     pub async fn stop(mut self) -> Result<()> {
         self.is_disconnected.store(true, Ordering::Relaxed);
         let (tx, rx) = oneshot::channel();
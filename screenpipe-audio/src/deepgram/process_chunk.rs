@@ -2,11 +2,12 @@ use anyhow::Result;
 use hound::{WavSpec, WavWriter};
 use log::{debug, error, info};
 use reqwest::Client;
-use screenpipe_core::Language;
+use screenpipe_core::{meeting, Language};
 use serde_json::Value;
 use std::io::Cursor;
 
 use crate::deepgram::{CUSTOM_DEEPGRAM_API_TOKEN, DEEPGRAM_API_URL};
+use crate::whisper::{TextSegment, Transcript};
 
 pub async fn transcribe_with_deepgram(
     api_key: &str,
@@ -14,7 +15,8 @@ pub async fn transcribe_with_deepgram(
     device: &str,
     sample_rate: u32,
     languages: Vec<Language>,
-) -> Result<String> {
+    keywords: &[String],
+) -> Result<Transcript> {
     debug!("starting deepgram transcription");
     let client = Client::new();
 
@@ -44,6 +46,11 @@ pub async fn transcribe_with_deepgram(
     let wav_data = cursor.into_inner();
 
     let mut query_params = String::from("model=nova-2&smart_format=true&sample_rate=16000");
+    // who says what matters in meetings, each speaker turn is a line
+    let diarize = meeting::in_meeting();
+    if diarize {
+        query_params.push_str("&diarize=true");
+    }
 
     if !languages.is_empty() {
         query_params = [
@@ -71,6 +78,13 @@ pub async fn transcribe_with_deepgram(
 
     let response = client
         .post(format!("{}?{}", *DEEPGRAM_API_URL, query_params))
+        // vocabulary to boost, encoded by reqwest as they're free text
+        .query(
+            &keywords
+                .iter()
+                .map(|keyword| ("keywords", keyword.as_str()))
+                .collect::<Vec<_>>(),
+        )
         .header("Content-Type", "audio/wav")
         // Use Bearer format when using custom endpoint/proxy
         .header(
@@ -87,6 +101,15 @@ pub async fn transcribe_with_deepgram(
     match response.await {
         Ok(resp) => {
             debug!("received response from deepgram api");
+            // rate limits and outages are worth retrying, keep them as reqwest errors
+            if let Err(e) = resp.error_for_status_ref() {
+                if e.status()
+                    .is_some_and(|status| status.as_u16() == 429 || status.is_server_error())
+                {
+                    error!("deepgram api unavailable: {:?}", e);
+                    return Err(anyhow::Error::new(e).context("Deepgram API unavailable"));
+                }
+            }
             match resp.json::<Value>().await {
                 Ok(result) => {
                     debug!("successfully parsed json response");
@@ -97,10 +120,15 @@ pub async fn transcribe_with_deepgram(
                         );
                         return Err(anyhow::anyhow!("Deepgram API error: {:?}", result));
                     }
-                    let transcription = result["results"]["channels"][0]["alternatives"][0]
-                        ["transcript"]
-                        .as_str()
-                        .unwrap_or("");
+                    let alternative = &result["results"]["channels"][0]["alternatives"][0];
+                    let turns = if diarize {
+                        speaker_turns(&alternative["words"])
+                    } else {
+                        None
+                    };
+                    let transcription = turns
+                        .as_deref()
+                        .unwrap_or(alternative["transcript"].as_str().unwrap_or(""));
 
                     if transcription.is_empty() {
                         info!(
@@ -115,7 +143,14 @@ pub async fn transcribe_with_deepgram(
                         );
                     }
 
-                    Ok(transcription.to_string())
+                    Ok(Transcript {
+                        text: transcription.to_string(),
+                        confidence: None,
+                        language: None,
+                        engine: None,
+                        segments: word_segments(&alternative["words"]),
+                        model_version: reported_model(&result["metadata"]),
+                    })
                 }
                 Err(e) => {
                     error!("Failed to parse JSON response: {:?}", e);
@@ -125,10 +160,65 @@ pub async fn transcribe_with_deepgram(
         }
         Err(e) => {
             error!("Failed to send request to Deepgram API: {:?}", e);
-            Err(anyhow::anyhow!(
-                "Failed to send request to Deepgram API: {:?}",
-                e
-            ))
+            Err(anyhow::Error::new(e).context("Failed to send request to Deepgram API"))
+        }
+    }
+}
+
+/// "name/version" of the model deepgram says it transcribed with, e.g.
+/// "2-general-nova/2024-01-18.26916"
+fn reported_model(metadata: &Value) -> Option<String> {
+    let id = metadata["models"][0].as_str()?;
+    let info = &metadata["model_info"][id];
+    Some(format!(
+        "{}/{}",
+        info["name"].as_str()?,
+        info["version"].as_str()?
+    ))
+}
+
+/// Each word of a transcription with when it was said
+fn word_segments(words: &Value) -> Vec<TextSegment> {
+    let Some(words) = words.as_array() else {
+        return Vec::new();
+    };
+    words
+        .iter()
+        .filter_map(|word| {
+            Some(TextSegment {
+                start: word["start"].as_f64()?,
+                end: word["end"].as_f64()?,
+                text: word["punctuated_word"]
+                    .as_str()
+                    .or_else(|| word["word"].as_str())?
+                    .to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The words of a diarized transcription joined in speaker turns, one per line. None when the
+/// words have no speaker
+fn speaker_turns(words: &Value) -> Option<String> {
+    let mut turns: Vec<(u64, String)> = Vec::new();
+    for word in words.as_array()? {
+        let speaker = word["speaker"].as_u64()?;
+        let text = word["punctuated_word"]
+            .as_str()
+            .or_else(|| word["word"].as_str())?;
+        match turns.last_mut() {
+            Some((last, turn)) if *last == speaker => {
+                turn.push(' ');
+                turn.push_str(text);
+            }
+            _ => turns.push((speaker, text.to_string())),
         }
     }
+    (!turns.is_empty()).then(|| {
+        turns
+            .into_iter()
+            .map(|(_, turn)| turn)
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
 }
@@ -1,14 +1,14 @@
 use crate::{
     deepgram::CUSTOM_DEEPGRAM_API_TOKEN, deepgram::DEEPGRAM_WEBSOCKET_URL,
-    realtime::RealtimeTranscriptionEvent, AudioStream,
+    realtime::RealtimeTranscriptionEvent, AudioBlock, AudioStream,
 };
-use crate::{AudioDevice, DeviceType};
-use anyhow::Result;
+use crate::{AudioDevice, DeviceType, KeywordSpotter};
+use anyhow::{anyhow, Result};
 use bytes::BufMut;
 use bytes::Bytes;
 use bytes::BytesMut;
 use crossbeam::channel::RecvError;
-use deepgram::common::options::Encoding;
+use deepgram::common::options::{Encoding, Endpointing, Language as DeepgramLanguage};
 use deepgram::common::stream_response::StreamResponse;
 use futures::channel::mpsc::{self, Receiver as FuturesReceiver};
 use futures::{SinkExt, TryStreamExt};
@@ -18,32 +18,53 @@ use std::sync::{atomic::AtomicBool, Arc};
 use std::time::Duration;
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::oneshot;
-use tracing::info;
+use tracing::{info, warn};
+
+/// Silence after which deepgram finalizes what was said so far
+const ENDPOINTING_MS: u32 = 300;
+/// Gap between words deepgram reports as the end of an utterance, for speech it never finalized
+/// through endpointing because of background noise
+const UTTERANCE_END_MS: u16 = 1000;
 
 pub async fn stream_transcription_deepgram(
     stream: Arc<AudioStream>,
-    _languages: Vec<Language>, // TODO impl language
+    languages: Vec<Language>,
     is_running: Arc<AtomicBool>,
     deepgram_api_key: Option<String>,
+    keywords: Vec<String>,
+    keyword_spotter: Option<KeywordSpotter>,
 ) -> Result<()> {
     start_deepgram_stream(
-        stream.subscribe().await,
+        stream.subscribe(),
         stream.device.clone(),
         stream.device_config.sample_rate().0,
         is_running,
         deepgram_api_key,
+        &languages,
+        keywords,
+        keyword_spotter,
     )
     .await?;
 
     Ok(())
 }
 
+/// Streams the audio to deepgram's websocket, sending its interim and final results as
+/// `transcription` events within a few hundred milliseconds of the speech.
+///
+/// The connection is kept alive through silences. A single language is forced, deepgram's
+/// streaming API can't pick among several so it uses its default then. Returns an error when the
+/// connection drops, for the caller to reconnect
+#[allow(clippy::too_many_arguments)]
 pub async fn start_deepgram_stream(
-    stream: Receiver<Vec<f32>>,
+    stream: Receiver<AudioBlock>,
     device: Arc<AudioDevice>,
     sample_rate: u32,
     is_running: Arc<AtomicBool>,
     deepgram_api_key: Option<String>,
+    languages: &[Language],
+    keywords: Vec<String>,
+    keyword_spotter: Option<KeywordSpotter>,
 ) -> Result<()> {
     let api_key = deepgram_api_key.unwrap_or(CUSTOM_DEEPGRAM_API_TOKEN.to_string());
 
@@ -51,20 +72,6 @@ pub async fn start_deepgram_stream(
         return Err(anyhow::anyhow!("Deepgram API key not found"));
     }
 
-    // create shutdown rx from is_running
-    let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
-
-    tokio::spawn(async move {
-        loop {
-            let running = is_running.load(std::sync::atomic::Ordering::SeqCst);
-            if !running {
-                shutdown_tx.send(()).unwrap();
-                break;
-            }
-            tokio::time::sleep(Duration::from_millis(100)).await;
-        }
-    });
-
     info!("Starting deepgram stream for device: {}", device);
 
     let deepgram = match DEEPGRAM_WEBSOCKET_URL.as_str().is_empty() {
@@ -76,14 +83,27 @@ pub async fn start_deepgram_stream(
 
     let deepgram_transcription = deepgram.transcription();
 
+    let mut options = deepgram::common::options::OptionsBuilder::new()
+        .model(deepgram::common::options::Model::Nova2)
+        .smart_format(true)
+        .keywords(keywords.iter().map(String::as_str));
+    match languages {
+        [language] => {
+            options = options.language(DeepgramLanguage::Other(language.as_lang_code().to_string()))
+        }
+        [] => {}
+        _ => warn!(
+            "deepgram streaming can't detect among {} languages, using its default",
+            languages.len()
+        ),
+    }
+
     let req = deepgram_transcription
-        .stream_request_with_options(
-            deepgram::common::options::OptionsBuilder::new()
-                .model(deepgram::common::options::Model::Nova2)
-                .smart_format(true)
-                .build(),
-        )
+        .stream_request_with_options(options.build())
         .keep_alive()
+        .interim_results(true)
+        .endpointing(Endpointing::CustomDurationMs(ENDPOINTING_MS))
+        .utterance_end_ms(UTTERANCE_END_MS)
         .channels(1)
         .sample_rate(sample_rate)
         .encoding(Encoding::Linear16);
@@ -92,37 +112,58 @@ pub async fn start_deepgram_stream(
     let mut results = req.stream(get_stream(stream)).await?;
     let device_clone = device.clone();
 
-    loop {
+    // create shutdown rx from is_running
+    let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
+
+    let shutdown_watcher = tokio::spawn(async move {
+        loop {
+            let running = is_running.load(std::sync::atomic::Ordering::SeqCst);
+            if !running {
+                let _ = shutdown_tx.send(());
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    });
+
+    let streamed = loop {
         tokio::select! {
             _ = &mut shutdown_rx => {
                 info!("Shutting down deepgram stream for device: {}", device);
-                break;
+                break Ok(());
             }
             result = results.try_next() => {
-                if let Ok(Some(result)) = result {
-                    handle_transcription(
-                        result,
-                        device_clone.clone(),
-                    ).await;
+                match result {
+                    Ok(Some(result)) => {
+                        handle_transcription(result, device_clone.clone(), keyword_spotter.as_ref())
+                            .await
+                    }
+                    Ok(None) => {
+                        break Err(anyhow!("deepgram stream closed for device: {}", device));
+                    }
+                    Err(e) => {
+                        break Err(anyhow!("deepgram stream failed for device {}: {}", device, e));
+                    }
                 }
             }
-            _ = tokio::time::sleep(Duration::from_millis(100)) => {
-                continue;
-            }
         }
-    }
+    };
+    // every reconnection starts its own watcher, the one of a dropped connection would otherwise
+    // poll is_running until the recording stops
+    shutdown_watcher.abort();
+    streamed?;
 
     handle.close_stream().await?;
     Ok(())
 }
 
-fn get_stream(mut stream: Receiver<Vec<f32>>) -> FuturesReceiver<Result<Bytes, RecvError>> {
+fn get_stream(mut stream: Receiver<AudioBlock>) -> FuturesReceiver<Result<Bytes, RecvError>> {
     let (mut tx, rx) = mpsc::channel(1);
 
     tokio::spawn(async move {
-        while let Ok(data) = stream.recv().await {
-            let mut bytes = BytesMut::with_capacity(data.len() * 2);
-            for sample in data {
+        while let Ok(block) = stream.recv().await {
+            let mut bytes = BytesMut::with_capacity(block.data.len() * 2);
+            for sample in block.data {
                 bytes.put_i16_le((sample * i16::MAX as f32) as i16);
             }
             if tx.send(Ok(bytes.freeze())).await.is_err() {
@@ -134,16 +175,25 @@ fn get_stream(mut stream: Receiver<Vec<f32>>) -> FuturesReceiver<Result<Bytes, R
     rx
 }
 
-async fn handle_transcription(result: StreamResponse, device: Arc<AudioDevice>) {
+async fn handle_transcription(
+    result: StreamResponse,
+    device: Arc<AudioDevice>,
+    keyword_spotter: Option<&KeywordSpotter>,
+) {
     if let StreamResponse::TranscriptResponse {
         channel, is_final, ..
     } = result
     {
-        let res = channel.alternatives.first().unwrap();
+        let Some(res) = channel.alternatives.first() else {
+            return;
+        };
         let text = res.transcript.clone();
         let is_input = device.device_type == DeviceType::Input;
 
         if !text.is_empty() {
+            if let Some(spotter) = keyword_spotter {
+                spotter.spot(&text, &device.to_string(), chrono::Utc::now(), is_final);
+            }
             let _ = send_event(
                 "transcription",
                 RealtimeTranscriptionEvent {
@@ -0,0 +1,141 @@
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// Audio waiting on disk to be transcribed, oldest first: the raw f32 samples of each item in a
+/// `<id>.pcm` file next to its `<id>.json` metadata. Bounded by the size of the samples
+pub struct DiskQueue<M> {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Ids of the queued items, ascending
+    ids: Vec<u64>,
+    bytes: u64,
+    _meta: PhantomData<M>,
+}
+
+/// An item of a `DiskQueue`
+pub struct QueuedAudio<M> {
+    pub id: u64,
+    pub samples: Vec<f32>,
+    pub meta: M,
+}
+
+impl<M: Serialize + DeserializeOwned> DiskQueue<M> {
+    /// Opens the queue in `dir`, picking up the items left there by a previous run
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut ids = Vec::new();
+        let mut bytes = 0;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("pcm") {
+                continue;
+            }
+            let Some(id) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse().ok())
+            else {
+                continue;
+            };
+            if !path.with_extension("json").exists() {
+                // interrupted while writing it
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            bytes += fs::metadata(&path)?.len();
+            ids.push(id);
+        }
+        ids.sort_unstable();
+        if !ids.is_empty() {
+            debug!("{} items waiting in {}", ids.len(), dir.display());
+        }
+
+        Ok(Self {
+            dir,
+            max_bytes,
+            ids,
+            bytes,
+            _meta: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Appends the audio, false without queuing it when that would exceed `max_bytes`
+    pub fn push(&mut self, samples: &[f32], meta: &M) -> Result<bool> {
+        let size = (samples.len() * std::mem::size_of::<f32>()) as u64;
+        if self.bytes + size > self.max_bytes {
+            warn!(
+                "{} is full ({} items, {} bytes)",
+                self.dir.display(),
+                self.ids.len(),
+                self.bytes
+            );
+            return Ok(false);
+        }
+        let id = self.ids.last().map_or(0, |id| id + 1);
+        // metadata last, items without it are incomplete
+        fs::write(self.path(id, "pcm"), bytemuck::cast_slice(samples))?;
+        fs::write(self.path(id, "json"), serde_json::to_vec(meta)?)?;
+        self.ids.push(id);
+        self.bytes += size;
+        Ok(true)
+    }
+
+    pub fn front_id(&self) -> Option<u64> {
+        self.ids.first().copied()
+    }
+
+    /// The oldest item, which stays queued until `remove`d
+    pub fn front(&self) -> Result<Option<QueuedAudio<M>>> {
+        let Some(&id) = self.ids.first() else {
+            return Ok(None);
+        };
+        let bytes = fs::read(self.path(id, "pcm"))?;
+        if bytes.len() % std::mem::size_of::<f32>() != 0 {
+            return Err(anyhow!("corrupted queued audio {}", id));
+        }
+        let samples = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        let meta = serde_json::from_slice(&fs::read(self.path(id, "json"))?)?;
+        Ok(Some(QueuedAudio { id, samples, meta }))
+    }
+
+    pub fn remove(&mut self, id: u64) -> Result<()> {
+        let Some(index) = self.ids.iter().position(|&i| i == id) else {
+            return Ok(());
+        };
+        let pcm = self.path(id, "pcm");
+        self.bytes = self
+            .bytes
+            .saturating_sub(fs::metadata(&pcm).map(|m| m.len()).unwrap_or(0));
+        remove_if_exists(&pcm)?;
+        remove_if_exists(&self.path(id, "json"))?;
+        self.ids.remove(index);
+        Ok(())
+    }
+
+    fn path(&self, id: u64, extension: &str) -> PathBuf {
+        self.dir.join(format!("{:020}.{}", id, extension))
+    }
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
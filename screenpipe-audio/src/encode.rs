@@ -1,4 +1,7 @@
+use bytes::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
 use screenpipe_core::find_ffmpeg_path;
+use std::ffi::OsStr;
 use std::io::Write;
 use std::{
     path::Path,
@@ -6,37 +9,295 @@ use std::{
 };
 use tracing::{debug, error};
 
+/// Opus bitrate keeping speech intelligible at a fraction of the size of aac
+pub const DEFAULT_OPUS_BITRATE_KBPS: u32 = 24;
+
+/// Sample rates AAC is played back at everywhere, Apple's decoders included
+const AAC_SAMPLE_RATES: [u32; 9] = [8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000];
+
+/// Codec and container audio files are encoded in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AudioCodec {
+    /// AAC-LC at 64kbps in mp4
+    #[default]
+    Aac,
+    /// Opus in ogg, tuned for speech
+    Opus { bitrate_kbps: u32 },
+    /// Lossless, for archiving captures as they were heard
+    Flac,
+    /// AAC-LC at 64kbps in m4a, which QuickTime and iOS play natively
+    M4a,
+}
+
+/// Where the audio of a file comes from, written in it as metadata tags (vorbis comments in ogg
+/// and flac, atoms in mp4) so an exported file still says what it is outside the database
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AudioTags {
+    pub device: String,
+    /// When capture of the audio started
+    pub captured_at: Option<DateTime<Utc>>,
+    /// Transcription engine the audio was sent to
+    pub engine: Option<String>,
+}
+
+impl AudioTags {
+    /// `-metadata` options of the tags. The standard title, artist, date and comment tags are
+    /// shown by players, the screenpipe_ ones are kept as is by ogg and flac only
+    fn ffmpeg_args(&self) -> Vec<String> {
+        let version = env!("CARGO_PKG_VERSION");
+        let captured_at = self
+            .captured_at
+            .map(|at| at.to_rfc3339_opts(SecondsFormat::Millis, true));
+        let mut comment = format!("recorded by screenpipe {}", version);
+        if let Some(engine) = &self.engine {
+            comment.push_str(&format!(", transcribed with {}", engine));
+        }
+
+        let mut tags = vec![
+            (
+                "title",
+                match &captured_at {
+                    Some(at) => format!("{} {}", self.device, at),
+                    None => self.device.clone(),
+                },
+            ),
+            ("artist", self.device.clone()),
+            ("comment", comment),
+            ("screenpipe_device", self.device.clone()),
+            ("screenpipe_version", version.to_string()),
+        ];
+        if let Some(at) = captured_at {
+            tags.push(("date", at.clone()));
+            tags.push(("screenpipe_captured_at", at));
+        }
+        if let Some(engine) = &self.engine {
+            tags.push(("screenpipe_engine", engine.clone()));
+        }
+        tags.into_iter()
+            .flat_map(|(key, value)| ["-metadata".to_string(), format!("{}={}", key, value)])
+            .collect()
+    }
+}
+
+impl AudioCodec {
+    /// Extension of the files of the codec's container
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "mp4",
+            AudioCodec::Opus { .. } => "ogg",
+            AudioCodec::Flac => "flac",
+            AudioCodec::M4a => "m4a",
+        }
+    }
+
+    /// Extension of the files `encode_stream` streams are appended to
+    pub fn stream_extension(&self) -> &'static str {
+        match self {
+            AudioCodec::Aac | AudioCodec::M4a => "aac",
+            AudioCodec::Opus { .. } | AudioCodec::Flac => "ogg",
+        }
+    }
+
+    /// Mime type of the codec's container, for the Content-Type of encoded audio
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            AudioCodec::Aac | AudioCodec::M4a => "audio/mp4",
+            AudioCodec::Opus { .. } => "audio/ogg",
+            AudioCodec::Flac => "audio/flac",
+        }
+    }
+
+    /// Output options for `sample_rate` audio with `channels` channels, to a file or to a pipe
+    /// when not `seekable`
+    fn ffmpeg_args(&self, sample_rate: u32, channels: u16, seekable: bool) -> Vec<String> {
+        let mut args = self.encoder_args(sample_rate, channels);
+        // mp4 can't go back to put its index at the start of a pipe, it's fragmented instead,
+        // which browsers play all the same
+        let movflags = if seekable {
+            "+faststart" // Optimize for web streaming
+        } else {
+            "frag_keyframe+empty_moov+default_base_moof"
+        };
+        let container: &[&str] = match self {
+            AudioCodec::Aac => &["-movflags", movflags, "-f", "mp4"],
+            AudioCodec::Opus { .. } => &["-f", "ogg"],
+            AudioCodec::Flac => &["-f", "flac"],
+            AudioCodec::M4a => &[
+                "-movflags",
+                movflags,
+                "-f",
+                "ipod", // The m4a flavor of mp4 itunes and iOS expect
+            ],
+        };
+        args.extend(container.iter().map(|arg| arg.to_string()));
+        args
+    }
+
+    /// Output options for streams that stay decodable when appended to one another: chained ogg
+    /// for opus and flac, adts for aac. mp4 can't be appended to
+    fn stream_args(&self, sample_rate: u32, channels: u16) -> Vec<String> {
+        let mut args = self.encoder_args(sample_rate, channels);
+        let container = match self {
+            AudioCodec::Aac | AudioCodec::M4a => "adts",
+            AudioCodec::Opus { .. } | AudioCodec::Flac => "ogg",
+        };
+        args.extend(["-f".to_string(), container.to_string()]);
+        args
+    }
+
+    fn encoder_args(&self, sample_rate: u32, channels: u16) -> Vec<String> {
+        let mut args = Vec::new();
+        // aac is resampled to a rate all players take and anything above stereo downmixed, a
+        // 96khz or 5.1 device would otherwise give files apple devices refuse
+        if matches!(self, AudioCodec::Aac | AudioCodec::M4a) {
+            if !AAC_SAMPLE_RATES.contains(&sample_rate) {
+                args.extend(["-ar".to_string(), "48000".to_string()]);
+            }
+            if channels > 2 {
+                args.extend(["-ac".to_string(), "2".to_string()]);
+            }
+        }
+        let encoder = match self {
+            AudioCodec::Aac | AudioCodec::M4a => [
+                "-c:a",
+                "aac",
+                "-b:a",
+                "64k", // Reduced bitrate for higher compression
+                "-profile:a",
+                "aac_low", // Use AAC-LC profile for better compatibility
+            ]
+            .map(String::from)
+            .to_vec(),
+            // libopus only takes 8, 12, 16, 24 and 48khz, ffmpeg resamples anything else
+            AudioCodec::Opus { bitrate_kbps } => vec![
+                "-c:a".to_string(),
+                "libopus".to_string(),
+                "-b:a".to_string(),
+                format!("{}k", bitrate_kbps),
+                "-application".to_string(),
+                "voip".to_string(), // Favor speech intelligibility
+            ],
+            AudioCodec::Flac => [
+                "-c:a",
+                "flac",
+                "-sample_fmt",
+                "s32", // Encoded as 24 bit, s16 would lose precision of the float capture
+            ]
+            .map(String::from)
+            .to_vec(),
+        };
+        args.extend(encoder);
+        args
+    }
+}
+
 pub fn encode_single_audio(
     data: &[u8],
     sample_rate: u32,
     channels: u16,
     output_path: &Path,
+    codec: AudioCodec,
+    tags: Option<&AudioTags>,
 ) -> anyhow::Result<()> {
+    let mut args = codec.ffmpeg_args(sample_rate, channels, true);
+    if let Some(tags) = tags {
+        args.extend(tags.ffmpeg_args());
+    }
+    run_ffmpeg(
+        data,
+        raw_input_args(sample_rate, channels),
+        args,
+        output_path.as_os_str(),
+    )?;
+    Ok(())
+}
+
+/// Audio encoded in memory with the mime type of its container, e.g. to send in a response
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncodedAudio {
+    pub bytes: Bytes,
+    pub content_type: &'static str,
+}
+
+/// `encode_single_audio` without a file: the encoded audio is returned instead of written
+pub fn encode_audio_to_bytes(
+    data: &[u8],
+    sample_rate: u32,
+    channels: u16,
+    codec: AudioCodec,
+    tags: Option<&AudioTags>,
+) -> anyhow::Result<EncodedAudio> {
+    let mut args = codec.ffmpeg_args(sample_rate, channels, false);
+    if let Some(tags) = tags {
+        args.extend(tags.ffmpeg_args());
+    }
+    let bytes = run_ffmpeg(
+        data,
+        raw_input_args(sample_rate, channels),
+        args,
+        OsStr::new("pipe:1"),
+    )?;
+    Ok(EncodedAudio {
+        bytes: Bytes::from(bytes),
+        content_type: codec.content_type(),
+    })
+}
+
+/// Encodes f32 samples to a stream of the codec that can be appended to another one of the same
+/// codec and still be decoded from its start, see `AudioCodec::stream_args`
+pub(crate) fn encode_stream(
+    data: &[u8],
+    sample_rate: u32,
+    channels: u16,
+    codec: AudioCodec,
+) -> anyhow::Result<Vec<u8>> {
+    run_ffmpeg(
+        data,
+        raw_input_args(sample_rate, channels),
+        codec.stream_args(sample_rate, channels),
+        OsStr::new("pipe:1"),
+    )
+}
+
+/// Re-encodes a stream of `encode_stream` to a stream of `codec`
+pub(crate) fn transcode_stream(data: &[u8], codec: AudioCodec) -> anyhow::Result<Vec<u8>> {
+    // the rate and channels of the source are kept, they already fit every codec
+    run_ffmpeg(
+        data,
+        Vec::new(),
+        codec.stream_args(48000, 1),
+        OsStr::new("pipe:1"),
+    )
+}
+
+/// Input options of f32 samples
+fn raw_input_args(sample_rate: u32, channels: u16) -> Vec<String> {
+    vec![
+        "-f".to_string(),
+        "f32le".to_string(),
+        "-ar".to_string(),
+        sample_rate.to_string(),
+        "-ac".to_string(),
+        channels.to_string(),
+    ]
+}
+
+/// Runs ffmpeg on `data` read with the input options `input_args` (probed when empty) and the
+/// output options `args`, returning what it wrote to stdout
+fn run_ffmpeg(
+    data: &[u8],
+    input_args: Vec<String>,
+    args: Vec<String>,
+    output: &OsStr,
+) -> anyhow::Result<Vec<u8>> {
     debug!("Starting FFmpeg process");
 
     let mut command = Command::new(find_ffmpeg_path().unwrap());
     command
-        .args([
-            "-f",
-            "f32le",
-            "-ar",
-            &sample_rate.to_string(),
-            "-ac",
-            &channels.to_string(),
-            "-i",
-            "pipe:0",
-            "-c:a",
-            "aac",
-            "-b:a",
-            "64k", // Reduced bitrate for higher compression
-            "-profile:a",
-            "aac_low", // Use AAC-LC profile for better compatibility
-            "-movflags",
-            "+faststart", // Optimize for web streaming
-            "-f",
-            "mp4",
-            output_path.to_str().unwrap(),
-        ])
+        .args(input_args)
+        .args(["-i", "pipe:0"])
+        .args(args)
+        .arg(output)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
@@ -48,18 +309,22 @@ pub fn encode_single_audio(
     debug!("FFmpeg process spawned");
     let mut stdin = ffmpeg.stdin.take().expect("Failed to open stdin");
 
-    stdin.write_all(data)?;
-
-    debug!("Dropping stdin");
-    drop(stdin);
+    // written from another thread, ffmpeg stops reading while its stdout isn't read
+    let data = data.to_vec();
+    let writer = std::thread::spawn(move || {
+        let written = stdin.write_all(&data);
+        debug!("Dropping stdin");
+        written
+    });
     debug!("Waiting for FFmpeg process to exit");
     let output = ffmpeg.wait_with_output().unwrap();
+    writer
+        .join()
+        .map_err(|_| anyhow::anyhow!("FFmpeg stdin writer panicked"))??;
     let status = output.status;
-    let stdout = String::from_utf8_lossy(&output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
 
     debug!("FFmpeg process exited with status: {}", status);
-    debug!("FFmpeg stdout: {}", stdout);
     debug!("FFmpeg stderr: {}", stderr);
 
     if !status.success() {
@@ -71,5 +336,5 @@ pub fn encode_single_audio(
         ));
     }
 
-    Ok(())
+    Ok(output.stdout)
 }
@@ -0,0 +1,150 @@
+use crate::AudioTranscriptionEngine;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use screenpipe_events::send_event;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Consecutive failures after which an engine is considered down
+const FAILURES_UNTIL_DOWN: u32 = 3;
+/// How long a down engine is skipped before it gets another try
+const RETRY_DOWN_AFTER: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    static ref HEALTH: DashMap<String, EngineHealth> = DashMap::new();
+    /// Engine currently transcribing instead of the primary one, if any
+    static ref ACTIVE_FALLBACK: Mutex<Option<String>> = Mutex::new(None);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineStatus {
+    Healthy,
+    /// Failed recently but still tried
+    Degraded,
+    /// Failed too often in a row, skipped until `RETRY_DOWN_AFTER` has passed
+    Down,
+}
+
+/// How a transcription engine of the fallback chain has been doing
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EngineHealth {
+    pub engine: String,
+    pub status: EngineStatus,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+}
+
+impl EngineHealth {
+    fn new(engine: String) -> Self {
+        Self {
+            engine,
+            status: EngineStatus::Healthy,
+            consecutive_failures: 0,
+            last_error: None,
+            last_success: None,
+            last_failure: None,
+        }
+    }
+}
+
+/// Sent as `transcription_fallback` whenever the engine doing the transcription changes, `active`
+/// is the primary engine again once it recovered
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TranscriptionFallbackEvent {
+    pub primary: String,
+    pub active: String,
+    /// Last error of the primary engine while falling back
+    pub reason: Option<String>,
+}
+
+/// Health of every engine that transcribed something since startup
+pub fn engine_health() -> Vec<EngineHealth> {
+    let mut health: Vec<EngineHealth> = HEALTH.iter().map(|entry| entry.clone()).collect();
+    health.sort_by(|a, b| a.engine.cmp(&b.engine));
+    health
+}
+
+/// Whether the engine is worth trying, false while it is down
+pub fn is_available(engine: &AudioTranscriptionEngine) -> bool {
+    HEALTH.get(&engine.to_string()).map_or(true, |health| {
+        health.status != EngineStatus::Down
+            || health.last_failure.map_or(true, |failure| {
+                Utc::now()
+                    .signed_duration_since(failure)
+                    .to_std()
+                    .unwrap_or_default()
+                    >= RETRY_DOWN_AFTER
+            })
+    })
+}
+
+pub fn record_success(engine: &AudioTranscriptionEngine) {
+    let mut health = HEALTH
+        .entry(engine.to_string())
+        .or_insert_with(|| EngineHealth::new(engine.to_string()));
+    if health.status != EngineStatus::Healthy {
+        info!("{} transcription engine recovered", engine);
+    }
+    health.status = EngineStatus::Healthy;
+    health.consecutive_failures = 0;
+    health.last_success = Some(Utc::now());
+}
+
+pub fn record_failure(engine: &AudioTranscriptionEngine, error: &anyhow::Error) {
+    let mut health = HEALTH
+        .entry(engine.to_string())
+        .or_insert_with(|| EngineHealth::new(engine.to_string()));
+    health.consecutive_failures += 1;
+    health.last_error = Some(error.to_string());
+    health.last_failure = Some(Utc::now());
+    let status = if health.consecutive_failures >= FAILURES_UNTIL_DOWN {
+        EngineStatus::Down
+    } else {
+        EngineStatus::Degraded
+    };
+    if status == EngineStatus::Down && health.status != EngineStatus::Down {
+        warn!(
+            "{} transcription engine failed {} times in a row, skipping it for {:?}",
+            engine, health.consecutive_failures, RETRY_DOWN_AFTER
+        );
+    }
+    health.status = status;
+}
+
+/// Notes which engine of the chain transcribed, sending a `transcription_fallback` event when it
+/// isn't the same as last time
+pub fn record_active(primary: &AudioTranscriptionEngine, active: &AudioTranscriptionEngine) {
+    let fallback = (active != primary).then(|| active.to_string());
+    let Ok(mut current) = ACTIVE_FALLBACK.lock() else {
+        return;
+    };
+    if *current == fallback {
+        return;
+    }
+    match &fallback {
+        Some(engine) => warn!("transcribing with {} while {} fails", engine, primary),
+        None => info!("transcribing with {} again", primary),
+    }
+    *current = fallback;
+    let reason = (active != primary)
+        .then(|| {
+            HEALTH
+                .get(&primary.to_string())
+                .and_then(|health| health.last_error.clone())
+        })
+        .flatten();
+    let _ = send_event(
+        "transcription_fallback",
+        TranscriptionFallbackEvent {
+            primary: primary.to_string(),
+            active: active.to_string(),
+            reason,
+        },
+    );
+}
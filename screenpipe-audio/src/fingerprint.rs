@@ -0,0 +1,254 @@
+use log::debug;
+use realfft::RealFftPlanner;
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+// frames of 64ms every 32ms of 16khz audio, 17 bands between 300hz and 3khz giving the 16 bits of
+// each frame (Haitsma and Kalker's fingerprint, on shorter frames for segments of a few seconds)
+const SAMPLE_RATE: f32 = 16000.0;
+const FRAME: usize = 1024;
+const HOP: usize = 512;
+const BANDS: usize = 17;
+const MIN_HZ: f32 = 300.0;
+const MAX_HZ: f32 = 3000.0;
+
+/// How segments heard on several devices at once are told apart
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DuplicateDetection {
+    /// Share of the fingerprint bits that may differ between two segments of the same audio. The
+    /// fingerprints of unrelated audio differ by about half
+    pub max_bit_error_rate: f32,
+    /// Difference between the capture times of the same audio on two devices that is searched
+    pub max_lag: Duration,
+    /// Audio needed in common to call two segments the same
+    pub min_overlap: Duration,
+    /// Share of a segment that has to be heard on another device for it to be dropped. A longer
+    /// segment than the one it overlaps has audio of its own, e.g. the user talking over a call
+    pub min_coverage: f32,
+    /// How long segments that were transcribed are kept to compare later ones with
+    pub history: Duration,
+}
+
+impl Default for DuplicateDetection {
+    fn default() -> Self {
+        Self {
+            max_bit_error_rate: 0.3,
+            max_lag: Duration::from_millis(1000),
+            min_overlap: Duration::from_secs(1),
+            min_coverage: 0.8,
+            history: Duration::from_secs(120),
+        }
+    }
+}
+
+/// 16 bits per frame: whether the energy difference between each pair of neighboring bands
+/// grew since the previous frame. Survives the room, the mic and codecs, not different audio
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fingerprint {
+    pub frames: Vec<u16>,
+}
+
+impl Fingerprint {
+    /// Fingerprint of 16khz mono audio, empty for audio shorter than two frames
+    pub fn new(audio: &[f32]) -> Self {
+        if audio.len() < FRAME {
+            return Self { frames: Vec::new() };
+        }
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FRAME);
+        let mut input = fft.make_input_vec();
+        let mut spectrum = fft.make_output_vec();
+        let window: Vec<f32> = (0..FRAME)
+            .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FRAME as f32).cos())
+            .collect();
+        // log spaced band edges, in fft bins
+        let edges: Vec<usize> = (0..=BANDS)
+            .map(|band| {
+                let hz = MIN_HZ * (MAX_HZ / MIN_HZ).powf(band as f32 / BANDS as f32);
+                (hz * FRAME as f32 / SAMPLE_RATE) as usize
+            })
+            .collect();
+
+        let energies: Vec<[f32; BANDS]> = (0..=(audio.len() - FRAME) / HOP)
+            .map(|frame| {
+                let samples = &audio[frame * HOP..frame * HOP + FRAME];
+                for ((x, &s), &w) in input.iter_mut().zip(samples).zip(&window) {
+                    *x = s * w;
+                }
+                let _ = fft.process(&mut input, &mut spectrum);
+                let mut bands = [0.0; BANDS];
+                for (band, energy) in bands.iter_mut().enumerate() {
+                    *energy = spectrum[edges[band]..edges[band + 1].max(edges[band] + 1)]
+                        .iter()
+                        .map(|bin| bin.norm_sqr())
+                        .sum();
+                }
+                bands
+            })
+            .collect();
+
+        let frames = energies
+            .windows(2)
+            .map(|pair| {
+                let (previous, current) = (&pair[0], &pair[1]);
+                (0..BANDS - 1).fold(0u16, |bits, band| {
+                    let difference =
+                        (current[band] - current[band + 1]) - (previous[band] - previous[band + 1]);
+                    bits | (((difference > 0.0) as u16) << band)
+                })
+            })
+            .collect();
+        Self { frames }
+    }
+
+    /// Share of differing bits at the best alignment of `other` starting `offset` frames after
+    /// this one, give or take `max_lag` frames, over at least `min_overlap` frames in common
+    fn bit_error_rate(
+        &self,
+        other: &Fingerprint,
+        offset: isize,
+        max_lag: isize,
+        min_overlap: usize,
+    ) -> Option<f32> {
+        (offset - max_lag..=offset + max_lag)
+            .filter_map(|lag| {
+                // frame i of self is frame i - lag of other
+                let start = lag.max(0) as usize;
+                let end = (other.frames.len() as isize + lag).min(self.frames.len() as isize);
+                if end - (start as isize) < min_overlap.max(1) as isize {
+                    return None;
+                }
+                let end = end as usize;
+                let errors: u32 = (start..end)
+                    .map(|i| {
+                        (self.frames[i] ^ other.frames[(i as isize - lag) as usize]).count_ones()
+                    })
+                    .sum();
+                Some(errors as f32 / ((end - start) * (BANDS - 1)) as f32)
+            })
+            .reduce(f32::min)
+    }
+}
+
+/// How far speech stands out from the noise floor of a segment, in db: the loudest fifth of its
+/// frames over the quietest fifth. Loopback audio, with digital silence between words, scores
+/// higher than a mic hearing the same speakers in a room
+pub fn cleanliness(audio: &[f32]) -> f32 {
+    let mut energies: Vec<f32> = audio
+        .chunks(HOP)
+        .map(|frame| frame.iter().map(|x| x * x).sum::<f32>() / frame.len() as f32)
+        .collect();
+    if energies.is_empty() {
+        return 0.0;
+    }
+    energies.sort_by(f32::total_cmp);
+    let fifth = (energies.len() / 5).max(1);
+    let mean = |energies: &[f32]| energies.iter().sum::<f32>() / energies.len() as f32;
+    let floor = mean(&energies[..fifth]).max(1e-10);
+    let speech = mean(&energies[energies.len() - fifth..]).max(1e-10);
+    10.0 * (speech / floor).log10()
+}
+
+/// A segment of one device, to compare with the segments of the others
+#[derive(Clone, Copy, Debug)]
+pub struct Candidate<'a> {
+    pub device: &'a str,
+    /// 16khz mono
+    pub samples: &'a [f32],
+    pub captured_at: SystemTime,
+}
+
+struct Heard {
+    device: String,
+    captured_at: SystemTime,
+    end: SystemTime,
+    fingerprint: Fingerprint,
+}
+
+/// Finds segments heard on two devices at once, a meeting coming out of the speakers caught by
+/// the mic and by the loopback device, so it is transcribed once, from the cleaner device
+pub struct DuplicateDetector {
+    config: DuplicateDetection,
+    /// Segments kept recently, oldest first
+    history: VecDeque<Heard>,
+}
+
+impl DuplicateDetector {
+    pub fn new(config: DuplicateDetection) -> Self {
+        Self {
+            config,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Indices of the `candidates` that are the same audio as a cleaner candidate of another
+    /// device, or as a segment of another device kept earlier: that one was transcribed already,
+    /// cleaner or not. The others are kept and remembered
+    pub fn duplicates(&mut self, candidates: &[Candidate]) -> Vec<usize> {
+        let frame = Duration::from_secs_f32(HOP as f32 / SAMPLE_RATE);
+        let frames = |duration: Duration| (duration.as_secs_f32() / frame.as_secs_f32()) as isize;
+        let max_lag = frames(self.config.max_lag);
+        let min_overlap = frames(self.config.min_overlap) as usize;
+
+        let mut order: Vec<(usize, f32)> = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| (i, cleanliness(candidate.samples)))
+            .collect();
+        order.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut duplicates = Vec::new();
+        let mut kept: Vec<Heard> = Vec::new();
+        for (i, clean) in order {
+            let candidate = &candidates[i];
+            let fingerprint = Fingerprint::new(candidate.samples);
+            let min_overlap = min_overlap
+                .max((fingerprint.frames.len() as f32 * self.config.min_coverage).ceil() as usize);
+            let end = candidate.captured_at
+                + Duration::from_secs_f32(candidate.samples.len() as f32 / SAMPLE_RATE);
+            let duplicate_of = self.history.iter().chain(&kept).find(|heard| {
+                heard.device != candidate.device
+                    && heard.captured_at < end + self.config.max_lag
+                    && candidate.captured_at < heard.end + self.config.max_lag
+                    && {
+                        // frames the candidate starts after the segment heard
+                        let offset = match candidate.captured_at.duration_since(heard.captured_at) {
+                            Ok(after) => frames(after),
+                            Err(e) => -frames(e.duration()),
+                        };
+                        heard
+                            .fingerprint
+                            .bit_error_rate(&fingerprint, offset, max_lag, min_overlap)
+                            .is_some_and(|rate| rate <= self.config.max_bit_error_rate)
+                    }
+            });
+            match duplicate_of {
+                Some(heard) => {
+                    debug!(
+                        "device: {}, segment also heard on {}, not transcribed twice ({:.1}db clean)",
+                        candidate.device, heard.device, clean
+                    );
+                    duplicates.push(i);
+                }
+                None => kept.push(Heard {
+                    device: candidate.device.to_string(),
+                    captured_at: candidate.captured_at,
+                    end,
+                    fingerprint,
+                }),
+            }
+        }
+
+        self.history.extend(kept);
+        if let Some(newest) = self.history.iter().map(|heard| heard.end).max() {
+            while self
+                .history
+                .front()
+                .is_some_and(|heard| heard.end + self.config.history < newest)
+            {
+                self.history.pop_front();
+            }
+        }
+        duplicates.sort_unstable();
+        duplicates
+    }
+}
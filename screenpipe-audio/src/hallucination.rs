@@ -0,0 +1,289 @@
+use crate::{stt::TranscriptionResult, vad_engine::VadEngine};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use vad_rs::VadStatus;
+
+/// Longest run of words checked for loops, e.g. "thank you for watching" is 4
+const MAX_NGRAM: usize = 8;
+
+/// Phrases whisper makes up on silence or music, learned from the subtitles it was trained on.
+/// Longer phrases come first so that "thank you" isn't taken out of "thank you for watching"
+const KNOWN_PHRASES: &[&str] = &[
+    "thank you so much for watching",
+    "thank you for watching",
+    "thanks for watching",
+    "thank you",
+    "please subscribe",
+    "like and subscribe",
+    "subscribe to my channel",
+    "see you in the next video",
+    "subtitles by the amara.org community",
+    "sous-titres réalisés para la communauté d'amara.org",
+    "untertitel der amara.org-community",
+    "продолжение следует",
+    "ご視聴ありがとうございました",
+];
+
+/// Why a transcript is considered made up by whisper
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hallucination {
+    /// The same words looping, e.g. "Thank you. Thank you. Thank you. Thank you."
+    Repetition,
+    /// Nothing but phrases whisper is known to make up
+    KnownPhrase,
+    /// The VAD found (almost) no speech in the segment
+    NoSpeech,
+    /// Text the engine timestamped over audio the VAD found nothing but silence in
+    SilentRegion,
+}
+
+impl Hallucination {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Hallucination::Repetition => "repetition",
+            Hallucination::KnownPhrase => "known_phrase",
+            Hallucination::NoSpeech => "no_speech",
+            Hallucination::SilentRegion => "silent_region",
+        }
+    }
+}
+
+impl FromStr for Hallucination {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "repetition" => Ok(Hallucination::Repetition),
+            "known_phrase" => Ok(Hallucination::KnownPhrase),
+            "no_speech" => Ok(Hallucination::NoSpeech),
+            "silent_region" => Ok(Hallucination::SilentRegion),
+            _ => anyhow::bail!("unknown hallucination: {}", s),
+        }
+    }
+}
+
+/// What to do with suspected hallucinations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HallucinationFilter {
+    /// Keep transcripts as the engine made them
+    Off,
+    /// Mark suspected hallucinations but keep their text
+    Flag,
+    /// Collapse repetition loops, drop made up phrases, segments without speech and text over
+    /// silence
+    #[default]
+    Drop,
+}
+
+/// What the VAD decided on each frame of a segment, kept with its audio until its transcript is
+/// checked
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VadDecisions {
+    pub frame: Duration,
+    /// Whether each frame is speech, None where the VAD was unsure (speech starting or ending)
+    pub frames: Vec<Option<bool>>,
+}
+
+impl VadDecisions {
+    /// Share of the frames that are speech
+    pub fn speech_ratio(&self) -> f32 {
+        if self.frames.is_empty() {
+            return 0.0;
+        }
+        let speech = self.frames.iter().filter(|&&f| f == Some(true)).count();
+        speech as f32 / self.frames.len() as f32
+    }
+
+    /// Whether every frame overlapping `start`..`end` (seconds from the start of the segment) is
+    /// silence
+    pub fn is_silent(&self, start: f64, end: f64) -> bool {
+        let frame = self.frame.as_secs_f64();
+        if frame <= 0.0 || end <= start {
+            return false;
+        }
+        let first = (start.max(0.0) / frame).floor() as usize;
+        let last = ((end / frame).ceil() as usize).min(self.frames.len());
+        first < last
+            && self.frames[first..last]
+                .iter()
+                .all(|&is_speech| is_speech == Some(false))
+    }
+}
+
+impl HallucinationFilter {
+    /// Checks the transcription of `result` against what the VAD decided on its audio, when
+    /// known: the whole transcription when there's too little speech, and each timestamped part
+    /// of it against the frames it was said over. Dropped transcriptions are set to None with
+    /// `hallucination` saying why
+    pub fn apply(
+        &self,
+        result: &mut TranscriptionResult,
+        vad: Option<&VadDecisions>,
+        min_speech_ratio: f32,
+    ) {
+        if *self == HallucinationFilter::Off {
+            return;
+        }
+        let Some(text) = result
+            .transcription
+            .as_deref()
+            .filter(|t| !t.trim().is_empty())
+        else {
+            return;
+        };
+
+        let (mut cleaned, mut found) = detect_hallucination(text);
+        let mut silent = vec![false; result.text_segments.len()];
+        if let Some(vad) = vad {
+            if vad.speech_ratio() < min_speech_ratio {
+                found = Some(Hallucination::NoSpeech);
+            } else if found.is_none() {
+                for (segment, is_silent) in result.text_segments.iter().zip(silent.iter_mut()) {
+                    *is_silent = vad.is_silent(segment.start, segment.end);
+                }
+                if silent.contains(&true) {
+                    found = Some(Hallucination::SilentRegion);
+                    cleaned = result
+                        .text_segments
+                        .iter()
+                        .zip(&silent)
+                        .filter(|(segment, is_silent)| {
+                            !**is_silent && !segment.text.trim().is_empty()
+                        })
+                        .map(|(segment, _)| segment.text.trim())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                }
+            }
+        }
+        let Some(hallucination) = found else {
+            return;
+        };
+        debug!(
+            "device: {}, {:?} hallucination in transcript: {}",
+            result.input.device, hallucination, text
+        );
+
+        result.hallucination = Some(hallucination);
+        if *self == HallucinationFilter::Drop {
+            result.transcription = match hallucination {
+                Hallucination::Repetition => Some(cleaned),
+                // the parts said over speech are kept
+                Hallucination::SilentRegion => Some(cleaned).filter(|text| !text.is_empty()),
+                Hallucination::KnownPhrase | Hallucination::NoSpeech => None,
+            };
+            let mut silent = silent.into_iter();
+            result
+                .text_segments
+                .retain(|_| !silent.next().unwrap_or(false));
+        }
+    }
+}
+
+/// Looks for hallucinations in `text`, returning it with repetition loops collapsed and the kind
+/// of hallucination found
+pub fn detect_hallucination(text: &str) -> (String, Option<Hallucination>) {
+    let collapsed = collapse_repetitions(text);
+    let found = collapsed.as_ref().map(|_| Hallucination::Repetition);
+    let text = collapsed.unwrap_or_else(|| text.to_string());
+
+    if is_known_phrase(&text) {
+        return (text, Some(Hallucination::KnownPhrase));
+    }
+    (text, found)
+}
+
+/// `text` with runs of the same words repeated back to back kept once, None if there are none.
+/// Single words need 4 repeats, so "no no no" stays
+fn collapse_repetitions(text: &str) -> Option<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let keys: Vec<String> = words.iter().map(|word| normalize(word)).collect();
+    let mut kept = Vec::with_capacity(words.len());
+    let mut collapsed = false;
+
+    let mut i = 0;
+    while i < words.len() {
+        let mut looped = None;
+        for n in 1..=MAX_NGRAM {
+            let min_repeats = if n == 1 { 4 } else { 3 };
+            if i + n * min_repeats > words.len() {
+                break;
+            }
+            let mut repeats = 1;
+            while i + (repeats + 1) * n <= words.len()
+                && keys[i + repeats * n..i + (repeats + 1) * n] == keys[i..i + n]
+            {
+                repeats += 1;
+            }
+            if repeats >= min_repeats {
+                looped = Some((n, repeats));
+                break;
+            }
+        }
+
+        match looped {
+            Some((n, repeats)) => {
+                kept.extend_from_slice(&words[i..i + n]);
+                i += n * repeats;
+                collapsed = true;
+            }
+            None => {
+                kept.push(words[i]);
+                i += 1;
+            }
+        }
+    }
+
+    collapsed.then(|| kept.join(" "))
+}
+
+/// Whether `text` is made of known hallucinated phrases only
+fn is_known_phrase(text: &str) -> bool {
+    let mut rest = format!(" {} ", normalize(text));
+    for phrase in KNOWN_PHRASES {
+        let phrase = format!(" {} ", normalize(phrase));
+        while rest.contains(&phrase) {
+            rest = rest.replace(&phrase, " ");
+        }
+    }
+    rest.trim().is_empty()
+}
+
+/// Lowercase words without punctuation, separated by single spaces
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Share of `samples` (16khz) the VAD considers speech, in the frames `prepare_segments` uses
+pub async fn speech_ratio(samples: &[f32], vad_engine: &Mutex<Box<dyn VadEngine + Send>>) -> f32 {
+    vad_decisions(samples, vad_engine).await.speech_ratio()
+}
+
+/// What the VAD decides on each frame of `samples` (16khz), in the frames `prepare_segments` uses.
+/// Frames it fails on are unsure
+pub async fn vad_decisions(
+    samples: &[f32],
+    vad_engine: &Mutex<Box<dyn VadEngine + Send>>,
+) -> VadDecisions {
+    let mut vad_engine = vad_engine.lock().await;
+    let frames = samples
+        .chunks(1600)
+        .map(|chunk| match vad_engine.audio_type(chunk) {
+            Ok(VadStatus::Speech) => Some(true),
+            Ok(VadStatus::Silence) => Some(false),
+            _ => None,
+        })
+        .collect();
+    VadDecisions {
+        frame: Duration::from_millis(100),
+        frames,
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{AudioDevice, AudioInput, CaptureTiming};
+use anyhow::{anyhow, Result};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const INDEX: &str = "index.json";
+
+/// Chunks handed to the whisper channel and not transcribed yet, kept on disk so they can be
+/// transcribed after a crash: the samples of each chunk as zlib compressed 16 bit pcm in
+/// `<id>.pcm.z`, described by a small `index.json`
+pub struct AudioJournal {
+    dir: PathBuf,
+    /// Oldest first
+    entries: Vec<JournalEntry>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    id: u64,
+    device: AudioDevice,
+    sample_rate: u32,
+    channels: u16,
+    capture_timing: Option<CaptureTiming>,
+}
+
+impl AudioJournal {
+    /// Opens the journal in `dir`, with the chunks a previous run left untranscribed
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut entries: Vec<JournalEntry> = match fs::read(dir.join(INDEX)) {
+            Ok(index) => serde_json::from_slice(&index).unwrap_or_else(|e| {
+                warn!("ignoring corrupted audio journal index: {:?}", e);
+                Vec::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        entries.retain(|entry| chunk_path(&dir, entry.id).exists());
+
+        // chunks written right before a crash, without making it to the index
+        for file in fs::read_dir(&dir)? {
+            let path = file?.path();
+            let id = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".pcm.z"))
+                .and_then(|id| id.parse::<u64>().ok());
+            if id.is_some_and(|id| !entries.iter().any(|entry| entry.id == id)) {
+                let _ = fs::remove_file(&path);
+            }
+        }
+
+        let journal = Self { dir, entries };
+        journal.write_index()?;
+        if !journal.entries.is_empty() {
+            debug!(
+                "{} untranscribed chunks in {}",
+                journal.entries.len(),
+                journal.dir.display()
+            );
+        }
+        Ok(journal)
+    }
+
+    /// Ids of the chunks in the journal, oldest first
+    pub fn pending(&self) -> Vec<u64> {
+        self.entries.iter().map(|entry| entry.id).collect()
+    }
+
+    /// Keeps the chunk until it is `remove`d, returning its id
+    pub fn append(&mut self, audio: &AudioInput) -> Result<u64> {
+        let id = self.entries.last().map_or(0, |entry| entry.id + 1);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+        for &sample in audio.data.iter() {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            encoder.write_all(&sample.to_le_bytes())?;
+        }
+        fs::write(chunk_path(&self.dir, id), encoder.finish()?)?;
+
+        self.entries.push(JournalEntry {
+            id,
+            device: audio.device.as_ref().clone(),
+            sample_rate: audio.sample_rate,
+            channels: audio.channels,
+            capture_timing: audio.capture_timing.clone(),
+        });
+        self.write_index()?;
+        Ok(id)
+    }
+
+    pub fn read(&self, id: u64) -> Result<AudioInput> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| anyhow!("no chunk {} in the audio journal", id))?;
+        let mut bytes = Vec::new();
+        ZlibDecoder::new(fs::File::open(chunk_path(&self.dir, id))?).read_to_end(&mut bytes)?;
+        let data = bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32)
+            .collect();
+
+        Ok(AudioInput {
+            data: Arc::new(data),
+            sample_rate: entry.sample_rate,
+            channels: entry.channels,
+            device: Arc::new(entry.device.clone()),
+            capture_timing: entry.capture_timing.clone(),
+        })
+    }
+
+    pub fn remove(&mut self, ids: &[u64]) -> Result<()> {
+        let count = self.entries.len();
+        self.entries.retain(|entry| !ids.contains(&entry.id));
+        if self.entries.len() == count {
+            return Ok(());
+        }
+        self.write_index()?;
+        for &id in ids {
+            match fs::remove_file(chunk_path(&self.dir, id)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the index at once, a crash never leaves a partial one
+    fn write_index(&self) -> Result<()> {
+        let tmp = self.dir.join(format!("{}.tmp", INDEX));
+        fs::write(&tmp, serde_json::to_vec(&self.entries)?)?;
+        fs::rename(&tmp, self.dir.join(INDEX))?;
+        Ok(())
+    }
+}
+
+fn chunk_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{:020}.pcm.z", id))
+}
@@ -0,0 +1,203 @@
+use crate::stt::TranscriptionResult;
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use screenpipe_events::send_event;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A configured phrase heard in a transcript, sent as a `keyword_detected` event and to the
+/// callbacks of the `KeywordSpotter`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeywordMatch {
+    /// The phrase as configured
+    pub phrase: String,
+    pub device: String,
+    /// The transcript the phrase was heard in
+    pub transcription: String,
+    /// When the transcribed audio was captured
+    pub timestamp: DateTime<Utc>,
+    /// False for partial results of live transcription, whose text may still change
+    pub is_final: bool,
+}
+
+type Callback = Arc<dyn Fn(&KeywordMatch) + Send + Sync>;
+
+struct Inner {
+    /// Each phrase with its words, normalized like transcripts are
+    phrases: Vec<(String, Vec<String>)>,
+    cooldown: Duration,
+    /// Capture time of the last match of each phrase (by index) on each device
+    last_matched: Mutex<HashMap<(usize, String), DateTime<Utc>>>,
+    callbacks: Mutex<Vec<Callback>>,
+}
+
+/// Spots configured phrases ("action item", a person's name) in transcripts as they come out of
+/// the whisper channel and live transcription, without waiting for them to be stored and
+/// searchable. Phrases match whole words regardless of case and punctuation.
+///
+/// Partial and final results of the same speech, and the whisper channel transcribing what live
+/// transcription already did, all contain the phrase: a phrase heard again on a device within
+/// `cooldown` of capture time of its last match doesn't match again. Clones share their callbacks
+/// and cooldowns
+#[derive(Clone)]
+pub struct KeywordSpotter {
+    inner: Arc<Inner>,
+}
+
+impl KeywordSpotter {
+    pub fn new(phrases: impl IntoIterator<Item = impl Into<String>>, cooldown: Duration) -> Self {
+        let phrases = phrases
+            .into_iter()
+            .map(Into::into)
+            .filter_map(|phrase: String| {
+                let words = normalized_words(&phrase);
+                if words.is_empty() {
+                    warn!("ignoring keyword trigger without words: {:?}", phrase);
+                    return None;
+                }
+                Some((phrase, words))
+            })
+            .collect();
+        Self {
+            inner: Arc::new(Inner {
+                phrases,
+                cooldown,
+                last_matched: Mutex::new(HashMap::new()),
+                callbacks: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub fn phrases(&self) -> impl Iterator<Item = &str> {
+        self.inner.phrases.iter().map(|(phrase, _)| phrase.as_str())
+    }
+
+    /// Calls `callback` with every match from now on, on the thread that transcribed it, so it
+    /// should hand off anything slow
+    pub fn on_match(&self, callback: impl Fn(&KeywordMatch) + Send + Sync + 'static) {
+        if let Ok(mut callbacks) = self.inner.callbacks.lock() {
+            callbacks.push(Arc::new(callback));
+        }
+    }
+
+    /// Spots the phrases in the transcription of `result`, see `spot`
+    pub fn spot_result(&self, result: &TranscriptionResult) -> Vec<KeywordMatch> {
+        match result.transcription.as_deref() {
+            Some(text) => self.spot(
+                text,
+                &result.input.device.to_string(),
+                result.captured_at.map(Into::into).unwrap_or_else(Utc::now),
+                result.is_final,
+            ),
+            None => Vec::new(),
+        }
+    }
+
+    /// Phrases in `text` that weren't matched on `device` within the cooldown, after sending them
+    /// as `keyword_detected` events and to the callbacks
+    pub fn spot(
+        &self,
+        text: &str,
+        device: &str,
+        timestamp: DateTime<Utc>,
+        is_final: bool,
+    ) -> Vec<KeywordMatch> {
+        let words = normalized_words(text);
+        if words.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches = Vec::new();
+        {
+            let Ok(mut last_matched) = self.inner.last_matched.lock() else {
+                return Vec::new();
+            };
+            for (i, (phrase, phrase_words)) in self.inner.phrases.iter().enumerate() {
+                if !words
+                    .windows(phrase_words.len())
+                    .any(|window| window == phrase_words.as_slice())
+                {
+                    continue;
+                }
+                // suppressed matches extend the cooldown, a phrase growing in partial results
+                // only matches once
+                let previous = last_matched.insert((i, device.to_string()), timestamp);
+                let within_cooldown = |previous: DateTime<Utc>| {
+                    (timestamp - previous).num_milliseconds().unsigned_abs()
+                        < self.inner.cooldown.as_millis() as u64
+                };
+                if previous.is_some_and(within_cooldown) {
+                    continue;
+                }
+                matches.push(KeywordMatch {
+                    phrase: phrase.clone(),
+                    device: device.to_string(),
+                    transcription: text.trim().to_string(),
+                    timestamp,
+                    is_final,
+                });
+            }
+        }
+        if matches.is_empty() {
+            return matches;
+        }
+
+        // callbacks are called without holding the lock, they may register others
+        let callbacks: Vec<Callback> = self
+            .inner
+            .callbacks
+            .lock()
+            .map(|callbacks| callbacks.clone())
+            .unwrap_or_default();
+        for keyword_match in &matches {
+            debug!(
+                "heard {:?} on {}",
+                keyword_match.phrase, keyword_match.device
+            );
+            let _ = send_event("keyword_detected", keyword_match.clone());
+            for callback in &callbacks {
+                callback(keyword_match);
+            }
+        }
+        matches
+    }
+}
+
+impl fmt::Debug for KeywordSpotter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeywordSpotter")
+            .field("phrases", &self.phrases().collect::<Vec<_>>())
+            .field("cooldown", &self.inner.cooldown)
+            .finish()
+    }
+}
+
+/// The same spotter, clones included
+impl PartialEq for KeywordSpotter {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+/// Lowercase words without punctuation, apostrophes within words kept ("don't")
+fn normalized_words(text: &str) -> Vec<String> {
+    text.chars()
+        .map(|c| {
+            if c == '’' {
+                '\''
+            } else if c.is_alphanumeric() || c == '\'' {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .map(|word| word.trim_matches('\'').to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
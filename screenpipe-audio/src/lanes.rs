@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Priority of audio waiting for transcription
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lane {
+    /// Audio being recorded right now
+    Realtime,
+    /// Audio catching up: recovered after a crash or imported from files
+    Backfill,
+}
+
+/// How long realtime audio may hold back the backfill
+#[derive(Clone, Debug, PartialEq)]
+pub struct LaneLimits {
+    /// Realtime batches in a row after which a backfill batch gets its turn
+    pub max_realtime_streak: u32,
+    /// Wait after which backfill goes first whatever the streak
+    pub max_backfill_wait: Duration,
+    /// Items the lanes hold at most, the rest is left to wait upstream where it blocks the
+    /// producers instead of piling up in memory
+    pub max_queued: usize,
+}
+
+impl Default for LaneLimits {
+    fn default() -> Self {
+        Self {
+            max_realtime_streak: 4,
+            max_backfill_wait: Duration::from_secs(120),
+            max_queued: 64,
+        }
+    }
+}
+
+/// Two lanes of items, realtime ones go first unless the backfill has been waiting past the
+/// `LaneLimits`
+pub struct PriorityLanes<T> {
+    realtime: VecDeque<T>,
+    backfill: VecDeque<(Instant, T)>,
+    /// Realtime batches taken in a row while backfill was waiting
+    streak: u32,
+    limits: LaneLimits,
+}
+
+impl<T> PriorityLanes<T> {
+    pub fn new(limits: LaneLimits) -> Self {
+        Self {
+            realtime: VecDeque::new(),
+            backfill: VecDeque::new(),
+            streak: 0,
+            limits,
+        }
+    }
+
+    pub fn push(&mut self, lane: Lane, item: T) {
+        match lane {
+            Lane::Realtime => self.realtime.push_back(item),
+            Lane::Backfill => self.backfill.push_back((Instant::now(), item)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.realtime.len() + self.backfill.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.realtime.is_empty() && self.backfill.is_empty()
+    }
+
+    /// Whether the lanes hold `LaneLimits::max_queued` items, nothing more should be pushed
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.limits.max_queued
+    }
+
+    /// Up to `max` of the oldest items of the lane whose turn it is
+    pub fn next_batch(&mut self, max: usize) -> Vec<T> {
+        let backfill_due = self.backfill.front().is_some_and(|(queued_at, _)| {
+            self.realtime.is_empty()
+                || self.streak >= self.limits.max_realtime_streak
+                || queued_at.elapsed() >= self.limits.max_backfill_wait
+        });
+        if backfill_due {
+            self.streak = 0;
+            let count = max.min(self.backfill.len());
+            return self.backfill.drain(..count).map(|(_, item)| item).collect();
+        }
+
+        if !self.backfill.is_empty() {
+            self.streak += 1;
+        }
+        let count = max.min(self.realtime.len());
+        self.realtime.drain(..count).collect()
+    }
+}
@@ -1,24 +1,58 @@
+pub mod alignment;
+pub mod archive;
+pub mod assemblyai;
 pub mod audio_processing;
+pub mod audio_tagging;
+pub mod azure;
+pub mod cloud_retry;
 mod core;
 pub mod deepgram;
+pub mod disk_queue;
 pub mod encode;
+pub mod engine_health;
+pub mod fingerprint;
+pub mod hallucination;
+pub mod journal;
+pub mod keyword_spotting;
+pub mod lanes;
+pub use screenpipe_core::models;
 mod multilingual;
+pub mod noise_gate;
 pub mod pcm_decode;
 pub mod pyannote;
 mod segments;
+pub mod speech_gate;
+pub mod speech_stats;
+pub mod stitching;
 pub mod stt;
+pub mod subtitles;
 mod tokenizer;
 pub mod vad_engine;
 pub mod whisper;
-pub use audio_processing::resample;
+pub mod whisper_api;
+pub use audio_processing::{resample, resample_with, ResampleQuality};
+pub use cloud_retry::{OfflineQueueConfig, RetryPolicy};
 pub use core::{
-    default_input_device, default_output_device, get_device_and_config, list_audio_devices,
-    parse_audio_device, record_and_transcribe, start_realtime_recording, trigger_audio_permission,
-    AudioDevice, AudioStream, AudioTranscriptionEngine, DeviceControl, DeviceType,
+    default_input_device, default_output_device, get_audio_host, get_device_and_config,
+    list_audio_devices, list_audio_hosts, list_host_audio_devices, live_audio_stream,
+    live_audio_streams, parse_audio_device, record_and_transcribe,
+    record_and_transcribe_with_options, start_realtime_recording, trigger_audio_permission,
+    AudioBlock, AudioDevice, AudioHost, AudioStream, AudioTranscriptionEngine, CaptureTiming,
+    DeviceControl, DeviceType, RecordingOptions, StreamOptions, FILE_AUDIO_HOST,
     LAST_AUDIO_CAPTURE,
 };
 pub mod realtime;
-pub use encode::encode_single_audio;
-pub use pcm_decode::pcm_decode;
-pub use stt::{create_whisper_channel, stt, AudioInput, TranscriptionResult};
+pub use encode::{
+    encode_audio_to_bytes, encode_single_audio, AudioCodec, AudioTags, EncodedAudio,
+};
+pub use hallucination::{Hallucination, HallucinationFilter};
+pub use keyword_spotting::{KeywordMatch, KeywordSpotter};
+pub use multilingual::{LanguageFilter, LanguageHint};
+pub use pcm_decode::{pcm_decode, pcm_stream, PcmStream};
+pub use speech_gate::{SpeechGate, VadGating};
+pub use stt::{
+    create_whisper_channel, stream_transcription, stt, transcribe, AudioInput, CloudApiKeys,
+    DeviceTranscriptionOptions, StreamingConfig, TranscriptionOptions, TranscriptionResult,
+};
 pub use vad_engine::VadEngineEnum;
+pub use whisper::{DetectedLanguage, SegmentConfidence, Task, TextSegment, Transcript};
@@ -1,4 +1,4 @@
-use crate::whisper::{token_id, Model};
+use crate::whisper::{token_id, DetectedLanguage, Model};
 use candle::IndexOp;
 use candle::{Result, Tensor, D};
 use candle_transformers::models::whisper::SOT_TOKEN;
@@ -108,13 +108,94 @@ pub const LANGUAGES: [(&str, &str); 99] = [
     ("su", "sundanese"),
 ];
 
-/// Returns the token id for the selected language.
+/// Which languages whisper may transcribe in
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum LanguageHint {
+    /// Detect among every language the model knows
+    #[default]
+    Auto,
+    /// Decode in this language without detecting, detection is unreliable on short chunks
+    Force(Language),
+    /// Detect among these languages only, e.g. the ones spoken in a bilingual household
+    Restrict(Vec<Language>),
+}
+
+impl LanguageHint {
+    /// Hint for the languages configured by the user: none detects any language, a single one
+    /// is forced and several restrict detection to them
+    pub fn from_languages(languages: &[Language]) -> Self {
+        match languages {
+            [] => Self::Auto,
+            [language] => Self::Force(language.clone()),
+            languages => Self::Restrict(languages.to_vec()),
+        }
+    }
+
+    /// Languages detection may pick from, empty for any
+    pub fn candidates(&self) -> Vec<Language> {
+        match self {
+            Self::Auto => Vec::new(),
+            Self::Force(language) => vec![language.clone()],
+            Self::Restrict(languages) => languages.clone(),
+        }
+    }
+}
+
+/// Probability from which a segment identified as spoken in another language is dropped
+const MIN_FILTER_PROBABILITY: f32 = 0.5;
+
+/// Languages to transcribe, segments identified as spoken in another one are dropped before
+/// being decoded, e.g. to not transcribe the TV in the background
+#[derive(Clone, Debug, PartialEq)]
+pub struct LanguageFilter {
+    pub languages: Vec<Language>,
+    /// Segments are only dropped when their language was identified with at least this
+    /// probability, the ones whisper is unsure about are transcribed
+    pub min_probability: f32,
+}
+
+impl LanguageFilter {
+    pub fn new(languages: Vec<Language>) -> Self {
+        Self {
+            languages,
+            min_probability: MIN_FILTER_PROBABILITY,
+        }
+    }
+
+    pub fn allows(&self, language: &DetectedLanguage) -> bool {
+        language.probability < self.min_probability
+            || self
+                .languages
+                .iter()
+                .any(|allowed| allowed.as_lang_code() == language.code)
+    }
+}
+
+/// Token id of the language to decode in, the forced one or the most likely allowed by `hint`
+pub fn language_token(
+    model: &mut Model,
+    tokenizer: &Tokenizer,
+    mel: &Tensor,
+    hint: &LanguageHint,
+) -> Result<(u32, DetectedLanguage)> {
+    match hint {
+        LanguageHint::Force(language) => {
+            let code = language.as_lang_code();
+            debug!("forced language: {:?}", code);
+            let token = token_id(tokenizer, &format!("<|{}|>", code))?;
+            Ok((token, DetectedLanguage::forced(code)))
+        }
+        hint => detect_language(model, tokenizer, mel, hint.candidates()),
+    }
+}
+
+/// Returns the token id for the selected language, with its probability among `languages`.
 pub fn detect_language(
     model: &mut Model,
     tokenizer: &Tokenizer,
     mel: &Tensor,
     languages: Vec<Language>,
-) -> Result<u32> {
+) -> Result<(u32, DetectedLanguage)> {
     let (_bsize, _, seq_len) = mel.dims3()?;
     let mel = mel.narrow(
         2,
@@ -157,7 +238,14 @@ pub fn detect_language(
 
     probabilities.sort_by(|(_, p1), (_, p2)| p2.total_cmp(p1));
 
-    let language = token_id(tokenizer, &format!("<|{}|>", probabilities[0].0))?;
-    debug!("detected language: {:?}", probabilities[0].0);
-    Ok(language)
+    let (code, probability) = probabilities[0];
+    let language = token_id(tokenizer, &format!("<|{}|>", code))?;
+    debug!("detected language: {:?} ({:.2})", code, probability);
+    Ok((
+        language,
+        DetectedLanguage {
+            code: code.to_string(),
+            probability: *probability,
+        },
+    ))
 }
@@ -0,0 +1,174 @@
+use log::debug;
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+use std::collections::HashMap;
+
+// frames of 32ms every 16ms at 16khz
+const FRAME: usize = 512;
+const HOP: usize = FRAME / 2;
+const BINS: usize = FRAME / 2 + 1;
+
+/// Share of the frames of a chunk, the quietest, the noise profile is learned from
+const QUIET_SHARE: f32 = 0.2;
+
+/// How steady noise, a fan or the air conditioning, is taken out of each chunk before it is
+/// transcribed. Much lighter than a neural denoiser, and as good on noise that doesn't change
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseGate {
+    /// Most a frequency is attenuated by, in db. Higher takes out more noise and makes speech
+    /// sound more hollow
+    pub reduction_db: f32,
+    /// How many times the noise profile is subtracted, over 1 for the noise louder than average
+    pub over_subtraction: f32,
+    /// How much louder than the noise profile quiet frames may be and still be learned from as
+    /// silence, in db
+    pub silence_margin_db: f32,
+    /// Weight of the quiet frames of each chunk in the noise profile
+    pub adaptation: f32,
+}
+
+impl Default for NoiseGate {
+    fn default() -> Self {
+        Self {
+            reduction_db: 12.0,
+            over_subtraction: 1.5,
+            silence_margin_db: 6.0,
+            adaptation: 0.2,
+        }
+    }
+}
+
+/// The noise gate of each device, with the noise profile it learned from the device's silences
+pub struct NoiseGates {
+    config: NoiseGate,
+    /// Noise power in each frequency bin of a frame
+    profiles: HashMap<String, Vec<f32>>,
+}
+
+impl NoiseGates {
+    pub fn new(config: NoiseGate) -> Self {
+        Self {
+            config,
+            profiles: HashMap::new(),
+        }
+    }
+
+    /// Learns the noise of `device` from the quietest frames of mono `audio`, then subtracts it
+    /// from every frame. Audio shorter than a frame is returned as is
+    pub fn process(&mut self, device: &str, audio: &[f32]) -> Vec<f32> {
+        if audio.len() < FRAME {
+            return audio.to_vec();
+        }
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(FRAME);
+        let inverse = planner.plan_fft_inverse(FRAME);
+        // square root of a periodic hann window for analysis and synthesis, their product at 50%
+        // overlap sums to one
+        let window: Vec<f32> = (0..FRAME)
+            .map(|i| {
+                (0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FRAME as f32).cos()).sqrt()
+            })
+            .collect();
+
+        // a frame of padding on each side, so every sample is in two frames
+        let padded_len = (audio.len() + 2 * HOP).div_ceil(HOP) * HOP;
+        let mut padded = vec![0.0; padded_len];
+        padded[HOP..HOP + audio.len()].copy_from_slice(audio);
+        let frames = (padded_len - FRAME) / HOP + 1;
+
+        let mut input = forward.make_input_vec();
+        let spectra: Vec<Vec<Complex<f32>>> = (0..frames)
+            .map(|frame| {
+                let samples = &padded[frame * HOP..frame * HOP + FRAME];
+                for ((x, &s), &w) in input.iter_mut().zip(samples).zip(&window) {
+                    *x = s * w;
+                }
+                let mut spectrum = forward.make_output_vec();
+                let _ = forward.process(&mut input, &mut spectrum);
+                spectrum
+            })
+            .collect();
+        let powers: Vec<Vec<f32>> = spectra
+            .iter()
+            .map(|spectrum| spectrum.iter().map(|bin| bin.norm_sqr()).collect())
+            .collect();
+
+        self.learn(device, &powers);
+        let Some(noise) = self.profiles.get(device) else {
+            return audio.to_vec();
+        };
+
+        let floor = 10f32.powf(-self.config.reduction_db / 10.0);
+        let mut output = vec![0.0; padded_len];
+        let mut frame_out = inverse.make_output_vec();
+        for (frame, (mut spectrum, power)) in spectra.into_iter().zip(&powers).enumerate() {
+            for ((bin, &power), &noise) in spectrum.iter_mut().zip(power).zip(noise) {
+                let gain = (1.0 - self.config.over_subtraction * noise / power.max(1e-12))
+                    .max(floor)
+                    .sqrt();
+                *bin *= gain;
+            }
+            // the inverse transform of a real signal has no imaginary dc and nyquist
+            spectrum[0].im = 0.0;
+            spectrum[BINS - 1].im = 0.0;
+            let _ = inverse.process(&mut spectrum, &mut frame_out);
+            for ((out, &x), &w) in output[frame * HOP..frame * HOP + FRAME]
+                .iter_mut()
+                .zip(&frame_out)
+                .zip(&window)
+            {
+                *out += x * w / FRAME as f32;
+            }
+        }
+        output.truncate(HOP + audio.len());
+        output.drain(..HOP);
+        output
+    }
+
+    /// Blends the quietest frames that sound like the noise learned so far into the profile of
+    /// `device`. Quiet frames louder than it, a chunk of nonstop speech or noise that got louder,
+    /// are learned from ten times slower
+    fn learn(&mut self, device: &str, powers: &[Vec<f32>]) {
+        // the first and last frames are half padding
+        let inner = &powers[1..powers.len() - 1];
+        if inner.is_empty() {
+            return;
+        }
+        let energies: Vec<f32> = inner.iter().map(|power| power.iter().sum()).collect();
+        let mut sorted = energies.clone();
+        sorted.sort_by(f32::total_cmp);
+        let quiet = sorted[((sorted.len() as f32 * QUIET_SHARE) as usize).min(sorted.len() - 1)];
+
+        let mut mean = vec![0.0; BINS];
+        let mut count = 0;
+        for (power, &energy) in inner.iter().zip(&energies) {
+            if energy <= quiet {
+                for (m, &p) in mean.iter_mut().zip(power) {
+                    *m += p;
+                }
+                count += 1;
+            }
+        }
+        for m in mean.iter_mut() {
+            *m /= count as f32;
+        }
+
+        match self.profiles.get_mut(device) {
+            Some(profile) => {
+                let margin = 10f32.powf(self.config.silence_margin_db / 10.0);
+                let rate = if mean.iter().sum::<f32>() <= profile.iter().sum::<f32>() * margin {
+                    self.config.adaptation
+                } else {
+                    self.config.adaptation / 10.0
+                };
+                for (p, m) in profile.iter_mut().zip(&mean) {
+                    *p += (m - *p) * rate;
+                }
+            }
+            None => {
+                debug!("device: {}, learned its noise profile", device);
+                self.profiles.insert(device.to_string(), mean);
+            }
+        }
+    }
+}
@@ -1,7 +1,21 @@
+use anyhow::{anyhow, Context};
 use log::debug;
+use screenpipe_core::find_ffmpeg_path;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::time::Duration;
 use symphonia::core::audio::{AudioBufferRef, Signal};
-use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
 use symphonia::core::conv::FromSample;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatReader;
+
+/// Rate audio decoded by ffmpeg is returned at
+const FFMPEG_SAMPLE_RATE: u32 = 16000;
+
+/// Chunks `pcm_decode` collects the stream in
+const DECODE_CHUNK: Duration = Duration::from_secs(60);
 
 fn conv<T>(samples: &mut Vec<f32>, data: std::borrow::Cow<symphonia::core::audio::AudioBuffer<T>>)
 where
@@ -11,68 +25,295 @@ where
     samples.extend(data.chan(0).iter().map(|v| f32::from_sample(*v)))
 }
 
-pub fn pcm_decode<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<(Vec<f32>, u32)> {
-    debug!("Starting PCM decoding for {:?}", path.as_ref());
+/// Samples of the first channel of the first audio track of a file, with their sample rate.
+///
+/// Wav, flac, mp3, ogg (vorbis), webm/mkv and mp4/m4a (aac, alac) are decoded in process. Codecs
+/// symphonia has no decoder for, opus in meeting recordings from webm or ogg mostly, are decoded
+/// by ffmpeg, mixed down to mono at 16khz. Long recordings are better read with `pcm_stream`
+pub fn pcm_decode<P: AsRef<Path>>(path: P) -> anyhow::Result<(Vec<f32>, u32)> {
+    let mut stream = pcm_stream(path, DECODE_CHUNK)?;
+    let mut pcm_data = Vec::new();
+    for chunk in &mut stream {
+        pcm_data.extend(chunk?);
+    }
+    Ok((pcm_data, stream.sample_rate()))
+}
+
+/// Decodes a file like `pcm_decode` a chunk of `chunk_duration` at a time, the last one shorter,
+/// so hours of recording never have to fit in memory at once
+pub fn pcm_stream<P: AsRef<Path>>(path: P, chunk_duration: Duration) -> anyhow::Result<PcmStream> {
+    let path = path.as_ref();
+    debug!("Starting PCM decoding for {:?}", path);
+    let (source, sample_rate) = open_source(path)?;
+    if sample_rate == 0 {
+        return Err(anyhow!("unknown sample rate of {}", path.display()));
+    }
+    Ok(PcmStream {
+        path: path.to_path_buf(),
+        source,
+        sample_rate,
+        chunk_len: ((chunk_duration.as_secs_f64() * sample_rate as f64) as usize).max(1),
+        pending: Vec::new(),
+        done: false,
+    })
+}
+
+/// Chunks of samples of a file being decoded, see `pcm_stream`
+pub struct PcmStream {
+    path: PathBuf,
+    source: Source,
+    sample_rate: u32,
+    chunk_len: usize,
+    /// Decoded samples not returned yet
+    pending: Vec<f32>,
+    done: bool,
+}
+
+enum Source {
+    Symphonia {
+        format: Box<dyn FormatReader>,
+        decoder: Box<dyn Decoder>,
+        track_id: u32,
+    },
+    /// f32le mono samples read from ffmpeg's stdout
+    Ffmpeg {
+        child: Child,
+        stdout: ChildStdout,
+        reason: String,
+    },
+}
+
+impl PcmStream {
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Decodes more samples into `pending`, false once the file is decoded
+    fn fill(&mut self) -> anyhow::Result<bool> {
+        match &mut self.source {
+            Source::Symphonia {
+                format,
+                decoder,
+                track_id,
+            } => loop {
+                let Ok(packet) = format.next_packet() else {
+                    return Ok(false);
+                };
+                // Consume any new metadata that has been read since the last packet.
+                while !format.metadata().is_latest() {
+                    format.metadata().pop();
+                }
+
+                // If the packet does not belong to the selected track, skip over it.
+                if packet.track_id() != *track_id {
+                    continue;
+                }
+                let decoded = match decoder.decode(&packet) {
+                    Ok(decoded) => decoded,
+                    // a corrupt packet is skipped, the rest of the file is still worth transcribing
+                    Err(SymphoniaError::DecodeError(e)) => {
+                        debug!("skipping undecodable packet: {}", e);
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                let pcm_data = &mut self.pending;
+                match decoded {
+                    AudioBufferRef::F32(buf) => pcm_data.extend(buf.chan(0)),
+                    AudioBufferRef::U8(data) => conv(pcm_data, data),
+                    AudioBufferRef::U16(data) => conv(pcm_data, data),
+                    AudioBufferRef::U24(data) => conv(pcm_data, data),
+                    AudioBufferRef::U32(data) => conv(pcm_data, data),
+                    AudioBufferRef::S8(data) => conv(pcm_data, data),
+                    AudioBufferRef::S16(data) => conv(pcm_data, data),
+                    AudioBufferRef::S24(data) => conv(pcm_data, data),
+                    AudioBufferRef::S32(data) => conv(pcm_data, data),
+                    AudioBufferRef::F64(data) => conv(pcm_data, data),
+                }
+                return Ok(true);
+            },
+            Source::Ffmpeg {
+                child,
+                stdout,
+                reason,
+            } => {
+                let mut bytes = vec![0u8; self.chunk_len * 4];
+                let mut read = 0;
+                while read < bytes.len() {
+                    match stdout.read(&mut bytes[read..])? {
+                        0 => break,
+                        n => read += n,
+                    }
+                }
+                self.pending.extend(
+                    bytes[..read - read % 4]
+                        .chunks_exact(4)
+                        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                );
+                if read == bytes.len() {
+                    return Ok(true);
+                }
+
+                let status = child.wait()?;
+                if !status.success() {
+                    let mut stderr = String::new();
+                    if let Some(mut pipe) = child.stderr.take() {
+                        let _ = pipe.read_to_string(&mut stderr);
+                    }
+                    return Err(anyhow!(
+                        "can't decode {}: {} and ffmpeg failed: {}",
+                        self.path.display(),
+                        reason,
+                        stderr.trim()
+                    ));
+                }
+                Ok(false)
+            }
+        }
+    }
+}
+
+impl Iterator for PcmStream {
+    type Item = anyhow::Result<Vec<f32>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done && self.pending.len() < self.chunk_len {
+            match self.fill() {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        if self.pending.is_empty() {
+            return None;
+        }
+        let rest = self
+            .pending
+            .split_off(self.chunk_len.min(self.pending.len()));
+        Some(Ok(std::mem::replace(&mut self.pending, rest)))
+    }
+}
+
+impl Drop for PcmStream {
+    fn drop(&mut self) {
+        // a stream dropped halfway doesn't leave ffmpeg running
+        if let Source::Ffmpeg { child, .. } = &mut self.source {
+            if !self.done {
+                let _ = child.kill();
+            }
+            let _ = child.wait();
+        }
+    }
+}
+
+/// The decoder of the first audio track of a file, with its sample rate
+fn open_source(path: &Path) -> anyhow::Result<(Source, u32)> {
     // Open the media source.
-    let src = std::fs::File::open(path)?;
+    let src =
+        std::fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
 
     // Create the media source stream.
     let mss = symphonia::core::io::MediaSourceStream::new(Box::new(src), Default::default());
 
-    // Create a probe hint using the file's extension. [Optional]
-    let hint = symphonia::core::probe::Hint::new();
-    // hint.with_extension("mp4");
+    // Create a probe hint using the file's extension.
+    let mut hint = symphonia::core::probe::Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
 
     // Use the default options for metadata and format readers.
     let meta_opts: symphonia::core::meta::MetadataOptions = Default::default();
     let fmt_opts: symphonia::core::formats::FormatOptions = Default::default();
 
     // Probe the media source.
-    let probed = symphonia::default::get_probe().format(&hint, mss, &fmt_opts, &meta_opts)?;
+    let probed = match symphonia::default::get_probe().format(&hint, mss, &fmt_opts, &meta_opts) {
+        Ok(probed) => probed,
+        Err(SymphoniaError::Unsupported(_)) => {
+            debug!("unsupported container, decoding {:?} with ffmpeg", path);
+            return ffmpeg_source(path, "its container isn't supported");
+        }
+        Err(e) => return Err(anyhow!("failed to read {}: {}", path.display(), e)),
+    };
     // Get the instantiated format reader.
-    let mut format = probed.format;
+    let format = probed.format;
 
     // Find the first audio track with a known (decodeable) codec.
     let track = format
         .tracks()
         .iter()
         .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-        .ok_or_else(|| anyhow::anyhow!("no supported audio tracks"))?;
+        .ok_or_else(|| anyhow!("no audio track found in {}", path.display()))?;
 
     // Use the default options for the decoder.
     let dec_opts: DecoderOptions = Default::default();
 
     // Create a decoder for the track.
-    let mut decoder = symphonia::default::get_codecs()
-        .make(&track.codec_params, &dec_opts)
-        .expect("unsupported codec");
+    let decoder = match symphonia::default::get_codecs().make(&track.codec_params, &dec_opts) {
+        Ok(decoder) => decoder,
+        Err(SymphoniaError::Unsupported(_)) => {
+            let codec = symphonia::default::get_codecs()
+                .get_codec(track.codec_params.codec)
+                .map_or_else(
+                    || format!("{}", track.codec_params.codec),
+                    |codec| codec.short_name.to_string(),
+                );
+            debug!(
+                "unsupported codec {}, decoding {:?} with ffmpeg",
+                codec, path
+            );
+            return ffmpeg_source(path, &format!("its {} codec isn't supported", codec));
+        }
+        Err(e) => return Err(anyhow!("failed to decode {}: {}", path.display(), e)),
+    };
     let track_id = track.id;
     let sample_rate = track.codec_params.sample_rate.unwrap_or(0);
-    let mut pcm_data = Vec::new();
-    debug!("Starting decode loop");
-    // The decode loop.
-    while let Ok(packet) = format.next_packet() {
-        // Consume any new metadata that has been read since the last packet.
-        while !format.metadata().is_latest() {
-            format.metadata().pop();
-        }
+    Ok((
+        Source::Symphonia {
+            format,
+            decoder,
+            track_id,
+        },
+        sample_rate,
+    ))
+}
 
-        // If the packet does not belong to the selected track, skip over it.
-        if packet.track_id() != track_id {
-            continue;
-        }
-        match decoder.decode(&packet)? {
-            AudioBufferRef::F32(buf) => pcm_data.extend(buf.chan(0)),
-            AudioBufferRef::U8(data) => conv(&mut pcm_data, data),
-            AudioBufferRef::U16(data) => conv(&mut pcm_data, data),
-            AudioBufferRef::U24(data) => conv(&mut pcm_data, data),
-            AudioBufferRef::U32(data) => conv(&mut pcm_data, data),
-            AudioBufferRef::S8(data) => conv(&mut pcm_data, data),
-            AudioBufferRef::S16(data) => conv(&mut pcm_data, data),
-            AudioBufferRef::S24(data) => conv(&mut pcm_data, data),
-            AudioBufferRef::S32(data) => conv(&mut pcm_data, data),
-            AudioBufferRef::F64(data) => conv(&mut pcm_data, data),
-        }
-    }
-    Ok((pcm_data, sample_rate))
+/// Decodes the first audio track of a file symphonia can't with ffmpeg, `reason` saying why for
+/// the error when ffmpeg isn't there or can't either
+fn ffmpeg_source(path: &Path, reason: &str) -> anyhow::Result<(Source, u32)> {
+    let ffmpeg = find_ffmpeg_path().ok_or_else(|| {
+        anyhow!(
+            "can't decode {}: {} and ffmpeg wasn't found",
+            path.display(),
+            reason
+        )
+    })?;
+    let mut child = Command::new(ffmpeg)
+        .args(["-v", "error", "-i"])
+        .arg(path)
+        .args([
+            "-vn",
+            "-ac",
+            "1",
+            "-ar",
+            &FFMPEG_SAMPLE_RATE.to_string(),
+            "-f",
+            "f32le",
+            "pipe:1",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().context("ffmpeg stdout not captured")?;
+    Ok((
+        Source::Ffmpeg {
+            child,
+            stdout,
+            reason: reason.to_string(),
+        },
+        FFMPEG_SAMPLE_RATE,
+    ))
 }
@@ -0,0 +1,59 @@
+use super::embedding::EmbeddingExtractor;
+use crate::resample;
+use anyhow::{bail, Result};
+
+const SAMPLE_RATE: u32 = 16000;
+/// Shorter samples don't give a stable voice embedding
+const MIN_ENROLLMENT_SECS: usize = 3;
+/// Window the enrollment sample is split in, embeddings of the windows are averaged
+const WINDOW_SECS: usize = 5;
+
+/// Voice embedding of an enrollment sample, comparable to the embeddings of diarized segments.
+///
+/// The sample should be a few seconds of the speaker alone, longer samples are split in windows
+/// whose embeddings are averaged, which evens out pauses and intonation.
+pub fn enrollment_embedding(
+    samples: &[f32],
+    sample_rate: u32,
+    extractor: &mut EmbeddingExtractor,
+) -> Result<Vec<f32>> {
+    let samples = if sample_rate != SAMPLE_RATE {
+        resample(samples, sample_rate, SAMPLE_RATE)?
+    } else {
+        samples.to_vec()
+    };
+
+    let min_samples = MIN_ENROLLMENT_SECS * SAMPLE_RATE as usize;
+    if samples.len() < min_samples {
+        bail!(
+            "enrollment sample is {:.1}s long, at least {}s of speech are needed",
+            samples.len() as f32 / SAMPLE_RATE as f32,
+            MIN_ENROLLMENT_SECS
+        );
+    }
+
+    let mut sum: Vec<f32> = Vec::new();
+    let mut windows = 0;
+    for window in samples.chunks(WINDOW_SECS * SAMPLE_RATE as usize) {
+        // a short tail would skew the average more than it adds
+        if window.len() < min_samples && windows > 0 {
+            break;
+        }
+        let embedding: Vec<f32> = extractor.compute(window)?.collect();
+        if sum.is_empty() {
+            sum = vec![0.0; embedding.len()];
+        }
+        let norm = embedding
+            .iter()
+            .map(|v| v * v)
+            .sum::<f32>()
+            .sqrt()
+            .max(f32::EPSILON);
+        for (total, value) in sum.iter_mut().zip(embedding) {
+            *total += value / norm;
+        }
+        windows += 1;
+    }
+
+    Ok(sum.into_iter().map(|v| v / windows as f32).collect())
+}
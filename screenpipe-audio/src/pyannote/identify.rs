@@ -1,20 +1,31 @@
 use anyhow::{bail, Result};
 use ndarray::Array1;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
+/// Speaker clusters of a transcription session. Clones share the same clusters, so the speaker
+/// ids stay consistent across the chunks of a recording
 #[derive(Debug, Clone)]
 pub struct EmbeddingManager {
+    inner: Arc<Mutex<Speakers>>,
+}
+
+#[derive(Debug)]
+struct Speakers {
     max_speakers: usize,
-    speakers: HashMap<usize, Array1<f32>>,
+    /// Centroid of each speaker and the number of embeddings averaged into it
+    speakers: HashMap<usize, (Array1<f32>, usize)>,
     next_speaker_id: usize,
 }
 
 impl EmbeddingManager {
     pub fn new(max_speakers: usize) -> Self {
         Self {
-            max_speakers,
-            speakers: HashMap::new(),
-            next_speaker_id: 1,
+            inner: Arc::new(Mutex::new(Speakers {
+                max_speakers,
+                speakers: HashMap::new(),
+                next_speaker_id: 1,
+            })),
         }
     }
 
@@ -25,56 +36,60 @@ impl EmbeddingManager {
         dot_product / (norm_a * norm_b)
     }
 
-    /// Search or create speaker
+    fn best_match(speakers: &Speakers, embedding: &Array1<f32>) -> Option<(usize, f32)> {
+        speakers
+            .speakers
+            .iter()
+            .map(|(&speaker_id, (centroid, _))| {
+                (speaker_id, Self::cosine_similarity(embedding, centroid))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Search or create speaker. A matched speaker's centroid moves towards the embedding so
+    /// clusters get more robust the longer someone talks
     pub fn search_speaker(&mut self, embedding: Vec<f32>, threshold: f32) -> Option<usize> {
         let embedding_array = Array1::from_vec(embedding);
-        let mut best_speaker_id = None;
-        let mut best_similarity = threshold;
+        let mut speakers = self.inner.lock().unwrap();
 
-        for (&speaker_id, speaker_embedding) in &self.speakers {
-            let similarity = Self::cosine_similarity(&embedding_array, speaker_embedding);
-            if similarity > best_similarity {
-                best_speaker_id = Some(speaker_id);
-                best_similarity = similarity;
+        match Self::best_match(&speakers, &embedding_array) {
+            Some((speaker_id, similarity)) if similarity > threshold => {
+                if let Some((centroid, count)) = speakers.speakers.get_mut(&speaker_id) {
+                    *count += 1;
+                    *centroid = &*centroid + (&embedding_array - &*centroid) / *count as f32;
+                }
+                Some(speaker_id)
             }
-        }
-
-        match best_speaker_id {
-            Some(id) => Some(id),
-            None if self.speakers.len() < self.max_speakers => {
-                Some(self.add_speaker(embedding_array))
+            _ if speakers.speakers.len() < speakers.max_speakers => {
+                Some(Self::add_speaker(&mut speakers, embedding_array))
             }
-            None => None,
+            _ => None,
         }
     }
 
     pub fn get_best_speaker_match(&mut self, embedding: Vec<f32>) -> Result<usize> {
-        if self.speakers.is_empty() {
-            bail!("no speakers")
-        }
-        let embedding_array = Array1::from_vec(embedding);
-        let mut best_speaker_id = 0;
-        let mut best_similarity = f32::MIN; // Initialize to the lowest possible value
-
-        for (&speaker_id, speaker_embedding) in &self.speakers {
-            let similarity = Self::cosine_similarity(&embedding_array, speaker_embedding);
-            if similarity > best_similarity {
-                best_speaker_id = speaker_id;
-                best_similarity = similarity;
-            }
+        let speakers = self.inner.lock().unwrap();
+        match Self::best_match(&speakers, &Array1::from_vec(embedding)) {
+            Some((speaker_id, _)) => Ok(speaker_id),
+            None => bail!("no speakers"),
         }
-        Ok(best_speaker_id)
     }
 
-    fn add_speaker(&mut self, embedding: Array1<f32>) -> usize {
-        let speaker_id = self.next_speaker_id;
-        self.speakers.insert(speaker_id, embedding);
-        self.next_speaker_id += 1;
+    fn add_speaker(speakers: &mut Speakers, embedding: Array1<f32>) -> usize {
+        let speaker_id = speakers.next_speaker_id;
+        speakers.speakers.insert(speaker_id, (embedding, 1));
+        speakers.next_speaker_id += 1;
         speaker_id
     }
 
     #[allow(unused)]
-    pub fn get_all_speakers(&self) -> &HashMap<usize, Array1<f32>> {
-        &self.speakers
+    pub fn get_all_speakers(&self) -> HashMap<usize, Array1<f32>> {
+        self.inner
+            .lock()
+            .unwrap()
+            .speakers
+            .iter()
+            .map(|(&speaker_id, (centroid, _))| (speaker_id, centroid.clone()))
+            .collect()
     }
 }
@@ -1,4 +1,5 @@
 pub mod embedding;
+pub mod enrollment;
 pub mod identify;
 pub mod models;
 pub mod segment;
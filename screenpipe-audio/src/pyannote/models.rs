@@ -1,5 +1,5 @@
+use crate::models::{self, ModelFile};
 use anyhow::Result;
-use dirs;
 use lazy_static::lazy_static;
 use log::{debug, info};
 use std::path::PathBuf;
@@ -23,8 +23,7 @@ pub async fn get_or_download_model(model_type: PyannoteModel) -> Result<PathBuf>
                 return Ok(path.clone());
             }
 
-            let cache_dir = get_cache_dir()?;
-            let path = cache_dir.join("segmentation-3.0.onnx");
+            let path = models::models_dir()?.join("segmentation-3.0.onnx");
 
             if path.exists() {
                 debug!("found existing segmentation model at: {:?}", path);
@@ -55,8 +54,7 @@ pub async fn get_or_download_model(model_type: PyannoteModel) -> Result<PathBuf>
                 return Ok(path.clone());
             }
 
-            let cache_dir = get_cache_dir()?;
-            let path = cache_dir.join("wespeaker_en_voxceleb_CAM++.onnx");
+            let path = models::models_dir()?.join("wespeaker_en_voxceleb_CAM++.onnx");
 
             if path.exists() {
                 debug!("found existing embedding model at: {:?}", path);
@@ -89,39 +87,24 @@ pub enum PyannoteModel {
 }
 
 async fn download_model(model_type: PyannoteModel) -> Result<()> {
-    let (url, filename) = match model_type {
+    // sha256 of the files in screenpipe-audio/models/pyannote
+    let (url, filename, sha256) = match model_type {
         PyannoteModel::Segmentation => (
             "https://github.com/mediar-ai/screenpipe/raw/refs/heads/main/screenpipe-audio/models/pyannote/segmentation-3.0.onnx",
             "segmentation-3.0.onnx",
+            "b78fc48113bb46fd247ae6a9aea737079550c647638db961df7e0e1e9f4ba62e",
         ),
         PyannoteModel::Embedding => (
             "https://github.com/mediar-ai/screenpipe/raw/refs/heads/main/screenpipe-audio/models/pyannote/wespeaker_en_voxceleb_CAM++.onnx",
             "wespeaker_en_voxceleb_CAM++.onnx",
+            "c46fad10b5f81e1aa4a60c162714208577093655076c5450f8c469e522ec54ef",
         ),
     };
 
-    info!("downloading {} model from {}", filename, url);
-    let response = reqwest::get(url).await?;
-    let model_data = response.bytes().await?;
-
-    let cache_dir = get_cache_dir()?;
-    tokio::fs::create_dir_all(&cache_dir).await?;
-    let path = cache_dir.join(filename);
-
-    info!(
-        "saving {} model ({} bytes) to {:?}",
-        filename,
-        model_data.len(),
-        path
-    );
-    let mut file = tokio::fs::File::create(&path).await?;
-    tokio::io::AsyncWriteExt::write_all(&mut file, &model_data).await?;
+    // the file only appears under its final name once complete, which the callers wait for
+    let file = ModelFile::new(filename, url).with_sha256(sha256);
+    tokio::task::spawn_blocking(move || models::get_or_download_with_events(&file)).await??;
     info!("{} model successfully downloaded and saved", filename);
 
     Ok(())
 }
-
-fn get_cache_dir() -> Result<PathBuf> {
-    let proj_dirs = dirs::cache_dir().ok_or_else(|| anyhow::anyhow!("failed to get cache dir"))?;
-    Ok(proj_dirs.join("screenpipe").join("models"))
-}
@@ -1,7 +1,8 @@
 use crate::pyannote::session;
+use crate::vad_engine::SegmentPadding;
 use anyhow::{Context, Result};
 use ndarray::{ArrayBase, Axis, IxDyn, ViewRepr};
-use std::{cmp::Ordering, path::Path, sync::Arc, sync::Mutex};
+use std::{cmp::Ordering, path::Path, sync::Arc, sync::Mutex, time::Duration};
 
 use super::{embedding::EmbeddingExtractor, identify::EmbeddingManager};
 
@@ -76,9 +77,13 @@ fn handle_new_segment(
 ) -> Option<SpeechSegment> {
     if let Some(mut prev_segment) = current_segment {
         if prev_segment.speaker == new_segment.speaker {
-            // Merge segments
+            // Merge segments, padded ones may overlap
+            let overlap = ((prev_segment.end - new_segment.start) * new_segment.sample_rate as f64)
+                .max(0.0) as usize;
             prev_segment.end = new_segment.end;
-            prev_segment.samples.extend(new_segment.samples);
+            prev_segment
+                .samples
+                .extend(new_segment.samples.into_iter().skip(overlap));
             Some(prev_segment)
         } else {
             // Different speaker, push previous and start new
@@ -104,6 +109,9 @@ pub struct SegmentIterator {
     start_offset: f64,
     current_segment: Option<SpeechSegment>,
     padded_samples: Vec<f32>,
+    /// Samples kept before and after the speech the model hears
+    pre_roll: usize,
+    hangover: usize,
 }
 
 impl SegmentIterator {
@@ -137,9 +145,21 @@ impl SegmentIterator {
             start_offset: 0.0,
             current_segment: None,
             padded_samples,
+            pre_roll: 0,
+            hangover: 0,
         })
     }
 
+    /// Widens segments by `padding`: the model opens and closes them right where it hears speech,
+    /// cutting the first and last sounds of words
+    pub fn with_padding(mut self, padding: SegmentPadding) -> Self {
+        let sample_rate = self.sample_rate as f64;
+        let samples = |duration: Duration| (duration.as_secs_f64() * sample_rate) as usize;
+        self.pre_roll = samples(padding.pre_roll);
+        self.hangover = samples(padding.hangover);
+        self
+    }
+
     fn process_window(&mut self, window: &[f32]) -> Result<Option<SpeechSegment>> {
         let array = ndarray::Array1::from_vec(window.to_vec());
         let array = array
@@ -172,8 +192,8 @@ impl SegmentIterator {
                     }
                 } else if self.is_speeching {
                     let new_segment = match create_speech_segment(
-                        self.start_offset,
-                        self.offset,
+                        (self.start_offset - self.pre_roll as f64).max(0.0),
+                        (self.offset + self.hangover as i32).min(self.samples.len() as i32),
                         self.sample_rate,
                         &self.samples,
                         &self.padded_samples,
@@ -1,7 +1,11 @@
-use crate::{deepgram::stream_transcription_deepgram, AudioStream};
+use crate::stt::{stream_transcription, StreamingConfig, TranscriptionOptions};
+use crate::whisper::{ModelOptions, WhisperModel};
+use crate::DeviceType;
+use crate::{deepgram::stream_transcription_deepgram, AudioStream, AudioTranscriptionEngine};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use screenpipe_core::Language;
+use screenpipe_events::send_event;
 use serde::{Deserialize, Serialize};
 use std::sync::{atomic::AtomicBool, Arc};
 
@@ -10,12 +14,85 @@ pub async fn realtime_stt(
     languages: Vec<Language>,
     is_running: Arc<AtomicBool>,
     deepgram_api_key: Option<String>,
+    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
+    model_options: ModelOptions,
+    transcription_options: TranscriptionOptions,
 ) -> Result<()> {
-    stream_transcription_deepgram(stream, languages, is_running, deepgram_api_key).await?;
+    if *audio_transcription_engine == AudioTranscriptionEngine::Deepgram {
+        stream_transcription_deepgram(
+            stream,
+            languages,
+            is_running,
+            deepgram_api_key,
+            transcription_options.keywords(),
+            transcription_options.keyword_spotter,
+        )
+        .await?;
+    } else {
+        stream_transcription_whisper(
+            stream,
+            languages,
+            is_running,
+            audio_transcription_engine,
+            model_options,
+            transcription_options,
+        )
+        .await?;
+    }
 
     Ok(())
 }
 
+/// Sliding window whisper transcription, partial and final results are sent as
+/// `transcription` events like deepgram's
+async fn stream_transcription_whisper(
+    stream: Arc<AudioStream>,
+    languages: Vec<Language>,
+    is_running: Arc<AtomicBool>,
+    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
+    model_options: ModelOptions,
+    transcription_options: TranscriptionOptions,
+) -> Result<()> {
+    // the model the whisper channel already has loaded, unless it unloaded it while idle
+    let local_engine = transcription_options.local_engine(&audio_transcription_engine);
+    let whisper_model =
+        tokio::task::spawn_blocking(move || WhisperModel::shared(&local_engine, &model_options))
+            .await??;
+    let is_input = stream.device.device_type == DeviceType::Input;
+    let keyword_spotter = transcription_options.keyword_spotter.clone();
+
+    stream_transcription(
+        stream.subscribe(),
+        stream.device.clone(),
+        stream.device_config.sample_rate().0,
+        whisper_model,
+        audio_transcription_engine,
+        languages,
+        transcription_options,
+        is_running,
+        StreamingConfig::default(),
+        |result| {
+            if let Some(spotter) = &keyword_spotter {
+                spotter.spot_result(&result);
+            }
+            let Some(text) = result.transcription.filter(|t| !t.trim().is_empty()) else {
+                return;
+            };
+            let _ = send_event(
+                "transcription",
+                RealtimeTranscriptionEvent {
+                    timestamp: result.captured_at.map(Into::into).unwrap_or_else(Utc::now),
+                    device: result.input.device.to_string(),
+                    transcription: text,
+                    is_final: result.is_final,
+                    is_input,
+                },
+            );
+        },
+    )
+    .await
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct RealtimeTranscriptionEvent {
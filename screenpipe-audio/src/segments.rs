@@ -1,4 +1,5 @@
 use crate::audio_processing::{average_noise_spectrum, spectral_subtraction};
+use crate::speech_stats;
 use crate::{
     audio_processing::normalize_v2,
     pyannote::{
@@ -6,10 +7,11 @@ use crate::{
         identify::EmbeddingManager,
         segment::{get_segments, SpeechSegment},
     },
-    vad_engine::VadEngine,
+    vad_engine::{SegmentPadding, VadEngine},
 };
 use anyhow::Result;
 use log::{error, info};
+use std::time::{Duration, SystemTime};
 use std::{path::PathBuf, sync::Arc, sync::Mutex as StdMutex};
 use tokio::sync::Mutex;
 use vad_rs::VadStatus;
@@ -21,6 +23,32 @@ pub async fn prepare_segments(
     embedding_manager: EmbeddingManager,
     embedding_extractor: Arc<StdMutex<EmbeddingExtractor>>,
     device: &str,
+) -> Result<tokio::sync::mpsc::Receiver<SpeechSegment>> {
+    prepare_segments_at(
+        audio_data,
+        vad_engine,
+        segmentation_model_path,
+        embedding_manager,
+        embedding_extractor,
+        device,
+        None,
+        SegmentPadding::default(),
+    )
+    .await
+}
+
+/// `prepare_segments` of audio captured from `captured_at`, counting its speech in the speech
+/// stats of the device, with `padding` around the speech of each segment
+#[allow(clippy::too_many_arguments)]
+pub async fn prepare_segments_at(
+    audio_data: &[f32],
+    vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>>,
+    segmentation_model_path: &PathBuf,
+    embedding_manager: EmbeddingManager,
+    embedding_extractor: Arc<StdMutex<EmbeddingExtractor>>,
+    device: &str,
+    captured_at: Option<SystemTime>,
+    padding: SegmentPadding,
 ) -> Result<tokio::sync::mpsc::Receiver<SpeechSegment>> {
     let audio_data = normalize_v2(audio_data);
 
@@ -32,11 +60,20 @@ pub async fn prepare_segments(
     let mut total_frames = 0;
     let mut speech_frame_count = 0;
 
-    for chunk in audio_data.chunks(frame_size) {
+    for (i, chunk) in audio_data.chunks(frame_size).enumerate() {
         total_frames += 1;
 
         let mut new_chunk = chunk.to_vec();
         let status = vad_engine.lock().await.audio_type(chunk);
+        if let Some(captured_at) = captured_at {
+            let frame = Duration::from_secs_f64(chunk.len() as f64 / 16000.0);
+            speech_stats::record_frame(
+                device,
+                (captured_at + Duration::from_millis(100) * i as u32).into(),
+                frame,
+                matches!(status, Ok(VadStatus::Speech)),
+            );
+        }
         match status {
             Ok(VadStatus::Speech) => {
                 if let Ok(processed_audio) = spectral_subtraction(chunk, noise) {
@@ -71,7 +108,8 @@ pub async fn prepare_segments(
             segmentation_model_path,
             embedding_extractor,
             embedding_manager,
-        )?;
+        )?
+        .with_padding(padding);
 
         for segment in segments.flatten() {
             if let Err(e) = tx.send(segment).await {
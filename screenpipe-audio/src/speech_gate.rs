@@ -0,0 +1,179 @@
+use crate::audio_processing::{resample_with, ResampleQuality};
+use crate::vad_engine::{SegmentPadding, VadEngine, VadEngineEnum, VadSettings};
+use anyhow::Result;
+use log::debug;
+use std::ops::Range;
+use std::time::Duration;
+use vad_rs::VadStatus;
+
+/// How the VAD picks the speech sent to the whisper channel when chunks are gated
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VadGating {
+    pub vad: VadSettings,
+    /// Audio sent before and after each stretch of speech, so its first and last words are whole
+    pub padding: SegmentPadding,
+}
+
+/// Finds the stretches of speech in a live stream as it is buffered, so only those are
+/// transcribed. Works on indices into the caller's buffer: `next_speech` classifies what was
+/// appended since the last call, `unneeded` says how much of the front can be dropped
+pub struct SpeechGate {
+    vad: Box<dyn VadEngine>,
+    sample_rate: u32,
+    /// Device samples classified at once
+    frame_len: usize,
+    frame: Duration,
+    /// 16khz samples of a frame, webrtc only takes 10, 20 or 30ms
+    vad_frame_len: usize,
+    pre_roll: usize,
+    hangover: usize,
+    max_len: usize,
+    /// Samples of the buffer classified so far
+    classified: usize,
+    /// Start of the speech being collected, pre-roll included
+    start: Option<usize>,
+    /// End of its last speech frame
+    speech_end: usize,
+    /// First of the frames in a row the VAD is unsure about while idle, speech may have started there
+    onset: Option<usize>,
+    /// (start, is speech) of the frames classified since the last `take_decisions`
+    decisions: Vec<(usize, bool)>,
+}
+
+impl SpeechGate {
+    /// Gate for a stream of `sample_rate` mono audio, cutting stretches of speech longer than
+    /// `max_len`
+    pub async fn create(gating: &VadGating, sample_rate: u32, max_len: Duration) -> Result<Self> {
+        let vad = gating.vad.create_engine().await?;
+        let frame = match gating.vad.engine {
+            VadEngineEnum::WebRtc => Duration::from_millis(30),
+            VadEngineEnum::Silero | VadEngineEnum::Energy | VadEngineEnum::WebRtcApm => {
+                Duration::from_millis(100)
+            }
+        };
+        Ok(Self::new(vad, sample_rate, frame, gating.padding, max_len))
+    }
+
+    pub fn new(
+        vad: Box<dyn VadEngine>,
+        sample_rate: u32,
+        frame: Duration,
+        padding: SegmentPadding,
+        max_len: Duration,
+    ) -> Self {
+        let samples = |duration: Duration| (duration.as_secs_f64() * sample_rate as f64) as usize;
+        Self {
+            vad,
+            sample_rate,
+            frame_len: samples(frame).max(1),
+            frame,
+            vad_frame_len: (frame.as_secs_f64() * 16000.0) as usize,
+            pre_roll: samples(padding.pre_roll),
+            hangover: samples(padding.hangover),
+            max_len: samples(max_len),
+            classified: 0,
+            start: None,
+            speech_end: 0,
+            onset: None,
+            decisions: Vec::new(),
+        }
+    }
+
+    /// How long the frames the VAD decides on last
+    pub fn frame(&self) -> Duration {
+        self.frame
+    }
+
+    /// Start in the buffer and whether it was speech of each frame classified since the last call
+    pub fn take_decisions(&mut self) -> Vec<(usize, bool)> {
+        std::mem::take(&mut self.decisions)
+    }
+
+    /// Classifies the frames of `buffer` not classified yet, up to the end of the next stretch of
+    /// speech: its range, padding included, or None once the rest of the buffer is classified
+    /// without one ending
+    pub fn next_speech(&mut self, buffer: &[f32]) -> Option<Range<usize>> {
+        while self.classified + self.frame_len <= buffer.len() {
+            let frame_start = self.classified;
+            self.classified += self.frame_len;
+            let status = self.classify(&buffer[frame_start..self.classified]);
+            self.decisions
+                .push((frame_start, status == VadStatus::Speech));
+            match (status, self.start) {
+                (VadStatus::Speech, None) => {
+                    let onset = self.onset.take().unwrap_or(frame_start);
+                    self.start = Some(onset.saturating_sub(self.pre_roll));
+                    self.speech_end = self.classified;
+                }
+                (VadStatus::Speech, Some(_)) => self.speech_end = self.classified,
+                (VadStatus::Unknown, None) => {
+                    self.onset.get_or_insert(frame_start);
+                }
+                (VadStatus::Silence, None) => self.onset = None,
+                (VadStatus::Silence, Some(start))
+                    if self.classified >= self.speech_end + self.hangover =>
+                {
+                    self.start = None;
+                    return Some(start..self.speech_end + self.hangover);
+                }
+                _ => {}
+            }
+
+            // speech going on is cut at the chunk duration, the next stretch starts with its pre-roll
+            // before the cut
+            if let Some(start) = self.start {
+                if self.classified - start >= self.max_len {
+                    self.start = None;
+                    return Some(start..self.classified);
+                }
+            }
+        }
+        None
+    }
+
+    /// The speech being collected when the stream ends in a buffer of `len` samples
+    pub fn finish(&mut self, len: usize) -> Option<Range<usize>> {
+        self.onset = None;
+        self.start.take().map(|start| start..len)
+    }
+
+    /// Samples at the front of the buffer that won't be sent: everything before the speech being
+    /// collected, or before the pre-roll of speech that may start next
+    pub fn unneeded(&self) -> usize {
+        match self.start {
+            Some(start) => start,
+            // a VAD unsure for longer than a chunk doesn't keep its audio forever
+            None => self
+                .onset
+                .unwrap_or(self.classified)
+                .max(self.classified.saturating_sub(self.max_len))
+                .saturating_sub(self.pre_roll),
+        }
+    }
+
+    /// Follows the caller dropping `n` samples from the front of the buffer
+    pub fn trim_front(&mut self, n: usize) {
+        self.classified = self.classified.saturating_sub(n);
+        self.speech_end = self.speech_end.saturating_sub(n);
+        self.start = self.start.map(|start| start.saturating_sub(n));
+        self.onset = self.onset.map(|onset| onset.saturating_sub(n));
+    }
+
+    /// Unknown when the VAD fails on a frame, it neither starts nor ends speech
+    fn classify(&mut self, frame: &[f32]) -> VadStatus {
+        let frame = if self.sample_rate == 16000 {
+            Ok(frame.to_vec())
+        } else {
+            // frames are only classified, a rough resampling does
+            resample_with(frame, self.sample_rate, 16000, ResampleQuality::Fast)
+        };
+        let status = frame.and_then(|mut frame| {
+            frame.resize(self.vad_frame_len, 0.0);
+            self.vad.audio_type(&frame)
+        });
+        status.unwrap_or_else(|e| {
+            debug!("vad failed on frame: {}", e);
+            VadStatus::Unknown
+        })
+    }
+}
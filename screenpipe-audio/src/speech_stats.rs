@@ -0,0 +1,137 @@
+use chrono::{DateTime, TimeZone, Utc};
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Hours of activity kept for each device, older hours are forgotten
+const RETAINED_HOURS: usize = 24 * 7;
+
+lazy_static! {
+    static ref ACTIVITY: DashMap<String, DeviceActivity> = DashMap::new();
+}
+
+#[derive(Default)]
+struct DeviceActivity {
+    /// (speech, silence) seconds of each hour, by unix hour
+    hours: BTreeMap<i64, (f64, f64)>,
+    /// End of the last frame counted, audio heard twice (overlapping chunks, speech transcribed
+    /// after the capture side counted it) is only counted once
+    counted_until: Option<DateTime<Utc>>,
+    /// End of the last frame of speech
+    last_speech: Option<DateTime<Utc>>,
+}
+
+/// Speech heard on a device during an hour
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HourlySpeech {
+    /// Start of the hour
+    pub hour: DateTime<Utc>,
+    pub speech_secs: f64,
+    pub silence_secs: f64,
+    /// Share of the audio captured during the hour that was speech
+    pub speech_ratio: f64,
+}
+
+/// Speech heard on a device over the queried hours
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpeechStats {
+    pub device: String,
+    pub speech_secs: f64,
+    pub silence_secs: f64,
+    pub speech_ratio: f64,
+    /// Hours audio was captured during, oldest first
+    pub hours: Vec<HourlySpeech>,
+}
+
+fn speech_ratio(speech_secs: f64, silence_secs: f64) -> f64 {
+    let total = speech_secs + silence_secs;
+    if total > 0.0 {
+        speech_secs / total
+    } else {
+        0.0
+    }
+}
+
+/// Counts the VAD decision on a frame of `device` audio lasting `frame` captured at `at`. Audio
+/// already counted for the device isn't counted again
+pub fn record_frame(device: &str, at: DateTime<Utc>, frame: Duration, is_speech: bool) {
+    let Ok(frame) = chrono::Duration::from_std(frame) else {
+        return;
+    };
+    let end = at + frame;
+    let mut activity = ACTIVITY.entry(device.to_string()).or_default();
+    if is_speech {
+        activity.last_speech = activity.last_speech.max(Some(end));
+    }
+    let from = activity.counted_until.map_or(at, |until| until.max(at));
+    if end <= from {
+        return;
+    }
+
+    let secs = (end - from).num_milliseconds() as f64 / 1000.0;
+    let (speech, silence) = activity
+        .hours
+        .entry(from.timestamp().div_euclid(3600))
+        .or_default();
+    if is_speech {
+        *speech += secs;
+    } else {
+        *silence += secs;
+    }
+    activity.counted_until = Some(end);
+    while activity.hours.len() > RETAINED_HOURS {
+        activity.hours.pop_first();
+    }
+}
+
+/// Devices speech was heard on since `since`
+pub fn devices_with_speech_since(since: DateTime<Utc>) -> Vec<String> {
+    ACTIVITY
+        .iter()
+        .filter(|entry| entry.last_speech.is_some_and(|last| last >= since))
+        .map(|entry| entry.key().clone())
+        .collect()
+}
+
+/// Speech stats of every device, or only `device`, over the hours overlapping `start`..`end`
+pub fn speech_stats(
+    device: Option<&str>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Vec<SpeechStats> {
+    let mut stats: Vec<SpeechStats> = ACTIVITY
+        .iter()
+        .filter(|entry| device.map_or(true, |device| entry.key() == device))
+        .map(|entry| {
+            let hours: Vec<HourlySpeech> = entry
+                .hours
+                .iter()
+                .filter_map(|(&hour, &(speech_secs, silence_secs))| {
+                    let hour = Utc.timestamp_opt(hour * 3600, 0).single()?;
+                    let overlaps = start
+                        .map_or(true, |start| hour + chrono::Duration::hours(1) > start)
+                        && end.map_or(true, |end| hour < end);
+                    overlaps.then(|| HourlySpeech {
+                        hour,
+                        speech_secs,
+                        silence_secs,
+                        speech_ratio: speech_ratio(speech_secs, silence_secs),
+                    })
+                })
+                .collect();
+            let speech_secs = hours.iter().map(|hour| hour.speech_secs).sum();
+            let silence_secs = hours.iter().map(|hour| hour.silence_secs).sum();
+            SpeechStats {
+                device: entry.key().clone(),
+                speech_secs,
+                silence_secs,
+                speech_ratio: speech_ratio(speech_secs, silence_secs),
+                hours,
+            }
+        })
+        .collect();
+    stats.sort_by(|a, b| a.device.cmp(&b.device));
+    stats
+}
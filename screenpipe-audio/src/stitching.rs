@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// Overlap of consecutive chunks of a device recorded without VAD gating
+pub const DEFAULT_CHUNK_OVERLAP: Duration = Duration::from_secs(2);
+
+/// Words a second of speech holds at most, to find the words of an overlap from its duration
+const WORDS_PER_SECOND: f64 = 4.0;
+
+/// Words of a transcript that must match the next one to call them the overlap, a single common
+/// word ("the", "and") is too likely to be a coincidence
+const MIN_MATCH_WORDS: usize = 2;
+
+/// Crossfades `head`, the start of a chunk, from `tail`, the same audio at the end of the
+/// previous chunk, over their common length. The raised cosine weights sum to one, so audio
+/// processed the same way in both is left as is
+pub fn overlap_add(tail: &[f32], head: &mut [f32]) {
+    let n = tail.len().min(head.len());
+    let tail = &tail[tail.len() - n..];
+    for (i, (sample, &previous)) in head.iter_mut().zip(tail).enumerate() {
+        let weight = 0.5 - 0.5 * (std::f32::consts::PI * (i as f32 + 0.5) / n as f32).cos();
+        *sample = previous * (1.0 - weight) + *sample * weight;
+    }
+}
+
+struct Tail {
+    samples: Vec<f32>,
+    /// When the last sample was captured
+    end: SystemTime,
+}
+
+/// Stitches the chunks of each device where they overlap. Each chunk is processed on its own, its
+/// filters warming up and its loudness normalized over it, so the start of a chunk doesn't sound
+/// like the end of the previous one it repeats. Crossfading it from the previous chunk keeps the
+/// words at the seam the same in both
+pub struct ChunkStitcher {
+    overlap: Duration,
+    tails: HashMap<String, Tail>,
+}
+
+impl ChunkStitcher {
+    /// Keeps `overlap` of the end of each chunk, zero stitches nothing
+    pub fn new(overlap: Duration) -> Self {
+        Self {
+            overlap,
+            tails: HashMap::new(),
+        }
+    }
+
+    /// Crossfades the start of `audio` of `device`, captured from `started_at`, from the end of
+    /// the previous chunk of the device when it repeats it. Chunks without capture time, or
+    /// captured long before the previous one (backfill), are left as they are
+    pub fn stitch(
+        &mut self,
+        device: &str,
+        audio: &mut [f32],
+        sample_rate: u32,
+        started_at: Option<SystemTime>,
+    ) {
+        if self.overlap.is_zero() {
+            return;
+        }
+        let Some(started_at) = started_at else {
+            self.tails.remove(device);
+            return;
+        };
+        if let Some(tail) = self.tails.get(device) {
+            let tail_duration =
+                Duration::from_secs_f64(tail.samples.len() as f64 / sample_rate as f64);
+            if let Ok(repeated) = tail.end.duration_since(started_at) {
+                if !repeated.is_zero() && repeated <= tail_duration {
+                    let n = ((repeated.as_secs_f64() * sample_rate as f64) as usize)
+                        .min(tail.samples.len())
+                        .min(audio.len());
+                    // the first samples of the chunk are the last ones of the tail
+                    overlap_add(&tail.samples[tail.samples.len() - n..], &mut audio[..n]);
+                }
+            }
+        }
+
+        let kept = ((self.overlap.as_secs_f64() * sample_rate as f64) as usize).min(audio.len());
+        self.tails.insert(
+            device.to_string(),
+            Tail {
+                samples: audio[audio.len() - kept..].to_vec(),
+                end: started_at + Duration::from_secs_f64(audio.len() as f64 / sample_rate as f64),
+            },
+        );
+    }
+}
+
+/// Merges two consecutive transcripts of a device whose audio overlapped by `overlap`, returning
+/// the previous one cut before the words they have in common and the current one starting at
+/// them. Words at the edges of a chunk are cut in half or guessed by the model, so the end of the
+/// previous transcript is replaced by the start of the current one, which heard those words
+/// whole, and the words before them come from the previous one.
+///
+/// Only the last words of the previous transcript and the first of the current one are compared,
+/// as many as the overlap can hold. None when they have no run of words in common: both are kept
+/// whole, repeating a few words rather than losing some
+pub fn merge_transcripts(
+    previous: &str,
+    current: &str,
+    overlap: Duration,
+) -> Option<(String, String)> {
+    let previous_words: Vec<&str> = previous.split_whitespace().collect();
+    let current_words: Vec<&str> = current.split_whitespace().collect();
+    // compared without case and punctuation, a word of punctuation only never matches
+    let normalize = |words: &[&str]| -> Vec<String> {
+        words
+            .iter()
+            .map(|word| {
+                word.chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .flat_map(char::to_lowercase)
+                    .collect()
+            })
+            .collect()
+    };
+    let window = (overlap.as_secs_f64() * WORDS_PER_SECOND).ceil() as usize + MIN_MATCH_WORDS;
+    let previous_start = previous_words.len().saturating_sub(window);
+    let previous_tail = normalize(&previous_words[previous_start..]);
+    let current_head = normalize(&current_words[..window.min(current_words.len())]);
+
+    // longest run of words in common, the latest in the previous transcript on ties
+    let mut best: Option<(usize, usize, usize)> = None;
+    let mut runs = vec![vec![0usize; current_head.len() + 1]; previous_tail.len() + 1];
+    for i in 1..=previous_tail.len() {
+        for j in 1..=current_head.len() {
+            if previous_tail[i - 1].is_empty() || previous_tail[i - 1] != current_head[j - 1] {
+                continue;
+            }
+            let run = runs[i - 1][j - 1] + 1;
+            runs[i][j] = run;
+            let longest = best.map_or(0, |(_, _, len)| len);
+            if run >= MIN_MATCH_WORDS && run >= longest {
+                best = Some((i - run, j - run, run));
+            }
+        }
+    }
+
+    let (i, j, _) = best?;
+    Some((
+        previous_words[..previous_start + i].join(" "),
+        current_words[j..].join(" "),
+    ))
+}
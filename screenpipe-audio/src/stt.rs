@@ -1,30 +1,57 @@
-use crate::audio_processing::write_audio_to_file;
+use crate::archive::{ArchiveConfig, AudioArchive};
+use crate::assemblyai::transcribe_with_assemblyai;
+use crate::audio_processing::{write_audio_to_file, AudioPipeline};
+use crate::audio_tagging::{is_music_only, AudioLabel, AudioTagger, AudioTagging};
+use crate::azure::{transcribe_with_azure, AzureSpeechCredentials};
+use crate::cloud_retry::{is_transient, CloudUnreachable, OfflineQueueConfig, RetryPolicy};
 use crate::deepgram::transcribe_with_deepgram;
+use crate::disk_queue::DiskQueue;
+use crate::encode::{AudioCodec, AudioTags};
+use crate::engine_health;
+use crate::fingerprint::{Candidate, DuplicateDetection, DuplicateDetector};
+use crate::journal::AudioJournal;
+use crate::keyword_spotting::KeywordSpotter;
+use crate::lanes::{Lane, LaneLimits, PriorityLanes};
+use crate::noise_gate::{NoiseGate, NoiseGates};
 use crate::pyannote::models::{get_or_download_model, PyannoteModel};
 use crate::pyannote::segment::SpeechSegment;
 pub use crate::segments::prepare_segments;
+use crate::segments::prepare_segments_at;
+use crate::stitching::{merge_transcripts, ChunkStitcher, DEFAULT_CHUNK_OVERLAP};
+use crate::whisper_api::{transcribe_with_whisper_api, WhisperApiProvider};
 use crate::{
+    hallucination::{self, Hallucination, HallucinationFilter, VadDecisions},
+    multilingual::{LanguageFilter, LanguageHint},
     pyannote::{embedding::EmbeddingExtractor, identify::EmbeddingManager},
-    vad_engine::{SileroVad, VadEngine, VadEngineEnum, VadSensitivity, WebRtcVad},
-    whisper::{process_with_whisper, WhisperModel},
-    AudioDevice, AudioTranscriptionEngine,
+    vad_engine::{
+        SegmentPadding, VadConfig, VadEngine, VadEngineEnum, VadSensitivity, VadSettings,
+    },
+    whisper::{
+        identify_language, process_batch_with_whisper, process_with_whisper, DecodingOptions,
+        DetectedLanguage, ModelOptions, ModelSource, SegmentConfidence, Task, TextSegment,
+        Transcript, WhisperModel,
+    },
+    AudioBlock, AudioDevice, AudioTranscriptionEngine, CaptureTiming, DeviceType, FILE_AUDIO_HOST,
 };
 use crate::{resample, DeviceControl};
 use anyhow::{anyhow, Result};
 use candle_transformers::models::whisper as m;
 use dashmap::DashMap;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 #[cfg(target_os = "macos")]
 use objc::rc::autoreleasepool;
 use screenpipe_core::Language;
+use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{
-    path::Path,
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
     sync::Arc,
     sync::Mutex as StdMutex,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 
 pub fn stt_sync(
     audio: &[f32],
@@ -34,15 +61,17 @@ pub fn stt_sync(
     audio_transcription_engine: Arc<AudioTranscriptionEngine>,
     deepgram_api_key: Option<String>,
     languages: Vec<Language>,
-) -> Result<String> {
+    options: &TranscriptionOptions,
+) -> Result<Transcript> {
     let mut whisper_model = whisper_model.clone();
     let audio = audio.to_vec();
+    let options = options.clone();
 
     let device = device.to_string();
     let handle = std::thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new().unwrap();
 
-        rt.block_on(stt(
+        rt.block_on(transcribe(
             &audio,
             sample_rate,
             &device,
@@ -50,6 +79,7 @@ pub fn stt_sync(
             audio_transcription_engine,
             deepgram_api_key,
             languages,
+            &options,
         ))
     });
 
@@ -65,43 +95,352 @@ pub async fn stt(
     audio_transcription_engine: Arc<AudioTranscriptionEngine>,
     deepgram_api_key: Option<String>,
     languages: Vec<Language>,
+    options: &TranscriptionOptions,
 ) -> Result<String> {
-    let model = &whisper_model.model;
-
-    debug!("Loading mel filters");
-    let mel_bytes = match model.config().num_mel_bins {
-        80 => include_bytes!("../models/whisper/melfilters.bytes").as_slice(),
-        128 => include_bytes!("../models/whisper/melfilters128.bytes").as_slice(),
-        nmel => anyhow::bail!("unexpected num_mel_bins {nmel}"),
-    };
-    let mut mel_filters = vec![0f32; mel_bytes.len() / 4];
-    <byteorder::LittleEndian as byteorder::ByteOrder>::read_f32_into(mel_bytes, &mut mel_filters);
+    transcribe(
+        audio,
+        sample_rate,
+        device,
+        whisper_model,
+        audio_transcription_engine,
+        deepgram_api_key,
+        languages,
+        options,
+    )
+    .await
+    .map(|t| t.text)
+}
 
-    let transcription: Result<String> = if audio_transcription_engine
-        == AudioTranscriptionEngine::Deepgram.into()
-    {
-        // Deepgram implementation
-        let api_key = deepgram_api_key.unwrap_or_default();
+/// Like `stt`, also returning how confident the engine was and the language it transcribed in
+#[allow(clippy::too_many_arguments)]
+pub async fn transcribe(
+    audio: &[f32],
+    sample_rate: u32,
+    device: &str,
+    whisper_model: &mut WhisperModel,
+    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
+    deepgram_api_key: Option<String>,
+    languages: Vec<Language>,
+    options: &TranscriptionOptions,
+) -> Result<Transcript> {
+    let language = LanguageHint::from_languages(&languages);
+    let primary = audio_transcription_engine.as_ref();
+    // whether every cloud engine of the chain failed for lack of connectivity
+    let mut unreachable = primary.is_cloud();
 
-        match transcribe_with_deepgram(&api_key, audio, device, sample_rate, languages.clone())
+    for engine in options.cloud_chain(primary) {
+        if !engine_health::is_available(engine) {
+            debug!("device: {}, skipping {} while it is down", device, engine);
+            continue;
+        }
+        let mut attempt = 0;
+        let result = loop {
+            match transcribe_with_cloud(
+                engine,
+                audio,
+                sample_rate,
+                device,
+                deepgram_api_key.clone(),
+                &languages,
+                options,
+            )
             .await
-        {
-            Ok(transcription) => Ok(transcription),
+            {
+                Err(e) if is_transient(&e) && attempt + 1 < options.retry.max_attempts => {
+                    let backoff = options.retry.backoff(attempt);
+                    warn!(
+                        "device: {}, {} transcription failed, retrying in {:?}: {}",
+                        device, engine, backoff, e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                result => break result,
+            }
+        };
+        match result {
+            Ok(mut transcript) => {
+                engine_health::record_success(engine);
+                engine_health::record_active(primary, engine);
+                transcript.engine = Some(engine.clone());
+                return Ok(transcript);
+            }
             Err(e) => {
+                unreachable &= is_transient(&e);
+                engine_health::record_failure(engine, &e);
                 error!(
-                    "device: {}, deepgram transcription failed, falling back to Whisper: {:?}",
-                    device, e
+                    "device: {}, {} transcription failed, falling back: {:?}",
+                    device, engine, e
                 );
-                // Fallback to Whisper
-                process_with_whisper(&mut *whisper_model, audio, &mel_filters, languages.clone())
             }
         }
-    } else {
-        // Existing Whisper implementation
-        process_with_whisper(&mut *whisper_model, audio, &mel_filters, languages)
-    };
+    }
+    if unreachable && options.offline_queue.is_some() {
+        return Err(CloudUnreachable.into());
+    }
+
+    let local_engine = options.local_engine(primary);
+    let transcript = process_with_whisper(
+        &mut *whisper_model,
+        audio,
+        &language,
+        options.task,
+        options.prompt.as_deref(),
+        &options.decoding,
+    );
+    match &transcript {
+        Ok(_) => {
+            engine_health::record_success(&local_engine);
+            engine_health::record_active(primary, &local_engine);
+        }
+        Err(e) => engine_health::record_failure(&local_engine, e),
+    }
+    transcript.map(|transcript| Transcript {
+        engine: Some(local_engine),
+        ..transcript
+    })
+}
+
+async fn transcribe_with_cloud(
+    engine: &AudioTranscriptionEngine,
+    audio: &[f32],
+    sample_rate: u32,
+    device: &str,
+    deepgram_api_key: Option<String>,
+    languages: &[Language],
+    options: &TranscriptionOptions,
+) -> Result<Transcript> {
+    match engine {
+        AudioTranscriptionEngine::Deepgram => {
+            let api_key = deepgram_api_key.unwrap_or_default();
+            transcribe_with_deepgram(
+                &api_key,
+                audio,
+                device,
+                sample_rate,
+                languages.to_vec(),
+                &options.keywords(),
+            )
+            .await
+        }
+        AudioTranscriptionEngine::AssemblyAI => match options.api_keys.assemblyai.as_deref() {
+            Some(api_key) => {
+                transcribe_with_assemblyai(
+                    api_key,
+                    audio,
+                    device,
+                    sample_rate,
+                    languages,
+                    &options.keywords(),
+                )
+                .await
+            }
+            None => Err(anyhow!("no assemblyai api key")),
+        },
+        AudioTranscriptionEngine::AzureSpeech => match options.api_keys.azure_speech.as_ref() {
+            Some(credentials) => {
+                transcribe_with_azure(
+                    credentials,
+                    audio,
+                    device,
+                    sample_rate,
+                    languages,
+                    &options.keywords(),
+                )
+                .await
+            }
+            None => Err(anyhow!("no azure speech key and region")),
+        },
+        AudioTranscriptionEngine::OpenAIWhisper | AudioTranscriptionEngine::GroqWhisper => {
+            let (provider, api_key) = match engine {
+                AudioTranscriptionEngine::OpenAIWhisper => (
+                    WhisperApiProvider::OpenAI,
+                    options.api_keys.openai.as_deref(),
+                ),
+                _ => (WhisperApiProvider::Groq, options.api_keys.groq.as_deref()),
+            };
+            match api_key {
+                Some(api_key) => {
+                    transcribe_with_whisper_api(
+                        provider,
+                        api_key,
+                        audio,
+                        device,
+                        sample_rate,
+                        languages,
+                        options.task,
+                        options.prompt.as_deref(),
+                    )
+                    .await
+                }
+                None => Err(anyhow!("no {} api key", provider)),
+            }
+        }
+        _ => Err(anyhow!("{} is not a cloud engine", engine)),
+    }
+}
+
+/// Transcription settings that can differ between devices
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TranscriptionOptions {
+    /// Translate speech to english instead of transcribing it in the spoken language, only
+    /// supported by the whisper engines (except faster-whisper)
+    pub task: Task,
+    /// Context such as names, jargon or product terms so they get spelled right: the initial
+    /// prompt of whisper, and comma or line separated keywords for deepgram
+    pub prompt: Option<String>,
+    /// Beam search, temperature fallback and thresholds of the local whisper engines
+    pub decoding: DecodingOptions,
+    /// What to do with repetition loops, made up phrases and transcripts of segments without speech
+    pub hallucination_filter: HallucinationFilter,
+    /// Credentials of the cloud engines other than deepgram
+    pub api_keys: CloudApiKeys,
+    /// Engines tried in order when the primary one fails. Cloud engines are tried until the first
+    /// local one, which is the model loaded for the chain and always transcribes in the end
+    pub fallback_engines: Vec<AudioTranscriptionEngine>,
+    /// Retries of cloud engines failing transiently, before the next engine of the chain
+    pub retry: RetryPolicy,
+    /// Queue segments on disk while no cloud engine of the chain can be reached, transcribing them
+    /// once one is back, instead of falling back to the local model. Only the default options'
+    /// queue is used
+    pub offline_queue: Option<OfflineQueueConfig>,
+    /// Phrases to spot in the transcripts of the device, sending events and calling callbacks
+    /// as soon as they are transcribed
+    pub keyword_spotter: Option<KeywordSpotter>,
+    /// Only transcribe segments spoken in these languages, identified with the local whisper model
+    /// before decoding
+    pub language_filter: Option<LanguageFilter>,
+}
 
-    transcription
+/// API keys of the cloud transcription engines, kept out of debug output
+#[derive(Clone, Default, PartialEq)]
+pub struct CloudApiKeys {
+    pub assemblyai: Option<String>,
+    pub azure_speech: Option<AzureSpeechCredentials>,
+    pub openai: Option<String>,
+    pub groq: Option<String>,
+}
+
+impl fmt::Debug for CloudApiKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CloudApiKeys")
+            .field(
+                "assemblyai",
+                &self.assemblyai.as_ref().map(|_| "<redacted>"),
+            )
+            .field("azure_speech", &self.azure_speech)
+            .field("openai", &self.openai.as_ref().map(|_| "<redacted>"))
+            .field("groq", &self.groq.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl TranscriptionOptions {
+    /// Cloud engines tried before the local model: `primary` and the fallbacks up to the first
+    /// local engine
+    pub fn cloud_chain<'a>(
+        &'a self,
+        primary: &'a AudioTranscriptionEngine,
+    ) -> impl Iterator<Item = &'a AudioTranscriptionEngine> {
+        std::iter::once(primary)
+            .chain(&self.fallback_engines)
+            .take_while(|engine| engine.is_cloud())
+    }
+
+    /// Engine of the local model transcribing when the cloud engines of the chain fail, the
+    /// default whisper when the chain has no local engine
+    pub fn local_engine(&self, primary: &AudioTranscriptionEngine) -> AudioTranscriptionEngine {
+        std::iter::once(primary)
+            .chain(&self.fallback_engines)
+            .find(|engine| !engine.is_cloud())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Terms of `prompt` boosted by deepgram
+    pub fn keywords(&self) -> Vec<String> {
+        self.prompt
+            .iter()
+            .flat_map(|prompt| prompt.split([',', '\n']))
+            .map(str::trim)
+            .filter(|keyword| !keyword.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// `TranscriptionOptions` of each device, devices without their own use `default`
+#[derive(Debug, Clone, Default)]
+pub struct DeviceTranscriptionOptions {
+    pub default: TranscriptionOptions,
+    pub devices: HashMap<AudioDevice, TranscriptionOptions>,
+    /// Where the whisper channel keeps the chunks it received until they are transcribed, to
+    /// resume after a crash. None keeps them in memory only
+    pub journal_dir: Option<PathBuf>,
+    /// How long live audio may hold back the backfill when transcription falls behind
+    pub lane_limits: LaneLimits,
+    /// Classify the chunks the whisper channel receives as music, typing, notification sounds...
+    pub audio_tagging: AudioTagging,
+    /// Thresholds and durations of the VAD segmenting chunks
+    pub vad_config: VadConfig,
+    /// Audio kept around the speech of each segment
+    pub segment_padding: SegmentPadding,
+    /// VAD of the devices that don't use the channel's engine and sensitivity, e.g. an aggressive
+    /// one for a noisy loopback device
+    pub device_vad: HashMap<AudioDevice, VadSettings>,
+    /// Codec of the audio file written of each chunk
+    pub audio_codec: AudioCodec,
+    /// Also keep the audio of each device in a rolling archive of long files with a manifest
+    pub archive: Option<ArchiveConfig>,
+    /// Processing of each chunk before it is transcribed, archived and encoded: DC removal,
+    /// high-pass filtering, loudness normalization
+    pub audio_pipeline: AudioPipeline,
+    /// Transcribe audio heard on several devices at once, e.g. the mic and loopback device in a
+    /// meeting without headphones, only once
+    pub duplicate_detection: Option<DuplicateDetection>,
+    /// Overlap of consecutive chunks of a device, the start of each is crossfaded from the end of
+    /// the previous one after processing. Zero leaves chunks as they are
+    pub chunk_overlap: Duration,
+    /// Subtract the steady noise each device hears in its silences before the audio pipeline
+    pub noise_gate: Option<NoiseGate>,
+}
+
+impl DeviceTranscriptionOptions {
+    pub fn for_device(&self, device: &AudioDevice) -> &TranscriptionOptions {
+        self.devices.get(device).unwrap_or(&self.default)
+    }
+}
+
+type SharedVad = Arc<Mutex<Box<dyn VadEngine + Send>>>;
+
+/// The VAD engine of each device, devices without their own settings share the default one
+struct DeviceVads {
+    default: SharedVad,
+    devices: HashMap<AudioDevice, SharedVad>,
+}
+
+impl DeviceVads {
+    async fn new(
+        default: &VadSettings,
+        devices: &HashMap<AudioDevice, VadSettings>,
+    ) -> Result<Self> {
+        let mut engines = HashMap::new();
+        for (device, settings) in devices {
+            info!("device: {}, vad: {}", device, settings.engine);
+            engines.insert(
+                device.clone(),
+                Arc::new(Mutex::new(settings.create_engine().await?)),
+            );
+        }
+        Ok(Self {
+            default: Arc::new(Mutex::new(default.create_engine().await?)),
+            devices: engines,
+        })
+    }
+
+    fn for_device(&self, device: &AudioDevice) -> &SharedVad {
+        self.devices.get(device).unwrap_or(&self.default)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -110,6 +449,8 @@ pub struct AudioInput {
     pub sample_rate: u32,
     pub channels: u16,
     pub device: Arc<AudioDevice>,
+    /// When each captured block of `data` was recorded, None for audio not coming from a live stream
+    pub capture_timing: Option<CaptureTiming>,
 }
 
 #[derive(Debug, Clone)]
@@ -117,35 +458,70 @@ pub struct TranscriptionResult {
     pub path: String,
     pub input: AudioInput,
     pub speaker_embedding: Vec<f32>,
+    /// Speaker of the segment as diarized by pyannote, consistent across the chunks transcribed by
+    /// the same whisper channel. None when diarization didn't attribute the segment
+    pub speaker_id: Option<usize>,
     pub transcription: Option<String>,
     pub timestamp: u64,
     pub error: Option<String>,
     pub start_time: f64,
     pub end_time: f64,
+    /// Length of the transcribed audio
+    pub audio_duration: Duration,
+    pub device_type: DeviceType,
+    /// Wall clock time the segment started being captured, when capture timing is known
+    pub captured_at: Option<SystemTime>,
+    /// Wall clock time the segment stopped being captured, when capture timing is known
+    pub captured_until: Option<SystemTime>,
+    /// From the end of the segment's capture to its transcription, when capture timing is known
+    pub latency: Option<Duration>,
+    /// Engine of the fallback chain that transcribed the segment, None for errors
+    pub engine: Option<AudioTranscriptionEngine>,
+    /// Model the engine transcribed with, see `AudioTranscriptionEngine::model_version`
+    pub model_version: Option<String>,
+    /// False for partial results of streaming transcription, which a later result supersedes
+    pub is_final: bool,
+    /// Whisper's confidence in the transcription, None for other engines and errors
+    pub confidence: Option<SegmentConfidence>,
+    /// Language whisper transcribed the segment in, None for other engines and errors
+    pub language: Option<DetectedLanguage>,
+    /// Set when the hallucination filter flagged the transcription, which is None if it was dropped
+    pub hallucination: Option<Hallucination>,
+    /// Sounds heard in the chunk the segment is part of, empty unless audio tagging is enabled
+    pub audio_events: Vec<AudioLabel>,
+    /// The transcription split where the engine timestamped it, see `Transcript::segments`
+    pub text_segments: Vec<TextSegment>,
 }
 
 impl TranscriptionResult {
-    // TODO --optimize
-    pub fn cleanup_overlap(&mut self, previous_transcript: String) -> Option<(String, String)> {
-        if let Some(transcription) = &self.transcription {
-            let transcription = transcription.to_string();
-            if let Some((prev_idx, cur_idx)) =
-                longest_common_word_substring(previous_transcript.as_str(), transcription.as_str())
-            {
-                // strip old transcript from prev_idx word pos
-                let new_prev = previous_transcript
-                    .split_whitespace()
-                    .collect::<Vec<&str>>()[..prev_idx]
-                    .join(" ");
-                // strip new transcript before cur_idx word pos
-                let new_cur =
-                    transcription.split_whitespace().collect::<Vec<&str>>()[cur_idx..].join(" ");
-
-                return Some((new_prev, new_cur));
-            }
-        }
+    /// Sets when the segment was captured and how long ago it ended
+    pub fn set_capture_time(
+        &mut self,
+        captured_at: Option<SystemTime>,
+        captured_until: Option<SystemTime>,
+    ) {
+        self.captured_at = captured_at;
+        self.captured_until = captured_until;
+        self.latency = captured_until.and_then(|until| until.elapsed().ok());
+    }
 
-        None
+    /// The previous transcript of the device cut before the words this one repeats, and this one
+    /// starting at them, see `merge_transcripts`. None when the segment doesn't start in the audio
+    /// of the previous one, e.g. a later segment of the same chunk
+    pub fn cleanup_overlap(
+        &self,
+        previous_transcript: &str,
+        previous_until: Option<SystemTime>,
+    ) -> Option<(String, String)> {
+        let transcription = self.transcription.as_ref()?;
+        let overlap = match (previous_until, self.captured_at) {
+            (Some(until), Some(at)) => until.duration_since(at).ok()?,
+            _ => DEFAULT_CHUNK_OVERLAP,
+        };
+        if overlap.is_zero() {
+            return None;
+        }
+        merge_transcripts(previous_transcript, transcription, overlap)
     }
 }
 
@@ -157,26 +533,61 @@ pub async fn create_whisper_channel(
     vad_sensitivity: VadSensitivity,
     languages: Vec<Language>,
     audio_devices_control: Option<Arc<DashMap<AudioDevice, DeviceControl>>>,
+    model_options: ModelOptions,
+    transcription_options: DeviceTranscriptionOptions,
 ) -> Result<(
     crossbeam::channel::Sender<AudioInput>,
     crossbeam::channel::Receiver<TranscriptionResult>,
     Arc<AtomicBool>, // Shutdown flag
 )> {
-    let mut whisper_model = WhisperModel::new(&audio_transcription_engine)?;
+    // None while unloaded after being idle for `model_options.unload_after_idle`
+    // the model of the first local engine of the chain, cloud engines fall back to it. Shared
+    // with the live transcription of the devices, each batch decodes with its own clone of it
+    let local_engine = transcription_options
+        .default
+        .local_engine(&audio_transcription_engine);
+    let mut whisper_model = Some(WhisperModel::shared(&local_engine, &model_options)?);
+    let mut last_transcription = Instant::now();
+    let batch_size = model_options.batch_size.max(1);
+    let mut offline_queue = match &transcription_options.default.offline_queue {
+        Some(config) => match DiskQueue::open(&config.dir, config.max_bytes) {
+            Ok(queue) => Some(queue),
+            Err(e) => {
+                error!("failed to open the offline transcription queue: {:?}", e);
+                None
+            }
+        },
+        None => None,
+    };
     let (input_sender, input_receiver): (
         crossbeam::channel::Sender<AudioInput>,
         crossbeam::channel::Receiver<AudioInput>,
     ) = crossbeam::channel::bounded(1000);
+    let journal = match &transcription_options.journal_dir {
+        Some(dir) => match AudioJournal::open(dir) {
+            Ok(journal) => Some(Arc::new(StdMutex::new(journal))),
+            Err(e) => {
+                error!("failed to open the audio journal: {:?}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    let input_receiver = journal_inputs(input_receiver, journal.clone());
+    let mut lanes = PriorityLanes::new(transcription_options.lane_limits.clone());
     let (output_sender, output_receiver): (
         crossbeam::channel::Sender<TranscriptionResult>,
         crossbeam::channel::Receiver<TranscriptionResult>,
     ) = crossbeam::channel::bounded(1000);
-    let mut vad_engine: Box<dyn VadEngine + Send> = match vad_engine {
-        VadEngineEnum::WebRtc => Box::new(WebRtcVad::new()),
-        VadEngineEnum::Silero => Box::new(SileroVad::new().await?),
-    };
-    vad_engine.set_sensitivity(vad_sensitivity);
-    let vad_engine = Arc::new(Mutex::new(vad_engine));
+    let vad_engines = DeviceVads::new(
+        &VadSettings {
+            engine: vad_engine,
+            sensitivity: vad_sensitivity,
+            config: transcription_options.vad_config.clone(),
+        },
+        &transcription_options.device_vad,
+    )
+    .await?;
     let shutdown_flag = Arc::new(AtomicBool::new(false));
     let shutdown_flag_clone = shutdown_flag.clone();
     let output_path = output_path.to_path_buf();
@@ -190,110 +601,376 @@ pub async fn create_whisper_channel(
             .ok_or_else(|| anyhow!("Invalid embedding model path"))?,
     )?));
 
+    let audio_tagger = match transcription_options.audio_tagging {
+        AudioTagging::Off => None,
+        AudioTagging::Label | AudioTagging::SkipMusic => {
+            match tokio::task::spawn_blocking(AudioTagger::new).await? {
+                Ok(tagger) => Some(tagger),
+                Err(e) => {
+                    error!("failed to load the audio tagging model: {:?}", e);
+                    None
+                }
+            }
+        }
+    };
+
+    // shared by every chunk so a speaker keeps the same id for the whole session
     let embedding_manager = EmbeddingManager::new(usize::MAX);
 
+    let mut duplicate_detector = transcription_options
+        .duplicate_detection
+        .map(DuplicateDetector::new);
+    let mut stitcher = ChunkStitcher::new(transcription_options.chunk_overlap);
+    let mut noise_gates = transcription_options.noise_gate.map(NoiseGates::new);
+
+    let mut archive = match &transcription_options.archive {
+        Some(config) => match AudioArchive::new(config.clone()) {
+            Ok(archive) => Some(archive),
+            Err(e) => {
+                error!("failed to open the audio archive: {:?}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
     tokio::spawn(async move {
         loop {
             if shutdown_flag_clone.load(Ordering::Relaxed) {
                 info!("Whisper channel shutting down");
                 break;
             }
-            debug!("Waiting for input from input_receiver");
-
-            crossbeam::select! {
-                recv(input_receiver) -> input_result => {
-                    match input_result {
-                        Ok(mut audio) => {
-                            // Check if device should be recording
-                            if let Some(control) = audio_devices_control.as_ref().unwrap().get(&audio.device) {
-                                if !control.is_running {
-                                    debug!("Skipping audio processing for stopped device: {}", audio.device);
-                                    continue;
-                                }
-                            } else {
-                                debug!("Device not found in control list: {}", audio.device);
-                                continue;
+            if let Some(idle) = model_options.unload_after_idle {
+                if whisper_model.is_some() && last_transcription.elapsed() >= idle {
+                    info!(
+                        "nothing transcribed for {:?}, unloading the transcription model",
+                        idle
+                    );
+                    whisper_model = None;
+                }
+            }
+            if lanes.is_empty() {
+                debug!("Waiting for input from input_receiver");
+                crossbeam::select! {
+                    recv(input_receiver) -> input_result => {
+                        match input_result {
+                            Ok(input) => lanes.push(input.lane(), input),
+                            Err(e) => {
+                                error!("Error receiving input: {:?}", e);
+                                break;
                             }
-
-                            debug!("Received input from input_receiver");
-                            let timestamp = SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .expect("Time went backwards")
-                                .as_secs();
-
-                            let audio_data = if audio.sample_rate != m::SAMPLE_RATE as u32 {
-                                match resample(
-                                    audio.data.as_ref(),
-                                    audio.sample_rate,
-                                    m::SAMPLE_RATE as u32,
-                                ) {
-                                    Ok(data) => data,
-                                    Err(e) => {
-                                        error!("Error resampling audio: {:?}", e);
-                                        continue;
-                                    }
-                                }
-                            } else {
-                                audio.data.as_ref().to_vec()
-                            };
-
-                            audio.data = Arc::new(audio_data.clone());
-                            audio.sample_rate = m::SAMPLE_RATE as u32;
-
-                            let mut segments = match prepare_segments(&audio_data, vad_engine.clone(), &segmentation_model_path, embedding_manager.clone(), embedding_extractor.clone(), &audio.device.to_string()).await {
-                                Ok(segments) => segments,
-                                Err(e) => {
-                                    error!("Error preparing segments: {:?}", e);
-                                    continue;
-                                }
-                            };
-
-                            let path = match write_audio_to_file(
-                                &audio.data.to_vec(),
-                                audio.sample_rate,
-                                &output_path,
-                                &audio.device.to_string(),
-                                false,
-                            ) {
-                                Ok(file_path) => file_path,
-                                Err(e) => {
-                                    error!("Error writing audio to file: {:?}", e);
-                                    "".to_string()
+                        }
+                    },
+                    // wake up regularly to notice shutdown and idleness while no audio comes in
+                    default(Duration::from_secs(5)) => {
+                        if let (Some(queue), Some(model)) = (offline_queue.as_mut().filter(|q| !q.is_empty()), whisper_model.as_deref()) {
+                            let results = drain_offline_queue(queue, &mut model.clone(), audio_transcription_engine.clone(), deepgram_api_key.clone(), languages.clone(), &transcription_options, batch_size);
+                            for (mut transcription_result, vad) in results {
+                                let options = transcription_options.for_device(&transcription_result.input.device);
+                                let min_speech_ratio = vad_engines.for_device(&transcription_result.input.device).lock().await.get_min_speech_ratio();
+                                options.hallucination_filter.apply(&mut transcription_result, vad.as_ref(), min_speech_ratio);
+                                set_local_model_version(&mut transcription_result, &model_options.source);
+                                if let Some(spotter) = &options.keyword_spotter {
+                                    spotter.spot_result(&transcription_result);
                                 }
-                            };
-
-                            while let Some(segment) = segments.recv().await {
-                                let path = path.clone();
-                                let transcription_result = if cfg!(target_os = "macos") {
-                                    #[cfg(target_os = "macos")]
-                                    {
-                                        let timestamp = timestamp + segment.start.round() as u64;
-                                        autoreleasepool(|| {
-                                            run_stt(segment, audio.device.clone(), &mut whisper_model, audio_transcription_engine.clone(), deepgram_api_key.clone(), languages.clone(), path, timestamp)
-                                        })
-                                    }
-                                    #[cfg(not(target_os = "macos"))]
-                                    {
-                                        unreachable!("This code should not be reached on non-macOS platforms")
-                                    }
-                                } else {
-                                    run_stt(segment, audio.device.clone(), &mut whisper_model, audio_transcription_engine.clone(), deepgram_api_key.clone(), languages.clone(), path, timestamp)
-                                };
-
                                 if output_sender.send(transcription_result).is_err() {
                                     break;
                                 }
                             }
-                        },
+                        }
+                        continue;
+                    },
+                }
+            }
+            // what piled up meanwhile waits in its lane, live audio goes ahead of the backfill
+            // unless that has been held back too long. Past the lanes' capacity it stays in the
+            // bounded channel, holding back the recorders and the imports
+            while !lanes.is_full() {
+                let Ok(input) = input_receiver.try_recv() else {
+                    break;
+                };
+                lanes.push(input.lane(), input);
+            }
+            if lanes.len() > batch_size {
+                debug!("{} chunks waiting for transcription", lanes.len());
+            }
+            // chunks of other devices in the same lane are transcribed together
+            let batch = lanes.next_batch(batch_size);
+            let journal_ids: Vec<u64> = batch.iter().filter_map(|input| input.journal_id).collect();
+
+            let mut jobs = Vec::new();
+            for JournaledInput {
+                mut audio,
+                recovered,
+                ..
+            } in batch
+            {
+                // Check if device should be recording, chunks recovered from the journal were recorded already
+                if recovered {
+                    debug!(
+                        "Transcribing chunk of {} recovered from the audio journal",
+                        audio.device
+                    );
+                } else if let Some(control) =
+                    audio_devices_control.as_ref().unwrap().get(&audio.device)
+                {
+                    if !control.is_running {
+                        debug!(
+                            "Skipping audio processing for stopped device: {}",
+                            audio.device
+                        );
+                        continue;
+                    }
+                } else if audio.device.host.as_deref() != Some(FILE_AUDIO_HOST) {
+                    debug!("Device not found in control list: {}", audio.device);
+                    continue;
+                }
+
+                debug!("Received input from input_receiver");
+                // prefer when the audio was captured over when it reached us
+                let timestamp = audio
+                    .capture_timing
+                    .as_ref()
+                    .and_then(|t| t.started_at())
+                    .unwrap_or_else(SystemTime::now)
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Time went backwards")
+                    .as_secs();
+
+                let audio_data = if audio.sample_rate != m::SAMPLE_RATE as u32 {
+                    match resample(
+                        audio.data.as_ref(),
+                        audio.sample_rate,
+                        m::SAMPLE_RATE as u32,
+                    ) {
+                        Ok(data) => data,
                         Err(e) => {
-                            error!("Error receiving input: {:?}", e);
-                            // Depending on the error type, you might want to break the loop or continue
-                            // For now, we'll continue to the next iteration
-                            break;
+                            error!("Error resampling audio: {:?}", e);
+                            continue;
                         }
                     }
-                },
+                } else {
+                    audio.data.as_ref().to_vec()
+                };
+
+                let audio_data = match noise_gates.as_mut() {
+                    Some(gates) => gates.process(&audio.device.to_string(), &audio_data),
+                    None => audio_data,
+                };
+                let mut audio_data = transcription_options
+                    .audio_pipeline
+                    .process(audio_data, m::SAMPLE_RATE as u32);
+                stitcher.stitch(
+                    &audio.device.to_string(),
+                    &mut audio_data,
+                    m::SAMPLE_RATE as u32,
+                    audio.capture_timing.as_ref().and_then(|t| t.started_at()),
+                );
+
+                audio.data = Arc::new(audio_data.clone());
+                audio.sample_rate = m::SAMPLE_RATE as u32;
+
+                if let Some(archive) = archive.as_mut() {
+                    let captured_at = audio
+                        .capture_timing
+                        .as_ref()
+                        .and_then(|t| t.started_at())
+                        .unwrap_or_else(SystemTime::now);
+                    if let Err(e) = archive.append(
+                        &audio.device.to_string(),
+                        &audio_data,
+                        audio.sample_rate,
+                        captured_at.into(),
+                    ) {
+                        error!("Error archiving audio: {:?}", e);
+                    }
+                }
+
+                let audio_events = match &audio_tagger {
+                    Some(tagger) => tagger.tag(&audio_data).unwrap_or_else(|e| {
+                        error!("Error tagging audio: {:?}", e);
+                        Vec::new()
+                    }),
+                    None => Vec::new(),
+                };
+                if transcription_options.audio_tagging == AudioTagging::SkipMusic
+                    && is_music_only(&audio_events)
+                {
+                    debug!("device: {}, skipping chunk of music", audio.device);
+                    continue;
+                }
+
+                let mut segments = match prepare_segments_at(
+                    &audio_data,
+                    vad_engines.for_device(&audio.device).clone(),
+                    &segmentation_model_path,
+                    embedding_manager.clone(),
+                    embedding_extractor.clone(),
+                    &audio.device.to_string(),
+                    audio.capture_timing.as_ref().and_then(|t| t.started_at()),
+                    transcription_options.segment_padding,
+                )
+                .await
+                {
+                    Ok(segments) => segments,
+                    Err(e) => {
+                        error!("Error preparing segments: {:?}", e);
+                        continue;
+                    }
+                };
+
+                let tags = AudioTags {
+                    device: audio.device.to_string(),
+                    captured_at: audio
+                        .capture_timing
+                        .as_ref()
+                        .and_then(|t| t.started_at())
+                        .map(Into::into),
+                    engine: Some(audio_transcription_engine.to_string()),
+                };
+                let path = match write_audio_to_file(
+                    &audio.data.to_vec(),
+                    audio.sample_rate,
+                    &output_path,
+                    &audio.device.to_string(),
+                    false,
+                    transcription_options.audio_codec,
+                    Some(&tags),
+                ) {
+                    Ok(file_path) => file_path,
+                    Err(e) => {
+                        error!("Error writing audio to file: {:?}", e);
+                        "".to_string()
+                    }
+                };
+
+                let options = transcription_options.for_device(&audio.device);
+                while let Some(segment) = segments.recv().await {
+                    jobs.push(SegmentJob {
+                        device: audio.device.clone(),
+                        path: path.clone(),
+                        timestamp: if cfg!(target_os = "macos") {
+                            timestamp + segment.start.round() as u64
+                        } else {
+                            timestamp
+                        },
+                        captured_at: audio
+                            .capture_timing
+                            .as_ref()
+                            .and_then(|t| t.time_at(segment.start)),
+                        captured_until: audio
+                            .capture_timing
+                            .as_ref()
+                            .and_then(|t| t.time_at(segment.end)),
+                        vad: if options.hallucination_filter != HallucinationFilter::Off {
+                            Some(
+                                hallucination::vad_decisions(
+                                    &segment.samples,
+                                    vad_engines.for_device(&audio.device),
+                                )
+                                .await,
+                            )
+                        } else {
+                            None
+                        },
+                        audio_events: audio_events.clone(),
+                        segment,
+                    });
+                }
             }
+
+            if let Some(detector) = duplicate_detector.as_mut() {
+                drop_duplicates(detector, &mut jobs);
+            }
+            if jobs.is_empty() {
+                forget_journaled(journal.as_ref(), &journal_ids);
+                continue;
+            }
+            if whisper_model.is_none() {
+                info!("reloading the transcription model");
+                match WhisperModel::shared(&local_engine, &model_options) {
+                    Ok(model) => whisper_model = Some(model),
+                    Err(e) => {
+                        error!("failed to reload the transcription model: {:?}", e);
+                        continue;
+                    }
+                }
+            }
+            let Some(mut model) = whisper_model.as_deref().cloned() else {
+                continue;
+            };
+            last_transcription = Instant::now();
+
+            let mut results = if cfg!(target_os = "macos") {
+                #[cfg(target_os = "macos")]
+                {
+                    autoreleasepool(|| {
+                        run_stt_batch(
+                            jobs,
+                            &mut model,
+                            audio_transcription_engine.clone(),
+                            deepgram_api_key.clone(),
+                            languages.clone(),
+                            &transcription_options,
+                            batch_size,
+                            offline_queue.as_mut(),
+                        )
+                    })
+                }
+                #[cfg(not(target_os = "macos"))]
+                {
+                    unreachable!("This code should not be reached on non-macOS platforms")
+                }
+            } else {
+                run_stt_batch(
+                    jobs,
+                    &mut model,
+                    audio_transcription_engine.clone(),
+                    deepgram_api_key.clone(),
+                    languages.clone(),
+                    &transcription_options,
+                    batch_size,
+                    offline_queue.as_mut(),
+                )
+            };
+            // catch up on what was queued while offline, unless this batch just got queued too
+            if let Some(queue) = offline_queue
+                .as_mut()
+                .filter(|q| !q.is_empty() && !results.is_empty())
+            {
+                results.extend(drain_offline_queue(
+                    queue,
+                    &mut model,
+                    audio_transcription_engine.clone(),
+                    deepgram_api_key.clone(),
+                    languages.clone(),
+                    &transcription_options,
+                    batch_size,
+                ));
+            }
+
+            for (mut transcription_result, vad) in results {
+                let options = transcription_options.for_device(&transcription_result.input.device);
+                let min_speech_ratio = vad_engines
+                    .for_device(&transcription_result.input.device)
+                    .lock()
+                    .await
+                    .get_min_speech_ratio();
+                options.hallucination_filter.apply(
+                    &mut transcription_result,
+                    vad.as_ref(),
+                    min_speech_ratio,
+                );
+                set_local_model_version(&mut transcription_result, &model_options.source);
+                if let Some(spotter) = &options.keyword_spotter {
+                    spotter.spot_result(&transcription_result);
+                }
+
+                if output_sender.send(transcription_result).is_err() {
+                    break;
+                }
+            }
+            forget_journaled(journal.as_ref(), &journal_ids);
         }
         // Cleanup code here (if needed)
     });
@@ -309,91 +986,709 @@ pub fn run_stt(
     audio_transcription_engine: Arc<AudioTranscriptionEngine>,
     deepgram_api_key: Option<String>,
     languages: Vec<Language>,
+    options: &TranscriptionOptions,
     path: String,
     timestamp: u64,
 ) -> TranscriptionResult {
-    let audio = segment.samples.clone();
-    let sample_rate = segment.sample_rate;
-    match stt_sync(
-        &audio,
-        sample_rate,
+    let transcript = stt_sync(
+        &segment.samples,
+        segment.sample_rate,
         &device.to_string(),
         whisper_model,
         audio_transcription_engine.clone(),
         deepgram_api_key.clone(),
         languages.clone(),
-    ) {
+        options,
+    );
+    transcription_result(segment, device, path, timestamp, transcript)
+}
+
+fn transcription_result(
+    segment: SpeechSegment,
+    device: Arc<AudioDevice>,
+    path: String,
+    timestamp: u64,
+    transcript: Result<Transcript>,
+) -> TranscriptionResult {
+    let sample_rate = segment.sample_rate;
+    let audio_duration = Duration::from_secs_f64((segment.end - segment.start).max(0.0));
+    let device_type = device.device_type.clone();
+    match transcript {
         Ok(transcription) => TranscriptionResult {
             input: AudioInput {
-                data: Arc::new(audio),
+                data: Arc::new(segment.samples),
                 sample_rate,
                 channels: 1,
                 device: device.clone(),
+                capture_timing: None,
             },
-            transcription: Some(transcription),
+            transcription: Some(transcription.text),
             path,
             timestamp,
             error: None,
-            speaker_embedding: segment.embedding.clone(),
+            speaker_embedding: segment.embedding,
+            speaker_id: segment.speaker.parse().ok(),
             start_time: segment.start,
             end_time: segment.end,
+            audio_duration,
+            device_type,
+            captured_at: None,
+            captured_until: None,
+            latency: None,
+            model_version: transcription.model_version(),
+            engine: transcription.engine,
+            is_final: true,
+            confidence: transcription.confidence,
+            language: transcription.language,
+            hallucination: None,
+            audio_events: Vec::new(),
+            text_segments: transcription.segments,
         },
         Err(e) => {
             error!("STT error for input {}: {:?}", device, e);
             TranscriptionResult {
                 input: AudioInput {
                     data: Arc::new(segment.samples),
-                    sample_rate: segment.sample_rate,
+                    sample_rate,
                     channels: 1,
                     device: device.clone(),
+                    capture_timing: None,
                 },
                 transcription: None,
                 path,
                 timestamp,
                 error: Some(e.to_string()),
                 speaker_embedding: Vec::new(),
+                speaker_id: segment.speaker.parse().ok(),
                 start_time: segment.start,
                 end_time: segment.end,
+                audio_duration,
+                device_type,
+                captured_at: None,
+                captured_until: None,
+                latency: None,
+                engine: None,
+                model_version: None,
+                is_final: true,
+                confidence: None,
+                language: None,
+                hallucination: None,
+                audio_events: Vec::new(),
+                text_segments: Vec::new(),
             }
         }
     }
 }
 
-pub fn longest_common_word_substring(s1: &str, s2: &str) -> Option<(usize, usize)> {
-    let s1 = s1.to_lowercase();
-    let s2 = s2.to_lowercase();
+/// Results of local engines running a model loaded from a path rather than their preset
+fn set_local_model_version(result: &mut TranscriptionResult, source: &ModelSource) {
+    if let (ModelSource::Path(path), Some(engine)) = (source, &result.engine) {
+        if !engine.is_cloud() {
+            result.model_version = Some(path.display().to_string());
+        }
+    }
+}
+
+/// A chunk received by the whisper channel
+struct JournaledInput {
+    audio: AudioInput,
+    /// Id in the audio journal, until transcribed
+    journal_id: Option<u64>,
+    /// Left in the journal by a previous run
+    recovered: bool,
+}
+
+impl JournaledInput {
+    /// Live audio goes ahead of audio recovered from the journal or imported from files
+    fn lane(&self) -> Lane {
+        if self.recovered || self.audio.device.host.as_deref() == Some(FILE_AUDIO_HOST) {
+            Lane::Backfill
+        } else {
+            Lane::Realtime
+        }
+    }
+}
+
+/// Forwards the chunks sent to the whisper channel once they are in the journal, after the ones a
+/// previous run left there
+fn journal_inputs(
+    input_receiver: crossbeam::channel::Receiver<AudioInput>,
+    journal: Option<Arc<StdMutex<AudioJournal>>>,
+) -> crossbeam::channel::Receiver<JournaledInput> {
+    let (sender, receiver) = crossbeam::channel::bounded(1000);
+    std::thread::spawn(move || {
+        if let Some(journal) = &journal {
+            let pending = journal.lock().map(|j| j.pending()).unwrap_or_default();
+            if !pending.is_empty() {
+                info!(
+                    "resuming transcription of {} chunks from the audio journal",
+                    pending.len()
+                );
+            }
+            for id in pending {
+                let audio = journal
+                    .lock()
+                    .map_err(|e| anyhow!("{}", e))
+                    .and_then(|j| j.read(id));
+                let input = match audio {
+                    Ok(audio) => JournaledInput {
+                        audio,
+                        journal_id: Some(id),
+                        recovered: true,
+                    },
+                    Err(e) => {
+                        error!("dropping unreadable journaled chunk {}: {:?}", id, e);
+                        forget_journaled(Some(journal), &[id]);
+                        continue;
+                    }
+                };
+                if sender.send(input).is_err() {
+                    return;
+                }
+            }
+        }
+
+        for audio in input_receiver {
+            let journal_id = journal.as_ref().and_then(|journal| {
+                journal
+                    .lock()
+                    .map_err(|e| anyhow!("{}", e))
+                    .and_then(|mut j| j.append(&audio))
+                    .map_err(|e| error!("failed to journal chunk of {}: {:?}", audio.device, e))
+                    .ok()
+            });
+            let input = JournaledInput {
+                audio,
+                journal_id,
+                recovered: false,
+            };
+            if sender.send(input).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+/// Drops the jobs whose audio was heard on another device too, see `DuplicateDetector`. Jobs
+/// without a capture time are kept
+fn drop_duplicates(detector: &mut DuplicateDetector, jobs: &mut Vec<SegmentJob>) {
+    let devices: Vec<String> = jobs.iter().map(|job| job.device.to_string()).collect();
+    let (indices, candidates): (Vec<usize>, Vec<Candidate>) = jobs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, job)| {
+            let candidate = Candidate {
+                device: &devices[i],
+                samples: &job.segment.samples,
+                captured_at: job.captured_at?,
+            };
+            Some((i, candidate))
+        })
+        .unzip();
+    let duplicates: Vec<usize> = detector
+        .duplicates(&candidates)
+        .into_iter()
+        .map(|i| indices[i])
+        .collect();
+    if duplicates.is_empty() {
+        return;
+    }
+    let mut i = 0;
+    jobs.retain(|_| {
+        let keep = !duplicates.contains(&i);
+        i += 1;
+        keep
+    });
+}
+
+/// Removes chunks done with from the journal
+fn forget_journaled(journal: Option<&Arc<StdMutex<AudioJournal>>>, ids: &[u64]) {
+    let Some(journal) = journal else {
+        return;
+    };
+    if ids.is_empty() {
+        return;
+    }
+    match journal.lock() {
+        Ok(mut journal) => {
+            if let Err(e) = journal.remove(ids) {
+                error!(
+                    "failed to remove transcribed chunks from the audio journal: {:?}",
+                    e
+                );
+            }
+        }
+        Err(e) => error!("audio journal lock poisoned: {}", e),
+    }
+}
+
+/// A speech segment of a chunk received by the whisper channel, waiting to be transcribed
+struct SegmentJob {
+    segment: SpeechSegment,
+    device: Arc<AudioDevice>,
+    path: String,
+    timestamp: u64,
+    captured_at: Option<SystemTime>,
+    captured_until: Option<SystemTime>,
+    /// What the VAD decided on each frame of the segment, for the hallucination filter
+    vad: Option<VadDecisions>,
+    audio_events: Vec<AudioLabel>,
+}
+
+/// A `SegmentJob` waiting in the offline queue, its samples are stored next to it
+#[derive(Serialize, Deserialize)]
+struct QueuedSegment {
+    device: AudioDevice,
+    path: String,
+    timestamp: u64,
+    captured_at: Option<SystemTime>,
+    #[serde(default)]
+    captured_until: Option<SystemTime>,
+    #[serde(default)]
+    vad: Option<VadDecisions>,
+    #[serde(default)]
+    audio_events: Vec<AudioLabel>,
+    start: f64,
+    end: f64,
+    speaker: String,
+    embedding: Vec<f32>,
+    sample_rate: u32,
+}
+
+/// Transcribes the segments of the chunks the whisper channel received together, in order,
+/// returning them with the VAD decisions on their audio.
+///
+/// With a `batch_size` above 1, the local whisper engines get the segments of devices sharing the
+/// same transcription options in batches of up to `batch_size`, which candle decodes in a single
+/// forward pass per token on GPU. Cloud engines transcribe them one by one; segments none of
+/// them could be reached for go to the `offline_queue` and are left out of the results, or to the
+/// local model once it is full.
+#[allow(clippy::too_many_arguments)]
+fn run_stt_batch(
+    jobs: Vec<SegmentJob>,
+    whisper_model: &mut WhisperModel,
+    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
+    deepgram_api_key: Option<String>,
+    languages: Vec<Language>,
+    transcription_options: &DeviceTranscriptionOptions,
+    batch_size: usize,
+    mut offline_queue: Option<&mut DiskQueue<QueuedSegment>>,
+) -> Vec<(TranscriptionResult, Option<VadDecisions>)> {
+    let jobs = filter_languages(jobs, whisper_model, transcription_options);
+    if batch_size <= 1 || jobs.len() <= 1 || audio_transcription_engine.is_cloud() {
+        return jobs
+            .into_iter()
+            .filter_map(|job| {
+                let options = transcription_options.for_device(&job.device);
+                let transcript = match stt_sync(
+                    &job.segment.samples,
+                    job.segment.sample_rate,
+                    &job.device.to_string(),
+                    whisper_model,
+                    audio_transcription_engine.clone(),
+                    deepgram_api_key.clone(),
+                    languages.clone(),
+                    options,
+                ) {
+                    Err(e) if e.is::<CloudUnreachable>() => {
+                        if let Some(queue) = offline_queue.as_deref_mut() {
+                            match queue.push(&job.segment.samples, &QueuedSegment::new(&job)) {
+                                Ok(true) => {
+                                    debug!(
+                                        "device: {}, segment queued until a cloud engine is back",
+                                        job.device
+                                    );
+                                    return None;
+                                }
+                                Ok(false) => {}
+                                Err(e) => error!("failed to queue segment: {:?}", e),
+                            }
+                        }
+                        process_with_whisper(
+                            whisper_model,
+                            &job.segment.samples,
+                            &LanguageHint::from_languages(&languages),
+                            options.task,
+                            options.prompt.as_deref(),
+                            &options.decoding,
+                        )
+                        .map(|transcript| Transcript {
+                            engine: Some(options.local_engine(&audio_transcription_engine)),
+                            ..transcript
+                        })
+                    }
+                    transcript => transcript,
+                };
+                let mut result = transcription_result(
+                    job.segment,
+                    job.device,
+                    job.path,
+                    job.timestamp,
+                    transcript,
+                );
+                result.set_capture_time(job.captured_at, job.captured_until);
+                result.audio_events = job.audio_events;
+                Some((result, job.vad))
+            })
+            .collect();
+    }
 
-    let s1 = s1.replace(|c| char::is_ascii_punctuation(&c), "");
-    let s2 = s2.replace(|c| char::is_ascii_punctuation(&c), "");
+    // only segments decoded with the same task, prompt and decoding options can share a batch
+    let mut groups: Vec<(&TranscriptionOptions, Vec<usize>)> = Vec::new();
+    for (i, job) in jobs.iter().enumerate() {
+        let options = transcription_options.for_device(&job.device);
+        match groups.iter_mut().find(|(o, _)| *o == options) {
+            Some((_, indices)) => indices.push(i),
+            None => groups.push((options, vec![i])),
+        }
+    }
 
-    let s1_words: Vec<&str> = s1.split_whitespace().collect();
-    let s2_words: Vec<&str> = s2.split_whitespace().collect();
+    let language = LanguageHint::from_languages(&languages);
+    let mut transcripts: Vec<Option<Result<Transcript>>> = jobs.iter().map(|_| None).collect();
+    for (options, indices) in groups {
+        for batch in indices.chunks(batch_size) {
+            let audios: Vec<&[f32]> = batch
+                .iter()
+                .map(|&i| jobs[i].segment.samples.as_slice())
+                .collect();
+            debug!("transcribing a batch of {} segments", audios.len());
+            let results = process_batch_with_whisper(
+                whisper_model,
+                &audios,
+                &language,
+                options.task,
+                options.prompt.as_deref(),
+                &options.decoding,
+            );
+            for (&i, result) in batch.iter().zip(results) {
+                transcripts[i] = Some(result.map(|transcript| Transcript {
+                    engine: Some(audio_transcription_engine.as_ref().clone()),
+                    ..transcript
+                }));
+            }
+        }
+    }
 
-    let s1_len = s1_words.len();
-    let s2_len = s2_words.len();
+    jobs.into_iter()
+        .zip(transcripts)
+        .map(|(job, transcript)| {
+            let transcript =
+                transcript.unwrap_or_else(|| Err(anyhow!("segment was not transcribed")));
+            let mut result =
+                transcription_result(job.segment, job.device, job.path, job.timestamp, transcript);
+            result.set_capture_time(job.captured_at, job.captured_until);
+            result.audio_events = job.audio_events;
+            (result, job.vad)
+        })
+        .collect()
+}
 
-    // Table to store lengths of longest common suffixes of word substrings
-    let mut dp = vec![vec![0; s2_len + 1]; s1_len + 1];
+/// Drops the segments identified as spoken in a language the `LanguageFilter` of their device
+/// doesn't allow, before they cost a decode or a cloud request
+fn filter_languages(
+    jobs: Vec<SegmentJob>,
+    whisper_model: &mut WhisperModel,
+    transcription_options: &DeviceTranscriptionOptions,
+) -> Vec<SegmentJob> {
+    jobs.into_iter()
+        .filter(|job| {
+            let Some(filter) = &transcription_options
+                .for_device(&job.device)
+                .language_filter
+            else {
+                return true;
+            };
+            match identify_language(whisper_model, &job.segment.samples) {
+                Ok(Some(language)) if !filter.allows(&language) => {
+                    debug!(
+                        "device: {}, skipping segment spoken in {} ({:.2})",
+                        job.device, language.code, language.probability
+                    );
+                    false
+                }
+                Ok(_) => true,
+                Err(e) => {
+                    warn!("failed to identify the language of a segment: {:?}", e);
+                    true
+                }
+            }
+        })
+        .collect()
+}
 
-    let mut max_len = 0;
-    let mut max_index_s1 = None; // Store the starting word index of the longest substring in s1
-    let mut max_index_s2 = None; // Store the starting word index of the longest substring in s2
+impl QueuedSegment {
+    fn new(job: &SegmentJob) -> Self {
+        Self {
+            device: job.device.as_ref().clone(),
+            path: job.path.clone(),
+            timestamp: job.timestamp,
+            captured_at: job.captured_at,
+            captured_until: job.captured_until,
+            vad: job.vad.clone(),
+            audio_events: job.audio_events.clone(),
+            start: job.segment.start,
+            end: job.segment.end,
+            speaker: job.segment.speaker.clone(),
+            embedding: job.segment.embedding.clone(),
+            sample_rate: job.segment.sample_rate,
+        }
+    }
+}
 
-    for i in 1..=s1_len {
-        for j in 1..=s2_len {
-            if s1_words[i - 1] == s2_words[j - 1] {
-                dp[i][j] = dp[i - 1][j - 1] + 1;
-                if dp[i][j] > max_len {
-                    max_len = dp[i][j];
-                    max_index_s1 = Some(i - max_len); // The start index of the match in s1
-                    max_index_s2 = Some(j - max_len); // The start index of the match in s2
+/// Transcribes up to `max` of the oldest queued segments, stopping at the first one the cloud
+/// engines still can't be reached for
+#[allow(clippy::too_many_arguments)]
+fn drain_offline_queue(
+    queue: &mut DiskQueue<QueuedSegment>,
+    whisper_model: &mut WhisperModel,
+    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
+    deepgram_api_key: Option<String>,
+    languages: Vec<Language>,
+    transcription_options: &DeviceTranscriptionOptions,
+    max: usize,
+) -> Vec<(TranscriptionResult, Option<VadDecisions>)> {
+    let mut results = Vec::new();
+    while results.len() < max {
+        let queued = match queue.front() {
+            Ok(Some(queued)) => queued,
+            Ok(None) => break,
+            Err(e) => {
+                error!("dropping unreadable queued segment: {:?}", e);
+                if let Some(id) = queue.front_id() {
+                    let _ = queue.remove(id);
                 }
+                continue;
             }
+        };
+        let meta = queued.meta;
+        let transcript = stt_sync(
+            &queued.samples,
+            meta.sample_rate,
+            &meta.device.to_string(),
+            whisper_model,
+            audio_transcription_engine.clone(),
+            deepgram_api_key.clone(),
+            languages.clone(),
+            transcription_options.for_device(&meta.device),
+        );
+        if transcript
+            .as_ref()
+            .is_err_and(|e| e.is::<CloudUnreachable>())
+        {
+            break;
         }
+        if let Err(e) = queue.remove(queued.id) {
+            error!("failed to remove queued segment: {:?}", e);
+        }
+        let segment = SpeechSegment {
+            start: meta.start,
+            end: meta.end,
+            samples: queued.samples,
+            speaker: meta.speaker,
+            embedding: meta.embedding,
+            sample_rate: meta.sample_rate,
+        };
+        let mut result = transcription_result(
+            segment,
+            Arc::new(meta.device),
+            meta.path,
+            meta.timestamp,
+            transcript,
+        );
+        result.set_capture_time(meta.captured_at, meta.captured_until);
+        result.audio_events = meta.audio_events;
+        results.push((result, meta.vad));
+    }
+    if !results.is_empty() {
+        info!(
+            "transcribed {} queued segments, {} left",
+            results.len(),
+            queue.len()
+        );
     }
+    results
+}
+
+/// Sliding window of streaming transcription
+#[derive(Clone, Debug)]
+pub struct StreamingConfig {
+    /// Amount of new audio after which the window is transcribed again
+    pub step: Duration,
+    /// Window length at which the transcription is finalized and a new window starts
+    pub window: Duration,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            step: Duration::from_secs(1),
+            window: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Below this RMS a stretch of audio is considered silent
+const SILENCE_RMS: f32 = 0.01;
+
+/// Transcribes a live stream incrementally instead of waiting for a full chunk.
+///
+/// Every `step` of new audio the current window is transcribed and passed to `on_result` as a
+/// partial result (`is_final: false`), each one replacing the previous. The window is finalized
+/// once it reaches `window` or the speaker pauses for a step, then starts over.
+#[allow(clippy::too_many_arguments)]
+pub async fn stream_transcription(
+    mut stream: broadcast::Receiver<AudioBlock>,
+    device: Arc<AudioDevice>,
+    sample_rate: u32,
+    whisper_model: Arc<WhisperModel>,
+    audio_transcription_engine: Arc<AudioTranscriptionEngine>,
+    languages: Vec<Language>,
+    options: TranscriptionOptions,
+    is_running: Arc<AtomicBool>,
+    config: StreamingConfig,
+    mut on_result: impl FnMut(TranscriptionResult),
+) -> Result<()> {
+    let step_samples = (config.step.as_secs_f64() * sample_rate as f64) as usize;
+    let window_samples = (config.window.as_secs_f64() * sample_rate as f64) as usize;
+    let mut window: Vec<f32> = Vec::with_capacity(window_samples);
+    let mut timing: Option<CaptureTiming> = None;
+    let mut pending = 0;
+
+    while is_running.load(Ordering::Relaxed) {
+        // wake up regularly to notice is_running going false
+        let block = match tokio::time::timeout(Duration::from_millis(100), stream.recv()).await {
+            Ok(Ok(block)) => block,
+            Ok(Err(broadcast::error::RecvError::Lagged(n))) => {
+                warn!(
+                    "streaming transcription of {} lagged by {} blocks",
+                    device, n
+                );
+                continue;
+            }
+            Ok(Err(broadcast::error::RecvError::Closed)) => break,
+            Err(_) => continue,
+        };
+
+        timing
+            .get_or_insert_with(|| {
+                CaptureTiming::new(block.captured_at - block.stream_offset, sample_rate)
+            })
+            .push_block(window.len(), block.stream_offset);
+        pending += block.data.len();
+        window.extend_from_slice(&block.data);
+        if pending < step_samples {
+            continue;
+        }
+        pending = 0;
+
+        if rms(&window) < SILENCE_RMS {
+            // nothing said yet, whisper would only hallucinate on it
+            window.clear();
+            timing = None;
+            continue;
+        }
+
+        let last_step = &window[window.len().saturating_sub(step_samples)..];
+        let is_final = window.len() >= window_samples
+            || (window.len() >= 2 * step_samples && rms(last_step) < SILENCE_RMS);
+
+        let audio = Arc::new(if sample_rate != m::SAMPLE_RATE as u32 {
+            resample(&window, sample_rate, m::SAMPLE_RATE as u32)?
+        } else {
+            window.clone()
+        });
+        let end_time = window.len() as f64 / sample_rate as f64;
+        // inference blocks, it runs off the async workers with a clone of the shared model
+        let transcript = {
+            let (audio, device, whisper_model, audio_transcription_engine, languages, options) = (
+                audio.clone(),
+                device.to_string(),
+                whisper_model.clone(),
+                audio_transcription_engine.clone(),
+                languages.clone(),
+                options.clone(),
+            );
+            let runtime = tokio::runtime::Handle::current();
+            tokio::task::spawn_blocking(move || {
+                runtime.block_on(transcribe(
+                    &audio,
+                    m::SAMPLE_RATE as u32,
+                    &device,
+                    &mut WhisperModel::clone(&whisper_model),
+                    audio_transcription_engine,
+                    None,
+                    languages,
+                    &options,
+                ))
+            })
+            .await
+            .unwrap_or_else(|e| Err(e.into()))
+        };
+        let (model_version, transcription, confidence, language, engine, error) = match transcript {
+            Ok(transcript) => (
+                transcript.model_version(),
+                Some(transcript.text),
+                transcript.confidence,
+                transcript.language,
+                transcript.engine,
+                None,
+            ),
+            Err(e) => (None, None, None, None, None, Some(e.to_string())),
+        };
+        let captured_at = timing.as_ref().and_then(|t| t.started_at());
+        let captured_until = timing.as_ref().and_then(|t| t.time_at(end_time));
+
+        let mut result = TranscriptionResult {
+            path: String::new(),
+            input: AudioInput {
+                data: audio,
+                sample_rate: m::SAMPLE_RATE as u32,
+                channels: 1,
+                device: device.clone(),
+                capture_timing: timing.clone(),
+            },
+            speaker_embedding: Vec::new(),
+            speaker_id: None,
+            transcription,
+            timestamp: captured_at
+                .unwrap_or_else(SystemTime::now)
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            error,
+            start_time: 0.0,
+            end_time,
+            audio_duration: Duration::from_secs_f64(end_time),
+            device_type: device.device_type.clone(),
+            captured_at,
+            captured_until,
+            latency: captured_until.and_then(|until| until.elapsed().ok()),
+            model_version,
+            engine,
+            is_final,
+            confidence,
+            language,
+            hallucination: None,
+            audio_events: Vec::new(),
+            text_segments: Vec::new(),
+        };
+        // the window was already checked to not be silent, so only the text is filtered
+        options.hallucination_filter.apply(&mut result, None, 0.0);
+        on_result(result);
+
+        if is_final {
+            window.clear();
+            timing = None;
+        }
+    }
+
+    Ok(())
+}
 
-    match (max_index_s1, max_index_s2) {
-        (Some(idx1), Some(idx2)) => Some((idx1, idx2)),
-        _ => None,
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
     }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
 }
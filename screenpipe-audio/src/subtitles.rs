@@ -0,0 +1,162 @@
+use crate::TranscriptionResult;
+use std::fmt::Write;
+use std::time::{Duration, SystemTime};
+
+/// Longest subtitle line, lines are wrapped at word boundaries past it
+const MAX_LINE_CHARS: usize = 42;
+/// Shortest a cue is shown
+const MIN_CUE: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    WebVtt,
+}
+
+impl SubtitleFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::WebVtt => "vtt",
+        }
+    }
+}
+
+/// A subtitle, timed from the origin of `cues`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    pub text: String,
+    pub speaker: Option<usize>,
+}
+
+/// Cues of the final, non empty transcriptions, in order. They are timed with the wall clock
+/// capture time when known, so results of several chunks line up, from `origin` or the earliest
+/// capture. Otherwise with the position of the segment in its chunk
+pub fn cues(results: &[TranscriptionResult], origin: Option<SystemTime>) -> Vec<Cue> {
+    let results: Vec<&TranscriptionResult> = results
+        .iter()
+        .filter(|result| result.is_final)
+        .filter(|result| {
+            result
+                .transcription
+                .as_deref()
+                .is_some_and(|text| !text.trim().is_empty())
+        })
+        .collect();
+    let origin = origin.or_else(|| results.iter().filter_map(|r| r.captured_at).min());
+
+    let mut cues: Vec<Cue> = results
+        .iter()
+        .map(|result| {
+            let (start, end) = match (origin, result.captured_at) {
+                (Some(origin), Some(captured_at)) => {
+                    let start = captured_at.duration_since(origin).unwrap_or_default();
+                    let end = match result.captured_until {
+                        Some(until) => until.duration_since(origin).unwrap_or_default(),
+                        None => start + result.audio_duration,
+                    };
+                    (start, end)
+                }
+                _ => (
+                    Duration::from_secs_f64(result.start_time.max(0.0)),
+                    Duration::from_secs_f64(result.end_time.max(0.0)),
+                ),
+            };
+            Cue {
+                start,
+                end: end.max(start + MIN_CUE),
+                text: result
+                    .transcription
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                speaker: result.speaker_id,
+            }
+        })
+        .collect();
+    cues.sort_by_key(|cue| cue.start);
+    cues
+}
+
+/// The final transcriptions of `results` as an SRT or WebVTT file, see `cues`
+pub fn to_subtitles(
+    results: &[TranscriptionResult],
+    origin: Option<SystemTime>,
+    format: SubtitleFormat,
+) -> String {
+    format_cues(&cues(results, origin), format)
+}
+
+pub fn format_cues(cues: &[Cue], format: SubtitleFormat) -> String {
+    let mut out = String::new();
+    if format == SubtitleFormat::WebVtt {
+        out.push_str("WEBVTT\n\n");
+    }
+    for (i, cue) in cues.iter().enumerate() {
+        let text = wrap(&cue.text);
+        match format {
+            SubtitleFormat::Srt => {
+                let _ = write!(
+                    out,
+                    "{}\n{} --> {}\n{}\n\n",
+                    i + 1,
+                    timestamp(cue.start, ','),
+                    timestamp(cue.end, ','),
+                    text
+                );
+            }
+            SubtitleFormat::WebVtt => {
+                let text = match cue.speaker {
+                    Some(speaker) => format!("<v Speaker {}>{}", speaker, escape_vtt(&text)),
+                    None => escape_vtt(&text),
+                };
+                let _ = write!(
+                    out,
+                    "{} --> {}\n{}\n\n",
+                    timestamp(cue.start, '.'),
+                    timestamp(cue.end, '.'),
+                    text
+                );
+            }
+        }
+    }
+    out
+}
+
+/// `hh:mm:ss,mmm` for SRT, `hh:mm:ss.mmm` for WebVTT
+fn timestamp(time: Duration, separator: char) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+fn wrap(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= MAX_LINE_CHARS => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines.join("\n")
+}
+
+/// WebVTT cue text can't hold raw `<`, `>` or `&`, nor `-->`
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -1,15 +1,19 @@
 use anyhow;
 use dirs;
 use lazy_static::lazy_static;
-use log::debug;
-use std::collections::VecDeque;
+use log::{debug, warn};
+use screenpipe_events::send_event;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Once;
+use std::time::Duration;
 use tokio::sync::Mutex;
 use tracing::info;
 use vad_rs::{Vad, VadStatus};
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum VadSensitivity {
     Low,
     #[default]
@@ -27,14 +31,147 @@ impl VadSensitivity {
     }
 }
 
+/// Tuning of the Silero VAD. Speech has to last `min_speech` to be reported and ends after
+/// `min_silence` without it
+#[derive(Clone, Debug, PartialEq)]
+pub struct VadConfig {
+    /// Speech probability above which a frame is speech, None uses the `VadSensitivity`'s
+    pub threshold: Option<f32>,
+    /// Shorter speech isn't reported, e.g. a cough or a click
+    pub min_speech: Duration,
+    /// Shorter pauses don't end speech
+    pub min_silence: Duration,
+    /// Silence still reported as speech right after it, for trailing sounds
+    pub padding: Duration,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            threshold: None,
+            ..VadPreset::Balanced.config()
+        }
+    }
+}
+
+/// Audio kept around speech when a segment is opened and closed on it, so word onsets and
+/// trailing sounds aren't cut
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SegmentPadding {
+    /// Before the speech started
+    pub pre_roll: Duration,
+    /// After it ended
+    pub hangover: Duration,
+}
+
+impl Default for SegmentPadding {
+    fn default() -> Self {
+        Self {
+            pre_roll: Duration::from_millis(200),
+            hangover: Duration::from_millis(300),
+        }
+    }
+}
+
+/// Ready made `VadConfig`s
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VadPreset {
+    /// Only clear, sustained speech, for noisy devices
+    Aggressive,
+    #[default]
+    Balanced,
+    /// Quiet and short speech too, at the cost of more noise transcribed
+    Sensitive,
+}
+
+impl VadPreset {
+    pub fn config(&self) -> VadConfig {
+        match self {
+            VadPreset::Aggressive => VadConfig {
+                threshold: Some(0.7),
+                min_speech: Duration::from_millis(500),
+                min_silence: Duration::from_millis(300),
+                padding: Duration::ZERO,
+            },
+            VadPreset::Balanced => VadConfig {
+                threshold: Some(0.5),
+                min_speech: Duration::from_millis(300),
+                min_silence: Duration::from_millis(500),
+                padding: Duration::ZERO,
+            },
+            VadPreset::Sensitive => VadConfig {
+                threshold: Some(0.3),
+                min_speech: Duration::from_millis(100),
+                min_silence: Duration::from_millis(1000),
+                padding: Duration::from_millis(300),
+            },
+        }
+    }
+}
+
+/// Engine, sensitivity and tuning of a VAD, each device can have its own
+#[derive(Clone, Debug, PartialEq)]
+pub struct VadSettings {
+    pub engine: VadEngineEnum,
+    pub sensitivity: VadSensitivity,
+    pub config: VadConfig,
+}
+
+impl Default for VadSettings {
+    fn default() -> Self {
+        Self {
+            engine: VadEngineEnum::Silero,
+            sensitivity: VadSensitivity::default(),
+            config: VadConfig::default(),
+        }
+    }
+}
+
+impl VadSettings {
+    /// The engine set up with the sensitivity and tuning, see `create_vad_engine`
+    pub async fn create_engine(&self) -> anyhow::Result<Box<dyn VadEngine + Send>> {
+        let mut vad = create_vad_engine(self.engine).await?;
+        vad.set_sensitivity(self.sensitivity);
+        vad.set_config(self.config.clone());
+        Ok(vad)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum VadEngineEnum {
     WebRtc,
     Silero,
+    /// Loudness and zero crossings, no model to load. Used in place of the others when they fail
+    /// to load
+    Energy,
+    /// Webrtc's audio processing module, gain control and noise suppression before its voice
+    /// detection, for quiet and whispered speech. Needs the `webrtc-apm` feature
+    WebRtcApm,
+}
+
+impl fmt::Display for VadEngineEnum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VadEngineEnum::WebRtc => write!(f, "webrtc"),
+            VadEngineEnum::Silero => write!(f, "silero"),
+            VadEngineEnum::Energy => write!(f, "energy"),
+            VadEngineEnum::WebRtcApm => write!(f, "webrtc-apm"),
+        }
+    }
+}
+
+/// Sent as `vad_fallback` when a VAD engine failed to load and the energy VAD is used instead
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VadFallbackEvent {
+    pub requested: String,
+    pub active: String,
+    pub reason: String,
 }
 
 pub trait VadEngine: Send {
     fn is_voice_segment(&mut self, audio_chunk: &[f32]) -> anyhow::Result<bool>;
     fn set_sensitivity(&mut self, sensitivity: VadSensitivity);
+    fn set_config(&mut self, config: VadConfig);
     fn audio_type(&mut self, audio_chunk: &[f32]) -> anyhow::Result<VadStatus>;
     fn get_min_speech_ratio(&self) -> f32;
 }
@@ -103,26 +240,71 @@ impl VadEngine for WebRtcVad {
         self.sensitivity = sensitivity;
     }
 
+    // webrtc decides frame by frame, only tuned through its sensitivity modes
+    fn set_config(&mut self, _config: VadConfig) {}
+
     fn get_min_speech_ratio(&self) -> f32 {
         self.sensitivity.min_speech_ratio()
     }
 }
 
-const FRAME_HISTORY: usize = 10; // Number of frames to consider for decision
-const SPEECH_THRESHOLD: f32 = 0.5;
-const SILENCE_THRESHOLD: f32 = 0.35;
-const SPEECH_FRAME_THRESHOLD: usize = 3; // Minimum number of frames above SPEECH_THRESHOLD to consider as speech
+const CHUNK_SIZE: usize = 1600; // 100 milliseconds
 
 lazy_static! {
     static ref MODEL_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
 }
 
 static DOWNLOAD_ONCE: Once = Once::new();
+/// Set when the model download failed, it isn't retried until the next start
+static DOWNLOAD_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Turns frame by frame speech decisions into speech that lasted `min_speech`, until
+/// `min_silence` without it
+#[derive(Default)]
+struct SpeechTracker {
+    config: VadConfig,
+    in_speech: bool,
+    /// Speech (or silence) heard in a row
+    speech_run: Duration,
+    silence_run: Duration,
+}
+
+impl SpeechTracker {
+    /// Status of a frame lasting `frame`. Unknown while speech may be starting or ending
+    fn update(&mut self, is_speech: bool, frame: Duration) -> VadStatus {
+        if is_speech {
+            self.silence_run = Duration::ZERO;
+            self.speech_run += frame;
+            if self.speech_run >= self.config.min_speech {
+                self.in_speech = true;
+            }
+            return if self.in_speech {
+                VadStatus::Speech
+            } else {
+                VadStatus::Unknown
+            };
+        }
+
+        self.speech_run = Duration::ZERO;
+        if !self.in_speech {
+            return VadStatus::Silence;
+        }
+        self.silence_run += frame;
+        if self.silence_run <= self.config.padding {
+            VadStatus::Speech
+        } else if self.silence_run < self.config.min_silence {
+            VadStatus::Unknown
+        } else {
+            self.in_speech = false;
+            VadStatus::Silence
+        }
+    }
+}
 
 pub struct SileroVad {
     vad: Vad,
-    prob_history: VecDeque<f32>,
     sensitivity: VadSensitivity,
+    tracker: SpeechTracker,
 }
 
 impl SileroVad {
@@ -137,8 +319,8 @@ impl SileroVad {
         debug!("SileroVad initialized successfully");
         Ok(Self {
             vad,
-            prob_history: VecDeque::with_capacity(FRAME_HISTORY),
             sensitivity: VadSensitivity::Medium,
+            tracker: SpeechTracker::default(),
         })
     }
 
@@ -159,13 +341,17 @@ impl SileroVad {
         DOWNLOAD_ONCE.call_once(|| {
             tokio::spawn(async move {
                 if let Err(e) = Self::download_model().await {
-                    debug!("error downloading silerovad model: {}", e);
+                    warn!("error downloading silerovad model: {}", e);
+                    DOWNLOAD_FAILED.store(true, Ordering::Relaxed);
                 }
             });
         });
 
         // wait for download to complete
         while !path.exists() {
+            if DOWNLOAD_FAILED.load(Ordering::Relaxed) {
+                return Err(anyhow::anyhow!("silerovad model download failed"));
+            }
             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         }
 
@@ -184,8 +370,11 @@ impl SileroVad {
         tokio::fs::create_dir_all(&cache_dir).await?;
         let path = cache_dir.join("silero_vad.onnx");
 
-        let mut file = tokio::fs::File::create(&path).await?;
+        // written aside and renamed, the model is only there once complete
+        let partial_path = path.with_extension("onnx.part");
+        let mut file = tokio::fs::File::create(&partial_path).await?;
         tokio::io::AsyncWriteExt::write_all(&mut file, &model_data).await?;
+        tokio::fs::rename(&partial_path, &path).await?;
         debug!("silerovad model downloaded and saved to: {:?}", path);
 
         Ok(())
@@ -197,30 +386,27 @@ impl SileroVad {
         Ok(proj_dirs.join("screenpipe").join("vad"))
     }
 
-    fn update_status(&mut self, prob: f32) -> VadStatus {
-        self.prob_history.push_back(prob);
-        if self.prob_history.len() > FRAME_HISTORY {
-            self.prob_history.pop_front();
-        }
+    /// Status of a frame lasting `frame` with speech probability `prob`
+    fn update_status(&mut self, prob: f32, frame: Duration) -> VadStatus {
+        let threshold = self
+            .tracker
+            .config
+            .threshold
+            .unwrap_or_else(|| self.get_threshold());
+        self.tracker.update(prob > threshold, frame)
+    }
 
-        let speech_frames = self
-            .prob_history
-            .iter()
-            .filter(|&&p| p > SPEECH_THRESHOLD)
-            .count();
-        let silence_frames = self
-            .prob_history
-            .iter()
-            .filter(|&&p| p < SILENCE_THRESHOLD)
-            .count();
+    /// Silero's speech probability for up to 100ms of audio, with how long it lasted
+    fn compute(&mut self, audio_chunk: &[f32]) -> anyhow::Result<(f32, Duration)> {
+        let frame = Duration::from_secs_f64(audio_chunk.len().min(CHUNK_SIZE) as f64 / 16000.0);
+        let mut chunk_data: Vec<f32> = audio_chunk.to_vec();
+        chunk_data.resize(CHUNK_SIZE, 0.0);
 
-        if speech_frames >= SPEECH_FRAME_THRESHOLD {
-            VadStatus::Speech
-        } else if silence_frames > self.prob_history.len() / 2 {
-            VadStatus::Silence
-        } else {
-            VadStatus::Unknown
-        }
+        let result = self.vad.compute(&chunk_data).map_err(|e| {
+            debug!("SileroVad Error computing VAD: {}", e);
+            anyhow::anyhow!("Vad compute error: {}", e)
+        })?;
+        Ok((result.prob, frame))
     }
 
     fn get_threshold(&self) -> f32 {
@@ -234,51 +420,229 @@ impl SileroVad {
 
 impl VadEngine for SileroVad {
     fn is_voice_segment(&mut self, audio_chunk: &[f32]) -> anyhow::Result<bool> {
-        const CHUNK_SIZE: usize = 1600; // 100 milliseconds
+        let (prob, frame) = self.compute(audio_chunk)?;
+        Ok(self.update_status(prob, frame) == VadStatus::Speech)
+    }
 
-        let threshold = self.get_threshold();
+    fn audio_type(&mut self, audio_chunk: &[f32]) -> anyhow::Result<VadStatus> {
+        let (prob, frame) = self.compute(audio_chunk)?;
+        Ok(self.update_status(prob, frame))
+    }
 
-        let mut chunk_data: Vec<f32> = audio_chunk.to_vec();
-        chunk_data.resize(CHUNK_SIZE, 0.0);
+    fn set_sensitivity(&mut self, sensitivity: VadSensitivity) {
+        self.sensitivity = sensitivity;
+    }
 
-        let result = self.vad.compute(&chunk_data).map_err(|e| {
-            debug!("SileroVad Error computing VAD: {}", e);
-            anyhow::anyhow!("Vad compute error: {}", e)
-        })?;
+    fn set_config(&mut self, config: VadConfig) {
+        self.tracker.config = config;
+    }
 
-        let status = self.update_status(result.prob);
+    fn get_min_speech_ratio(&self) -> f32 {
+        self.sensitivity.min_speech_ratio()
+    }
+}
+
+/// Quieter than this is silence whatever the noise floor, about -50dBFS
+const ENERGY_MIN_RMS: f32 = 0.003;
+/// Share of samples crossing zero above which audio is hiss or noise rather than voice
+const ENERGY_MAX_ZERO_CROSSING_RATE: f32 = 0.3;
+
+/// Speech as audio clearly louder than the noise floor, crossing zero at the rate of voice rather
+/// than hiss. Needs no model, the fallback for when the others can't load
+pub struct EnergyVad {
+    sensitivity: VadSensitivity,
+    tracker: SpeechTracker,
+    /// Rms of background noise
+    noise_floor: f32,
+}
+
+impl Default for EnergyVad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        Ok(status == VadStatus::Speech && result.prob > threshold)
+impl EnergyVad {
+    pub fn new() -> Self {
+        Self {
+            sensitivity: VadSensitivity::Medium,
+            tracker: SpeechTracker::default(),
+            noise_floor: ENERGY_MIN_RMS,
+        }
+    }
+
+    /// How many times louder than the noise floor speech is
+    fn margin(&self) -> f32 {
+        match self.sensitivity {
+            VadSensitivity::Low => 4.0,
+            VadSensitivity::Medium => 2.8,
+            VadSensitivity::High => 2.0,
+        }
+    }
+
+    fn is_speech(&mut self, audio_chunk: &[f32]) -> bool {
+        if audio_chunk.is_empty() {
+            return false;
+        }
+        let rms =
+            (audio_chunk.iter().map(|s| s * s).sum::<f32>() / audio_chunk.len() as f32).sqrt();
+        let crossings = audio_chunk
+            .windows(2)
+            .filter(|pair| (pair[0] >= 0.0) != (pair[1] >= 0.0))
+            .count();
+        let zero_crossing_rate = crossings as f32 / audio_chunk.len() as f32;
+        let is_speech = rms > ENERGY_MIN_RMS
+            && rms > self.noise_floor * self.margin()
+            && zero_crossing_rate < ENERGY_MAX_ZERO_CROSSING_RATE;
+
+        // the floor follows quieter audio right away and louder audio slowly, barely during
+        // speech so a lasting change of background noise is still picked up
+        if rms < self.noise_floor {
+            self.noise_floor = rms.max(ENERGY_MIN_RMS);
+        } else {
+            let rate = if is_speech { 0.002 } else { 0.05 };
+            self.noise_floor += (rms - self.noise_floor) * rate;
+        }
+        is_speech
+    }
+}
+
+impl VadEngine for EnergyVad {
+    fn is_voice_segment(&mut self, audio_chunk: &[f32]) -> anyhow::Result<bool> {
+        Ok(self.audio_type(audio_chunk)? == VadStatus::Speech)
     }
 
     fn audio_type(&mut self, audio_chunk: &[f32]) -> anyhow::Result<VadStatus> {
-        const CHUNK_SIZE: usize = 1600; // 100 milliseconds
+        let frame = Duration::from_secs_f64(audio_chunk.len() as f64 / 16000.0);
+        let is_speech = self.is_speech(audio_chunk);
+        Ok(self.tracker.update(is_speech, frame))
+    }
 
-        let threshold = self.get_threshold();
+    fn set_sensitivity(&mut self, sensitivity: VadSensitivity) {
+        self.sensitivity = sensitivity;
+    }
 
-        let mut chunk_data: Vec<f32> = audio_chunk.to_vec();
-        chunk_data.resize(CHUNK_SIZE, 0.0);
+    fn set_config(&mut self, config: VadConfig) {
+        self.tracker.config = config;
+    }
 
-        let result = self.vad.compute(&chunk_data).map_err(|e| {
-            debug!("SileroVad Error computing VAD: {}", e);
-            anyhow::anyhow!("Vad compute error: {}", e)
-        })?;
+    fn get_min_speech_ratio(&self) -> f32 {
+        self.sensitivity.min_speech_ratio()
+    }
+}
 
-        let status = self.update_status(result.prob);
+/// Share of the 10ms frames of a chunk the audio processing module has to hear voice in for the
+/// chunk to be speech, its decisions flicker within words
+#[cfg(feature = "webrtc-apm")]
+const APM_MIN_VOICED_SHARE: f32 = 0.3;
+
+/// Webrtc's audio processing module: high pass filter, noise suppression and adaptive digital
+/// gain bring quiet and whispered speech up to a level its voice detection picks up, where
+/// silero's probability stays low. Works on 10ms frames of 48khz audio
+#[cfg(feature = "webrtc-apm")]
+pub struct WebRtcApmVad {
+    processor: webrtc_audio_processing::Processor,
+    sensitivity: VadSensitivity,
+    tracker: SpeechTracker,
+}
 
-        if status == VadStatus::Speech && result.prob > threshold {
-            return Ok(VadStatus::Speech);
-        }
+#[cfg(feature = "webrtc-apm")]
+impl WebRtcApmVad {
+    pub fn new() -> anyhow::Result<Self> {
+        let processor = webrtc_audio_processing::Processor::new(
+            &webrtc_audio_processing::InitializationConfig {
+                num_capture_channels: 1,
+                num_render_channels: 1,
+                ..Default::default()
+            },
+        )
+        .map_err(|e| anyhow::anyhow!("webrtc apm creation error: {:?}", e))?;
+        let mut vad = Self {
+            processor,
+            sensitivity: VadSensitivity::Medium,
+            tracker: SpeechTracker::default(),
+        };
+        vad.configure();
+        Ok(vad)
+    }
 
-        match status {
-            VadStatus::Unknown => Ok(VadStatus::Unknown),
-            // this is super misleading
-            _ => Ok(VadStatus::Silence),
+    fn configure(&mut self) {
+        use webrtc_audio_processing::{
+            Config, GainControl, GainControlMode, NoiseSuppression, NoiseSuppressionLevel,
+            VoiceDetection, VoiceDetectionLikelihood,
+        };
+        // the likelihood of declaring a frame voice, higher clips less speech and lets more
+        // noise through
+        let detection_likelihood = match self.sensitivity {
+            VadSensitivity::Low => VoiceDetectionLikelihood::Low,
+            VadSensitivity::Medium => VoiceDetectionLikelihood::Moderate,
+            VadSensitivity::High => VoiceDetectionLikelihood::High,
+        };
+        self.processor.set_config(Config {
+            gain_control: Some(GainControl {
+                mode: GainControlMode::AdaptiveDigital,
+                target_level_dbfs: 3,
+                compression_gain_db: 20,
+                enable_limiter: true,
+            }),
+            noise_suppression: Some(NoiseSuppression {
+                suppression_level: NoiseSuppressionLevel::Moderate,
+            }),
+            voice_detection: Some(VoiceDetection {
+                detection_likelihood,
+            }),
+            enable_high_pass_filter: true,
+            ..Default::default()
+        });
+    }
+
+    /// Whether enough of the 10ms frames of 16khz `audio_chunk` have voice
+    fn is_speech(&mut self, audio_chunk: &[f32]) -> anyhow::Result<bool> {
+        let frame_len = webrtc_audio_processing::NUM_SAMPLES_PER_FRAME as usize;
+        let mut audio = crate::audio_processing::resample_with(
+            audio_chunk,
+            16000,
+            48000,
+            crate::audio_processing::ResampleQuality::Fast,
+        )?;
+        let frames = audio.len().div_ceil(frame_len);
+        if frames == 0 {
+            return Ok(false);
         }
+        audio.resize(frames * frame_len, 0.0);
+
+        let mut voiced = 0;
+        for frame in audio.chunks_mut(frame_len) {
+            self.processor
+                .process_capture_frame(frame)
+                .map_err(|e| anyhow::anyhow!("webrtc apm error: {:?}", e))?;
+            if self.processor.get_stats().has_voice == Some(true) {
+                voiced += 1;
+            }
+        }
+        Ok(voiced as f32 >= frames as f32 * APM_MIN_VOICED_SHARE)
+    }
+}
+
+#[cfg(feature = "webrtc-apm")]
+impl VadEngine for WebRtcApmVad {
+    fn is_voice_segment(&mut self, audio_chunk: &[f32]) -> anyhow::Result<bool> {
+        Ok(self.audio_type(audio_chunk)? == VadStatus::Speech)
+    }
+
+    fn audio_type(&mut self, audio_chunk: &[f32]) -> anyhow::Result<VadStatus> {
+        let frame = Duration::from_secs_f64(audio_chunk.len() as f64 / 16000.0);
+        let is_speech = self.is_speech(audio_chunk)?;
+        Ok(self.tracker.update(is_speech, frame))
     }
 
     fn set_sensitivity(&mut self, sensitivity: VadSensitivity) {
         self.sensitivity = sensitivity;
+        self.configure();
+    }
+
+    fn set_config(&mut self, config: VadConfig) {
+        self.tracker.config = config;
     }
 
     fn get_min_speech_ratio(&self) -> f32 {
@@ -286,15 +650,39 @@ impl VadEngine for SileroVad {
     }
 }
 
-pub async fn create_vad_engine(engine: VadEngineEnum) -> anyhow::Result<Box<dyn VadEngine>> {
-    match engine {
+/// The engine, or the energy VAD when it fails to load (e.g. the silero model can't be downloaded
+/// on an offline first run), with a `vad_fallback` event
+pub async fn create_vad_engine(engine: VadEngineEnum) -> anyhow::Result<Box<dyn VadEngine + Send>> {
+    let loaded: anyhow::Result<Box<dyn VadEngine + Send>> = match engine {
         VadEngineEnum::WebRtc => Ok(Box::new(WebRtcVad::new())),
-        VadEngineEnum::Silero => {
-            let silero_vad = SileroVad::new().await?;
-            Ok(Box::new(silero_vad))
+        VadEngineEnum::Silero => SileroVad::new()
+            .await
+            .map(|silero_vad| Box::new(silero_vad) as Box<dyn VadEngine + Send>),
+        VadEngineEnum::Energy => Ok(Box::new(EnergyVad::new())),
+        #[cfg(feature = "webrtc-apm")]
+        VadEngineEnum::WebRtcApm => {
+            WebRtcApmVad::new().map(|apm_vad| Box::new(apm_vad) as Box<dyn VadEngine + Send>)
         }
-    }
+        #[cfg(not(feature = "webrtc-apm"))]
+        VadEngineEnum::WebRtcApm => Err(anyhow::anyhow!(
+            "webrtc-apm vad needs screenpipe built with the webrtc-apm feature"
+        )),
+    };
+    loaded.or_else(|e| {
+        warn!("failed to load {} vad, using the energy vad: {}", engine, e);
+        let _ = send_event(
+            "vad_fallback",
+            VadFallbackEvent {
+                requested: engine.to_string(),
+                active: VadEngineEnum::Energy.to_string(),
+                reason: e.to_string(),
+            },
+        );
+        Ok(Box::new(EnergyVad::new()) as Box<dyn VadEngine + Send>)
+    })
 }
 
 unsafe impl Send for WebRtcVad {}
 unsafe impl Send for SileroVad {}
+#[cfg(feature = "webrtc-apm")]
+unsafe impl Send for WebRtcApmVad {}
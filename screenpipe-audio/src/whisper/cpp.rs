@@ -0,0 +1,201 @@
+use super::{
+    DecodingOptions, DetectedLanguage, DevicePreference, SegmentConfidence, Task, TextSegment,
+    Transcript,
+};
+use crate::models::{self, ModelFile};
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use screenpipe_core::Language;
+use std::path::Path;
+use std::sync::Arc;
+use whisper_rs::{
+    get_lang_id, get_lang_str, FullParams, SamplingStrategy, WhisperContext,
+    WhisperContextParameters, WhisperState,
+};
+
+const GGML_REPO: &str = "ggerganov/whisper.cpp";
+
+/// whisper.cpp (GGML) model, much faster than candle on machines without a usable GPU
+#[derive(Clone)]
+pub struct WhisperCppModel {
+    context: Arc<WhisperContext>,
+}
+
+impl WhisperCppModel {
+    /// Loads `model_file` of the whisper.cpp repo, e.g. "ggml-large-v3-turbo-q5_0.bin"
+    pub fn new(model_file: &str, device: &DevicePreference) -> Result<Self> {
+        debug!("Fetching whisper.cpp model {}", model_file);
+        let model_path =
+            models::get_or_download_with_events(&ModelFile::huggingface(GGML_REPO, model_file))?;
+
+        Self::from_file(&model_path, device)
+    }
+
+    /// Loads a local ggml model file, e.g. one converted from a fine-tuned checkpoint
+    pub fn from_file(model_path: &Path, device: &DevicePreference) -> Result<Self> {
+        let model_path = model_path
+            .to_str()
+            .ok_or_else(|| anyhow!("invalid whisper.cpp model path"))?;
+        // whisper.cpp uses the GPU backend it was built with (metal/cuda features) unless told not to
+        let mut params = WhisperContextParameters::default();
+        match device {
+            DevicePreference::Cpu => {
+                params.use_gpu(false);
+            }
+            DevicePreference::Cuda(ordinal) => {
+                params.gpu_device(*ordinal as i32);
+            }
+            DevicePreference::Auto | DevicePreference::Metal => {}
+        }
+        let context = WhisperContext::new_with_params(model_path, params)
+            .map_err(|e| anyhow!("failed to load whisper.cpp model: {}", e))?;
+        info!("whisper.cpp model loaded from {}", model_path);
+
+        Ok(Self {
+            context: Arc::new(context),
+        })
+    }
+
+    /// Transcribes 16khz mono audio, restricting language detection to `languages` if not empty
+    pub fn transcribe(
+        &self,
+        audio: &[f32],
+        languages: &[Language],
+        task: Task,
+        prompt: Option<&str>,
+        decoding: &DecodingOptions,
+    ) -> Result<Transcript> {
+        let mut state = self.context.create_state()?;
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get().min(8))
+            .unwrap_or(4);
+
+        let detected = match languages {
+            [language] => DetectedLanguage::forced(language.as_lang_code()),
+            _ => detect_language(&mut state, audio, languages, threads)?,
+        };
+        let language = detected.code.as_str();
+        debug!("whisper.cpp decoding with language {}", language);
+
+        let strategy = if decoding.beam_size > 1 {
+            SamplingStrategy::BeamSearch {
+                beam_size: decoding.beam_size as i32,
+                patience: -1.0,
+            }
+        } else {
+            SamplingStrategy::Greedy { best_of: 1 }
+        };
+        let mut params = FullParams::new(strategy);
+        // whisper.cpp falls back by a fixed increment, and uses token entropy where openai uses
+        // the compression ratio (same 2.4 default)
+        let temperatures = &decoding.temperatures;
+        params.set_temperature(temperatures.first().copied().unwrap_or(0.0) as f32);
+        params.set_temperature_inc(match temperatures.as_slice() {
+            [first, second, ..] => (second - first) as f32,
+            _ => 0.0,
+        });
+        params.set_entropy_thold(decoding.compression_ratio_threshold as f32);
+        params.set_logprob_thold(decoding.logprob_threshold as f32);
+        params.set_no_speech_thold(decoding.no_speech_threshold as f32);
+        params.set_n_threads(threads as i32);
+        params.set_language(Some(language));
+        params.set_translate(task == Task::Translate);
+        if let Some(prompt) = prompt {
+            params.set_initial_prompt(prompt);
+        }
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_special(false);
+        params.set_print_timestamps(false);
+        state.full(params, audio)?;
+
+        let eot = self.context.token_eot();
+        let mut transcript = String::new();
+        let mut segments = Vec::new();
+        let (mut logprob_sum, mut no_speech_sum, mut duration_sum) = (0f64, 0f64, 0f64);
+
+        for i in 0..state.full_n_segments()? {
+            let text = state.full_get_segment_text(i)?;
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            transcript.push_str(text);
+            transcript.push('\n');
+
+            // segment timestamps are in 10ms units
+            let start = state.full_get_segment_t0(i)? as f64 / 100.0;
+            let end = state.full_get_segment_t1(i)? as f64 / 100.0;
+            let duration = end - start;
+            segments.push(TextSegment {
+                start,
+                end,
+                text: text.to_string(),
+            });
+            let mut logprobs = Vec::new();
+            for j in 0..state.full_n_tokens(i)? {
+                // ids from eot on are special tokens (timestamps, language, ...)
+                if state.full_get_token_id(i, j)? < eot {
+                    logprobs.push(state.full_get_token_data(i, j)?.plog as f64);
+                }
+            }
+            if duration > 0.0 && !logprobs.is_empty() {
+                let avg_logprob = logprobs.iter().sum::<f64>() / logprobs.len() as f64;
+                logprob_sum += avg_logprob * duration;
+                no_speech_sum += state.full_get_segment_no_speech_prob(i)? as f64 * duration;
+                duration_sum += duration;
+            }
+        }
+
+        let confidence = (duration_sum > 0.0).then(|| SegmentConfidence {
+            avg_logprob: logprob_sum / duration_sum,
+            no_speech_prob: no_speech_sum / duration_sum,
+        });
+
+        Ok(Transcript {
+            text: transcript,
+            confidence,
+            language: Some(detected),
+            engine: None,
+            segments,
+            model_version: None,
+        })
+    }
+}
+
+/// Most likely of the allowed languages (any if empty), detected here rather than by whisper.cpp
+/// which can only auto detect among all of them and doesn't report the probability
+fn detect_language(
+    state: &mut WhisperState,
+    audio: &[f32],
+    languages: &[Language],
+    threads: usize,
+) -> Result<DetectedLanguage> {
+    state.pcm_to_mel(audio, threads)?;
+    let (_, probs) = state.lang_detect(0, threads)?;
+
+    let (code, probability) = if languages.is_empty() {
+        probs
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .and_then(|(id, prob)| Some((get_lang_str(id as i32)?, *prob)))
+    } else {
+        languages
+            .iter()
+            .map(|language| {
+                let code = language.as_lang_code();
+                let prob = get_lang_id(code)
+                    .and_then(|id| probs.get(id as usize).copied())
+                    .unwrap_or(0.0);
+                (code, prob)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+    .ok_or_else(|| anyhow!("no language to detect"))?;
+
+    Ok(DetectedLanguage {
+        code: code.to_string(),
+        probability,
+    })
+}
@@ -0,0 +1,140 @@
+use super::{timestamped_segments, DecodingOptions, DetectedLanguage, Task, Transcript};
+use crate::models::{self, ModelFile};
+use anyhow::{anyhow, Result};
+use candle_transformers::models::whisper as m;
+use ct2rs::{Config, Whisper, WhisperOptions};
+use log::{debug, info, warn};
+use screenpipe_core::Language;
+use std::sync::Arc;
+
+/// large-v3-turbo converted for CTranslate2 the same way faster-whisper models are
+const CT2_REPO: &str = "deepdml/faster-whisper-large-v3-turbo-ct2";
+const CT2_FILES: [&str; 5] = [
+    "config.json",
+    "model.bin",
+    "preprocessor_config.json",
+    "tokenizer.json",
+    "vocabulary.json",
+];
+
+/// CTranslate2 (faster-whisper) model, a lot faster than candle for large models on CPU
+#[derive(Clone)]
+pub struct FasterWhisperModel {
+    whisper: Arc<Whisper>,
+}
+
+impl FasterWhisperModel {
+    pub fn new() -> Result<Self> {
+        debug!("Fetching CTranslate2 model {}", CT2_REPO);
+        // the files of the repo all land in the same directory, which ct2 loads as a whole
+        let mut model_dir = None;
+        for file in CT2_FILES {
+            let path =
+                models::get_or_download_with_events(&ModelFile::huggingface(CT2_REPO, file))?;
+            model_dir = path.parent().map(|dir| dir.to_path_buf());
+        }
+        let model_dir = model_dir.ok_or_else(|| anyhow!("invalid CTranslate2 model path"))?;
+
+        let whisper = Whisper::new(&model_dir, Config::default())
+            .map_err(|e| anyhow!("failed to load CTranslate2 model: {}", e))?;
+        info!("CTranslate2 model loaded from {}", model_dir.display());
+
+        Ok(Self {
+            whisper: Arc::new(whisper),
+        })
+    }
+
+    /// Transcribes 16khz mono audio. ct2 doesn't return decoding scores through `generate`, so
+    /// there is no confidence. `generate` builds the prompt itself, so neither `Task::Translate` nor
+    /// an initial prompt are supported
+    pub fn transcribe(
+        &self,
+        audio: &[f32],
+        languages: &[Language],
+        task: Task,
+        prompt: Option<&str>,
+        decoding: &DecodingOptions,
+    ) -> Result<Transcript> {
+        if task == Task::Translate {
+            warn!("faster-whisper can't translate, transcribing instead");
+        }
+        if prompt.is_some() {
+            debug!("faster-whisper doesn't support initial prompts, ignoring it");
+        }
+        let language = match languages {
+            [language] => DetectedLanguage::forced(language.as_lang_code()),
+            _ => self.detect_language(audio, languages)?,
+        };
+        debug!("CTranslate2 decoding with language {:?}", language.code);
+
+        let segments = self
+            .whisper
+            .generate(
+                audio,
+                Some(language.code.as_str()),
+                true,
+                &WhisperOptions {
+                    beam_size: decoding.beam_size,
+                    sampling_temperature: decoding.temperatures.first().copied().unwrap_or(0.0)
+                        as f32,
+                    ..Default::default()
+                },
+            )
+            .map_err(|e| anyhow!("CTranslate2 transcription failed: {}", e))?;
+
+        // one decoding per 30s chunk, timestamped from the start of its chunk
+        let audio_duration = audio.len() as f64 / m::SAMPLE_RATE as f64;
+        let mut transcript = String::new();
+        let mut text_segments = Vec::new();
+        for (i, text) in segments.iter().enumerate() {
+            let start = (i * m::CHUNK_LENGTH) as f64;
+            let duration = (audio_duration - start).min(m::CHUNK_LENGTH as f64);
+            let said = timestamped_segments(text, start, duration);
+            if said.is_empty() {
+                continue;
+            }
+            let line = said.iter().map(|s| s.text.as_str()).collect::<Vec<_>>();
+            transcript.push_str(&line.join(" "));
+            transcript.push('\n');
+            text_segments.extend(said);
+        }
+
+        Ok(Transcript {
+            text: transcript,
+            confidence: None,
+            language: Some(language),
+            engine: None,
+            segments: text_segments,
+            model_version: None,
+        })
+    }
+
+    /// Most likely of `languages`, or of any language if empty
+    fn detect_language(&self, audio: &[f32], languages: &[Language]) -> Result<DetectedLanguage> {
+        let detected = self
+            .whisper
+            .detect_language(audio)
+            .map_err(|e| anyhow!("CTranslate2 language detection failed: {}", e))?;
+
+        // results of the first 30s window, most likely first
+        let candidates = detected.into_iter().next().unwrap_or_default();
+        let detected = candidates.iter().find_map(|c| {
+            let code = c.language.trim_start_matches("<|").trim_end_matches("|>");
+            let allowed =
+                languages.is_empty() || languages.iter().any(|l| l.as_lang_code() == code);
+            allowed.then(|| DetectedLanguage {
+                code: code.to_string(),
+                probability: c.probability,
+            })
+        });
+        detected
+            .or_else(|| {
+                // none of the allowed languages made it into the candidates
+                languages.first().map(|l| DetectedLanguage {
+                    code: l.as_lang_code().to_string(),
+                    probability: 0.0,
+                })
+            })
+            .ok_or_else(|| anyhow!("no language detected"))
+    }
+}
@@ -1,23 +1,68 @@
 use crate::whisper::Model;
 use anyhow::{Error as E, Result};
 use candle::{Device, IndexOp, Tensor};
-use candle_nn::ops::softmax;
+use candle_nn::ops::{log_softmax, softmax};
 use candle_transformers::models::whisper as m;
+use flate2::{write::ZlibEncoder, Compression};
 use log::{debug, error, info};
 use rand::{distributions::Distribution, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
 use tokenizers::Tokenizer;
 
 #[derive(Debug, Clone)]
 pub struct DecodingResult {
     tokens: Vec<u32>,
     pub text: String,
-    avg_logprob: f64,
-    no_speech_prob: f64,
+    pub avg_logprob: f64,
+    pub no_speech_prob: f64,
     #[allow(dead_code)]
     temperature: f64,
     compression_ratio: f64,
 }
 
+/// Decoding parameters of the local whisper engines, the defaults are openai's
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodingOptions {
+    /// Beams kept by beam search at temperature 0, 1 decodes greedily
+    pub beam_size: usize,
+    /// Temperatures tried in turn until a decoding passes the thresholds below
+    pub temperatures: Vec<f64>,
+    /// Decodings compressing better than this (gzip ratio) are repetitive, retried at the next
+    /// temperature
+    pub compression_ratio_threshold: f64,
+    /// Decodings with a lower average log probability are retried at the next temperature
+    pub logprob_threshold: f64,
+    /// Windows more likely than this to hold no speech are skipped if the decoding is unsure too
+    pub no_speech_threshold: f64,
+}
+
+impl Default for DecodingOptions {
+    fn default() -> Self {
+        Self {
+            beam_size: 1,
+            temperatures: m::TEMPERATURES.to_vec(),
+            compression_ratio_threshold: m::COMPRESSION_RATIO_THRESHOLD,
+            logprob_threshold: m::LOGPROB_THRESHOLD,
+            no_speech_threshold: m::NO_SPEECH_THRESHOLD,
+        }
+    }
+}
+
+impl DecodingOptions {
+    /// Whether a window with these probabilities most likely holds no speech
+    pub fn is_no_speech(&self, no_speech_prob: f64, avg_logprob: f64) -> bool {
+        no_speech_prob > self.no_speech_threshold && avg_logprob < self.logprob_threshold
+    }
+
+    /// Whether a decoding is too repetitive or unsure to keep, so the next temperature is tried
+    pub(crate) fn needs_fallback(&self, dr: &DecodingResult) -> bool {
+        let needs_fallback = dr.compression_ratio > self.compression_ratio_threshold
+            || dr.avg_logprob < self.logprob_threshold;
+        needs_fallback && dr.no_speech_prob <= self.no_speech_threshold
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Segment {
     pub start: f64,
@@ -33,13 +78,19 @@ pub struct Decoder<'a> {
     tokenizer: &'a Tokenizer,
     suppress_tokens: Tensor,
     sot_token: u32,
-    transcribe_token: u32,
+    task_token: u32,
     eot_token: u32,
     no_speech_token: u32,
     no_timestamps_token: u32,
     language_token: Option<u32>,
+    /// `<|startofprev|>` followed by the initial prompt, empty without one
+    prompt_tokens: Vec<u32>,
+    options: DecodingOptions,
 }
 
+/// Precedes the text whisper is conditioned on, e.g. an initial prompt
+pub(crate) const SOT_PREV_TOKEN: &str = "<|startofprev|>";
+
 impl<'a> Decoder<'a> {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -48,6 +99,9 @@ impl<'a> Decoder<'a> {
         seed: u64,
         device: &Device,
         language_token: Option<u32>,
+        task: Task,
+        prompt: Option<&str>,
+        options: &DecodingOptions,
         timestamps: bool,
         verbose: bool,
     ) -> Result<Self> {
@@ -65,7 +119,9 @@ impl<'a> Decoder<'a> {
             .collect();
         let suppress_tokens = Tensor::new(suppress_tokens.as_slice(), device)?;
         let sot_token = token_id(tokenizer, m::SOT_TOKEN)?;
-        let transcribe_token = token_id(tokenizer, m::TRANSCRIBE_TOKEN)?;
+        let task_token = token_id(tokenizer, task.token())?;
+        let prompt_tokens =
+            prompt_tokens(tokenizer, prompt, model.config().max_target_positions / 2)?;
         let eot_token = token_id(tokenizer, m::EOT_TOKEN)?;
         let no_speech_token = m::NO_SPEECH_TOKENS
             .iter()
@@ -83,11 +139,13 @@ impl<'a> Decoder<'a> {
             verbose,
             suppress_tokens,
             sot_token,
-            transcribe_token,
+            task_token,
             eot_token,
             no_speech_token,
             language_token,
             no_timestamps_token,
+            prompt_tokens,
+            options: options.clone(),
         })
     }
 
@@ -98,16 +156,24 @@ impl<'a> Decoder<'a> {
         }
         let sample_len = self.model.config().max_target_positions / 2;
         let mut no_speech_prob = f64::NAN;
-        let mut tokens = vec![self.sot_token];
+        let mut tokens = self.prompt_tokens.clone();
+        let prompt_len = tokens.len();
+        tokens.push(self.sot_token);
         if let Some(language_token) = self.language_token {
             tokens.push(language_token);
         }
-        tokens.push(self.transcribe_token);
+        tokens.push(self.task_token);
 
         if !self.timestamps {
             tokens.push(self.no_timestamps_token);
         }
 
+        if t == 0f64 && self.options.beam_size > 1 {
+            let (tokens, sum_logprob, no_speech_prob) =
+                self.beam_search(&audio_features, tokens, prompt_len)?;
+            return self.decoding_result(tokens, prompt_len, sum_logprob, no_speech_prob, t);
+        }
+
         let mut sum_logprob = 0f64;
         let mut last_token_was_timestamp = false;
 
@@ -121,7 +187,12 @@ impl<'a> Decoder<'a> {
                 .decoder_forward(&tokens_t, &audio_features, i == 0)?;
 
             if i == 0 {
-                let logits = self.model.decoder_final_linear(&ys.i(..1)?)?.i(0)?.i(0)?;
+                // predicted right after sot, which comes after the prompt
+                let logits = self
+                    .model
+                    .decoder_final_linear(&ys.i(..1)?)?
+                    .i(0)?
+                    .i(prompt_len)?;
                 no_speech_prob = softmax(&logits, 0)?
                     .i(self.no_speech_token as usize)?
                     .to_scalar::<f32>()? as f64;
@@ -182,30 +253,227 @@ impl<'a> Decoder<'a> {
             last_token_was_timestamp = next_token > self.no_timestamps_token;
         }
 
+        self.decoding_result(tokens, prompt_len, sum_logprob, no_speech_prob, t)
+    }
+
+    /// Greedily decodes a batch of 30s windows (`mel` of shape batch x mel bins x frames) at
+    /// temperature 0, a single forward pass per token for the whole batch. Each window gets its
+    /// own language token, rows that are done get eot appended until the others finish
+    pub fn decode_batch(
+        &mut self,
+        mel: &Tensor,
+        language_tokens: &[Option<u32>],
+    ) -> Result<Vec<DecodingResult>> {
+        let audio_features = self.model.encoder_forward(mel, true)?;
+        let batch_size = language_tokens.len();
+        let max_len = self.model.config().max_target_positions;
+        let prompt_len = self.prompt_tokens.len();
+
+        let mut rows: Vec<Vec<u32>> = language_tokens
+            .iter()
+            .map(|language_token| {
+                let mut tokens = self.prompt_tokens.clone();
+                tokens.push(self.sot_token);
+                tokens.extend(language_token);
+                tokens.push(self.task_token);
+                if !self.timestamps {
+                    tokens.push(self.no_timestamps_token);
+                }
+                tokens
+            })
+            .collect();
+        let initial_len = rows.first().map(Vec::len).unwrap_or_default();
+        if rows.iter().any(|row| row.len() != initial_len) {
+            anyhow::bail!("rows of a batch need the same number of initial tokens");
+        }
+
+        let mut sum_logprobs = vec![0f64; batch_size];
+        let mut no_speech_probs = vec![f64::NAN; batch_size];
+        // length of each row once it decoded eot
+        let mut lengths: Vec<Option<usize>> = vec![None; batch_size];
+
+        for i in 0..max_len / 2 {
+            let len = rows[0].len();
+            let tokens = Tensor::from_vec(rows.concat(), (batch_size, len), mel.device())?;
+            let ys = self
+                .model
+                .decoder_forward(&tokens, &audio_features, i == 0)?;
+
+            if i == 0 {
+                // predicted right after sot, which comes after the prompt
+                let logits = self
+                    .model
+                    .decoder_final_linear(&ys.narrow(1, prompt_len, 1)?)?
+                    .squeeze(1)?;
+                let probs = softmax(&logits, candle::D::Minus1)?
+                    .i((.., self.no_speech_token as usize))?
+                    .to_vec1::<f32>()?;
+                for (no_speech_prob, prob) in no_speech_probs.iter_mut().zip(probs) {
+                    *no_speech_prob = prob as f64;
+                }
+            }
+
+            let logits = self
+                .model
+                .decoder_final_linear(&ys.narrow(1, len - 1, 1)?)?
+                .squeeze(1)?
+                .broadcast_add(&self.suppress_tokens)?;
+            let logprobs: Vec<Vec<f32>> = log_softmax(&logits, candle::D::Minus1)?.to_vec2()?;
+            let logits: Vec<Vec<f32>> = logits.to_vec2()?;
+
+            for (row, mut logits_v) in logits.into_iter().enumerate() {
+                if lengths[row].is_some() {
+                    rows[row].push(self.eot_token);
+                    continue;
+                }
+                apply_repetition_penalty(&mut logits_v, &rows[row][initial_len..], 1.0);
+                let next_token = logits_v
+                    .iter()
+                    .enumerate()
+                    .max_by(|(_, u), (_, v)| u.total_cmp(v))
+                    .map(|(i, _)| i as u32)
+                    .unwrap();
+                rows[row].push(next_token);
+                sum_logprobs[row] += logprobs[row][next_token as usize] as f64;
+                if next_token == self.eot_token {
+                    lengths[row] = Some(rows[row].len());
+                }
+            }
+
+            if lengths.iter().all(Option::is_some) || rows[0].len() > max_len {
+                break;
+            }
+        }
+
+        rows.into_iter()
+            .zip(lengths)
+            .zip(sum_logprobs.into_iter().zip(no_speech_probs))
+            .map(|((mut tokens, length), (sum_logprob, no_speech_prob))| {
+                if let Some(length) = length {
+                    tokens.truncate(length);
+                }
+                self.decoding_result(tokens, prompt_len, sum_logprob, no_speech_prob, 0.0)
+            })
+            .collect()
+    }
+
+    fn decoding_result(
+        &self,
+        mut tokens: Vec<u32>,
+        prompt_len: usize,
+        sum_logprob: f64,
+        no_speech_prob: f64,
+        t: f64,
+    ) -> Result<DecodingResult> {
+        // the prompt is plain text, it would end up in the transcript
+        let tokens = tokens.split_off(prompt_len);
         let text = self.tokenizer.decode(&tokens, true).map_err(E::msg)?;
         let avg_logprob = sum_logprob / tokens.len() as f64;
 
         Ok(DecodingResult {
             tokens,
+            compression_ratio: compression_ratio(&text),
             text,
             avg_logprob,
             no_speech_prob,
             temperature: t,
-            compression_ratio: f64::NAN,
         })
     }
 
+    /// Beam search over the tokens following `tokens`. Returns the beam with the best length
+    /// normalized log probability, its summed log probability and the no speech probability
+    fn beam_search(
+        &mut self,
+        audio_features: &Tensor,
+        tokens: Vec<u32>,
+        prompt_len: usize,
+    ) -> Result<(Vec<u32>, f64, f64)> {
+        let beam_size = self.options.beam_size;
+        let max_len = self.model.config().max_target_positions;
+        let initial_len = tokens.len();
+        let mut no_speech_prob = f64::NAN;
+        let mut beams = vec![(tokens, 0f64)];
+        let mut finished: Vec<(Vec<u32>, f64)> = Vec::new();
+
+        for i in 0..max_len / 2 {
+            let mut candidates = Vec::new();
+            for (tokens, sum_logprob) in &beams {
+                let tokens_t = Tensor::new(tokens.as_slice(), audio_features.device())?;
+                let ys =
+                    self.model
+                        .decoder_forward(&tokens_t.unsqueeze(0)?, audio_features, i == 0)?;
+                if i == 0 {
+                    let logits = self
+                        .model
+                        .decoder_final_linear(&ys.i(..1)?)?
+                        .i(0)?
+                        .i(prompt_len)?;
+                    no_speech_prob = softmax(&logits, 0)?
+                        .i(self.no_speech_token as usize)?
+                        .to_scalar::<f32>()? as f64;
+                }
+
+                let (_, seq_len, _) = ys.dims3()?;
+                let logits = self
+                    .model
+                    .decoder_final_linear(&ys.i((..1, seq_len - 1..))?)?
+                    .i(0)?
+                    .i(0)?
+                    .broadcast_add(&self.suppress_tokens)?;
+                let mut logprobs: Vec<(usize, f32)> = log_softmax(&logits, 0)?
+                    .to_vec1::<f32>()?
+                    .into_iter()
+                    .enumerate()
+                    .collect();
+                // one more than the beam size, in case one of them ends the beam
+                let keep = (beam_size + 1).min(logprobs.len());
+                logprobs.select_nth_unstable_by(keep - 1, |a, b| b.1.total_cmp(&a.1));
+                for &(token, logprob) in &logprobs[..keep] {
+                    let mut next = tokens.clone();
+                    next.push(token as u32);
+                    candidates.push((next, sum_logprob + logprob as f64));
+                }
+            }
+
+            candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+            beams.clear();
+            for (tokens, sum_logprob) in candidates {
+                if tokens.last() == Some(&self.eot_token) || tokens.len() >= max_len {
+                    finished.push((tokens, sum_logprob));
+                } else if beams.len() < beam_size {
+                    beams.push((tokens, sum_logprob));
+                }
+            }
+            if finished.len() >= beam_size || beams.is_empty() {
+                break;
+            }
+        }
+
+        // beams still going when running out of length compete too
+        finished.extend(beams);
+        let normalized = |(tokens, sum_logprob): &(Vec<u32>, f64)| {
+            sum_logprob / (tokens.len() - initial_len).max(1) as f64
+        };
+        let (tokens, sum_logprob) = finished
+            .into_iter()
+            .max_by(|a, b| normalized(a).total_cmp(&normalized(b)))
+            .ok_or_else(|| E::msg("beam search produced no beam"))?;
+        Ok((tokens, sum_logprob, no_speech_prob))
+    }
+
     fn decode_with_fallback(&mut self, segment: &Tensor) -> Result<DecodingResult> {
-        for (i, &t) in m::TEMPERATURES.iter().enumerate() {
+        let temperatures = match self.options.temperatures.as_slice() {
+            [] => vec![0f64],
+            temperatures => temperatures.to_vec(),
+        };
+        for (i, &t) in temperatures.iter().enumerate() {
             let dr: Result<DecodingResult> = self.decode(segment, t);
-            if i == m::TEMPERATURES.len() - 1 {
+            if i == temperatures.len() - 1 {
                 return dr;
             }
             match dr {
                 Ok(dr) => {
-                    let needs_fallback = dr.compression_ratio > m::COMPRESSION_RATIO_THRESHOLD
-                        || dr.avg_logprob < m::LOGPROB_THRESHOLD;
-                    if !needs_fallback || dr.no_speech_prob > m::NO_SPEECH_THRESHOLD {
+                    if !self.options.needs_fallback(&dr) {
                         return Ok(dr);
                     }
                 }
@@ -240,7 +508,7 @@ impl<'a> Decoder<'a> {
             let segment_duration = (segment_size * m::HOP_LENGTH) as f64 / m::SAMPLE_RATE as f64;
             let dr = self.decode_with_fallback(&mel_segment)?;
             seek += segment_size;
-            if dr.no_speech_prob > m::NO_SPEECH_THRESHOLD && dr.avg_logprob < m::LOGPROB_THRESHOLD {
+            if self.options.is_no_speech(dr.no_speech_prob, dr.avg_logprob) {
                 debug!("no speech detected, skipping {seek} {dr:?}");
                 continue;
             }
@@ -284,6 +552,27 @@ impl<'a> Decoder<'a> {
         Ok(segments)
     }
 }
+/// `<|startofprev|>` and the tokens of `prompt`, keeping its end when longer than `max_len`
+/// like openai's implementation does. Empty without a prompt
+pub(crate) fn prompt_tokens(
+    tokenizer: &Tokenizer,
+    prompt: Option<&str>,
+    max_len: usize,
+) -> Result<Vec<u32>> {
+    let Some(prompt) = prompt.map(str::trim).filter(|p| !p.is_empty()) else {
+        return Ok(Vec::new());
+    };
+    let encoding = tokenizer
+        .encode(format!(" {}", prompt), false)
+        .map_err(E::msg)?;
+    let ids = encoding.get_ids();
+    let keep = ids.len().min(max_len.saturating_sub(1));
+
+    let mut tokens = vec![token_id(tokenizer, SOT_PREV_TOKEN)?];
+    tokens.extend_from_slice(&ids[ids.len() - keep..]);
+    Ok(tokens)
+}
+
 pub fn token_id(tokenizer: &Tokenizer, token: &str) -> candle::Result<u32> {
     match tokenizer.token_to_id(token) {
         None => candle::bail!("no token-id for {token}"),
@@ -291,13 +580,39 @@ pub fn token_id(tokenizer: &Tokenizer, token: &str) -> candle::Result<u32> {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Whether whisper writes down the audio in the spoken language or translates it to english
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Task {
+    #[default]
     Transcribe,
-    #[allow(dead_code)]
     Translate,
 }
 
+impl Task {
+    pub fn token(&self) -> &'static str {
+        match self {
+            Task::Transcribe => m::TRANSCRIBE_TOKEN,
+            Task::Translate => m::TRANSLATE_TOKEN,
+        }
+    }
+}
+
+/// How much better than its own length `text` compresses, high for repetitive decodings
+fn compression_ratio(text: &str) -> f64 {
+    if text.is_empty() {
+        return 0.0;
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    match encoder
+        .write_all(text.as_bytes())
+        .and_then(|_| encoder.finish())
+    {
+        Ok(compressed) => text.len() as f64 / compressed.len() as f64,
+        Err(_) => 0.0,
+    }
+}
+
 fn apply_repetition_penalty(logits: &mut [f32], token_history: &[u32], penalty: f32) {
     for &token in token_history {
         if let Some(logit) = logits.get_mut(token as usize) {
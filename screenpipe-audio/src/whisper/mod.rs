@@ -1,7 +1,21 @@
+#[cfg(feature = "whisper-cpp")]
+mod cpp;
+#[cfg(feature = "ctranslate2")]
+mod ct2;
 mod decoder;
 mod model;
+mod onnx;
 mod process_chunk;
+#[cfg(feature = "vosk")]
+mod vosk;
 
+#[cfg(feature = "whisper-cpp")]
+pub use cpp::*;
+#[cfg(feature = "ctranslate2")]
+pub use ct2::*;
 pub use decoder::*;
 pub use model::*;
+pub use onnx::*;
 pub use process_chunk::*;
+#[cfg(feature = "vosk")]
+pub use vosk::*;
@@ -1,24 +1,306 @@
-use anyhow::{Error as E, Result};
-use candle::{Device, Tensor};
+use anyhow::{anyhow, Error as E, Result};
+use candle::{Device, DeviceLocation, Tensor};
 use candle_nn::VarBuilder;
 use candle_transformers::models::whisper::{self as m, Config};
 use hf_hub::{api::sync::Api, Repo, RepoType};
-use log::{debug, info};
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 use tokenizers::Tokenizer;
 
+#[cfg(feature = "whisper-cpp")]
+use super::WhisperCppModel;
+use super::{OnnxWhisper, MULTILINGUAL_VOCAB_SIZE};
+
+/// whisper.cpp model file of the engines running on whisper.cpp, from ggerganov/whisper.cpp
+#[cfg(feature = "whisper-cpp")]
+fn ggml_model(engine: &crate::AudioTranscriptionEngine) -> &'static str {
+    match engine {
+        crate::AudioTranscriptionEngine::WhisperLargeV3Q5 => "ggml-large-v3-q5_0.bin",
+        crate::AudioTranscriptionEngine::WhisperLargeV3TurboQ8 => "ggml-large-v3-turbo-q8_0.bin",
+        _ => "ggml-large-v3-turbo-q5_0.bin",
+    }
+}
+
+/// Quantized gguf conversions of the openai checkpoints loadable by candle
+const QUANTIZED_REPO: &str = "lmz/candle-whisper";
+
+/// What a shared model was loaded for, see `WhisperModel::shared`
+type SharedModelKey = (
+    crate::AudioTranscriptionEngine,
+    ModelSource,
+    DevicePreference,
+);
+
+lazy_static! {
+    static ref ACTIVE_DEVICE: Mutex<Option<String>> = Mutex::new(None);
+    static ref SHARED_MODELS: Mutex<Vec<(SharedModelKey, Weak<WhisperModel>)>> =
+        Mutex::new(Vec::new());
+}
+
+/// Device the most recently loaded local model runs on, e.g. "cuda:0", None before any is loaded
+pub fn active_whisper_device() -> Option<String> {
+    ACTIVE_DEVICE.lock().ok().and_then(|device| device.clone())
+}
+
+fn set_active_device(device: String) {
+    info!("transcription model running on {}", device);
+    if let Ok(mut active) = ACTIVE_DEVICE.lock() {
+        *active = Some(device);
+    }
+}
+
+/// Hardware to run local models on. Unavailable GPUs fall back to the cpu
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum DevicePreference {
+    /// Metal, then the first CUDA GPU, then the cpu
+    #[default]
+    Auto,
+    Cpu,
+    Cuda(usize),
+    Metal,
+}
+
+impl FromStr for DevicePreference {
+    type Err = anyhow::Error;
+
+    /// "auto", "cpu", "metal", "cuda" or "cuda:<ordinal>"
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "cpu" => Ok(Self::Cpu),
+            "metal" => Ok(Self::Metal),
+            "cuda" => Ok(Self::Cuda(0)),
+            other => other
+                .strip_prefix("cuda:")
+                .and_then(|ordinal| ordinal.parse().ok())
+                .map(Self::Cuda)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "unknown device {}, expected auto, cpu, metal or cuda[:n]",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+impl fmt::Display for DevicePreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Cpu => write!(f, "cpu"),
+            Self::Cuda(ordinal) => write!(f, "cuda:{}", ordinal),
+            Self::Metal => write!(f, "metal"),
+        }
+    }
+}
+
+impl DevicePreference {
+    /// The candle device to use, falling back to the cpu when the GPU isn't there or this
+    /// build lacks the cuda/metal feature
+    pub fn candle_device(&self) -> Device {
+        let gpu = match self {
+            Self::Cpu => return Device::Cpu,
+            Self::Auto => {
+                return Device::new_metal(0)
+                    .or_else(|_| Device::new_cuda(0))
+                    .unwrap_or(Device::Cpu)
+            }
+            Self::Cuda(ordinal) => Device::new_cuda(*ordinal),
+            Self::Metal => Device::new_metal(0),
+        };
+        gpu.unwrap_or_else(|e| {
+            warn!("{} is not available, transcribing on cpu: {}", self, e);
+            Device::Cpu
+        })
+    }
+}
+
+/// Where local model weights come from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ModelSource {
+    /// The preset of the transcription engine, downloaded from huggingface
+    #[default]
+    Hub,
+    /// A local model: a whisper.cpp ggml `.bin` file, or a directory with `config.json`,
+    /// `tokenizer.json` and safetensors or gguf weights (e.g. a fine-tuned checkpoint)
+    Path(PathBuf),
+}
+
+/// How local models are loaded
+#[derive(Clone, Debug, Default)]
+pub struct ModelOptions {
+    pub device: DevicePreference,
+    pub source: ModelSource,
+    /// Segments the whisper channel decodes together when several devices have audio pending.
+    /// Only candle models on a GPU batch, 0 or 1 transcribes one segment at a time
+    pub batch_size: usize,
+    /// Unload the model after this long without anything to transcribe, freeing its memory until
+    /// the next speech comes in and it is loaded again. None keeps it loaded
+    pub unload_after_idle: Option<Duration>,
+}
+
+/// A loaded local transcription model, candle, whisper.cpp, CTranslate2, ONNX or vosk depending on the engine
+#[derive(Clone)]
+pub enum WhisperModel {
+    Candle(CandleWhisper),
+    #[cfg(feature = "whisper-cpp")]
+    Cpp(WhisperCppModel),
+    #[cfg(feature = "ctranslate2")]
+    Ct2(super::FasterWhisperModel),
+    Onnx(OnnxWhisper),
+    #[cfg(feature = "vosk")]
+    Vosk(super::VoskModel),
+}
+
+impl WhisperModel {
+    pub fn new(engine: &crate::AudioTranscriptionEngine, options: &ModelOptions) -> Result<Self> {
+        if let ModelSource::Path(path) = &options.source {
+            #[cfg(feature = "vosk")]
+            if *engine == crate::AudioTranscriptionEngine::Vosk {
+                set_active_device("cpu (vosk)".to_string());
+                return Ok(Self::Vosk(super::VoskModel::from_dir(path)?));
+            }
+            return Self::from_path(path, &options.device);
+        }
+
+        match engine {
+            #[cfg(feature = "whisper-cpp")]
+            crate::AudioTranscriptionEngine::WhisperCpp
+            | crate::AudioTranscriptionEngine::WhisperLargeV3Q5
+            | crate::AudioTranscriptionEngine::WhisperLargeV3TurboQ8 => {
+                let model = WhisperCppModel::new(ggml_model(engine), &options.device)?;
+                Ok(Self::cpp(model, &options.device))
+            }
+            #[cfg(not(feature = "whisper-cpp"))]
+            crate::AudioTranscriptionEngine::WhisperCpp
+            | crate::AudioTranscriptionEngine::WhisperLargeV3Q5
+            | crate::AudioTranscriptionEngine::WhisperLargeV3TurboQ8 => {
+                anyhow::bail!(
+                    "{} needs screenpipe built with the whisper-cpp feature",
+                    engine
+                )
+            }
+            #[cfg(feature = "ctranslate2")]
+            crate::AudioTranscriptionEngine::FasterWhisper => {
+                let model = super::FasterWhisperModel::new()?;
+                set_active_device("cpu (ctranslate2)".to_string());
+                Ok(Self::Ct2(model))
+            }
+            #[cfg(not(feature = "ctranslate2"))]
+            crate::AudioTranscriptionEngine::FasterWhisper => {
+                anyhow::bail!("faster-whisper needs screenpipe built with the ctranslate2 feature")
+            }
+            #[cfg(feature = "vosk")]
+            crate::AudioTranscriptionEngine::Vosk => {
+                let model = super::VoskModel::new()?;
+                set_active_device("cpu (vosk)".to_string());
+                Ok(Self::Vosk(model))
+            }
+            #[cfg(not(feature = "vosk"))]
+            crate::AudioTranscriptionEngine::Vosk => {
+                anyhow::bail!("vosk needs screenpipe built with the vosk feature")
+            }
+            crate::AudioTranscriptionEngine::WhisperOnnx(provider) => {
+                let model = OnnxWhisper::new(provider)?;
+                set_active_device(format!("{:?} (onnx)", provider).to_lowercase());
+                Ok(Self::Onnx(model))
+            }
+            _ => Ok(Self::candle(CandleWhisper::new(engine, &options.device)?)),
+        }
+    }
+
+    /// The model of `engine` already loaded with `options` by someone else, e.g. the whisper
+    /// channel for the live transcription, loading it when nobody holds it. Its clones share the
+    /// weights and decode independently. Blocks while loading
+    pub fn shared(
+        engine: &crate::AudioTranscriptionEngine,
+        options: &ModelOptions,
+    ) -> Result<Arc<Self>> {
+        let key = (
+            engine.clone(),
+            options.source.clone(),
+            options.device.clone(),
+        );
+        // held while loading, so two callers asking at once load it once
+        let mut models = SHARED_MODELS
+            .lock()
+            .map_err(|_| anyhow!("shared models lock poisoned"))?;
+        models.retain(|(_, model)| model.strong_count() > 0);
+        if let Some(model) = models
+            .iter()
+            .find(|(loaded, _)| *loaded == key)
+            .and_then(|(_, model)| model.upgrade())
+        {
+            return Ok(model);
+        }
+        let model = Arc::new(Self::new(engine, options)?);
+        models.push((key, Arc::downgrade(&model)));
+        Ok(model)
+    }
+
+    /// Loads a local model regardless of the engine preset, see `ModelSource::Path`
+    fn from_path(path: &Path, device: &DevicePreference) -> Result<Self> {
+        info!("loading local whisper model from {}", path.display());
+        if path.is_file() {
+            #[cfg(feature = "whisper-cpp")]
+            return Ok(Self::cpp(WhisperCppModel::from_file(path, device)?, device));
+            #[cfg(not(feature = "whisper-cpp"))]
+            return Err(anyhow!(
+                "ggml model {} needs screenpipe built with the whisper-cpp feature",
+                path.display()
+            ));
+        }
+        if !path.is_dir() {
+            return Err(anyhow!("model path {} does not exist", path.display()));
+        }
+        Ok(Self::candle(CandleWhisper::from_dir(path, device)?))
+    }
+
+    #[cfg(feature = "whisper-cpp")]
+    fn cpp(model: WhisperCppModel, device: &DevicePreference) -> Self {
+        set_active_device(match device {
+            DevicePreference::Cpu => "cpu (whisper.cpp)".to_string(),
+            _ => "gpu if available (whisper.cpp)".to_string(),
+        });
+        Self::Cpp(model)
+    }
+
+    fn candle(model: CandleWhisper) -> Self {
+        set_active_device(match model.device.location() {
+            DeviceLocation::Cpu => "cpu".to_string(),
+            DeviceLocation::Cuda { gpu_id } => format!("cuda:{}", gpu_id),
+            DeviceLocation::Metal { gpu_id } => format!("metal:{}", gpu_id),
+        });
+        Self::Candle(model)
+    }
+}
+
 #[derive(Clone)]
-pub struct WhisperModel {
+pub struct CandleWhisper {
     pub model: Model,
     pub tokenizer: Tokenizer,
     pub device: Device,
 }
 
-impl WhisperModel {
-    pub fn new(engine: &crate::AudioTranscriptionEngine) -> Result<Self> {
+impl CandleWhisper {
+    pub fn new(
+        engine: &crate::AudioTranscriptionEngine,
+        device: &DevicePreference,
+    ) -> Result<Self> {
         debug!("Initializing WhisperModel");
-        let device = Device::new_metal(0).unwrap_or(Device::new_cuda(0).unwrap_or(Device::Cpu));
+        let device = device.candle_device();
         info!("device = {:?}", device);
 
+        if let crate::AudioTranscriptionEngine::WhisperTinyQ8 = engine {
+            return Self::quantized(QUANTIZED_REPO, "tiny", "q80", device);
+        }
+
         debug!("Fetching model files");
         let (config_filename, tokenizer_filename, weights_filename) = {
             let api = Api::new()?;
@@ -38,6 +320,16 @@ impl WhisperModel {
                     RepoType::Model,
                     "main".to_string(),
                 ),
+                crate::AudioTranscriptionEngine::WhisperLargeV3 => Repo::with_revision(
+                    "openai/whisper-large-v3".to_string(),
+                    RepoType::Model,
+                    "main".to_string(),
+                ),
+                crate::AudioTranscriptionEngine::WhisperDistilSmallEn => Repo::with_revision(
+                    "distil-whisper/distil-small.en".to_string(),
+                    RepoType::Model,
+                    "main".to_string(),
+                ),
                 _ => Repo::with_revision(
                     "openai/whisper-large-v3-turbo".to_string(),
                     RepoType::Model,
@@ -51,13 +343,67 @@ impl WhisperModel {
             (config, tokenizer, model)
         };
 
+        Self::load(
+            &config_filename,
+            &tokenizer_filename,
+            &[weights_filename],
+            device,
+        )
+    }
+
+    /// Loads a local checkpoint in the huggingface layout: `config.json`, `tokenizer.json` and
+    /// `model.safetensors` (or shards of it), or a single `.gguf` file for quantized weights
+    pub fn from_dir(dir: &Path, device: &DevicePreference) -> Result<Self> {
+        let config_filename = dir.join("config.json");
+        let tokenizer_filename = dir.join("tokenizer.json");
+        for file in [&config_filename, &tokenizer_filename] {
+            if !file.exists() {
+                return Err(anyhow!("{} not found in model directory", file.display()));
+            }
+        }
+
+        let mut safetensors = Vec::new();
+        let mut gguf = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("safetensors") => safetensors.push(path),
+                Some("gguf") => gguf.push(path),
+                _ => {}
+            }
+        }
+        safetensors.sort();
+
+        let device = device.candle_device();
+        info!("device = {:?}", device);
+        match (safetensors.is_empty(), gguf.as_slice()) {
+            (false, _) => Self::load(&config_filename, &tokenizer_filename, &safetensors, device),
+            (true, [weights]) => {
+                Self::load_quantized(&config_filename, &tokenizer_filename, weights, device)
+            }
+            (true, []) => Err(anyhow!(
+                "no safetensors or gguf weights found in {}",
+                dir.display()
+            )),
+            (true, _) => Err(anyhow!(
+                "more than one gguf file in {}, keep only the one to load",
+                dir.display()
+            )),
+        }
+    }
+
+    fn load(
+        config_filename: &Path,
+        tokenizer_filename: &Path,
+        weights_filenames: &[PathBuf],
+        device: Device,
+    ) -> Result<Self> {
         debug!("Parsing config and tokenizer");
-        let config: Config = serde_json::from_str(&std::fs::read_to_string(config_filename)?)?;
-        let tokenizer = Tokenizer::from_file(tokenizer_filename).map_err(E::msg)?;
+        let (config, tokenizer) = load_config_and_tokenizer(config_filename, tokenizer_filename)?;
         // tokenizer.with_pre_tokenizer(PreT)
         debug!("Loading model weights");
         let vb =
-            unsafe { VarBuilder::from_mmaped_safetensors(&[weights_filename], m::DTYPE, &device)? };
+            unsafe { VarBuilder::from_mmaped_safetensors(weights_filenames, m::DTYPE, &device)? };
         let whisper = m::model::Whisper::load(&vb, config.clone())?;
 
         let model = Model::Normal(whisper);
@@ -69,6 +415,97 @@ impl WhisperModel {
             device,
         })
     }
+
+    /// Loads gguf weights quantized to `quantization` (e.g. "q80", "q4k"), published as
+    /// `model-{size}-{quantization}.gguf` with `config-{size}.json` and `tokenizer-{size}.json`
+    fn quantized(repo: &str, size: &str, quantization: &str, device: Device) -> Result<Self> {
+        debug!("Fetching quantized model files");
+        let api_repo = Api::new()?.repo(Repo::with_revision(
+            repo.to_string(),
+            RepoType::Model,
+            "main".to_string(),
+        ));
+        let config_filename = api_repo.get(&format!("config-{size}.json"))?;
+        let tokenizer_filename = api_repo.get(&format!("tokenizer-{size}.json"))?;
+        let weights_filename = api_repo.get(&format!("model-{size}-{quantization}.gguf"))?;
+
+        Self::load_quantized(
+            &config_filename,
+            &tokenizer_filename,
+            &weights_filename,
+            device,
+        )
+    }
+
+    fn load_quantized(
+        config_filename: &Path,
+        tokenizer_filename: &Path,
+        weights_filename: &Path,
+        device: Device,
+    ) -> Result<Self> {
+        let (config, tokenizer) = load_config_and_tokenizer(config_filename, tokenizer_filename)?;
+        debug!("Loading quantized model weights");
+        let vb = candle_transformers::quantized_var_builder::VarBuilder::from_gguf(
+            weights_filename,
+            &device,
+        )?;
+        let whisper = m::quantized_model::Whisper::load(&vb, config)?;
+
+        Ok(Self {
+            model: Model::Quantized(whisper),
+            tokenizer,
+            device,
+        })
+    }
+}
+
+/// Reads a whisper config and tokenizer, checking they belong together: a custom tokenizer
+/// must fit in the model vocabulary and have the special tokens decoding relies on
+fn load_config_and_tokenizer(
+    config_filename: &Path,
+    tokenizer_filename: &Path,
+) -> Result<(Config, Tokenizer)> {
+    let config: Config =
+        serde_json::from_str(&std::fs::read_to_string(config_filename)?).map_err(|e| {
+            anyhow!(
+                "{} is not a whisper config: {}",
+                config_filename.display(),
+                e
+            )
+        })?;
+    let tokenizer = Tokenizer::from_file(tokenizer_filename).map_err(E::msg)?;
+
+    let tokenizer_vocab_size = tokenizer.get_vocab_size(true);
+    if tokenizer_vocab_size > config.vocab_size {
+        return Err(anyhow!(
+            "tokenizer has {} tokens but the model vocabulary only {}",
+            tokenizer_vocab_size,
+            config.vocab_size
+        ));
+    }
+
+    let mut required = vec![
+        m::SOT_TOKEN,
+        m::EOT_TOKEN,
+        m::TRANSCRIBE_TOKEN,
+        m::NO_TIMESTAMPS_TOKEN,
+    ];
+    // multilingual models pick a language token, english-only ones never do
+    if config.vocab_size >= MULTILINGUAL_VOCAB_SIZE {
+        required.push("<|en|>");
+    }
+    let missing: Vec<&str> = required
+        .into_iter()
+        .filter(|token| tokenizer.token_to_id(token).is_none())
+        .collect();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "tokenizer is missing whisper special tokens {:?}",
+            missing
+        ));
+    }
+
+    Ok((config, tokenizer))
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,320 @@
+use super::{
+    mel_filters, prompt_tokens, timestamped_segments, token_id, DecodingOptions, DetectedLanguage,
+    SegmentConfidence, Task, Transcript,
+};
+use crate::multilingual::LANGUAGES;
+use anyhow::{anyhow, Context, Error as E, Result};
+use candle_transformers::models::whisper::{self as m, audio, Config};
+use hf_hub::{api::sync::Api, Repo, RepoType};
+use log::{debug, info, warn};
+use ndarray::{Array2, Array3, Axis};
+use ort::{
+    CPUExecutionProvider, CUDAExecutionProvider, CoreMLExecutionProvider,
+    DirectMLExecutionProvider, ExecutionProviderDispatch, GraphOptimizationLevel, Session,
+    TensorRTExecutionProvider,
+};
+use screenpipe_core::Language;
+use std::path::Path;
+use std::sync::Arc;
+use tokenizers::Tokenizer;
+
+const ONNX_REPO: &str = "onnx-community/whisper-large-v3-turbo";
+/// Upper bound of tokens decoded per 30s window, as in openai's implementation
+const MAX_TOKENS: usize = 224;
+
+/// Hardware the ONNX engine runs on. CPU is always kept as a fallback when the requested
+/// provider isn't available on this machine or in this build
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ExecutionProvider {
+    /// DirectML/CUDA on Windows, CoreML on macOS, CUDA on Linux
+    #[default]
+    Auto,
+    Cpu,
+    Cuda(i32),
+    TensorRt(i32),
+    DirectMl(i32),
+    CoreMl,
+}
+
+impl ExecutionProvider {
+    fn dispatch(&self) -> Vec<ExecutionProviderDispatch> {
+        match self {
+            Self::Auto if cfg!(target_os = "windows") => vec![
+                DirectMLExecutionProvider::default().build(),
+                CUDAExecutionProvider::default().build(),
+            ],
+            Self::Auto if cfg!(target_os = "macos") => {
+                vec![CoreMLExecutionProvider::default().build()]
+            }
+            Self::Auto => vec![CUDAExecutionProvider::default().build()],
+            Self::Cpu => vec![],
+            Self::Cuda(device) => vec![CUDAExecutionProvider::default()
+                .with_device_id(*device)
+                .build()],
+            Self::TensorRt(device) => vec![TensorRTExecutionProvider::default()
+                .with_device_id(*device)
+                .build()],
+            Self::DirectMl(device) => vec![DirectMLExecutionProvider::default()
+                .with_device_id(*device)
+                .build()],
+            Self::CoreMl => vec![CoreMLExecutionProvider::default().build()],
+        }
+    }
+}
+
+/// Whisper exported to ONNX, run with ONNX Runtime on the configured execution provider
+#[derive(Clone)]
+pub struct OnnxWhisper {
+    encoder: Arc<Session>,
+    decoder: Arc<Session>,
+    config: Config,
+    tokenizer: Tokenizer,
+    mel_filters: Vec<f32>,
+}
+
+impl OnnxWhisper {
+    pub fn new(provider: &ExecutionProvider) -> Result<Self> {
+        debug!("Fetching ONNX whisper model {}", ONNX_REPO);
+        let repo = Api::new()?.repo(Repo::with_revision(
+            ONNX_REPO.to_string(),
+            RepoType::Model,
+            "main".to_string(),
+        ));
+        let config_filename = repo.get("config.json")?;
+        let tokenizer_filename = repo.get("tokenizer.json")?;
+        let encoder_filename = repo.get("onnx/encoder_model.onnx")?;
+        // weights over 2GB are stored next to the graph, ort picks them up from the same directory
+        if let Err(e) = repo.get("onnx/encoder_model.onnx_data") {
+            debug!("no external encoder weights: {}", e);
+        }
+        let decoder_filename = repo.get("onnx/decoder_model.onnx")?;
+
+        let config: Config = serde_json::from_str(&std::fs::read_to_string(config_filename)?)?;
+        let tokenizer = Tokenizer::from_file(tokenizer_filename).map_err(E::msg)?;
+
+        info!("ONNX whisper execution provider: {:?}", provider);
+        let encoder = create_session(&encoder_filename, provider)?;
+        let decoder = create_session(&decoder_filename, provider)?;
+
+        Ok(Self {
+            encoder: Arc::new(encoder),
+            decoder: Arc::new(decoder),
+            mel_filters: mel_filters(config.num_mel_bins)?,
+            config,
+            tokenizer,
+        })
+    }
+
+    /// Transcribes 16khz mono audio in 30s windows with greedy decoding, so only the no speech
+    /// thresholds of `decoding` apply
+    pub fn transcribe(
+        &self,
+        audio: &[f32],
+        languages: &[Language],
+        task: Task,
+        prompt: Option<&str>,
+        decoding: &DecodingOptions,
+    ) -> Result<Transcript> {
+        let n_mels = self.config.num_mel_bins;
+        let mel = audio::pcm_to_mel(&self.config, audio, &self.mel_filters);
+        let mel = Array2::from_shape_vec((n_mels, mel.len() / n_mels), mel)?;
+        let content_frames = audio.len() / m::HOP_LENGTH;
+
+        let mut transcript = String::new();
+        let mut segments = Vec::new();
+        let (mut logprob_sum, mut no_speech_sum, mut duration_sum) = (0f64, 0f64, 0f64);
+        // language of the first window with speech
+        let mut language = None;
+
+        let mut seek = 0;
+        while seek < content_frames.min(mel.ncols()) {
+            let segment_size = usize::min(content_frames - seek, m::N_FRAMES);
+            // the exported encoder only takes full 30s windows
+            let mut features = Array3::<f32>::zeros((1, n_mels, m::N_FRAMES));
+            let end = (seek + segment_size).min(mel.ncols());
+            features
+                .slice_mut(ndarray::s![0, .., ..end - seek])
+                .assign(&mel.slice(ndarray::s![.., seek..end]));
+            let start = (seek * m::HOP_LENGTH) as f64 / m::SAMPLE_RATE as f64;
+            seek += segment_size;
+
+            let (text, avg_logprob, no_speech_prob, window_language) =
+                self.decode_window(features, languages, task, prompt)?;
+            if decoding.is_no_speech(no_speech_prob, avg_logprob) {
+                debug!("no speech detected, skipping window ending at frame {seek}");
+                continue;
+            }
+            let duration = (segment_size * m::HOP_LENGTH) as f64 / m::SAMPLE_RATE as f64;
+            let text = text.trim();
+            if !text.is_empty() {
+                transcript.push_str(text);
+                transcript.push('\n');
+                // decoded without timestamps, the text spans its window
+                segments.extend(timestamped_segments(text, start, duration));
+            }
+            language.get_or_insert(window_language);
+
+            logprob_sum += avg_logprob * duration;
+            no_speech_sum += no_speech_prob * duration;
+            duration_sum += duration;
+        }
+
+        let confidence = (duration_sum > 0.0).then(|| SegmentConfidence {
+            avg_logprob: logprob_sum / duration_sum,
+            no_speech_prob: no_speech_sum / duration_sum,
+        });
+
+        Ok(Transcript {
+            text: transcript,
+            confidence,
+            language,
+            engine: None,
+            segments,
+            model_version: None,
+        })
+    }
+
+    /// Text, average log probability, no speech probability and language of a 30s window
+    fn decode_window(
+        &self,
+        features: Array3<f32>,
+        languages: &[Language],
+        task: Task,
+        prompt: Option<&str>,
+    ) -> Result<(String, f64, f64, DetectedLanguage)> {
+        let outputs = self
+            .encoder
+            .run(ort::inputs!["input_features" => features.view()]?)?;
+        let hidden_states = outputs
+            .get("last_hidden_state")
+            .context("encoder output not found")?
+            .try_extract_tensor::<f32>()?
+            .to_owned();
+
+        let sot_token = token_id(&self.tokenizer, m::SOT_TOKEN)?;
+        let task_token = token_id(&self.tokenizer, task.token())?;
+        let no_timestamps_token = token_id(&self.tokenizer, m::NO_TIMESTAMPS_TOKEN)?;
+        let eot_token = token_id(&self.tokenizer, m::EOT_TOKEN)?;
+        let no_speech_token = m::NO_SPEECH_TOKENS
+            .iter()
+            .find_map(|token| token_id(&self.tokenizer, token).ok())
+            .ok_or_else(|| anyhow!("unable to find any non-speech token"))?;
+
+        // the first prediction after sot holds both the language and no speech probabilities
+        let first = softmax(&self.next_logits(&[sot_token], &hidden_states)?);
+        let no_speech_prob = first[no_speech_token as usize] as f64;
+        let language_codes: Vec<&str> = if languages.is_empty() {
+            LANGUAGES.iter().map(|(code, _)| *code).collect()
+        } else {
+            languages.iter().map(|l| l.as_lang_code()).collect()
+        };
+        let (language_code, language_token) = language_codes
+            .iter()
+            .filter_map(|code| {
+                token_id(&self.tokenizer, &format!("<|{code}|>"))
+                    .ok()
+                    .map(|token| (*code, token))
+            })
+            .max_by(|a, b| first[a.1 as usize].total_cmp(&first[b.1 as usize]))
+            .ok_or_else(|| anyhow!("no language token found"))?;
+        let language = match languages {
+            [_] => DetectedLanguage::forced(language_code),
+            _ => DetectedLanguage {
+                code: language_code.to_string(),
+                probability: first[language_token as usize],
+            },
+        };
+
+        let mut tokens = prompt_tokens(&self.tokenizer, prompt, MAX_TOKENS)?;
+        tokens.extend([sot_token, language_token, task_token, no_timestamps_token]);
+        let prompt_len = tokens.len();
+        let mut logprob_sum = 0f64;
+        while tokens.len() - prompt_len < MAX_TOKENS
+            && tokens.len() < self.config.max_target_positions
+        {
+            let logits = self.next_logits(&tokens, &hidden_states)?;
+            let (next, _) = logits
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .ok_or_else(|| anyhow!("empty logits"))?;
+            let next = next as u32;
+            if next == eot_token {
+                break;
+            }
+            logprob_sum += log_softmax_at(&logits, next as usize);
+            tokens.push(next);
+        }
+
+        let generated = &tokens[prompt_len..];
+        let avg_logprob = logprob_sum / generated.len().max(1) as f64;
+        let text = self.tokenizer.decode(generated, true).map_err(E::msg)?;
+
+        Ok((text, avg_logprob, no_speech_prob, language))
+    }
+
+    /// Logits of the token following `tokens`
+    fn next_logits(
+        &self,
+        tokens: &[u32],
+        hidden_states: &ndarray::ArrayD<f32>,
+    ) -> Result<Vec<f32>> {
+        let input_ids = Array2::from_shape_vec(
+            (1, tokens.len()),
+            tokens.iter().map(|t| *t as i64).collect(),
+        )?;
+        let outputs = self.decoder.run(ort::inputs![
+            "input_ids" => input_ids.view(),
+            "encoder_hidden_states" => hidden_states.view(),
+        ]?)?;
+        let logits = outputs
+            .get("logits")
+            .context("decoder output not found")?
+            .try_extract_tensor::<f32>()?;
+
+        // [batch, tokens, vocab]
+        let last = logits
+            .index_axis(Axis(0), 0)
+            .index_axis(Axis(0), tokens.len() - 1)
+            .iter()
+            .copied()
+            .collect();
+        Ok(last)
+    }
+}
+
+fn create_session(path: &Path, provider: &ExecutionProvider) -> Result<Session> {
+    let mut providers = provider.dispatch();
+    providers.push(CPUExecutionProvider::default().build());
+
+    let session = Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .with_execution_providers(providers)?
+        .commit_from_file(path);
+    match session {
+        Ok(session) => Ok(session),
+        Err(e) if *provider != ExecutionProvider::Cpu => {
+            warn!(
+                "failed to load {} with {:?}, falling back to cpu: {}",
+                path.display(),
+                provider,
+                e
+            );
+            create_session(path, &ExecutionProvider::Cpu)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exp: Vec<f32> = logits.iter().map(|l| (l - max).exp()).collect();
+    let sum: f32 = exp.iter().sum();
+    exp.into_iter().map(|e| e / sum).collect()
+}
+
+fn log_softmax_at(logits: &[f32], index: usize) -> f64 {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max) as f64;
+    let sum: f64 = logits.iter().map(|l| (*l as f64 - max).exp()).sum();
+    logits[index] as f64 - max - sum.ln()
+}
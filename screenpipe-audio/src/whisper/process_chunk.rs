@@ -1,33 +1,288 @@
 use super::Segment;
 use crate::{
-    multilingual,
-    whisper::{Decoder, WhisperModel},
+    multilingual::{self, LanguageHint},
+    whisper::{CandleWhisper, Decoder, DecodingOptions, Task, WhisperModel},
+    AudioTranscriptionEngine,
 };
 use anyhow::Result;
 use candle::Tensor;
-use candle_transformers::models::whisper::audio;
+use candle_transformers::models::whisper::{self as m, audio};
 use lazy_static::lazy_static;
-use log::debug;
+use log::{debug, warn};
 use regex::Regex;
-use screenpipe_core::Language;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
+/// Multilingual whisper models have this many tokens or more, english-only ones one less
+pub(crate) const MULTILINGUAL_VOCAB_SIZE: usize = 51865;
+
 lazy_static! {
     static ref TOKEN_REGEX: Regex = Regex::new(r"<\|\d{1,2}\.\d{1,2}\|>").unwrap();
 }
 
+/// How sure whisper was of a transcript, averaged over its decoded segments by duration
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SegmentConfidence {
+    /// Average log probability of the decoded tokens, closer to 0 is better
+    pub avg_logprob: f64,
+    /// Probability that the audio contains no speech at all
+    pub no_speech_prob: f64,
+}
+
+impl SegmentConfidence {
+    /// Rough 0..1 score combining both, for consumers that just want to threshold
+    pub fn score(&self) -> f64 {
+        (self.avg_logprob.exp() * (1.0 - self.no_speech_prob)).clamp(0.0, 1.0)
+    }
+}
+
+/// Language a segment was transcribed in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectedLanguage {
+    /// ISO 639-1 code, e.g. "en"
+    pub code: String,
+    /// Probability whisper gave the language, 1 when it was forced
+    pub probability: f32,
+}
+
+impl DetectedLanguage {
+    pub fn forced(code: &str) -> Self {
+        Self {
+            code: code.to_string(),
+            probability: 1.0,
+        }
+    }
+}
+
+/// Text of a transcribed segment, with whisper's decoding confidence and the language it was
+/// transcribed in (None for engines not reporting them)
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    pub text: String,
+    pub confidence: Option<SegmentConfidence>,
+    pub language: Option<DetectedLanguage>,
+    /// Engine of the fallback chain that transcribed, set by `transcribe`
+    pub engine: Option<AudioTranscriptionEngine>,
+    /// The text split where the engine timestamped it, empty for engines not giving timestamps
+    pub segments: Vec<TextSegment>,
+    /// Model the provider says it transcribed with, None for providers not reporting it
+    pub model_version: Option<String>,
+}
+
+impl Transcript {
+    /// Model the transcript was made with: the one the provider reported, else the one of the
+    /// local engine that transcribed it
+    pub fn model_version(&self) -> Option<String> {
+        self.model_version.clone().or_else(|| {
+            self.engine
+                .as_ref()
+                .and_then(|engine| engine.model_version())
+                .map(str::to_string)
+        })
+    }
+}
+
+/// Part of a transcript with when it was said, in seconds from the start of the transcribed audio
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextSegment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
 pub fn process_with_whisper(
     whisper_model: &mut WhisperModel,
     audio: &[f32],
-    mel_filters: &[f32],
-    languages: Vec<Language>,
-) -> Result<String> {
-    let model = &mut whisper_model.model;
-    let tokenizer = &whisper_model.tokenizer;
-    let device = &whisper_model.device;
+    language: &LanguageHint,
+    task: Task,
+    prompt: Option<&str>,
+    decoding: &DecodingOptions,
+) -> Result<Transcript> {
+    match whisper_model {
+        WhisperModel::Candle(whisper) => {
+            process_with_candle(whisper, audio, language, task, prompt, decoding)
+        }
+        // the other backends force a single candidate and detect among several themselves
+        #[cfg(feature = "whisper-cpp")]
+        WhisperModel::Cpp(whisper) => {
+            whisper.transcribe(audio, &language.candidates(), task, prompt, decoding)
+        }
+        #[cfg(feature = "ctranslate2")]
+        WhisperModel::Ct2(whisper) => {
+            whisper.transcribe(audio, &language.candidates(), task, prompt, decoding)
+        }
+        WhisperModel::Onnx(whisper) => {
+            whisper.transcribe(audio, &language.candidates(), task, prompt, decoding)
+        }
+        #[cfg(feature = "vosk")]
+        WhisperModel::Vosk(model) => {
+            model.transcribe(audio, &language.candidates(), task, prompt, decoding)
+        }
+    }
+}
+
+/// Most likely language of `audio` among every language the model knows, from its first 30s,
+/// without decoding it. None for models that can't tell: english-only ones and the other backends
+/// than candle, which identify languages only while transcribing
+pub fn identify_language(
+    whisper_model: &mut WhisperModel,
+    audio: &[f32],
+) -> Result<Option<DetectedLanguage>> {
+    let WhisperModel::Candle(whisper) = whisper_model else {
+        return Ok(None);
+    };
+    let model = &mut whisper.model;
+    if model.config().vocab_size < MULTILINGUAL_VOCAB_SIZE {
+        return Ok(None);
+    }
+
+    let num_mel_bins = model.config().num_mel_bins;
+    let mel_filters = mel_filters(num_mel_bins)?;
+    let audio = &audio[..audio.len().min(m::N_SAMPLES)];
+    let mel = audio::pcm_to_mel(model.config(), audio, &mel_filters);
+    let frames = mel.len() / num_mel_bins;
+    let mel = Tensor::from_vec(mel, (1, num_mel_bins, frames), &whisper.device)?;
+    let (_, language) = multilingual::detect_language(model, &whisper.tokenizer, &mel, Vec::new())?;
+    Ok(Some(language))
+}
+
+/// Transcribes several clips with the same settings. Candle models on a GPU decode the clips of
+/// up to 30s together, falling back to one at a time for clips whose greedy decoding is too
+/// repetitive or unsure; other models transcribe one clip after the other
+pub fn process_batch_with_whisper(
+    whisper_model: &mut WhisperModel,
+    audios: &[&[f32]],
+    language: &LanguageHint,
+    task: Task,
+    prompt: Option<&str>,
+    decoding: &DecodingOptions,
+) -> Vec<Result<Transcript>> {
+    let mut transcripts: Vec<Option<Result<Transcript>>> = audios.iter().map(|_| None).collect();
+
+    if let WhisperModel::Candle(whisper) = whisper_model {
+        // batches decode greedily at temperature 0, like the first attempt of a single clip
+        let greedy =
+            decoding.beam_size <= 1 && decoding.temperatures.first().map_or(true, |&t| t == 0.0);
+        let batchable: Vec<usize> = (0..audios.len())
+            .filter(|&i| audios[i].len() <= m::N_SAMPLES)
+            .collect();
+        if !whisper.device.is_cpu() && greedy && batchable.len() > 1 {
+            let clips: Vec<&[f32]> = batchable.iter().map(|&i| audios[i]).collect();
+            match batch_with_candle(whisper, &clips, language, task, prompt, decoding) {
+                Ok(results) => {
+                    for (i, transcript) in batchable.into_iter().zip(results) {
+                        transcripts[i] = transcript.map(Ok);
+                    }
+                }
+                Err(e) => warn!(
+                    "batched transcription failed, transcribing one by one: {}",
+                    e
+                ),
+            }
+        }
+    }
+
+    transcripts
+        .into_iter()
+        .zip(audios)
+        .map(|(transcript, audio)| {
+            transcript.unwrap_or_else(|| {
+                process_with_whisper(whisper_model, audio, language, task, prompt, decoding)
+            })
+        })
+        .collect()
+}
+
+/// Decodes clips of up to 30s in one batch. None for the clips whose decoding needs another
+/// temperature, which are left to `process_with_candle`
+fn batch_with_candle(
+    whisper: &mut CandleWhisper,
+    audios: &[&[f32]],
+    language: &LanguageHint,
+    task: Task,
+    prompt: Option<&str>,
+    decoding: &DecodingOptions,
+) -> Result<Vec<Option<Transcript>>> {
+    let model = &mut whisper.model;
+    let tokenizer = &whisper.tokenizer;
+    let device = &whisper.device;
+
+    let num_mel_bins = model.config().num_mel_bins;
+    let mel_filters = mel_filters(num_mel_bins)?;
+    let multilingual = model.config().vocab_size >= MULTILINGUAL_VOCAB_SIZE;
+
+    let mut mels = Vec::with_capacity(audios.len());
+    let mut languages = Vec::with_capacity(audios.len());
+    for audio in audios {
+        let mel = audio::pcm_to_mel(model.config(), audio, &mel_filters);
+        let frames = mel.len() / num_mel_bins;
+        // pcm_to_mel pads with at least a window of silence, so clips up to 30s fill N_FRAMES
+        let mel =
+            Tensor::from_vec(mel, (1, num_mel_bins, frames), device)?.narrow(2, 0, m::N_FRAMES)?;
+        languages.push(if multilingual {
+            let (token, language) = multilingual::language_token(model, tokenizer, &mel, language)?;
+            (Some(token), language)
+        } else {
+            (None, DetectedLanguage::forced("en"))
+        });
+        mels.push(mel);
+    }
+    let mel = Tensor::cat(&mels, 0)?;
+    let language_tokens: Vec<Option<u32>> = languages.iter().map(|(token, _)| *token).collect();
+
+    // english-only models can't translate, see process_with_candle
+    let task = if multilingual { task } else { Task::Transcribe };
+    let mut dc = Decoder::new(
+        model, tokenizer, 42, device, None, task, prompt, decoding, true, false,
+    )?;
+    debug!("decoding a batch of {} clips", audios.len());
+    let results = dc.decode_batch(&mel, &language_tokens)?;
+
+    let can_fall_back = decoding.temperatures.len() > 1;
+    let mut transcripts = Vec::with_capacity(results.len());
+    for (dr, (_, language)) in results.into_iter().zip(languages) {
+        if can_fall_back && decoding.needs_fallback(&dr) {
+            transcripts.push(None);
+            continue;
+        }
+        let segments = if decoding.is_no_speech(dr.no_speech_prob, dr.avg_logprob) {
+            Vec::new()
+        } else {
+            vec![Segment {
+                start: 0.0,
+                duration: m::CHUNK_LENGTH as f64,
+                dr,
+            }]
+        };
+        let (text, confidence, segments) = process_segments(segments)?;
+        transcripts.push(Some(Transcript {
+            text,
+            confidence,
+            language: Some(language),
+            engine: None,
+            segments,
+            model_version: None,
+        }));
+    }
+    Ok(transcripts)
+}
+
+fn process_with_candle(
+    whisper: &mut CandleWhisper,
+    audio: &[f32],
+    language: &LanguageHint,
+    task: Task,
+    prompt: Option<&str>,
+    decoding: &DecodingOptions,
+) -> Result<Transcript> {
+    let model = &mut whisper.model;
+    let tokenizer = &whisper.tokenizer;
+    let device = &whisper.device;
+
+    let mel_filters = mel_filters(model.config().num_mel_bins)?;
 
     debug!("converting pcm to mel spectrogram");
-    let mel = audio::pcm_to_mel(model.config(), audio, mel_filters);
+    let mel = audio::pcm_to_mel(model.config(), audio, &mel_filters);
     let mel_len = mel.len();
 
     debug!("creating tensor from mel spectrogram");
@@ -41,27 +296,65 @@ pub fn process_with_whisper(
         device,
     )?;
 
-    debug!("detecting language");
-    let language_token = Some(multilingual::detect_language(
-        model,
-        tokenizer,
-        &mel,
-        languages.clone(),
-    )?);
+    // english-only models (e.g. distil-small.en) have no language tokens and can't translate
+    let (language_token, language, task) = if model.config().vocab_size >= MULTILINGUAL_VOCAB_SIZE {
+        let (token, language) = multilingual::language_token(model, tokenizer, &mel, language)?;
+        (Some(token), language, task)
+    } else {
+        (None, DetectedLanguage::forced("en"), Task::Transcribe)
+    };
 
     debug!("initializing decoder");
-    let mut dc = Decoder::new(model, tokenizer, 42, device, language_token, true, false)?;
+    let mut dc = Decoder::new(
+        model,
+        tokenizer,
+        42,
+        device,
+        language_token,
+        task,
+        prompt,
+        decoding,
+        true,
+        false,
+    )?;
 
     debug!("starting decoding process");
     let segments = dc.run(&mel)?;
     debug!("decoding complete");
 
-    process_segments(segments)
+    let (text, confidence, segments) = process_segments(segments)?;
+    Ok(Transcript {
+        text,
+        confidence,
+        language: Some(language),
+        engine: None,
+        segments,
+        model_version: None,
+    })
+}
+
+/// Mel filter bank for models with `num_mel_bins` mel bins
+pub(crate) fn mel_filters(num_mel_bins: usize) -> Result<Vec<f32>> {
+    debug!("Loading mel filters");
+    let mel_bytes = match num_mel_bins {
+        80 => include_bytes!("../../models/whisper/melfilters.bytes").as_slice(),
+        128 => include_bytes!("../../models/whisper/melfilters128.bytes").as_slice(),
+        nmel => anyhow::bail!("unexpected num_mel_bins {nmel}"),
+    };
+    let mut mel_filters = vec![0f32; mel_bytes.len() / 4];
+    <byteorder::LittleEndian as byteorder::ByteOrder>::read_f32_into(mel_bytes, &mut mel_filters);
+    Ok(mel_filters)
 }
 
-fn process_segments(segments: Vec<Segment>) -> Result<String> {
+/// Text of the decoded segments without their timestamp tokens, their confidence and the text
+/// split at the timestamps
+fn process_segments(
+    segments: Vec<Segment>,
+) -> Result<(String, Option<SegmentConfidence>, Vec<TextSegment>)> {
     let mut ranges: HashSet<String> = HashSet::new();
     let mut transcript = String::new();
+    let mut text_segments = Vec::new();
+    let (mut logprob_sum, mut no_speech_sum, mut duration_sum) = (0f64, 0f64, 0f64);
 
     let mut min_time: f32 = f32::MAX;
     let mut max_time: f32 = f32::MIN;
@@ -81,13 +374,57 @@ fn process_segments(segments: Vec<Segment>) -> Result<String> {
                 continue;
             }
 
+            text_segments.extend(timestamped_segments(&text, segment.start, segment.duration));
             text = TOKEN_REGEX.replace_all(&text, "").to_string();
             text.push('\n');
             transcript.push_str(&text);
+
+            if segment.dr.avg_logprob.is_finite() && segment.dr.no_speech_prob.is_finite() {
+                logprob_sum += segment.dr.avg_logprob * segment.duration;
+                no_speech_sum += segment.dr.no_speech_prob * segment.duration;
+                duration_sum += segment.duration;
+            }
         }
     }
 
-    Ok(transcript)
+    let confidence = (duration_sum > 0.0).then(|| SegmentConfidence {
+        avg_logprob: logprob_sum / duration_sum,
+        no_speech_prob: no_speech_sum / duration_sum,
+    });
+
+    Ok((transcript, confidence, text_segments))
+}
+
+/// `text` decoded from `duration` seconds of audio starting `start` seconds in, split at its
+/// timestamp tokens. Text without timestamps spans the whole audio
+pub(crate) fn timestamped_segments(text: &str, start: f64, duration: f64) -> Vec<TextSegment> {
+    let mut segments = Vec::new();
+    // time and end of the previous timestamp token
+    let mut previous: Option<(f64, usize)> = None;
+    for token in TOKEN_REGEX.find_iter(text) {
+        let Ok(time) = token.as_str()[2..token.as_str().len() - 2].parse::<f64>() else {
+            continue;
+        };
+        if let Some((previous_time, from)) = previous {
+            let said = text[from..token.start()].trim();
+            if !said.is_empty() {
+                segments.push(TextSegment {
+                    start: start + previous_time,
+                    end: start + time,
+                    text: said.to_string(),
+                });
+            }
+        }
+        previous = Some((time, token.end()));
+    }
+    if previous.is_none() && !text.trim().is_empty() {
+        segments.push(TextSegment {
+            start,
+            end: start + duration,
+            text: text.trim().to_string(),
+        });
+    }
+    segments
 }
 
 fn extract_time_tokens(text: &str, token_regex: &Regex) -> (String, String) {
@@ -0,0 +1,190 @@
+use super::{DecodingOptions, DetectedLanguage, SegmentConfidence, Task, TextSegment, Transcript};
+use crate::models::{self, ModelFile};
+use anyhow::{anyhow, Result};
+use candle_transformers::models::whisper as m;
+use log::{debug, info, warn};
+use screenpipe_core::Language;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use vosk::{DecodingState, Model, Recognizer, Word};
+
+/// Audio fed to the recognizer at once, it finalizes an utterance whenever it hears its end
+const FEED_SAMPLES: usize = 3200;
+
+/// Small (~50 MB) vosk model of a language, from alphacephei.com/vosk/models
+fn small_model(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "en" => "vosk-model-small-en-us-0.15",
+        "de" => "vosk-model-small-de-0.15",
+        "fr" => "vosk-model-small-fr-0.22",
+        "es" => "vosk-model-small-es-0.42",
+        "ru" => "vosk-model-small-ru-0.22",
+        "zh" => "vosk-model-small-cn-0.22",
+        "ja" => "vosk-model-small-ja-0.22",
+        "it" => "vosk-model-small-it-0.22",
+        "pt" => "vosk-model-small-pt-0.3",
+        "nl" => "vosk-model-small-nl-0.22",
+        "ko" => "vosk-model-small-ko-0.22",
+        "hi" => "vosk-model-small-hi-0.22",
+        "tr" => "vosk-model-small-tr-0.3",
+        _ => return None,
+    })
+}
+
+/// Vosk (kaldi) recognizer, a lot lighter than any whisper model but less accurate. Models are
+/// per language and loaded on first use
+#[derive(Clone)]
+pub struct VoskModel {
+    models: HashMap<String, Arc<Model>>,
+    /// Model of `ModelSource::Path`, used whatever the language
+    local: Option<Arc<Model>>,
+}
+
+impl VoskModel {
+    pub fn new() -> Result<Self> {
+        let mut model = Self {
+            models: HashMap::new(),
+            local: None,
+        };
+        // fetch english upfront so the first segment isn't stuck behind a download
+        model.for_language("en")?;
+        Ok(model)
+    }
+
+    /// An unpacked vosk model directory
+    pub fn from_dir(path: &Path) -> Result<Self> {
+        Ok(Self {
+            models: HashMap::new(),
+            local: Some(Arc::new(load(path)?)),
+        })
+    }
+
+    /// Transcribes 16khz mono audio with the model of the first of `languages` vosk has, english
+    /// if none. Vosk neither detects languages nor translates, and takes no prompt
+    pub fn transcribe(
+        &mut self,
+        audio: &[f32],
+        languages: &[Language],
+        task: Task,
+        prompt: Option<&str>,
+        _decoding: &DecodingOptions,
+    ) -> Result<Transcript> {
+        if task == Task::Translate {
+            warn!("vosk can't translate, transcribing instead");
+        }
+        if prompt.is_some() {
+            debug!("vosk doesn't support initial prompts, ignoring it");
+        }
+        let code = languages
+            .iter()
+            .map(|language| language.as_lang_code())
+            .find(|code| small_model(code).is_some())
+            .unwrap_or("en");
+        let (model, model_version) = match &self.local {
+            Some(model) => (model.clone(), None),
+            None => (
+                self.for_language(code)?,
+                small_model(code).map(str::to_string),
+            ),
+        };
+
+        let mut recognizer = Recognizer::new(&model, m::SAMPLE_RATE as f32)
+            .ok_or_else(|| anyhow!("failed to create vosk recognizer"))?;
+        recognizer.set_words(true);
+
+        let samples: Vec<i16> = audio
+            .iter()
+            .map(|&sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect();
+        let mut lines = Vec::new();
+        let mut confidences = Vec::new();
+        let mut segments = Vec::new();
+        // each finalized utterance, timed by its first and last words
+        let mut collect = |text: &str, words: &[Word]| {
+            if !text.trim().is_empty() {
+                lines.push(text.trim().to_string());
+                confidences.extend(words.iter().map(|w| w.conf));
+                if let (Some(first), Some(last)) = (words.first(), words.last()) {
+                    segments.push(TextSegment {
+                        start: first.start as f64,
+                        end: last.end as f64,
+                        text: text.trim().to_string(),
+                    });
+                }
+            }
+        };
+        for piece in samples.chunks(FEED_SAMPLES) {
+            let state = recognizer
+                .accept_waveform(piece)
+                .map_err(|e| anyhow!("vosk failed to decode: {:?}", e))?;
+            if state == DecodingState::Finalized {
+                if let Some(result) = recognizer.result().single() {
+                    collect(result.text, &result.result);
+                }
+            }
+        }
+        if let Some(result) = recognizer.final_result().single() {
+            collect(result.text, &result.result);
+        }
+
+        let mut text = lines.join("\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        let confidence = (!confidences.is_empty()).then(|| {
+            let mean = confidences.iter().sum::<f32>() / confidences.len() as f32;
+            SegmentConfidence {
+                avg_logprob: (mean as f64).max(f64::MIN_POSITIVE).ln(),
+                no_speech_prob: 0.0,
+            }
+        });
+
+        Ok(Transcript {
+            text,
+            confidence,
+            language: Some(DetectedLanguage::forced(code)),
+            engine: None,
+            segments,
+            model_version,
+        })
+    }
+
+    /// The small model of the language, downloaded and unpacked the first time
+    fn for_language(&mut self, code: &str) -> Result<Arc<Model>> {
+        if let Some(model) = self.models.get(code) {
+            return Ok(model.clone());
+        }
+        let name = small_model(code).ok_or_else(|| anyhow!("no vosk model for {}", code))?;
+        let model = Arc::new(load(&model_dir(name)?)?);
+        self.models.insert(code.to_string(), model.clone());
+        Ok(model)
+    }
+}
+
+fn load(path: &Path) -> Result<Model> {
+    let model = Model::new(path.to_string_lossy())
+        .ok_or_else(|| anyhow!("failed to load vosk model from {}", path.display()))?;
+    info!("vosk model loaded from {}", path.display());
+    Ok(model)
+}
+
+/// Directory of the unpacked model `name` in the models directory
+fn model_dir(name: &str) -> Result<PathBuf> {
+    let vosk_dir = models::models_dir()?.join("vosk");
+    let dir = vosk_dir.join(name);
+    if dir.is_dir() {
+        return Ok(dir);
+    }
+
+    let archive = models::get_or_download_with_events(&ModelFile::new(
+        format!("vosk/{}.zip", name),
+        format!("https://alphacephei.com/vosk/models/{}.zip", name),
+    ))?;
+    debug!("unpacking {:?}", archive);
+    // the archive holds a single `name` directory
+    zip::ZipArchive::new(File::open(&archive)?)?.extract(&vosk_dir)?;
+    fs::remove_file(&archive)?;
+    Ok(dir)
+}
@@ -0,0 +1,196 @@
+use crate::whisper::{DetectedLanguage, SegmentConfidence, Task, TextSegment, Transcript};
+use anyhow::Result;
+use clap::ValueEnum;
+use hound::{WavSpec, WavWriter};
+use log::{debug, info};
+use reqwest::multipart::{Form, Part};
+use reqwest::Client;
+use screenpipe_core::Language;
+use serde::Deserialize;
+use std::fmt;
+use tempfile::NamedTempFile;
+
+/// Both APIs reject uploads above 25 MB
+const UPLOAD_LIMIT_BYTES: usize = 25 * 1024 * 1024;
+/// Room left for the wav header and the multipart fields
+const UPLOAD_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// Hosted whisper behind an OpenAI compatible transcription API
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WhisperApiProvider {
+    /// OpenAI's `whisper-1`
+    OpenAI,
+    /// Groq's `whisper-large-v3`
+    Groq,
+}
+
+impl WhisperApiProvider {
+    fn base_url(&self) -> &'static str {
+        match self {
+            WhisperApiProvider::OpenAI => "https://api.openai.com/v1",
+            WhisperApiProvider::Groq => "https://api.groq.com/openai/v1",
+        }
+    }
+
+    fn model(&self) -> &'static str {
+        match self {
+            WhisperApiProvider::OpenAI => "whisper-1",
+            WhisperApiProvider::Groq => "whisper-large-v3",
+        }
+    }
+}
+
+impl fmt::Display for WhisperApiProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WhisperApiProvider::OpenAI => write!(f, "openai"),
+            WhisperApiProvider::Groq => write!(f, "groq"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct VerboseTranscription {
+    text: String,
+    /// Full name, e.g. "english"
+    language: Option<String>,
+    #[serde(default)]
+    segments: Vec<ApiSegment>,
+}
+
+#[derive(Deserialize)]
+struct ApiSegment {
+    start: f64,
+    end: f64,
+    #[serde(default)]
+    text: String,
+    avg_logprob: f64,
+    no_speech_prob: f64,
+}
+
+/// Transcribes with a hosted whisper: the chunk is encoded to a temporary wav file which is posted
+/// to the transcription endpoint, or the translation one for `Task::Translate`. Chunks too large
+/// for a single upload are split and their transcripts joined.
+///
+/// A single language is forced, otherwise whisper detects it
+#[allow(clippy::too_many_arguments)]
+pub async fn transcribe_with_whisper_api(
+    provider: WhisperApiProvider,
+    api_key: &str,
+    audio_data: &[f32],
+    device: &str,
+    sample_rate: u32,
+    languages: &[Language],
+    task: Task,
+    prompt: Option<&str>,
+) -> Result<Transcript> {
+    debug!("starting {} whisper api transcription", provider);
+    let client = Client::new();
+    let endpoint = match task {
+        Task::Transcribe => "transcriptions",
+        Task::Translate => "translations",
+    };
+    // 16 bit mono samples
+    let samples_per_upload = (UPLOAD_LIMIT_BYTES - UPLOAD_OVERHEAD_BYTES) / 2;
+
+    let mut texts = Vec::new();
+    let mut segments = Vec::new();
+    let mut language = None;
+    let (mut logprob_sum, mut no_speech_sum, mut duration_sum) = (0.0, 0.0, 0.0);
+    for (i, part) in audio_data.chunks(samples_per_upload).enumerate() {
+        // timestamps of later uploads start over at 0
+        let offset = (i * samples_per_upload) as f64 / sample_rate as f64;
+        let file = temp_wav(part, sample_rate)?;
+        let mut form = Form::new()
+            .part(
+                "file",
+                Part::bytes(tokio::fs::read(file.path()).await?)
+                    .file_name("audio.wav")
+                    .mime_str("audio/wav")?,
+            )
+            .text("model", provider.model())
+            .text("response_format", "verbose_json");
+        if let (Task::Transcribe, [forced]) = (task, languages) {
+            form = form.text("language", forced.as_lang_code());
+        }
+        if let Some(prompt) = prompt {
+            form = form.text("prompt", prompt.to_string());
+        }
+
+        let transcription: VerboseTranscription = client
+            .post(format!("{}/audio/{}", provider.base_url(), endpoint))
+            .bearer_auth(api_key)
+            .multipart(form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        for segment in &transcription.segments {
+            let duration = (segment.end - segment.start).max(0.0);
+            if segment.avg_logprob.is_finite() && segment.no_speech_prob.is_finite() {
+                logprob_sum += segment.avg_logprob * duration;
+                no_speech_sum += segment.no_speech_prob * duration;
+                duration_sum += duration;
+            }
+            segments.push(TextSegment {
+                start: offset + segment.start,
+                end: offset + segment.end,
+                text: segment.text.trim().to_string(),
+            });
+        }
+        if language.is_none() {
+            language = transcription.language.as_deref().and_then(language_code);
+        }
+        texts.push(transcription.text.trim().to_string());
+    }
+
+    let text = texts.join(" ");
+    info!(
+        "device: {}, {} whisper api transcription successful. length: {} characters",
+        device,
+        provider,
+        text.len()
+    );
+
+    Ok(Transcript {
+        text,
+        confidence: (duration_sum > 0.0).then(|| SegmentConfidence {
+            avg_logprob: logprob_sum / duration_sum,
+            no_speech_prob: no_speech_sum / duration_sum,
+        }),
+        language: language.map(|code| DetectedLanguage {
+            code,
+            probability: 1.0,
+        }),
+        engine: None,
+        segments,
+        model_version: None,
+    })
+}
+
+/// ISO 639-1 code of the language name the APIs answer with
+fn language_code(name: &str) -> Option<String> {
+    Language::from_str(name, true)
+        .ok()
+        .map(|language| language.as_lang_code().to_string())
+        .or_else(|| (name.len() == 2).then(|| name.to_lowercase()))
+}
+
+/// `audio_data` as a mono 16 bit pcm wav file, removed once dropped
+fn temp_wav(audio_data: &[f32], sample_rate: u32) -> Result<NamedTempFile> {
+    let file = tempfile::Builder::new().suffix(".wav").tempfile()?;
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = WavWriter::create(file.path(), spec)?;
+    for &sample in audio_data {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    Ok(file)
+}
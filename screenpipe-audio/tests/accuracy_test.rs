@@ -4,8 +4,8 @@ use screenpipe_audio::pyannote::embedding::EmbeddingExtractor;
 use screenpipe_audio::pyannote::identify::EmbeddingManager;
 use screenpipe_audio::stt::{prepare_segments, stt};
 use screenpipe_audio::vad_engine::{SileroVad, VadEngine};
-use screenpipe_audio::whisper::WhisperModel;
-use screenpipe_audio::{resample, AudioInput, AudioTranscriptionEngine};
+use screenpipe_audio::whisper::{ModelOptions, WhisperModel};
+use screenpipe_audio::{resample, AudioInput, AudioTranscriptionEngine, TranscriptionOptions};
 use screenpipe_core::Language;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -49,7 +49,11 @@ async fn test_transcription_accuracy() {
     ];
 
     let whisper_model = Arc::new(Mutex::new(
-        WhisperModel::new(&AudioTranscriptionEngine::WhisperLargeV3Turbo).unwrap(),
+        WhisperModel::new(
+            &AudioTranscriptionEngine::WhisperLargeV3Turbo,
+            &ModelOptions::default(),
+        )
+        .unwrap(),
     ));
     let vad_engine: Arc<Mutex<Box<dyn VadEngine + Send>>> =
         Arc::new(Mutex::new(Box::new(SileroVad::new().await.unwrap())));
@@ -89,6 +93,7 @@ async fn test_transcription_accuracy() {
                 sample_rate: 44100, // hardcoded based on test data sample rate
                 channels: 1,
                 device: Arc::new(screenpipe_audio::default_input_device().unwrap()),
+                capture_timing: None,
             };
 
             let audio_data = if audio_input.sample_rate != whisper::SAMPLE_RATE as u32 {
@@ -128,6 +133,7 @@ async fn test_transcription_accuracy() {
                     Arc::new(AudioTranscriptionEngine::WhisperLargeV3Turbo),
                     None,
                     vec![Language::English],
+                    &TranscriptionOptions::default(),
                 )
                 .await
                 .unwrap();
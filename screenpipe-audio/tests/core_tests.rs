@@ -7,10 +7,10 @@ mod tests {
     use screenpipe_audio::pyannote::identify::EmbeddingManager;
     use screenpipe_audio::stt::{prepare_segments, stt};
     use screenpipe_audio::vad_engine::{SileroVad, VadEngine, VadEngineEnum, VadSensitivity};
-    use screenpipe_audio::whisper::WhisperModel;
+    use screenpipe_audio::whisper::{ModelOptions, WhisperModel};
     use screenpipe_audio::{
         default_output_device, list_audio_devices, pcm_decode, AudioInput, AudioStream,
-        AudioTranscriptionEngine,
+        AudioTranscriptionEngine, DeviceTranscriptionOptions, TranscriptionOptions,
     };
     use screenpipe_audio::{parse_audio_device, record_and_transcribe};
     use screenpipe_core::Language;
@@ -46,6 +46,1196 @@ mod tests {
         assert_eq!(spec.to_string(), "Test Device (input)");
     }
 
+    #[test]
+    fn test_capture_timing_maps_samples_to_wall_clock() {
+        use screenpipe_audio::CaptureTiming;
+        use std::time::{Duration, SystemTime};
+
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut timing = CaptureTiming::new(start, 100);
+        timing.push_block(0, Duration::from_millis(500));
+        timing.push_block(100, Duration::from_millis(1_510));
+
+        assert_eq!(
+            timing.started_at(),
+            Some(start + Duration::from_millis(500))
+        );
+        assert_eq!(
+            timing.time_at(1.5),
+            Some(start + Duration::from_millis(2_010))
+        );
+
+        // dropping the first 150 samples keeps the remaining samples at the same time
+        timing.trim_front(150);
+        assert_eq!(
+            timing.started_at(),
+            Some(start + Duration::from_millis(2_010))
+        );
+    }
+
+    #[test]
+    fn test_parse_audio_device_with_host() {
+        let spec = parse_audio_device("Focusrite USB (input) [ASIO]").unwrap();
+        assert_eq!(spec.name, "Focusrite USB");
+        assert_eq!(spec.host.as_deref(), Some("ASIO"));
+        assert_eq!(spec.to_string(), "Focusrite USB (input) [ASIO]");
+
+        let spec = parse_audio_device("Display 1 (output)").unwrap();
+        assert_eq!(spec.host, None);
+    }
+
+    #[test]
+    fn test_segment_confidence_score() {
+        use screenpipe_audio::SegmentConfidence;
+
+        let confident = SegmentConfidence {
+            avg_logprob: -0.1,
+            no_speech_prob: 0.02,
+        };
+        let hallucination = SegmentConfidence {
+            avg_logprob: -1.2,
+            no_speech_prob: 0.8,
+        };
+        assert!(confident.score() > 0.85);
+        assert!(hallucination.score() < 0.1);
+        assert!(hallucination.score() >= 0.0);
+    }
+
+    #[test]
+    fn test_model_download_checksum_and_remove() {
+        use screenpipe_audio::models::{
+            get_or_download_in, list_models_in, remove_model_in, ModelFile,
+        };
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let body = b"not really a model";
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/model.bin", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = [0u8; 1024];
+                let n = stream.read(&mut request).unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..n]).to_lowercase();
+                // resuming the whole file, there is nothing left to send
+                let header = if request.contains(&format!("range: bytes={}-", body.len())) {
+                    format!(
+                        "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\n\r\n",
+                        body.len()
+                    )
+                } else {
+                    format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len())
+                };
+                stream.write_all(header.as_bytes()).unwrap();
+                if header.starts_with("HTTP/1.1 200") {
+                    stream.write_all(body).unwrap();
+                }
+            }
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let name = "model.bin";
+        let wrong = ModelFile::new(name, &url).with_sha256("00".repeat(32));
+        assert!(get_or_download_in(dir.path(), &wrong, None).is_err());
+        assert!(!dir.path().join("model.bin.part").exists());
+
+        let sha256 = format!("{:x}", <sha2::Sha256 as sha2::Digest>::digest(body));
+        let file = ModelFile::new(name, &url).with_sha256(&sha256);
+        let path = get_or_download_in(dir.path(), &file, None).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+
+        let listed = list_models_in(dir.path()).unwrap();
+        assert!(listed
+            .iter()
+            .any(|m| m.name == name && m.size == body.len() as u64));
+        remove_model_in(dir.path(), name).unwrap();
+        assert!(!path.exists());
+
+        // a part file that was already complete is verified and kept
+        std::fs::write(dir.path().join("model.bin.part"), body).unwrap();
+        let path = get_or_download_in(dir.path(), &file, None).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        assert!(!dir.path().join("model.bin.part").exists());
+    }
+
+    #[test]
+    fn test_local_model_path_validation() {
+        use screenpipe_audio::whisper::ModelSource;
+
+        let dir = std::env::temp_dir().join(format!("screenpipe-model-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let options = |path: PathBuf| ModelOptions {
+            source: ModelSource::Path(path),
+            ..Default::default()
+        };
+        let engine = AudioTranscriptionEngine::WhisperLargeV3Turbo;
+
+        let missing = WhisperModel::new(&engine, &options(dir.join("missing")));
+        assert!(missing
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("does not exist"));
+
+        std::fs::write(dir.join("config.json"), "{}").unwrap();
+        let no_tokenizer = WhisperModel::new(&engine, &options(dir.clone()));
+        assert!(no_tokenizer
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("tokenizer.json not found"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_speaker_ids_shared_across_chunks() {
+        let manager = EmbeddingManager::new(usize::MAX);
+        let alice = vec![1.0, 0.1, 0.0];
+        let bob = vec![0.0, 0.1, 1.0];
+
+        // each chunk gets its own clone of the manager, like in the whisper channel
+        let mut first_chunk = manager.clone();
+        assert_eq!(first_chunk.search_speaker(alice.clone(), 0.5), Some(1));
+        assert_eq!(first_chunk.search_speaker(bob.clone(), 0.5), Some(2));
+
+        let mut second_chunk = manager.clone();
+        assert_eq!(
+            second_chunk.search_speaker(vec![0.1, 0.1, 1.0], 0.5),
+            Some(2)
+        );
+        assert_eq!(
+            second_chunk.search_speaker(vec![0.9, 0.2, 0.0], 0.5),
+            Some(1)
+        );
+        assert_eq!(manager.get_all_speakers().len(), 2);
+    }
+
+    #[test]
+    fn test_language_hint_from_languages() {
+        use screenpipe_audio::LanguageHint;
+
+        assert_eq!(LanguageHint::from_languages(&[]), LanguageHint::Auto);
+        assert_eq!(
+            LanguageHint::from_languages(&[Language::French]),
+            LanguageHint::Force(Language::French)
+        );
+        let bilingual = LanguageHint::from_languages(&[Language::English, Language::Spanish]);
+        assert_eq!(
+            bilingual,
+            LanguageHint::Restrict(vec![Language::English, Language::Spanish])
+        );
+        assert_eq!(bilingual.candidates().len(), 2);
+        assert!(LanguageHint::Auto.candidates().is_empty());
+    }
+
+    #[test]
+    fn test_transcription_options_for_device() {
+        use screenpipe_audio::whisper::Task;
+        use screenpipe_audio::{AudioDevice, DeviceType};
+
+        let meeting = AudioDevice::new("meeting".to_string(), DeviceType::Output);
+        let mut options = DeviceTranscriptionOptions::default();
+        options.devices.insert(
+            meeting.clone(),
+            TranscriptionOptions {
+                task: Task::Translate,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(options.for_device(&meeting).task, Task::Translate);
+        let mic = AudioDevice::new("mic".to_string(), DeviceType::Input);
+        assert_eq!(options.for_device(&mic).task, Task::Transcribe);
+    }
+
+    #[test]
+    fn test_transcription_prompt_keywords() {
+        let options = TranscriptionOptions {
+            prompt: Some("Kubernetes, Grafana\nscreenpipe,".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.keywords(),
+            vec!["Kubernetes", "Grafana", "screenpipe"]
+        );
+        assert!(TranscriptionOptions::default().keywords().is_empty());
+    }
+
+    #[test]
+    fn test_detect_hallucination() {
+        use screenpipe_audio::hallucination::detect_hallucination;
+        use screenpipe_audio::Hallucination;
+
+        assert_eq!(
+            detect_hallucination("So I think we should. Ship it. Ship it. Ship it. Ship it today"),
+            (
+                "So I think we should. Ship it. today".to_string(),
+                Some(Hallucination::Repetition)
+            )
+        );
+        assert_eq!(
+            detect_hallucination("Thank you. Thank you. Thank you. Thank you."),
+            ("Thank you.".to_string(), Some(Hallucination::KnownPhrase))
+        );
+        assert_eq!(
+            detect_hallucination(" Thanks for watching! Please subscribe."),
+            (
+                " Thanks for watching! Please subscribe.".to_string(),
+                Some(Hallucination::KnownPhrase)
+            )
+        );
+
+        // real speech, including short repeats and a thank you, is left alone
+        for text in [
+            "No, no, no, that's not what I meant",
+            "Thank you for the review, I'll fix the tests",
+        ] {
+            assert_eq!(detect_hallucination(text), (text.to_string(), None));
+        }
+    }
+
+    #[test]
+    fn test_vad_decisions() {
+        use screenpipe_audio::hallucination::VadDecisions;
+
+        // 0.3s of silence, speech starting, 0.3s of speech, 0.4s of silence
+        let vad = VadDecisions {
+            frame: Duration::from_millis(100),
+            frames: vec![
+                Some(false),
+                Some(false),
+                Some(false),
+                None,
+                Some(true),
+                Some(true),
+                Some(true),
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(false),
+            ],
+        };
+        assert!((vad.speech_ratio() - 3.0 / 11.0).abs() < 1e-6);
+        assert!(vad.is_silent(0.0, 0.3));
+        assert!(vad.is_silent(0.75, 1.1));
+        // text reaching into speech, or where the vad was unsure, isn't over silence
+        assert!(!vad.is_silent(0.2, 0.45));
+        assert!(!vad.is_silent(0.0, 0.35));
+        assert!(!vad.is_silent(0.65, 1.1));
+        assert!(!vad.is_silent(2.0, 3.0));
+    }
+
+    #[test]
+    fn test_transcription_engine_chain() {
+        use AudioTranscriptionEngine::*;
+
+        let options = TranscriptionOptions {
+            fallback_engines: vec![AssemblyAI, WhisperTiny, GroqWhisper],
+            ..Default::default()
+        };
+        // cloud engines are tried up to the first local one
+        assert_eq!(
+            options.cloud_chain(&Deepgram).cloned().collect::<Vec<_>>(),
+            vec![Deepgram, AssemblyAI]
+        );
+        assert_eq!(options.local_engine(&Deepgram), WhisperTiny);
+        // a local primary engine transcribes by itself
+        assert_eq!(options.cloud_chain(&WhisperCpp).count(), 0);
+        assert_eq!(options.local_engine(&WhisperCpp), WhisperCpp);
+        // without a local engine in the chain the default one is loaded
+        assert_eq!(
+            TranscriptionOptions::default().local_engine(&Deepgram),
+            WhisperLargeV3Turbo
+        );
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy = screenpipe_audio::RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(10), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_disk_queue() {
+        use screenpipe_audio::disk_queue::DiskQueue;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut queue: DiskQueue<String> = DiskQueue::open(dir.path(), 16).unwrap();
+        assert!(queue.push(&[0.5, -0.25], &"first".to_string()).unwrap());
+        assert!(queue.push(&[1.0], &"second".to_string()).unwrap());
+        // 12 of 16 bytes are queued
+        assert!(!queue.push(&[0.0, 0.0], &"third".to_string()).unwrap());
+
+        // survives a restart
+        let mut queue: DiskQueue<String> = DiskQueue::open(dir.path(), 16).unwrap();
+        assert_eq!(queue.len(), 2);
+        let front = queue.front().unwrap().unwrap();
+        assert_eq!(front.samples, vec![0.5, -0.25]);
+        assert_eq!(front.meta, "first");
+        queue.remove(front.id).unwrap();
+        assert_eq!(queue.front().unwrap().unwrap().meta, "second");
+    }
+
+    #[test]
+    #[ignore] // downloads the alignment model
+    fn test_forced_alignment() {
+        use screenpipe_audio::alignment::Aligner;
+
+        let (audio, sample_rate) = pcm_decode("test_data/accuracy3.wav").unwrap();
+        let audio = screenpipe_audio::resample(&audio, sample_rate, 16000).unwrap();
+        let transcript = "again, screenpipe allows you to get meeting summaries, locally";
+
+        let words = Aligner::new().unwrap().align(&audio, transcript).unwrap();
+        assert_eq!(words.len(), transcript.split_whitespace().count());
+        assert_eq!(words[0].word, "again,");
+        // in order, without overlapping, within the audio
+        for pair in words.windows(2) {
+            assert!(pair[0].start < pair[0].end);
+            assert!(pair[0].end <= pair[1].start);
+        }
+        assert!(words.last().unwrap().end <= audio.len() as f64 / 16000.0);
+    }
+
+    #[test]
+    #[ignore] // downloads the audio tagging model
+    fn test_audio_tagging() {
+        use screenpipe_audio::audio_tagging::{is_music_only, AudioEvent, AudioTagger};
+
+        let tagger = AudioTagger::new().unwrap();
+        let (audio, sample_rate) = pcm_decode("test_data/accuracy3.wav").unwrap();
+        let audio = screenpipe_audio::resample(&audio, sample_rate, 16000).unwrap();
+
+        let labels = tagger.tag(&audio).unwrap();
+        assert_eq!(labels[0].event, AudioEvent::Speech);
+        assert!(!is_music_only(&labels));
+
+        let labels = tagger.tag(&vec![0.0; 16000 * 2]).unwrap();
+        assert!(labels
+            .iter()
+            .any(|label| label.event == AudioEvent::Silence));
+        assert!(!labels.iter().any(|label| label.event == AudioEvent::Speech));
+    }
+
+    #[test]
+    #[ignore] // needs ffmpeg
+    fn test_encode_codecs() {
+        use screenpipe_audio::{encode_single_audio, AudioCodec};
+
+        let (audio, sample_rate) = pcm_decode("test_data/accuracy3.wav").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let encode = |codec: AudioCodec| {
+            let path = dir.path().join(format!("out.{}", codec.extension()));
+            encode_single_audio(
+                bytemuck::cast_slice(&audio),
+                sample_rate,
+                1,
+                &path,
+                codec,
+                None,
+            )
+            .unwrap();
+            std::fs::read(path).unwrap()
+        };
+
+        let opus = encode(AudioCodec::Opus { bitrate_kbps: 24 });
+        assert_eq!(&opus[..4], b"OggS");
+        assert!(opus.len() < encode(AudioCodec::Aac).len());
+        assert_eq!(&encode(AudioCodec::Flac)[..4], b"fLaC");
+        assert_eq!(&encode(AudioCodec::M4a)[4..11], b"ftypM4A");
+    }
+
+    #[test]
+    #[ignore] // needs ffmpeg
+    fn test_encode_audio_to_bytes() {
+        use screenpipe_audio::{encode_audio_to_bytes, AudioCodec};
+
+        let (audio, sample_rate) = pcm_decode("test_data/accuracy3.wav").unwrap();
+        let encode = |codec| {
+            encode_audio_to_bytes(bytemuck::cast_slice(&audio), sample_rate, 1, codec, None)
+                .unwrap()
+        };
+
+        let opus = encode(AudioCodec::Opus { bitrate_kbps: 24 });
+        assert_eq!(opus.content_type, "audio/ogg");
+        assert_eq!(&opus.bytes[..4], b"OggS");
+        let flac = encode(AudioCodec::Flac);
+        assert_eq!(flac.content_type, "audio/flac");
+        assert_eq!(&flac.bytes[..4], b"fLaC");
+        // fragmented, nothing to seek back to in memory
+        let aac = encode(AudioCodec::Aac);
+        assert_eq!(aac.content_type, "audio/mp4");
+        assert_eq!(&aac.bytes[4..8], b"ftyp");
+    }
+
+    #[test]
+    #[ignore] // needs ffmpeg
+    fn test_encode_tags() {
+        use chrono::TimeZone;
+        use screenpipe_audio::{encode_single_audio, AudioCodec, AudioTags};
+
+        let (audio, sample_rate) = pcm_decode("test_data/accuracy3.wav").unwrap();
+        let tags = AudioTags {
+            device: "MacBook Pro Microphone (input)".to_string(),
+            captured_at: Some(Utc.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap()),
+            engine: Some("WhisperLargeV3Turbo".to_string()),
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let contains = |haystack: &[u8], needle: &str| {
+            haystack
+                .windows(needle.len())
+                .any(|window| window == needle.as_bytes())
+        };
+        for codec in [
+            AudioCodec::Opus { bitrate_kbps: 24 },
+            AudioCodec::Flac,
+            AudioCodec::M4a,
+        ] {
+            let path = dir.path().join(format!("tagged.{}", codec.extension()));
+            encode_single_audio(
+                bytemuck::cast_slice(&audio),
+                sample_rate,
+                1,
+                &path,
+                codec,
+                Some(&tags),
+            )
+            .unwrap();
+            let file = std::fs::read(&path).unwrap();
+            assert!(contains(&file, &tags.device), "{:?}", codec);
+            assert!(contains(&file, "2024-05-01T09:30:00.000Z"), "{:?}", codec);
+            assert!(contains(&file, "WhisperLargeV3Turbo"), "{:?}", codec);
+        }
+    }
+
+    #[test]
+    #[ignore] // needs ffmpeg
+    fn test_pcm_decode_containers() {
+        use screenpipe_audio::{encode_single_audio, AudioCodec};
+
+        let (audio, sample_rate) = pcm_decode("test_data/accuracy3.wav").unwrap();
+        let duration = audio.len() as f64 / sample_rate as f64;
+        let dir = tempfile::tempdir().unwrap();
+        // opus has no symphonia decoder and goes through ffmpeg, the rest are decoded in process
+        for codec in [
+            AudioCodec::Opus { bitrate_kbps: 24 },
+            AudioCodec::Flac,
+            AudioCodec::M4a,
+        ] {
+            let path = dir.path().join(format!("in.{}", codec.extension()));
+            encode_single_audio(
+                bytemuck::cast_slice(&audio),
+                sample_rate,
+                1,
+                &path,
+                codec,
+                None,
+            )
+            .unwrap();
+            let (decoded, decoded_rate) = pcm_decode(&path).unwrap();
+            let decoded_duration = decoded.len() as f64 / decoded_rate as f64;
+            assert!(
+                (decoded_duration - duration).abs() < 0.5,
+                "{:?}: {}s decoded of {}s",
+                codec,
+                decoded_duration,
+                duration
+            );
+        }
+
+        let not_audio = dir.path().join("notes.webm");
+        std::fs::write(&not_audio, "not a recording").unwrap();
+        assert!(pcm_decode(&not_audio).is_err());
+    }
+
+    #[test]
+    fn test_normalize_loudness() {
+        use screenpipe_audio::audio_processing::{
+            integrated_loudness, normalize_loudness, LoudnessNormalization,
+        };
+
+        let sine = |amplitude: f32| -> Vec<f32> {
+            (0..16000 * 5)
+                .map(|i| {
+                    amplitude * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 16000.0).sin()
+                })
+                .collect()
+        };
+        let config = LoudnessNormalization::default();
+
+        // a quiet mic is brought up to the target
+        let quiet = sine(0.01);
+        let normalized = normalize_loudness(&quiet, 16000, &config);
+        let loudness = integrated_loudness(&normalized, 16000).unwrap();
+        assert!((loudness - config.target_lufs).abs() < 0.5, "{}", loudness);
+
+        // a target above what the ceiling allows is limited, not clipped
+        let loud = LoudnessNormalization {
+            target_lufs: -3.0,
+            ..config
+        };
+        let normalized = normalize_loudness(&sine(0.5), 16000, &loud);
+        let ceiling = 10f32.powf(loud.true_peak_dbtp as f32 / 20.0);
+        assert!(normalized.iter().all(|x| x.abs() <= ceiling + 0.01));
+
+        let silence = vec![0.0; 16000];
+        assert_eq!(normalize_loudness(&silence, 16000, &config), silence);
+    }
+
+    #[test]
+    fn test_audio_pipeline() {
+        use screenpipe_audio::audio_processing::{AudioPipeline, AudioStage};
+
+        let tone = |hz: f32, amplitude: f32, i: usize| {
+            amplitude * (2.0 * std::f32::consts::PI * hz * i as f32 / 16000.0).sin()
+        };
+        let rms =
+            |audio: &[f32]| (audio.iter().map(|x| x * x).sum::<f32>() / audio.len() as f32).sqrt();
+        // speech band tone over a dc offset and 20hz rumble
+        let audio: Vec<f32> = (0..16000 * 2)
+            .map(|i| 0.2 + tone(20.0, 0.3, i) + tone(1000.0, 0.1, i))
+            .collect();
+
+        let pipeline = AudioPipeline::new().dc_removal().high_pass(80.0);
+        assert_eq!(
+            pipeline.stages(),
+            &[
+                AudioStage::DcRemoval,
+                AudioStage::HighPass { cutoff_hz: 80.0 }
+            ]
+        );
+        let filtered = pipeline.process(audio.clone(), 16000);
+        // past the filter settling, only the tone is left
+        let settled = &filtered[1600..];
+        let mean = settled.iter().sum::<f32>() / settled.len() as f32;
+        assert!(mean.abs() < 0.01, "{}", mean);
+        let expected = 0.1 / 2f32.sqrt();
+        assert!(
+            (rms(settled) - expected).abs() < 0.1 * expected,
+            "{}",
+            rms(settled)
+        );
+
+        assert_eq!(AudioPipeline::new().process(audio.clone(), 16000), audio);
+    }
+
+    #[test]
+    fn test_noise_gate() {
+        use screenpipe_audio::noise_gate::{NoiseGate, NoiseGates};
+
+        let rms =
+            |audio: &[f32]| (audio.iter().map(|x| x * x).sum::<f32>() / audio.len() as f32).sqrt();
+        // a second of fan noise, then a tone over it
+        let mut state = 7u32;
+        let audio: Vec<f32> = (0..16000 * 3)
+            .map(|i| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                let noise = ((state >> 16) as f32 / 65536.0 * 2.0 - 1.0) * 0.05;
+                let tone = if i >= 16000 {
+                    0.3 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16000.0).sin()
+                } else {
+                    0.0
+                };
+                tone + noise
+            })
+            .collect();
+        let tone: Vec<f32> = (16000..16000 * 3)
+            .map(|i| 0.3 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16000.0).sin())
+            .collect();
+        let residual = |audio: &[f32]| {
+            let difference: Vec<f32> = audio[16000..]
+                .iter()
+                .zip(&tone)
+                .map(|(x, t)| x - t)
+                .collect();
+            rms(&difference)
+        };
+
+        let mut gates = NoiseGates::new(NoiseGate::default());
+        let gated = gates.process("mic (input)", &audio);
+        assert_eq!(gated.len(), audio.len());
+        // the noise is down about 6db, the tone is kept
+        assert!(rms(&gated[..16000]) < 0.6 * rms(&audio[..16000]));
+        assert!(residual(&gated) < 0.7 * residual(&audio));
+        assert!((rms(&gated[16000..]) - rms(&tone)).abs() < 0.05 * rms(&tone));
+        // too short to have a frame
+        assert_eq!(gates.process("mic (input)", &audio[..100]), &audio[..100]);
+    }
+
+    #[test]
+    fn test_resample_fast() {
+        use screenpipe_audio::{resample_with, ResampleQuality};
+
+        let sine = |rate: f32, len: usize| -> Vec<f32> {
+            (0..len)
+                .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / rate).sin())
+                .collect()
+        };
+        let resampled =
+            resample_with(&sine(48000.0, 48000), 48000, 16000, ResampleQuality::Fast).unwrap();
+        assert_eq!(resampled.len(), 16000);
+        let error = resampled
+            .iter()
+            .zip(sine(16000.0, 16000))
+            .fold(0.0f32, |max, (a, b)| max.max((a - b).abs()));
+        assert!(error < 0.01, "{}", error);
+
+        let same = sine(16000.0, 100);
+        assert_eq!(
+            resample_with(&same, 16000, 16000, ResampleQuality::Fast).unwrap(),
+            same
+        );
+    }
+
+    #[test]
+    fn test_duplicate_detection() {
+        use screenpipe_audio::fingerprint::{Candidate, DuplicateDetection, DuplicateDetector};
+        use std::time::SystemTime;
+
+        // tones changing loudness every 50ms with a pause every 1.2s, a stand-in for speech
+        let speech = |seed: u64| -> Vec<f32> {
+            let mut state = seed;
+            let mut amplitudes = Vec::new();
+            (0..16000 * 4)
+                .map(|i| {
+                    if i % 800 == 0 {
+                        state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                        amplitudes = (0..4)
+                            .map(|k| ((state >> (16 * k)) & 0xff) as f32 / 2550.0)
+                            .collect();
+                    }
+                    if (i / 4800) % 4 == 3 {
+                        return 0.0;
+                    }
+                    [400.0, 900.0, 1500.0, 2400.0]
+                        .iter()
+                        .zip(&amplitudes)
+                        .map(|(hz, a)| {
+                            a * (2.0 * std::f32::consts::PI * hz * i as f32 / 16000.0).sin()
+                        })
+                        .sum()
+                })
+                .collect()
+        };
+        let loopback = speech(1);
+        // the mic hears it quieter, over a noise floor, 100ms later
+        let mut noise = 7u32;
+        let mic: Vec<f32> = std::iter::repeat(0.0)
+            .take(1600)
+            .chain(loopback.iter().map(|x| x * 0.5))
+            .map(|x| {
+                noise = noise.wrapping_mul(1664525).wrapping_add(1013904223);
+                x + (noise >> 16) as f32 / 65536.0 * 0.02 - 0.01
+            })
+            .collect();
+        let other = speech(2);
+
+        let at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let candidate = |device, samples| Candidate {
+            device,
+            samples,
+            captured_at: at,
+        };
+        let mut detector = DuplicateDetector::new(DuplicateDetection::default());
+        assert_eq!(
+            detector.duplicates(&[
+                candidate("mic (input)", &mic),
+                candidate("speakers (output)", &loopback),
+            ]),
+            vec![0]
+        );
+        // the same audio again later is a duplicate of the loopback segment heard before
+        assert_eq!(
+            detector.duplicates(&[candidate("mic (input)", &mic)]),
+            vec![0]
+        );
+        assert!(detector
+            .duplicates(&[candidate("mic (input)", &other)])
+            .is_empty());
+
+        // the mic hears the first 1.5s of the call, then the user talks over it: most of the
+        // segment is theirs and is transcribed too
+        let call = &loopback[..24000];
+        let talking_over: Vec<f32> = mic[..1600 + 24000]
+            .iter()
+            .copied()
+            .chain(other[1600 + 24000..].iter().map(|x| {
+                noise = noise.wrapping_mul(1664525).wrapping_add(1013904223);
+                x + (noise >> 16) as f32 / 65536.0 * 0.02 - 0.01
+            }))
+            .collect();
+        let mut detector = DuplicateDetector::new(DuplicateDetection::default());
+        assert!(detector
+            .duplicates(&[
+                candidate("mic (input)", &talking_over),
+                candidate("speakers (output)", call),
+            ])
+            .is_empty());
+        // but a segment heard whole is still dropped
+        assert_eq!(
+            detector.duplicates(&[candidate("mic (input)", &mic[..1600 + 24000])]),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn test_chunk_stitching() {
+        use screenpipe_audio::stitching::{merge_transcripts, ChunkStitcher};
+        use std::time::SystemTime;
+
+        let overlap = Duration::from_secs(2);
+        // the word cut at the end of the previous chunk comes from the next one, which heard it whole
+        assert_eq!(
+            merge_transcripts(
+                "we should ship the release on Friday, aft",
+                "on friday after the review is done",
+                overlap
+            ),
+            Some((
+                "we should ship the release".to_string(),
+                "on friday after the review is done".to_string()
+            ))
+        );
+        // a single word in common is a coincidence
+        assert_eq!(
+            merge_transcripts("i think the", "the plan works", overlap),
+            None
+        );
+        // words far from the seam aren't the overlap
+        assert_eq!(
+            merge_transcripts(
+                "the release went out on monday and we spent the week fixing the bugs people found in it",
+                "the release notes were late",
+                Duration::from_secs(1)
+            ),
+            None
+        );
+
+        // the next chunk repeats the last 0.5s of the previous one, normalized louder
+        let tone: Vec<f32> = (0..16000)
+            .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 16000.0).sin() * 0.1)
+            .collect();
+        let at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut stitcher = ChunkStitcher::new(overlap);
+        let mut previous = tone.clone();
+        stitcher.stitch("mic (input)", &mut previous, 16000, Some(at));
+        let mut next: Vec<f32> = tone[8000..]
+            .iter()
+            .chain(&tone[..8000])
+            .map(|x| x * 2.0)
+            .collect();
+        stitcher.stitch(
+            "mic (input)",
+            &mut next,
+            16000,
+            Some(at + Duration::from_millis(500)),
+        );
+        // starts as the previous chunk ended, ends the overlap as it was processed
+        assert!((next[0] - tone[8000]).abs() < 1e-3);
+        assert!((next[7999] - tone[15999] * 2.0).abs() < 1e-3);
+        assert_eq!(next[8000], tone[0] * 2.0);
+
+        // a chunk captured before the previous one isn't stitched to it
+        let mut backfill = tone.clone();
+        stitcher.stitch("mic (input)", &mut backfill, 16000, Some(at));
+        assert_eq!(backfill, tone);
+    }
+
+    #[test]
+    fn test_pcm_stream() {
+        use screenpipe_audio::pcm_stream;
+
+        let (audio, sample_rate) = pcm_decode("test_data/accuracy1.wav").unwrap();
+        let stream = pcm_stream("test_data/accuracy1.wav", Duration::from_secs(1)).unwrap();
+        assert_eq!(stream.sample_rate(), sample_rate);
+        let chunks: Vec<Vec<f32>> = stream.map(Result::unwrap).collect();
+
+        let (last, full) = chunks.split_last().unwrap();
+        assert!(full.iter().all(|chunk| chunk.len() == sample_rate as usize));
+        assert!(!last.is_empty() && last.len() <= sample_rate as usize);
+        assert_eq!(chunks.concat(), audio);
+    }
+
+    #[test]
+    #[ignore] // needs ffmpeg
+    fn test_audio_archive() {
+        use chrono::TimeZone;
+        use screenpipe_audio::archive::{
+            find_entries, read_entry, recompress, ArchiveConfig, AudioArchive,
+        };
+        use screenpipe_audio::AudioCodec;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = AudioArchive::new(ArchiveConfig {
+            dir: dir.path().to_path_buf(),
+            file_duration: Duration::from_secs(3600),
+            codec: AudioCodec::Flac,
+            recompress: None,
+        })
+        .unwrap();
+        let device = "test archive (input)";
+        let at = |secs| Utc.timestamp_opt(1_700_002_800 + secs, 0).unwrap();
+        let audio: Vec<f32> = (0..16000 * 10)
+            .map(|i| 0.3 * (2.0 * std::f32::consts::PI * 200.0 * i as f32 / 16000.0).sin())
+            .collect();
+
+        let first = archive
+            .append(device, &audio, 16000, at(0))
+            .unwrap()
+            .unwrap();
+        // overlapping the first by 2s, only its last 8s are archived
+        let second = archive
+            .append(device, &audio, 16000, at(8))
+            .unwrap()
+            .unwrap();
+        assert_eq!((second.start, second.end), (at(10), at(18)));
+        assert_eq!(second.file, first.file);
+        assert_eq!(second.offset, first.length);
+        // the next hour goes to another file
+        let third = archive
+            .append(device, &audio, 16000, at(3600))
+            .unwrap()
+            .unwrap();
+        assert_ne!(third.file, first.file);
+
+        let entries = find_entries(dir.path(), Some(device), at(12), at(3605)).unwrap();
+        assert_eq!(entries, vec![second.clone(), third.clone()]);
+        assert_eq!(&read_entry(&second).unwrap()[..4], b"OggS");
+
+        // only the first hour is old enough
+        let reclaimed =
+            recompress(dir.path(), at(3600), AudioCodec::Opus { bitrate_kbps: 12 }).unwrap();
+        assert!(reclaimed > 0);
+        assert!(!first.file.exists());
+        let entries = find_entries(dir.path(), Some(device), at(0), at(3605)).unwrap();
+        assert_eq!(entries.len(), 3);
+        for entry in &entries[..2] {
+            assert!(entry.recompressed);
+            assert_ne!(entry.file, first.file);
+            assert_eq!(&read_entry(entry).unwrap()[..4], b"OggS");
+        }
+        assert_eq!(
+            (entries[1].start, entries[1].end),
+            (second.start, second.end)
+        );
+        assert_eq!(entries[2], third);
+        // already recompressed
+        assert_eq!(
+            recompress(dir.path(), at(3600), AudioCodec::Opus { bitrate_kbps: 12 }).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_language_filter() {
+        use screenpipe_audio::{DetectedLanguage, LanguageFilter};
+
+        let filter = LanguageFilter::new(vec![Language::English, Language::German]);
+        let detected = |code: &str, probability| DetectedLanguage {
+            code: code.to_string(),
+            probability,
+        };
+        assert!(filter.allows(&detected("de", 0.9)));
+        assert!(!filter.allows(&detected("fr", 0.9)));
+        // unsure, transcribed anyway
+        assert!(filter.allows(&detected("fr", 0.3)));
+    }
+
+    #[test]
+    fn test_keyword_spotter() {
+        use chrono::TimeZone;
+        use screenpipe_audio::KeywordSpotter;
+        use std::sync::atomic::AtomicUsize;
+
+        let spotter = KeywordSpotter::new(["action item", "Zoë"], Duration::from_secs(30));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_clone = calls.clone();
+        spotter.on_match(move |_| {
+            calls_clone.fetch_add(1, Ordering::Relaxed);
+        });
+        let at = |secs| Utc.timestamp_opt(secs, 0).unwrap();
+
+        let matches = spotter.spot("OK, one more ACTION-item for zoë.", "mic", at(0), false);
+        assert_eq!(
+            matches
+                .iter()
+                .map(|m| m.phrase.as_str())
+                .collect::<Vec<_>>(),
+            vec!["action item", "Zoë"]
+        );
+        // the final result of the same speech, and the other device, within the cooldown
+        assert!(spotter
+            .spot("ok one more action item for zoë", "mic", at(2), true)
+            .is_empty());
+        assert_eq!(spotter.spot("action item", "speaker", at(2), true).len(), 1);
+        // whole words only
+        assert!(spotter
+            .spot("reaction items", "speaker", at(100), true)
+            .is_empty());
+        assert_eq!(spotter.spot("action item", "mic", at(60), true).len(), 1);
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn test_energy_vad() {
+        use screenpipe_audio::vad_engine::{EnergyVad, VadConfig};
+        use vad_rs::VadStatus;
+
+        let mut vad = EnergyVad::new();
+        vad.set_config(VadConfig::default());
+        let silence = vec![0.0; 1600];
+        let voice: Vec<f32> = (0..1600)
+            .map(|i| 0.3 * (2.0 * std::f32::consts::PI * 200.0 * i as f32 / 16000.0).sin())
+            .collect();
+        let hiss: Vec<f32> = (0..1600)
+            .map(|i| if i % 2 == 0 { 0.3 } else { -0.3 })
+            .collect();
+
+        assert_eq!(vad.audio_type(&silence).unwrap(), VadStatus::Silence);
+        assert_eq!(vad.audio_type(&hiss).unwrap(), VadStatus::Silence);
+        assert_eq!(vad.audio_type(&silence).unwrap(), VadStatus::Silence);
+        // speech once it lasted 300ms
+        assert_eq!(vad.audio_type(&voice).unwrap(), VadStatus::Unknown);
+        assert_eq!(vad.audio_type(&voice).unwrap(), VadStatus::Unknown);
+        assert_eq!(vad.audio_type(&voice).unwrap(), VadStatus::Speech);
+    }
+
+    #[test]
+    fn test_speech_stats() {
+        use chrono::TimeZone;
+        use screenpipe_audio::speech_stats::{
+            devices_with_speech_since, record_frame, speech_stats,
+        };
+
+        let device = "test speech stats (input)";
+        let frame = Duration::from_secs(1);
+        let at = |secs| Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap();
+        for secs in 0..10 {
+            record_frame(device, at(secs), frame, secs < 3);
+        }
+        // audio heard again, e.g. the overlap of the next chunk
+        for secs in 8..12 {
+            record_frame(device, at(secs), frame, true);
+        }
+
+        let stats = speech_stats(Some(device), None, None);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].speech_secs, 5.0);
+        assert_eq!(stats[0].silence_secs, 7.0);
+        assert!((stats[0].speech_ratio - 5.0 / 12.0).abs() < 1e-9);
+        // hours ending before the queried range are left out
+        assert!(speech_stats(Some(device), Some(at(7200)), None)[0]
+            .hours
+            .is_empty());
+        // the last speech ended at 12s
+        assert!(devices_with_speech_since(at(11)).contains(&device.to_string()));
+        assert!(!devices_with_speech_since(at(13)).contains(&device.to_string()));
+    }
+
+    #[test]
+    fn test_speech_gate() {
+        use screenpipe_audio::vad_engine::{SegmentPadding, VadConfig};
+        use screenpipe_audio::SpeechGate;
+        use vad_rs::VadStatus;
+
+        // loud frames are speech
+        struct LoudVad;
+        impl VadEngine for LoudVad {
+            fn is_voice_segment(&mut self, audio_chunk: &[f32]) -> anyhow::Result<bool> {
+                Ok(self.audio_type(audio_chunk)? == VadStatus::Speech)
+            }
+            fn set_sensitivity(&mut self, _sensitivity: VadSensitivity) {}
+            fn set_config(&mut self, _config: VadConfig) {}
+            fn audio_type(&mut self, audio_chunk: &[f32]) -> anyhow::Result<VadStatus> {
+                Ok(if audio_chunk.iter().any(|s| s.abs() > 0.1) {
+                    VadStatus::Speech
+                } else {
+                    VadStatus::Silence
+                })
+            }
+            fn get_min_speech_ratio(&self) -> f32 {
+                0.0
+            }
+        }
+
+        let mut gate = SpeechGate::new(
+            Box::new(LoudVad),
+            16000,
+            Duration::from_millis(100),
+            SegmentPadding {
+                pre_roll: Duration::from_millis(200),
+                hangover: Duration::from_millis(300),
+            },
+            Duration::from_secs(2),
+        );
+        // 1s of silence, 1s of speech, 1s of silence
+        let mut buffer = vec![0.0; 16000];
+        buffer.extend(vec![0.5; 16000]);
+        buffer.extend(vec![0.0; 16000]);
+        assert_eq!(gate.next_speech(&buffer), Some(12800..36800));
+        assert_eq!(gate.next_speech(&buffer), None);
+        // only the pre-roll of what may come next is kept
+        assert_eq!(gate.unneeded(), 48000 - 3200);
+
+        // speech longer than the max length is cut
+        let mut gate = SpeechGate::new(
+            Box::new(LoudVad),
+            16000,
+            Duration::from_millis(100),
+            SegmentPadding {
+                pre_roll: Duration::from_millis(200),
+                hangover: Duration::from_millis(200),
+            },
+            Duration::from_secs(2),
+        );
+        let buffer = vec![0.5; 16000 * 3];
+        assert_eq!(gate.next_speech(&buffer), Some(0..32000));
+        assert_eq!(gate.next_speech(&buffer), None);
+        assert_eq!(gate.finish(buffer.len()), Some(28800..48000));
+    }
+
+    #[test]
+    fn test_subtitles() {
+        use screenpipe_audio::subtitles::{format_cues, Cue, SubtitleFormat};
+
+        let cues = vec![
+            Cue {
+                start: Duration::from_millis(1500),
+                end: Duration::from_millis(3250),
+                text: "hello <world>".to_string(),
+                speaker: Some(1),
+            },
+            Cue {
+                start: Duration::from_secs(3661),
+                end: Duration::from_secs(3662),
+                text: "bye".to_string(),
+                speaker: None,
+            },
+        ];
+        assert_eq!(
+            format_cues(&cues, SubtitleFormat::Srt),
+            "1\n00:00:01,500 --> 00:00:03,250\nhello <world>\n\n2\n01:01:01,000 --> 01:01:02,000\nbye\n\n"
+        );
+        assert_eq!(
+            format_cues(&cues, SubtitleFormat::WebVtt),
+            "WEBVTT\n\n00:00:01.500 --> 00:00:03.250\n<v Speaker 1>hello &lt;world&gt;\n\n01:01:01.000 --> 01:01:02.000\nbye\n\n"
+        );
+    }
+
+    #[test]
+    fn test_priority_lanes() {
+        use screenpipe_audio::lanes::{Lane, LaneLimits, PriorityLanes};
+
+        let mut lanes = PriorityLanes::new(LaneLimits {
+            max_realtime_streak: 2,
+            max_backfill_wait: Duration::from_secs(3600),
+            ..Default::default()
+        });
+        lanes.push(Lane::Backfill, "old");
+        for live in ["a", "b", "c", "d"] {
+            lanes.push(Lane::Realtime, live);
+        }
+
+        assert_eq!(lanes.next_batch(1), vec!["a"]);
+        assert_eq!(lanes.next_batch(1), vec!["b"]);
+        // the backfill isn't starved past the streak
+        assert_eq!(lanes.next_batch(1), vec!["old"]);
+        assert_eq!(lanes.next_batch(8), vec!["c", "d"]);
+        assert!(lanes.is_empty());
+    }
+
+    #[test]
+    fn test_priority_lanes_capacity() {
+        use screenpipe_audio::lanes::{Lane, LaneLimits, PriorityLanes};
+
+        let mut lanes = PriorityLanes::new(LaneLimits {
+            max_queued: 3,
+            ..Default::default()
+        });
+        let (sender, receiver) = crossbeam::channel::bounded(10);
+        for i in 0..10 {
+            sender.send(i).unwrap();
+        }
+
+        // the whisper channel pulls only while there is room, the rest stays in the channel
+        while !lanes.is_full() {
+            let Ok(item) = receiver.try_recv() else {
+                break;
+            };
+            lanes.push(Lane::Backfill, item);
+        }
+        assert_eq!(lanes.len(), 3);
+        assert_eq!(receiver.len(), 7);
+        // the channel fills up behind them and holds back the senders
+        for i in 10..13 {
+            sender.send(i).unwrap();
+        }
+        assert!(sender.try_send(13).is_err());
+
+        assert_eq!(lanes.next_batch(2), vec![0, 1]);
+        assert!(!lanes.is_full());
+        lanes.push(Lane::Realtime, receiver.try_recv().unwrap());
+        lanes.push(Lane::Realtime, receiver.try_recv().unwrap());
+        assert!(lanes.is_full());
+    }
+
+    #[test]
+    fn test_audio_journal() {
+        use screenpipe_audio::journal::AudioJournal;
+        use screenpipe_audio::{AudioDevice, DeviceType};
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = AudioInput {
+            data: Arc::new(vec![0.0, 0.5, -0.5, 1.0]),
+            sample_rate: 48000,
+            channels: 1,
+            device: Arc::new(AudioDevice::new("mic".to_string(), DeviceType::Input)),
+            capture_timing: None,
+        };
+        let mut journal = AudioJournal::open(dir.path()).unwrap();
+        let first = journal.append(&input).unwrap();
+        let second = journal.append(&input).unwrap();
+        journal.remove(&[first]).unwrap();
+
+        // what wasn't transcribed is still there after a restart
+        let journal = AudioJournal::open(dir.path()).unwrap();
+        assert_eq!(journal.pending(), vec![second]);
+        let recovered = journal.read(second).unwrap();
+        assert_eq!(recovered.sample_rate, 48000);
+        assert_eq!(recovered.device, input.device);
+        for (recovered, original) in recovered.data.iter().zip(input.data.iter()) {
+            assert!((recovered - original).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_parse_device_preference() {
+        use screenpipe_audio::whisper::DevicePreference;
+
+        assert_eq!(
+            "auto".parse::<DevicePreference>().unwrap(),
+            DevicePreference::Auto
+        );
+        assert_eq!(
+            "CPU".parse::<DevicePreference>().unwrap(),
+            DevicePreference::Cpu
+        );
+        assert_eq!(
+            "cuda".parse::<DevicePreference>().unwrap(),
+            DevicePreference::Cuda(0)
+        );
+        assert_eq!(
+            "cuda:1".parse::<DevicePreference>().unwrap(),
+            DevicePreference::Cuda(1)
+        );
+        assert_eq!(DevicePreference::Cuda(1).to_string(), "cuda:1");
+        assert!("cuda:x".parse::<DevicePreference>().is_err());
+        assert!("vulkan".parse::<DevicePreference>().is_err());
+    }
+
     #[tokio::test]
     #[ignore] // Add this if you want to skip this test in regular test runs
     async fn test_record_and_transcribe() {
@@ -101,6 +1291,35 @@ mod tests {
         std::fs::remove_file(output_path).unwrap();
     }
 
+    #[tokio::test]
+    #[ignore] // needs an audio device
+    async fn test_live_audio_stream() {
+        use screenpipe_audio::{live_audio_stream, live_audio_streams};
+
+        let device = default_output_device().unwrap();
+        let is_running = Arc::new(AtomicBool::new(true));
+        let stream = AudioStream::from_device(Arc::new(device.clone()), is_running.clone())
+            .await
+            .unwrap()
+            .shared();
+
+        // another crate taps the device without opening it again
+        let tapped = live_audio_stream(&device).unwrap();
+        assert_eq!(tapped.sample_rate(), stream.sample_rate());
+        assert_eq!(live_audio_streams().len(), 1);
+        let mut receiver = tapped.subscribe();
+        drop(tapped);
+        let block = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!block.data.is_empty());
+
+        is_running.store(false, Ordering::Relaxed);
+        drop(stream);
+        assert!(live_audio_stream(&device).is_none());
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_record_and_transcribe_interrupt_before_end() {
@@ -217,6 +1436,8 @@ mod tests {
             VadSensitivity::High,
             vec![],
             None,
+            ModelOptions::default(),
+            DeviceTranscriptionOptions::default(),
         )
         .await
         .unwrap();
@@ -285,6 +1506,67 @@ mod tests {
         std::fs::remove_file(output_path_2).unwrap_or_default();
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn test_whisper_channel_unloads_idle_model() {
+        setup();
+        use screenpipe_audio::{create_whisper_channel, AudioDevice, DeviceType, FILE_AUDIO_HOST};
+
+        let engine = AudioTranscriptionEngine::WhisperTiny;
+        let model_options = ModelOptions {
+            unload_after_idle: Some(Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let (whisper_sender, whisper_receiver, shutdown_flag) = create_whisper_channel(
+            Arc::new(engine.clone()),
+            VadEngineEnum::Silero,
+            None,
+            dir.path(),
+            VadSensitivity::Medium,
+            vec![Language::English],
+            None,
+            model_options.clone(),
+            DeviceTranscriptionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        // the channel holds the only strong reference, its model is freed once it lets go
+        let loaded = Arc::downgrade(&WhisperModel::shared(&engine, &model_options).unwrap());
+        assert!(loaded.upgrade().is_some());
+        // the channel checks for idleness every 5s while no audio comes in
+        tokio::time::sleep(Duration::from_secs(7)).await;
+        assert!(loaded.upgrade().is_none(), "idle model still loaded");
+
+        // speech coming in loads it again
+        let (samples, sample_rate) = pcm_decode("test_data/accuracy1.wav").unwrap();
+        whisper_sender
+            .send(AudioInput {
+                data: Arc::new(samples[..sample_rate as usize * 10].to_vec()),
+                sample_rate,
+                channels: 1,
+                device: Arc::new(
+                    AudioDevice::new("test.wav".to_string(), DeviceType::Input)
+                        .with_host(Some(FILE_AUDIO_HOST.to_string())),
+                ),
+                capture_timing: None,
+            })
+            .unwrap();
+        let result = tokio::task::spawn_blocking(move || {
+            whisper_receiver.recv_timeout(Duration::from_secs(120))
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(result.error.is_none());
+        assert!(!result.transcription.unwrap_or_default().trim().is_empty());
+
+        let reloaded = WhisperModel::shared(&engine, &model_options).unwrap();
+        assert!(Arc::strong_count(&reloaded) > 1, "model not held again");
+        shutdown_flag.store(true, Ordering::Relaxed);
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_audio_transcription_language() {
@@ -293,7 +1575,11 @@ mod tests {
 
         // Setup
         let whisper_model = Arc::new(tokio::sync::Mutex::new(
-            WhisperModel::new(&AudioTranscriptionEngine::WhisperLargeV3Turbo).unwrap(),
+            WhisperModel::new(
+                &AudioTranscriptionEngine::WhisperLargeV3Turbo,
+                &ModelOptions::default(),
+            )
+            .unwrap(),
         ));
         let vad_engine: Arc<tokio::sync::Mutex<Box<dyn VadEngine + Send>>> = Arc::new(
             tokio::sync::Mutex::new(Box::new(SileroVad::new().await.unwrap())),
@@ -306,6 +1592,7 @@ mod tests {
             sample_rate: 44100, // hardcoded based on test data sample rate
             channels: 1,
             device: Arc::new(screenpipe_audio::default_input_device().unwrap()),
+            capture_timing: None,
         };
 
         // Create the missing parameters
@@ -352,6 +1639,7 @@ mod tests {
                 Arc::new(AudioTranscriptionEngine::WhisperLargeV3Turbo),
                 None,
                 vec![Language::Arabic],
+                &TranscriptionOptions::default(),
             )
             .await
             .unwrap();
@@ -391,6 +1679,7 @@ mod tests {
             sample_rate: 16000, // Adjust this based on your test audio
             channels: 1,
             device: Arc::new(default_output_device().unwrap()),
+            capture_timing: None,
         };
 
         let project_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -416,8 +1705,11 @@ mod tests {
         let embedding_manager = EmbeddingManager::new(usize::MAX);
 
         // Initialize the WhisperModel
-        let mut whisper_model = WhisperModel::new(&AudioTranscriptionEngine::WhisperLargeV3Turbo)
-            .expect("Failed to initialize WhisperModel");
+        let mut whisper_model = WhisperModel::new(
+            &AudioTranscriptionEngine::WhisperLargeV3Turbo,
+            &ModelOptions::default(),
+        )
+        .expect("Failed to initialize WhisperModel");
 
         // Initialize VAD engine
         let vad_engine: Box<dyn VadEngine + Send> = Box::new(SileroVad::new().await.unwrap());
@@ -447,6 +1739,7 @@ mod tests {
                 Arc::new(AudioTranscriptionEngine::WhisperLargeV3Turbo),
                 None,
                 vec![Language::English],
+                &TranscriptionOptions::default(),
             )
             .await
             .unwrap();
@@ -458,4 +1751,312 @@ mod tests {
 
         debug!("Transcription completed in {:?}", elapsed_time);
     }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_stream_transcription_partial_results() {
+        setup();
+        use screenpipe_audio::{
+            stream_transcription, AudioBlock, AudioDevice, DeviceType, StreamingConfig,
+        };
+
+        let (samples, sample_rate) = pcm_decode("test_data/accuracy1.wav").unwrap();
+        let (tx, rx) = tokio::sync::broadcast::channel(10_000);
+        // feed the file as 100ms blocks, like a live stream would
+        for block in samples.chunks(sample_rate as usize / 10) {
+            tx.send(AudioBlock::new(block.to_vec())).unwrap();
+        }
+        drop(tx);
+
+        let whisper_model = WhisperModel::shared(
+            &AudioTranscriptionEngine::WhisperTiny,
+            &ModelOptions::default(),
+        )
+        .unwrap();
+        // loaded once, the live transcription of another device gets the same model
+        assert!(Arc::ptr_eq(
+            &whisper_model,
+            &WhisperModel::shared(
+                &AudioTranscriptionEngine::WhisperTiny,
+                &ModelOptions::default()
+            )
+            .unwrap()
+        ));
+        let mut results = Vec::new();
+        stream_transcription(
+            rx,
+            Arc::new(AudioDevice::new("test".to_string(), DeviceType::Input)),
+            sample_rate,
+            whisper_model,
+            Arc::new(AudioTranscriptionEngine::WhisperTiny),
+            vec![Language::English],
+            TranscriptionOptions::default(),
+            Arc::new(AtomicBool::new(true)),
+            StreamingConfig::default(),
+            |result| results.push(result),
+        )
+        .await
+        .unwrap();
+
+        assert!(results.iter().any(|r| !r.is_final), "no partial results");
+        assert!(results.iter().any(|r| r.is_final), "no final results");
+        assert!(results
+            .iter()
+            .all(|r| r.error.is_none() && r.transcription.is_some()));
+    }
+
+    #[cfg(feature = "whisper-cpp")]
+    #[tokio::test]
+    #[ignore]
+    async fn test_whisper_cpp_transcription() {
+        setup();
+        use screenpipe_audio::{resample, transcribe};
+
+        let (samples, sample_rate) = pcm_decode("test_data/accuracy1.wav").unwrap();
+        let samples = resample(&samples, sample_rate, 16000).unwrap();
+        let mut whisper_model = WhisperModel::new(
+            &AudioTranscriptionEngine::WhisperCpp,
+            &ModelOptions::default(),
+        )
+        .unwrap();
+
+        let transcript = transcribe(
+            &samples[..16000 * 30],
+            16000,
+            "test",
+            &mut whisper_model,
+            Arc::new(AudioTranscriptionEngine::WhisperCpp),
+            None,
+            vec![Language::English],
+            &TranscriptionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!transcript.text.trim().is_empty());
+        assert!(transcript.confidence.is_some());
+    }
+
+    #[test]
+    #[ignore]
+    fn test_candle_segments_checked_against_vad() {
+        setup();
+        use screenpipe_audio::hallucination::VadDecisions;
+        use screenpipe_audio::pyannote::segment::SpeechSegment;
+        use screenpipe_audio::stt::run_stt;
+        use screenpipe_audio::{
+            resample, AudioDevice, DeviceType, Hallucination, HallucinationFilter,
+        };
+
+        let (samples, sample_rate) = pcm_decode("test_data/accuracy1.wav").unwrap();
+        let samples = resample(&samples, sample_rate, 16000).unwrap();
+        let mut whisper_model = WhisperModel::new(
+            &AudioTranscriptionEngine::WhisperTiny,
+            &ModelOptions::default(),
+        )
+        .unwrap();
+
+        let mut result = run_stt(
+            SpeechSegment {
+                start: 0.0,
+                end: 30.0,
+                samples: samples[..16000 * 30].to_vec(),
+                speaker: String::new(),
+                embedding: Vec::new(),
+                sample_rate: 16000,
+            },
+            Arc::new(AudioDevice::new("test".to_string(), DeviceType::Input)),
+            &mut whisper_model,
+            Arc::new(AudioTranscriptionEngine::WhisperTiny),
+            None,
+            vec![Language::English],
+            &TranscriptionOptions::default(),
+            String::new(),
+            0,
+        );
+        // candle times what it decoded
+        assert!(!result.text_segments.is_empty());
+        assert!(result
+            .text_segments
+            .iter()
+            .all(|s| s.start < s.end && s.end <= 30.0 + 1e-6));
+        assert!(result.text_segments.iter().any(|s| s.start >= 10.0));
+
+        // speech in the first 10s only, what was said after is dropped
+        let vad = VadDecisions {
+            frame: Duration::from_millis(100),
+            frames: (0..300).map(|i| Some(i < 100)).collect(),
+        };
+        HallucinationFilter::Drop.apply(&mut result, Some(&vad), 0.0);
+        assert_eq!(result.hallucination, Some(Hallucination::SilentRegion));
+        assert!(!result.text_segments.is_empty());
+        assert!(result.text_segments.iter().all(|s| s.start < 10.0));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_quantized_whisper_transcription() {
+        setup();
+        use screenpipe_audio::resample;
+
+        let (samples, sample_rate) = pcm_decode("test_data/accuracy1.wav").unwrap();
+        let samples = resample(&samples, sample_rate, 16000).unwrap();
+
+        #[allow(unused_mut)]
+        let mut engines = vec![AudioTranscriptionEngine::WhisperTinyQ8];
+        #[cfg(feature = "whisper-cpp")]
+        engines.push(AudioTranscriptionEngine::WhisperLargeV3TurboQ8);
+        for engine in engines {
+            let mut whisper_model = WhisperModel::new(&engine, &ModelOptions::default()).unwrap();
+            let transcript = stt(
+                &samples[..16000 * 30],
+                16000,
+                "test",
+                &mut whisper_model,
+                Arc::new(engine.clone()),
+                None,
+                vec![Language::English],
+                &TranscriptionOptions::default(),
+            )
+            .await
+            .unwrap();
+
+            assert!(
+                !transcript.trim().is_empty(),
+                "{} transcribed nothing",
+                engine
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_beam_search_transcription() {
+        setup();
+        use screenpipe_audio::whisper::DecodingOptions;
+        use screenpipe_audio::{resample, transcribe};
+
+        let (samples, sample_rate) = pcm_decode("test_data/accuracy1.wav").unwrap();
+        let samples = resample(&samples, sample_rate, 16000).unwrap();
+        let engine = AudioTranscriptionEngine::WhisperTiny;
+        let mut whisper_model = WhisperModel::new(&engine, &ModelOptions::default()).unwrap();
+        let options = TranscriptionOptions {
+            decoding: DecodingOptions {
+                beam_size: 3,
+                temperatures: vec![0.0],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let transcript = transcribe(
+            &samples[..16000 * 30],
+            16000,
+            "test",
+            &mut whisper_model,
+            Arc::new(engine),
+            None,
+            vec![Language::English],
+            &options,
+        )
+        .await
+        .unwrap();
+
+        assert!(!transcript.text.trim().is_empty());
+        assert!(transcript.confidence.is_some());
+    }
+
+    #[test]
+    #[ignore]
+    fn test_batched_transcription() {
+        setup();
+        use screenpipe_audio::whisper::{process_batch_with_whisper, DecodingOptions, Task};
+        use screenpipe_audio::{resample, LanguageHint};
+
+        let (samples, sample_rate) = pcm_decode("test_data/accuracy1.wav").unwrap();
+        let samples = resample(&samples, sample_rate, 16000).unwrap();
+        // batches only on a GPU, elsewhere the clips are transcribed one by one
+        let mut whisper_model = WhisperModel::new(
+            &AudioTranscriptionEngine::WhisperTiny,
+            &ModelOptions::default(),
+        )
+        .unwrap();
+        let clips: Vec<&[f32]> = samples.chunks(16000 * 10).take(4).collect();
+
+        let transcripts = process_batch_with_whisper(
+            &mut whisper_model,
+            &clips,
+            &LanguageHint::from_languages(&[Language::English]),
+            Task::Transcribe,
+            None,
+            &DecodingOptions::default(),
+        );
+
+        assert_eq!(transcripts.len(), clips.len());
+        for transcript in transcripts {
+            let transcript = transcript.unwrap();
+            assert!(!transcript.text.trim().is_empty());
+            assert_eq!(transcript.language.unwrap().code, "en");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_onnx_whisper_transcription() {
+        setup();
+        use screenpipe_audio::whisper::ExecutionProvider;
+        use screenpipe_audio::{resample, transcribe};
+
+        let (samples, sample_rate) = pcm_decode("test_data/accuracy1.wav").unwrap();
+        let samples = resample(&samples, sample_rate, 16000).unwrap();
+        let engine = AudioTranscriptionEngine::WhisperOnnx(ExecutionProvider::Cpu);
+        let mut whisper_model = WhisperModel::new(&engine, &ModelOptions::default()).unwrap();
+
+        let transcript = transcribe(
+            &samples[..16000 * 30],
+            16000,
+            "test",
+            &mut whisper_model,
+            Arc::new(engine),
+            None,
+            vec![Language::English],
+            &TranscriptionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!transcript.text.trim().is_empty());
+        assert!(transcript.confidence.is_some());
+    }
+
+    #[cfg(feature = "ctranslate2")]
+    #[tokio::test]
+    #[ignore]
+    async fn test_faster_whisper_transcription() {
+        setup();
+        use screenpipe_audio::resample;
+
+        let (samples, sample_rate) = pcm_decode("test_data/accuracy1.wav").unwrap();
+        let samples = resample(&samples, sample_rate, 16000).unwrap();
+        let mut whisper_model = WhisperModel::new(
+            &AudioTranscriptionEngine::FasterWhisper,
+            &ModelOptions::default(),
+        )
+        .unwrap();
+
+        let transcript = stt(
+            &samples[..16000 * 30],
+            16000,
+            "test",
+            &mut whisper_model,
+            Arc::new(AudioTranscriptionEngine::FasterWhisper),
+            None,
+            vec![Language::English],
+            &TranscriptionOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!transcript.trim().is_empty());
+    }
 }
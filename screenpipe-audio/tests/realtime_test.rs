@@ -1,6 +1,6 @@
 use futures::StreamExt;
 use screenpipe_audio::realtime::RealtimeTranscriptionEvent;
-use screenpipe_audio::{deepgram::start_deepgram_stream, AudioDevice};
+use screenpipe_audio::{deepgram::start_deepgram_stream, AudioBlock, AudioDevice};
 use screenpipe_audio::{pcm_decode, DeviceType};
 use screenpipe_events::subscribe_to_event;
 use std::{
@@ -35,6 +35,9 @@ async fn test_realtime_transcription() {
             sample_rate,
             is_running_clone,
             Some(deepgram_api_key),
+            &[],
+            Vec::new(),
+            None,
         )
         .await;
 
@@ -66,9 +69,10 @@ async fn test_realtime_transcription() {
     let samples = samples.clone();
 
     for sample in samples.chunks(sample_rate as usize * 5) {
-        tx.send(sample.to_vec()).unwrap_or_else(|e| {
-            panic!("Failed to send sample: {}", e);
-        });
+        tx.send(AudioBlock::new(sample.to_vec()))
+            .unwrap_or_else(|e| {
+                panic!("Failed to send sample: {}", e);
+            });
         tokio::time::sleep(Duration::from_millis(10)).await;
     }
 
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tracing::info;
+
+static PAUSE: Mutex<Option<PauseReason>> = Mutex::new(None);
+
+/// Why nothing is captured
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseReason {
+    /// No input for a while
+    Idle,
+    /// The screen is locked or the screensaver is on
+    Locked,
+}
+
+impl PauseReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PauseReason::Idle => "idle",
+            PauseReason::Locked => "locked",
+        }
+    }
+
+    pub fn parse(reason: &str) -> Option<Self> {
+        match reason {
+            "idle" => Some(PauseReason::Idle),
+            "locked" => Some(PauseReason::Locked),
+            _ => None,
+        }
+    }
+}
+
+/// Pauses the capture of the screen and of audio, or resumes it with None
+pub fn set_pause(reason: Option<PauseReason>) {
+    let mut pause = PAUSE.lock().unwrap_or_else(|e| e.into_inner());
+    if *pause != reason {
+        match reason {
+            Some(reason) => info!("pausing capture, {}", reason.as_str()),
+            None => info!("resuming capture"),
+        }
+        *pause = reason;
+    }
+}
+
+pub fn pause_reason() -> Option<PauseReason> {
+    *PAUSE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn is_paused() -> bool {
+    pause_reason().is_some()
+}
@@ -0,0 +1,52 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Drift from the wall clock past which the clock is anchored again, after the computer slept
+/// (the monotonic clock stops then on linux) or the time was set
+const MAX_DRIFT: Duration = Duration::from_secs(2);
+
+static ANCHOR: Mutex<Option<Anchor>> = Mutex::new(None);
+
+/// The wall clock time of an instant of the monotonic clock
+#[derive(Clone, Copy)]
+struct Anchor {
+    instant: Instant,
+    time: SystemTime,
+}
+
+impl Anchor {
+    fn now() -> Self {
+        Self {
+            instant: Instant::now(),
+            time: SystemTime::now(),
+        }
+    }
+
+    fn time_at(&self, instant: Instant) -> SystemTime {
+        match instant.checked_duration_since(self.instant) {
+            Some(elapsed) => self.time + elapsed,
+            None => self.time - self.instant.duration_since(instant),
+        }
+    }
+}
+
+/// Wall clock time of `instant`, read from the monotonic clock shared by audio and vision so the
+/// times of audio chunks and frames compare, whatever happens to the wall clock in between
+pub fn time_at(instant: Instant) -> SystemTime {
+    let mut anchor = ANCHOR.lock().unwrap_or_else(|e| e.into_inner());
+    let now = Anchor::now();
+    let current = anchor.get_or_insert(now);
+    let drift = match current.time_at(now.instant).duration_since(now.time) {
+        Ok(ahead) => ahead,
+        Err(behind) => behind.duration(),
+    };
+    if drift > MAX_DRIFT {
+        *current = now;
+    }
+    current.time_at(instant)
+}
+
+/// Current time of the shared clock
+pub fn now() -> SystemTime {
+    time_at(Instant::now())
+}
@@ -38,7 +38,13 @@ pub use pii_removal::*;
 pub mod network;
 pub use network::*;
 
+pub mod models;
+
 pub use language::{Language, TESSERACT_LANGUAGES};
 
 pub mod devices;
 pub use devices::*;
+
+pub mod capture_pause;
+pub mod clock;
+pub mod meeting;
@@ -0,0 +1,25 @@
+use std::sync::Mutex;
+use tracing::info;
+
+/// Id of the meeting session going on
+static MEETING: Mutex<Option<i64>> = Mutex::new(None);
+
+/// Starts the meeting session of `id`, or ends the one going on with None
+pub fn set_meeting(id: Option<i64>) {
+    let mut meeting = MEETING.lock().unwrap_or_else(|e| e.into_inner());
+    if *meeting != id {
+        match id {
+            Some(id) => info!("meeting {} started", id),
+            None => info!("meeting ended"),
+        }
+        *meeting = id;
+    }
+}
+
+pub fn current_meeting() -> Option<i64> {
+    *MEETING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn in_meeting() -> bool {
+    current_meeting().is_some()
+}
@@ -0,0 +1,400 @@
+use anyhow::{anyhow, bail, Result};
+use log::{debug, info};
+use reqwest::{
+    blocking::Client,
+    header::{CONTENT_RANGE, RANGE},
+    redirect, StatusCode,
+};
+use screenpipe_events::send_event;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Bytes downloaded between two `model_download` events when the size of the file is unknown
+const UNKNOWN_SIZE_EVENT_STEP: u64 = 10 * 1024 * 1024;
+
+/// A model file and where to fetch it from
+#[derive(Clone, Debug)]
+pub struct ModelFile {
+    /// Path relative to the models directory, e.g. "segmentation-3.0.onnx"
+    pub name: String,
+    pub url: String,
+    /// Hex encoded sha256 the download is checked against. When unknown, files of the huggingface
+    /// hub are checked against the sha256 the hub reports for them
+    pub sha256: Option<String>,
+}
+
+impl ModelFile {
+    pub fn new(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            url: url.into(),
+            sha256: None,
+        }
+    }
+
+    /// `file` of the huggingface `repo` at main, stored as "<repo>/<file>"
+    pub fn huggingface(repo: &str, file: &str) -> Self {
+        Self::new(
+            format!("{}/{}", repo, file),
+            format!("https://huggingface.co/{}/resolve/main/{}", repo, file),
+        )
+    }
+
+    pub fn with_sha256(mut self, sha256: impl Into<String>) -> Self {
+        self.sha256 = Some(sha256.into());
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DownloadProgress {
+    pub downloaded: u64,
+    /// Unknown when the server doesn't send a content length
+    pub total: Option<u64>,
+}
+
+/// Sent as `model_download` about every percent of a model downloaded by
+/// `get_or_download_with_events`, and once it is complete
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModelDownloadEvent {
+    /// Name of the model file, e.g. "ggerganov/whisper.cpp/ggml-large-v3-turbo-q5_0.bin"
+    pub name: String,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelSource {
+    /// Downloaded by screenpipe into `models_dir`
+    Screenpipe,
+    /// Fetched through the huggingface hub cache (candle and onnx models)
+    Huggingface,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CachedModel {
+    pub name: String,
+    pub path: PathBuf,
+    /// Size on disk in bytes
+    pub size: u64,
+    pub source: ModelSource,
+}
+
+pub fn models_dir() -> Result<PathBuf> {
+    let cache_dir = dirs::cache_dir().ok_or_else(|| anyhow!("failed to get cache dir"))?;
+    Ok(cache_dir.join("screenpipe").join("models"))
+}
+
+/// Where the huggingface hub keeps the models it downloads, the same as `hf_hub::Cache::default`
+/// without depending on hf-hub
+fn hub_cache_dir() -> Option<PathBuf> {
+    std::env::var_os("HF_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".cache").join("huggingface")))
+        .map(|dir| dir.join("hub"))
+}
+
+/// Path of `file` in the models directory, downloading it first if it isn't there yet.
+///
+/// Interrupted downloads are kept next to the model as `.part` and resumed on the next call.
+/// Blocks until the download is done, async callers should use `spawn_blocking`.
+pub fn get_or_download(
+    file: &ModelFile,
+    on_progress: Option<&(dyn Fn(DownloadProgress) + Sync)>,
+) -> Result<PathBuf> {
+    get_or_download_in(&models_dir()?, file, on_progress)
+}
+
+/// `get_or_download` sending how the download goes as `model_download` events, for the app to
+/// show while a model it needs is fetched
+pub fn get_or_download_with_events(file: &ModelFile) -> Result<PathBuf> {
+    let reported = AtomicU64::new(0);
+    let on_progress = |progress: DownloadProgress| {
+        let step = progress
+            .total
+            .map_or(UNKNOWN_SIZE_EVENT_STEP, |total| (total / 100).max(1));
+        let complete = Some(progress.downloaded) == progress.total;
+        if progress.downloaded < reported.load(Ordering::Relaxed) + step && !complete {
+            return;
+        }
+        reported.store(progress.downloaded, Ordering::Relaxed);
+        let _ = send_event(
+            "model_download",
+            ModelDownloadEvent {
+                name: file.name.clone(),
+                downloaded: progress.downloaded,
+                total: progress.total,
+            },
+        );
+    };
+    get_or_download(file, Some(&on_progress))
+}
+
+/// `get_or_download` with `dir` as the models directory
+pub fn get_or_download_in(
+    dir: &Path,
+    file: &ModelFile,
+    on_progress: Option<&(dyn Fn(DownloadProgress) + Sync)>,
+) -> Result<PathBuf> {
+    let path = dir.join(&file.name);
+    if path.exists() {
+        debug!("using cached model {:?}", path);
+        return Ok(path);
+    }
+
+    info!("downloading model {} from {}", file.name, file.url);
+    // the blocking client can't be used on a runtime thread, so it always gets its own
+    std::thread::scope(|s| {
+        s.spawn(|| download(file, &path, on_progress))
+            .join()
+            .map_err(|_| anyhow!("download of {} panicked", file.name))?
+    })?;
+    info!("model {} saved to {:?}", file.name, path);
+
+    Ok(path)
+}
+
+fn download(
+    file: &ModelFile,
+    path: &Path,
+    on_progress: Option<&(dyn Fn(DownloadProgress) + Sync)>,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let part_path = part_path(path);
+    let expected = file.sha256.clone().or_else(|| hub_sha256(&file.url));
+
+    let mut hasher = Sha256::new();
+    let mut downloaded = 0u64;
+    if part_path.exists() {
+        downloaded = std::io::copy(&mut File::open(&part_path)?, &mut hasher)?;
+    }
+
+    let client = Client::builder().timeout(None).build()?;
+    let mut request = client.get(&file.url);
+    if downloaded > 0 {
+        request = request.header(RANGE, format!("bytes={}-", downloaded));
+    }
+    let response = request.send()?;
+    if downloaded > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // nothing left after the part file: it is complete, unless the server says its size is
+        // another
+        let size = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|range| range.to_str().ok())
+            .and_then(|range| range.strip_prefix("bytes */"))
+            .and_then(|size| size.parse::<u64>().ok());
+        if size.is_some_and(|size| size != downloaded) {
+            debug!("part of {} doesn't match the file, restarting", file.name);
+            fs::remove_file(&part_path)?;
+            return download(file, path, on_progress);
+        }
+        info!("download of {} was already complete", file.name);
+        return finish(file, &part_path, path, hasher, expected.as_deref());
+    }
+    let mut response = response.error_for_status()?;
+
+    let mut out = if response.status() == StatusCode::PARTIAL_CONTENT {
+        info!("resuming download of {} at {} bytes", file.name, downloaded);
+        OpenOptions::new().append(true).open(&part_path)?
+    } else {
+        if downloaded > 0 {
+            debug!("server doesn't support resuming, restarting {}", file.name);
+        }
+        hasher = Sha256::new();
+        downloaded = 0;
+        File::create(&part_path)?
+    };
+    let total = response.content_length().map(|len| len + downloaded);
+
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut logged_percent = 0;
+    loop {
+        let n = response.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        out.write_all(&buf[..n])?;
+        hasher.update(&buf[..n]);
+        downloaded += n as u64;
+
+        if let Some(on_progress) = on_progress {
+            on_progress(DownloadProgress { downloaded, total });
+        }
+        if let Some(total) = total.filter(|total| *total > 0) {
+            let percent = downloaded * 100 / total;
+            if percent >= logged_percent + 10 {
+                logged_percent = percent - percent % 10;
+                info!("downloading {}: {}%", file.name, logged_percent);
+            }
+        }
+    }
+    out.flush()?;
+
+    // the part file is kept so the next attempt picks up from here
+    if let Some(total) = total {
+        if downloaded < total {
+            bail!(
+                "download of {} interrupted at {} of {} bytes",
+                file.name,
+                downloaded,
+                total
+            );
+        }
+    }
+
+    finish(file, &part_path, path, hasher, expected.as_deref())
+}
+
+/// Checks the complete download at `part_path` against `expected` and moves it to `path`. A part
+/// file that doesn't match is deleted
+fn finish(
+    file: &ModelFile,
+    part_path: &Path,
+    path: &Path,
+    hasher: Sha256,
+    expected: Option<&str>,
+) -> Result<()> {
+    let digest = format!("{:x}", hasher.finalize());
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(&digest) => {
+            fs::remove_file(part_path)?;
+            bail!(
+                "checksum mismatch for {}: expected {}, got {}",
+                file.name,
+                expected,
+                digest
+            );
+        }
+        Some(_) => debug!("checksum of {} verified", file.name),
+        None => debug!("no checksum known for {}, sha256 {}", file.name, digest),
+    }
+
+    fs::rename(part_path, path)?;
+    Ok(())
+}
+
+/// sha256 the huggingface hub reports for a file of `url` stored with git lfs, where models
+/// weights are. None for other urls and for small files, which the hub identifies by their git
+/// sha1 instead
+fn hub_sha256(url: &str) -> Option<String> {
+    if !url.starts_with("https://huggingface.co/") {
+        return None;
+    }
+    // the hub answers with a redirect to its cdn, carrying the sha256 of the file
+    let client = Client::builder()
+        .redirect(redirect::Policy::none())
+        .build()
+        .ok()?;
+    let response = client.head(url).send().ok()?;
+    let etag = response.headers().get("x-linked-etag")?.to_str().ok()?;
+    let etag = etag.trim_start_matches("W/").trim_matches('"');
+    (etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| etag.to_ascii_lowercase())
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// Models downloaded by screenpipe and the huggingface hub cache, with their size on disk
+pub fn list_models() -> Result<Vec<CachedModel>> {
+    list_models_in(&models_dir()?)
+}
+
+/// `list_models` with `dir` as the models directory
+pub fn list_models_in(dir: &Path) -> Result<Vec<CachedModel>> {
+    let mut models = Vec::new();
+
+    if dir.exists() {
+        for path in walk_files(dir)? {
+            models.push(CachedModel {
+                name: relative_name(dir, &path),
+                size: path.metadata()?.len(),
+                path,
+                source: ModelSource::Screenpipe,
+            });
+        }
+    }
+
+    if let Some(hub_dir) = hub_cache_dir().filter(|dir| dir.exists()) {
+        for entry in fs::read_dir(&hub_dir)? {
+            let path = entry?.path();
+            let Some(repo) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("models--"))
+            else {
+                continue;
+            };
+            models.push(CachedModel {
+                name: repo.replace("--", "/"),
+                size: walk_files(&path)?
+                    .iter()
+                    .filter_map(|file| file.metadata().ok())
+                    .map(|metadata| metadata.len())
+                    .sum(),
+                path,
+                source: ModelSource::Huggingface,
+            });
+        }
+    }
+
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(models)
+}
+
+/// Deletes a model listed by `list_models`, it is downloaded again the next time it's needed
+pub fn remove_model(name: &str) -> Result<CachedModel> {
+    remove_model_in(&models_dir()?, name)
+}
+
+/// `remove_model` with `dir` as the models directory
+pub fn remove_model_in(dir: &Path, name: &str) -> Result<CachedModel> {
+    let model = list_models_in(dir)?
+        .into_iter()
+        .find(|model| model.name == name)
+        .ok_or_else(|| anyhow!("model {} not found", name))?;
+
+    if model.path.is_dir() {
+        fs::remove_dir_all(&model.path)?;
+    } else {
+        fs::remove_file(&model.path)?;
+    }
+    info!("removed model {} ({} bytes)", model.name, model.size);
+
+    Ok(model)
+}
+
+/// Files under `dir`, without following symlinks (the hub cache links snapshots to blobs)
+fn walk_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            files.extend(walk_files(&entry.path())?);
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(files)
+}
+
+fn relative_name(dir: &Path, path: &Path) -> String {
+    path.strip_prefix(dir)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
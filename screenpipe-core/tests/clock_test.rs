@@ -0,0 +1,21 @@
+use screenpipe_core::clock;
+use std::time::{Duration, Instant, SystemTime};
+
+#[test]
+fn test_clock() {
+    let before = Instant::now();
+    let now = clock::now();
+    let wall = SystemTime::now();
+    let drift = match now.duration_since(wall) {
+        Ok(ahead) => ahead,
+        Err(behind) => behind.duration(),
+    };
+    assert!(drift < Duration::from_secs(1));
+
+    // instants map to times the same distance apart
+    let later = before + Duration::from_secs(5);
+    let gap = clock::time_at(later)
+        .duration_since(clock::time_at(before))
+        .unwrap();
+    assert_eq!(gap, Duration::from_secs(5));
+}
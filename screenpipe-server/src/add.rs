@@ -133,11 +133,7 @@ pub async fn handle_index_command(
 
         // Create video chunk and frames first
         let frame_ids = db
-            .create_video_with_frames(
-                video_path.to_str().unwrap(),
-                frames.clone(),
-                metadata.clone(),
-            )
+            .create_video_with_frames(video_path.to_str().unwrap(), frames.len(), metadata.clone())
             .await?;
 
         let mut previous_image: Option<DynamicImage> = None;
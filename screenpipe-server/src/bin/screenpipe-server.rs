@@ -5,23 +5,32 @@ use dashmap::DashMap;
 use dirs::home_dir;
 use futures::pin_mut;
 use port_check::is_local_ipv4_port_free;
+use screenpipe_audio::archive::recompress_periodically;
 use screenpipe_audio::{
-    default_input_device, default_output_device, list_audio_devices, parse_audio_device,
-    AudioDevice, DeviceControl,
+    default_input_device, default_output_device, list_audio_devices, list_audio_hosts,
+    list_host_audio_devices, parse_audio_device, AudioDevice, DeviceControl, StreamOptions,
 };
 use screenpipe_core::find_ffmpeg_path;
 use screenpipe_server::{
     cli::{
-        AudioCommand, Cli, CliAudioTranscriptionEngine, CliOcrEngine, Command, OutputFormat,
-        PipeCommand, VisionCommand,
+        AudioCommand, Cli, CliAudioTranscriptionEngine, CliOcrEngine, Command, ModelCommand,
+        OutputFormat, PipeCommand, VisionCommand,
     },
-    handle_index_command,
+    detect_meetings, handle_index_command, handle_subtitles_command, pause_capture_when_away,
     pipe_manager::PipeInfo,
-    start_continuous_recording, watch_pid, DatabaseManager, PipeManager, ResourceMonitor, Server,
+    record_input_events, redaction,
+    search_analyzer::QueryAnalyzer,
+    start_continuous_recording, text_embeds,
+    video_archive::{archive_periodically, VideoArchiveConfig},
+    video_encoder, watch_pid, DatabaseManager, PipeManager, ResourceMonitor, ScreenshotOptions,
+    Server,
 };
+use screenpipe_vision::capture_screenshot_by_window::WindowFilters;
 use screenpipe_vision::monitor::list_monitors;
 #[cfg(target_os = "macos")]
 use screenpipe_vision::run_ui;
+use screenpipe_vision::video_call;
+use screenpipe_vision::{changed_regions, cursor, ocr_filter, ocr_languages, ocr_preprocess};
 use serde_json::{json, Value};
 use std::{
     collections::HashMap,
@@ -190,11 +199,15 @@ async fn main() -> anyhow::Result<()> {
     };
 
     let pipe_manager = Arc::new(PipeManager::new(local_data_dir_clone.clone()));
+    text_embeds::init(cli.embedding_backend());
     if let Some(ref command) = cli.command {
         match command {
             Command::Audio { subcommand } => match subcommand {
-                AudioCommand::List { output } => {
-                    let devices = list_audio_devices().await?;
+                AudioCommand::List { output, host } => {
+                    let devices = match host {
+                        Some(host) => list_host_audio_devices(&host).await?,
+                        None => list_audio_devices().await?,
+                    };
                     match output {
                         OutputFormat::Json => println!(
                             "{}",
@@ -214,6 +227,44 @@ async fn main() -> anyhow::Result<()> {
                     }
                     return Ok(());
                 }
+                AudioCommand::Hosts { output } => {
+                    let hosts = list_audio_hosts();
+                    match output {
+                        OutputFormat::Json => println!(
+                            "{}",
+                            serde_json::to_string_pretty(&json!({
+                                "data": hosts,
+                                "success": true
+                            }))?
+                        ),
+                        OutputFormat::Text => {
+                            println!("available audio hosts:");
+                            for host in hosts.iter() {
+                                if host.is_default {
+                                    println!("  {} (default)", host.id);
+                                } else {
+                                    println!("  {}", host.id);
+                                }
+                            }
+                        }
+                    }
+                    return Ok(());
+                }
+                AudioCommand::Subtitles {
+                    path,
+                    format,
+                    output,
+                } => {
+                    handle_subtitles_command(
+                        &cli,
+                        &local_data_dir,
+                        path,
+                        format.clone().into(),
+                        output.clone(),
+                    )
+                    .await?;
+                    return Ok(());
+                }
             },
             Command::Vision { subcommand } => match subcommand {
                 VisionCommand::List { output } => {
@@ -241,6 +292,51 @@ async fn main() -> anyhow::Result<()> {
                     return Ok(());
                 }
             },
+            Command::Model { subcommand } => match subcommand {
+                ModelCommand::List { output } => {
+                    let models = screenpipe_audio::models::list_models()?;
+                    match output {
+                        OutputFormat::Json => println!(
+                            "{}",
+                            serde_json::to_string_pretty(&json!({
+                                "data": models,
+                                "success": true
+                            }))?
+                        ),
+                        OutputFormat::Text => {
+                            println!("downloaded models:");
+                            for model in models.iter() {
+                                println!(
+                                    "  {} ({:.1} MB)",
+                                    model.name,
+                                    model.size as f64 / 1_000_000.0
+                                );
+                            }
+                            let total: u64 = models.iter().map(|m| m.size).sum();
+                            println!("total: {:.1} MB", total as f64 / 1_000_000.0);
+                        }
+                    }
+                    return Ok(());
+                }
+                ModelCommand::Remove { name, output } => {
+                    let model = screenpipe_audio::models::remove_model(name)?;
+                    match output {
+                        OutputFormat::Json => println!(
+                            "{}",
+                            serde_json::to_string_pretty(&json!({
+                                "data": model,
+                                "success": true
+                            }))?
+                        ),
+                        OutputFormat::Text => println!(
+                            "removed {} ({:.1} MB freed)",
+                            model.name,
+                            model.size as f64 / 1_000_000.0
+                        ),
+                    }
+                    return Ok(());
+                }
+            },
             Command::Completions { shell } => {
                 cli.handle_completions(shell.clone())?;
                 return Ok(());
@@ -293,7 +389,12 @@ async fn main() -> anyhow::Result<()> {
                 // this command just download models and stuff (useful to have specific step to display in UI)
 
                 // ! should prob skip if deepgram?
-                WhisperModel::new(&cli.audio_transcription_engine.into()).unwrap();
+                WhisperModel::new(
+                    &cli.audio_transcription_engine
+                        .resolve(&cli.onnx_execution_provider),
+                    &cli.model_options(),
+                )
+                .unwrap();
                 // ! assuming silero is used
                 SileroVad::new().await.unwrap();
 
@@ -501,6 +602,20 @@ async fn main() -> anyhow::Result<()> {
             })?,
     );
 
+    let languages = cli
+        .unique_languages()
+        .map_err(|e| anyhow::anyhow!("invalid languages: {}", e))?;
+    let search_analyzer = cli.search_analyzer.resolve(&languages);
+    db.set_query_analyzer(QueryAnalyzer::new(search_analyzer, languages.clone()));
+    let db_reindex = db.clone();
+    tokio::spawn(async move {
+        match db_reindex.rebuild_search_index(search_analyzer).await {
+            Ok(true) => info!("search index rebuilt with {} analyzer", search_analyzer),
+            Ok(false) => debug!("search index already uses {} analyzer", search_analyzer),
+            Err(e) => error!("failed to rebuild search index: {}", e),
+        }
+    });
+
     let db_server = db.clone();
 
     // Channel for controlling the recorder ! TODO RENAME SHIT
@@ -513,8 +628,8 @@ async fn main() -> anyhow::Result<()> {
     } else {
         cli.monitor_id.clone()
     };
+    let monitor_configs = cli.monitor_configs()?;
 
-    let languages = cli.unique_languages().unwrap();
     let languages_clone = languages.clone();
 
     let ocr_engine_clone = cli.ocr_engine.clone();
@@ -546,6 +661,87 @@ async fn main() -> anyhow::Result<()> {
     };
 
     let audio_chunk_duration = Duration::from_secs(cli.audio_chunk_duration);
+    let audio_stream_options = match (cli.audio_buffer_frames, cli.low_latency_audio) {
+        (Some(frames), _) => StreamOptions {
+            buffer_frames: Some(frames),
+        },
+        (None, true) => StreamOptions::low_latency(),
+        (None, false) => StreamOptions::default(),
+    };
+    let recording_options = cli.recording_options();
+    let model_options = cli.model_options();
+    let transcription_options = cli.transcription_options(&local_data_dir)?;
+    redaction::init(cli.redaction_options())?;
+    ocr_languages::init(cli.ocr_language_options());
+    ocr_filter::init(cli.ocr_filter_config());
+    changed_regions::init(!cli.disable_changed_region_ocr);
+    ocr_preprocess::init(cli.ocr_preprocessing.clone().into());
+    cursor::init(cli.capture_cursor);
+    #[cfg(all(target_os = "linux", feature = "pipewire"))]
+    screenpipe_vision::wayland::init(local_data_dir.clone());
+    video_encoder::init(
+        cli.video_codec.clone().into(),
+        cli.video_encoder.clone().into(),
+    )
+    .await;
+    if let Some(days) = cli.video_archive_days {
+        match video_encoder::resolve(
+            cli.video_archive_codec.clone().into(),
+            cli.video_encoder.clone().into(),
+        )
+        .await
+        {
+            Some(encoder) => {
+                tokio::spawn(archive_periodically(
+                    db.clone(),
+                    VideoArchiveConfig {
+                        after: Duration::from_secs(days * 24 * 3600),
+                        encoder,
+                    },
+                ));
+            }
+            None => warn!(
+                "no {:?} encoder works on this machine, video chunks won't be archived",
+                cli.video_archive_codec
+            ),
+        }
+    }
+    if let Some(archive) = transcription_options.archive.clone() {
+        tokio::spawn(recompress_periodically(archive));
+    }
+    if cli.enable_input_events {
+        tokio::spawn(record_input_events(
+            db.clone(),
+            cli.app_filters(),
+            cli.sensitive_windows(),
+        ));
+    }
+    if cli.detect_meetings {
+        video_call::init_meeting_fps(cli.meeting_fps);
+        tokio::spawn(detect_meetings(db.clone()));
+    }
+    if cli.enable_text_embeddings {
+        text_embeds::spawn_text_embedder(db.clone());
+    }
+    if cli.pause_on_idle.is_some() || cli.pause_on_lock {
+        tokio::spawn(pause_capture_when_away(
+            db.clone(),
+            cli.pause_on_idle
+                .map(|minutes| Duration::from_secs(minutes * 60)),
+            cli.pause_on_lock,
+        ));
+    }
+    // on demand screenshots are captured and read the same as the frames recorded
+    let screenshot_options = (!cli.disable_vision).then(|| ScreenshotOptions {
+        ocr_engine: cli.ocr_engine.clone().into(),
+        languages: languages.clone(),
+        window_filters: WindowFilters::new(&cli.ignored_windows, &cli.included_windows)
+            .with_single_window(cli.capture_window.as_deref())
+            .with_app_filters(cli.app_filters())
+            .with_sensitive_windows(cli.sensitive_windows()),
+        capture_unfocused_windows: cli.capture_unfocused_windows,
+        use_pii_removal: cli.use_pii_removal,
+    });
     let (realtime_vision_sender, _) = tokio::sync::broadcast::channel(1000);
     let realtime_vision_sender = Arc::new(realtime_vision_sender.clone());
     let realtime_vision_sender_clone = realtime_vision_sender.clone();
@@ -565,9 +761,15 @@ async fn main() -> anyhow::Result<()> {
                     vision_control_clone.clone(),
                     audio_devices_control_recording.clone(),
                     cli.disable_audio,
-                    Arc::new(cli.audio_transcription_engine.clone().into()),
+                    Arc::new(
+                        cli.audio_transcription_engine
+                            .resolve(&cli.onnx_execution_provider),
+                    ),
                     Arc::new(cli.ocr_engine.clone().into()),
                     monitor_ids_clone.clone(),
+                    monitor_configs.clone(),
+                    cli.ocr_scale,
+                    cli.video_scale,
                     cli.use_pii_removal,
                     cli.disable_vision,
                     vad_engine_clone,
@@ -575,13 +777,25 @@ async fn main() -> anyhow::Result<()> {
                     &audio_handle,
                     &cli.ignored_windows,
                     &cli.included_windows,
+                    cli.capture_window.clone(),
+                    cli.app_filters(),
+                    cli.sensitive_windows(),
                     cli.deepgram_api_key.clone(),
                     cli.vad_sensitivity.clone(),
                     languages.clone(),
                     cli.capture_unfocused_windows,
+                    cli.adaptive_fps(),
+                    !cli.disable_frame_dedup,
+                    cli.enable_accessibility_tree,
+                    cli.enable_frame_embeddings,
                     realtime_audio_devices.clone(),
                     cli.enable_realtime_audio_transcription,
                     realtime_vision_sender_clone,
+                    audio_stream_options.clone(),
+                    recording_options.clone(),
+                    cli.watch_folder.clone(),
+                    model_options.clone(),
+                    transcription_options.clone(),
                 );
 
                 let result = tokio::select! {
@@ -634,6 +848,8 @@ async fn main() -> anyhow::Result<()> {
         cli.disable_vision,
         cli.disable_audio,
         cli.enable_ui_monitoring,
+        cli.enable_eval_dataset,
+        screenshot_options,
     );
 
     let mut rx = audio_devices_tx.subscribe();
@@ -905,8 +1121,14 @@ async fn main() -> anyhow::Result<()> {
     println!("└────────────────────────┴────────────────────────────────────┘");
 
     // Add warning for cloud arguments and telemetry
-    if warning_audio_transcription_engine_clone == CliAudioTranscriptionEngine::Deepgram
-        || warning_ocr_engine_clone == CliOcrEngine::Unstructured
+    if matches!(
+        warning_audio_transcription_engine_clone,
+        CliAudioTranscriptionEngine::Deepgram
+            | CliAudioTranscriptionEngine::AssemblyAI
+            | CliAudioTranscriptionEngine::AzureSpeech
+            | CliAudioTranscriptionEngine::OpenAIWhisper
+            | CliAudioTranscriptionEngine::GroqWhisper
+    ) || warning_ocr_engine_clone == CliOcrEngine::Unstructured
     {
         println!(
             "{}",
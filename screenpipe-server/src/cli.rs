@@ -1,13 +1,39 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use clap::{Parser, Subcommand, ValueHint};
 use clap_complete::{generate, Shell};
 use clap::CommandFactory;
 use screenpipe_audio::{vad_engine::VadSensitivity, AudioTranscriptionEngine as CoreAudioTranscriptionEngine};
-use screenpipe_vision::{custom_ocr::CustomOcrConfig, utils::OcrEngine as CoreOcrEngine};
+use screenpipe_vision::{active_window::AppFilters, adaptive_fps::AdaptiveFpsConfig, custom_ocr::CustomOcrConfig, frame_scale::FrameScale, monitor::MonitorConfig, ocr_filter::OcrFilterConfig, ocr_languages::OcrLanguageOptions, ocr_preprocess::OcrPreprocessing, sensitive_windows::SensitiveWindows, utils::OcrEngine as CoreOcrEngine};
 use clap::ValueEnum;
-use screenpipe_audio::vad_engine::VadEngineEnum;
+use screenpipe_audio::vad_engine::{
+    SegmentPadding, VadConfig, VadEngineEnum, VadPreset, VadSettings,
+};
+use screenpipe_audio::whisper::{
+    DecodingOptions, DevicePreference, ExecutionProvider, ModelOptions, ModelSource, Task,
+};
+use screenpipe_audio::audio_tagging::AudioTagging;
+use screenpipe_audio::archive::{ArchiveConfig, Recompression};
+use screenpipe_audio::audio_processing::{AudioPipeline, LoudnessNormalization};
+use screenpipe_audio::fingerprint::DuplicateDetection;
+use screenpipe_audio::noise_gate::NoiseGate;
+use screenpipe_audio::azure::AzureSpeechCredentials;
+use screenpipe_audio::encode::DEFAULT_OPUS_BITRATE_KBPS;
+use screenpipe_audio::lanes::LaneLimits;
+use screenpipe_audio::subtitles::SubtitleFormat;
+use screenpipe_audio::{
+    parse_audio_device, AudioCodec, AudioDevice, CloudApiKeys, DeviceTranscriptionOptions,
+    HallucinationFilter, KeywordSpotter, LanguageFilter, OfflineQueueConfig, RecordingOptions,
+    RetryPolicy, TranscriptionOptions, VadGating,
+};
 use screenpipe_core::Language;
+use crate::redaction::RedactionOptions;
+use crate::search_analyzer::SearchAnalyzer;
+use crate::text_embeds::EmbeddingBackend;
+use crate::video_encoder::{EncoderBackend, EncoderPreference, VideoCodec};
+use crate::watch_folder::WatchFolder;
 
 #[derive(Clone, Debug, ValueEnum, PartialEq)]
 pub enum CliAudioTranscriptionEngine {
@@ -19,6 +45,36 @@ pub enum CliAudioTranscriptionEngine {
     WhisperDistilLargeV3,
     #[clap(name = "whisper-large-v3-turbo")]
     WhisperLargeV3Turbo,
+    #[cfg(feature = "whisper-cpp")]
+    #[clap(name = "whisper-cpp")]
+    WhisperCpp,
+    #[clap(name = "faster-whisper")]
+    FasterWhisper,
+    #[clap(name = "whisper-onnx")]
+    WhisperOnnx,
+    #[clap(name = "vosk")]
+    Vosk,
+    #[clap(name = "whisper-tiny-q8")]
+    WhisperTinyQ8,
+    // ggml models, only whisper.cpp runs them
+    #[cfg(feature = "whisper-cpp")]
+    #[clap(name = "whisper-large-v3-q5")]
+    WhisperLargeV3Q5,
+    #[cfg(feature = "whisper-cpp")]
+    #[clap(name = "whisper-large-v3-turbo-q8")]
+    WhisperLargeV3TurboQ8,
+    #[clap(name = "whisper-large-v3")]
+    WhisperLargeV3,
+    #[clap(name = "whisper-distil-small-en")]
+    WhisperDistilSmallEn,
+    #[clap(name = "assemblyai")]
+    AssemblyAI,
+    #[clap(name = "azure-speech")]
+    AzureSpeech,
+    #[clap(name = "openai-whisper")]
+    OpenAIWhisper,
+    #[clap(name = "groq-whisper")]
+    GroqWhisper,
 }
 
 impl From<CliAudioTranscriptionEngine> for CoreAudioTranscriptionEngine {
@@ -32,6 +88,71 @@ impl From<CliAudioTranscriptionEngine> for CoreAudioTranscriptionEngine {
             CliAudioTranscriptionEngine::WhisperLargeV3Turbo => {
                 CoreAudioTranscriptionEngine::WhisperLargeV3Turbo
             }
+            #[cfg(feature = "whisper-cpp")]
+            CliAudioTranscriptionEngine::WhisperCpp => CoreAudioTranscriptionEngine::WhisperCpp,
+            CliAudioTranscriptionEngine::FasterWhisper => {
+                CoreAudioTranscriptionEngine::FasterWhisper
+            }
+            CliAudioTranscriptionEngine::WhisperOnnx => {
+                CoreAudioTranscriptionEngine::WhisperOnnx(ExecutionProvider::Auto)
+            }
+            CliAudioTranscriptionEngine::WhisperTinyQ8 => CoreAudioTranscriptionEngine::WhisperTinyQ8,
+            #[cfg(feature = "whisper-cpp")]
+            CliAudioTranscriptionEngine::WhisperLargeV3Q5 => {
+                CoreAudioTranscriptionEngine::WhisperLargeV3Q5
+            }
+            #[cfg(feature = "whisper-cpp")]
+            CliAudioTranscriptionEngine::WhisperLargeV3TurboQ8 => {
+                CoreAudioTranscriptionEngine::WhisperLargeV3TurboQ8
+            }
+            CliAudioTranscriptionEngine::WhisperLargeV3 => CoreAudioTranscriptionEngine::WhisperLargeV3,
+            CliAudioTranscriptionEngine::WhisperDistilSmallEn => {
+                CoreAudioTranscriptionEngine::WhisperDistilSmallEn
+            }
+            CliAudioTranscriptionEngine::AssemblyAI => CoreAudioTranscriptionEngine::AssemblyAI,
+            CliAudioTranscriptionEngine::AzureSpeech => CoreAudioTranscriptionEngine::AzureSpeech,
+            CliAudioTranscriptionEngine::Vosk => CoreAudioTranscriptionEngine::Vosk,
+            CliAudioTranscriptionEngine::OpenAIWhisper => {
+                CoreAudioTranscriptionEngine::OpenAIWhisper
+            }
+            CliAudioTranscriptionEngine::GroqWhisper => CoreAudioTranscriptionEngine::GroqWhisper,
+        }
+    }
+}
+
+impl CliAudioTranscriptionEngine {
+    pub fn resolve(&self, provider: &CliExecutionProvider) -> CoreAudioTranscriptionEngine {
+        match self {
+            CliAudioTranscriptionEngine::WhisperOnnx => {
+                CoreAudioTranscriptionEngine::WhisperOnnx(provider.clone().into())
+            }
+            engine => engine.clone().into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliExecutionProvider {
+    Auto,
+    Cpu,
+    Cuda,
+    #[clap(name = "tensorrt")]
+    TensorRt,
+    #[clap(name = "directml")]
+    DirectMl,
+    #[clap(name = "coreml")]
+    CoreMl,
+}
+
+impl From<CliExecutionProvider> for ExecutionProvider {
+    fn from(cli_provider: CliExecutionProvider) -> Self {
+        match cli_provider {
+            CliExecutionProvider::Auto => ExecutionProvider::Auto,
+            CliExecutionProvider::Cpu => ExecutionProvider::Cpu,
+            CliExecutionProvider::Cuda => ExecutionProvider::Cuda(0),
+            CliExecutionProvider::TensorRt => ExecutionProvider::TensorRt(0),
+            CliExecutionProvider::DirectMl => ExecutionProvider::DirectMl(0),
+            CliExecutionProvider::CoreMl => ExecutionProvider::CoreMl,
         }
     }
 }
@@ -45,6 +166,7 @@ pub enum CliOcrEngine {
     WindowsNative,
     #[cfg(target_os = "macos")]
     AppleNative,
+    PaddleOcr,
     Custom,
 }
 
@@ -58,6 +180,7 @@ impl From<CliOcrEngine> for CoreOcrEngine {
             CliOcrEngine::WindowsNative => CoreOcrEngine::WindowsNative,
             #[cfg(target_os = "macos")]
             CliOcrEngine::AppleNative => CoreOcrEngine::AppleNative,
+            CliOcrEngine::PaddleOcr => CoreOcrEngine::PaddleOcr,
             CliOcrEngine::Custom => {
                 // Try to read config from environment variable
                 if let Ok(config_str) = std::env::var("SCREENPIPE_CUSTOM_OCR_CONFIG") {
@@ -81,6 +204,10 @@ pub enum CliVadEngine {
     WebRtc,
     #[clap(name = "silero")]
     Silero,
+    #[clap(name = "energy")]
+    Energy,
+    #[clap(name = "webrtc-apm")]
+    WebRtcApm,
 }
 
 impl From<CliVadEngine> for VadEngineEnum {
@@ -88,6 +215,62 @@ impl From<CliVadEngine> for VadEngineEnum {
         match cli_engine {
             CliVadEngine::WebRtc => VadEngineEnum::WebRtc,
             CliVadEngine::Silero => VadEngineEnum::Silero,
+            CliVadEngine::Energy => VadEngineEnum::Energy,
+            CliVadEngine::WebRtcApm => VadEngineEnum::WebRtcApm,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliAudioCodec {
+    #[clap(name = "aac")]
+    Aac,
+    #[clap(name = "opus")]
+    Opus,
+    #[clap(name = "flac")]
+    Flac,
+    #[clap(name = "m4a")]
+    M4a,
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliVideoCodec {
+    H264,
+    Hevc,
+    Av1,
+}
+
+impl From<CliVideoCodec> for VideoCodec {
+    fn from(cli_codec: CliVideoCodec) -> Self {
+        match cli_codec {
+            CliVideoCodec::H264 => VideoCodec::H264,
+            CliVideoCodec::Hevc => VideoCodec::Hevc,
+            CliVideoCodec::Av1 => VideoCodec::Av1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliVideoEncoder {
+    Auto,
+    Software,
+    #[clap(name = "videotoolbox")]
+    VideoToolbox,
+    Nvenc,
+    #[clap(name = "qsv")]
+    QuickSync,
+}
+
+impl From<CliVideoEncoder> for EncoderPreference {
+    fn from(cli_encoder: CliVideoEncoder) -> Self {
+        match cli_encoder {
+            CliVideoEncoder::Auto => EncoderPreference::Auto,
+            CliVideoEncoder::Software => EncoderPreference::Backend(EncoderBackend::Software),
+            CliVideoEncoder::VideoToolbox => {
+                EncoderPreference::Backend(EncoderBackend::VideoToolbox)
+            }
+            CliVideoEncoder::Nvenc => EncoderPreference::Backend(EncoderBackend::Nvenc),
+            CliVideoEncoder::QuickSync => EncoderPreference::Backend(EncoderBackend::QuickSync),
         }
     }
 }
@@ -109,6 +292,111 @@ impl From<CliVadSensitivity> for VadSensitivity {
     }
 }
 
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliVadPreset {
+    Aggressive,
+    Balanced,
+    Sensitive,
+}
+
+impl From<CliVadPreset> for VadPreset {
+    fn from(cli_preset: CliVadPreset) -> Self {
+        match cli_preset {
+            CliVadPreset::Aggressive => VadPreset::Aggressive,
+            CliVadPreset::Balanced => VadPreset::Balanced,
+            CliVadPreset::Sensitive => VadPreset::Sensitive,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliHallucinationFilter {
+    Off,
+    Flag,
+    Drop,
+}
+
+impl From<CliHallucinationFilter> for HallucinationFilter {
+    fn from(cli_filter: CliHallucinationFilter) -> Self {
+        match cli_filter {
+            CliHallucinationFilter::Off => HallucinationFilter::Off,
+            CliHallucinationFilter::Flag => HallucinationFilter::Flag,
+            CliHallucinationFilter::Drop => HallucinationFilter::Drop,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliAudioTagging {
+    Off,
+    Label,
+    SkipMusic,
+}
+
+impl From<CliAudioTagging> for AudioTagging {
+    fn from(cli_tagging: CliAudioTagging) -> Self {
+        match cli_tagging {
+            CliAudioTagging::Off => AudioTagging::Off,
+            CliAudioTagging::Label => AudioTagging::Label,
+            CliAudioTagging::SkipMusic => AudioTagging::SkipMusic,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliOcrPreprocessing {
+    Off,
+    Auto,
+    Binarize,
+}
+
+impl From<CliOcrPreprocessing> for OcrPreprocessing {
+    fn from(cli_preprocessing: CliOcrPreprocessing) -> Self {
+        match cli_preprocessing {
+            CliOcrPreprocessing::Off => OcrPreprocessing::Off,
+            CliOcrPreprocessing::Auto => OcrPreprocessing::Auto,
+            CliOcrPreprocessing::Binarize => OcrPreprocessing::Binarize,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliWhisperTask {
+    Transcribe,
+    Translate,
+}
+
+impl From<CliWhisperTask> for Task {
+    fn from(cli_task: CliWhisperTask) -> Self {
+        match cli_task {
+            CliWhisperTask::Transcribe => Task::Transcribe,
+            CliWhisperTask::Translate => Task::Translate,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliSearchAnalyzer {
+    /// Pick from --language (cjk for chinese/japanese/korean, stemmed when all languages have a stemmer)
+    Auto,
+    Basic,
+    Folded,
+    Stemmed,
+    Cjk,
+}
+
+impl CliSearchAnalyzer {
+    pub fn resolve(&self, languages: &[Language]) -> SearchAnalyzer {
+        match self {
+            CliSearchAnalyzer::Auto => SearchAnalyzer::for_languages(languages),
+            CliSearchAnalyzer::Basic => SearchAnalyzer::Basic,
+            CliSearchAnalyzer::Folded => SearchAnalyzer::Folded,
+            CliSearchAnalyzer::Stemmed => SearchAnalyzer::Stemmed,
+            CliSearchAnalyzer::Cjk => SearchAnalyzer::Cjk,
+        }
+    }
+}
+
 #[derive(Parser)]
 #[command(
     author, 
@@ -126,11 +414,182 @@ pub struct Cli {
     #[cfg_attr(not(target_os = "macos"), arg(short, long, default_value_t = 1.0))]
     #[cfg_attr(target_os = "macos", arg(short, long, default_value_t = 0.5))] 
     pub fps: f64, // ! not crazy about this (inconsistent behaviour across platforms) see https://github.com/mediar-ai/screenpipe/issues/173
+
+    /// Capture a monitor less often while its screen doesn't change, down to --min-fps, and at --fps again
+    /// as soon as it does
+    #[arg(long, default_value_t = false)]
+    pub adaptive_fps: bool,
+
+    /// Slowest a still screen is captured at with --adaptive-fps
+    #[arg(long, default_value_t = 0.2)]
+    pub min_fps: f64,
+
+    /// Detect meetings (zoom, meet, teams... calls) from their windows and from speech heard both on a
+    /// microphone and on the speakers, capture at --meeting-fps during them, have deepgram tell speakers
+    /// apart, and list them with what was captured during each with /meetings
+    #[arg(long, default_value_t = false)]
+    pub detect_meetings: bool,
+
+    /// Frames per second captured at least during meetings, with --detect-meetings
+    #[arg(long, default_value_t = 2.0)]
+    pub meeting_fps: f64,
+
+    /// OCR and store frames identical to the last frame OCRed. By default these are stored as a
+    /// reference to it
+    #[arg(long, default_value_t = false)]
+    pub disable_frame_dedup: bool,
+
+    /// OCR windows whole every time. By default only the rows of a window that changed since it was last OCRed are
+    /// read again, the text of the rest is kept, much faster on mostly static screens like editors
+    #[arg(long, default_value_t = false)]
+    pub disable_changed_region_ocr: bool,
+
+    /// Preprocessing of images before they are OCRed. auto inverts dark themed windows and stretches the contrast
+    /// of dull ones, binarize also makes them black and white, off OCRs them as captured
+    #[arg(long, value_enum, default_value_t = CliOcrPreprocessing::Auto)]
+    pub ocr_preprocessing: CliOcrPreprocessing,
+
+    /// Codec of the video chunks
+    #[arg(long, value_enum, default_value_t = CliVideoCodec::Hevc)]
+    pub video_codec: CliVideoCodec,
+
+    /// Encoder of the video chunks: auto uses VideoToolbox on macOS, and NVENC or QuickSync when the gpu
+    /// has it, libx264/libx265 otherwise
+    #[arg(long, value_enum, default_value_t = CliVideoEncoder::Auto)]
+    pub video_encoder: CliVideoEncoder,
+
+    /// Re-encode video chunks to --video-archive-codec once their last frame is this many days old, to
+    /// reclaim disk. Frames stay where they were in the chunks
+    #[arg(long)]
+    pub video_archive_days: Option<u64>,
+
+    /// Codec old video chunks are re-encoded to, with the encoder of --video-encoder when it has it
+    #[arg(long, value_enum, default_value_t = CliVideoCodec::Av1)]
+    pub video_archive_codec: CliVideoCodec,
+
+    /// Read the labels, text and positions of the elements of the focused window from the accessibility
+    /// tree of the OS alongside OCR, searchable with /ui/elements. Needs the accessibility permission on
+    /// macOS, not supported on Linux yet
+    #[arg(long, default_value_t = false)]
+    pub enable_accessibility_tree: bool,
+
+    /// Record clicks, with the element clicked, and how many keys were pressed and how much was scrolled,
+    /// never the keys typed. Searchable with /input/events. Needs the accessibility and input monitoring
+    /// permissions on macOS
+    #[arg(long, default_value_t = false)]
+    pub enable_input_events: bool,
+
+    /// Store where the pointer was and the text selected in the focused window with every frame, for replays
+    /// to show them with /frames/:id/cursor and to search by the text selected. Nothing is drawn on the
+    /// frames. The selection needs the accessibility permission on macOS and isn't read on Linux yet
+    #[arg(long, default_value_t = false)]
+    pub capture_cursor: bool,
+
+    /// Compute a CLIP embedding of the image of each frame stored, to find frames from a description
+    /// of what was on screen with /search/visual, like "a bar chart", even without text in them. The
+    /// model is downloaded on first use
+    #[arg(long, default_value_t = false)]
+    pub enable_frame_embeddings: bool,
+
+    /// Embed the text read on screen and the transcriptions in the background, to search them by
+    /// meaning as well as by their words with /search/semantic. Uses nomic-embed-text in a local
+    /// ollama unless --embedding-api-url is set
+    #[arg(long, default_value_t = false)]
+    pub enable_text_embeddings: bool,
+
+    /// OpenAI compatible embeddings api to embed text with instead of ollama, like
+    /// https://api.openai.com/v1/embeddings, authenticated with --openai-api-key
+    #[arg(long)]
+    pub embedding_api_url: Option<String>,
+
+    /// Model text is embedded with, nomic-embed-text with ollama and text-embedding-3-small with
+    /// an api by default. Text embedded with another model isn't found by its meaning until
+    /// embedded again
+    #[arg(long)]
+    pub embedding_model: Option<String>,
+
+    /// Pause the capture of the screen and of audio after this many minutes without a keypress or
+    /// a mouse move, until the next one. Needs xprintidle on linux
+    #[arg(long)]
+    pub pause_on_idle: Option<u64>,
+
+    /// Pause the capture of the screen and of audio while the screen is locked or the screensaver
+    /// is on. The pauses are listed by /pauses
+    #[arg(long, default_value_t = false)]
+    pub pause_on_lock: bool,
     
     /// Audio chunk duration in seconds
     #[arg(short = 'd', long, default_value_t = 30)]
     pub audio_chunk_duration: u64,
 
+    /// Seconds at the end of each audio chunk sent again at the start of the next one, so words cut
+    /// at the seam are transcribed whole and merged into a single transcript. 0 disables it
+    #[arg(long, default_value_t = 2.0)]
+    pub audio_chunk_overlap_secs: f64,
+
+    /// Codec of the audio files kept of each chunk: aac (mp4), m4a for apple devices, opus (ogg), much
+    /// smaller for speech, or flac for lossless archival
+    #[arg(long, value_enum, default_value_t = CliAudioCodec::Aac)]
+    pub audio_codec: CliAudioCodec,
+
+    /// Bitrate of opus audio files in kbps
+    #[arg(long, default_value_t = DEFAULT_OPUS_BITRATE_KBPS)]
+    pub audio_bitrate_kbps: u32,
+
+    /// Also keep the audio of each device in long files (see --audio-archive-file-minutes) with a manifest of the
+    /// time and byte range of each chunk, so a few minutes can be played back without decoding a whole day
+    #[arg(long, default_value_t = false)]
+    pub audio_archive: bool,
+
+    /// Minutes of audio in each file of the audio archive
+    #[arg(long, default_value_t = 60)]
+    pub audio_archive_file_minutes: u64,
+
+    /// Re-encode audio archive files to low bitrate opus (see --audio-archive-recompress-bitrate-kbps) once their
+    /// audio is this many days old, to reclaim disk. Transcripts are kept as they are
+    #[arg(long)]
+    pub audio_archive_recompress_days: Option<u64>,
+
+    /// Opus bitrate old audio archive files are re-encoded to
+    #[arg(long, default_value_t = 12)]
+    pub audio_archive_recompress_bitrate_kbps: u32,
+
+    /// Transcribe speech heard on several devices at once (the mic catching a meeting played by the speakers, and the
+    /// loopback device) once, from the device hearing it the cleanest, by comparing audio fingerprints
+    #[arg(long, default_value_t = false)]
+    pub suppress_duplicate_audio: bool,
+
+    /// Learn the steady noise of each device (fans, air conditioning) from its silences and subtract it from the
+    /// audio before it is transcribed and encoded
+    #[arg(long, default_value_t = false)]
+    pub noise_gate: bool,
+
+    /// Most the --noise-gate attenuates a frequency by, in db
+    #[arg(long, default_value_t = 12.0)]
+    pub noise_gate_reduction_db: f32,
+
+    /// Remove the DC offset of audio before it is transcribed and encoded, cheap mics and sound cards add one that
+    /// throws off the VAD
+    #[arg(long, default_value_t = false)]
+    pub remove_dc_offset: bool,
+
+    /// Filter out rumble and hum under this frequency (e.g. 80) before audio is transcribed and encoded
+    #[arg(long)]
+    pub high_pass_hz: Option<f32>,
+
+    /// Normalize the loudness of audio to --loudness-target-lufs (EBU R128) before it is transcribed and encoded, so
+    /// quiet laptop mics and loud loopback devices transcribe and play back alike
+    #[arg(long, default_value_t = false)]
+    pub normalize_loudness: bool,
+
+    /// Integrated loudness audio is normalized to, in LUFS
+    #[arg(long, default_value_t = -23.0, allow_hyphen_values = true)]
+    pub loudness_target_lufs: f64,
+
+    /// True peak ceiling of normalized audio, in dBTP
+    #[arg(long, default_value_t = -1.0, allow_hyphen_values = true)]
+    pub loudness_true_peak_dbtp: f64,
+
     /// Port to run the server on
     #[arg(short = 'p', long, default_value_t = 3030)]
     pub port: u16,
@@ -139,7 +598,8 @@ pub struct Cli {
     #[arg(long, default_value_t = false)]
     pub disable_audio: bool,
 
-    /// Audio devices to use (can be specified multiple times)
+    /// Audio devices to use (can be specified multiple times). Append a host in brackets to pick
+    /// the backend explicitly, e.g. "Focusrite USB (input) [ASIO]"
     #[arg(short = 'i', long)]
     pub audio_device: Vec<String>,
 
@@ -160,13 +620,153 @@ pub struct Cli {
     /// WhisperTiny is a local, lightweight transcription model, recommended for high data privacy.
     /// WhisperDistilLargeV3 is a local, lightweight transcription model (-a whisper-large), recommended for higher quality audio than tiny.
     /// WhisperLargeV3Turbo is a local, lightweight transcription model (-a whisper-large-v3-turbo), recommended for higher quality audio than tiny.
+    /// WhisperCpp runs a quantized large-v3-turbo on whisper.cpp (-a whisper-cpp), much faster than the others on machines without a GPU, requires building with the whisper-cpp feature.
+    /// FasterWhisper runs large-v3-turbo on CTranslate2 (-a faster-whisper), requires building with the ctranslate2 feature.
+    /// WhisperOnnx runs large-v3-turbo on ONNX Runtime (-a whisper-onnx), on the hardware picked with --onnx-execution-provider.
+    /// Vosk runs small vosk models (-a vosk), for very low-end hardware at lower accuracy, requires building with the vosk feature.
+    /// WhisperLargeV3 is the full large-v3 model (-a whisper-large-v3), most accurate but slowest.
+    /// WhisperDistilSmallEn is a small english-only model (-a whisper-distil-small-en), faster than tiny at better accuracy for english.
+    /// Quantized models trade a bit of accuracy for 3-4x less memory: whisper-tiny-q8 (int8), whisper-large-v3-q5 (5 bit) and whisper-large-v3-turbo-q8 (int8), the last two on whisper.cpp, only in builds with the whisper-cpp feature.
+    /// AssemblyAI is a cloud-based transcription service with speaker labels (-a assemblyai), needs --assemblyai-api-key.
+    /// AzureSpeech is Azure's cloud speech to text with diarization (-a azure-speech), needs --azure-speech-key and --azure-speech-region.
+    /// OpenAIWhisper and GroqWhisper are hosted whisper models (-a openai-whisper, -a groq-whisper), need --openai-api-key or --groq-api-key.
     #[arg(short = 'a', long, value_enum, default_value_t = CliAudioTranscriptionEngine::WhisperLargeV3Turbo)]
     pub audio_transcription_engine: CliAudioTranscriptionEngine,
 
-    /// Enable realtime audio transcription
+    /// Engines tried in order when the audio transcription engine fails, e.g. -a deepgram --audio-transcription-fallback assemblyai --audio-transcription-fallback whisper-tiny
+    /// (can be specified multiple times). Cloud engines are tried up to the first local one, which is the model loaded and always transcribes in the end.
+    /// Engines failing 3 times in a row are skipped for a minute
+    #[arg(long, value_enum)]
+    pub audio_transcription_fallback: Vec<CliAudioTranscriptionEngine>,
+
+    /// Times a cloud transcription engine is tried when it can't be reached, rate limits or has an outage,
+    /// waiting longer between each, before the next engine of the chain takes over
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    pub transcription_retries: u32,
+
+    /// Keep the audio on disk while no cloud transcription engine can be reached, and transcribe it
+    /// once connectivity returns instead of falling back to the local model
+    #[arg(long, default_value_t = false)]
+    pub offline_transcription_queue: bool,
+
+    /// Size of the audio waiting in the offline transcription queue above which the local model takes over
+    #[arg(long, default_value_t = 500)]
+    pub offline_transcription_queue_max_mb: u64,
+
+    /// Don't keep the audio waiting to be transcribed on disk. By default it is, so that a restart after a crash
+    /// transcribes whatever was recorded but not transcribed yet
+    #[arg(long, default_value_t = false)]
+    pub disable_transcription_journal: bool,
+
+    /// When transcription falls behind, live audio goes ahead of audio recovered after a crash or imported from files.
+    /// After this many batches of live audio in a row, a batch of the backfill gets its turn
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_realtime_streak: u32,
+
+    /// Seconds after which audio waiting in the backfill goes ahead of live audio
+    #[arg(long, default_value_t = 120)]
+    pub max_backfill_wait_secs: u64,
+
+    /// ONNX Runtime execution provider of the whisper-onnx engine, falls back to cpu when unavailable.
+    /// auto picks directml/cuda on windows, coreml on macos and cuda on linux
+    #[arg(long, value_enum, default_value_t = CliExecutionProvider::Auto)]
+    pub onnx_execution_provider: CliExecutionProvider,
+
+    /// Device the local whisper models run on: auto, cpu, metal, cuda or cuda:<n> for a specific GPU.
+    /// Falls back to the cpu when the GPU isn't available, the device in use is reported by /health
+    #[arg(long, default_value = "auto")]
+    pub whisper_device: DevicePreference,
+
+    /// Local whisper model to use instead of the preset of the transcription engine: a whisper.cpp ggml .bin file,
+    /// or a directory with config.json, tokenizer.json and safetensors/gguf weights (e.g. a fine-tuned model).
+    /// With -a vosk, an unpacked vosk model directory
+    #[arg(long, value_hint = ValueHint::AnyPath)]
+    pub whisper_model_path: Option<PathBuf>,
+
+    /// Speech segments transcribed together when several devices have audio waiting, in one forward pass
+    /// per token. Only whisper models running on candle on a GPU batch, 1 transcribes one segment at a time
+    #[arg(long, default_value_t = 4)]
+    pub whisper_batch_size: usize,
+
+    /// Unload the transcription model after this many minutes without speech to transcribe, freeing its RAM/VRAM.
+    /// It is loaded again when speech comes in. Kept loaded when not set
+    #[arg(long)]
+    pub whisper_unload_after_idle: Option<u64>,
+
+    /// Transcribe speech in the language it is spoken, or translate it to english text.
+    /// Not supported by deepgram and faster-whisper, which always transcribe
+    #[arg(long, value_enum, default_value_t = CliWhisperTask::Transcribe)]
+    pub whisper_task: CliWhisperTask,
+
+    /// Audio devices whose speech is translated to english whatever --whisper-task is (can be specified multiple times)
+    #[arg(long)]
+    pub translate_audio_device: Vec<String>,
+
+    /// Names, jargon or product terms that transcription should spell right, separated by commas.
+    /// Used as the whisper initial prompt and as deepgram keywords
+    #[arg(long)]
+    pub transcription_prompt: Option<String>,
+
+    /// Transcription prompt of a single audio device, replacing --transcription-prompt for it,
+    /// e.g. "MacBook Pro Microphone (input)=Kubernetes, Grafana" (can be specified multiple times)
+    #[arg(long)]
+    pub audio_device_prompt: Vec<String>,
+
+    /// Phrase sending a keyword_detected event as soon as it is transcribed, e.g. "action item"
+    /// or a name (can be specified multiple times)
+    #[arg(long)]
+    pub keyword_trigger: Vec<String>,
+
+    /// Seconds of audio during which a keyword heard again on the same device isn't reported again
+    #[arg(long, default_value_t = 30)]
+    pub keyword_trigger_cooldown_secs: u64,
+
+    /// Beams kept by the local whisper engines' beam search, 1 decodes greedily (faster)
+    #[arg(long, default_value_t = 1)]
+    pub whisper_beam_size: usize,
+
+    /// Comma separated temperatures whisper decodes at in turn, moving on when a decoding is too repetitive
+    /// or unsure. Defaults to 0,0.2,0.4,0.6,0.8,1
+    #[arg(long, value_delimiter = ',')]
+    pub whisper_temperatures: Vec<f64>,
+
+    /// Gzip compression ratio above which a decoding is considered repetitive and retried
+    #[arg(long, default_value_t = 2.4)]
+    pub whisper_compression_ratio_threshold: f64,
+
+    /// Average log probability below which a decoding is retried
+    #[arg(long, default_value_t = -1.0, allow_hyphen_values = true)]
+    pub whisper_logprob_threshold: f64,
+
+    /// No speech probability above which an unsure decoding is dropped as silence
+    #[arg(long, default_value_t = 0.6)]
+    pub whisper_no_speech_threshold: f64,
+
+    /// What to do with transcripts that look made up: repetition loops, phrases like "thanks for watching"
+    /// and segments the VAD found no speech in. Flag keeps them, marked in search results; drop collapses
+    /// loops and discards the rest before they are stored
+    #[arg(long, value_enum, default_value_t = CliHallucinationFilter::Drop)]
+    pub hallucination_filter: CliHallucinationFilter,
+
+    /// Classify audio chunks as speech, music, typing, notification sounds or silence with a small
+    /// audio tagging model (YAMNet), labeling their transcriptions. Skip-music also doesn't transcribe
+    /// chunks of music without speech
+    #[arg(long, value_enum, default_value_t = CliAudioTagging::Off)]
+    pub audio_tagging: CliAudioTagging,
+
+    /// Enable realtime audio transcription (live captions with partial results): deepgram's websocket
+    /// with sub-second interim results when it is the transcription engine, sliding window whisper otherwise
     #[arg(long, default_value_t = false)]
     pub enable_realtime_audio_transcription: bool,
 
+    /// Capture audio with small (~10ms) buffers for lower realtime transcription latency, at the cost of more wakeups
+    #[arg(long, default_value_t = false)]
+    pub low_latency_audio: bool,
+
+    /// Audio capture buffer size in frames, overrides --low-latency-audio
+    #[arg(long)]
+    pub audio_buffer_frames: Option<u32>,
+
     /// OCR engine to use.
     /// AppleNative is the default local OCR engine for macOS.
     /// WindowsNative is a local OCR engine for Windows.
@@ -190,19 +790,76 @@ pub struct Cli {
     #[arg(short = 'm', long)]
     pub monitor_id: Vec<u32>,
 
+    /// Settings of a single monitor: "off" to not record it, "fps=<fps>", "ocr-scale=<scale>", "video-scale=<scale>"
+    /// and an OCR engine, e.g. "2=fps=0.2,tesseract" for a secondary monitor that changes rarely (can be specified
+    /// multiple times)
+    #[arg(long)]
+    pub monitor_config: Vec<String>,
+
+    /// Shrink the frames OCRed, by a factor like 0.5 or to a max width / height like 1920px. The text of 5K displays
+    /// is read as well at half the size, much faster
+    #[arg(long, default_value = "full")]
+    pub ocr_scale: FrameScale,
+
+    /// Shrink the frames stored in the videos, by a factor like 0.5 or to a max width / height like 1920px, they are
+    /// OCRed before being shrunk
+    #[arg(long, default_value = "full")]
+    pub video_scale: FrameScale,
+
+    /// Languages to support. Transcription is forced to a single language, or restricted to the languages given
     #[arg(short = 'l', long, value_enum)]
     pub language: Vec<Language>,
 
+    /// Only transcribe speech in these languages: the language of each segment is identified with the local
+    /// whisper model first, segments clearly spoken in another one are skipped (can be specified multiple times)
+    #[arg(long, value_enum)]
+    pub language_filter: Vec<Language>,
+
+    /// Languages of the text on screen, the most likely first, when they differ from --language
+    /// (can be specified multiple times)
+    #[arg(long, value_enum)]
+    pub ocr_language: Vec<Language>,
+
+    /// Detect the language of the text on screen: among the OCR languages with apple-native,
+    /// among every language installed with tesseract when none is given. Slower
+    #[arg(long, default_value_t = false)]
+    pub ocr_language_detection: bool,
+
+    /// Drop the words OCR engines are less confident about, between 0 and 1. Apple native only
+    /// rates whole lines, windows native doesn't rate them
+    #[arg(long, default_value_t = 0.0)]
+    pub ocr_min_confidence: f64,
+
+    /// Keep the strings OCR reads in icons, gradients and borders, like "|||" or "lIlIl"
+    #[arg(long, default_value_t = false)]
+    pub disable_ocr_junk_filter: bool,
+
+    /// Search index analyzer: diacritics folding, stemming and stop words, or CJK substring matching
+    /// (queries under 3 characters scan the text instead of the index). Changing it rebuilds the
+    /// search index in the background at startup, in a single transaction rather than a resumable
+    /// job: a rebuild interrupted by a shutdown starts over at the next start
+    #[arg(long, value_enum, default_value_t = CliSearchAnalyzer::Basic)]
+    pub search_analyzer: CliSearchAnalyzer,
+
     /// Enable PII removal from OCR text property that is saved to db and returned in search results
     #[arg(long, default_value_t = false)]
     pub use_pii_removal: bool,
 
+    /// Remove PII from transcripts too, before they are saved to db
+    #[arg(long, default_value_t = false)]
+    pub pii_removal_transcripts: bool,
+
+    /// Also remove the names of people from the text PII is removed from, found with a small local NER model
+    /// downloaded on first use
+    #[arg(long, default_value_t = false)]
+    pub pii_removal_names: bool,
+
     /// Disable vision recording
     #[arg(long, default_value_t = false)]
     pub disable_vision: bool,
 
     /// VAD engine to use for speech detection
-    #[arg(long, value_enum, default_value_t = CliVadEngine::Silero)] // Silero or WebRtc
+    #[arg(long, value_enum, default_value_t = CliVadEngine::Silero)] // Silero, WebRtc, Energy or WebRtcApm
     pub vad_engine: CliVadEngine,
 
     /// List of windows to ignore (by title) for screen recording - we use contains to match, example:
@@ -217,6 +874,40 @@ pub struct Cli {
     #[arg(long)]
     pub included_windows: Vec<String>,
 
+    /// Only record the window whose app name or title contains this, following it as it moves between
+    /// monitors, e.g. --capture-window "Code". The rest of the screen is recorded black and not OCRed
+    #[arg(long)]
+    pub capture_window: Option<String>,
+
+    /// Apps whose focus pauses screen recording, by bundle id (macOS), process name or name, e.g.
+    /// --ignored-apps "com.1password.1password" --ignored-apps "KeePassXC". Their windows are never captured
+    #[arg(long)]
+    pub ignored_apps: Vec<String>,
+
+    /// Only record the screen while one of these apps is focused, by bundle id (macOS), process name or name
+    #[arg(long)]
+    pub included_apps: Vec<String>,
+
+    /// Blank the windows of password managers and banking sites in the frames recorded, and don't OCR them
+    #[arg(long, default_value_t = false)]
+    pub blank_sensitive_windows: bool,
+
+    /// Windows to blank in the frames recorded and not OCR, by app name or title - we use contains to match,
+    /// e.g. --sensitive-windows "Signal" --sensitive-windows "Private Browsing"
+    #[arg(long)]
+    pub sensitive_windows: Vec<String>,
+
+    /// Record the private (incognito) windows of browsers. By default they are blanked in the frames recorded
+    /// and never OCRed, whether they are told apart by their title, the companion extension or the browser
+    #[arg(long, default_value_t = false)]
+    pub capture_private_windows: bool,
+
+    /// Folders whose new screenshots, screen recordings and audio files are OCRed / transcribed into the timeline,
+    /// as "path" or "label=path", the label being what the content is attributed to, example:
+    /// --watch-folder "OBS=~/Videos/OBS" --watch-folder ~/Desktop
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    pub watch_folder: Vec<WatchFolder>,
+
     /// Video chunk duration in seconds
     #[arg(long, default_value_t = 60)]
     pub video_chunk_duration: u64,
@@ -225,6 +916,26 @@ pub struct Cli {
     #[arg(long = "deepgram-api-key")]
     pub deepgram_api_key: Option<String>,
 
+    /// AssemblyAI API key, used by the assemblyai audio transcription engine
+    #[arg(long = "assemblyai-api-key")]
+    pub assemblyai_api_key: Option<String>,
+
+    /// Key of the Azure speech resource, used by the azure-speech audio transcription engine
+    #[arg(long = "azure-speech-key", requires = "azure_speech_region")]
+    pub azure_speech_key: Option<String>,
+
+    /// Region of the Azure speech resource, e.g. eastus
+    #[arg(long = "azure-speech-region")]
+    pub azure_speech_region: Option<String>,
+
+    /// OpenAI API key, used by the openai-whisper audio transcription engine
+    #[arg(long = "openai-api-key")]
+    pub openai_api_key: Option<String>,
+
+    /// Groq API key, used by the groq-whisper audio transcription engine
+    #[arg(long = "groq-api-key")]
+    pub groq_api_key: Option<String>,
+
     /// PID to watch for auto-destruction. If provided, screenpipe will stop when this PID is no longer running.
     #[arg(long)]
     pub auto_destruct_pid: Option<u32>,
@@ -233,6 +944,47 @@ pub struct Cli {
     #[arg(long, value_enum, default_value_t = CliVadSensitivity::High)]
     pub vad_sensitivity: CliVadSensitivity,
 
+    /// Silero VAD tuning: aggressive only keeps clear, sustained speech (noisy devices), sensitive keeps quiet
+    /// and short speech too. Without it the speech threshold follows --vad-sensitivity
+    #[arg(long, value_enum)]
+    pub vad_preset: Option<CliVadPreset>,
+
+    /// Speech probability (0-1) above which the Silero VAD considers audio speech, overriding the preset
+    #[arg(long)]
+    pub vad_threshold: Option<f32>,
+
+    /// Milliseconds speech has to last to be considered speech, overriding the preset
+    #[arg(long)]
+    pub vad_min_speech_ms: Option<u64>,
+
+    /// Milliseconds of silence ending speech, shorter pauses are part of it, overriding the preset
+    #[arg(long)]
+    pub vad_min_silence_ms: Option<u64>,
+
+    /// Milliseconds of audio still considered speech after it ends, overriding the preset
+    #[arg(long)]
+    pub vad_padding_ms: Option<u64>,
+
+    /// VAD of a single audio device, replacing --vad-engine, --vad-sensitivity and the tuning for it with
+    /// any of an engine, a sensitivity and a preset, e.g. "Display 1 (output)=silero,aggressive" for a noisy
+    /// loopback device or "Headset Microphone (input)=high,sensitive" (can be specified multiple times)
+    #[arg(long)]
+    pub audio_device_vad: Vec<String>,
+
+    /// Only send the speech the VAD hears to transcription instead of every --audio-chunk-duration of audio,
+    /// each stretch of speech as a chunk of at most that duration. Much cheaper on mostly quiet devices
+    #[arg(long, default_value_t = false)]
+    pub vad_gated_chunking: bool,
+
+    /// Milliseconds of audio kept before speech when a segment is opened on it, so word onsets aren't cut.
+    /// Applies to transcribed segments and to the speech sent with --vad-gated-chunking
+    #[arg(long, default_value_t = 200)]
+    pub vad_pre_roll_ms: u64,
+
+    /// Milliseconds of audio kept after speech when a segment is closed, so trailing sounds aren't cut
+    #[arg(long, default_value_t = 300)]
+    pub vad_hangover_ms: u64,
+
     /// Disable telemetry
     #[arg(long, default_value_t = false)]
     pub disable_telemetry: bool,
@@ -258,6 +1010,10 @@ pub struct Cli {
     #[arg(long, default_value_t = false)]
     pub capture_unfocused_windows: bool,
 
+    /// Allow saving user-approved (audio, corrected transcript) and (frame, corrected OCR) pairs to a local evaluation dataset
+    #[arg(long, default_value_t = false)]
+    pub enable_eval_dataset: bool,
+
     #[command(subcommand)]
     pub command: Option<Command>,
 
@@ -267,13 +1023,333 @@ pub struct Cli {
 
 impl Cli {
     pub fn unique_languages(&self) -> Result<Vec<Language>, String> {
-        let mut unique_langs = std::collections::HashSet::new();
+        // in the order given, the first one is the most likely for OCR
+        let mut unique_langs: Vec<Language> = Vec::new();
         for lang in &self.language {
-            if !unique_langs.insert(lang.clone()) {
-                // continue don't care
+            if !unique_langs.contains(lang) {
+                unique_langs.push(lang.clone());
+            }
+        }
+        Ok(unique_langs)
+    }
+    pub fn model_options(&self) -> ModelOptions {
+        ModelOptions {
+            device: self.whisper_device.clone(),
+            source: self
+                .whisper_model_path
+                .clone()
+                .map(ModelSource::Path)
+                .unwrap_or_default(),
+            batch_size: self.whisper_batch_size,
+            unload_after_idle: self
+                .whisper_unload_after_idle
+                .map(|minutes| Duration::from_secs(minutes * 60)),
+        }
+    }
+    pub fn embedding_backend(&self) -> EmbeddingBackend {
+        EmbeddingBackend::new(
+            self.embedding_api_url.clone(),
+            self.embedding_model.clone(),
+            self.openai_api_key.clone(),
+        )
+    }
+    pub fn decoding_options(&self) -> DecodingOptions {
+        let defaults = DecodingOptions::default();
+        DecodingOptions {
+            beam_size: self.whisper_beam_size.max(1),
+            temperatures: if self.whisper_temperatures.is_empty() {
+                defaults.temperatures
+            } else {
+                self.whisper_temperatures.clone()
+            },
+            compression_ratio_threshold: self.whisper_compression_ratio_threshold,
+            logprob_threshold: self.whisper_logprob_threshold,
+            no_speech_threshold: self.whisper_no_speech_threshold,
+        }
+    }
+    pub fn vad_config(&self) -> VadConfig {
+        let mut config = match &self.vad_preset {
+            Some(preset) => VadPreset::from(preset.clone()).config(),
+            None => VadConfig::default(),
+        };
+        if let Some(threshold) = self.vad_threshold {
+            config.threshold = Some(threshold.clamp(0.0, 1.0));
+        }
+        if let Some(ms) = self.vad_min_speech_ms {
+            config.min_speech = Duration::from_millis(ms);
+        }
+        if let Some(ms) = self.vad_min_silence_ms {
+            config.min_silence = Duration::from_millis(ms);
+        }
+        if let Some(ms) = self.vad_padding_ms {
+            config.padding = Duration::from_millis(ms);
+        }
+        config
+    }
+    pub fn vad_settings(&self) -> VadSettings {
+        VadSettings {
+            engine: self.vad_engine.clone().into(),
+            sensitivity: self.vad_sensitivity.clone().into(),
+            config: self.vad_config(),
+        }
+    }
+    /// The VAD of each device given with --audio-device-vad
+    pub fn device_vad(&self) -> anyhow::Result<HashMap<AudioDevice, VadSettings>> {
+        let mut devices = HashMap::new();
+        for device_vad in &self.audio_device_vad {
+            let (d, settings) = device_vad.rsplit_once('=').ok_or_else(|| {
+                anyhow::anyhow!("expected <device>=<vad settings>, got {}", device_vad)
+            })?;
+            let vad = devices
+                .entry(parse_audio_device(d.trim())?)
+                .or_insert_with(|| self.vad_settings());
+            for setting in settings.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                if let Ok(engine) = CliVadEngine::from_str(setting, true) {
+                    vad.engine = engine.into();
+                } else if let Ok(sensitivity) = CliVadSensitivity::from_str(setting, true) {
+                    vad.sensitivity = sensitivity.into();
+                } else if let Ok(preset) = CliVadPreset::from_str(setting, true) {
+                    vad.config = VadPreset::from(preset).config();
+                } else {
+                    return Err(anyhow::anyhow!(
+                        "unknown vad setting {:?} for {}, expected an engine, a sensitivity \
+                         or a preset",
+                        setting,
+                        d.trim()
+                    ));
+                }
+            }
+        }
+        Ok(devices)
+    }
+    /// The settings of each monitor given with --monitor-config
+    pub fn monitor_configs(&self) -> anyhow::Result<HashMap<u32, MonitorConfig>> {
+        let mut monitors = HashMap::new();
+        for monitor_config in &self.monitor_config {
+            let (id, settings) = monitor_config.split_once('=').ok_or_else(|| {
+                anyhow::anyhow!("expected <monitor id>=<settings>, got {}", monitor_config)
+            })?;
+            let id: u32 = id
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid monitor id {:?}", id.trim()))?;
+            let config: &mut MonitorConfig = monitors.entry(id).or_default();
+            for setting in settings.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                if setting.eq_ignore_ascii_case("off") {
+                    config.disabled = true;
+                } else if setting.eq_ignore_ascii_case("on") {
+                    config.disabled = false;
+                } else if let Some(fps) = setting.strip_prefix("fps=") {
+                    match fps.trim().parse::<f64>() {
+                        Ok(fps) if fps.is_finite() && fps > 0.0 => config.fps = Some(fps),
+                        _ => {
+                            return Err(anyhow::anyhow!("invalid fps {:?} for monitor {}", fps, id))
+                        }
+                    }
+                } else if let Some(scale) = setting.strip_prefix("ocr-scale=") {
+                    config.ocr_scale = Some(
+                        scale
+                            .parse()
+                            .map_err(|e| anyhow::anyhow!("{} for monitor {}", e, id))?,
+                    );
+                } else if let Some(scale) = setting.strip_prefix("video-scale=") {
+                    config.video_scale = Some(
+                        scale
+                            .parse()
+                            .map_err(|e| anyhow::anyhow!("{} for monitor {}", e, id))?,
+                    );
+                } else if let Ok(engine) = CliOcrEngine::from_str(setting, true) {
+                    config.ocr_engine = Some(engine.into());
+                } else {
+                    return Err(anyhow::anyhow!(
+                        "unknown setting {:?} for monitor {}, expected off, on, fps=<fps>, \
+                         ocr-scale=<scale>, video-scale=<scale> or an ocr engine",
+                        setting,
+                        id
+                    ));
+                }
+            }
+        }
+        Ok(monitors)
+    }
+    pub fn adaptive_fps(&self) -> Option<AdaptiveFpsConfig> {
+        (self.adaptive_fps && self.min_fps.is_finite() && self.min_fps > 0.0).then(|| AdaptiveFpsConfig {
+            min_fps: self.min_fps,
+            ..Default::default()
+        })
+    }
+    pub fn ocr_language_options(&self) -> OcrLanguageOptions {
+        let mut languages: Vec<Language> = Vec::new();
+        for lang in &self.ocr_language {
+            if !languages.contains(lang) {
+                languages.push(lang.clone());
             }
         }
-        Ok(unique_langs.into_iter().collect())
+        OcrLanguageOptions {
+            languages,
+            auto_detect: self.ocr_language_detection,
+        }
+    }
+    pub fn ocr_filter_config(&self) -> OcrFilterConfig {
+        OcrFilterConfig {
+            min_confidence: self.ocr_min_confidence,
+            junk: !self.disable_ocr_junk_filter,
+        }
+    }
+    pub fn redaction_options(&self) -> RedactionOptions {
+        RedactionOptions {
+            transcripts: self.pii_removal_transcripts,
+            names: self.pii_removal_names,
+        }
+    }
+    pub fn app_filters(&self) -> AppFilters {
+        AppFilters::new(&self.ignored_apps, &self.included_apps)
+    }
+    pub fn sensitive_windows(&self) -> SensitiveWindows {
+        SensitiveWindows::new(self.blank_sensitive_windows, &self.sensitive_windows)
+            .with_private_windows(!self.capture_private_windows)
+    }
+    pub fn segment_padding(&self) -> SegmentPadding {
+        SegmentPadding {
+            pre_roll: Duration::from_millis(self.vad_pre_roll_ms),
+            hangover: Duration::from_millis(self.vad_hangover_ms),
+        }
+    }
+    pub fn audio_codec(&self) -> AudioCodec {
+        match self.audio_codec {
+            CliAudioCodec::Aac => AudioCodec::Aac,
+            CliAudioCodec::Opus => AudioCodec::Opus {
+                bitrate_kbps: self.audio_bitrate_kbps,
+            },
+            CliAudioCodec::Flac => AudioCodec::Flac,
+            CliAudioCodec::M4a => AudioCodec::M4a,
+        }
+    }
+    pub fn recording_options(&self) -> RecordingOptions {
+        RecordingOptions {
+            vad_gating: self.vad_gated_chunking.then(|| VadGating {
+                vad: self.vad_settings(),
+                padding: self.segment_padding(),
+            }),
+            overlap: self.audio_chunk_overlap(),
+        }
+    }
+    pub fn audio_chunk_overlap(&self) -> Duration {
+        Duration::from_secs_f64(self.audio_chunk_overlap_secs.max(0.0))
+    }
+    pub fn transcription_options(
+        &self,
+        data_dir: &Path,
+    ) -> anyhow::Result<DeviceTranscriptionOptions> {
+        let default = TranscriptionOptions {
+            task: self.whisper_task.clone().into(),
+            prompt: self.transcription_prompt.clone(),
+            decoding: self.decoding_options(),
+            hallucination_filter: self.hallucination_filter.clone().into(),
+            api_keys: CloudApiKeys {
+                assemblyai: self.assemblyai_api_key.clone(),
+                azure_speech: self
+                    .azure_speech_key
+                    .clone()
+                    .zip(self.azure_speech_region.clone())
+                    .map(|(key, region)| AzureSpeechCredentials { key, region }),
+                openai: self.openai_api_key.clone(),
+                groq: self.groq_api_key.clone(),
+            },
+            fallback_engines: self
+                .audio_transcription_fallback
+                .iter()
+                .map(|engine| engine.resolve(&self.onnx_execution_provider))
+                .collect(),
+            retry: RetryPolicy {
+                max_attempts: self.transcription_retries,
+                ..Default::default()
+            },
+            offline_queue: self.offline_transcription_queue.then(|| OfflineQueueConfig {
+                dir: data_dir.join("data").join("transcription-queue"),
+                max_bytes: self.offline_transcription_queue_max_mb * 1024 * 1024,
+            }),
+            language_filter: (!self.language_filter.is_empty())
+                .then(|| LanguageFilter::new(self.language_filter.clone())),
+            // shared by every device, a phrase is spotted once per device whichever path heard it
+            keyword_spotter: (!self.keyword_trigger.is_empty()).then(|| {
+                KeywordSpotter::new(
+                    self.keyword_trigger.clone(),
+                    Duration::from_secs(self.keyword_trigger_cooldown_secs),
+                )
+            }),
+        };
+        let mut devices = HashMap::new();
+        for d in &self.translate_audio_device {
+            devices
+                .entry(parse_audio_device(d)?)
+                .or_insert_with(|| default.clone())
+                .task = Task::Translate;
+        }
+        for device_prompt in &self.audio_device_prompt {
+            let (d, prompt) = device_prompt.split_once('=').ok_or_else(|| {
+                anyhow::anyhow!("expected <device>=<prompt>, got {}", device_prompt)
+            })?;
+            devices
+                .entry(parse_audio_device(d.trim())?)
+                .or_insert_with(|| default.clone())
+                .prompt = Some(prompt.trim().to_string());
+        }
+        Ok(DeviceTranscriptionOptions {
+            default,
+            devices,
+            journal_dir: (!self.disable_transcription_journal)
+                .then(|| data_dir.join("data").join("transcription-journal")),
+            lane_limits: LaneLimits {
+                max_realtime_streak: self.max_realtime_streak,
+                max_backfill_wait: Duration::from_secs(self.max_backfill_wait_secs),
+                ..Default::default()
+            },
+            audio_tagging: self.audio_tagging.clone().into(),
+            vad_config: self.vad_config(),
+            segment_padding: self.segment_padding(),
+            device_vad: self.device_vad()?,
+            audio_codec: self.audio_codec(),
+            archive: self.audio_archive.then(|| ArchiveConfig {
+                dir: data_dir.join("data").join("audio-archive"),
+                file_duration: Duration::from_secs(self.audio_archive_file_minutes * 60),
+                codec: self.audio_codec(),
+                recompress: self.audio_archive_recompress_days.map(|days| Recompression {
+                    after: Duration::from_secs(days * 24 * 3600),
+                    codec: AudioCodec::Opus {
+                        bitrate_kbps: self.audio_archive_recompress_bitrate_kbps,
+                    },
+                }),
+            }),
+            audio_pipeline: self.audio_pipeline(),
+            duplicate_detection: self
+                .suppress_duplicate_audio
+                .then(DuplicateDetection::default),
+            chunk_overlap: self.audio_chunk_overlap(),
+            noise_gate: self.noise_gate.then(|| NoiseGate {
+                reduction_db: self.noise_gate_reduction_db,
+                ..Default::default()
+            }),
+        })
+    }
+
+    /// DC removal, high-pass then loudness normalization, the stages enabled
+    pub fn audio_pipeline(&self) -> AudioPipeline {
+        let mut pipeline = AudioPipeline::new();
+        if self.remove_dc_offset {
+            pipeline = pipeline.dc_removal();
+        }
+        if let Some(cutoff_hz) = self.high_pass_hz {
+            pipeline = pipeline.high_pass(cutoff_hz);
+        }
+        if self.normalize_loudness {
+            pipeline = pipeline.loudness(LoudnessNormalization {
+                target_lufs: self.loudness_target_lufs,
+                true_peak_dbtp: self.loudness_true_peak_dbtp,
+                ..Default::default()
+            });
+        }
+        pipeline
     }
     pub fn handle_completions(&self, shell: Shell) -> anyhow::Result<()> {
         let mut cmd = Self::command();
@@ -294,6 +1370,11 @@ pub enum Command {
         #[command(subcommand)]
         subcommand: VisionCommand,
     },
+    /// Downloaded model management commands
+    Model {
+        #[command(subcommand)]
+        subcommand: ModelCommand,
+    },
     /// Pipe management commands
     Pipe {
         #[command(subcommand)]
@@ -351,6 +1432,45 @@ pub enum AudioCommand {
         /// Output format
         #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
         output: OutputFormat,
+        /// Only list devices of this audio host (see `audio hosts`)
+        #[arg(long)]
+        host: Option<String>,
+    },
+    /// List available audio hosts (backends such as CoreAudio, WASAPI, ASIO)
+    Hosts {
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Transcribe an audio or video file to subtitles, with the transcription options of the main command
+    Subtitles {
+        /// Audio or video file to transcribe
+        #[arg(value_hint = ValueHint::FilePath)]
+        path: PathBuf,
+        /// Subtitle format
+        #[arg(short, long, value_enum, default_value_t = CliSubtitleFormat::Srt)]
+        format: CliSubtitleFormat,
+        /// Subtitle file to write. Default to the input file with the format's extension
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ModelCommand {
+    /// List downloaded models and their size on disk
+    List {
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Remove a downloaded model, it is downloaded again the next time it's needed
+    Remove {
+        /// Name of the model as shown by `model list`
+        name: String,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
     },
 }
 
@@ -462,3 +1582,18 @@ pub enum OutputFormat {
     Text,
     Json,
 }
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliSubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl From<CliSubtitleFormat> for SubtitleFormat {
+    fn from(format: CliSubtitleFormat) -> Self {
+        match format {
+            CliSubtitleFormat::Srt => SubtitleFormat::Srt,
+            CliSubtitleFormat::Vtt => SubtitleFormat::WebVtt,
+        }
+    }
+}
@@ -1,28 +1,62 @@
 use crate::cli::{CliVadEngine, CliVadSensitivity};
-use crate::db_types::Speaker;
+use crate::db_types::{FrameDetails, Speaker, TranscriptionDetails};
+use crate::now;
+use crate::redaction::{self, redact, redact_transcript};
+use crate::watch_folder::{FolderWatcher, WatchFolder};
 use crate::{DatabaseManager, VideoCapture};
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use image::DynamicImage;
 use dashmap::DashMap;
 use futures::future::join_all;
 use tracing::{debug, error, info, warn};
 use screenpipe_audio::vad_engine::VadSensitivity;
+use screenpipe_audio::whisper::ModelOptions;
 use screenpipe_audio::{
-    create_whisper_channel, record_and_transcribe, vad_engine::VadEngineEnum, AudioDevice,
-    AudioInput, AudioTranscriptionEngine, DeviceControl, TranscriptionResult,
+    create_whisper_channel, record_and_transcribe_with_options, vad_engine::VadEngineEnum,
+    AudioDevice, AudioInput, AudioTranscriptionEngine, DeviceControl, DeviceTranscriptionOptions,
+    RecordingOptions, TranscriptionResult,
 };
-use screenpipe_audio::{start_realtime_recording, AudioStream};
-use screenpipe_core::pii_removal::remove_pii;
+use screenpipe_audio::{speech_stats, start_realtime_recording, AudioStream, StreamOptions};
+use screenpipe_core::capture_pause::{self, PauseReason};
+use screenpipe_core::clock;
+use screenpipe_core::meeting;
 use screenpipe_core::Language;
-use screenpipe_vision::core::{RealtimeVisionEvent, WindowOcr};
+use screenpipe_vision::accessibility::AccessibilityTree;
+use screenpipe_vision::active_window::AppFilters;
+use screenpipe_vision::adaptive_fps::AdaptiveFpsConfig;
+use screenpipe_vision::clip;
+use screenpipe_vision::idle;
+use screenpipe_vision::core::{CaptureResult, RealtimeVisionEvent, WindowOcr};
+use screenpipe_vision::cursor::CursorOverlay;
+use screenpipe_vision::frame_scale::FrameScale;
+use screenpipe_vision::input_events::start_input_capture;
+use screenpipe_vision::monitor::MonitorConfig;
+use screenpipe_vision::ocr_layout::OcrLayout;
+use screenpipe_vision::ocr_structure::extract_structures;
+use screenpipe_vision::sensitive_windows::SensitiveWindows;
+use screenpipe_vision::video_call::{call_window, meeting_app_window};
 use screenpipe_vision::OcrEngine;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::runtime::Handle;
+use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
+/// Frames waiting to be embedded, the ones captured while it is full are not embedded
+const FRAME_EMBEDDING_QUEUE: usize = 8;
+/// How often whether the user is away is checked
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// How often whether a meeting goes on is checked
+const MEETING_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// How long a meeting goes on after nothing tells it anymore
+const MEETING_END_GRACE: Duration = Duration::from_secs(60);
+/// How recent speech heard on a device has to be for a conversation to go on
+const MEETING_SPEECH_WINDOW: Duration = Duration::from_secs(30);
+
 #[allow(clippy::too_many_arguments)]
 pub async fn start_continuous_recording(
     db: Arc<DatabaseManager>,
@@ -36,6 +70,9 @@ pub async fn start_continuous_recording(
     audio_transcription_engine: Arc<AudioTranscriptionEngine>,
     ocr_engine: Arc<OcrEngine>,
     monitor_ids: Vec<u32>,
+    monitor_configs: HashMap<u32, MonitorConfig>,
+    ocr_scale: FrameScale,
+    video_scale: FrameScale,
     use_pii_removal: bool,
     vision_disabled: bool,
     vad_engine: CliVadEngine,
@@ -43,25 +80,60 @@ pub async fn start_continuous_recording(
     audio_handle: &Handle,
     ignored_windows: &[String],
     include_windows: &[String],
+    capture_window: Option<String>,
+    app_filters: AppFilters,
+    sensitive_windows: SensitiveWindows,
     deepgram_api_key: Option<String>,
     vad_sensitivity: CliVadSensitivity,
     languages: Vec<Language>,
     capture_unfocused_windows: bool,
+    adaptive_fps: Option<AdaptiveFpsConfig>,
+    frame_dedup: bool,
+    accessibility_tree: bool,
+    frame_embeddings: bool,
     realtime_audio_devices: Vec<Arc<AudioDevice>>,
     realtime_audio_enabled: bool,
     realtime_vision_sender: Arc<tokio::sync::broadcast::Sender<RealtimeVisionEvent>>,
+    audio_stream_options: StreamOptions,
+    recording_options: RecordingOptions,
+    watch_folders: Vec<WatchFolder>,
+    model_options: ModelOptions,
+    transcription_options: DeviceTranscriptionOptions,
 ) -> Result<()> {
     debug!("Starting video recording for monitor {:?}", monitor_ids);
     let video_tasks = if !vision_disabled {
         monitor_ids
             .iter()
+            .filter(|&&monitor_id| {
+                let disabled = monitor_configs
+                    .get(&monitor_id)
+                    .is_some_and(|config| config.disabled);
+                if disabled {
+                    info!("Not recording monitor {}, it is disabled", monitor_id);
+                }
+                !disabled
+            })
             .map(|&monitor_id| {
                 let db_manager_video = Arc::clone(&db);
                 let output_path_video = Arc::clone(&output_path);
                 let is_running_video = Arc::clone(&vision_control);
-                let ocr_engine = Arc::clone(&ocr_engine);
+                let config = monitor_configs.get(&monitor_id);
+                let fps = config.and_then(|config| config.fps).unwrap_or(fps);
+                let ocr_engine = match config.and_then(|config| config.ocr_engine.clone()) {
+                    Some(ocr_engine) => Arc::new(ocr_engine),
+                    None => Arc::clone(&ocr_engine),
+                };
+                let ocr_scale = config
+                    .and_then(|config| config.ocr_scale)
+                    .unwrap_or(ocr_scale);
+                let video_scale = config
+                    .and_then(|config| config.video_scale)
+                    .unwrap_or(video_scale);
                 let ignored_windows_video = ignored_windows.to_vec();
                 let include_windows_video = include_windows.to_vec();
+                let capture_window = capture_window.clone();
+                let app_filters = app_filters.clone();
+                let sensitive_windows = sensitive_windows.clone();
                 let realtime_vision_sender_clone = realtime_vision_sender.clone();
 
                 let languages = languages.clone();
@@ -78,9 +150,18 @@ pub async fn start_continuous_recording(
                         use_pii_removal,
                         &ignored_windows_video,
                         &include_windows_video,
+                        capture_window,
+                        app_filters,
+                        sensitive_windows,
                         video_chunk_duration,
                         languages.clone(),
                         capture_unfocused_windows,
+                        adaptive_fps,
+                        frame_dedup,
+                        accessibility_tree,
+                        frame_embeddings,
+                        ocr_scale,
+                        video_scale,
                         realtime_vision_sender_clone,
                     )
                     .await
@@ -118,12 +199,28 @@ pub async fn start_continuous_recording(
             VadSensitivity::from(vad_sensitivity),
             languages.clone(),
             Some(audio_devices_control.clone()),
+            model_options.clone(),
+            transcription_options.clone(),
         )
         .await?
     };
     let whisper_sender_clone = whisper_sender.clone();
     let db_manager_audio = Arc::clone(&db);
 
+    let watch_folder_task = if !watch_folders.is_empty() {
+        let watcher = FolderWatcher::new(
+            Arc::clone(&db),
+            watch_folders,
+            Arc::clone(&ocr_engine),
+            languages.clone(),
+            use_pii_removal,
+            (!audio_disabled).then(|| whisper_sender.clone()),
+        );
+        Some(vision_handle.spawn(watcher.run()))
+    } else {
+        None
+    };
+
     let audio_task = if !audio_disabled {
         audio_handle.spawn(async move {
             record_audio(
@@ -137,6 +234,10 @@ pub async fn start_continuous_recording(
                 realtime_audio_devices,
                 languages,
                 deepgram_api_key,
+                audio_stream_options,
+                recording_options,
+                model_options,
+                transcription_options,
             )
             .await
         })
@@ -159,6 +260,9 @@ pub async fn start_continuous_recording(
     if let Err(e) = audio_task.await {
         error!("Audio recording error: {:?}", e);
     }
+    if let Some(task) = watch_folder_task {
+        task.abort();
+    }
 
     // Shutdown the whisper channel
     whisper_shutdown_flag.store(true, Ordering::Relaxed);
@@ -183,9 +287,18 @@ async fn record_video(
     use_pii_removal: bool,
     ignored_windows: &[String],
     include_windows: &[String],
+    capture_window: Option<String>,
+    app_filters: AppFilters,
+    sensitive_windows: SensitiveWindows,
     video_chunk_duration: Duration,
     languages: Vec<Language>,
     capture_unfocused_windows: bool,
+    adaptive_fps: Option<AdaptiveFpsConfig>,
+    frame_dedup: bool,
+    accessibility_tree: bool,
+    frame_embeddings: bool,
+    ocr_scale: FrameScale,
+    video_scale: FrameScale,
     realtime_vision_sender: Arc<tokio::sync::broadcast::Sender<RealtimeVisionEvent>>,
 ) -> Result<()> {
     debug!("record_video: Starting");
@@ -221,28 +334,118 @@ async fn record_video(
         monitor_id,
         ignored_windows,
         include_windows,
+        capture_window,
+        app_filters,
+        sensitive_windows,
         languages,
         capture_unfocused_windows,
+        adaptive_fps,
+        frame_dedup,
+        accessibility_tree,
+        ocr_scale,
+        video_scale,
     );
 
+    // last frame inserted, the duplicates sent after it refer to it
+    let mut last_frame_id: Option<i64> = None;
+    // tree stored last, the one of a window that didn't change isn't stored again
+    let mut last_tree: Option<AccessibilityTree> = None;
+    let embedding_sender = frame_embeddings.then(|| spawn_frame_embedder(Arc::clone(&db)));
     while is_running.load(Ordering::SeqCst) {
         if let Some(frame) = video_capture.ocr_frame_queue.pop() {
+            let details = frame_details(&frame, use_pii_removal).await;
+            if frame.duplicate {
+                if let Some(original_id) = last_frame_id {
+                    let captured_at = clock::time_at(frame.timestamp).into();
+                    if let Err(e) = db
+                        .insert_duplicate_frame(original_id, Some(captured_at), &details)
+                        .await
+                    {
+                        warn!("Failed to insert duplicate of frame {}: {}", original_id, e);
+                    }
+                }
+            }
             for window_result in &frame.window_ocr_results {
-                match db.insert_frame(&device_name, None).await {
+                if window_result.focused {
+                    now::update_focused_window(
+                        &window_result.app_name,
+                        &window_result.window_name,
+                    );
+                }
+                match db
+                    .insert_frame_with_details(
+                        &device_name,
+                        Some(clock::time_at(frame.timestamp).into()),
+                        &details,
+                    )
+                    .await
+                {
                     Ok(frame_id) => {
-                        let text_json =
-                            serde_json::to_string(&window_result.text_json).unwrap_or_default();
-
-                        let text = if use_pii_removal {
-                            &remove_pii(&window_result.text)
+                        last_frame_id = Some(frame_id);
+                        // skipped while the frames before are still being embedded
+                        if let Some(sender) = embedding_sender
+                            .as_ref()
+                            .filter(|sender| sender.capacity() > 0)
+                        {
+                            let _ = sender.try_send((frame_id, window_result.image.clone()));
+                        }
+                        if window_result.focused {
+                            if let Some(tree) = &frame.accessibility_tree {
+                                let tree = if use_pii_removal {
+                                    let tree = tree.clone();
+                                    redaction::off_runtime(move || redact_tree(&tree))
+                                        .await
+                                        .map_err(|e| warn!("Failed to redact ui elements: {}", e))
+                                        .ok()
+                                } else {
+                                    Some(tree.clone())
+                                };
+                                if let Some(tree) =
+                                    tree.filter(|tree| last_tree.as_ref() != Some(tree))
+                                {
+                                    match db.insert_ui_elements(frame_id, &tree).await {
+                                        Ok(()) => last_tree = Some(tree),
+                                        Err(e) => warn!(
+                                            "Failed to insert ui elements of frame {}: {}",
+                                            frame_id, e
+                                        ),
+                                    }
+                                }
+                            }
+                        }
+                        let (text, words, layout) = if use_pii_removal {
+                            let (text, words, layout) = (
+                                window_result.text.clone(),
+                                window_result.text_json.clone(),
+                                window_result.layout.clone(),
+                            );
+                            match redaction::off_runtime(move || {
+                                redact_window(&text, words, &layout)
+                            })
+                            .await
+                            {
+                                Ok(redacted) => redacted,
+                                Err(e) => {
+                                    error!(
+                                        "Failed to redact window {} of frame {}: {}, skipping it",
+                                        window_result.window_name, frame_id, e
+                                    );
+                                    continue;
+                                }
+                            }
                         } else {
-                            &window_result.text
+                            (
+                                window_result.text.clone(),
+                                window_result.text_json.clone(),
+                                window_result.layout.clone(),
+                            )
                         };
+                        let text_json = serde_json::to_string(&words).unwrap_or_default();
 
                         let _ = realtime_vision_sender.send(RealtimeVisionEvent::Ocr(WindowOcr {
                             image: Some(frame.image.clone()),
                             text: text.clone(),
-                            text_json: window_result.text_json.clone(),
+                            text_json: words,
                             app_name: window_result.app_name.clone(),
                             window_name: window_result.window_name.clone(),
                             focused: window_result.focused,
@@ -252,7 +455,7 @@ async fn record_video(
                         if let Err(e) = db
                             .insert_ocr_text(
                                 frame_id,
-                                text,
+                                &text,
                                 &text_json,
                                 &window_result.app_name,
                                 &window_result.window_name,
@@ -267,6 +470,22 @@ async fn record_video(
                             );
                             continue;
                         }
+                        if !layout.blocks.is_empty() {
+                            if let Err(e) = db.insert_ocr_layout(frame_id, &layout).await {
+                                warn!("Failed to insert OCR layout of frame {}: {}", frame_id, e);
+                            }
+                            let structures = extract_structures(&layout);
+                            if !structures.is_empty() {
+                                if let Err(e) =
+                                    db.insert_ocr_structures(frame_id, &structures).await
+                                {
+                                    warn!(
+                                        "Failed to insert tables and code of frame {}: {}",
+                                        frame_id, e
+                                    );
+                                }
+                            }
+                        }
                     }
                     Err(e) => {
                         warn!("Failed to insert frame: {}", e);
@@ -282,6 +501,208 @@ async fn record_video(
     Ok(())
 }
 
+/// The monitor, what was focused, the page of the browser, the virtual desktop and the pointer
+/// when `frame` was captured, stored with it
+async fn frame_details(frame: &CaptureResult, use_pii_removal: bool) -> FrameDetails {
+    let cursor = match &frame.cursor {
+        Some(cursor) => match cursor.selected_text.clone() {
+            Some(text) if use_pii_removal => {
+                match redaction::off_runtime(move || redact(&text)).await {
+                    Ok(text) => Some(CursorOverlay {
+                        selected_text: Some(text),
+                        ..cursor.clone()
+                    }),
+                    Err(e) => {
+                        warn!(
+                            "Failed to redact selected text of frame {}: {}",
+                            frame.frame_number, e
+                        );
+                        None
+                    }
+                }
+            }
+            _ => Some(cursor.clone()),
+        },
+        None => None,
+    };
+    FrameDetails {
+        monitor: Some(frame.monitor.clone()),
+        active_app: frame.active_app.clone(),
+        in_call: frame.in_call,
+        browser_tab: frame.browser_tab.clone(),
+        virtual_desktop: frame.virtual_desktop,
+        cursor,
+    }
+}
+
+/// Stores the CLIP embedding of the images of the frames sent, one at a time
+fn spawn_frame_embedder(db: Arc<DatabaseManager>) -> mpsc::Sender<(i64, DynamicImage)> {
+    let (sender, mut receiver) = mpsc::channel::<(i64, DynamicImage)>(FRAME_EMBEDDING_QUEUE);
+    tokio::spawn(async move {
+        while let Some((frame_id, image)) = receiver.recv().await {
+            match clip::image_embedding(&image).await {
+                Ok(embedding) => {
+                    if let Err(e) = db.insert_frame_embedding(frame_id, &embedding).await {
+                        warn!("Failed to insert embedding of frame {}: {}", frame_id, e);
+                    }
+                }
+                Err(e) => warn!("Failed to embed frame {}: {}", frame_id, e),
+            }
+        }
+    });
+    sender
+}
+
+fn redact_tree(tree: &AccessibilityTree) -> AccessibilityTree {
+    let mut tree = tree.clone();
+    for element in &mut tree.elements {
+        element.label = redact(&element.label);
+        element.text = redact(&element.text);
+    }
+    tree
+}
+
+/// The text, words and layout of a window with PII masked. The words are dropped when any of the
+/// text was masked, they would give it away
+fn redact_window(
+    text: &str,
+    words: Vec<HashMap<String, String>>,
+    layout: &OcrLayout,
+) -> (String, Vec<HashMap<String, String>>, OcrLayout) {
+    let redacted = redact(text);
+    let words = if redacted == text { words } else { Vec::new() };
+    (redacted, words, redact_layout(layout))
+}
+
+fn redact_layout(layout: &OcrLayout) -> OcrLayout {
+    let mut layout = layout.clone();
+    for line in layout.blocks.iter_mut().flat_map(|block| block.lines.iter_mut()) {
+        let redacted = redact(&line.text);
+        // the words of the line would give away what was redacted
+        if redacted != line.text {
+            line.text = redacted;
+            line.words.clear();
+        }
+    }
+    layout
+}
+
+/// Stores the clicks, keypresses and scrolls, leaving out the ones in apps not recorded
+pub async fn record_input_events(
+    db: Arc<DatabaseManager>,
+    app_filters: AppFilters,
+    sensitive_windows: SensitiveWindows,
+) {
+    let (input_tx, mut input_rx) = tokio::sync::mpsc::channel(512);
+    start_input_capture(sensitive_windows, input_tx);
+    while let Some(event) = input_rx.recv().await {
+        if let Some(app_name) = &event.app_name {
+            if !app_filters.is_app_name_allowed(app_name) {
+                continue;
+            }
+        }
+        if let Err(e) = db.insert_input_event(&event).await {
+            warn!("Failed to insert input event: {}", e);
+        }
+    }
+}
+
+/// Pauses the capture while no input came for `idle_after` or the screen is locked, when
+/// `pause_on_lock`, and stores the pauses
+pub async fn pause_capture_when_away(
+    db: Arc<DatabaseManager>,
+    idle_after: Option<Duration>,
+    pause_on_lock: bool,
+) {
+    // left open when screenpipe stopped while paused
+    if let Err(e) = db.end_capture_pauses(None, Utc::now()).await {
+        warn!("Failed to end the previous capture pauses: {}", e);
+    }
+    let mut pause: Option<(PauseReason, i64)> = None;
+    loop {
+        let reason = tokio::task::spawn_blocking(move || {
+            if pause_on_lock && idle::is_screen_locked() {
+                return Some(PauseReason::Locked);
+            }
+            let idle_after = idle_after?;
+            idle::idle_time()
+                .filter(|idle_time| *idle_time >= idle_after)
+                .map(|_| PauseReason::Idle)
+        })
+        .await
+        .ok()
+        .flatten();
+
+        if reason != pause.map(|(reason, _)| reason) {
+            capture_pause::set_pause(reason);
+            let now = Utc::now();
+            if let Some((_, id)) = pause.take() {
+                if let Err(e) = db.end_capture_pauses(Some(id), now).await {
+                    warn!("Failed to end capture pause: {}", e);
+                }
+            }
+            if let Some(reason) = reason {
+                match db.insert_capture_pause(reason.as_str(), now).await {
+                    Ok(id) => pause = Some((reason, id)),
+                    Err(e) => warn!("Failed to insert capture pause: {}", e),
+                }
+            }
+        }
+        tokio::time::sleep(PAUSE_CHECK_INTERVAL).await;
+    }
+}
+
+/// Detects meetings, from the window of a call, or from speech heard both on a microphone and on
+/// the speakers while an app for calls is open, and stores them as meeting sessions. Capture is
+/// boosted while one goes on, see `init_meeting_fps`
+pub async fn detect_meetings(db: Arc<DatabaseManager>) {
+    // left open when screenpipe stopped during a meeting
+    if let Err(e) = db.end_meeting_sessions(None, Utc::now()).await {
+        warn!("Failed to end the previous meeting sessions: {}", e);
+    }
+    let mut current: Option<i64> = None;
+    let mut last_seen = Instant::now();
+    loop {
+        let since = Utc::now() - chrono::Duration::from_std(MEETING_SPEECH_WINDOW).unwrap();
+        let speaking = speech_stats::devices_with_speech_since(since);
+        let both_ways = speaking.iter().any(|device| device.ends_with("(input)"))
+            && speaking.iter().any(|device| device.ends_with("(output)"));
+        let window = tokio::task::spawn_blocking(move || {
+            call_window().or_else(|| both_ways.then(meeting_app_window).flatten())
+        })
+        .await
+        .ok()
+        .flatten();
+
+        match (window, current) {
+            (Some((app_name, title)), None) => {
+                match db
+                    .insert_meeting_session(&app_name, &title, Utc::now())
+                    .await
+                {
+                    Ok(id) => {
+                        current = Some(id);
+                        last_seen = Instant::now();
+                        meeting::set_meeting(current);
+                    }
+                    Err(e) => warn!("Failed to insert meeting session: {}", e),
+                }
+            }
+            (Some(_), Some(_)) => last_seen = Instant::now(),
+            // a call window closed and reopened, or a pause in the conversation, is the same meeting
+            (None, Some(id)) if last_seen.elapsed() >= MEETING_END_GRACE => {
+                if let Err(e) = db.end_meeting_sessions(Some(id), Utc::now()).await {
+                    warn!("Failed to end meeting session: {}", e);
+                }
+                current = None;
+                meeting::set_meeting(None);
+            }
+            _ => {}
+        }
+        tokio::time::sleep(MEETING_CHECK_INTERVAL).await;
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn record_audio(
     db: Arc<DatabaseManager>,
@@ -294,10 +715,13 @@ async fn record_audio(
     realtime_audio_devices: Vec<Arc<AudioDevice>>,
     languages: Vec<Language>,
     deepgram_api_key: Option<String>,
+    audio_stream_options: StreamOptions,
+    recording_options: RecordingOptions,
+    model_options: ModelOptions,
+    transcription_options: DeviceTranscriptionOptions,
 ) -> Result<()> {
     let mut handles: HashMap<String, JoinHandle<()>> = HashMap::new();
-    let mut previous_transcript = "".to_string();
-    let mut previous_transcript_id: Option<i64> = None;
+    let mut previous_transcripts: HashMap<String, PreviousTranscript> = HashMap::new();
     loop {
         // Iterate over DashMap entries and process each device
         for entry in audio_devices_control.iter() {
@@ -331,6 +755,15 @@ async fn record_audio(
             let realtime_audio_devices_clone = realtime_audio_devices.clone();
             let languages_clone = languages.clone();
             let deepgram_api_key_clone = deepgram_api_key.clone();
+            let audio_stream_options = audio_stream_options.clone();
+            let recording_options = match transcription_options.device_vad.get(audio_device.as_ref()) {
+                Some(vad) => recording_options.clone().with_vad(vad),
+                None => recording_options.clone(),
+            };
+            let audio_transcription_engine_clone = audio_transcription_engine.clone();
+            let model_options = model_options.clone();
+            let device_transcription_options =
+                transcription_options.for_device(&audio_device).clone();
             let handle = tokio::spawn(async move {
                 let audio_device_clone = Arc::clone(&audio_device);
                 let deepgram_api_key = deepgram_api_key_clone.clone();
@@ -345,9 +778,10 @@ async fn record_audio(
                 while is_running.load(Ordering::Relaxed) {
                     let deepgram_api_key = deepgram_api_key.clone();
                     let is_running_loop = Arc::clone(&is_running); // Create separate reference for the loop
-                    let audio_stream = match AudioStream::from_device(
+                    let audio_stream = match AudioStream::from_device_with_options(
                         audio_device_clone.clone(),
                         Arc::clone(&is_running_loop), // Clone from original Arc
+                        audio_stream_options.clone(),
                     )
                     .await
                     {
@@ -369,16 +803,18 @@ async fn record_audio(
 
                     let mut recording_handles: Vec<JoinHandle<()>> = vec![];
 
-                    let audio_stream = Arc::new(audio_stream);
+                    let audio_stream = audio_stream.shared();
                     let whisper_sender_clone = whisper_sender_clone.clone();
                     let audio_stream_clone = audio_stream.clone();
                     let is_running_loop_clone = is_running_loop.clone();
+                    let recording_options = recording_options.clone();
                     let record_handle = Some(tokio::spawn(async move {
-                        let _ = record_and_transcribe(
+                        let _ = record_and_transcribe_with_options(
                             audio_stream,
                             chunk_duration,
                             whisper_sender_clone.clone(),
                             is_running_loop_clone.clone(),
+                            recording_options,
                         )
                         .await;
                     }));
@@ -391,6 +827,9 @@ async fn record_audio(
                     let realtime_audio_devices_clone = realtime_audio_devices_clone.clone();
                     let languages_clone = languages_clone.clone();
                     let is_running_loop = is_running_loop.clone();
+                    let audio_transcription_engine = audio_transcription_engine_clone.clone();
+                    let model_options = model_options.clone();
+                    let device_transcription_options = device_transcription_options.clone();
                     let live_transcription_handle = Some(tokio::spawn(async move {
                         if realtime_audio_enabled
                             && realtime_audio_devices_clone.contains(&audio_device_clone)
@@ -400,6 +839,9 @@ async fn record_audio(
                                 languages_clone.clone(),
                                 is_running_loop.clone(),
                                 deepgram_api_key.clone(),
+                                audio_transcription_engine,
+                                model_options,
+                                device_transcription_options,
                             )
                             .await;
                         }
@@ -433,32 +875,32 @@ async fn record_audio(
                 transcription.input.device, transcription.transcription
             );
 
-            // Insert the new transcript after fetching
-            let mut current_transcript: Option<String> = transcription.transcription.clone();
+            // the start of the transcript may repeat the end of the previous one of the device,
+            // whose audio it overlapped
+            let device = transcription.input.device.to_string();
+            let previous = previous_transcripts.get(&device);
+            let previous_transcript_id = previous.and_then(|previous| previous.id);
             let mut processed_previous: Option<String> = None;
-            if let Some((previous, current)) =
-                transcription.cleanup_overlap(previous_transcript.clone())
-            {
-                if !previous.is_empty() && !current.is_empty() {
-                    if previous != previous_transcript {
-                        processed_previous = Some(previous);
-                    }
-                    if current_transcript.is_some()
-                        && current != current_transcript.clone().unwrap_or_default()
-                    {
-                        current_transcript = Some(current);
+            if let Some(previous) = previous.filter(|_| transcription.is_final) {
+                if let Some((previous_text, current)) =
+                    transcription.cleanup_overlap(&previous.text, previous.captured_until)
+                {
+                    if !previous_text.is_empty() && !current.is_empty() {
+                        if previous_text != previous.text {
+                            processed_previous = Some(previous_text);
+                        }
+                        transcription.transcription = Some(current);
                     }
                 }
             }
 
-            transcription.transcription = current_transcript.clone();
-            if current_transcript.is_some() {
-                previous_transcript = current_transcript.unwrap();
-            } else {
+            let Some(current_transcript) = transcription.transcription.clone() else {
                 continue;
-            }
+            };
+            let is_final = transcription.is_final;
+            let captured_until = transcription.captured_until;
             // Process the audio result
-            match process_audio_result(
+            let id = match process_audio_result(
                 &db,
                 transcription,
                 audio_transcription_engine.clone(),
@@ -467,8 +909,21 @@ async fn record_audio(
             )
             .await
             {
-                Err(e) => error!("Error processing audio result: {}", e),
-                Ok(id) => previous_transcript_id = id,
+                Err(e) => {
+                    error!("Error processing audio result: {}", e);
+                    None
+                }
+                Ok(id) => id,
+            };
+            if is_final {
+                previous_transcripts.insert(
+                    device,
+                    PreviousTranscript {
+                        text: current_transcript,
+                        id,
+                        captured_until,
+                    },
+                );
             }
         }
 
@@ -476,6 +931,16 @@ async fn record_audio(
     }
 }
 
+/// Last transcript of a device, whose end the next one may repeat
+struct PreviousTranscript {
+    text: String,
+    /// Row of the transcript, None when it wasn't inserted
+    id: Option<i64>,
+    captured_until: Option<SystemTime>,
+}
+
+/// Inserts a transcript, returning its row. `previous_transcript` replaces the text of the row
+/// `previous_transcript_id` when its end was cut for this one
 async fn process_audio_result(
     db: &DatabaseManager,
     result: TranscriptionResult,
@@ -483,6 +948,19 @@ async fn process_audio_result(
     previous_transcript: Option<String>,
     previous_transcript_id: Option<i64>,
 ) -> Result<Option<i64>, anyhow::Error> {
+    // partial results of streaming transcription are only for live captions
+    if !result.is_final {
+        return Ok(None);
+    }
+
+    if result.hallucination.is_some() && result.transcription.is_none() {
+        debug!(
+            "dropped {:?} hallucination of device {}",
+            result.hallucination, result.input.device
+        );
+        return Ok(None);
+    }
+
     if result.error.is_some() || result.transcription.is_none() {
         error!(
             "Error in audio recording: {}. Not inserting audio result",
@@ -496,8 +974,19 @@ async fn process_audio_result(
     info!("Detected speaker: {:?}", speaker);
 
     let transcription = result.transcription.unwrap();
-    let transcription_engine = audio_transcription_engine.to_string();
-    let mut chunk_id: Option<i64> = None;
+    let transcription = redaction::off_runtime(move || redact_transcript(&transcription)).await?;
+    now::update_transcript(
+        &result.input.device.to_string(),
+        &transcription,
+        Some(&speaker),
+    );
+    // the engine of the fallback chain that actually transcribed
+    let transcription_engine = result
+        .engine
+        .as_ref()
+        .unwrap_or(audio_transcription_engine.as_ref())
+        .to_string();
+    let mut inserted: Option<i64> = None;
 
     info!(
         "device {} inserting audio chunk: {:?}",
@@ -505,14 +994,16 @@ async fn process_audio_result(
     );
     if let Some(id) = previous_transcript_id {
         if let Some(prev_transcript) = previous_transcript {
+            let prev_transcript =
+                redaction::off_runtime(move || redact_transcript(&prev_transcript)).await?;
             match db
-                .update_audio_transcription(id, prev_transcript.as_str())
+                .set_audio_transcription_text(id, &prev_transcript)
                 .await
             {
                 Ok(_) => {}
                 Err(e) => error!(
-                    "Failed to update transcription for {}: audio_chunk_id {}",
-                    result.input.device, e
+                    "Failed to update transcription {} for {}: {}",
+                    id, result.input.device, e
                 ),
             }
         }
@@ -520,11 +1011,22 @@ async fn process_audio_result(
     match db.get_or_insert_audio_chunk(&result.path).await {
         Ok(audio_chunk_id) => {
             if transcription.is_empty() {
-                return Ok(Some(audio_chunk_id));
+                return Ok(None);
             }
 
-            if let Err(e) = db
-                .insert_audio_transcription(
+            // one insert, dated when its audio was captured rather than when it was transcribed
+            let details = TranscriptionDetails {
+                captured_at: result.captured_at.map(DateTime::<Utc>::from),
+                confidence: result.confidence,
+                language: result.language.clone(),
+                hallucination: result.hallucination,
+                captured_until: result.captured_until.map(DateTime::<Utc>::from),
+                audio_duration: Some(result.audio_duration.as_secs_f64()),
+                latency: result.latency.map(|latency| latency.as_secs_f64()),
+                model_version: result.model_version.clone(),
+            };
+            match db
+                .insert_audio_transcription_with_details(
                     audio_chunk_id,
                     &transcription,
                     0,
@@ -533,20 +1035,24 @@ async fn process_audio_result(
                     Some(speaker.id),
                     Some(result.start_time),
                     Some(result.end_time),
+                    &details,
                 )
                 .await
             {
-                error!(
-                    "Failed to insert audio transcription for device {}: {}",
-                    result.input.device, e
-                );
-                return Ok(Some(audio_chunk_id));
-            } else {
-                debug!(
-                    "Inserted audio transcription for chunk {} from device {} using {}",
-                    audio_chunk_id, result.input.device, transcription_engine
-                );
-                chunk_id = Some(audio_chunk_id);
+                Err(e) => {
+                    error!(
+                        "Failed to insert audio transcription for device {}: {}",
+                        result.input.device, e
+                    );
+                    return Ok(None);
+                }
+                Ok(transcription_id) => {
+                    debug!(
+                        "Inserted audio transcription for chunk {} from device {} using {}",
+                        audio_chunk_id, result.input.device, transcription_engine
+                    );
+                    inserted = Some(transcription_id);
+                }
             }
         }
         Err(e) => error!(
@@ -554,7 +1060,7 @@ async fn process_audio_result(
             result.input.device, e
         ),
     }
-    Ok(chunk_id)
+    Ok(inserted)
 }
 
 async fn get_or_create_speaker_from_embedding(
@@ -1,7 +1,14 @@
 use chrono::{DateTime, Utc};
-use image::DynamicImage;
 use libsqlite3_sys::sqlite3_auto_extension;
-use screenpipe_audio::{AudioDevice, DeviceType};
+use screenpipe_audio::{AudioDevice, DetectedLanguage, DeviceType, SegmentConfidence};
+use screenpipe_vision::accessibility::AccessibilityTree;
+use screenpipe_vision::active_window::ActiveApp;
+use screenpipe_vision::browser_tab::BrowserTab;
+use screenpipe_vision::cursor::CursorOverlay;
+use screenpipe_vision::input_events::{InputActivity, InputEvent};
+use screenpipe_vision::monitor::MonitorData;
+use screenpipe_vision::ocr_layout::{LayoutBlock, LayoutBox, LayoutLine, OcrLayout};
+use screenpipe_vision::ocr_structure::OcrStructure;
 use screenpipe_vision::OcrEngine;
 use sqlite_vec::sqlite3_vec_init;
 use sqlx::migrate::MigrateDatabase;
@@ -12,7 +19,7 @@ use sqlx::Row;
 use sqlx::TypeInfo;
 use sqlx::ValueRef;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, warn};
 
 use std::collections::BTreeMap;
@@ -21,17 +28,25 @@ use tokio::time::{timeout, Duration as TokioDuration};
 use zerocopy::AsBytes;
 
 use crate::db_types::{
-    AudioChunksResponse, AudioEntry, AudioResult, AudioResultRaw, FrameData, OCREntry, OCRResult,
-    OCRResultRaw, Speaker, TagContentType,
+    AlignedFrame, AudioEmbeddingMatchRaw, CapturePause, ContentType, InputActivityBucket,
+    InputEventResult, MeetingSession, OCREmbeddingMatchRaw, OcrLineResult, TranscriptSegment,
+    UiContent, UiElementResult, VisualSearchResult,
+};
+use crate::db_types::{
+    AudioChunksResponse, AudioEntry, AudioResult, AudioResultRaw, FrameData, FrameDetails,
+    OCREntry, OCRResult, OCRResultRaw, Speaker, TagContentType, TranscriptionDetails,
 };
-use crate::db_types::{ContentType, UiContent};
 use crate::db_types::{SearchResult, TimeSeriesChunk};
+use crate::fts_query::{FtsQuery, FtsTable};
+use crate::query_plan::{self, QueryPlanReport, SearchShape, StageReport, TableScan};
+use crate::search_analyzer::{QueryAnalyzer, SearchAnalyzer};
 use crate::video_utils::VideoMetadata;
 
-use futures::future::try_join_all;
+use futures::future::join_all;
 
 pub struct DatabaseManager {
     pub pool: SqlitePool,
+    query_analyzer: std::sync::RwLock<QueryAnalyzer>,
 }
 
 impl DatabaseManager {
@@ -77,7 +92,10 @@ impl DatabaseManager {
             .execute(&pool)
             .await?;
 
-        let db_manager = DatabaseManager { pool };
+        let db_manager = DatabaseManager {
+            pool,
+            query_analyzer: std::sync::RwLock::new(QueryAnalyzer::default()),
+        };
 
         // Run migrations after establishing the connection
         Self::run_migrations(&db_manager.pool).await?;
@@ -133,18 +151,45 @@ impl DatabaseManager {
         speaker_id: Option<i64>,
         start_time: Option<f64>,
         end_time: Option<f64>,
+    ) -> Result<i64, sqlx::Error> {
+        self.insert_audio_transcription_with_details(
+            audio_chunk_id,
+            transcription,
+            offset_index,
+            transcription_engine,
+            device,
+            speaker_id,
+            start_time,
+            end_time,
+            &TranscriptionDetails::default(),
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn insert_audio_transcription_with_details(
+        &self,
+        audio_chunk_id: i64,
+        transcription: &str,
+        offset_index: i64,
+        transcription_engine: &str,
+        device: &AudioDevice,
+        speaker_id: Option<i64>,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
+        details: &TranscriptionDetails,
     ) -> Result<i64, sqlx::Error> {
         let text_length = transcription.len() as i64;
         let mut tx = self.pool.begin().await?;
 
-        // Insert the full transcription
+        // Insert the full transcription with all its details, so the fts index sees it once
         let id = sqlx::query(
-            "INSERT INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine, device, is_input_device, speaker_id, start_time, end_time, text_length) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            "INSERT INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine, device, is_input_device, speaker_id, start_time, end_time, text_length, avg_logprob, no_speech_prob, language, language_probability, hallucination, captured_until, audio_duration, latency, model_version) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
         )
         .bind(audio_chunk_id)
         .bind(transcription)
         .bind(offset_index)
-        .bind(Utc::now())
+        .bind(details.captured_at.unwrap_or_else(Utc::now))
         .bind(transcription_engine)
         .bind(&device.name)
         .bind(device.device_type == DeviceType::Input)
@@ -152,6 +197,15 @@ impl DatabaseManager {
         .bind(start_time)
         .bind(end_time)
         .bind(text_length)
+        .bind(details.confidence.as_ref().map(|c| c.avg_logprob))
+        .bind(details.confidence.as_ref().map(|c| c.no_speech_prob))
+        .bind(details.language.as_ref().map(|l| l.code.as_str()))
+        .bind(details.language.as_ref().map(|l| l.probability as f64))
+        .bind(details.hallucination.map(|h| h.as_str()))
+        .bind(details.captured_until)
+        .bind(details.audio_duration)
+        .bind(details.latency)
+        .bind(details.model_version.as_deref())
         .execute(&mut *tx)
         .await?
         .last_insert_rowid();
@@ -211,6 +265,50 @@ impl DatabaseManager {
         })
     }
 
+    /// Registers the voice embedding of an enrollment sample for `name`. Transcripts whose
+    /// embedding matches it are attributed to that speaker, enrolling the same name again adds
+    /// another embedding to the existing speaker
+    pub async fn enroll_speaker(
+        &self,
+        name: &str,
+        embedding: &[f32],
+    ) -> Result<Speaker, SqlxError> {
+        let mut tx = self.pool.begin().await?;
+
+        let existing: Option<i64> =
+            sqlx::query_scalar("SELECT id FROM speakers WHERE name = ?1 ORDER BY id LIMIT 1")
+                .bind(name)
+                .fetch_optional(&mut *tx)
+                .await?;
+        let id = match existing {
+            Some(id) => id,
+            None => sqlx::query("INSERT INTO speakers (name) VALUES (?1)")
+                .bind(name)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_rowid(),
+        };
+
+        let bytes: &[u8] = embedding.as_bytes();
+        sqlx::query(
+            "INSERT INTO speaker_embeddings (embedding, speaker_id) VALUES (vec_f32(?1), ?2)",
+        )
+        .bind(bytes)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        let speaker = sqlx::query_as(
+            "SELECT id, name, COALESCE(metadata, '') AS metadata FROM speakers WHERE id = ?1",
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(speaker)
+    }
+
     pub async fn update_speaker_metadata(
         &self,
         speaker_id: i64,
@@ -288,10 +386,58 @@ impl DatabaseManager {
         Ok(id)
     }
 
+    /// Chunks whose frames are all older than `before`, not yet re-encoded to `codec`, the
+    /// oldest first
+    pub async fn video_chunks_to_archive(
+        &self,
+        before: DateTime<Utc>,
+        codec: &str,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT video_chunks.id, video_chunks.file_path
+            FROM video_chunks
+            WHERE (video_chunks.codec IS NULL OR video_chunks.codec != ?2)
+                AND (SELECT MAX(timestamp) FROM frames WHERE frames.video_chunk_id = video_chunks.id) < ?1
+            ORDER BY video_chunks.id
+            LIMIT ?3
+            "#,
+        )
+        .bind(before)
+        .bind(codec)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn set_video_chunk_codec(
+        &self,
+        video_chunk_id: i64,
+        codec: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE video_chunks SET codec = ?1 WHERE id = ?2")
+            .bind(codec)
+            .bind(video_chunk_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     pub async fn insert_frame(
         &self,
         device_name: &str,
         timestamp: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        self.insert_frame_with_details(device_name, timestamp, &FrameDetails::default())
+            .await
+    }
+
+    pub async fn insert_frame_with_details(
+        &self,
+        device_name: &str,
+        timestamp: Option<DateTime<Utc>>,
+        details: &FrameDetails,
     ) -> Result<i64, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
         debug!("insert_frame Transaction started");
@@ -326,14 +472,48 @@ impl DatabaseManager {
 
         let timestamp = timestamp.unwrap_or_else(Utc::now);
 
-        // Insert the new frame with file_path as name
+        // Insert the new frame with file_path as name and all its details at once
+        let monitor = details.monitor.as_ref();
         let id = sqlx::query(
-            "INSERT INTO frames (video_chunk_id, offset_index, timestamp, name) VALUES (?1, ?2, ?3, ?4)",
+            r#"
+            INSERT INTO frames (
+                video_chunk_id, offset_index, timestamp, name, monitor_name, monitor_width,
+                monitor_height, monitor_x, monitor_y, focused_app_name, focused_window_name,
+                in_call, browser_url, tab_title, virtual_desktop, cursor_x, cursor_y,
+                selected_text, selection_x, selection_y, selection_width, selection_height
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)
+            "#,
         )
         .bind(video_chunk_id)
         .bind(offset_index)
         .bind(timestamp)
         .bind(file_path)
+        .bind(monitor.map(|monitor| &monitor.name))
+        .bind(monitor.map(|monitor| monitor.width))
+        .bind(monitor.map(|monitor| monitor.height))
+        .bind(monitor.map(|monitor| monitor.x))
+        .bind(monitor.map(|monitor| monitor.y))
+        .bind(details.active_app.as_ref().map(|app| &app.name))
+        // cleared when the window is sensitive
+        .bind(
+            details
+                .active_app
+                .as_ref()
+                .map(|app| &app.window_title)
+                .filter(|title| !title.is_empty()),
+        )
+        .bind(details.in_call)
+        .bind(details.browser_tab.as_ref().map(|tab| &tab.url))
+        .bind(details.browser_tab.as_ref().map(|tab| &tab.title))
+        .bind(details.virtual_desktop)
+        .bind(details.cursor.as_ref().map(|cursor| cursor.cursor_x))
+        .bind(details.cursor.as_ref().map(|cursor| cursor.cursor_y))
+        .bind(details.cursor.as_ref().and_then(|cursor| cursor.selected_text.as_ref()))
+        .bind(cursor_selection(details).map(|selection| selection.left))
+        .bind(cursor_selection(details).map(|selection| selection.top))
+        .bind(cursor_selection(details).map(|selection| selection.width))
+        .bind(cursor_selection(details).map(|selection| selection.height))
         .execute(&mut *tx)
         .await?
         .last_insert_rowid();
@@ -345,6 +525,324 @@ impl DatabaseManager {
         Ok(id)
     }
 
+    /// Inserts a frame looking the same as the frame `duplicate_of`, pointing at its video frame
+    /// and the original it repeats, with the `details` of when it was captured
+    pub async fn insert_duplicate_frame(
+        &self,
+        duplicate_of: i64,
+        timestamp: Option<DateTime<Utc>>,
+        details: &FrameDetails,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            r#"
+            INSERT INTO frames (
+                video_chunk_id, offset_index, timestamp, name, monitor_name, monitor_width,
+                monitor_height, monitor_x, monitor_y, duplicate_of, focused_app_name,
+                focused_window_name, in_call, browser_url, tab_title, virtual_desktop, cursor_x,
+                cursor_y, selected_text, selection_x, selection_y, selection_width,
+                selection_height
+            )
+            SELECT video_chunk_id, offset_index, ?1, name, monitor_name, monitor_width,
+                monitor_height, monitor_x, monitor_y, COALESCE(duplicate_of, id), ?3, ?4, ?5, ?6,
+                ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15
+            FROM frames
+            WHERE id = ?2
+            "#,
+        )
+        .bind(timestamp.unwrap_or_else(Utc::now))
+        .bind(duplicate_of)
+        .bind(details.active_app.as_ref().map(|app| &app.name))
+        .bind(
+            details
+                .active_app
+                .as_ref()
+                .map(|app| &app.window_title)
+                .filter(|title| !title.is_empty()),
+        )
+        .bind(details.in_call)
+        .bind(details.browser_tab.as_ref().map(|tab| &tab.url))
+        .bind(details.browser_tab.as_ref().map(|tab| &tab.title))
+        .bind(details.virtual_desktop)
+        .bind(details.cursor.as_ref().map(|cursor| cursor.cursor_x))
+        .bind(details.cursor.as_ref().map(|cursor| cursor.cursor_y))
+        .bind(
+            details
+                .cursor
+                .as_ref()
+                .and_then(|cursor| cursor.selected_text.as_ref()),
+        )
+        .bind(cursor_selection(details).map(|selection| selection.left))
+        .bind(cursor_selection(details).map(|selection| selection.top))
+        .bind(cursor_selection(details).map(|selection| selection.width))
+        .bind(cursor_selection(details).map(|selection| selection.height))
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn set_frame_monitor(
+        &self,
+        frame_id: i64,
+        monitor: &MonitorData,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE frames SET monitor_name = ?1, monitor_width = ?2, monitor_height = ?3, monitor_x = ?4, monitor_y = ?5 WHERE id = ?6",
+        )
+        .bind(&monitor.name)
+        .bind(monitor.width)
+        .bind(monitor.height)
+        .bind(monitor.x)
+        .bind(monitor.y)
+        .bind(frame_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_frame_browser_tab(
+        &self,
+        frame_id: i64,
+        tab: &BrowserTab,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE frames SET browser_url = ?1, tab_title = ?2 WHERE id = ?3")
+            .bind(&tab.url)
+            .bind(&tab.title)
+            .bind(frame_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_frame_virtual_desktop(
+        &self,
+        frame_id: i64,
+        virtual_desktop: u32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE frames SET virtual_desktop = ?1 WHERE id = ?2")
+            .bind(virtual_desktop)
+            .bind(frame_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_frame_cursor(
+        &self,
+        frame_id: i64,
+        cursor: &CursorOverlay,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE frames SET cursor_x = ?1, cursor_y = ?2, selected_text = ?3, selection_x = ?4, selection_y = ?5, selection_width = ?6, selection_height = ?7 WHERE id = ?8",
+        )
+        .bind(cursor.cursor_x)
+        .bind(cursor.cursor_y)
+        .bind(&cursor.selected_text)
+        .bind(cursor.selection.map(|selection| selection.left))
+        .bind(cursor.selection.map(|selection| selection.top))
+        .bind(cursor.selection.map(|selection| selection.width))
+        .bind(cursor.selection.map(|selection| selection.height))
+        .bind(frame_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Pointer and selection stored with the frame, None when the frame doesn't exist
+    pub async fn get_frame_cursor(
+        &self,
+        frame_id: i64,
+    ) -> Result<Option<CursorOverlay>, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT cursor_x, cursor_y, selected_text, selection_x, selection_y, selection_width, selection_height FROM frames WHERE id = ?1",
+        )
+        .bind(frame_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|row| {
+            let selection = match (
+                row.get::<Option<f64>, _>("selection_x"),
+                row.get::<Option<f64>, _>("selection_y"),
+                row.get::<Option<f64>, _>("selection_width"),
+                row.get::<Option<f64>, _>("selection_height"),
+            ) {
+                (Some(left), Some(top), Some(width), Some(height)) => Some(LayoutBox {
+                    left,
+                    top,
+                    width,
+                    height,
+                }),
+                _ => None,
+            };
+            CursorOverlay {
+                cursor_x: row.get("cursor_x"),
+                cursor_y: row.get("cursor_y"),
+                selected_text: row.get("selected_text"),
+                selection,
+            }
+        }))
+    }
+
+    pub async fn set_frame_focus(
+        &self,
+        frame_id: i64,
+        active_app: Option<&ActiveApp>,
+        in_call: bool,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE frames SET focused_app_name = ?1, focused_window_name = ?2, in_call = ?3 WHERE id = ?4",
+        )
+        .bind(active_app.map(|app| &app.name))
+        // cleared when the window is sensitive
+        .bind(
+            active_app
+                .map(|app| &app.window_title)
+                .filter(|title| !title.is_empty()),
+        )
+        .bind(in_call)
+        .bind(frame_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Stores the elements of `tree`, read with frame `frame_id`
+    pub async fn insert_ui_elements(
+        &self,
+        frame_id: i64,
+        tree: &AccessibilityTree,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for element in &tree.elements {
+            sqlx::query(
+                "INSERT INTO ui_elements (frame_id, app_name, window_name, role, label, text, depth, x, y, width, height) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            )
+            .bind(frame_id)
+            .bind(&tree.app_name)
+            .bind(&tree.window_name)
+            .bind(&element.role)
+            .bind(&element.label)
+            .bind(&element.text)
+            .bind(element.depth)
+            .bind(element.bounds.map(|bounds| bounds.x))
+            .bind(element.bounds.map(|bounds| bounds.y))
+            .bind(element.bounds.map(|bounds| bounds.width))
+            .bind(element.bounds.map(|bounds| bounds.height))
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stores the lines of `layout`, read in the window of `frame_id`
+    pub async fn insert_ocr_layout(
+        &self,
+        frame_id: i64,
+        layout: &OcrLayout,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (block_index, block) in layout.blocks.iter().enumerate() {
+            for (line_index, line) in block.lines.iter().enumerate() {
+                sqlx::query(
+                    "INSERT INTO ocr_lines (frame_id, block_index, line_index, text, x, y, width, height, words) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                )
+                .bind(frame_id)
+                .bind(block_index as i64)
+                .bind(line_index as i64)
+                .bind(&line.text)
+                .bind(line.bbox.left)
+                .bind(line.bbox.top)
+                .bind(line.bbox.width)
+                .bind(line.bbox.height)
+                .bind(serde_json::to_string(&line.words).unwrap_or_default())
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stores the tables and blocks of code read in the window of `frame_id`
+    pub async fn insert_ocr_structures(
+        &self,
+        frame_id: i64,
+        structures: &[OcrStructure],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for structure in structures {
+            sqlx::query(
+                "INSERT INTO ocr_structures (frame_id, kind, text, content, x, y, width, height) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )
+            .bind(frame_id)
+            .bind(structure.kind())
+            .bind(structure.text())
+            .bind(serde_json::to_string(&structure.content).unwrap_or_default())
+            .bind(structure.bbox.left)
+            .bind(structure.bbox.top)
+            .bind(structure.bbox.width)
+            .bind(structure.bbox.height)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stores `event`, linked to the frame captured last before it
+    pub async fn insert_input_event(&self, event: &InputEvent) -> Result<i64, sqlx::Error> {
+        let timestamp = DateTime::<Utc>::from(event.timestamp);
+        let (kind, count) = match &event.activity {
+            InputActivity::Click { .. } => ("click", 1),
+            InputActivity::Keys { count } => ("keys", *count),
+            InputActivity::Scroll { count, .. } => ("scroll", *count),
+        };
+        let (x, y, button, element) = match &event.activity {
+            InputActivity::Click {
+                x,
+                y,
+                button,
+                element,
+            } => (Some(*x), Some(*y), Some(button), element.as_ref()),
+            _ => (None, None, None, None),
+        };
+        let (delta_x, delta_y) = match &event.activity {
+            InputActivity::Scroll {
+                delta_x, delta_y, ..
+            } => (Some(*delta_x), Some(*delta_y)),
+            _ => (None, None),
+        };
+        let id = sqlx::query(
+            r#"
+            INSERT INTO input_events (
+                timestamp, kind, count, x, y, button, delta_x, delta_y, app_name, window_name,
+                element_role, element_label, frame_id
+            )
+            VALUES (
+                ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12,
+                (SELECT id FROM frames WHERE timestamp <= ?1 ORDER BY timestamp DESC LIMIT 1)
+            )
+            "#,
+        )
+        .bind(timestamp)
+        .bind(kind)
+        .bind(count)
+        .bind(x)
+        .bind(y)
+        .bind(button)
+        .bind(delta_x)
+        .bind(delta_y)
+        .bind(&event.app_name)
+        .bind(&event.window_name)
+        .bind(element.map(|element| &element.role))
+        .bind(element.map(|element| &element.label))
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub async fn insert_ocr_text(
         &self,
@@ -472,75 +970,114 @@ impl DatabaseManager {
         max_length: Option<usize>,
         speaker_ids: Option<Vec<i64>>,
         frame_name: Option<&str>,
+        language: Option<&str>,
+        browser_url: Option<&str>,
+        focused_app_name: Option<&str>,
+        in_call: Option<bool>,
+        virtual_desktop: Option<u32>,
+        selected_text: Option<&str>,
+        transcription_engine: Option<&str>,
+        model_version: Option<&str>,
     ) -> Result<Vec<SearchResult>, sqlx::Error> {
         let mut results = Vec::new();
 
+        let has_frame_filter = app_name.is_some()
+            || window_name.is_some()
+            || frame_name.is_some()
+            || browser_url.is_some()
+            || focused_app_name.is_some()
+            || in_call.is_some()
+            || virtual_desktop.is_some()
+            || selected_text.is_some();
+
         match content_type {
             ContentType::All => {
-                let (ocr_results, audio_results, ui_results) =
-                    if app_name.is_none() && window_name.is_none() && frame_name.is_none() {
-                        // Run all three queries in parallel
-                        let (ocr, audio, ui) = tokio::try_join!(
-                            self.search_ocr(
-                                query,
-                                limit,
-                                offset,
-                                start_time,
-                                end_time,
-                                app_name,
-                                window_name,
-                                min_length,
-                                max_length,
-                                frame_name,
-                            ),
-                            self.search_audio(
-                                query,
-                                limit,
-                                offset,
-                                start_time,
-                                end_time,
-                                min_length,
-                                max_length,
-                                speaker_ids
-                            ),
-                            self.search_ui_monitoring(
-                                query,
-                                app_name,
-                                window_name,
-                                start_time,
-                                end_time,
-                                limit,
-                                offset,
-                            )
-                        )?;
-                        (ocr, Some(audio), ui)
+                let (ocr_results, audio_results, ui_results) = if !has_frame_filter {
+                    // Run all three queries in parallel
+                    let (ocr, audio, ui) = tokio::try_join!(
+                        self.search_ocr(
+                            query,
+                            limit,
+                            offset,
+                            start_time,
+                            end_time,
+                            app_name,
+                            window_name,
+                            min_length,
+                            max_length,
+                            frame_name,
+                            browser_url,
+                            focused_app_name,
+                            in_call,
+                            virtual_desktop,
+                            selected_text,
+                        ),
+                        self.search_audio(
+                            query,
+                            limit,
+                            offset,
+                            start_time,
+                            end_time,
+                            min_length,
+                            max_length,
+                            speaker_ids,
+                            language,
+                            transcription_engine,
+                            model_version,
+                        ),
+                        self.search_ui_monitoring(
+                            query,
+                            app_name,
+                            window_name,
+                            start_time,
+                            end_time,
+                            limit,
+                            offset,
+                        )
+                    )?;
+                    (ocr, Some(audio), ui)
+                } else {
+                    // Run only OCR and UI queries in parallel when app/window filters are present
+                    let (ocr, ui) = tokio::try_join!(
+                        self.search_ocr(
+                            query,
+                            limit,
+                            offset,
+                            start_time,
+                            end_time,
+                            app_name,
+                            window_name,
+                            min_length,
+                            max_length,
+                            frame_name,
+                            browser_url,
+                            focused_app_name,
+                            in_call,
+                            virtual_desktop,
+                            selected_text,
+                        ),
+                        self.search_ui_monitoring(
+                            query,
+                            app_name,
+                            window_name,
+                            start_time,
+                            end_time,
+                            limit,
+                            offset,
+                        )
+                    )?;
+                    // ui monitoring doesn't know which page a browser showed, which desktop or
+                    // what was selected
+                    let ui = if browser_url.is_some()
+                        || virtual_desktop.is_some()
+                        || selected_text.is_some()
+                    {
+                        Vec::new()
                     } else {
-                        // Run only OCR and UI queries in parallel when app/window filters are present
-                        let (ocr, ui) = tokio::try_join!(
-                            self.search_ocr(
-                                query,
-                                limit,
-                                offset,
-                                start_time,
-                                end_time,
-                                app_name,
-                                window_name,
-                                min_length,
-                                max_length,
-                                frame_name,
-                            ),
-                            self.search_ui_monitoring(
-                                query,
-                                app_name,
-                                window_name,
-                                start_time,
-                                end_time,
-                                limit,
-                                offset,
-                            )
-                        )?;
-                        (ocr, None, ui)
+                        ui
                     };
+                    (ocr, None, ui)
+                };
 
                 results.extend(ocr_results.into_iter().map(SearchResult::OCR));
                 if let Some(audio) = audio_results {
@@ -561,12 +1098,24 @@ impl DatabaseManager {
                         min_length,
                         max_length,
                         frame_name,
+                        browser_url,
+                        focused_app_name,
+                        in_call,
+                        virtual_desktop,
+                        selected_text,
                     )
                     .await?;
                 results.extend(ocr_results.into_iter().map(SearchResult::OCR));
             }
             ContentType::Audio => {
-                if app_name.is_none() && window_name.is_none() {
+                if app_name.is_none()
+                    && window_name.is_none()
+                    && browser_url.is_none()
+                    && focused_app_name.is_none()
+                    && in_call.is_none()
+                    && virtual_desktop.is_none()
+                    && selected_text.is_none()
+                {
                     let audio_results = self
                         .search_audio(
                             query,
@@ -577,6 +1126,9 @@ impl DatabaseManager {
                             min_length,
                             max_length,
                             speaker_ids,
+                            language,
+                            transcription_engine,
+                            model_version,
                         )
                         .await?;
                     results.extend(audio_results.into_iter().map(SearchResult::Audio));
@@ -607,6 +1159,9 @@ impl DatabaseManager {
                         min_length,
                         max_length,
                         speaker_ids,
+                        language,
+                        transcription_engine,
+                        model_version,
                     )
                     .await?;
                 let ui_results = self
@@ -637,6 +1192,11 @@ impl DatabaseManager {
                         min_length,
                         max_length,
                         frame_name,
+                        browser_url,
+                        focused_app_name,
+                        in_call,
+                        virtual_desktop,
+                        selected_text,
                     )
                     .await?;
                 let ui_results = self
@@ -665,6 +1225,9 @@ impl DatabaseManager {
                         min_length,
                         max_length,
                         speaker_ids,
+                        language,
+                        transcription_engine,
+                        model_version,
                     )
                     .await?;
                 let ocr_results = self
@@ -679,6 +1242,11 @@ impl DatabaseManager {
                         min_length,
                         max_length,
                         frame_name,
+                        browser_url,
+                        focused_app_name,
+                        in_call,
+                        virtual_desktop,
+                        selected_text,
                     )
                     .await?;
 
@@ -725,52 +1293,16 @@ impl DatabaseManager {
         min_length: Option<usize>,
         max_length: Option<usize>,
         frame_name: Option<&str>,
+        browser_url: Option<&str>,
+        focused_app_name: Option<&str>,
+        in_call: Option<bool>,
+        virtual_desktop: Option<u32>,
+        selected_text: Option<&str>,
     ) -> Result<Vec<OCRResult>, sqlx::Error> {
-        let base_sql = if query.is_empty() {
-            "ocr_text"
-        } else {
-            "ocr_text_fts JOIN ocr_text ON ocr_text_fts.frame_id = ocr_text.frame_id"
-        };
-
-        let where_clause = if query.is_empty() {
-            "WHERE 1=1"
-        } else {
-            "WHERE ocr_text_fts MATCH ?1"
+        let Some((query, text_match)) = self.text_query(query, FtsTable::Ocr) else {
+            return Ok(Vec::new());
         };
-
-        let sql = format!(
-            r#"
-            SELECT
-                ocr_text.frame_id,
-                ocr_text.text as ocr_text,
-                ocr_text.text_json,
-                frames.timestamp,
-                frames.name as frame_name,
-                video_chunks.file_path,
-                frames.offset_index,
-                ocr_text.app_name,
-                ocr_text.ocr_engine,
-                ocr_text.window_name,
-                GROUP_CONCAT(tags.name, ',') as tags
-            FROM {}
-            JOIN frames ON ocr_text.frame_id = frames.id
-            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
-            LEFT JOIN vision_tags ON frames.id = vision_tags.vision_id
-            LEFT JOIN tags ON vision_tags.tag_id = tags.id
-            {}
-                AND (?2 IS NULL OR frames.timestamp >= ?2)
-                AND (?3 IS NULL OR frames.timestamp <= ?3)
-                AND (?4 IS NULL OR ocr_text.app_name LIKE '%' || ?4 || '%')
-                AND (?5 IS NULL OR ocr_text.window_name LIKE '%' || ?5 || '%')
-                AND (?6 IS NULL OR COALESCE(ocr_text.text_length,LENGTH(ocr_text.text)) >= ?6)
-                AND (?7 IS NULL OR COALESCE(ocr_text.text_length,LENGTH(ocr_text.text)) <= ?7)
-                AND (?8 IS NULL OR frames.name LIKE '%' || ?8 || '%' COLLATE NOCASE)
-            GROUP BY ocr_text.frame_id
-            ORDER BY frames.timestamp DESC
-            LIMIT ?9 OFFSET ?10
-            "#,
-            base_sql, where_clause
-        );
+        let sql = ocr_search_sql(text_match);
 
         let raw_results: Vec<OCRResultRaw> = sqlx::query_as(&sql)
             .bind(query)
@@ -783,28 +1315,15 @@ impl DatabaseManager {
             .bind(frame_name)
             .bind(limit)
             .bind(offset)
+            .bind(browser_url)
+            .bind(focused_app_name)
+            .bind(in_call)
+            .bind(virtual_desktop)
+            .bind(selected_text)
             .fetch_all(&self.pool)
             .await?;
 
-        Ok(raw_results
-            .into_iter()
-            .map(|raw| OCRResult {
-                frame_id: raw.frame_id,
-                ocr_text: raw.ocr_text,
-                text_json: raw.text_json,
-                timestamp: raw.timestamp,
-                frame_name: raw.frame_name,
-                file_path: raw.file_path,
-                offset_index: raw.offset_index,
-                app_name: raw.app_name,
-                ocr_engine: raw.ocr_engine,
-                window_name: raw.window_name,
-                tags: raw
-                    .tags
-                    .map(|t| t.split(',').map(String::from).collect())
-                    .unwrap_or_default(),
-            })
-            .collect())
+        Ok(raw_results.into_iter().map(OCRResult::from).collect())
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -818,6 +1337,9 @@ impl DatabaseManager {
         min_length: Option<usize>,
         max_length: Option<usize>,
         speaker_ids: Option<Vec<i64>>,
+        language: Option<&str>,
+        transcription_engine: Option<&str>,
+        model_version: Option<&str>,
     ) -> Result<Vec<AudioResult>, sqlx::Error> {
         let mut json_array: String = "[]".to_string();
         if let Some(ids) = speaker_ids {
@@ -826,51 +1348,10 @@ impl DatabaseManager {
             }
         }
 
-        let base_sql = if query.is_empty() {
-            "audio_transcriptions"
-        } else {
-            "audio_transcriptions_fts JOIN audio_transcriptions ON audio_transcriptions_fts.audio_chunk_id = audio_transcriptions.audio_chunk_id"
-        };
-
-        let where_clause = if query.is_empty() {
-            "WHERE 1=1"
-        } else {
-            "WHERE audio_transcriptions_fts MATCH ?1"
+        let Some((query, text_match)) = self.text_query(query, FtsTable::Audio) else {
+            return Ok(Vec::new());
         };
-
-        let sql = format!(
-            r#"
-            SELECT
-                audio_transcriptions.audio_chunk_id,
-                audio_transcriptions.transcription,
-                audio_transcriptions.timestamp,
-                audio_chunks.file_path,
-                audio_transcriptions.offset_index,
-                audio_transcriptions.transcription_engine,
-                GROUP_CONCAT(tags.name, ',') as tags,
-                audio_transcriptions.device as device_name,
-                audio_transcriptions.is_input_device,
-                audio_transcriptions.speaker_id,
-                audio_transcriptions.start_time,
-                audio_transcriptions.end_time
-            FROM {}
-            JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
-            LEFT JOIN speakers on audio_transcriptions.speaker_id = speakers.id
-            LEFT JOIN audio_tags ON audio_chunks.id = audio_tags.audio_chunk_id
-            LEFT JOIN tags ON audio_tags.tag_id = tags.id
-            {}
-                AND (?2 IS NULL OR audio_transcriptions.timestamp >= ?2)
-                AND (?3 IS NULL OR audio_transcriptions.timestamp <= ?3)
-                AND (?4 IS NULL OR COALESCE(audio_transcriptions.text_length, LENGTH(audio_transcriptions.transcription)) >= ?4)
-                AND (?5 IS NULL OR COALESCE(audio_transcriptions.text_length, LENGTH(audio_transcriptions.transcription)) <= ?5)
-                AND (speakers.id IS NULL OR speakers.hallucination = 0)
-                AND (json_array_length(?6) = 0 OR audio_transcriptions.speaker_id IN (SELECT value FROM json_each(?6)))
-            GROUP BY audio_transcriptions.audio_chunk_id, audio_transcriptions.offset_index
-            ORDER BY audio_transcriptions.timestamp DESC
-            LIMIT ?7 OFFSET ?8
-            "#,
-            base_sql, where_clause
-        );
+        let sql = audio_search_sql(text_match);
 
         let raw_results: Vec<AudioResultRaw> = sqlx::query_as(&sql)
             .bind(query)
@@ -881,42 +1362,342 @@ impl DatabaseManager {
             .bind(json_array)
             .bind(limit)
             .bind(offset)
+            .bind(language)
+            .bind(transcription_engine)
+            .bind(model_version)
             .fetch_all(&self.pool)
             .await?;
 
-        let futures = raw_results.into_iter().map(|raw| async move {
-            let speaker = match raw.speaker_id {
-                Some(id) => match self.get_speaker_by_id(id).await {
-                    Ok(speaker) => Some(speaker),
-                    Err(_) => None,
+        Ok(join_all(raw_results.into_iter().map(|raw| self.audio_result(raw))).await)
+    }
+
+    /// Result of a transcription row, with its speaker
+    async fn audio_result(&self, raw: AudioResultRaw) -> AudioResult {
+        let speaker = match raw.speaker_id {
+            Some(id) => match self.get_speaker_by_id(id).await {
+                Ok(speaker) => Some(speaker),
+                Err(_) => None,
+            },
+            None => None,
+        };
+
+        AudioResult {
+            audio_chunk_id: raw.audio_chunk_id,
+            transcription: raw.transcription,
+            timestamp: raw.timestamp,
+            file_path: raw.file_path,
+            offset_index: raw.offset_index,
+            transcription_engine: raw.transcription_engine,
+            tags: raw
+                .tags
+                .map(|t| t.split(',').map(String::from).collect())
+                .unwrap_or_default(),
+            device_name: raw.device_name,
+            device_type: if raw.is_input_device {
+                DeviceType::Input
+            } else {
+                DeviceType::Output
+            },
+            speaker,
+            start_time: raw.start_time,
+            end_time: raw.end_time,
+            confidence: raw.avg_logprob.zip(raw.no_speech_prob).map(
+                |(avg_logprob, no_speech_prob)| SegmentConfidence {
+                    avg_logprob,
+                    no_speech_prob,
                 },
-                None => None,
+            ),
+            language: raw
+                .language
+                .zip(raw.language_probability)
+                .map(|(code, probability)| DetectedLanguage {
+                    code,
+                    probability: probability as f32,
+                }),
+            hallucination: raw.hallucination.and_then(|h| h.parse().ok()),
+            audio_duration: raw.audio_duration,
+            captured_until: raw.captured_until,
+            latency: raw.latency,
+            model_version: raw.model_version,
+        }
+    }
+
+    /// Runs a search sub-query by sub-query like `search` does, timing each one and
+    /// collecting its query plan, indexes used and full table scans
+    #[allow(clippy::too_many_arguments)]
+    pub async fn explain_search(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        speaker_ids: Option<Vec<i64>>,
+        frame_name: Option<&str>,
+        language: Option<&str>,
+        browser_url: Option<&str>,
+        focused_app_name: Option<&str>,
+        in_call: Option<bool>,
+        virtual_desktop: Option<u32>,
+        selected_text: Option<&str>,
+        transcription_engine: Option<&str>,
+        model_version: Option<&str>,
+    ) -> Result<QueryPlanReport, sqlx::Error> {
+        let total_start = Instant::now();
+        let ocr_query = self.text_query(query, FtsTable::Ocr);
+        let audio_query = self.text_query(query, FtsTable::Audio);
+        let ui_query = self.text_query(query, FtsTable::Ui);
+        let has_app_or_window_filter = app_name.is_some()
+            || window_name.is_some()
+            || browser_url.is_some()
+            || focused_app_name.is_some()
+            || in_call.is_some()
+            || virtual_desktop.is_some()
+            || selected_text.is_some();
+
+        // same sub-queries and limits as `search`
+        let (run_ocr, run_audio, run_ui, stage_limit) = match content_type {
+            ContentType::All => (
+                true,
+                !has_app_or_window_filter && frame_name.is_none(),
+                true,
+                limit,
+            ),
+            ContentType::OCR => (true, false, false, limit),
+            ContentType::Audio => (false, !has_app_or_window_filter, false, limit),
+            ContentType::UI => (false, false, true, limit),
+            ContentType::AudioAndUi => (false, true, true, limit / 2),
+            ContentType::OcrAndUi => (true, false, true, limit / 2),
+            ContentType::AudioAndOcr => (true, true, false, limit / 2),
+        };
+        let explain = |sql: String| format!("EXPLAIN QUERY PLAN {}", sql);
+        let mut stages = Vec::new();
+
+        if let Some((normalized, text_match)) = ocr_query.as_ref().filter(|_| run_ocr) {
+            let started = Instant::now();
+            let rows = self
+                .search_ocr(
+                    query,
+                    stage_limit,
+                    offset,
+                    start_time,
+                    end_time,
+                    app_name,
+                    window_name,
+                    min_length,
+                    max_length,
+                    frame_name,
+                    browser_url,
+                    focused_app_name,
+                    in_call,
+                    virtual_desktop,
+                    selected_text,
+                )
+                .await?
+                .len();
+            let elapsed = started.elapsed();
+            let plan: Vec<(i64, i64, i64, String)> =
+                sqlx::query_as(&explain(ocr_search_sql(*text_match)))
+                    .bind(normalized)
+                    .bind(start_time)
+                    .bind(end_time)
+                    .bind(app_name)
+                    .bind(window_name)
+                    .bind(min_length.map(|l| l as i64))
+                    .bind(max_length.map(|l| l as i64))
+                    .bind(frame_name)
+                    .bind(stage_limit)
+                    .bind(offset)
+                    .bind(browser_url)
+                    .bind(focused_app_name)
+                    .bind(in_call)
+                    .bind(virtual_desktop)
+                    .bind(selected_text)
+                    .fetch_all(&self.pool)
+                    .await?;
+            stages.push(self.stage_report("ocr", elapsed, rows, plan).await?);
+        }
+
+        if let Some((normalized, text_match)) = audio_query.as_ref().filter(|_| run_audio) {
+            let json_array = match &speaker_ids {
+                Some(ids) if !ids.is_empty() => serde_json::to_string(ids).unwrap_or_default(),
+                _ => "[]".to_string(),
             };
+            let started = Instant::now();
+            let rows = self
+                .search_audio(
+                    query,
+                    stage_limit,
+                    offset,
+                    start_time,
+                    end_time,
+                    min_length,
+                    max_length,
+                    speaker_ids.clone(),
+                    language,
+                    transcription_engine,
+                    model_version,
+                )
+                .await?
+                .len();
+            let elapsed = started.elapsed();
+            let plan: Vec<(i64, i64, i64, String)> =
+                sqlx::query_as(&explain(audio_search_sql(*text_match)))
+                    .bind(normalized)
+                    .bind(start_time)
+                    .bind(end_time)
+                    .bind(min_length.map(|l| l as i64))
+                    .bind(max_length.map(|l| l as i64))
+                    .bind(json_array)
+                    .bind(stage_limit)
+                    .bind(offset)
+                    .bind(language)
+                    .bind(transcription_engine)
+                    .bind(model_version)
+                    .fetch_all(&self.pool)
+                    .await?;
+            stages.push(self.stage_report("audio", elapsed, rows, plan).await?);
+        }
 
-            Ok::<AudioResult, sqlx::Error>(AudioResult {
-                audio_chunk_id: raw.audio_chunk_id,
-                transcription: raw.transcription,
-                timestamp: raw.timestamp,
-                file_path: raw.file_path,
-                offset_index: raw.offset_index,
-                transcription_engine: raw.transcription_engine,
-                tags: raw
-                    .tags
-                    .map(|t| t.split(',').map(String::from).collect())
-                    .unwrap_or_default(),
-                device_name: raw.device_name,
-                device_type: if raw.is_input_device {
-                    DeviceType::Input
-                } else {
-                    DeviceType::Output
-                },
-                speaker,
-                start_time: raw.start_time,
-                end_time: raw.end_time,
-            })
+        if let Some((normalized, text_match)) = ui_query.as_ref().filter(|_| run_ui) {
+            let started = Instant::now();
+            let rows = self
+                .search_ui_monitoring(
+                    query,
+                    app_name,
+                    window_name,
+                    start_time,
+                    end_time,
+                    stage_limit,
+                    offset,
+                )
+                .await?
+                .len();
+            let elapsed = started.elapsed();
+            let plan: Vec<(i64, i64, i64, String)> =
+                sqlx::query_as(&explain(ui_search_sql(*text_match)))
+                    .bind(normalized)
+                    .bind(start_time)
+                    .bind(end_time)
+                    .bind(app_name)
+                    .bind(window_name)
+                    .bind(stage_limit)
+                    .bind(offset)
+                    .fetch_all(&self.pool)
+                    .await?;
+            stages.push(self.stage_report("ui", elapsed, rows, plan).await?);
+        }
+
+        // the count runs several statements depending on the content type, only timed
+        let started = Instant::now();
+        let total = self
+            .count_search_results(
+                query,
+                content_type.clone(),
+                start_time,
+                end_time,
+                app_name,
+                window_name,
+                min_length,
+                max_length,
+                speaker_ids,
+                frame_name,
+                language,
+                browser_url,
+                focused_app_name,
+                in_call,
+                virtual_desktop,
+                selected_text,
+                transcription_engine,
+                model_version,
+            )
+            .await?;
+        stages.push(StageReport {
+            stage: "count".to_string(),
+            elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+            rows_returned: total,
+            plan: Vec::new(),
+            indexes_used: Vec::new(),
+            full_scans: Vec::new(),
         });
 
-        Ok(try_join_all(futures).await?.into_iter().collect())
+        // the renderings differ only by the columns fields target, report the first one
+        let normalized = ocr_query
+            .or(audio_query)
+            .or(ui_query)
+            .map(|(normalized, _)| normalized)
+            .unwrap_or_default();
+        let shape = SearchShape {
+            has_text_query: !normalized.is_empty(),
+            has_time_range: start_time.is_some() || end_time.is_some(),
+            has_app_or_window_filter,
+            all_content_types: content_type == ContentType::All,
+        };
+        let suggestions = query_plan::suggestions(&shape, &stages);
+
+        Ok(QueryPlanReport {
+            normalized_query: normalized,
+            total_ms: total_start.elapsed().as_secs_f64() * 1000.0,
+            stages,
+            suggestions,
+        })
+    }
+
+    async fn stage_report(
+        &self,
+        stage: &str,
+        elapsed: Duration,
+        rows_returned: usize,
+        plan: Vec<(i64, i64, i64, String)>,
+    ) -> Result<StageReport, sqlx::Error> {
+        let plan: Vec<String> = plan.into_iter().map(|(_, _, _, detail)| detail).collect();
+        let (indexes_used, scanned_tables) = query_plan::parse_plan(&plan);
+
+        let mut full_scans = Vec::with_capacity(scanned_tables.len());
+        for table in scanned_tables {
+            let rows = self.estimated_rows(&table).await;
+            full_scans.push(TableScan { table, rows });
+        }
+
+        Ok(StageReport {
+            stage: stage.to_string(),
+            elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+            rows_returned,
+            plan,
+            indexes_used,
+            full_scans,
+        })
+    }
+
+    /// Rows of `table` without counting them, which would scan it on every explain: the count
+    /// `ANALYZE` left in sqlite_stat1, else the largest rowid. 0 when neither is known
+    async fn estimated_rows(&self, table: &str) -> i64 {
+        let analyzed: Option<String> =
+            sqlx::query_scalar("SELECT stat FROM sqlite_stat1 WHERE tbl = ?1 LIMIT 1")
+                .bind(table)
+                .fetch_optional(&self.pool)
+                .await
+                .ok()
+                .flatten();
+        if let Some(rows) = analyzed
+            .as_deref()
+            .and_then(|stat| stat.split_whitespace().next())
+            .and_then(|rows| rows.parse().ok())
+        {
+            return rows;
+        }
+        // table names come from sqlite's own plan and are plain identifiers
+        sqlx::query_scalar::<_, Option<i64>>(&format!("SELECT MAX(rowid) FROM {}", table))
+            .fetch_one(&self.pool)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or(0)
     }
 
     pub async fn get_frame(&self, frame_id: i64) -> Result<Option<(String, i64)>, sqlx::Error> {
@@ -938,6 +1719,41 @@ impl DatabaseManager {
         .await
     }
 
+    /// Returns the file path and the concatenated transcription of an audio chunk
+    pub async fn get_audio_chunk_with_transcription(
+        &self,
+        audio_chunk_id: i64,
+    ) -> Result<Option<(String, String)>, sqlx::Error> {
+        sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT
+                audio_chunks.file_path,
+                COALESCE(GROUP_CONCAT(audio_transcriptions.transcription, ' '), '')
+            FROM
+                audio_chunks
+            LEFT JOIN
+                audio_transcriptions ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            WHERE
+                audio_chunks.id = ?1
+            GROUP BY
+                audio_chunks.id
+            "#,
+        )
+        .bind(audio_chunk_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Returns the OCR text recorded for a frame, joined across windows
+    pub async fn get_frame_ocr_text(&self, frame_id: i64) -> Result<String, sqlx::Error> {
+        sqlx::query_scalar::<_, String>(
+            "SELECT COALESCE(GROUP_CONCAT(text, '\n'), '') FROM ocr_text WHERE frame_id = ?1",
+        )
+        .bind(frame_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub async fn count_search_results(
         &self,
@@ -951,7 +1767,47 @@ impl DatabaseManager {
         max_length: Option<usize>,
         speaker_ids: Option<Vec<i64>>,
         frame_name: Option<&str>,
+        language: Option<&str>,
+        browser_url: Option<&str>,
+        focused_app_name: Option<&str>,
+        in_call: Option<bool>,
+        virtual_desktop: Option<u32>,
+        selected_text: Option<&str>,
+        transcription_engine: Option<&str>,
+        model_version: Option<&str>,
     ) -> Result<usize, sqlx::Error> {
+        let ocr_query = self.text_query(query, FtsTable::Ocr);
+        let audio_query = self.text_query(query, FtsTable::Audio);
+        let ui_query = self.text_query(query, FtsTable::Ui);
+        let (ocr_table, ocr_match) = count_source(
+            ocr_query.as_ref(),
+            "ocr_text",
+            "ocr_text_fts JOIN ocr_text ON ocr_text_fts.frame_id = ocr_text.frame_id",
+            "ocr_text_fts MATCH ?1",
+            "ocr_text.text LIKE '%' || ?1 || '%'",
+        );
+        let (audio_table, audio_match) = count_source(
+            audio_query.as_ref(),
+            "audio_transcriptions",
+            "audio_transcriptions_fts JOIN audio_transcriptions ON audio_transcriptions_fts.audio_chunk_id = audio_transcriptions.audio_chunk_id",
+            "audio_transcriptions_fts MATCH ?1",
+            "audio_transcriptions.transcription LIKE '%' || ?1 || '%'",
+        );
+        let (ui_table, ui_match) = count_source(
+            ui_query.as_ref(),
+            "ui_monitoring",
+            "ui_monitoring_fts JOIN ui_monitoring ON ui_monitoring_fts.ui_id = ui_monitoring.id",
+            "ui_monitoring_fts MATCH ?1",
+            "ui_monitoring.text_output LIKE '%' || ?1 || '%'",
+        );
+        let text = |query: Option<(String, TextMatch)>| query.map(|(text, _)| text);
+        let audio_query = text(audio_query);
+        let ui_query = text(ui_query);
+        let query = match content_type {
+            ContentType::Audio => audio_query.clone(),
+            ContentType::UI => ui_query.clone(),
+            _ => text(ocr_query),
+        };
         let json_array = if let Some(ids) = speaker_ids {
             if !ids.is_empty() {
                 serde_json::to_string(&ids).unwrap_or_default()
@@ -977,17 +1833,14 @@ impl DatabaseManager {
                         AND (?6 IS NULL OR COALESCE(ocr_text.text_length, LENGTH(ocr_text.text)) >= ?6)
                         AND (?7 IS NULL OR COALESCE(ocr_text.text_length, LENGTH(ocr_text.text)) <= ?7)
                         AND (?8 IS NULL OR frames.name LIKE '%' || ?8 || '%' COLLATE NOCASE)
+                        AND (?11 IS NULL OR frames.browser_url LIKE '%' || ?11 || '%' COLLATE NOCASE)
+                        AND (?12 IS NULL OR frames.focused_app_name LIKE '%' || ?12 || '%' COLLATE NOCASE)
+                        AND (?13 IS NULL OR frames.in_call = ?13)
+                        AND (?14 IS NULL OR frames.virtual_desktop = ?14)
+                        AND (?15 IS NULL OR frames.selected_text LIKE '%' || ?15 || '%' COLLATE NOCASE)
                     "#,
-                    table = if query.is_empty() {
-                        "ocr_text"
-                    } else {
-                        "ocr_text_fts JOIN ocr_text ON ocr_text_fts.frame_id = ocr_text.frame_id"
-                    },
-                    match_condition = if query.is_empty() {
-                        "1=1"
-                    } else {
-                        "ocr_text_fts MATCH ?1"
-                    }
+                    table = ocr_table,
+                    match_condition = ocr_match
                 )
             }
             ContentType::Audio => {
@@ -1001,17 +1854,12 @@ impl DatabaseManager {
                         AND (?4 IS NULL OR COALESCE(audio_transcriptions.text_length, LENGTH(audio_transcriptions.transcription)) >= ?4)
                         AND (?5 IS NULL OR COALESCE(audio_transcriptions.text_length, LENGTH(audio_transcriptions.transcription)) <= ?5)
                         AND (json_array_length(?6) = 0 OR audio_transcriptions.speaker_id IN (SELECT value FROM json_each(?6)))
+                        AND (?7 IS NULL OR audio_transcriptions.language = ?7)
+                        AND (?8 IS NULL OR audio_transcriptions.transcription_engine = ?8)
+                        AND (?9 IS NULL OR audio_transcriptions.model_version = ?9)
                     "#,
-                    table = if query.is_empty() {
-                        "audio_transcriptions"
-                    } else {
-                        "audio_transcriptions_fts JOIN audio_transcriptions ON audio_transcriptions_fts.audio_chunk_id = audio_transcriptions.audio_chunk_id"
-                    },
-                    match_condition = if query.is_empty() {
-                        "1=1"
-                    } else {
-                        "audio_transcriptions_fts MATCH ?1"
-                    }
+                    table = audio_table,
+                    match_condition = audio_match
                 )
             }
             ContentType::UI => {
@@ -1027,16 +1875,8 @@ impl DatabaseManager {
                         AND (?6 IS NULL OR COALESCE(ui_monitoring.text_length, LENGTH(ui_monitoring.text_output)) >= ?6)
                         AND (?7 IS NULL OR COALESCE(ui_monitoring.text_length, LENGTH(ui_monitoring.text_output)) <= ?7)
                     "#,
-                    table = if query.is_empty() {
-                        "ui_monitoring"
-                    } else {
-                        "ui_monitoring_fts JOIN ui_monitoring ON ui_monitoring_fts.ui_id = ui_monitoring.id"
-                    },
-                    match_condition = if query.is_empty() {
-                        "1=1"
-                    } else {
-                        "ui_monitoring_fts MATCH ?1"
-                    }
+                    table = ui_table,
+                    match_condition = ui_match
                 )
             }
             ContentType::All => {
@@ -1055,6 +1895,11 @@ impl DatabaseManager {
                             AND (?6 IS NULL OR COALESCE(ocr_text.text_length, LENGTH(ocr_text.text)) >= ?6)
                             AND (?7 IS NULL OR COALESCE(ocr_text.text_length, LENGTH(ocr_text.text)) <= ?7)
                             AND (?8 IS NULL OR frames.name LIKE '%' || ?8 || '%' COLLATE NOCASE)
+                            AND (?11 IS NULL OR frames.browser_url LIKE '%' || ?11 || '%' COLLATE NOCASE)
+                            AND (?12 IS NULL OR frames.focused_app_name LIKE '%' || ?12 || '%' COLLATE NOCASE)
+                            AND (?13 IS NULL OR frames.in_call = ?13)
+                            AND (?14 IS NULL OR frames.virtual_desktop = ?14)
+                            AND (?15 IS NULL OR frames.selected_text LIKE '%' || ?15 || '%' COLLATE NOCASE)
                         UNION ALL
                         -- Audio part
                         SELECT DISTINCT audio_transcriptions.id
@@ -1066,6 +1911,10 @@ impl DatabaseManager {
                             AND (?7 IS NULL OR COALESCE(audio_transcriptions.text_length, LENGTH(audio_transcriptions.transcription)) <= ?7)
                             AND audio_transcriptions.transcription != ''
                             AND (json_array_length(?9) = 0 OR audio_transcriptions.speaker_id IN (SELECT value FROM json_each(?9)))
+                            AND (?10 IS NULL OR audio_transcriptions.language = ?10)
+                            AND (?18 IS NULL OR audio_transcriptions.transcription_engine = ?18)
+                            AND (?19 IS NULL OR audio_transcriptions.model_version = ?19)
+                            AND ?11 IS NULL AND ?12 IS NULL AND ?13 IS NULL AND ?14 IS NULL AND ?15 IS NULL
                         UNION ALL
                         -- UI part
                         SELECT DISTINCT ui_monitoring.id
@@ -1078,37 +1927,11 @@ impl DatabaseManager {
                             AND (?6 IS NULL OR COALESCE(ui_monitoring.text_length, LENGTH(ui_monitoring.text_output)) >= ?6)
                             AND (?7 IS NULL OR COALESCE(ui_monitoring.text_length, LENGTH(ui_monitoring.text_output)) <= ?7)
                             AND ui_monitoring.text_output != ''
+                            AND ?11 IS NULL AND ?12 IS NULL AND ?13 IS NULL AND ?14 IS NULL AND ?15 IS NULL
                     )"#,
-                    ocr_table = if query.is_empty() {
-                        "ocr_text"
-                    } else {
-                        "ocr_text_fts JOIN ocr_text ON ocr_text_fts.frame_id = ocr_text.frame_id"
-                    },
-                    ocr_match = if query.is_empty() {
-                        "1=1"
-                    } else {
-                        "ocr_text_fts MATCH ?1"
-                    },
-                    audio_table = if query.is_empty() {
-                        "audio_transcriptions"
-                    } else {
-                        "audio_transcriptions_fts JOIN audio_transcriptions ON audio_transcriptions_fts.audio_chunk_id = audio_transcriptions.audio_chunk_id"
-                    },
-                    audio_match = if query.is_empty() {
-                        "1=1"
-                    } else {
-                        "audio_transcriptions_fts MATCH ?1"
-                    },
-                    ui_table = if query.is_empty() {
-                        "ui_monitoring"
-                    } else {
-                        "ui_monitoring_fts JOIN ui_monitoring ON ui_monitoring_fts.ui_id = ui_monitoring.id"
-                    },
-                    ui_match = if query.is_empty() {
-                        "1=1"
-                    } else {
-                        "ui_monitoring_fts MATCH ?1"
-                    }
+                    // each table matches the query as rendered for its own columns
+                    audio_match = audio_match.replace("?1", "?16"),
+                    ui_match = ui_match.replace("?1", "?17"),
                 )
             }
             _ => return Ok(0),
@@ -1123,6 +1946,9 @@ impl DatabaseManager {
                     .bind(min_length.map(|l| l as i64))
                     .bind(max_length.map(|l| l as i64))
                     .bind(json_array)
+                    .bind(language)
+                    .bind(transcription_engine)
+                    .bind(model_version)
                     .fetch_one(&self.pool)
                     .await?
             }
@@ -1137,6 +1963,16 @@ impl DatabaseManager {
                     .bind(min_length.map(|l| l as i64))
                     .bind(max_length.map(|l| l as i64))
                     .bind(json_array)
+                    .bind(language)
+                    .bind(browser_url)
+                    .bind(focused_app_name)
+                    .bind(in_call)
+                    .bind(virtual_desktop)
+                    .bind(selected_text)
+                    .bind(audio_query)
+                    .bind(ui_query)
+                    .bind(transcription_engine)
+                    .bind(model_version)
                     .fetch_one(&self.pool)
                     .await?
             }
@@ -1526,45 +2362,10 @@ impl DatabaseManager {
         limit: u32,
         offset: u32,
     ) -> Result<Vec<UiContent>, sqlx::Error> {
-        let base_sql = if query.is_empty() {
-            "ui_monitoring"
-        } else {
-            "ui_monitoring_fts JOIN ui_monitoring ON ui_monitoring_fts.ui_id = ui_monitoring.id"
-        };
-
-        let where_clause = if query.is_empty() {
-            "WHERE 1=1"
-        } else {
-            "WHERE ui_monitoring_fts MATCH ?1"
+        let Some((query, text_match)) = self.text_query(query, FtsTable::Ui) else {
+            return Ok(Vec::new());
         };
-
-        let sql = format!(
-            r#"
-            SELECT
-                ui_monitoring.id,
-                ui_monitoring.text_output,
-                ui_monitoring.timestamp,
-                ui_monitoring.app,
-                ui_monitoring.window,
-                ui_monitoring.initial_traversal_at,
-                video_chunks.file_path,
-                frames.offset_index
-            FROM {}
-            LEFT JOIN frames ON
-                frames.timestamp BETWEEN
-                    datetime(ui_monitoring.timestamp, '-1 seconds')
-                    AND datetime(ui_monitoring.timestamp, '+1 seconds')
-            LEFT JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
-            {}
-                AND (?2 IS NULL OR ui_monitoring.timestamp >= ?2)
-                AND (?3 IS NULL OR ui_monitoring.timestamp <= ?3)
-                AND (?4 IS NULL OR ui_monitoring.app LIKE '%' || ?4 || '%')
-                AND (?5 IS NULL OR ui_monitoring.window LIKE '%' || ?5 || '%')
-            ORDER BY ui_monitoring.timestamp DESC
-            LIMIT ?6 OFFSET ?7
-            "#,
-            base_sql, where_clause
-        );
+        let sql = ui_search_sql(text_match);
 
         sqlx::query_as(&sql)
             .bind(query)
@@ -1578,64 +2379,301 @@ impl DatabaseManager {
             .await
     }
 
-    // Add tags to UI monitoring entry
-    pub async fn add_tags_to_ui_monitoring(
+    /// Elements whose label or text contains `query`, the newest first
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_ui_elements(
         &self,
-        ui_monitoring_id: i64,
-        tag_ids: &[i64],
-    ) -> Result<(), anyhow::Error> {
-        for tag_id in tag_ids {
-            sqlx::query(
-                "INSERT OR IGNORE INTO ui_monitoring_tags (ui_monitoring_id, tag_id) VALUES (?, ?)",
-            )
-            .bind(ui_monitoring_id)
-            .bind(tag_id)
-            .execute(&self.pool)
-            .await?;
-        }
-        Ok(())
+        query: &str,
+        app_name: Option<&str>,
+        role: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<UiElementResult>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                ui_elements.id,
+                ui_elements.frame_id,
+                frames.timestamp,
+                ui_elements.app_name,
+                ui_elements.window_name,
+                ui_elements.role,
+                ui_elements.label,
+                ui_elements.text,
+                ui_elements.depth,
+                ui_elements.x,
+                ui_elements.y,
+                ui_elements.width,
+                ui_elements.height
+            FROM ui_elements
+            JOIN frames ON ui_elements.frame_id = frames.id
+            WHERE (?1 = '' OR ui_elements.label LIKE '%' || ?1 || '%' OR ui_elements.text LIKE '%' || ?1 || '%')
+                AND (?2 IS NULL OR ui_elements.app_name LIKE '%' || ?2 || '%')
+                AND (?3 IS NULL OR ui_elements.role = ?3)
+                AND (?4 IS NULL OR frames.timestamp >= ?4)
+                AND (?5 IS NULL OR frames.timestamp <= ?5)
+            ORDER BY frames.timestamp DESC, ui_elements.id
+            LIMIT ?6 OFFSET ?7
+            "#,
+        )
+        .bind(query)
+        .bind(app_name)
+        .bind(role)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
     }
 
-    // Get tags for UI monitoring entry
-    pub async fn get_ui_monitoring_tags(
-        &self,
-        ui_monitoring_id: i64,
-    ) -> Result<Vec<String>, anyhow::Error> {
-        let tags = sqlx::query_as::<_, (String,)>(
-            "SELECT t.name FROM tags t
-             JOIN ui_monitoring_tags ut ON t.id = ut.tag_id
-             WHERE ut.ui_monitoring_id = ?",
+    /// The text read in the window of `frame_id` in blocks and lines, None when its layout wasn't
+    /// stored
+    pub async fn get_frame_layout(&self, frame_id: i64) -> Result<Option<OcrLayout>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT block_index, text, x, y, width, height, words FROM ocr_lines WHERE frame_id = ?1 ORDER BY block_index, line_index",
         )
-        .bind(ui_monitoring_id)
+        .bind(frame_id)
         .fetch_all(&self.pool)
         .await?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
 
-        Ok(tags.into_iter().map(|t| t.0).collect())
+        let mut blocks: Vec<(i64, LayoutBlock)> = Vec::new();
+        for row in rows {
+            let block_index: i64 = row.get("block_index");
+            let line = LayoutLine {
+                text: row.get("text"),
+                bbox: LayoutBox {
+                    left: row.get("x"),
+                    top: row.get("y"),
+                    width: row.get("width"),
+                    height: row.get("height"),
+                },
+                words: serde_json::from_str(row.get::<&str, _>("words")).unwrap_or_default(),
+            };
+            match blocks.last_mut() {
+                Some((index, block)) if *index == block_index => {
+                    block.bbox = block.bbox.union(&line.bbox);
+                    block.lines.push(line);
+                }
+                _ => blocks.push((
+                    block_index,
+                    LayoutBlock {
+                        bbox: line.bbox,
+                        lines: vec![line],
+                    },
+                )),
+            }
+        }
+        Ok(Some(OcrLayout {
+            blocks: blocks.into_iter().map(|(_, block)| block).collect(),
+        }))
     }
 
-    pub async fn get_audio_chunks_for_speaker(
+    /// The tables and blocks of code read in the window of `frame_id`, top to bottom
+    pub async fn get_frame_structures(
         &self,
-        speaker_id: i64,
-    ) -> Result<Vec<AudioChunksResponse>, sqlx::Error> {
-        sqlx::query_as::<_, AudioChunksResponse>(
-            r#"
-            SELECT
-                ac.*,
-                at.start_time,
-                at.end_time,
-                ac.file_path
-            FROM audio_chunks ac
-            JOIN audio_transcriptions at ON ac.id = at.audio_chunk_id
-            WHERE at.speaker_id = ?
-            ORDER BY at.start_time
-            "#,
+        frame_id: i64,
+    ) -> Result<Vec<OcrStructure>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT content, x, y, width, height FROM ocr_structures WHERE frame_id = ?1 ORDER BY id",
         )
-        .bind(speaker_id)
+        .bind(frame_id)
         .fetch_all(&self.pool)
-        .await
+        .await?;
+        rows.into_iter()
+            .map(|row| {
+                Ok(OcrStructure {
+                    bbox: LayoutBox {
+                        left: row.get("x"),
+                        top: row.get("y"),
+                        width: row.get("width"),
+                        height: row.get("height"),
+                    },
+                    content: serde_json::from_str(row.get::<&str, _>("content"))
+                        .map_err(|e| sqlx::Error::Decode(e.into()))?,
+                })
+            })
+            .collect()
     }
 
-    // get unnamed speakers
+    /// Lines of OCR text containing `query` and entirely inside `region` of their window, the
+    /// newest first
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_ocr_lines(
+        &self,
+        query: &str,
+        region: Option<LayoutBox>,
+        app_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<OcrLineResult>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                ocr_lines.id,
+                ocr_lines.frame_id,
+                frames.timestamp,
+                ocr_text.app_name,
+                ocr_text.window_name,
+                ocr_lines.block_index,
+                ocr_lines.line_index,
+                ocr_lines.text,
+                ocr_lines.x,
+                ocr_lines.y,
+                ocr_lines.width,
+                ocr_lines.height
+            FROM ocr_lines
+            JOIN frames ON ocr_lines.frame_id = frames.id
+            JOIN ocr_text ON ocr_text.frame_id = frames.id
+            WHERE (?1 = '' OR ocr_lines.text LIKE '%' || ?1 || '%')
+                AND (?2 IS NULL OR (
+                    ocr_lines.x >= ?2 AND ocr_lines.y >= ?3
+                    AND ocr_lines.x + ocr_lines.width <= ?4 AND ocr_lines.y + ocr_lines.height <= ?5
+                ))
+                AND (?6 IS NULL OR ocr_text.app_name LIKE '%' || ?6 || '%')
+                AND (?7 IS NULL OR frames.timestamp >= ?7)
+                AND (?8 IS NULL OR frames.timestamp <= ?8)
+            ORDER BY frames.timestamp DESC, ocr_lines.block_index, ocr_lines.line_index
+            LIMIT ?9 OFFSET ?10
+            "#,
+        )
+        .bind(query)
+        .bind(region.map(|region| region.left))
+        .bind(region.map(|region| region.top))
+        .bind(region.map(|region| region.right()))
+        .bind(region.map(|region| region.bottom()))
+        .bind(app_name)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Input events of `kind` ("click", "keys" or "scroll"), the newest first
+    pub async fn search_input_events(
+        &self,
+        kind: Option<&str>,
+        app_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<InputEventResult>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT *
+            FROM input_events
+            WHERE (?1 IS NULL OR kind = ?1)
+                AND (?2 IS NULL OR app_name LIKE '%' || ?2 || '%')
+                AND (?3 IS NULL OR timestamp >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+            ORDER BY timestamp DESC
+            LIMIT ?5 OFFSET ?6
+            "#,
+        )
+        .bind(kind)
+        .bind(app_name)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Clicks, keypresses and scrolls of each minute between `start_time` and `end_time` that had
+    /// any, the oldest first
+    pub async fn input_activity(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<InputActivityBucket>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                strftime('%Y-%m-%dT%H:%M:00Z', timestamp) AS minute,
+                SUM(CASE WHEN kind = 'click' THEN count ELSE 0 END) AS clicks,
+                SUM(CASE WHEN kind = 'keys' THEN count ELSE 0 END) AS keypresses,
+                SUM(CASE WHEN kind = 'scroll' THEN count ELSE 0 END) AS scrolls
+            FROM input_events
+            WHERE timestamp >= ?1 AND timestamp <= ?2
+            GROUP BY minute
+            ORDER BY minute
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    // Add tags to UI monitoring entry
+    pub async fn add_tags_to_ui_monitoring(
+        &self,
+        ui_monitoring_id: i64,
+        tag_ids: &[i64],
+    ) -> Result<(), anyhow::Error> {
+        for tag_id in tag_ids {
+            sqlx::query(
+                "INSERT OR IGNORE INTO ui_monitoring_tags (ui_monitoring_id, tag_id) VALUES (?, ?)",
+            )
+            .bind(ui_monitoring_id)
+            .bind(tag_id)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    // Get tags for UI monitoring entry
+    pub async fn get_ui_monitoring_tags(
+        &self,
+        ui_monitoring_id: i64,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let tags = sqlx::query_as::<_, (String,)>(
+            "SELECT t.name FROM tags t
+             JOIN ui_monitoring_tags ut ON t.id = ut.tag_id
+             WHERE ut.ui_monitoring_id = ?",
+        )
+        .bind(ui_monitoring_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(tags.into_iter().map(|t| t.0).collect())
+    }
+
+    pub async fn get_audio_chunks_for_speaker(
+        &self,
+        speaker_id: i64,
+    ) -> Result<Vec<AudioChunksResponse>, sqlx::Error> {
+        sqlx::query_as::<_, AudioChunksResponse>(
+            r#"
+            SELECT
+                ac.*,
+                at.start_time,
+                at.end_time,
+                ac.file_path
+            FROM audio_chunks ac
+            JOIN audio_transcriptions at ON ac.id = at.audio_chunk_id
+            WHERE at.speaker_id = ?
+            ORDER BY at.start_time
+            "#,
+        )
+        .bind(speaker_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    // get unnamed speakers
     pub async fn get_unnamed_speakers(
         &self,
         limit: u32,
@@ -1891,7 +2929,7 @@ impl DatabaseManager {
     pub async fn create_video_with_frames(
         &self,
         file_path: &str,
-        frames: Vec<DynamicImage>,
+        frame_count: usize,
         metadata: VideoMetadata,
     ) -> Result<Vec<i64>, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
@@ -1914,9 +2952,9 @@ impl DatabaseManager {
                 .last_insert_rowid();
 
         // 2. Create frames with correct timestamps and default name
-        let mut frame_ids = Vec::with_capacity(frames.len());
+        let mut frame_ids = Vec::with_capacity(frame_count);
 
-        for (i, _frame) in frames.iter().enumerate() {
+        for i in 0..frame_count {
             let frame_timestamp = metadata.creation_time
                 + chrono::Duration::milliseconds((i as f64 * (1000.0 / metadata.fps)) as i64);
 
@@ -1939,8 +2977,7 @@ impl DatabaseManager {
         tx.commit().await?;
         debug!(
             "created {} frames for video chunk {}",
-            frames.len(),
-            video_chunk_id
+            frame_count, video_chunk_id
         );
 
         Ok(frame_ids)
@@ -1959,6 +2996,242 @@ impl DatabaseManager {
         Ok(())
     }
 
+    /// Stores the CLIP embedding of the image of `frame_id`
+    pub async fn insert_frame_embedding(
+        &self,
+        frame_id: i64,
+        embedding: &[f32],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR REPLACE INTO frame_embeddings (frame_id, embedding) VALUES (?1, vec_f32(?2))")
+            .bind(frame_id)
+            .bind(embedding.as_bytes())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Frames whose image embedding is the closest to `embedding`, closer than `threshold` in
+    /// cosine distance when given, nearest first
+    pub async fn search_frames_by_embedding(
+        &self,
+        embedding: &[f32],
+        threshold: Option<f32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+    ) -> Result<Vec<VisualSearchResult>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                frames.id AS frame_id,
+                frames.timestamp,
+                video_chunks.file_path,
+                frames.offset_index,
+                ocr_text.app_name,
+                ocr_text.window_name,
+                ocr_text.text AS ocr_text,
+                vec_distance_cosine(frame_embeddings.embedding, vec_f32(?1)) AS distance
+            FROM frame_embeddings
+            JOIN frames ON frame_embeddings.frame_id = frames.id
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            LEFT JOIN ocr_text ON ocr_text.frame_id = frames.id
+            WHERE (?2 IS NULL OR vec_distance_cosine(frame_embeddings.embedding, vec_f32(?1)) < ?2)
+                AND (?3 IS NULL OR frames.timestamp >= ?3)
+                AND (?4 IS NULL OR frames.timestamp <= ?4)
+            ORDER BY distance ASC
+            LIMIT ?5
+            "#,
+        )
+        .bind(embedding.as_bytes())
+        .bind(threshold)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Starts a pause of the capture for `reason` at `start_time`, returns its id
+    pub async fn insert_capture_pause(
+        &self,
+        reason: &str,
+        start_time: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query("INSERT INTO capture_pauses (reason, start_time) VALUES (?1, ?2)")
+            .bind(reason)
+            .bind(start_time)
+            .execute(&self.pool)
+            .await?
+            .last_insert_rowid();
+        Ok(id)
+    }
+
+    /// Ends the pauses going on at `end_time`, the one of `id` only when given. Pauses left open
+    /// by a crash end when the next one starts
+    pub async fn end_capture_pauses(
+        &self,
+        id: Option<i64>,
+        end_time: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE capture_pauses SET end_time = ?2 WHERE end_time IS NULL AND (?1 IS NULL OR id = ?1)",
+        )
+        .bind(id)
+        .bind(end_time)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Pauses overlapping `start_time` to `end_time`, latest first
+    pub async fn list_capture_pauses(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<CapturePause>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, reason, start_time, end_time
+            FROM capture_pauses
+            WHERE (?1 IS NULL OR end_time IS NULL OR end_time >= ?1)
+                AND (?2 IS NULL OR start_time <= ?2)
+            ORDER BY start_time DESC
+            LIMIT ?3 OFFSET ?4
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Starts a meeting session detected in the window of `app_name` titled `title`, returns its id
+    pub async fn insert_meeting_session(
+        &self,
+        app_name: &str,
+        title: &str,
+        start_time: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO meeting_sessions (app_name, title, start_time) VALUES (?1, ?2, ?3)",
+        )
+        .bind(app_name)
+        .bind(title)
+        .bind(start_time)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    /// Ends the meeting sessions going on at `end_time`, the one of `id` only when given
+    pub async fn end_meeting_sessions(
+        &self,
+        id: Option<i64>,
+        end_time: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE meeting_sessions SET end_time = ?2 WHERE end_time IS NULL AND (?1 IS NULL OR id = ?1)",
+        )
+        .bind(id)
+        .bind(end_time)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Meeting sessions overlapping `start_time` to `end_time`, latest first
+    pub async fn list_meeting_sessions(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<MeetingSession>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, app_name, title, start_time, end_time
+            FROM meeting_sessions
+            WHERE (?1 IS NULL OR end_time IS NULL OR end_time >= ?1)
+                AND (?2 IS NULL OR start_time <= ?2)
+            ORDER BY start_time DESC
+            LIMIT ?3 OFFSET ?4
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_meeting_session(
+        &self,
+        id: i64,
+    ) -> Result<Option<MeetingSession>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, app_name, title, start_time, end_time FROM meeting_sessions WHERE id = ?1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn get_transcript_segment(
+        &self,
+        id: i64,
+    ) -> Result<Option<TranscriptSegment>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, transcription, device, timestamp, start_time, end_time
+            FROM audio_transcriptions
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Frames captured from `start_time` to `end_time` on every monitor, and the last one of each
+    /// monitor before `start_time`, since `since`, which was still on screen then. Oldest first
+    pub async fn get_aligned_frames(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        since: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<AlignedFrame>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT f.id AS frame_id, f.timestamp, f.monitor_name, vc.file_path, f.offset_index,
+                f.focused_app_name AS app_name, f.focused_window_name AS window_name
+            FROM frames f
+            JOIN video_chunks vc ON vc.id = f.video_chunk_id
+            WHERE (f.timestamp >= ?1 AND f.timestamp <= ?2)
+                OR f.id IN (
+                    SELECT MAX(id)
+                    FROM frames
+                    WHERE timestamp < ?1 AND timestamp >= ?3
+                    GROUP BY monitor_name
+                )
+            ORDER BY f.timestamp ASC
+            LIMIT ?4
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
     pub async fn search_similar_embeddings(
         &self,
         embedding: Vec<f32>,
@@ -1987,6 +3260,12 @@ impl DatabaseManager {
                 ocr_text.app_name,
                 ocr_text.ocr_engine,
                 ocr_text.window_name,
+                frames.browser_url,
+                frames.focused_app_name,
+                frames.focused_window_name,
+                frames.in_call,
+                frames.virtual_desktop,
+                frames.selected_text,
                 GROUP_CONCAT(tags.name, ',') as tags
             FROM embedding_matches
             JOIN ocr_text ON embedding_matches.frame_id = ocr_text.frame_id
@@ -2007,27 +3286,206 @@ impl DatabaseManager {
             .fetch_all(&self.pool)
             .await?;
 
+        Ok(raw_results.into_iter().map(OCRResult::from).collect())
+    }
+
+    /// Stores the embedding of the text of the transcription of `id`
+    pub async fn insert_transcription_embedding(
+        &self,
+        id: i64,
+        embedding: &[f32],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO audio_transcription_embeddings (audio_transcription_id, embedding) VALUES (?1, vec_f32(?2))",
+        )
+        .bind(id)
+        .bind(embedding.as_bytes())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Text read in the frames that isn't embedded yet, by frame id, latest first
+    pub async fn ocr_text_without_embedding(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT ocr_text.frame_id, GROUP_CONCAT(ocr_text.text, char(10)) AS text
+            FROM ocr_text
+            LEFT JOIN ocr_text_embeddings ON ocr_text.frame_id = ocr_text_embeddings.frame_id
+            WHERE ocr_text_embeddings.frame_id IS NULL AND ocr_text.text != ''
+            GROUP BY ocr_text.frame_id
+            ORDER BY ocr_text.frame_id DESC
+            LIMIT ?1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Transcriptions that aren't embedded yet, by id, latest first
+    pub async fn transcriptions_without_embedding(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT audio_transcriptions.id, audio_transcriptions.transcription
+            FROM audio_transcriptions
+            LEFT JOIN audio_transcription_embeddings
+                ON audio_transcriptions.id = audio_transcription_embeddings.audio_transcription_id
+            WHERE audio_transcription_embeddings.audio_transcription_id IS NULL
+                AND audio_transcriptions.transcription != ''
+            ORDER BY audio_transcriptions.id DESC
+            LIMIT ?1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Frames whose text embedding is the closest to `embedding`, closer than `threshold` in
+    /// cosine distance when given, nearest first. Embeddings of another model, of another length,
+    /// are left out
+    pub async fn search_ocr_text_embeddings(
+        &self,
+        embedding: &[f32],
+        threshold: Option<f32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+    ) -> Result<Vec<(OCRResult, f64)>, sqlx::Error> {
+        let raw_results: Vec<OCREmbeddingMatchRaw> = sqlx::query_as(
+            r#"
+            WITH matches AS (
+                SELECT
+                    ocr_text_embeddings.frame_id,
+                    MIN(vec_distance_cosine(ocr_text_embeddings.embedding, vec_f32(?1))) AS distance
+                FROM ocr_text_embeddings
+                JOIN frames ON ocr_text_embeddings.frame_id = frames.id
+                WHERE vec_length(ocr_text_embeddings.embedding) = vec_length(vec_f32(?1))
+                    AND (?3 IS NULL OR frames.timestamp >= ?3)
+                    AND (?4 IS NULL OR frames.timestamp <= ?4)
+                GROUP BY ocr_text_embeddings.frame_id
+                HAVING ?2 IS NULL OR distance < ?2
+                ORDER BY distance ASC
+                LIMIT ?5
+            )
+            SELECT
+                ocr_text.frame_id,
+                ocr_text.text as ocr_text,
+                ocr_text.text_json,
+                frames.timestamp,
+                frames.name as frame_name,
+                video_chunks.file_path,
+                frames.offset_index,
+                ocr_text.app_name,
+                ocr_text.ocr_engine,
+                ocr_text.window_name,
+                frames.browser_url,
+                frames.focused_app_name,
+                frames.focused_window_name,
+                frames.in_call,
+                frames.virtual_desktop,
+                frames.selected_text,
+                GROUP_CONCAT(tags.name, ',') as tags,
+                matches.distance
+            FROM matches
+            JOIN ocr_text ON matches.frame_id = ocr_text.frame_id
+            JOIN frames ON ocr_text.frame_id = frames.id
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            LEFT JOIN vision_tags ON frames.id = vision_tags.vision_id
+            LEFT JOIN tags ON vision_tags.tag_id = tags.id
+            GROUP BY ocr_text.frame_id
+            ORDER BY matches.distance ASC
+            "#,
+        )
+        .bind(embedding.as_bytes())
+        .bind(threshold)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
         Ok(raw_results
             .into_iter()
-            .map(|raw| OCRResult {
-                frame_id: raw.frame_id,
-                ocr_text: raw.ocr_text,
-                text_json: raw.text_json,
-                timestamp: raw.timestamp,
-                file_path: raw.file_path,
-                offset_index: raw.offset_index,
-                app_name: raw.app_name,
-                ocr_engine: raw.ocr_engine,
-                window_name: raw.window_name,
-                frame_name: raw.frame_name,
-                tags: raw
-                    .tags
-                    .map(|t| t.split(',').map(String::from).collect())
-                    .unwrap_or_default(),
-            })
+            .map(|raw| (OCRResult::from(raw.result), raw.distance))
             .collect())
     }
 
+    /// Transcriptions whose embedding is the closest to `embedding`, like
+    /// `search_ocr_text_embeddings`
+    pub async fn search_transcription_embeddings(
+        &self,
+        embedding: &[f32],
+        threshold: Option<f32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+    ) -> Result<Vec<(AudioResult, f64)>, sqlx::Error> {
+        let raw_results: Vec<AudioEmbeddingMatchRaw> = sqlx::query_as(
+            r#"
+            SELECT
+                audio_transcriptions.audio_chunk_id,
+                audio_transcriptions.transcription,
+                audio_transcriptions.timestamp,
+                audio_chunks.file_path,
+                audio_transcriptions.offset_index,
+                audio_transcriptions.transcription_engine,
+                GROUP_CONCAT(tags.name, ',') as tags,
+                audio_transcriptions.device as device_name,
+                audio_transcriptions.is_input_device,
+                audio_transcriptions.speaker_id,
+                audio_transcriptions.start_time,
+                audio_transcriptions.end_time,
+                audio_transcriptions.avg_logprob,
+                audio_transcriptions.no_speech_prob,
+                audio_transcriptions.language,
+                audio_transcriptions.language_probability,
+                audio_transcriptions.hallucination,
+                audio_transcriptions.audio_duration,
+                audio_transcriptions.captured_until,
+                audio_transcriptions.latency,
+                audio_transcriptions.model_version,
+                vec_distance_cosine(audio_transcription_embeddings.embedding, vec_f32(?1)) AS distance
+            FROM audio_transcription_embeddings
+            JOIN audio_transcriptions
+                ON audio_transcription_embeddings.audio_transcription_id = audio_transcriptions.id
+            JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+            LEFT JOIN speakers on audio_transcriptions.speaker_id = speakers.id
+            LEFT JOIN audio_tags ON audio_chunks.id = audio_tags.audio_chunk_id
+            LEFT JOIN tags ON audio_tags.tag_id = tags.id
+            WHERE vec_length(audio_transcription_embeddings.embedding) = vec_length(vec_f32(?1))
+                AND (?3 IS NULL OR audio_transcriptions.timestamp >= ?3)
+                AND (?4 IS NULL OR audio_transcriptions.timestamp <= ?4)
+                AND (speakers.id IS NULL OR speakers.hallucination = 0)
+            GROUP BY audio_transcriptions.id
+            HAVING ?2 IS NULL OR distance < ?2
+            ORDER BY distance ASC
+            LIMIT ?5
+            "#,
+        )
+        .bind(embedding.as_bytes())
+        .bind(threshold)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(join_all(
+            raw_results
+                .into_iter()
+                .map(|raw| async move { (self.audio_result(raw.result).await, raw.distance) }),
+        )
+        .await)
+    }
+
     // Add method to update frame names
     pub async fn update_frame_name(&self, frame_id: i64, name: &str) -> Result<(), sqlx::Error> {
         sqlx::query("UPDATE frames SET name = ?1 WHERE id = ?2")
@@ -2052,6 +3510,155 @@ impl DatabaseManager {
         Ok(())
     }
 
+    /// Sets the analyzer used to rewrite search queries. Call `rebuild_search_index` so the
+    /// index matches it.
+    pub fn set_query_analyzer(&self, analyzer: QueryAnalyzer) {
+        if let Ok(mut current) = self.query_analyzer.write() {
+            *current = analyzer;
+        }
+    }
+
+    fn parse_query(&self, query: &str) -> FtsQuery {
+        match self.query_analyzer.read() {
+            Ok(analyzer) => analyzer.parse_query(query),
+            Err(_) => FtsQuery::parse(query),
+        }
+    }
+
+    /// The text of `query` bound as ?1 of the searches of `table` and how it's matched, None when
+    /// nothing in the table can match
+    fn text_query(&self, query: &str, table: FtsTable) -> Option<(String, TextMatch)> {
+        let substring = self
+            .query_analyzer
+            .read()
+            .ok()
+            .and_then(|analyzer| analyzer.substring_query(query));
+        if let Some(substring) = substring {
+            return Some((substring, TextMatch::Substring));
+        }
+        let query = self.parse_query(query).for_table(table)?;
+        let text_match = if query.is_empty() {
+            TextMatch::All
+        } else {
+            TextMatch::Fts
+        };
+        Some((query, text_match))
+    }
+
+    /// Returns the analyzer the FTS tables are currently built with
+    pub async fn get_index_analyzer(&self) -> Result<SearchAnalyzer, sqlx::Error> {
+        let value = sqlx::query_scalar::<_, String>(
+            "SELECT value FROM search_settings WHERE key = 'analyzer'",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(value.and_then(|v| v.parse().ok()).unwrap_or_default())
+    }
+
+    /// Recreates the FTS tables with the analyzer's tokenizer and repopulates them from the
+    /// source tables. No-op when the index already uses this analyzer. Returns whether a rebuild happened.
+    pub async fn rebuild_search_index(
+        &self,
+        analyzer: SearchAnalyzer,
+    ) -> Result<bool, sqlx::Error> {
+        if self.get_index_analyzer().await? == analyzer {
+            return Ok(false);
+        }
+
+        let tokenizer = analyzer.fts_tokenizer();
+        let mut tx = self.pool.begin().await?;
+
+        // triggers are defined on the source tables and reference the fts tables by name,
+        // so they keep working once the tables are recreated
+        let statements = [
+            "DROP TABLE IF EXISTS ocr_text_fts".to_string(),
+            format!(
+                "CREATE VIRTUAL TABLE ocr_text_fts USING fts5(text, app_name, window_name, frame_id UNINDEXED, tokenize='{}')",
+                tokenizer
+            ),
+            r#"INSERT INTO ocr_text_fts(frame_id, text, app_name, window_name)
+            SELECT frame_id, COALESCE(text, ''), COALESCE(app_name, ''), COALESCE(window_name, '')
+            FROM ocr_text
+            WHERE text IS NOT NULL AND text != '' AND frame_id IS NOT NULL"#
+                .to_string(),
+            "DROP TABLE IF EXISTS audio_transcriptions_fts".to_string(),
+            format!(
+                "CREATE VIRTUAL TABLE audio_transcriptions_fts USING fts5(transcription, device, audio_chunk_id UNINDEXED, speaker_id, start_time UNINDEXED, end_time UNINDEXED, tokenize='{}')",
+                tokenizer
+            ),
+            r#"INSERT INTO audio_transcriptions_fts(transcription, device, audio_chunk_id, speaker_id, start_time, end_time)
+            SELECT COALESCE(transcription, ''), COALESCE(device, ''), audio_chunk_id, speaker_id, start_time, end_time
+            FROM audio_transcriptions
+            WHERE transcription IS NOT NULL AND transcription != '' AND audio_chunk_id IS NOT NULL"#
+                .to_string(),
+            "DROP TABLE IF EXISTS ui_monitoring_fts".to_string(),
+            format!(
+                "CREATE VIRTUAL TABLE ui_monitoring_fts USING fts5(text_output, app, window, ui_id UNINDEXED, tokenize='{}')",
+                tokenizer
+            ),
+            r#"INSERT INTO ui_monitoring_fts(ui_id, text_output, app, window)
+            SELECT id, COALESCE(text_output, ''), COALESCE(app, ''), COALESCE(window, '')
+            FROM ui_monitoring
+            WHERE text_output IS NOT NULL AND text_output != '' AND id IS NOT NULL"#
+                .to_string(),
+        ];
+
+        for statement in statements.iter() {
+            sqlx::query(statement).execute(&mut *tx).await?;
+        }
+
+        sqlx::query("INSERT OR REPLACE INTO search_settings (key, value) VALUES ('analyzer', ?1)")
+            .bind(analyzer.to_string())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Whether a watched folder has seen this file before, whatever the outcome
+    pub async fn is_file_ingested(&self, file_path: &str) -> Result<bool, sqlx::Error> {
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM ingested_files WHERE file_path = ?1")
+                .bind(file_path)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(count > 0)
+    }
+
+    pub async fn mark_file_ingested(
+        &self,
+        file_path: &str,
+        source: &str,
+        status: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO ingested_files (file_path, source, status) VALUES (?1, ?2, ?3)",
+        )
+        .bind(file_path)
+        .bind(source)
+        .bind(status)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_audio_transcription_text(
+        &self,
+        id: i64,
+        transcription: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE audio_transcriptions SET transcription = ?1, text_length = ?2 WHERE id = ?3",
+        )
+        .bind(transcription)
+        .bind(transcription.len() as i64)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
     pub async fn repair_database(&self) -> Result<(), anyhow::Error> {
         debug!("starting aggressive database repair process");
 
@@ -2139,3 +3746,192 @@ impl DatabaseManager {
         }
     }
 }
+
+/// How the text of a search is matched in a table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TextMatch {
+    /// No text to search, every row matches
+    All,
+    /// The text is an fts5 MATCH expression
+    Fts,
+    /// The text is a substring of the searched column, for queries shorter than the trigram index
+    /// can match
+    Substring,
+}
+
+/// The table and the condition of a count of the rows of `table` matching `query`, as returned
+/// by [`DatabaseManager::text_query`]: none of the rows when it's None
+fn count_source(
+    query: Option<&(String, TextMatch)>,
+    table: &'static str,
+    fts_join: &'static str,
+    fts_match: &'static str,
+    substring_match: &'static str,
+) -> (&'static str, &'static str) {
+    match query.map(|(_, text_match)| text_match) {
+        None => (table, "0"),
+        Some(TextMatch::All) => (table, "1=1"),
+        Some(TextMatch::Fts) => (fts_join, fts_match),
+        Some(TextMatch::Substring) => (table, substring_match),
+    }
+}
+
+fn ocr_search_sql(text_match: TextMatch) -> String {
+    let (base_sql, where_clause) = match text_match {
+        TextMatch::All => ("ocr_text", "WHERE 1=1"),
+        TextMatch::Fts => (
+            "ocr_text_fts JOIN ocr_text ON ocr_text_fts.frame_id = ocr_text.frame_id",
+            "WHERE ocr_text_fts MATCH ?1",
+        ),
+        TextMatch::Substring => ("ocr_text", "WHERE ocr_text.text LIKE '%' || ?1 || '%'"),
+    };
+
+    format!(
+        r#"
+        SELECT
+            ocr_text.frame_id,
+            ocr_text.text as ocr_text,
+            ocr_text.text_json,
+            frames.timestamp,
+            frames.name as frame_name,
+            video_chunks.file_path,
+            frames.offset_index,
+            ocr_text.app_name,
+            ocr_text.ocr_engine,
+            ocr_text.window_name,
+            frames.browser_url,
+            frames.focused_app_name,
+            frames.focused_window_name,
+            frames.in_call,
+            frames.virtual_desktop,
+            frames.selected_text,
+            GROUP_CONCAT(tags.name, ',') as tags
+        FROM {}
+        JOIN frames ON ocr_text.frame_id = frames.id
+        JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+        LEFT JOIN vision_tags ON frames.id = vision_tags.vision_id
+        LEFT JOIN tags ON vision_tags.tag_id = tags.id
+        {}
+            AND (?2 IS NULL OR frames.timestamp >= ?2)
+            AND (?3 IS NULL OR frames.timestamp <= ?3)
+            AND (?4 IS NULL OR ocr_text.app_name LIKE '%' || ?4 || '%')
+            AND (?5 IS NULL OR ocr_text.window_name LIKE '%' || ?5 || '%')
+            AND (?6 IS NULL OR COALESCE(ocr_text.text_length,LENGTH(ocr_text.text)) >= ?6)
+            AND (?7 IS NULL OR COALESCE(ocr_text.text_length,LENGTH(ocr_text.text)) <= ?7)
+            AND (?8 IS NULL OR frames.name LIKE '%' || ?8 || '%' COLLATE NOCASE)
+            AND (?11 IS NULL OR frames.browser_url LIKE '%' || ?11 || '%' COLLATE NOCASE)
+            AND (?12 IS NULL OR frames.focused_app_name LIKE '%' || ?12 || '%' COLLATE NOCASE)
+            AND (?13 IS NULL OR frames.in_call = ?13)
+            AND (?14 IS NULL OR frames.virtual_desktop = ?14)
+            AND (?15 IS NULL OR frames.selected_text LIKE '%' || ?15 || '%' COLLATE NOCASE)
+        GROUP BY ocr_text.frame_id
+        ORDER BY frames.timestamp DESC
+        LIMIT ?9 OFFSET ?10
+        "#,
+        base_sql, where_clause
+    )
+}
+
+fn audio_search_sql(text_match: TextMatch) -> String {
+    let (base_sql, where_clause) = match text_match {
+        TextMatch::All => ("audio_transcriptions", "WHERE 1=1"),
+        TextMatch::Fts => (
+            "audio_transcriptions_fts JOIN audio_transcriptions ON audio_transcriptions_fts.audio_chunk_id = audio_transcriptions.audio_chunk_id",
+            "WHERE audio_transcriptions_fts MATCH ?1",
+        ),
+        TextMatch::Substring => ("audio_transcriptions", "WHERE audio_transcriptions.transcription LIKE '%' || ?1 || '%'"),
+    };
+
+    format!(
+        r#"
+        SELECT
+            audio_transcriptions.audio_chunk_id,
+            audio_transcriptions.transcription,
+            audio_transcriptions.timestamp,
+            audio_chunks.file_path,
+            audio_transcriptions.offset_index,
+            audio_transcriptions.transcription_engine,
+            GROUP_CONCAT(tags.name, ',') as tags,
+            audio_transcriptions.device as device_name,
+            audio_transcriptions.is_input_device,
+            audio_transcriptions.speaker_id,
+            audio_transcriptions.start_time,
+            audio_transcriptions.end_time,
+            audio_transcriptions.avg_logprob,
+            audio_transcriptions.no_speech_prob,
+            audio_transcriptions.language,
+            audio_transcriptions.language_probability,
+            audio_transcriptions.hallucination,
+            audio_transcriptions.audio_duration,
+            audio_transcriptions.captured_until,
+            audio_transcriptions.latency,
+            audio_transcriptions.model_version
+        FROM {}
+        JOIN audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
+        LEFT JOIN speakers on audio_transcriptions.speaker_id = speakers.id
+        LEFT JOIN audio_tags ON audio_chunks.id = audio_tags.audio_chunk_id
+        LEFT JOIN tags ON audio_tags.tag_id = tags.id
+        {}
+            AND (?2 IS NULL OR audio_transcriptions.timestamp >= ?2)
+            AND (?3 IS NULL OR audio_transcriptions.timestamp <= ?3)
+            AND (?4 IS NULL OR COALESCE(audio_transcriptions.text_length, LENGTH(audio_transcriptions.transcription)) >= ?4)
+            AND (?5 IS NULL OR COALESCE(audio_transcriptions.text_length, LENGTH(audio_transcriptions.transcription)) <= ?5)
+            AND (speakers.id IS NULL OR speakers.hallucination = 0)
+            AND (json_array_length(?6) = 0 OR audio_transcriptions.speaker_id IN (SELECT value FROM json_each(?6)))
+            AND (?9 IS NULL OR audio_transcriptions.language = ?9)
+            AND (?10 IS NULL OR audio_transcriptions.transcription_engine = ?10)
+            AND (?11 IS NULL OR audio_transcriptions.model_version = ?11)
+        GROUP BY audio_transcriptions.audio_chunk_id, audio_transcriptions.offset_index
+        ORDER BY audio_transcriptions.timestamp DESC
+        LIMIT ?7 OFFSET ?8
+        "#,
+        base_sql, where_clause
+    )
+}
+
+fn ui_search_sql(text_match: TextMatch) -> String {
+    let (base_sql, where_clause) = match text_match {
+        TextMatch::All => ("ui_monitoring", "WHERE 1=1"),
+        TextMatch::Fts => (
+            "ui_monitoring_fts JOIN ui_monitoring ON ui_monitoring_fts.ui_id = ui_monitoring.id",
+            "WHERE ui_monitoring_fts MATCH ?1",
+        ),
+        TextMatch::Substring => (
+            "ui_monitoring",
+            "WHERE ui_monitoring.text_output LIKE '%' || ?1 || '%'",
+        ),
+    };
+
+    format!(
+        r#"
+        SELECT
+            ui_monitoring.id,
+            ui_monitoring.text_output,
+            ui_monitoring.timestamp,
+            ui_monitoring.app,
+            ui_monitoring.window,
+            ui_monitoring.initial_traversal_at,
+            video_chunks.file_path,
+            frames.offset_index
+        FROM {}
+        LEFT JOIN frames ON
+            frames.timestamp BETWEEN
+                datetime(ui_monitoring.timestamp, '-1 seconds')
+                AND datetime(ui_monitoring.timestamp, '+1 seconds')
+        LEFT JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+        {}
+            AND (?2 IS NULL OR ui_monitoring.timestamp >= ?2)
+            AND (?3 IS NULL OR ui_monitoring.timestamp <= ?3)
+            AND (?4 IS NULL OR ui_monitoring.app LIKE '%' || ?4 || '%')
+            AND (?5 IS NULL OR ui_monitoring.window LIKE '%' || ?5 || '%')
+        ORDER BY ui_monitoring.timestamp DESC
+        LIMIT ?6 OFFSET ?7
+        "#,
+        base_sql, where_clause
+    )
+}
+
+/// Selection of the pointer when the frame was captured, stored as four columns
+fn cursor_selection(details: &FrameDetails) -> Option<LayoutBox> {
+    details.cursor.as_ref().and_then(|cursor| cursor.selection)
+}
@@ -1,5 +1,9 @@
 use chrono::{DateTime, Utc};
-use screenpipe_audio::DeviceType;
+use screenpipe_audio::{DetectedLanguage, DeviceType, Hallucination, SegmentConfidence};
+use screenpipe_vision::active_window::ActiveApp;
+use screenpipe_vision::browser_tab::BrowserTab;
+use screenpipe_vision::cursor::CursorOverlay;
+use screenpipe_vision::monitor::MonitorData;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use std::error::Error as StdError;
@@ -35,6 +39,12 @@ pub struct OCRResultRaw {
     pub app_name: String,
     pub ocr_engine: String,
     pub window_name: String,
+    pub browser_url: Option<String>,
+    pub focused_app_name: Option<String>,
+    pub focused_window_name: Option<String>,
+    pub in_call: bool,
+    pub virtual_desktop: Option<u32>,
+    pub selected_text: Option<String>,
     pub tags: Option<String>,
 }
 
@@ -50,9 +60,55 @@ pub struct OCRResult {
     pub app_name: String,
     pub ocr_engine: String,
     pub window_name: String,
+    /// Page the browser showed, when the frame is of one
+    pub browser_url: Option<String>,
+    /// App and window focused when the frame was captured, not always the window the text is of
+    pub focused_app_name: Option<String>,
+    pub focused_window_name: Option<String>,
+    /// Whether a video call was going on
+    pub in_call: bool,
+    /// Virtual desktop shown on the monitor, from 1
+    pub virtual_desktop: Option<u32>,
+    /// Text selected in the focused window, with --capture-cursor
+    pub selected_text: Option<String>,
     pub tags: Vec<String>,
 }
 
+impl From<OCRResultRaw> for OCRResult {
+    fn from(raw: OCRResultRaw) -> Self {
+        OCRResult {
+            frame_id: raw.frame_id,
+            frame_name: raw.frame_name,
+            ocr_text: raw.ocr_text,
+            text_json: raw.text_json,
+            timestamp: raw.timestamp,
+            file_path: raw.file_path,
+            offset_index: raw.offset_index,
+            app_name: raw.app_name,
+            ocr_engine: raw.ocr_engine,
+            window_name: raw.window_name,
+            browser_url: raw.browser_url,
+            focused_app_name: raw.focused_app_name,
+            focused_window_name: raw.focused_window_name,
+            in_call: raw.in_call,
+            virtual_desktop: raw.virtual_desktop,
+            selected_text: raw.selected_text,
+            tags: raw
+                .tags
+                .map(|t| t.split(',').map(String::from).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// OCR text found by its embedding, `distance` being the cosine distance to the query
+#[derive(FromRow)]
+pub struct OCREmbeddingMatchRaw {
+    #[sqlx(flatten)]
+    pub result: OCRResultRaw,
+    pub distance: f64,
+}
+
 #[derive(Debug, Deserialize, PartialEq, Default, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum ContentType {
@@ -86,6 +142,23 @@ pub struct AudioResultRaw {
     pub speaker_id: Option<i64>,
     pub start_time: Option<f64>,
     pub end_time: Option<f64>,
+    pub avg_logprob: Option<f64>,
+    pub no_speech_prob: Option<f64>,
+    pub language: Option<String>,
+    pub language_probability: Option<f64>,
+    pub hallucination: Option<String>,
+    pub audio_duration: Option<f64>,
+    pub captured_until: Option<DateTime<Utc>>,
+    pub latency: Option<f64>,
+    pub model_version: Option<String>,
+}
+
+/// Transcription found by its embedding, `distance` being the cosine distance to the query
+#[derive(FromRow)]
+pub struct AudioEmbeddingMatchRaw {
+    #[sqlx(flatten)]
+    pub result: AudioResultRaw,
+    pub distance: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
@@ -109,6 +182,17 @@ pub struct AudioResult {
     pub speaker: Option<Speaker>,
     pub start_time: Option<f64>,
     pub end_time: Option<f64>,
+    pub confidence: Option<SegmentConfidence>,
+    pub language: Option<DetectedLanguage>,
+    pub hallucination: Option<Hallucination>,
+    /// Seconds of audio transcribed
+    pub audio_duration: Option<f64>,
+    /// When its audio stopped being captured
+    pub captured_until: Option<DateTime<Utc>>,
+    /// Seconds from the end of the capture to the transcription
+    pub latency: Option<f64>,
+    /// Model the transcription was made with, None for cloud engines not reporting it
+    pub model_version: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -134,6 +218,150 @@ pub struct UiContent {
     pub frame_name: Option<String>,
 }
 
+/// An element of the accessibility tree of a window, with the frame it was read with
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct UiElementResult {
+    pub id: i64,
+    pub frame_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub app_name: String,
+    pub window_name: String,
+    pub role: String,
+    pub label: String,
+    pub text: String,
+    pub depth: i64,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+}
+
+/// A line of OCR text and where it was in its window, in fractions of the window's size from its
+/// top left corner
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct OcrLineResult {
+    pub id: i64,
+    pub frame_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub app_name: String,
+    pub window_name: String,
+    /// Reading order of the block in the window, and of the line in the block
+    pub block_index: i64,
+    pub line_index: i64,
+    pub text: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A frame whose image looks like what was searched, by the distance of their CLIP embeddings
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct VisualSearchResult {
+    pub frame_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub file_path: String,
+    pub offset_index: i64,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    /// None when no text was read in it
+    pub ocr_text: Option<String>,
+    /// Cosine distance, from 0 for the closest
+    pub distance: f64,
+}
+
+/// A stretch of time nothing was captured
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CapturePause {
+    pub id: i64,
+    /// "idle" or "locked"
+    pub reason: String,
+    pub start_time: DateTime<Utc>,
+    /// None while the pause goes on
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+/// A meeting detected, from the window of a call or speech heard both ways while an app for calls
+/// is open
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct MeetingSession {
+    pub id: i64,
+    /// App and title of the window the meeting was detected in
+    pub app_name: String,
+    pub title: String,
+    pub start_time: DateTime<Utc>,
+    /// None while the meeting goes on
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+/// A transcript segment, with when its audio was captured
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct TranscriptSegment {
+    pub id: i64,
+    pub transcription: String,
+    pub device: String,
+    /// When the audio of the segment started being captured
+    pub timestamp: DateTime<Utc>,
+    /// Seconds into the audio chunk the segment starts and ends at
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+}
+
+impl TranscriptSegment {
+    /// When the audio of the segment stopped being captured
+    pub fn end(&self) -> DateTime<Utc> {
+        let secs = match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) if end > start => end - start,
+            _ => 0.0,
+        };
+        self.timestamp + chrono::Duration::milliseconds((secs * 1000.0) as i64)
+    }
+}
+
+/// A frame on screen while something was said
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct AlignedFrame {
+    pub frame_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub monitor_name: Option<String>,
+    pub file_path: String,
+    pub offset_index: i64,
+    /// Focused when the frame was captured
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+}
+
+/// A click, or keypresses or scrolls counted over a few seconds
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct InputEventResult {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    /// "click", "keys" or "scroll"
+    pub kind: String,
+    /// Keys pressed or wheel ticks, 1 for a click
+    pub count: i64,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub button: Option<String>,
+    pub delta_x: Option<i64>,
+    pub delta_y: Option<i64>,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    pub element_role: Option<String>,
+    pub element_label: Option<String>,
+    /// Frame captured last before the event
+    pub frame_id: Option<i64>,
+}
+
+/// Input of a minute, a minute with frames but none is one of reading or watching
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct InputActivityBucket {
+    pub minute: DateTime<Utc>,
+    pub clicks: i64,
+    pub keypresses: i64,
+    pub scrolls: i64,
+}
+
 #[derive(Debug, Clone)]
 pub struct FrameData {
     pub frame_id: i64,
@@ -198,3 +426,34 @@ pub struct AudioChunksResponse {
     pub file_path: String,
     pub timestamp: DateTime<Utc>,
 }
+
+/// What is known of a frame besides its place in the video, written with it in a single insert
+#[derive(Debug, Clone, Default)]
+pub struct FrameDetails {
+    /// Monitor it was captured on, duplicates keep the one of their original
+    pub monitor: Option<MonitorData>,
+    /// App focused when it was captured, its window title cleared when sensitive
+    pub active_app: Option<ActiveApp>,
+    pub in_call: bool,
+    pub browser_tab: Option<BrowserTab>,
+    pub virtual_desktop: Option<u32>,
+    pub cursor: Option<CursorOverlay>,
+}
+
+/// What is known of a transcription besides its text, written with it in a single insert
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptionDetails {
+    /// When its audio was captured, the time of the insert when unknown
+    pub captured_at: Option<DateTime<Utc>>,
+    pub confidence: Option<SegmentConfidence>,
+    pub language: Option<DetectedLanguage>,
+    /// Set when the text is kept although it looks like a hallucination
+    pub hallucination: Option<Hallucination>,
+    /// When its audio stopped being captured
+    pub captured_until: Option<DateTime<Utc>>,
+    /// Seconds of audio transcribed
+    pub audio_duration: Option<f64>,
+    /// Seconds from the end of the capture to the transcription
+    pub latency: Option<f64>,
+    pub model_version: Option<String>,
+}
@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tracing::info;
+use uuid::Uuid;
+
+const AUDIO_MANIFEST: &str = "audio.jsonl";
+const OCR_MANIFEST: &str = "ocr.jsonl";
+
+/// Local, offline store of user-approved (input, corrected text) pairs.
+///
+/// Layout under `<screenpipe_dir>/eval_dataset`:
+/// - `audio/<id>.<ext>` + `audio.jsonl`
+/// - `frames/<id>.jpg` + `ocr.jsonl`
+///
+/// Media paths in the manifests are relative to the dataset root so the folder can be moved around.
+pub struct EvalDataset {
+    root: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioSample {
+    pub id: String,
+    pub audio_path: String,
+    /// Transcript as corrected by the user
+    pub reference: String,
+    /// Transcript the engine originally produced
+    pub hypothesis: String,
+    pub audio_chunk_id: i64,
+    pub approved_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrSample {
+    pub id: String,
+    pub frame_path: String,
+    /// OCR text as corrected by the user
+    pub reference: String,
+    /// OCR text the engine originally produced
+    pub hypothesis: String,
+    pub frame_id: i64,
+    pub approved_at: DateTime<Utc>,
+}
+
+impl EvalDataset {
+    pub fn new(screenpipe_dir: &Path) -> Self {
+        Self {
+            root: screenpipe_dir.join("eval_dataset"),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Copies the audio chunk into the dataset and records the corrected transcript
+    pub async fn add_audio_sample(
+        &self,
+        source_path: &str,
+        audio_chunk_id: i64,
+        hypothesis: &str,
+        reference: &str,
+    ) -> Result<AudioSample> {
+        let id = Uuid::new_v4().to_string();
+        let ext = Path::new(source_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("mp4");
+        let rel_path = format!("audio/{}.{}", id, ext);
+
+        self.copy_into(Path::new(source_path), &rel_path).await?;
+
+        let sample = AudioSample {
+            id,
+            audio_path: rel_path,
+            reference: reference.to_string(),
+            hypothesis: hypothesis.to_string(),
+            audio_chunk_id,
+            approved_at: Utc::now(),
+        };
+        self.append(AUDIO_MANIFEST, &sample).await?;
+        info!("added audio sample {} to eval dataset", sample.id);
+        Ok(sample)
+    }
+
+    /// Copies an extracted frame into the dataset and records the corrected OCR text
+    pub async fn add_ocr_sample(
+        &self,
+        frame_image_path: &str,
+        frame_id: i64,
+        hypothesis: &str,
+        reference: &str,
+    ) -> Result<OcrSample> {
+        let id = Uuid::new_v4().to_string();
+        let rel_path = format!("frames/{}.jpg", id);
+
+        self.copy_into(Path::new(frame_image_path), &rel_path)
+            .await?;
+
+        let sample = OcrSample {
+            id,
+            frame_path: rel_path,
+            reference: reference.to_string(),
+            hypothesis: hypothesis.to_string(),
+            frame_id,
+            approved_at: Utc::now(),
+        };
+        self.append(OCR_MANIFEST, &sample).await?;
+        info!("added ocr sample {} to eval dataset", sample.id);
+        Ok(sample)
+    }
+
+    pub async fn audio_samples(&self) -> Result<Vec<AudioSample>> {
+        read_manifest(&self.root.join(AUDIO_MANIFEST)).await
+    }
+
+    pub async fn ocr_samples(&self) -> Result<Vec<OcrSample>> {
+        read_manifest(&self.root.join(OCR_MANIFEST)).await
+    }
+
+    async fn copy_into(&self, source: &Path, rel_path: &str) -> Result<()> {
+        if !source.exists() {
+            return Err(anyhow!("source file not found: {}", source.display()));
+        }
+        let dest = self.root.join(rel_path);
+        if let Some(parent) = dest.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::copy(source, &dest).await?;
+        Ok(())
+    }
+
+    async fn append<T: Serialize>(&self, manifest: &str, sample: &T) -> Result<()> {
+        tokio::fs::create_dir_all(&self.root).await?;
+        let mut line = serde_json::to_string(sample)?;
+        line.push('\n');
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.root.join(manifest))
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+async fn read_manifest<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Vec<T>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = tokio::fs::read_to_string(path).await?;
+    content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).map_err(Into::into))
+        .collect()
+}
@@ -0,0 +1,426 @@
+/// Part of what was captured a term is looked for in, `field:term`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryField {
+    /// Text on screen, read or from the accessibility tree, `ocr:` or `text:`
+    Ocr,
+    /// What was said, `transcript:` or `audio:`
+    Transcript,
+    /// Title of the window, `window:` or `title:`
+    Window,
+    /// Name of the app, `app:`
+    App,
+}
+
+impl QueryField {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "ocr" | "text" => Some(QueryField::Ocr),
+            "transcript" | "audio" => Some(QueryField::Transcript),
+            "window" | "title" => Some(QueryField::Window),
+            "app" => Some(QueryField::App),
+            _ => None,
+        }
+    }
+}
+
+/// FTS tables a query is run against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FtsTable {
+    Ocr,
+    Audio,
+    Ui,
+}
+
+impl FtsTable {
+    /// Column of `field`, None when the table doesn't have it and terms of the field can't match
+    fn column(&self, field: QueryField) -> Option<&'static str> {
+        match (self, field) {
+            (FtsTable::Ocr, QueryField::Ocr) => Some("text"),
+            (FtsTable::Ocr, QueryField::Window) => Some("window_name"),
+            (FtsTable::Ocr, QueryField::App) => Some("app_name"),
+            (FtsTable::Audio, QueryField::Transcript) => Some("transcription"),
+            (FtsTable::Ui, QueryField::Ocr) => Some("text_output"),
+            (FtsTable::Ui, QueryField::Window) => Some("window"),
+            (FtsTable::Ui, QueryField::App) => Some("app"),
+            _ => None,
+        }
+    }
+}
+
+/// A word, or words following each other when `quoted`, of a query
+#[derive(Clone, Debug, PartialEq)]
+pub struct Term {
+    pub text: String,
+    pub quoted: bool,
+    /// Matches the words starting with the last one, `word*`
+    pub prefix: bool,
+    /// Matches in any field when None
+    pub field: Option<QueryField>,
+}
+
+/// A query parsed
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryNode {
+    Term(Term),
+    And(Vec<QueryNode>),
+    Or(Vec<QueryNode>),
+    /// Left out of the matches of what it's with, `NOT x` or `-x`
+    Not(Box<QueryNode>),
+}
+
+impl QueryNode {
+    /// Terms of the query, in order
+    pub fn terms(&self) -> Vec<&Term> {
+        match self {
+            QueryNode::Term(term) => vec![term],
+            QueryNode::And(nodes) | QueryNode::Or(nodes) => {
+                nodes.iter().flat_map(QueryNode::terms).collect()
+            }
+            QueryNode::Not(node) => node.terms(),
+        }
+    }
+
+    /// The query with each term replaced by what `f` makes of it, left out when None. None when
+    /// no term is left
+    pub fn map_terms(self, f: &mut dyn FnMut(Term) -> Option<Term>) -> Option<QueryNode> {
+        match self {
+            QueryNode::Term(term) => f(term).map(QueryNode::Term),
+            QueryNode::And(nodes) => map_all(nodes, f).map(QueryNode::And),
+            QueryNode::Or(nodes) => map_all(nodes, f).map(QueryNode::Or),
+            QueryNode::Not(node) => node.map_terms(f).map(|node| QueryNode::Not(Box::new(node))),
+        }
+    }
+
+    fn with_field(self, field: Option<QueryField>) -> Self {
+        let Some(field) = field else {
+            return self;
+        };
+        match self {
+            QueryNode::Term(term) => QueryNode::Term(Term {
+                field: term.field.or(Some(field)),
+                ..term
+            }),
+            QueryNode::And(nodes) => QueryNode::And(
+                nodes
+                    .into_iter()
+                    .map(|node| node.with_field(Some(field)))
+                    .collect(),
+            ),
+            QueryNode::Or(nodes) => QueryNode::Or(
+                nodes
+                    .into_iter()
+                    .map(|node| node.with_field(Some(field)))
+                    .collect(),
+            ),
+            QueryNode::Not(node) => QueryNode::Not(Box::new(node.with_field(Some(field)))),
+        }
+    }
+
+    fn render(&self, table: FtsTable, nested: bool) -> Rendered {
+        match self {
+            QueryNode::Term(term) => {
+                let column = match term.field {
+                    Some(field) => match table.column(field) {
+                        Some(column) => format!("{} : ", column),
+                        None => return Rendered::Never,
+                    },
+                    None => String::new(),
+                };
+                let prefix = if term.prefix { "*" } else { "" };
+                Rendered::Match(format!("{}{}{}", column, quote(&term.text), prefix))
+            }
+            QueryNode::Or(nodes) => {
+                let mut branches = Vec::new();
+                for node in nodes {
+                    match node.render(table, true) {
+                        Rendered::Always => return Rendered::Always,
+                        Rendered::Never => {}
+                        Rendered::Match(branch) => branches.push(branch),
+                    }
+                }
+                join(branches, " OR ", nested).map_or(Rendered::Never, Rendered::Match)
+            }
+            QueryNode::And(nodes) => {
+                let (mut required, mut excluded) = (Vec::new(), Vec::new());
+                for node in nodes {
+                    match node {
+                        QueryNode::Not(node) => match node.render(table, true) {
+                            Rendered::Always => return Rendered::Never,
+                            Rendered::Never => {}
+                            Rendered::Match(branch) => excluded.push(branch),
+                        },
+                        node => match node.render(table, true) {
+                            Rendered::Never => return Rendered::Never,
+                            Rendered::Always => {}
+                            Rendered::Match(branch) => required.push(branch),
+                        },
+                    }
+                }
+                // fts5 can only leave out matches of something, exclusions alone match everything
+                let Some(excluded) = join(excluded, " OR ", true) else {
+                    return join(required, " AND ", nested)
+                        .map_or(Rendered::Always, Rendered::Match);
+                };
+                match join(required, " AND ", true) {
+                    Some(required) => {
+                        Rendered::Match(wrap(format!("{} NOT {}", required, excluded), nested))
+                    }
+                    None => Rendered::Always,
+                }
+            }
+            // only found alone when nothing comes before it
+            QueryNode::Not(_) => Rendered::Always,
+        }
+    }
+}
+
+fn map_all(
+    nodes: Vec<QueryNode>,
+    f: &mut dyn FnMut(Term) -> Option<Term>,
+) -> Option<Vec<QueryNode>> {
+    let nodes: Vec<QueryNode> = nodes
+        .into_iter()
+        .filter_map(|node| node.map_terms(f))
+        .collect();
+    (!nodes.is_empty()).then_some(nodes)
+}
+
+enum Rendered {
+    /// Nothing in the table can match
+    Never,
+    /// Everything in the table matches
+    Always,
+    Match(String),
+}
+
+/// `parts` joined by `operator`, in parentheses when there are several and `nested`. None when
+/// there are no parts
+fn join(parts: Vec<String>, operator: &str, nested: bool) -> Option<String> {
+    match parts.len() {
+        0 => None,
+        1 => parts.into_iter().next(),
+        _ => Some(wrap(parts.join(operator), nested)),
+    }
+}
+
+fn wrap(expression: String, nested: bool) -> String {
+    if nested {
+        format!("({})", expression)
+    } else {
+        expression
+    }
+}
+
+/// `text` as an fts5 string, where every character is literal
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Field(QueryField),
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+}
+
+fn tokenize(query: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+                let phrase: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                if !phrase.trim().is_empty() {
+                    tokens.push(Token::Phrase(phrase));
+                }
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '"' | '(' | ')') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                push_word(&mut tokens, &word);
+            }
+        }
+    }
+    tokens
+}
+
+fn push_word(tokens: &mut Vec<Token>, word: &str) {
+    match word {
+        "AND" => return tokens.push(Token::And),
+        "OR" => return tokens.push(Token::Or),
+        "NOT" => return tokens.push(Token::Not),
+        _ => {}
+    }
+    if let Some(rest) = word.strip_prefix('-').filter(|rest| !rest.is_empty()) {
+        tokens.push(Token::Not);
+        return push_word(tokens, rest);
+    }
+    if let Some((name, rest)) = word.split_once(':') {
+        if let Some(field) = QueryField::parse(name) {
+            tokens.push(Token::Field(field));
+            if !rest.is_empty() {
+                push_word(tokens, rest);
+            }
+            return;
+        }
+    }
+    if word.trim_matches(['*', '-', '+']).is_empty() {
+        return;
+    }
+    tokens.push(Token::Word(word.to_string()));
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn or(&mut self) -> Option<QueryNode> {
+        let mut branches = Vec::new();
+        loop {
+            branches.extend(self.and());
+            if self.peek() != Some(&Token::Or) {
+                break;
+            }
+            self.pos += 1;
+        }
+        match branches.len() {
+            0 | 1 => branches.pop(),
+            _ => Some(QueryNode::Or(branches)),
+        }
+    }
+
+    fn and(&mut self) -> Option<QueryNode> {
+        let mut nodes = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(Token::Or) | Some(Token::Close) => break,
+                Some(Token::And) => self.pos += 1,
+                _ => nodes.extend(self.unary()),
+            }
+        }
+        match nodes.len() {
+            1 if !matches!(nodes[0], QueryNode::Not(_)) => nodes.pop(),
+            0 => None,
+            _ => Some(QueryNode::And(nodes)),
+        }
+    }
+
+    fn unary(&mut self) -> Option<QueryNode> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return self.unary().map(|node| QueryNode::Not(Box::new(node)));
+        }
+        self.atom(None)
+    }
+
+    fn atom(&mut self, field: Option<QueryField>) -> Option<QueryNode> {
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        match token {
+            Token::Field(inner) => {
+                let inner = *inner;
+                self.atom(Some(inner)).map(|node| node.with_field(field))
+            }
+            Token::Open => {
+                let node = self.or();
+                if self.peek() == Some(&Token::Close) {
+                    self.pos += 1;
+                }
+                node.map(|node| node.with_field(field))
+            }
+            Token::Word(word) => {
+                let text = word.trim_end_matches('*');
+                Some(QueryNode::Term(Term {
+                    text: text.to_string(),
+                    quoted: false,
+                    prefix: text.len() < word.len(),
+                    field,
+                }))
+            }
+            Token::Phrase(phrase) => Some(QueryNode::Term(Term {
+                text: phrase.clone(),
+                quoted: true,
+                prefix: false,
+                field,
+            })),
+            // operators out of place are left out
+            Token::Close | Token::And | Token::Or | Token::Not => None,
+        }
+    }
+}
+
+/// Parses a search query: words all have to match, `"quoted words"` in a row, `word*` words
+/// starting with it, `AND`, `OR`, `NOT` or `-` and parentheses combine them and `transcript:`,
+/// `ocr:`, `window:` and `app:` look for a word, a phrase or parentheses in one field only.
+/// Any text parses, None when it has no terms
+pub fn parse(query: &str) -> Option<QueryNode> {
+    let mut parser = Parser {
+        tokens: tokenize(query),
+        pos: 0,
+    };
+    let mut nodes = Vec::new();
+    while parser.pos < parser.tokens.len() {
+        nodes.extend(parser.or());
+        // a ")" without a "(" is left out
+        if parser.peek() == Some(&Token::Close) {
+            parser.pos += 1;
+        }
+    }
+    match nodes.len() {
+        0 | 1 => nodes.pop(),
+        _ => Some(QueryNode::And(nodes)),
+    }
+}
+
+/// A query ready to be run against each FTS table
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FtsQuery(pub Option<QueryNode>);
+
+impl FtsQuery {
+    pub fn parse(query: &str) -> Self {
+        FtsQuery(parse(query))
+    }
+
+    /// MATCH expression of the query for `table`, with all the text quoted so it's never a syntax
+    /// error. Empty when everything matches, None when nothing in the table can, like a query
+    /// only on transcripts for the OCR table
+    pub fn for_table(&self, table: FtsTable) -> Option<String> {
+        let Some(node) = &self.0 else {
+            return Some(String::new());
+        };
+        match node.render(table, false) {
+            Rendered::Never => None,
+            Rendered::Always => Some(String::new()),
+            Rendered::Match(expression) => Some(expression),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::db_types::SearchResult;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// Constant of reciprocal rank fusion, 60 as in the paper introducing it: the first results of a
+/// ranking count more than the next ones without a single ranking deciding alone
+pub const RRF_K: f64 = 60.0;
+
+/// Fuses `rankings`, best first, into one ranking with the score of each item, best first. An
+/// item scores 1 / (k + rank) in each ranking it's in, from rank 1, at its best rank when it's in
+/// one several times. Ties keep the order items first appear in
+pub fn reciprocal_rank_fusion<K: Eq + Hash + Clone>(rankings: &[Vec<K>], k: f64) -> Vec<(K, f64)> {
+    // score and order of first appearance of each item
+    let mut scores: HashMap<K, (f64, usize)> = HashMap::new();
+    for ranking in rankings {
+        let mut seen = HashSet::new();
+        for (rank, key) in ranking.iter().enumerate() {
+            if !seen.insert(key) {
+                continue;
+            }
+            let order = scores.len();
+            scores.entry(key.clone()).or_insert((0.0, order)).0 += 1.0 / (k + rank as f64 + 1.0);
+        }
+    }
+    let mut fused: Vec<(K, f64, usize)> = scores
+        .into_iter()
+        .map(|(key, (score, order))| (key, score, order))
+        .collect();
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.2.cmp(&b.2)));
+    fused
+        .into_iter()
+        .map(|(key, score, _)| (key, score))
+        .collect()
+}
+
+/// What a search result is of, the same whether it's found by its text or its meaning
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ResultKey {
+    Frame(i64),
+    Transcription {
+        audio_chunk_id: i64,
+        offset_index: i64,
+    },
+    Ui(i64),
+}
+
+impl From<&SearchResult> for ResultKey {
+    fn from(result: &SearchResult) -> Self {
+        match result {
+            SearchResult::OCR(ocr) => ResultKey::Frame(ocr.frame_id),
+            SearchResult::Audio(audio) => ResultKey::Transcription {
+                audio_chunk_id: audio.audio_chunk_id,
+                offset_index: audio.offset_index,
+            },
+            SearchResult::UI(ui) => ResultKey::Ui(ui.id),
+        }
+    }
+}
+
+/// A result of a hybrid search
+#[derive(Debug)]
+pub struct FusedResult {
+    pub result: SearchResult,
+    /// Reciprocal rank fusion score, higher is better
+    pub score: f64,
+    /// Cosine distance of its embedding to the one of the query, None when it was only found by
+    /// full text search
+    pub distance: Option<f64>,
+}
+
+/// The `limit` best results of full text search, `fts` best first, and of embedding search,
+/// `semantic` nearest first, by reciprocal rank fusion
+pub fn fuse(
+    fts: Vec<SearchResult>,
+    semantic: Vec<(SearchResult, f64)>,
+    limit: usize,
+) -> Vec<FusedResult> {
+    let fts_keys: Vec<ResultKey> = fts.iter().map(ResultKey::from).collect();
+    let semantic_keys: Vec<ResultKey> = semantic
+        .iter()
+        .map(|(result, _)| ResultKey::from(result))
+        .collect();
+    let ranking = reciprocal_rank_fusion(&[fts_keys, semantic_keys], RRF_K);
+
+    let mut distances: HashMap<ResultKey, f64> = HashMap::new();
+    let mut results: HashMap<ResultKey, SearchResult> = HashMap::new();
+    for (result, distance) in semantic {
+        let key = ResultKey::from(&result);
+        distances.entry(key.clone()).or_insert(distance);
+        results.entry(key).or_insert(result);
+    }
+    // the text found by full text search is the one the query matched
+    for result in fts {
+        results.insert(ResultKey::from(&result), result);
+    }
+
+    ranking
+        .into_iter()
+        .take(limit)
+        .filter_map(|(key, score)| {
+            Some(FusedResult {
+                distance: distances.get(&key).copied(),
+                result: results.remove(&key)?,
+                score,
+            })
+        })
+        .collect()
+}
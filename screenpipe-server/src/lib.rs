@@ -4,21 +4,34 @@ pub mod cli;
 pub mod core;
 pub mod db;
 pub mod db_types;
+pub mod eval_dataset;
 pub mod filtering;
+pub mod fts_query;
+pub mod hybrid_search;
 mod add;
+pub mod now;
 pub mod pipe_manager;
 mod plugin;
+pub mod query_plan;
+pub mod redaction;
 mod resource_monitor;
+pub mod search_analyzer;
 mod server;
+mod subtitles;
 mod video;
+pub mod video_archive;
 pub mod video_cache;
+pub mod video_encoder;
 mod video_db;
 pub mod video_utils;
 pub mod text_embeds;
+pub mod watch_folder;
 
 pub use auto_destruct::watch_pid;
 pub use cli::Cli;
-pub use core::start_continuous_recording;
+pub use core::{
+    detect_meetings, pause_capture_when_away, record_input_events, start_continuous_recording,
+};
 pub use db::DatabaseManager;
 pub use add::handle_index_command;
 pub use pipe_manager::PipeManager;
@@ -30,7 +43,9 @@ pub use server::AppState;
 pub use server::ContentItem;
 pub use server::HealthCheckResponse;
 pub use server::PaginatedResponse;
+pub use server::ScreenshotOptions;
 pub use server::Server;
+pub use subtitles::handle_subtitles_command;
 pub use video::VideoCapture;
 pub use axum::Json as JsonResponse;
 pub use server::{
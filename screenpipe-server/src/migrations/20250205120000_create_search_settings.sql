@@ -0,0 +1,7 @@
+-- Key/value settings for the search index, e.g. which analyzer the FTS tables were built with
+CREATE TABLE IF NOT EXISTS search_settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+
+INSERT OR IGNORE INTO search_settings (key, value) VALUES ('analyzer', 'basic');
@@ -0,0 +1,12 @@
+-- Files picked up from watched folders, so each one is ingested once across restarts
+CREATE TABLE IF NOT EXISTS ingested_files (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    file_path TEXT NOT NULL UNIQUE,
+    source TEXT NOT NULL,
+    -- ingested, failed, skipped (already there when the folder was first watched)
+    -- or watched (the folder itself, once its existing files are skipped)
+    status TEXT NOT NULL,
+    ingested_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_ingested_files_source ON ingested_files(source);
@@ -0,0 +1,3 @@
+-- Whisper decoding confidence, NULL for engines that don't report it
+ALTER TABLE audio_transcriptions ADD COLUMN avg_logprob REAL;
+ALTER TABLE audio_transcriptions ADD COLUMN no_speech_prob REAL;
@@ -0,0 +1,3 @@
+-- Spoken language detected by whisper, NULL for engines that don't report it
+ALTER TABLE audio_transcriptions ADD COLUMN language TEXT;
+ALTER TABLE audio_transcriptions ADD COLUMN language_probability REAL;
@@ -0,0 +1,2 @@
+-- Why the hallucination filter flagged the transcription (repetition, known_phrase, no_speech), NULL if it didn't
+ALTER TABLE audio_transcriptions ADD COLUMN hallucination TEXT;
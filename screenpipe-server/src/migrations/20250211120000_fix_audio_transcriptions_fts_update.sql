@@ -0,0 +1,14 @@
+-- Update only the fts row of the updated transcription, not those of every segment of its chunk
+DROP TRIGGER IF EXISTS audio_transcriptions_update;
+
+CREATE TRIGGER IF NOT EXISTS audio_transcriptions_update AFTER UPDATE ON audio_transcriptions
+WHEN NEW.transcription IS NOT NULL AND NEW.transcription != '' AND OLD.audio_chunk_id IS NOT NULL
+BEGIN
+    UPDATE audio_transcriptions_fts
+    SET transcription = NEW.transcription,
+        device = COALESCE(NEW.device, ''),
+        start_time = NEW.start_time,
+        end_time = NEW.end_time
+    WHERE audio_chunk_id = OLD.audio_chunk_id
+      AND start_time IS OLD.start_time;
+END;
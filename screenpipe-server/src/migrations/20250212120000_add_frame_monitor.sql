@@ -0,0 +1,6 @@
+-- Monitor each frame was captured on, NULL for frames recorded before or added through the api
+ALTER TABLE frames ADD COLUMN monitor_name TEXT;
+ALTER TABLE frames ADD COLUMN monitor_width INTEGER;
+ALTER TABLE frames ADD COLUMN monitor_height INTEGER;
+ALTER TABLE frames ADD COLUMN monitor_x INTEGER;
+ALTER TABLE frames ADD COLUMN monitor_y INTEGER;
@@ -0,0 +1,2 @@
+-- Frame a frame looks the same as, it was not OCRed and shows the video frame of the original
+ALTER TABLE frames ADD COLUMN duplicate_of INTEGER;
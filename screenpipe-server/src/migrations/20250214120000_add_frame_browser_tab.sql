@@ -0,0 +1,3 @@
+-- Active tab of the browser focused when the frame was captured
+ALTER TABLE frames ADD COLUMN browser_url TEXT;
+ALTER TABLE frames ADD COLUMN tab_title TEXT;
@@ -0,0 +1,5 @@
+-- App and window focused when the frame was captured, whichever monitor it was on, and whether
+-- a video call was going on
+ALTER TABLE frames ADD COLUMN focused_app_name TEXT;
+ALTER TABLE frames ADD COLUMN focused_window_name TEXT;
+ALTER TABLE frames ADD COLUMN in_call BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,20 @@
+-- Elements of the accessibility tree of the focused window, read alongside ocr
+CREATE TABLE IF NOT EXISTS ui_elements (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    frame_id INTEGER NOT NULL,
+    app_name TEXT NOT NULL,
+    window_name TEXT NOT NULL,
+    role TEXT NOT NULL,
+    label TEXT NOT NULL,
+    text TEXT NOT NULL,
+    depth INTEGER NOT NULL,
+    x REAL,
+    y REAL,
+    width REAL,
+    height REAL,
+    FOREIGN KEY (frame_id) REFERENCES frames(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_ui_elements_frame_id ON ui_elements(frame_id);
+CREATE INDEX IF NOT EXISTS idx_ui_elements_role ON ui_elements(role);
+CREATE INDEX IF NOT EXISTS idx_ui_elements_app_name ON ui_elements(app_name);
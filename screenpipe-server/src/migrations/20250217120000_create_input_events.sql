@@ -0,0 +1,22 @@
+-- Clicks, and keypresses and scrolls counted over a few seconds, never the keys typed. frame_id is
+-- the frame captured last before the event
+CREATE TABLE IF NOT EXISTS input_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    kind TEXT NOT NULL,
+    count INTEGER NOT NULL DEFAULT 1,
+    x REAL,
+    y REAL,
+    button TEXT,
+    delta_x INTEGER,
+    delta_y INTEGER,
+    app_name TEXT,
+    window_name TEXT,
+    element_role TEXT,
+    element_label TEXT,
+    frame_id INTEGER,
+    FOREIGN KEY (frame_id) REFERENCES frames(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_input_events_timestamp ON input_events(timestamp);
+CREATE INDEX IF NOT EXISTS idx_input_events_frame_id ON input_events(frame_id);
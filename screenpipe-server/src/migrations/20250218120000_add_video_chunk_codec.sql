@@ -0,0 +1,2 @@
+-- Codec a video chunk was re-encoded to by the archive, null while it is as it was recorded
+ALTER TABLE video_chunks ADD COLUMN codec TEXT;
@@ -0,0 +1,17 @@
+-- Lines of ocr text with their position in the window, in fractions of its size from its top left
+-- corner, in reading order. The boxes of their words are in `words`, as json
+CREATE TABLE IF NOT EXISTS ocr_lines (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    frame_id INTEGER NOT NULL,
+    block_index INTEGER NOT NULL,
+    line_index INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    x REAL NOT NULL,
+    y REAL NOT NULL,
+    width REAL NOT NULL,
+    height REAL NOT NULL,
+    words TEXT NOT NULL,
+    FOREIGN KEY (frame_id) REFERENCES frames(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_ocr_lines_frame_id ON ocr_lines(frame_id);
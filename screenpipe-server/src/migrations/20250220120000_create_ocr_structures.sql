@@ -0,0 +1,17 @@
+-- Tables and blocks of code read in a window, with their position in it in fractions of its size
+-- from its top left corner. `content` is their cells or their indented lines as json, `text` what
+-- is copied out of them
+CREATE TABLE IF NOT EXISTS ocr_structures (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    frame_id INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    text TEXT NOT NULL,
+    content TEXT NOT NULL,
+    x REAL NOT NULL,
+    y REAL NOT NULL,
+    width REAL NOT NULL,
+    height REAL NOT NULL,
+    FOREIGN KEY (frame_id) REFERENCES frames(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_ocr_structures_frame_id ON ocr_structures(frame_id);
@@ -0,0 +1,10 @@
+-- CLIP embedding of the image of a frame, to find frames from a description of what was on screen
+CREATE TABLE IF NOT EXISTS frame_embeddings (
+    frame_id INTEGER PRIMARY KEY,
+    embedding FLOAT[512] NOT NULL
+    check(
+      typeof(embedding) == 'blob'
+      and vec_length(embedding) == 512
+    ),
+    FOREIGN KEY (frame_id) REFERENCES frames(id) ON DELETE CASCADE
+);
@@ -0,0 +1,11 @@
+-- Stretches of time nothing was captured as the user was idle or the screen was locked
+CREATE TABLE IF NOT EXISTS capture_pauses (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- "idle" or "locked"
+    reason TEXT NOT NULL,
+    start_time TIMESTAMP NOT NULL,
+    -- NULL while the pause goes on
+    end_time TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_capture_pauses_start_time ON capture_pauses(start_time);
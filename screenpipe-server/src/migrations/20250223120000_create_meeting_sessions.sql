@@ -0,0 +1,12 @@
+-- Meetings detected, what was captured between their start and end is part of them
+CREATE TABLE IF NOT EXISTS meeting_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- app and title of the window the meeting was detected in
+    app_name TEXT NOT NULL,
+    title TEXT NOT NULL,
+    start_time TIMESTAMP NOT NULL,
+    -- NULL while the meeting goes on
+    end_time TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_meeting_sessions_start_time ON meeting_sessions(start_time);
@@ -0,0 +1,2 @@
+-- Virtual desktop shown on the monitor when the frame was captured, from 1
+ALTER TABLE frames ADD COLUMN virtual_desktop INTEGER;
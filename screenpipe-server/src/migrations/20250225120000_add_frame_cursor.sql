@@ -0,0 +1,9 @@
+-- Where the pointer and the text selected in the focused window were on the frame, in fractions
+-- of its size from its top left corner, for replays to show them over it
+ALTER TABLE frames ADD COLUMN cursor_x REAL;
+ALTER TABLE frames ADD COLUMN cursor_y REAL;
+ALTER TABLE frames ADD COLUMN selected_text TEXT;
+ALTER TABLE frames ADD COLUMN selection_x REAL;
+ALTER TABLE frames ADD COLUMN selection_y REAL;
+ALTER TABLE frames ADD COLUMN selection_width REAL;
+ALTER TABLE frames ADD COLUMN selection_height REAL;
@@ -0,0 +1,16 @@
+-- Embedding of the text of a transcription, to search transcriptions by meaning with /search/semantic
+CREATE TABLE IF NOT EXISTS audio_transcription_embeddings (
+    audio_transcription_id INTEGER PRIMARY KEY,
+    embedding BLOB NOT NULL,
+    FOREIGN KEY (audio_transcription_id) REFERENCES audio_transcriptions(id) ON DELETE CASCADE
+);
+
+-- an embedding of text that was corrected no longer matches it, it's computed again
+CREATE TRIGGER IF NOT EXISTS audio_transcription_embeddings_au
+AFTER UPDATE OF transcription ON audio_transcriptions
+BEGIN
+    DELETE FROM audio_transcription_embeddings WHERE audio_transcription_id = NEW.id;
+END;
+
+-- to find the frames whose text isn't embedded yet
+CREATE INDEX IF NOT EXISTS idx_ocr_text_embeddings_frame_id ON ocr_text_embeddings(frame_id);
@@ -0,0 +1,9 @@
+-- How a transcription was made, to filter and audit transcriptions once engines and models change
+ALTER TABLE audio_transcriptions ADD COLUMN audio_duration REAL;
+ALTER TABLE audio_transcriptions ADD COLUMN captured_until TIMESTAMP;
+ALTER TABLE audio_transcriptions ADD COLUMN latency REAL;
+-- NULL for cloud engines not reporting their model
+ALTER TABLE audio_transcriptions ADD COLUMN model_version TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_audio_transcriptions_transcription_engine ON audio_transcriptions(transcription_engine);
+CREATE INDEX IF NOT EXISTS idx_audio_transcriptions_model_version ON audio_transcriptions(model_version);
@@ -0,0 +1,127 @@
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::db_types::Speaker;
+
+/// Speakers heard within this window are reported as active
+const ACTIVE_SPEAKER_WINDOW_SECS: i64 = 60;
+
+static CURRENT_CONTEXT: Lazy<RwLock<CurrentContext>> =
+    Lazy::new(|| RwLock::new(CurrentContext::default()));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveSpeaker {
+    pub id: i64,
+    pub name: String,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NowResponse {
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    pub focus_updated_at: Option<DateTime<Utc>>,
+    pub last_transcript: Option<String>,
+    pub last_transcript_device: Option<String>,
+    pub transcript_updated_at: Option<DateTime<Utc>>,
+    pub active_speakers: Vec<ActiveSpeaker>,
+}
+
+#[derive(Default)]
+struct CurrentContext {
+    app_name: Option<String>,
+    window_name: Option<String>,
+    focus_updated_at: Option<DateTime<Utc>>,
+    last_transcript: Option<String>,
+    last_transcript_device: Option<String>,
+    transcript_updated_at: Option<DateTime<Utc>>,
+    speakers: HashMap<i64, ActiveSpeaker>,
+}
+
+/// Records the window that currently has focus. Called from the capture loop, never hits the db.
+pub fn update_focused_window(app_name: &str, window_name: &str) {
+    if let Ok(mut ctx) = CURRENT_CONTEXT.write() {
+        ctx.app_name = Some(app_name.to_string());
+        ctx.window_name = Some(window_name.to_string());
+        ctx.focus_updated_at = Some(Utc::now());
+    }
+}
+
+/// Records the latest transcript and marks its speaker as active
+pub fn update_transcript(device: &str, transcript: &str, speaker: Option<&Speaker>) {
+    let now = Utc::now();
+    if let Ok(mut ctx) = CURRENT_CONTEXT.write() {
+        if let Some(sentence) = last_sentence(transcript) {
+            ctx.last_transcript = Some(sentence);
+            ctx.last_transcript_device = Some(device.to_string());
+            ctx.transcript_updated_at = Some(now);
+        }
+
+        if let Some(speaker) = speaker {
+            ctx.speakers.insert(
+                speaker.id,
+                ActiveSpeaker {
+                    id: speaker.id,
+                    name: speaker.name.clone(),
+                    last_seen: now,
+                },
+            );
+        }
+
+        ctx.speakers.retain(|_, s| {
+            now.signed_duration_since(s.last_seen).num_seconds() < ACTIVE_SPEAKER_WINDOW_SECS
+        });
+    }
+}
+
+pub fn current_context() -> NowResponse {
+    let now = Utc::now();
+    let ctx = match CURRENT_CONTEXT.read() {
+        Ok(ctx) => ctx,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let mut active_speakers: Vec<ActiveSpeaker> = ctx
+        .speakers
+        .values()
+        .filter(|s| {
+            now.signed_duration_since(s.last_seen).num_seconds() < ACTIVE_SPEAKER_WINDOW_SECS
+        })
+        .cloned()
+        .collect();
+    active_speakers.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+
+    NowResponse {
+        app_name: ctx.app_name.clone(),
+        window_name: ctx.window_name.clone(),
+        focus_updated_at: ctx.focus_updated_at,
+        last_transcript: ctx.last_transcript.clone(),
+        last_transcript_device: ctx.last_transcript_device.clone(),
+        transcript_updated_at: ctx.transcript_updated_at,
+        active_speakers,
+    }
+}
+
+/// Returns the last complete-looking sentence of a transcript, or the trailing fragment if none
+pub fn last_sentence(transcript: &str) -> Option<String> {
+    let trimmed = transcript.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let body = trimmed.trim_end_matches(['.', '!', '?', '。', '！', '？']);
+    let start = body
+        .rfind(['.', '!', '?', '。', '！', '？', '\n'])
+        .map(|i| i + body[i..].chars().next().map_or(1, |c| c.len_utf8()))
+        .unwrap_or(0);
+
+    let sentence = trimmed[start..].trim();
+    if sentence.is_empty() {
+        None
+    } else {
+        Some(sentence.to_string())
+    }
+}
@@ -0,0 +1,135 @@
+use serde::Serialize;
+
+/// Stages slower than this get a suggestion attached
+const SLOW_STAGE_MS: f64 = 500.0;
+/// Full scans of tables bigger than this are worth narrowing down
+const LARGE_SCAN_ROWS: i64 = 100_000;
+
+/// What a search did under the hood, returned by `/search/explain`
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryPlanReport {
+    /// The query as it was sent to the full text index, after analyzer rewriting
+    pub normalized_query: String,
+    pub total_ms: f64,
+    pub stages: Vec<StageReport>,
+    pub suggestions: Vec<String>,
+}
+
+/// One of the sub-queries a search runs (ocr, audio, ui, count)
+#[derive(Debug, Clone, Serialize)]
+pub struct StageReport {
+    pub stage: String,
+    pub elapsed_ms: f64,
+    pub rows_returned: usize,
+    /// sqlite `EXPLAIN QUERY PLAN` lines, empty for stages without a single statement
+    pub plan: Vec<String>,
+    pub indexes_used: Vec<String>,
+    /// Tables read row by row, with an estimate of their row count as an upper bound of rows
+    /// scanned
+    pub full_scans: Vec<TableScan>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableScan {
+    pub table: String,
+    pub rows: i64,
+}
+
+/// Filters of the explained search that the suggestions depend on
+#[derive(Debug, Clone, Default)]
+pub struct SearchShape {
+    pub has_text_query: bool,
+    pub has_time_range: bool,
+    pub has_app_or_window_filter: bool,
+    pub all_content_types: bool,
+}
+
+/// Indexes used and tables fully scanned according to `EXPLAIN QUERY PLAN` details, e.g.
+/// "SEARCH frames USING INDEX idx_frames_timestamp (timestamp>?)" or "SCAN ocr_text"
+pub fn parse_plan(details: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut indexes = Vec::new();
+    let mut scanned = Vec::new();
+
+    for detail in details {
+        // sqlite before 3.36 prints "SCAN TABLE x"
+        let detail =
+            detail
+                .replacen("SCAN TABLE ", "SCAN ", 1)
+                .replacen("SEARCH TABLE ", "SEARCH ", 1);
+        let words: Vec<&str> = detail.split_whitespace().collect();
+        if let Some(pos) = words.iter().position(|w| *w == "INDEX") {
+            // "VIRTUAL TABLE INDEX 0:M1" is the fts5 index, named after the table
+            if pos >= 2 && words[pos - 2] == "VIRTUAL" {
+                if let Some(table) = words.get(1).filter(|t| t.ends_with("_fts")) {
+                    push_unique(&mut indexes, format!("{} (fts)", table));
+                }
+            } else if let Some(index) = words.get(pos + 1) {
+                push_unique(&mut indexes, index.to_string());
+            }
+        } else if detail.contains("USING INTEGER PRIMARY KEY") {
+            if let Some(table) = words.get(1) {
+                push_unique(&mut indexes, format!("{} (primary key)", table));
+            }
+        } else if words.first() == Some(&"SCAN") {
+            if let Some(table) = words.get(1) {
+                let is_table = table.chars().all(|c| c.is_alphanumeric() || c == '_');
+                if is_table && *table != "CONSTANT" {
+                    push_unique(&mut scanned, table.to_string());
+                }
+            }
+        }
+    }
+
+    (indexes, scanned)
+}
+
+pub fn suggestions(shape: &SearchShape, stages: &[StageReport]) -> Vec<String> {
+    let mut suggestions = Vec::new();
+
+    let large_scans: Vec<&TableScan> = stages
+        .iter()
+        .flat_map(|s| s.full_scans.iter())
+        .filter(|s| s.rows >= LARGE_SCAN_ROWS)
+        .collect();
+
+    if !shape.has_text_query && !large_scans.is_empty() {
+        suggestions.push(
+            "add a text query (q) so the full text index can be used instead of scanning every row"
+                .to_string(),
+        );
+    }
+    if !shape.has_time_range && !large_scans.is_empty() {
+        let tables: Vec<&str> = large_scans.iter().map(|s| s.table.as_str()).collect();
+        suggestions.push(format!(
+            "add start_time/end_time to narrow the scan of {}",
+            tables.join(", ")
+        ));
+    }
+    if shape.has_app_or_window_filter && !shape.has_text_query && !shape.has_time_range {
+        suggestions.push(
+            "app_name/window_name match substrings (LIKE '%...%') and can't use an index, combine them with a time range or text query"
+                .to_string(),
+        );
+    }
+    if shape.all_content_types && stages.iter().filter(|s| s.stage != "count").count() > 1 {
+        suggestions.push(
+            "set content_type (ocr, audio, ui) to skip the sub-queries you don't need".to_string(),
+        );
+    }
+    for stage in stages {
+        if stage.elapsed_ms >= SLOW_STAGE_MS {
+            suggestions.push(format!(
+                "{} stage took {:.0}ms, lower the limit or narrow the filters",
+                stage.stage, stage.elapsed_ms
+            ));
+        }
+    }
+
+    suggestions
+}
+
+fn push_unique(items: &mut Vec<String>, item: String) {
+    if !items.contains(&item) {
+        items.push(item);
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::Result;
+use candle::{DType, Device, Tensor};
+use candle_nn::{Linear, Module, VarBuilder};
+use candle_transformers::models::bert::{BertModel, Config};
+use once_cell::sync::OnceCell;
+use screenpipe_core::models::{get_or_download_with_events, ModelFile};
+use screenpipe_core::pii_removal::{count_redactions, remove_pii};
+use std::ops::Range;
+use tokenizers::models::wordpiece::WordPiece;
+use tokenizers::normalizers::BertNormalizer;
+use tokenizers::pre_tokenizers::bert::BertPreTokenizer;
+use tokenizers::Tokenizer;
+use tracing::{debug, info};
+
+/// Small cased BERT finetuned to tag people, organizations and places
+const NER_MODEL: &str = "dslim/bert-base-NER";
+/// Tokens the model reads at once, without [CLS] and [SEP]
+const MAX_TOKENS: usize = 510;
+const NAME_PLACEHOLDER: &str = "[NAME]";
+
+static REDACTION: OnceCell<Redaction> = OnceCell::new();
+
+/// What is redacted before it is written to the database, besides the OCR text of
+/// --use-pii-removal
+#[derive(Clone, Debug, Default)]
+pub struct RedactionOptions {
+    /// Transcripts too
+    pub transcripts: bool,
+    /// Names of people found by an NER model, the patterns can't tell a name from other words
+    pub names: bool,
+}
+
+struct Redaction {
+    transcripts: bool,
+    names: Option<NameRecognizer>,
+}
+
+/// Loads the NER model when names are redacted. Text is only redacted with the patterns until
+/// it is called
+pub fn init(options: RedactionOptions) -> Result<()> {
+    let names = if options.names {
+        info!("loading {} to redact names", NER_MODEL);
+        Some(NameRecognizer::load()?)
+    } else {
+        None
+    };
+    let _ = REDACTION.set(Redaction {
+        transcripts: options.transcripts,
+        names,
+    });
+    Ok(())
+}
+
+/// Masks the emails, card numbers, SSNs, api keys and, when enabled, names in `text`
+pub fn redact(text: &str) -> String {
+    let text = remove_pii(text);
+    let Some(names) = REDACTION
+        .get()
+        .and_then(|redaction| redaction.names.as_ref())
+    else {
+        return text;
+    };
+    match names.person_spans(&text) {
+        Ok(spans) if spans.is_empty() => text,
+        Ok(spans) => {
+            let mut redacted = text;
+            count_redactions(NAME_PLACEHOLDER, spans.len() as u64);
+            for span in spans.into_iter().rev() {
+                redacted.replace_range(span, NAME_PLACEHOLDER);
+            }
+            redacted
+        }
+        Err(e) => {
+            debug!("failed to find names to redact: {}", e);
+            text
+        }
+    }
+}
+
+/// Runs `f` on a blocking thread, finding names runs a BERT model that would stall the async
+/// runtime
+pub async fn off_runtime<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Ok(tokio::task::spawn_blocking(f).await?)
+}
+
+/// `transcript` redacted when transcripts are
+pub fn redact_transcript(transcript: &str) -> String {
+    if REDACTION
+        .get()
+        .is_some_and(|redaction| redaction.transcripts)
+    {
+        redact(transcript)
+    } else {
+        transcript.to_string()
+    }
+}
+
+struct NameRecognizer {
+    model: BertModel,
+    classifier: Linear,
+    tokenizer: Tokenizer,
+    /// Label of each class, e.g. "B-PER" for the first token of the name of a person
+    labels: Vec<String>,
+    cls: u32,
+    sep: u32,
+    device: Device,
+}
+
+impl NameRecognizer {
+    fn load() -> Result<Self> {
+        let device = Device::Cpu;
+        let fetch =
+            |file: &str| get_or_download_with_events(&ModelFile::huggingface(NER_MODEL, file));
+        let config_file = fetch("config.json")?;
+        let vocab_file = fetch("vocab.txt")?;
+        let model_file = fetch("model.safetensors")?;
+
+        let config_json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&config_file)?)?;
+        let config: Config = serde_json::from_value(config_json.clone())?;
+        let id2label = config_json["id2label"]
+            .as_object()
+            .ok_or_else(|| anyhow::anyhow!("{} has no labels", NER_MODEL))?;
+        let mut labels = vec![String::new(); id2label.len()];
+        for (id, label) in id2label {
+            let id: usize = id.parse()?;
+            if let (Some(slot), Some(label)) = (labels.get_mut(id), label.as_str()) {
+                *slot = label.to_string();
+            }
+        }
+
+        // the model only ships the vocabulary of its wordpiece tokenizer, cased
+        let wordpiece = WordPiece::from_file(&vocab_file.to_string_lossy())
+            .build()
+            .map_err(anyhow::Error::msg)?;
+        let mut tokenizer = Tokenizer::new(wordpiece);
+        tokenizer.with_normalizer(Some(BertNormalizer::new(true, true, None, false)));
+        tokenizer.with_pre_tokenizer(Some(BertPreTokenizer));
+        let token_id = |token: &str| {
+            tokenizer
+                .token_to_id(token)
+                .ok_or_else(|| anyhow::anyhow!("{} has no {} token", NER_MODEL, token))
+        };
+        let (cls, sep) = (token_id("[CLS]")?, token_id("[SEP]")?);
+
+        let vb =
+            unsafe { VarBuilder::from_mmaped_safetensors(&[model_file], DType::F32, &device)? };
+        let model = BertModel::load(vb.clone(), &config)?;
+        let classifier = candle_nn::linear(config.hidden_size, labels.len(), vb.pp("classifier"))?;
+
+        Ok(Self {
+            model,
+            classifier,
+            tokenizer,
+            labels,
+            cls,
+            sep,
+            device,
+        })
+    }
+
+    /// Byte ranges of `text` the model tags as names of people, a range per name
+    fn person_spans(&self, text: &str) -> Result<Vec<Range<usize>>> {
+        let encoding = self
+            .tokenizer
+            .encode(text, false)
+            .map_err(anyhow::Error::msg)?;
+        let offsets = encoding.get_offsets();
+        let mut spans: Vec<Range<usize>> = Vec::new();
+        let mut last_person_token: Option<usize> = None;
+
+        for (chunk_index, chunk) in encoding.get_ids().chunks(MAX_TOKENS).enumerate() {
+            let mut ids = Vec::with_capacity(chunk.len() + 2);
+            ids.push(self.cls);
+            ids.extend_from_slice(chunk);
+            ids.push(self.sep);
+            let input_ids = Tensor::new(ids.as_slice(), &self.device)?.unsqueeze(0)?;
+            let token_type_ids = input_ids.zeros_like()?;
+            let hidden = self.model.forward(&input_ids, &token_type_ids, None)?;
+            let classes = self
+                .classifier
+                .forward(&hidden)?
+                .squeeze(0)?
+                .argmax(1)?
+                .to_vec1::<u32>()?;
+
+            // without the classes of [CLS] and [SEP]
+            for (i, &class) in classes[1..classes.len() - 1].iter().enumerate() {
+                let token = chunk_index * MAX_TOKENS + i;
+                let is_person = self
+                    .labels
+                    .get(class as usize)
+                    .is_some_and(|label| label.ends_with("-PER"));
+                if !is_person {
+                    continue;
+                }
+                let (start, end) = offsets[token];
+                match spans.last_mut() {
+                    // the next word, or piece of word, of the same name
+                    Some(span) if last_person_token.is_some_and(|last| last + 1 == token) => {
+                        span.end = end
+                    }
+                    _ => spans.push(start..end),
+                }
+                last_person_token = Some(token);
+            }
+        }
+        Ok(spans)
+    }
+}
@@ -0,0 +1,244 @@
+use crate::fts_query::{self, FtsQuery, QueryNode, Term};
+use rust_stemmers::{Algorithm, Stemmer};
+use screenpipe_core::Language;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How text is tokenized in the FTS index and how queries are rewritten before hitting it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchAnalyzer {
+    /// unicode61 tokens, exact (case-insensitive) word matches
+    #[default]
+    Basic,
+    /// like basic but folds diacritics, "réunion" matches "reunion"
+    Folded,
+    /// folded + stop word removal and stemmed prefix queries for the configured languages
+    Stemmed,
+    /// trigram index so CJK text (no word boundaries) can be searched by substring
+    Cjk,
+}
+
+impl SearchAnalyzer {
+    pub fn fts_tokenizer(&self) -> &'static str {
+        match self {
+            SearchAnalyzer::Basic => "unicode61",
+            SearchAnalyzer::Folded | SearchAnalyzer::Stemmed => "unicode61 remove_diacritics 2",
+            SearchAnalyzer::Cjk => "trigram",
+        }
+    }
+
+    /// Picks a sensible analyzer for the languages the user records in
+    pub fn for_languages(languages: &[Language]) -> Self {
+        if languages.iter().any(is_cjk) {
+            SearchAnalyzer::Cjk
+        } else if !languages.is_empty() && languages.iter().all(|l| stemmer_for(l).is_some()) {
+            SearchAnalyzer::Stemmed
+        } else {
+            SearchAnalyzer::Folded
+        }
+    }
+}
+
+impl fmt::Display for SearchAnalyzer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SearchAnalyzer::Basic => "basic",
+            SearchAnalyzer::Folded => "folded",
+            SearchAnalyzer::Stemmed => "stemmed",
+            SearchAnalyzer::Cjk => "cjk",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for SearchAnalyzer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "basic" => Ok(SearchAnalyzer::Basic),
+            "folded" => Ok(SearchAnalyzer::Folded),
+            "stemmed" => Ok(SearchAnalyzer::Stemmed),
+            "cjk" => Ok(SearchAnalyzer::Cjk),
+            _ => Err(format!("unknown search analyzer: {}", s)),
+        }
+    }
+}
+
+/// Analyzer plus the languages it applies to, used to rewrite user queries
+#[derive(Clone, Debug, Default)]
+pub struct QueryAnalyzer {
+    pub analyzer: SearchAnalyzer,
+    pub languages: Vec<Language>,
+}
+
+impl QueryAnalyzer {
+    pub fn new(analyzer: SearchAnalyzer, languages: Vec<Language>) -> Self {
+        Self {
+            analyzer,
+            languages,
+        }
+    }
+
+    /// Parses a query and rewrites its words for the index. Quoted phrases and prefixes are
+    /// searched as typed
+    pub fn parse_query(&self, query: &str) -> FtsQuery {
+        let query = query.trim();
+        if self.analyzer == SearchAnalyzer::Cjk && !query.is_empty() && !uses_fts_syntax(query) {
+            // trigram matches substrings, quoting keeps multi-word queries contiguous
+            return FtsQuery(Some(QueryNode::Term(Term {
+                text: query.to_string(),
+                quoted: true,
+                prefix: false,
+                field: None,
+            })));
+        }
+
+        let node = fts_query::parse(query);
+        match self.analyzer {
+            SearchAnalyzer::Stemmed => FtsQuery(node.and_then(|node| self.stem_query(node))),
+            _ => FtsQuery(node),
+        }
+    }
+
+    /// The query to search as a substring of the text instead of through the index. The trigram
+    /// index can't match anything shorter than 3 characters, like the 2 character words common in
+    /// chinese and japanese, or short english terms in an index built for them too
+    pub fn substring_query(&self, query: &str) -> Option<String> {
+        let query = query.trim();
+        (self.analyzer == SearchAnalyzer::Cjk
+            && !query.is_empty()
+            && query.chars().count() < 3
+            && !uses_fts_syntax(query))
+        .then(|| query.to_string())
+    }
+
+    fn stem_query(&self, query: QueryNode) -> Option<QueryNode> {
+        // a query made only of stop words is still a query, search it as typed
+        if query
+            .terms()
+            .iter()
+            .all(|term| term.quoted || term.prefix || self.is_stop_word(&term.text))
+        {
+            return Some(query);
+        }
+
+        let stemmers: Vec<Stemmer> = self
+            .languages
+            .iter()
+            .filter_map(stemmer_for)
+            .map(Stemmer::create)
+            .collect();
+
+        query.map_terms(&mut |term| {
+            if term.quoted || term.prefix {
+                return Some(term);
+            }
+            if self.is_stop_word(&term.text) {
+                return None;
+            }
+            if !term.text.chars().all(char::is_alphanumeric) {
+                return Some(term);
+            }
+            let lower = term.text.to_lowercase();
+            let stem = stemmers
+                .iter()
+                .map(|s| s.stem(&lower).into_owned())
+                .min_by_key(|s| s.chars().count())
+                .unwrap_or_else(|| lower.clone());
+            // very short stems would match half the index
+            Some(if stem.chars().count() < 3 {
+                Term {
+                    text: lower,
+                    ..term
+                }
+            } else {
+                Term {
+                    text: stem,
+                    prefix: true,
+                    ..term
+                }
+            })
+        })
+    }
+
+    fn is_stop_word(&self, term: &str) -> bool {
+        let lower = term.to_lowercase();
+        self.languages
+            .iter()
+            .any(|l| stop_words(l).contains(&lower.as_str()))
+    }
+}
+
+fn uses_fts_syntax(query: &str) -> bool {
+    query.contains(['"', '*', '(', ')', ':', '^', '{', '}', '+', '-'])
+        || query
+            .split_whitespace()
+            .any(|t| matches!(t, "AND" | "OR" | "NOT" | "NEAR"))
+}
+
+fn is_cjk(language: &Language) -> bool {
+    matches!(
+        language,
+        Language::Chinese
+            | Language::Japanese
+            | Language::Korean
+            | Language::Thai
+            | Language::Lao
+            | Language::Khmer
+    )
+}
+
+fn stemmer_for(language: &Language) -> Option<Algorithm> {
+    match language {
+        Language::English => Some(Algorithm::English),
+        Language::Arabic => Some(Algorithm::Arabic),
+        Language::Danish => Some(Algorithm::Danish),
+        Language::Dutch => Some(Algorithm::Dutch),
+        Language::Finnish => Some(Algorithm::Finnish),
+        Language::French => Some(Algorithm::French),
+        Language::German => Some(Algorithm::German),
+        Language::Greek => Some(Algorithm::Greek),
+        Language::Hungarian => Some(Algorithm::Hungarian),
+        Language::Italian => Some(Algorithm::Italian),
+        Language::Norwegian => Some(Algorithm::Norwegian),
+        Language::Portuguese => Some(Algorithm::Portuguese),
+        Language::Romanian => Some(Algorithm::Romanian),
+        Language::Russian => Some(Algorithm::Russian),
+        Language::Spanish => Some(Algorithm::Spanish),
+        Language::Swedish => Some(Algorithm::Swedish),
+        Language::Turkish => Some(Algorithm::Turkish),
+        _ => None,
+    }
+}
+
+fn stop_words(language: &Language) -> &'static [&'static str] {
+    match language {
+        Language::English => &[
+            "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "in", "is", "it", "of",
+            "on", "or", "that", "the", "this", "to", "was", "were", "with",
+        ],
+        Language::French => &[
+            "au", "aux", "avec", "ce", "dans", "de", "des", "du", "en", "est", "et", "la", "le",
+            "les", "par", "pour", "sur", "un", "une",
+        ],
+        Language::German => &[
+            "der", "die", "das", "den", "dem", "des", "ein", "eine", "einen", "und", "oder", "ist",
+            "im", "in", "mit", "von", "zu", "auf", "für",
+        ],
+        Language::Spanish => &[
+            "el", "la", "los", "las", "un", "una", "y", "o", "de", "del", "en", "con", "por",
+            "para", "es", "que",
+        ],
+        Language::Portuguese => &[
+            "o", "a", "os", "as", "um", "uma", "e", "ou", "de", "do", "da", "em", "com", "por",
+            "para", "que",
+        ],
+        Language::Italian => &[
+            "il", "lo", "la", "i", "gli", "le", "un", "una", "e", "o", "di", "del", "della", "in",
+            "con", "per", "che",
+        ],
+        _ => &[],
+    }
+}
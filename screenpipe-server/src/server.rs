@@ -21,7 +21,11 @@ use image::ImageFormat::{self};
 use screenpipe_events::{send_event, subscribe_to_all_events, Event as ScreenpipeEvent};
 
 use crate::{
-    db_types::{ContentType, FrameData, SearchResult, Speaker, TagContentType},
+    db_types::{
+        AlignedFrame, CapturePause, ContentType, FrameData, InputActivityBucket, InputEventResult,
+        MeetingSession, OcrLineResult, SearchResult, Speaker, TagContentType, TranscriptSegment,
+        UiElementResult, VisualSearchResult,
+    },
     pipe_manager::PipeManager,
     video::{finish_ffmpeg_process, start_ffmpeg_process, write_frame_to_ffmpeg, MAX_FPS},
     video_cache::{AudioEntry, DeviceFrame, FrameCache, FrameMetadata, TimeSeriesFrame},
@@ -31,14 +35,39 @@ use crate::{
     },
     DatabaseManager,
 };
+use crate::eval_dataset::{AudioSample, EvalDataset, OcrSample};
+use crate::now::{current_context, NowResponse};
+use crate::query_plan::QueryPlanReport;
+use crate::redaction::{self, redact};
 use crate::{plugin::ApiPluginLayer, video_utils::extract_frame};
+use base64::{engine::general_purpose, Engine as _};
 use chrono::{DateTime, Utc};
 use screenpipe_audio::{
-    default_input_device, default_output_device, list_audio_devices, AudioDevice, DeviceType,
+    default_input_device, default_output_device, list_audio_devices, list_audio_hosts, AudioDevice,
+    AudioHost, DetectedLanguage, DeviceType, Hallucination, SegmentConfidence,
+};
+use screenpipe_audio::engine_health::{engine_health, EngineHealth};
+use screenpipe_audio::speech_stats::{speech_stats, SpeechStats};
+use screenpipe_audio::whisper::active_whisper_device;
+use screenpipe_core::pii_removal::redaction_counts;
+use screenpipe_core::capture_pause::{self, PauseReason};
+use screenpipe_core::Language;
+use screenpipe_audio::pcm_decode;
+use screenpipe_audio::pyannote::{
+    embedding::EmbeddingExtractor,
+    enrollment::enrollment_embedding,
+    models::{get_or_download_model, PyannoteModel},
 };
 use tracing::{debug, error, info};
 
-use screenpipe_vision::monitor::{list_monitors, get_monitor_by_id};
+use screenpipe_vision::browser_tab::{report_tab, BrowserTab};
+use screenpipe_vision::clip;
+use screenpipe_vision::capture_screenshot_by_window::WindowFilters;
+use screenpipe_vision::cursor::CursorOverlay;
+use screenpipe_vision::monitor::{get_default_monitor, get_monitor_by_id, list_monitors};
+use screenpipe_vision::capture_frame;
+use screenpipe_vision::ocr_layout::{LayoutBox, OcrLayout};
+use screenpipe_vision::ocr_structure::OcrStructure;
 use screenpipe_vision::OcrEngine;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{json, Value};
@@ -69,7 +98,8 @@ use screenpipe_audio::LAST_AUDIO_CAPTURE;
 
 use std::str::FromStr;
 
-use crate::text_embeds::generate_embedding;
+use crate::hybrid_search::fuse;
+use crate::text_embeds::{embed, generate_embedding};
 
 pub struct AppState {
     pub db: Arc<DatabaseManager>,
@@ -82,6 +112,19 @@ pub struct AppState {
     pub ui_monitoring_enabled: bool,
     pub frame_cache: Option<Arc<FrameCache>>,
     pub frame_image_cache: Option<Arc<Mutex<LruCache<i64, (String, Instant)>>>>,
+    pub eval_dataset: Option<Arc<EvalDataset>>,
+    /// None when vision is disabled
+    pub screenshot_options: Option<Arc<ScreenshotOptions>>,
+}
+
+/// What on demand screenshots are captured and read with, the same as the frames recorded
+#[derive(Clone)]
+pub struct ScreenshotOptions {
+    pub ocr_engine: OcrEngine,
+    pub languages: Vec<Language>,
+    pub window_filters: WindowFilters,
+    pub capture_unfocused_windows: bool,
+    pub use_pii_removal: bool,
 }
 
 // Update the SearchQuery struct
@@ -113,6 +156,32 @@ pub(crate) struct SearchQuery {
         default = "default_speaker_ids"
     )]
     speaker_ids: Option<Vec<i64>>,
+    /// ISO 639-1 code of the spoken language, only audio detected in it is returned
+    #[serde(default)]
+    language: Option<String>,
+    /// Part of the url of the page a browser showed, e.g. "github.com", only frames of it are
+    /// returned
+    #[serde(default)]
+    browser_url: Option<String>,
+    /// Part of the name of the app focused when the frame was captured, whichever window the
+    /// text is of
+    #[serde(default)]
+    focused_app_name: Option<String>,
+    /// Only frames captured during a video call, or outside of one
+    #[serde(default)]
+    in_call: Option<bool>,
+    /// Only frames of this virtual desktop, from 1
+    #[serde(default)]
+    virtual_desktop: Option<u32>,
+    /// Part of the text selected in the focused window when the frame was captured
+    #[serde(default)]
+    selected_text: Option<String>,
+    /// Only audio transcribed by this engine, e.g. "WhisperLargeV3Turbo"
+    #[serde(default)]
+    transcription_engine: Option<String>,
+    /// Only audio transcribed with this model, e.g. "openai/whisper-large-v3-turbo"
+    #[serde(default)]
+    model_version: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -189,6 +258,12 @@ pub struct OCRContent {
     pub tags: Vec<String>,
     pub frame: Option<String>,
     pub frame_name: Option<String>,
+    pub browser_url: Option<String>,
+    pub focused_app_name: Option<String>,
+    pub focused_window_name: Option<String>,
+    pub in_call: bool,
+    pub virtual_desktop: Option<u32>,
+    pub selected_text: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -204,6 +279,21 @@ pub struct AudioContent {
     pub speaker: Option<Speaker>,
     pub start_time: Option<f64>,
     pub end_time: Option<f64>,
+    /// Whisper's confidence, low scores are likely hallucinations
+    pub confidence: Option<SegmentConfidence>,
+    /// Spoken language detected by whisper
+    pub language: Option<DetectedLanguage>,
+    /// Why the hallucination filter flagged the transcription, if it did
+    pub hallucination: Option<Hallucination>,
+    pub transcription_engine: String,
+    /// Model the transcription was made with, None for cloud engines not reporting it
+    pub model_version: Option<String>,
+    /// Seconds of audio transcribed
+    pub audio_duration: Option<f64>,
+    /// When its audio stopped being captured
+    pub captured_until: Option<DateTime<Utc>>,
+    /// Seconds from the end of the capture to the transcription
+    pub latency: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -271,6 +361,17 @@ pub struct HealthCheckResponse {
     pub ui_status: String,
     pub message: String,
     pub verbose_instructions: Option<String>,
+    /// Device the local transcription model runs on, e.g. "cuda:0", None until it is loaded
+    pub audio_transcription_device: Option<String>,
+    /// Status of the transcription engines of the fallback chain that have been used
+    #[serde(default)]
+    pub audio_transcription_engines: Vec<EngineHealth>,
+    /// PII redacted since the start, by the placeholder replacing it, e.g. "[EMAIL]"
+    #[serde(default)]
+    pub pii_redactions: std::collections::BTreeMap<String, u64>,
+    /// Why the capture is paused, the user being idle or the screen locked, None while capturing
+    #[serde(default)]
+    pub capture_paused: Option<PauseReason>,
 }
 
 // Update the search function
@@ -282,7 +383,7 @@ pub(crate) async fn search(
     (StatusCode, JsonResponse<serde_json::Value>),
 > {
     info!(
-        "received search request: query='{}', content_type={:?}, limit={}, offset={}, start_time={:?}, end_time={:?}, app_name={:?}, window_name={:?}, min_length={:?}, max_length={:?}, speaker_ids={:?}, frame_name={:?}",
+        "received search request: query='{}', content_type={:?}, limit={}, offset={}, start_time={:?}, end_time={:?}, app_name={:?}, window_name={:?}, min_length={:?}, max_length={:?}, speaker_ids={:?}, frame_name={:?}, browser_url={:?}, focused_app_name={:?}, in_call={:?}, virtual_desktop={:?}, selected_text={:?}",
         query.q.as_deref().unwrap_or(""),
         query.content_type,
         query.pagination.limit,
@@ -295,6 +396,11 @@ pub(crate) async fn search(
         query.max_length,
         query.speaker_ids,
         query.frame_name,
+        query.browser_url,
+        query.focused_app_name,
+        query.in_call,
+        query.virtual_desktop,
+        query.selected_text,
     );
 
     let query_str = query.q.as_deref().unwrap_or("");
@@ -315,6 +421,14 @@ pub(crate) async fn search(
             query.max_length,
             query.speaker_ids.clone(),
             query.frame_name.as_deref(),
+            query.language.as_deref(),
+            query.browser_url.as_deref(),
+            query.focused_app_name.as_deref(),
+            query.in_call,
+            query.virtual_desktop,
+            query.selected_text.as_deref(),
+            query.transcription_engine.as_deref(),
+            query.model_version.as_deref(),
         ),
         state.db.count_search_results(
             query_str,
@@ -327,6 +441,14 @@ pub(crate) async fn search(
             query.max_length,
             query.speaker_ids.clone(),
             query.frame_name.as_deref(),
+            query.language.as_deref(),
+            query.browser_url.as_deref(),
+            query.focused_app_name.as_deref(),
+            query.in_call,
+            query.virtual_desktop,
+            query.selected_text.as_deref(),
+            query.transcription_engine.as_deref(),
+            query.model_version.as_deref(),
         ),
     )
     .await
@@ -338,47 +460,7 @@ pub(crate) async fn search(
         )
     })?;
 
-    let mut content_items: Vec<ContentItem> = results
-        .iter()
-        .map(|result| match result {
-            SearchResult::OCR(ocr) => ContentItem::OCR(OCRContent {
-                frame_id: ocr.frame_id,
-                text: ocr.ocr_text.clone(),
-                timestamp: ocr.timestamp,
-                file_path: ocr.file_path.clone(),
-                offset_index: ocr.offset_index,
-                app_name: ocr.app_name.clone(),
-                window_name: ocr.window_name.clone(),
-                tags: ocr.tags.clone(),
-                frame: None,
-                frame_name: Some(ocr.frame_name.clone()),
-            }),
-            SearchResult::Audio(audio) => ContentItem::Audio(AudioContent {
-                chunk_id: audio.audio_chunk_id,
-                transcription: audio.transcription.clone(),
-                timestamp: audio.timestamp,
-                file_path: audio.file_path.clone(),
-                offset_index: audio.offset_index,
-                tags: audio.tags.clone(),
-                device_name: audio.device_name.clone(),
-                device_type: audio.device_type.clone(),
-                speaker: audio.speaker.clone(),
-                start_time: audio.start_time,
-                end_time: audio.end_time,
-            }),
-            SearchResult::UI(ui) => ContentItem::UI(UiContent {
-                id: ui.id,
-                text: ui.text.clone(),
-                timestamp: ui.timestamp,
-                app_name: ui.app_name.clone(),
-                window_name: ui.window_name.clone(),
-                initial_traversal_at: ui.initial_traversal_at,
-                file_path: ui.file_path.clone(),
-                offset_index: ui.offset_index,
-                frame_name: ui.frame_name.clone(),
-            }),
-        })
-        .collect();
+    let mut content_items: Vec<ContentItem> = results.iter().map(content_item).collect();
 
     if query.include_frames {
         debug!("extracting frames for ocr content");
@@ -416,6 +498,108 @@ pub(crate) async fn search(
     }))
 }
 
+fn content_item(result: &SearchResult) -> ContentItem {
+    match result {
+        SearchResult::OCR(ocr) => ContentItem::OCR(OCRContent {
+            frame_id: ocr.frame_id,
+            text: ocr.ocr_text.clone(),
+            timestamp: ocr.timestamp,
+            file_path: ocr.file_path.clone(),
+            offset_index: ocr.offset_index,
+            app_name: ocr.app_name.clone(),
+            window_name: ocr.window_name.clone(),
+            tags: ocr.tags.clone(),
+            frame: None,
+            frame_name: Some(ocr.frame_name.clone()),
+            browser_url: ocr.browser_url.clone(),
+            focused_app_name: ocr.focused_app_name.clone(),
+            focused_window_name: ocr.focused_window_name.clone(),
+            in_call: ocr.in_call,
+            virtual_desktop: ocr.virtual_desktop,
+            selected_text: ocr.selected_text.clone(),
+        }),
+        SearchResult::Audio(audio) => ContentItem::Audio(AudioContent {
+            chunk_id: audio.audio_chunk_id,
+            transcription: audio.transcription.clone(),
+            timestamp: audio.timestamp,
+            file_path: audio.file_path.clone(),
+            offset_index: audio.offset_index,
+            tags: audio.tags.clone(),
+            device_name: audio.device_name.clone(),
+            device_type: audio.device_type.clone(),
+            speaker: audio.speaker.clone(),
+            start_time: audio.start_time,
+            end_time: audio.end_time,
+            confidence: audio.confidence,
+            language: audio.language.clone(),
+            hallucination: audio.hallucination,
+            transcription_engine: audio.transcription_engine.clone(),
+            model_version: audio.model_version.clone(),
+            audio_duration: audio.audio_duration,
+            captured_until: audio.captured_until,
+            latency: audio.latency,
+        }),
+        SearchResult::UI(ui) => ContentItem::UI(UiContent {
+            id: ui.id,
+            text: ui.text.clone(),
+            timestamp: ui.timestamp,
+            app_name: ui.app_name.clone(),
+            window_name: ui.window_name.clone(),
+            initial_traversal_at: ui.initial_traversal_at,
+            file_path: ui.file_path.clone(),
+            offset_index: ui.offset_index,
+            frame_name: ui.frame_name.clone(),
+        }),
+    }
+}
+
+/// Same parameters as `/search`, returns how the search was executed instead of its results
+pub(crate) async fn explain_search_handler(
+    Query(query): Query<SearchQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<QueryPlanReport>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let report = state
+        .db
+        .explain_search(
+            query.q.as_deref().unwrap_or(""),
+            query.content_type.clone(),
+            query.pagination.limit,
+            query.pagination.offset,
+            query.start_time,
+            query.end_time,
+            query.app_name.as_deref(),
+            query.window_name.as_deref(),
+            query.min_length,
+            query.max_length,
+            query.speaker_ids.clone(),
+            query.frame_name.as_deref(),
+            query.language.as_deref(),
+            query.browser_url.as_deref(),
+            query.focused_app_name.as_deref(),
+            query.in_call,
+            query.virtual_desktop,
+            query.selected_text.as_deref(),
+            query.transcription_engine.as_deref(),
+            query.model_version.as_deref(),
+        )
+        .await
+        .map_err(|e| {
+            error!("failed to explain search: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("failed to explain search: {}", e)})),
+            )
+        })?;
+
+    info!(
+        "explained search '{}': {:.1}ms over {} stages",
+        report.normalized_query,
+        report.total_ms,
+        report.stages.len()
+    );
+    Ok(JsonResponse(report))
+}
+
 pub(crate) async fn api_list_audio_devices(
     State(_state): State<Arc<AppState>>,
 ) -> Result<JsonResponse<Vec<ListDeviceResponse>>, (StatusCode, JsonResponse<serde_json::Value>)> {
@@ -461,6 +645,28 @@ pub(crate) async fn api_list_audio_devices(
     }
 }
 
+pub(crate) async fn api_list_audio_hosts() -> JsonResponse<Vec<AudioHost>> {
+    JsonResponse(list_audio_hosts())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SpeechStatsQuery {
+    device: Option<String>,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+}
+
+/// Seconds of speech and silence the VAD heard on each device, per hour, since startup
+pub(crate) async fn api_speech_stats(
+    Query(query): Query<SpeechStatsQuery>,
+) -> JsonResponse<Vec<SpeechStats>> {
+    JsonResponse(speech_stats(
+        query.device.as_deref(),
+        query.start_time,
+        query.end_time,
+    ))
+}
+
 pub async fn api_list_monitors(
 ) -> Result<JsonResponse<Vec<MonitorInfo>>, (StatusCode, JsonResponse<serde_json::Value>)> {
     let monitors = list_monitors().await;
@@ -575,92 +781,785 @@ pub async fn health_check(State(state): State<Arc<AppState>>) -> JsonResponse<He
         }
     };
 
-    let now = Utc::now();
-    let threshold = Duration::from_secs(3600); // 1 hour
+    let now = Utc::now();
+    let threshold = Duration::from_secs(3600); // 1 hour
+
+    // nothing is captured on purpose, it isn't stale
+    let capture_paused = capture_pause::pause_reason();
+
+    let frame_status = if state.vision_disabled {
+        "disabled"
+    } else if capture_paused.is_some() {
+        "paused"
+    } else {
+        match last_frame {
+            Some(timestamp)
+                if now.signed_duration_since(timestamp)
+                    < chrono::Duration::from_std(threshold).unwrap() =>
+            {
+                "ok"
+            }
+            Some(_) => "stale",
+            None => "no data",
+        }
+    };
+
+    let audio_status = if state.audio_disabled {
+        "disabled"
+    } else if capture_paused.is_some() {
+        "paused"
+    } else if audio_active {
+        "ok"
+    } else {
+        "stale"
+    };
+
+    let ui_status = if !state.ui_monitoring_enabled {
+        "disabled"
+    } else {
+        match last_ui {
+            Some(timestamp)
+                if now.signed_duration_since(timestamp)
+                    < chrono::Duration::from_std(threshold).unwrap() =>
+            {
+                "ok"
+            }
+            Some(_) => "stale",
+            None => "no data",
+        }
+    };
+
+    let is_working = |status: &str| matches!(status, "ok" | "disabled" | "paused");
+    let (overall_status, message, verbose_instructions, status_code) = if is_working(frame_status)
+        && is_working(audio_status)
+        && is_working(ui_status)
+    {
+        (
+            "healthy",
+            "all systems are functioning normally.".to_string(),
+            None,
+            200,
+        )
+    } else {
+        let mut unhealthy_systems = Vec::new();
+        if !is_working(frame_status) {
+            unhealthy_systems.push("vision");
+        }
+        if !is_working(audio_status) {
+            unhealthy_systems.push("audio");
+        }
+        if !is_working(ui_status) {
+            unhealthy_systems.push("ui monitoring");
+        }
+
+        (
+            "unhealthy",
+            format!("some systems are not functioning properly: {}. frame status: {}, audio status: {}, ui status: {}",
+                    unhealthy_systems.join(", "), frame_status, audio_status, ui_status),
+            Some("if you're experiencing issues, please try contacting us on discord".to_string()),
+            500,
+        )
+    };
+
+    JsonResponse(HealthCheckResponse {
+        status: overall_status.to_string(),
+        status_code,
+        last_frame_timestamp: last_frame,
+        last_audio_timestamp: audio,
+        last_ui_timestamp: last_ui,
+        frame_status: frame_status.to_string(),
+        audio_status: audio_status.to_string(),
+        ui_status: ui_status.to_string(),
+        message,
+        verbose_instructions,
+        audio_transcription_device: active_whisper_device(),
+        audio_transcription_engines: engine_health(),
+        pii_redactions: redaction_counts(),
+        capture_paused,
+    })
+}
+
+// served from memory only so assistants and widgets can poll it at high frequency
+pub(crate) async fn now_handler() -> JsonResponse<NowResponse> {
+    JsonResponse(current_context())
+}
+
+// called by the companion browser extension each time the active tab changes, for browsers whose
+// url can't be read from the OS (firefox, and all of them on linux)
+pub(crate) async fn report_browser_tab_handler(
+    Json(tab): Json<BrowserTab>,
+) -> JsonResponse<Value> {
+    debug!("browser extension reported tab {}", tab.url);
+    report_tab(tab);
+    JsonResponse(json!({"success": true}))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UiElementsQuery {
+    #[serde(default)]
+    q: Option<String>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+    #[serde(default)]
+    app_name: Option<String>,
+    #[serde(default)]
+    role: Option<String>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+// elements of the accessibility tree read with the frames, recorded with --enable-accessibility-tree
+pub(crate) async fn ui_elements_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<UiElementsQuery>,
+) -> Result<JsonResponse<Vec<UiElementResult>>, (StatusCode, JsonResponse<Value>)> {
+    state
+        .db
+        .search_ui_elements(
+            query.q.as_deref().unwrap_or(""),
+            query.app_name.as_deref(),
+            query.role.as_deref(),
+            query.start_time,
+            query.end_time,
+            query.pagination.limit,
+            query.pagination.offset,
+        )
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("failed to search ui elements: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })
+}
+
+#[derive(Deserialize)]
+pub(crate) struct OcrLinesQuery {
+    #[serde(default)]
+    q: Option<String>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+    #[serde(default)]
+    region: Option<String>,
+    #[serde(default)]
+    app_name: Option<String>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+/// A part of windows: "top" or "bottom", the sixth of the window along that side where title, tool
+/// and status bars are, "left" or "right", the quarter where sidebars are, or "x,y,width,height"
+/// in fractions of the window's size from its top left corner
+fn parse_region(region: &str) -> Option<LayoutBox> {
+    let (left, top, width, height) = match region {
+        "top" => (0.0, 0.0, 1.0, 1.0 / 6.0),
+        "bottom" => (0.0, 5.0 / 6.0, 1.0, 1.0 / 6.0),
+        "left" => (0.0, 0.0, 0.25, 1.0),
+        "right" => (0.75, 0.0, 0.25, 1.0),
+        _ => {
+            let values = region
+                .split(',')
+                .map(|value| value.trim().parse::<f64>().ok())
+                .collect::<Option<Vec<_>>>()?;
+            match values.as_slice() {
+                [left, top, width, height] => (*left, *top, *width, *height),
+                _ => return None,
+            }
+        }
+    };
+    Some(LayoutBox {
+        left,
+        top,
+        width,
+        height,
+    })
+}
+
+// lines of ocr text with their position in the window, e.g. ?region=top for the text of title and
+// tool bars
+pub(crate) async fn ocr_lines_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<OcrLinesQuery>,
+) -> Result<JsonResponse<Vec<OcrLineResult>>, (StatusCode, JsonResponse<Value>)> {
+    let region = match query.region.as_deref() {
+        Some(region) => Some(parse_region(region).ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                JsonResponse(json!({
+                    "error": "region must be top, bottom, left, right or x,y,width,height"
+                })),
+            )
+        })?),
+        None => None,
+    };
+    state
+        .db
+        .search_ocr_lines(
+            query.q.as_deref().unwrap_or(""),
+            region,
+            query.app_name.as_deref(),
+            query.start_time,
+            query.end_time,
+            query.pagination.limit,
+            query.pagination.offset,
+        )
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("failed to search ocr lines: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })
+}
+
+#[derive(Serialize)]
+pub(crate) struct FrameStructure {
+    #[serde(flatten)]
+    structure: OcrStructure,
+    /// What to copy out of it
+    text: String,
+}
+
+// tables and blocks of code read in a frame, with their cells and indentation
+pub(crate) async fn frame_structures_handler(
+    State(state): State<Arc<AppState>>,
+    Path(frame_id): Path<i64>,
+) -> Result<JsonResponse<Vec<FrameStructure>>, (StatusCode, JsonResponse<Value>)> {
+    let structures = state
+        .db
+        .get_frame_structures(frame_id)
+        .await
+        .map_err(|e| {
+            error!("failed to get the tables and code of frame {}: {}", frame_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })?;
+    Ok(JsonResponse(
+        structures
+            .into_iter()
+            .map(|structure| FrameStructure {
+                text: structure.text(),
+                structure,
+            })
+            .collect(),
+    ))
+}
+
+// pointer and selection stored with a frame, with --capture-cursor, for replays to show them over it
+pub(crate) async fn frame_cursor_handler(
+    State(state): State<Arc<AppState>>,
+    Path(frame_id): Path<i64>,
+) -> Result<JsonResponse<CursorOverlay>, (StatusCode, JsonResponse<Value>)> {
+    match state.db.get_frame_cursor(frame_id).await {
+        Ok(Some(cursor)) => Ok(JsonResponse(cursor)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("no frame {}", frame_id)})),
+        )),
+        Err(e) => {
+            error!("failed to get the cursor of frame {}: {}", frame_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            ))
+        }
+    }
+}
+
+// blocks, lines and words of the text of a frame with their boxes, to highlight them over it
+pub(crate) async fn frame_layout_handler(
+    State(state): State<Arc<AppState>>,
+    Path(frame_id): Path<i64>,
+) -> Result<JsonResponse<OcrLayout>, (StatusCode, JsonResponse<Value>)> {
+    match state.db.get_frame_layout(frame_id).await {
+        Ok(Some(layout)) => Ok(JsonResponse(layout)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("no layout stored for frame {}", frame_id)})),
+        )),
+        Err(e) => {
+            error!("failed to get the layout of frame {}: {}", frame_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct InputEventsQuery {
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    app_name: Option<String>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+// clicks, keypresses and scrolls, recorded with --enable-input-events
+pub(crate) async fn input_events_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<InputEventsQuery>,
+) -> Result<JsonResponse<Vec<InputEventResult>>, (StatusCode, JsonResponse<Value>)> {
+    state
+        .db
+        .search_input_events(
+            query.kind.as_deref(),
+            query.app_name.as_deref(),
+            query.start_time,
+            query.end_time,
+            query.pagination.limit,
+            query.pagination.offset,
+        )
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("failed to search input events: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CapturePausesQuery {
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CapturePausesResponse {
+    /// Why the capture is paused now, None while capturing
+    paused: Option<PauseReason>,
+    pauses: Vec<CapturePause>,
+}
+
+// stretches of time the user was idle or the screen locked, with --pause-on-idle or --pause-on-lock
+pub(crate) async fn capture_pauses_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CapturePausesQuery>,
+) -> Result<JsonResponse<CapturePausesResponse>, (StatusCode, JsonResponse<Value>)> {
+    state
+        .db
+        .list_capture_pauses(
+            query.start_time,
+            query.end_time,
+            query.pagination.limit,
+            query.pagination.offset,
+        )
+        .await
+        .map(|pauses| {
+            JsonResponse(CapturePausesResponse {
+                paused: capture_pause::pause_reason(),
+                pauses,
+            })
+        })
+        .map_err(|e| {
+            error!("failed to list capture pauses: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })
+}
+
+#[derive(Deserialize)]
+pub(crate) struct MeetingsQuery {
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+// meetings detected with --detect-meetings
+pub(crate) async fn meetings_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<MeetingsQuery>,
+) -> Result<JsonResponse<Vec<MeetingSession>>, (StatusCode, JsonResponse<Value>)> {
+    state
+        .db
+        .list_meeting_sessions(
+            query.start_time,
+            query.end_time,
+            query.pagination.limit,
+            query.pagination.offset,
+        )
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("failed to list meeting sessions: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })
+}
+
+#[derive(Deserialize)]
+pub(crate) struct MeetingQuery {
+    /// Of the transcripts and of the frames each
+    #[serde(default = "default_meeting_limit")]
+    limit: u32,
+}
+
+fn default_meeting_limit() -> u32 {
+    1000
+}
+
+#[derive(Serialize)]
+pub(crate) struct MeetingResponse {
+    #[serde(flatten)]
+    session: MeetingSession,
+    /// What was said during the meeting, oldest first
+    transcripts: Vec<ContentItem>,
+    /// What was on screen during the meeting, oldest first
+    frames: Vec<ContentItem>,
+}
+
+// a meeting and what was captured during it, as one object
+pub(crate) async fn meeting_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(query): Query<MeetingQuery>,
+) -> Result<JsonResponse<MeetingResponse>, (StatusCode, JsonResponse<Value>)> {
+    let internal_error = |e: sqlx::Error| {
+        error!("failed to get meeting {}: {}", id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": e.to_string()})),
+        )
+    };
+    let session = state
+        .db
+        .get_meeting_session(id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": format!("no meeting {}", id)})),
+            )
+        })?;
+
+    let end_time = session.end_time.unwrap_or_else(Utc::now);
+    let captured = |content_type| {
+        state.db.search(
+            "",
+            content_type,
+            query.limit,
+            0,
+            Some(session.start_time),
+            Some(end_time),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    };
+    let (transcripts, frames) = try_join(captured(ContentType::Audio), captured(ContentType::OCR))
+        .await
+        .map_err(internal_error)?;
+    let oldest_first = |mut results: Vec<SearchResult>| -> Vec<ContentItem> {
+        results.sort_by_key(|result| match result {
+            SearchResult::OCR(ocr) => ocr.timestamp,
+            SearchResult::Audio(audio) => audio.timestamp,
+            SearchResult::UI(ui) => ui.timestamp,
+        });
+        results.iter().map(content_item).collect()
+    };
+
+    Ok(JsonResponse(MeetingResponse {
+        transcripts: oldest_first(transcripts),
+        frames: oldest_first(frames),
+        session,
+    }))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TranscriptionFramesQuery {
+    /// Seconds of frames before and after the segment
+    #[serde(default = "default_frames_padding")]
+    padding: f64,
+    #[serde(default = "default_transcription_frames_limit")]
+    limit: u32,
+}
+
+fn default_frames_padding() -> f64 {
+    1.0
+}
+
+fn default_transcription_frames_limit() -> u32 {
+    100
+}
+
+/// Frames before the segment are looked for this far back, for the one still on screen
+const FRAMES_LOOKBACK_SECS: i64 = 60;
+
+#[derive(Serialize)]
+pub(crate) struct TranscriptionFramesResponse {
+    #[serde(flatten)]
+    segment: TranscriptSegment,
+    /// When the audio of the segment stopped being captured
+    end: DateTime<Utc>,
+    /// On screen while the segment was said, oldest first
+    frames: Vec<AlignedFrame>,
+}
+
+// the frames on screen while a transcript segment was said, audio and frames share a clock
+pub(crate) async fn transcription_frames_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(query): Query<TranscriptionFramesQuery>,
+) -> Result<JsonResponse<TranscriptionFramesResponse>, (StatusCode, JsonResponse<Value>)> {
+    let internal_error = |e: sqlx::Error| {
+        error!("failed to get the frames of transcription {}: {}", id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": e.to_string()})),
+        )
+    };
+    let segment = state
+        .db
+        .get_transcript_segment(id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": format!("no transcription {}", id)})),
+            )
+        })?;
+
+    let end = segment.end();
+    let padding = chrono::Duration::milliseconds((query.padding.max(0.0) * 1000.0) as i64);
+    let start_time = segment.timestamp - padding;
+    let frames = state
+        .db
+        .get_aligned_frames(
+            start_time,
+            end + padding,
+            start_time - chrono::Duration::seconds(FRAMES_LOOKBACK_SECS),
+            query.limit,
+        )
+        .await
+        .map_err(internal_error)?;
+
+    Ok(JsonResponse(TranscriptionFramesResponse {
+        segment,
+        end,
+        frames,
+    }))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct InputActivityQuery {
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+}
+
+// input per minute, for the timeline to tell active work from reading
+pub(crate) async fn input_activity_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<InputActivityQuery>,
+) -> Result<JsonResponse<Vec<InputActivityBucket>>, (StatusCode, JsonResponse<Value>)> {
+    state
+        .db
+        .input_activity(query.start_time, query.end_time)
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("failed to get input activity: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })
+}
+
+#[derive(Deserialize)]
+pub struct AddAudioSampleRequest {
+    audio_chunk_id: i64,
+    corrected_transcription: String,
+    #[serde(default)]
+    approved: bool,
+}
+
+#[derive(Deserialize)]
+pub struct AddOcrSampleRequest {
+    frame_id: i64,
+    corrected_text: String,
+    #[serde(default)]
+    approved: bool,
+}
+
+#[derive(Serialize)]
+pub struct EvalSamplesResponse {
+    root: String,
+    audio: Vec<AudioSample>,
+    ocr: Vec<OcrSample>,
+}
+
+fn eval_dataset_or_error(
+    state: &AppState,
+    approved: bool,
+) -> Result<Arc<EvalDataset>, (StatusCode, JsonResponse<Value>)> {
+    let dataset = state.eval_dataset.clone().ok_or_else(|| {
+        (
+            StatusCode::FORBIDDEN,
+            JsonResponse(json!({"error": "eval dataset recording is disabled, start with --enable-eval-dataset"})),
+        )
+    })?;
+    // nothing is ever stored without explicit user approval
+    if !approved {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "sample must be explicitly approved"})),
+        ));
+    }
+    Ok(dataset)
+}
+
+pub(crate) async fn add_audio_sample_handler(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(payload): JsonResponse<AddAudioSampleRequest>,
+) -> Result<JsonResponse<AudioSample>, (StatusCode, JsonResponse<Value>)> {
+    let dataset = eval_dataset_or_error(&state, payload.approved)?;
+
+    let (file_path, hypothesis) = match state
+        .db
+        .get_audio_chunk_with_transcription(payload.audio_chunk_id)
+        .await
+    {
+        Ok(Some(chunk)) => chunk,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": "audio chunk not found"})),
+            ))
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("database error: {}", e)})),
+            ))
+        }
+    };
+
+    dataset
+        .add_audio_sample(
+            &file_path,
+            payload.audio_chunk_id,
+            &hypothesis,
+            &payload.corrected_transcription,
+        )
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("failed to add audio sample: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })
+}
 
-    let frame_status = if state.vision_disabled {
-        "disabled"
-    } else {
-        match last_frame {
-            Some(timestamp)
-                if now.signed_duration_since(timestamp)
-                    < chrono::Duration::from_std(threshold).unwrap() =>
-            {
-                "ok"
-            }
-            Some(_) => "stale",
-            None => "no data",
-        }
-    };
+pub(crate) async fn add_ocr_sample_handler(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(payload): JsonResponse<AddOcrSampleRequest>,
+) -> Result<JsonResponse<OcrSample>, (StatusCode, JsonResponse<Value>)> {
+    let dataset = eval_dataset_or_error(&state, payload.approved)?;
 
-    let audio_status = if state.audio_disabled {
-        "disabled"
-    } else if audio_active {
-        "ok"
-    } else {
-        "stale"
+    let internal_error = |e: String| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": e})),
+        )
     };
 
-    let ui_status = if !state.ui_monitoring_enabled {
-        "disabled"
-    } else {
-        match last_ui {
-            Some(timestamp)
-                if now.signed_duration_since(timestamp)
-                    < chrono::Duration::from_std(threshold).unwrap() =>
-            {
-                "ok"
-            }
-            Some(_) => "stale",
-            None => "no data",
+    let (file_path, offset_index) = match state.db.get_frame(payload.frame_id).await {
+        Ok(Some(frame)) => frame,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": "frame not found"})),
+            ))
         }
+        Err(e) => return Err(internal_error(format!("database error: {}", e))),
     };
-
-    let (overall_status, message, verbose_instructions, status_code) = if (frame_status == "ok"
-        || frame_status == "disabled")
-        && (audio_status == "ok" || audio_status == "disabled")
-        && (ui_status == "ok" || ui_status == "disabled")
-    {
-        (
-            "healthy",
-            "all systems are functioning normally.".to_string(),
-            None,
-            200,
+    let hypothesis = state
+        .db
+        .get_frame_ocr_text(payload.frame_id)
+        .await
+        .map_err(|e| internal_error(format!("database error: {}", e)))?;
+    let frame_path = extract_frame_from_video(&file_path, offset_index)
+        .await
+        .map_err(|e| internal_error(format!("failed to extract frame: {}", e)))?;
+
+    dataset
+        .add_ocr_sample(
+            &frame_path,
+            payload.frame_id,
+            &hypothesis,
+            &payload.corrected_text,
         )
-    } else {
-        let mut unhealthy_systems = Vec::new();
-        if frame_status != "ok" && frame_status != "disabled" {
-            unhealthy_systems.push("vision");
-        }
-        if audio_status != "ok" && audio_status != "disabled" {
-            unhealthy_systems.push("audio");
-        }
-        if ui_status != "ok" && ui_status != "disabled" {
-            unhealthy_systems.push("ui monitoring");
-        }
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("failed to add ocr sample: {}", e);
+            internal_error(e.to_string())
+        })
+}
 
+pub(crate) async fn list_eval_samples_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<EvalSamplesResponse>, (StatusCode, JsonResponse<Value>)> {
+    let dataset = eval_dataset_or_error(&state, true)?;
+    let internal_error = |e: anyhow::Error| {
         (
-            "unhealthy",
-            format!("some systems are not functioning properly: {}. frame status: {}, audio status: {}, ui status: {}",
-                    unhealthy_systems.join(", "), frame_status, audio_status, ui_status),
-            Some("if you're experiencing issues, please try contacting us on discord".to_string()),
-            500,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": e.to_string()})),
         )
     };
 
-    JsonResponse(HealthCheckResponse {
-        status: overall_status.to_string(),
-        status_code,
-        last_frame_timestamp: last_frame,
-        last_audio_timestamp: audio,
-        last_ui_timestamp: last_ui,
-        frame_status: frame_status.to_string(),
-        audio_status: audio_status.to_string(),
-        ui_status: ui_status.to_string(),
-        message,
-        verbose_instructions,
-    })
+    Ok(JsonResponse(EvalSamplesResponse {
+        root: dataset.root().to_string_lossy().into_owned(),
+        audio: dataset.audio_samples().await.map_err(internal_error)?,
+        ocr: dataset.ocr_samples().await.map_err(internal_error)?,
+    }))
 }
+
 // Request and response structs
 #[derive(Deserialize)]
 struct DownloadPipeRequest {
@@ -903,6 +1802,8 @@ pub struct Server {
     vision_disabled: bool,
     audio_disabled: bool,
     ui_monitoring_enabled: bool,
+    eval_dataset_enabled: bool,
+    screenshot_options: Option<ScreenshotOptions>,
 }
 
 impl Server {
@@ -916,6 +1817,8 @@ impl Server {
         vision_disabled: bool,
         audio_disabled: bool,
         ui_monitoring_enabled: bool,
+        eval_dataset_enabled: bool,
+        screenshot_options: Option<ScreenshotOptions>,
     ) -> Self {
         Server {
             db,
@@ -926,6 +1829,8 @@ impl Server {
             vision_disabled,
             audio_disabled,
             ui_monitoring_enabled,
+            eval_dataset_enabled,
+            screenshot_options,
         }
     }
 
@@ -962,6 +1867,12 @@ impl Server {
             } else {
                 None
             },
+            eval_dataset: if self.eval_dataset_enabled {
+                Some(Arc::new(EvalDataset::new(&self.screenpipe_dir)))
+            } else {
+                None
+            },
+            screenshot_options: self.screenshot_options.map(Arc::new),
         });
 
         let app = create_router()
@@ -1132,6 +2043,137 @@ async fn add_frame_to_db(
     Ok(())
 }
 
+#[derive(Deserialize)]
+pub(crate) struct ScreenshotQuery {
+    /// The default monitor when None
+    monitor_id: Option<u32>,
+    /// App or title of the window captured alone
+    window: Option<String>,
+    #[serde(default = "default_true")]
+    include_image: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Serialize)]
+pub(crate) struct ScreenshotWindow {
+    app_name: String,
+    window_name: String,
+    focused: bool,
+    text: String,
+    confidence: f64,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ScreenshotResponse {
+    timestamp: DateTime<Utc>,
+    monitor_id: u32,
+    /// Png, base64 encoded
+    image: Option<String>,
+    /// Of all the windows, the focused one first
+    text: String,
+    windows: Vec<ScreenshotWindow>,
+}
+
+// captures and OCRs the screen now, nothing is stored
+pub(crate) async fn screenshot_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ScreenshotQuery>,
+) -> Result<JsonResponse<ScreenshotResponse>, (StatusCode, JsonResponse<Value>)> {
+    let error_response =
+        |status: StatusCode, message: String| (status, JsonResponse(json!({"error": message})));
+    let options = state
+        .screenshot_options
+        .as_ref()
+        .ok_or_else(|| {
+            error_response(StatusCode::BAD_REQUEST, "vision is disabled".to_string())
+        })?;
+    let monitor = match query.monitor_id {
+        Some(monitor_id) => get_monitor_by_id(monitor_id).await.ok_or_else(|| {
+            error_response(
+                StatusCode::NOT_FOUND,
+                format!("monitor {} not found", monitor_id),
+            )
+        })?,
+        None => get_default_monitor().await,
+    };
+    // the window of --capture-window otherwise, other windows aren't captured then
+    let window_filters = match query.window.as_deref() {
+        Some(window) => options
+            .window_filters
+            .clone()
+            .with_single_window(Some(window)),
+        None => options.window_filters.clone(),
+    };
+
+    let frame = capture_frame(
+        &monitor,
+        &window_filters,
+        &options.ocr_engine,
+        options.languages.clone(),
+        options.capture_unfocused_windows,
+    )
+    .await
+    .map_err(|e| {
+        error!("failed to capture a screenshot: {}", e);
+        error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?
+    .ok_or_else(|| {
+        error_response(
+            StatusCode::NOT_FOUND,
+            "nothing to capture: the window isn't on the monitor or the focused app isn't recorded"
+                .to_string(),
+        )
+    })?;
+
+    let image = if query.include_image {
+        let mut buffer = Vec::new();
+        frame
+            .image
+            .write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)
+            .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        Some(general_purpose::STANDARD.encode(buffer))
+    } else {
+        None
+    };
+    let use_pii_removal = options.use_pii_removal;
+    let window_ocr_results = frame.window_ocr_results;
+    let mut windows: Vec<ScreenshotWindow> = redaction::off_runtime(move || {
+        window_ocr_results
+            .into_iter()
+            .map(|window| ScreenshotWindow {
+                text: if use_pii_removal {
+                    redact(&window.text)
+                } else {
+                    window.text
+                },
+                app_name: window.app_name,
+                window_name: window.window_name,
+                focused: window.focused,
+                confidence: window.confidence,
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    windows.sort_by_key(|window| !window.focused);
+
+    Ok(JsonResponse(ScreenshotResponse {
+        timestamp: Utc::now(),
+        monitor_id: monitor.id(),
+        image,
+        text: windows
+            .iter()
+            .map(|window| window.text.as_str())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        windows,
+    }))
+}
+
 fn encode_frame_from_file_path(file_path: &str) -> Result<Vec<u8>, anyhow::Error> {
     let image = image::open(file_path)?;
     let mut buffer = Vec::new();
@@ -1169,10 +2211,7 @@ async fn add_transcription_to_db(
 ) -> Result<(), anyhow::Error> {
     let db = &state.db;
 
-    let device = AudioDevice {
-        name: device_name.to_string(),
-        device_type: DeviceType::Input,
-    };
+    let device = AudioDevice::new(device_name.to_string(), DeviceType::Input);
 
     let dummy_audio_chunk_id = db.insert_audio_chunk("").await?;
 
@@ -1656,6 +2695,53 @@ async fn merge_speakers_handler(
     Ok(JsonResponse(json!({"success": true})))
 }
 
+async fn enroll_speaker_handler(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<EnrollSpeakerRequest>,
+) -> Result<JsonResponse<Speaker>, (StatusCode, JsonResponse<Value>)> {
+    let name = payload.name.trim().to_string();
+    if name.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "speaker name can't be empty"})),
+        ));
+    }
+
+    let embedding = compute_enrollment_embedding(PathBuf::from(&payload.audio_path))
+        .await
+        .map_err(|e| {
+            error!("failed to enroll speaker {}: {}", name, e);
+            (
+                StatusCode::BAD_REQUEST,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })?;
+
+    let speaker = state
+        .db
+        .enroll_speaker(&name, &embedding)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })?;
+    info!("enrolled speaker {} (id {})", speaker.name, speaker.id);
+
+    Ok(JsonResponse(speaker))
+}
+
+async fn compute_enrollment_embedding(audio_path: PathBuf) -> anyhow::Result<Vec<f32>> {
+    let model_path = get_or_download_model(PyannoteModel::Embedding).await?;
+    tokio::task::spawn_blocking(move || {
+        let (samples, sample_rate) = pcm_decode(&audio_path)?;
+        let mut extractor = EmbeddingExtractor::new(model_path)?;
+        enrollment_embedding(&samples, sample_rate, &mut extractor)
+    })
+    .await?
+}
+
 async fn get_similar_speakers_handler(
     State(state): State<Arc<AppState>>,
     Query(request): Query<GetSimilarSpeakersRequest>,
@@ -1835,6 +2921,179 @@ async fn semantic_search_handler(
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct VisualSearchQuery {
+    text: String,
+    limit: Option<u32>,
+    threshold: Option<f32>,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+}
+
+// frames whose image looks like the text, from their clip embeddings
+async fn visual_search_handler(
+    Query(query): Query<VisualSearchQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<Vec<VisualSearchResult>>, (StatusCode, JsonResponse<Value>)> {
+    let embedding = clip::text_embedding(&query.text).await.map_err(|e| {
+        error!("failed to embed '{}': {}", query.text, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("failed to embed the text: {}", e)})),
+        )
+    })?;
+    state
+        .db
+        .search_frames_by_embedding(
+            &embedding,
+            query.threshold,
+            query.start_time,
+            query.end_time,
+            query.limit.unwrap_or(10),
+        )
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("failed to search frames by embedding: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": e.to_string()})),
+            )
+        })
+}
+
+/// Candidates taken from full text search and from embedding search for each result returned
+const HYBRID_SEARCH_CANDIDATES: u32 = 3;
+
+#[derive(Debug, Deserialize)]
+struct HybridSearchQuery {
+    q: String,
+    #[serde(default)]
+    content_type: ContentType,
+    limit: Option<u32>,
+    /// Cosine distance under which text is found by its embedding
+    threshold: Option<f32>,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct HybridSearchResult {
+    #[serde(flatten)]
+    content: ContentItem,
+    /// Reciprocal rank fusion score, higher is better
+    score: f64,
+    /// Cosine distance to the query, None when only the words matched
+    distance: Option<f64>,
+}
+
+// text and transcriptions matching the query by its words and by its meaning, from the
+// embeddings of --enable-text-embeddings, in one ranking
+async fn hybrid_search_handler(
+    Query(query): Query<HybridSearchQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<Vec<HybridSearchResult>>, (StatusCode, JsonResponse<Value>)> {
+    let limit = query.limit.unwrap_or(20);
+    let candidates = limit.saturating_mul(HYBRID_SEARCH_CANDIDATES);
+    let internal_error = |e: String| {
+        error!("semantic search for '{}' failed: {}", query.q, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": e})),
+        )
+    };
+
+    let embedding = embed(&query.q)
+        .await
+        .map_err(|e| internal_error(format!("failed to embed the query: {}", e)))?;
+
+    let (search_ocr, search_audio) = match query.content_type {
+        ContentType::All | ContentType::AudioAndOcr => (true, true),
+        ContentType::OCR | ContentType::OcrAndUi => (true, false),
+        ContentType::Audio | ContentType::AudioAndUi => (false, true),
+        ContentType::UI => (false, false),
+    };
+    let (fts, ocr, audio) = tokio::join!(
+        state.db.search(
+            &query.q,
+            query.content_type.clone(),
+            candidates,
+            0,
+            query.start_time,
+            query.end_time,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        ),
+        async {
+            if !search_ocr {
+                return Ok(Vec::new());
+            }
+            state
+                .db
+                .search_ocr_text_embeddings(
+                    &embedding,
+                    query.threshold,
+                    query.start_time,
+                    query.end_time,
+                    candidates,
+                )
+                .await
+        },
+        async {
+            if !search_audio {
+                return Ok(Vec::new());
+            }
+            state
+                .db
+                .search_transcription_embeddings(
+                    &embedding,
+                    query.threshold,
+                    query.start_time,
+                    query.end_time,
+                    candidates,
+                )
+                .await
+        },
+    );
+    let fts = fts.map_err(|e| internal_error(format!("failed to search: {}", e)))?;
+    let ocr = ocr.map_err(|e| internal_error(format!("failed to search embeddings: {}", e)))?;
+    let audio = audio.map_err(|e| internal_error(format!("failed to search embeddings: {}", e)))?;
+
+    // the embeddings of text and transcriptions are of the same model, their distances compare
+    let mut semantic: Vec<(SearchResult, f64)> = ocr
+        .into_iter()
+        .map(|(ocr, distance)| (SearchResult::OCR(ocr), distance))
+        .chain(
+            audio
+                .into_iter()
+                .map(|(audio, distance)| (SearchResult::Audio(audio), distance)),
+        )
+        .collect();
+    semantic.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let results = fuse(fts, semantic, limit as usize)
+        .into_iter()
+        .map(|fused| HybridSearchResult {
+            content: content_item(&fused.result),
+            score: fused.score,
+            distance: fused.distance,
+        })
+        .collect();
+    Ok(JsonResponse(results))
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct VisionDeviceControlRequest {
     device_id: u32,
@@ -2017,8 +3276,12 @@ pub fn create_router() -> Router<Arc<AppState>> {
 
     let router = Router::new()
         .route("/search", get(search))
+        .route("/search/explain", get(explain_search_handler))
         .route("/audio/list", get(api_list_audio_devices))
+        .route("/audio/hosts", get(api_list_audio_hosts))
+        .route("/audio/speech-stats", get(api_speech_stats))
         .route("/vision/list", get(api_list_monitors))
+        .route("/screenshot", get(screenshot_handler))
         .route(
             "/tags/:content_type/:id",
             post(add_tags).delete(remove_tags),
@@ -2036,6 +3299,19 @@ pub fn create_router() -> Router<Arc<AppState>> {
         .route("/pipes/update-version", post(update_pipe_version_handler))
         .route("/pipes/delete", post(delete_pipe_handler))
         .route("/health", get(health_check))
+        .route("/now", get(now_handler))
+        .route("/browser/tab", post(report_browser_tab_handler))
+        .route("/ui/elements", get(ui_elements_handler))
+        .route("/ocr/lines", get(ocr_lines_handler))
+        .route("/input/events", get(input_events_handler))
+        .route("/pauses", get(capture_pauses_handler))
+        .route("/meetings", get(meetings_handler))
+        .route("/meetings/:id", get(meeting_handler))
+        .route(
+            "/transcriptions/:id/frames",
+            get(transcription_frames_handler),
+        )
+        .route("/input/activity", get(input_activity_handler))
         .route("/ws/health", get(ws_health_handler))
         .route("/raw_sql", post(execute_raw_sql))
         .route("/add", post(add_to_database))
@@ -2050,13 +3326,22 @@ pub fn create_router() -> Router<Arc<AppState>> {
         )
         .route("/speakers/merge", post(merge_speakers_handler))
         .route("/speakers/similar", get(get_similar_speakers_handler))
+        .route("/speakers/enroll", post(enroll_speaker_handler))
         .route("/experimental/frames/merge", post(merge_frames_handler))
         .route("/experimental/validate/media", get(validate_media_handler))
         // .route("/audio/start", post(start_audio_device))
         // .route("/audio/stop", post(stop_audio_device))
         .route("/ws/events", get(ws_events_handler))
         .route("/semantic-search", get(semantic_search_handler))
+        .route("/search/visual", get(visual_search_handler))
+        .route("/search/semantic", get(hybrid_search_handler))
         .route("/frames/:frame_id", get(get_frame_data))
+        .route("/frames/:frame_id/layout", get(frame_layout_handler))
+        .route("/frames/:frame_id/cursor", get(frame_cursor_handler))
+        .route("/frames/:frame_id/structures", get(frame_structures_handler))
+        .route("/eval/samples", get(list_eval_samples_handler))
+        .route("/eval/samples/audio", post(add_audio_sample_handler))
+        .route("/eval/samples/ocr", post(add_ocr_sample_handler))
         // .route("/vision/start", post(start_vision_device))
         // .route("/vision/stop", post(stop_vision_device))
         // .route("/audio/restart", post(restart_audio_devices))
@@ -2405,6 +3690,13 @@ pub struct DeletePipeRequest {
     pipe_id: String,
 }
 
+#[derive(Deserialize, Debug)]
+struct EnrollSpeakerRequest {
+    name: String,
+    /// Recording of the speaker alone, a few seconds of speech in any format screenpipe decodes
+    audio_path: String,
+}
+
 #[derive(Deserialize, Debug)]
 struct MergeSpeakersRequest {
     speaker_to_keep_id: i64,
@@ -0,0 +1,99 @@
+use anyhow::{anyhow, Result};
+use dashmap::DashMap;
+use screenpipe_audio::subtitles::{to_subtitles, SubtitleFormat};
+use screenpipe_audio::{
+    create_whisper_channel, pcm_stream, resample, AudioDevice, AudioInput, CaptureTiming,
+    DeviceType, FILE_AUDIO_HOST,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::info;
+
+use crate::Cli;
+
+/// Audio handed to the whisper channel at once
+const PIECE: Duration = Duration::from_secs(30);
+/// Rate pieces are sent at, the channel transcribes at it anyway and pieces waiting for their
+/// turn take a third of the memory of 48khz ones
+const SAMPLE_RATE: u32 = 16000;
+
+/// Transcribes an audio or video file with the configured engine and writes its subtitles next to
+/// it, or to `output`
+pub async fn handle_subtitles_command(
+    cli: &Cli,
+    data_dir: &Path,
+    path: &Path,
+    format: SubtitleFormat,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    // decoded a piece at a time while the channel transcribes, hours of recording are never
+    // decoded at once
+    let stream = pcm_stream(path, PIECE)?;
+
+    let mut transcription_options = cli.transcription_options(data_dir)?;
+    // a one-off transcription, nothing to resume or catch up on later
+    transcription_options.journal_dir = None;
+    transcription_options.default.offline_queue = None;
+    // the channel writes the audio of each chunk, not worth keeping here
+    let chunks_dir = tempfile::tempdir()?;
+    let (sender, receiver, _) = create_whisper_channel(
+        Arc::new(
+            cli.audio_transcription_engine
+                .resolve(&cli.onnx_execution_provider),
+        ),
+        cli.vad_engine.clone().into(),
+        cli.deepgram_api_key.clone(),
+        chunks_dir.path(),
+        cli.vad_sensitivity.clone().into(),
+        cli.unique_languages().map_err(|e| anyhow!(e))?,
+        Some(Arc::new(DashMap::new())),
+        cli.model_options(),
+        transcription_options,
+    )
+    .await?;
+
+    let device = Arc::new(
+        AudioDevice::new(path.display().to_string(), DeviceType::Input)
+            .with_host(Some(FILE_AUDIO_HOST.to_string())),
+    );
+    let started_at = SystemTime::now();
+    info!("transcribing {}", path.display());
+    let sending = tokio::task::spawn_blocking(move || -> Result<usize> {
+        let sample_rate = stream.sample_rate();
+        let mut pieces = 0;
+        for (i, piece) in stream.enumerate() {
+            let piece = if sample_rate == SAMPLE_RATE {
+                piece?
+            } else {
+                resample(&piece?, sample_rate, SAMPLE_RATE)?
+            };
+            let mut capture_timing = CaptureTiming::new(started_at, SAMPLE_RATE);
+            capture_timing.push_block(0, PIECE * i as u32);
+            sender.send(AudioInput {
+                data: Arc::new(piece),
+                sample_rate: SAMPLE_RATE,
+                channels: 1,
+                device: Arc::clone(&device),
+                capture_timing: Some(capture_timing),
+            })?;
+            pieces += 1;
+        }
+        // the channel stops once it transcribed everything sent before the sender is dropped
+        Ok(pieces)
+    });
+    let results = tokio::task::spawn_blocking(move || receiver.iter().collect::<Vec<_>>());
+    let pieces = sending
+        .await
+        .map_err(|e| anyhow!("audio decoding panicked: {}", e))??;
+    if pieces == 0 {
+        return Err(anyhow!("no audio in {}", path.display()));
+    }
+    let results = results.await?;
+
+    let subtitles = to_subtitles(&results, Some(started_at), format);
+    let output = output.unwrap_or_else(|| path.with_extension(format.extension()));
+    std::fs::write(&output, subtitles)?;
+    println!("subtitles written to {}", output.display());
+    Ok(())
+}
@@ -1,7 +1,67 @@
+use crate::db::DatabaseManager;
 use anyhow::Result;
+use once_cell::sync::OnceCell;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+const OLLAMA_URL: &str = "http://localhost:11434";
+const OLLAMA_MODEL: &str = "nomic-embed-text";
+const API_MODEL: &str = "text-embedding-3-small";
+/// Texts of each kind embedded in a round of the embedder
+const EMBEDDING_BATCH: u32 = 32;
+/// Wait before looking for new text once everything is embedded, or after a failure
+const EMBEDDING_INTERVAL: Duration = Duration::from_secs(30);
+
+static BACKEND: OnceCell<EmbeddingBackend> = OnceCell::new();
+
+/// What computes the embeddings of text. Embeddings of different models can't be compared, the
+/// ones of another model are left out of searches
+#[derive(Clone, Debug, PartialEq)]
+pub enum EmbeddingBackend {
+    /// A model run locally by ollama
+    Ollama { model: String },
+    /// An openai compatible embeddings api, like https://api.openai.com/v1/embeddings
+    Api {
+        url: String,
+        model: String,
+        api_key: Option<String>,
+    },
+}
+
+impl Default for EmbeddingBackend {
+    fn default() -> Self {
+        EmbeddingBackend::Ollama {
+            model: OLLAMA_MODEL.to_string(),
+        }
+    }
+}
+
+impl EmbeddingBackend {
+    /// The api at `url` when given, ollama otherwise, with `model` or the default one of each
+    pub fn new(url: Option<String>, model: Option<String>, api_key: Option<String>) -> Self {
+        match url {
+            Some(url) => EmbeddingBackend::Api {
+                url,
+                model: model.unwrap_or_else(|| API_MODEL.to_string()),
+                api_key,
+            },
+            None => EmbeddingBackend::Ollama {
+                model: model.unwrap_or_else(|| OLLAMA_MODEL.to_string()),
+            },
+        }
+    }
+}
+
+pub fn init(backend: EmbeddingBackend) {
+    let _ = BACKEND.set(backend);
+}
+
+pub fn current() -> EmbeddingBackend {
+    BACKEND.get().cloned().unwrap_or_default()
+}
 
 #[derive(Debug, Serialize)]
 struct OllamaRequest {
@@ -14,25 +74,65 @@ struct OllamaResponse {
     embedding: Vec<f32>,
 }
 
-/// Generates embeddings for text using Ollama's nomic-embed-text model
+#[derive(Debug, Serialize)]
+struct ApiRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse {
+    data: Vec<ApiEmbedding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiEmbedding {
+    embedding: Vec<f32>,
+}
+
+/// Generates embeddings for the text of a frame with the configured backend
 pub async fn generate_embedding(text: &str, frame_id: i64) -> Result<Vec<f32>> {
+    debug!(
+        "generating embedding for frame_id: {}, text: {}",
+        frame_id, text
+    );
+    let embedding = embed(text).await?;
+    info!("generated embedding for frame_id: {}", frame_id);
+    Ok(embedding)
+}
+
+/// Embedding of `text` with the configured backend
+pub async fn embed(text: &str) -> Result<Vec<f32>> {
+    match current() {
+        EmbeddingBackend::Ollama { model } => ollama_embedding(text, model).await,
+        EmbeddingBackend::Api {
+            url,
+            model,
+            api_key,
+        } => api_embedding(text, &url, &model, api_key.as_deref()).await,
+    }
+}
+
+async fn ollama_embedding(text: &str, model: String) -> Result<Vec<f32>> {
     let client = Client::new();
-    
-    debug!("generating embedding for frame_id: {}, text: {}", frame_id, text);
 
     // Check if Ollama server is running
-    if let Err(e) = client.get("http://localhost:11434/api/version").send().await {
+    if let Err(e) = client
+        .get(format!("{}/api/version", OLLAMA_URL))
+        .send()
+        .await
+    {
         error!("ollama server not running: {}", e);
         return Err(anyhow::anyhow!("ollama server not running"));
     }
 
     let request = OllamaRequest {
-        model: "nomic-embed-text".to_string(),
+        model,
         prompt: text.to_string(),
     };
 
     let response = client
-        .post("http://localhost:11434/api/embeddings")
+        .post(format!("{}/api/embeddings", OLLAMA_URL))
         .json(&request)
         .send()
         .await?;
@@ -42,8 +142,71 @@ pub async fn generate_embedding(text: &str, frame_id: i64) -> Result<Vec<f32>> {
         return Err(anyhow::anyhow!("failed to generate embedding"));
     }
 
-    let embedding = response.json::<OllamaResponse>().await?;
-    info!("generated embedding for frame_id: {}", frame_id);
-    
-    Ok(embedding.embedding)
+    Ok(response.json::<OllamaResponse>().await?.embedding)
+}
+
+async fn api_embedding(
+    text: &str,
+    url: &str,
+    model: &str,
+    api_key: Option<&str>,
+) -> Result<Vec<f32>> {
+    let mut request = Client::new()
+        .post(url)
+        .json(&ApiRequest { model, input: text });
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    let response = request.send().await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        error!("failed to generate embedding: {} {}", status, body);
+        return Err(anyhow::anyhow!("failed to generate embedding: {}", status));
+    }
+
+    response
+        .json::<ApiResponse>()
+        .await?
+        .data
+        .into_iter()
+        .next()
+        .map(|data| data.embedding)
+        .ok_or_else(|| anyhow::anyhow!("no embedding in the response of {}", url))
+}
+
+/// Embeds the text read and the transcriptions stored that aren't yet, in the background and
+/// latest first, for /search/semantic to find them by meaning
+pub fn spawn_text_embedder(db: Arc<DatabaseManager>) {
+    tokio::spawn(async move {
+        loop {
+            match embed_pending(&db).await {
+                Ok(0) => tokio::time::sleep(EMBEDDING_INTERVAL).await,
+                Ok(count) => debug!("embedded {} texts", count),
+                Err(e) => {
+                    warn!("failed to embed text: {}", e);
+                    tokio::time::sleep(EMBEDDING_INTERVAL).await;
+                }
+            }
+        }
+    });
+}
+
+/// Embeds a batch of the text read and of the transcriptions that aren't embedded yet, returns
+/// how many were
+pub async fn embed_pending(db: &DatabaseManager) -> Result<usize> {
+    let mut count = 0;
+    for (frame_id, text) in db.ocr_text_without_embedding(EMBEDDING_BATCH).await? {
+        let embedding = embed(&text).await?;
+        db.insert_embeddings(frame_id, serde_json::to_string(&embedding)?)
+            .await?;
+        count += 1;
+    }
+    for (id, text) in db.transcriptions_without_embedding(EMBEDDING_BATCH).await? {
+        let embedding = embed(&text).await?;
+        db.insert_transcription_embedding(id, &embedding).await?;
+        count += 1;
+    }
+    Ok(count)
 }
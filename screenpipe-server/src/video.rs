@@ -1,9 +1,12 @@
+use crate::video_encoder;
 use chrono::Utc;
 use crossbeam::queue::ArrayQueue;
 use image::ImageFormat::{self};
 use screenpipe_core::{find_ffmpeg_path, Language};
 use screenpipe_vision::{
-    capture_screenshot_by_window::WindowFilters, continuous_capture, CaptureResult, OcrEngine,
+    active_window::AppFilters, adaptive_fps::AdaptiveFpsConfig,
+    capture_screenshot_by_window::WindowFilters, continuous_capture, frame_scale::FrameScale,
+    sensitive_windows::SensitiveWindows, CaptureResult, OcrEngine,
 };
 use std::borrow::Cow;
 use std::path::PathBuf;
@@ -38,8 +41,16 @@ impl VideoCapture {
         monitor_id: u32,
         ignore_list: &[String],
         include_list: &[String],
+        capture_window: Option<String>,
+        app_filters: AppFilters,
+        sensitive_windows: SensitiveWindows,
         languages: Vec<Language>,
         capture_unfocused_windows: bool,
+        adaptive_fps: Option<AdaptiveFpsConfig>,
+        frame_dedup: bool,
+        accessibility_tree: bool,
+        ocr_scale: FrameScale,
+        video_scale: FrameScale,
     ) -> Self {
         let fps = if fps.is_finite() && fps > 0.0 {
             fps
@@ -56,7 +67,12 @@ impl VideoCapture {
         let capture_video_frame_queue = video_frame_queue.clone();
         let capture_ocr_frame_queue = ocr_frame_queue.clone();
         let (result_sender, mut result_receiver) = channel(512);
-        let window_filters = Arc::new(WindowFilters::new(ignore_list, include_list));
+        let window_filters = Arc::new(
+            WindowFilters::new(ignore_list, include_list)
+                .with_single_window(capture_window.as_deref())
+                .with_app_filters(app_filters)
+                .with_sensitive_windows(sensitive_windows),
+        );
         let window_filters_clone = Arc::clone(&window_filters);
         let _capture_thread = tokio::spawn(async move {
             continuous_capture(
@@ -67,6 +83,10 @@ impl VideoCapture {
                 window_filters_clone,
                 languages.clone(),
                 capture_unfocused_windows,
+                adaptive_fps,
+                frame_dedup,
+                accessibility_tree,
+                ocr_scale,
             )
             .await;
         });
@@ -101,7 +121,9 @@ impl VideoCapture {
 
                 let result = Arc::new(result);
 
-                let video_pushed = push_to_queue(&capture_video_frame_queue, &result, "Video");
+                // a duplicate is stored as a reference to the frame it repeats, already encoded
+                let video_pushed =
+                    result.duplicate || push_to_queue(&capture_video_frame_queue, &result, "Video");
                 let ocr_pushed = push_to_queue(&capture_ocr_frame_queue, &result, "OCR");
 
                 if !video_pushed || !ocr_pushed {
@@ -132,6 +154,7 @@ impl VideoCapture {
                 new_chunk_callback_clone,
                 monitor_id,
                 video_chunk_duration,
+                video_scale,
             )
             .await;
         });
@@ -155,7 +178,7 @@ pub async fn start_ffmpeg_process(output_file: &str, fps: f64) -> Result<Child,
     info!("Starting FFmpeg process for file: {}", output_file);
     let fps_str = fps.to_string();
     let mut command = Command::new(find_ffmpeg_path().unwrap());
-    let mut args = vec![
+    let args = [
         "-f",
         "image2pipe",
         "-vcodec",
@@ -168,21 +191,10 @@ pub async fn start_ffmpeg_process(output_file: &str, fps: f64) -> Result<Child,
         "pad=width=ceil(iw/2)*2:height=ceil(ih/2)*2",
     ];
 
-    args.extend_from_slice(&[
-        "-vcodec",
-        "libx265",
-        "-tag:v",
-        "hvc1",
-        "-preset",
-        "ultrafast",
-        "-crf",
-        "23",
-    ]);
-
-    args.extend_from_slice(&["-pix_fmt", "yuv420p", output_file]);
-
     command
         .args(&args)
+        .args(video_encoder::current().ffmpeg_args(fps))
+        .arg(output_file)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
@@ -218,6 +230,7 @@ async fn save_frames_as_video(
     new_chunk_callback: Arc<dyn Fn(&str) + Send + Sync>,
     monitor_id: u32,
     video_chunk_duration: Duration,
+    video_scale: FrameScale,
 ) {
     debug!("Starting save_frames_as_video function");
     let frames_per_video = (fps * video_chunk_duration.as_secs_f64()).ceil() as usize;
@@ -233,7 +246,7 @@ async fn save_frames_as_video(
 
             frame_count = 0;
             let first_frame = wait_for_first_frame(frame_queue).await;
-            let buffer = encode_frame(&first_frame);
+            let buffer = encode_frame(&first_frame, video_scale);
 
             let output_file = create_output_file(output_path, monitor_id);
             new_chunk_callback(&output_file);
@@ -266,6 +279,7 @@ async fn save_frames_as_video(
             &mut frame_count,
             frames_per_video,
             fps,
+            video_scale,
         )
         .await;
 
@@ -285,10 +299,12 @@ async fn wait_for_first_frame(
     }
 }
 
-fn encode_frame(frame: &CaptureResult) -> Vec<u8> {
+fn encode_frame(frame: &CaptureResult, video_scale: FrameScale) -> Vec<u8> {
+    let scaled = video_scale.apply(&frame.image);
     let mut buffer = Vec::new();
-    frame
-        .image
+    scaled
+        .as_ref()
+        .unwrap_or(&frame.image)
         .write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)
         .expect("Failed to encode frame");
     buffer
@@ -319,11 +335,12 @@ async fn process_frames(
     frame_count: &mut usize,
     frames_per_video: usize,
     fps: f64,
+    video_scale: FrameScale,
 ) {
     let write_timeout = Duration::from_secs_f64(1.0 / fps);
     while *frame_count < frames_per_video {
         if let Some(frame) = frame_queue.pop() {
-            let buffer = encode_frame(&frame);
+            let buffer = encode_frame(&frame, video_scale);
             if let Some(stdin) = current_stdin.as_mut() {
                 if let Err(e) = write_frame_with_retry(stdin, &buffer).await {
                     error!("Failed to write frame to ffmpeg after max retries: {}", e);
@@ -0,0 +1,126 @@
+use crate::video_encoder::VideoEncoder;
+use crate::video_utils::get_video_metadata;
+use crate::DatabaseManager;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use screenpipe_core::find_ffmpeg_path;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{debug, error, info, warn};
+
+/// How often chunks old enough are looked for
+const ARCHIVE_INTERVAL: Duration = Duration::from_secs(3600);
+/// Chunks re-encoded each time, an encoder busy for hours would slow the recording down
+const CHUNKS_PER_RUN: u32 = 50;
+
+/// Video chunks re-encoded once they are old enough, typically to av1
+#[derive(Clone, Copy, Debug)]
+pub struct VideoArchiveConfig {
+    /// Age of the last frame of a chunk it is re-encoded after
+    pub after: Duration,
+    pub encoder: VideoEncoder,
+}
+
+/// Re-encodes the chunks of `config` every hour, in place so their path and the offsets of
+/// their frames stay valid
+pub async fn archive_periodically(db: Arc<DatabaseManager>, config: VideoArchiveConfig) {
+    let Ok(after) = chrono::Duration::from_std(config.after) else {
+        error!("video archive age out of range");
+        return;
+    };
+    let codec = config.encoder.codec.name();
+    let mut interval = tokio::time::interval(ARCHIVE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let chunks = match db
+            .video_chunks_to_archive(Utc::now() - after, codec, CHUNKS_PER_RUN)
+            .await
+        {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                error!("failed to list video chunks to archive: {}", e);
+                continue;
+            }
+        };
+        let mut reclaimed: u64 = 0;
+        for (id, file_path) in chunks {
+            match reencode(Path::new(&file_path), &config.encoder).await {
+                Ok(saved) => reclaimed += saved,
+                Err(e) => {
+                    warn!("failed to archive video chunk {}: {}", file_path, e);
+                    continue;
+                }
+            }
+            // also marks the chunks whose file is gone or didn't get smaller, not to retry them
+            if let Err(e) = db.set_video_chunk_codec(id, codec).await {
+                error!("failed to mark video chunk {} as archived: {}", id, e);
+            }
+        }
+        if reclaimed > 0 {
+            info!(
+                "archiving video chunks with {} reclaimed {} MB",
+                config.encoder,
+                reclaimed / (1024 * 1024)
+            );
+        }
+    }
+}
+
+/// Re-encodes `file` with `encoder`, keeping the original when the new file isn't smaller.
+/// Returns the bytes saved
+async fn reencode(file: &Path, encoder: &VideoEncoder) -> Result<u64> {
+    if !file.exists() {
+        debug!(
+            "video chunk {} was deleted, not archiving it",
+            file.display()
+        );
+        return Ok(0);
+    }
+    let ffmpeg = find_ffmpeg_path().ok_or_else(|| anyhow!("ffmpeg not found"))?;
+    let fps = match get_video_metadata(&file.to_string_lossy()).await {
+        Ok(metadata) => metadata.fps,
+        Err(e) => {
+            debug!("failed to read the fps of {}: {}", file.display(), e);
+            1.0
+        }
+    };
+    let tmp = file.with_extension("archive.mp4");
+    let output = Command::new(ffmpeg)
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(file)
+        // keeps the creation time frames are dated with, and one output frame per input frame
+        .args(["-map", "0:v:0", "-map_metadata", "0", "-vsync", "0"])
+        .args(encoder.ffmpeg_args(fps))
+        .arg(&tmp)
+        .output()
+        .await?;
+    if !output.status.success() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return Err(anyhow!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let old_len = tokio::fs::metadata(file).await?.len();
+    let len = tokio::fs::metadata(&tmp).await?.len();
+    if len >= old_len {
+        debug!(
+            "{} isn't smaller with {}, keeping it as it is",
+            file.display(),
+            encoder
+        );
+        tokio::fs::remove_file(&tmp).await?;
+        return Ok(0);
+    }
+    tokio::fs::rename(&tmp, file).await?;
+    debug!(
+        "archived {} from {} to {} bytes",
+        file.display(),
+        old_len,
+        len
+    );
+    Ok(old_len - len)
+}
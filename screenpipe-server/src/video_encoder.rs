@@ -0,0 +1,234 @@
+use once_cell::sync::OnceCell;
+use screenpipe_core::find_ffmpeg_path;
+use std::fmt;
+use std::process::Stdio;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// Longest a hardware encoder goes without a keyframe, seeking to a frame decodes at most these
+const KEYFRAME_INTERVAL_SECS: f64 = 10.0;
+
+static ENCODER: OnceCell<VideoEncoder> = OnceCell::new();
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VideoCodec {
+    H264,
+    #[default]
+    Hevc,
+    /// About half the size of h264 for screen content, slower to encode
+    Av1,
+}
+
+impl VideoCodec {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "h264",
+            VideoCodec::Hevc => "hevc",
+            VideoCodec::Av1 => "av1",
+        }
+    }
+}
+
+/// Where the frames are encoded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EncoderBackend {
+    /// libx264, libx265 or svt-av1, on the cpu
+    #[default]
+    Software,
+    /// macOS
+    VideoToolbox,
+    /// Nvidia gpus
+    Nvenc,
+    /// Intel gpus
+    QuickSync,
+}
+
+/// What --video-encoder asks for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EncoderPreference {
+    /// The first hardware encoder of the platform that works, software otherwise
+    #[default]
+    Auto,
+    Backend(EncoderBackend),
+}
+
+/// The ffmpeg encoder the video chunks are written with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VideoEncoder {
+    pub codec: VideoCodec,
+    pub backend: EncoderBackend,
+}
+
+impl VideoEncoder {
+    pub fn new(codec: VideoCodec, backend: EncoderBackend) -> Self {
+        Self { codec, backend }
+    }
+
+    pub fn ffmpeg_name(&self) -> &'static str {
+        match (self.backend, self.codec) {
+            (EncoderBackend::Software, VideoCodec::H264) => "libx264",
+            (EncoderBackend::Software, VideoCodec::Hevc) => "libx265",
+            (EncoderBackend::VideoToolbox, VideoCodec::H264) => "h264_videotoolbox",
+            (EncoderBackend::VideoToolbox, VideoCodec::Hevc) => "hevc_videotoolbox",
+            (EncoderBackend::Nvenc, VideoCodec::H264) => "h264_nvenc",
+            (EncoderBackend::Nvenc, VideoCodec::Hevc) => "hevc_nvenc",
+            (EncoderBackend::QuickSync, VideoCodec::H264) => "h264_qsv",
+            (EncoderBackend::QuickSync, VideoCodec::Hevc) => "hevc_qsv",
+            (EncoderBackend::Software, VideoCodec::Av1) => "libsvtav1",
+            // macs don't encode av1, it is never picked as `exists` is false
+            (EncoderBackend::VideoToolbox, VideoCodec::Av1) => "av1_videotoolbox",
+            // rtx 40 and intel arc gpus
+            (EncoderBackend::Nvenc, VideoCodec::Av1) => "av1_nvenc",
+            (EncoderBackend::QuickSync, VideoCodec::Av1) => "av1_qsv",
+        }
+    }
+
+    /// Whether ffmpeg has the encoder at all
+    pub fn exists(&self) -> bool {
+        !(self.backend == EncoderBackend::VideoToolbox && self.codec == VideoCodec::Av1)
+    }
+
+    /// Output arguments of ffmpeg for frames coming at `fps`. Every frame captured is encoded,
+    /// the n-th frame of a chunk stays at offset n, which is how frames are found in it
+    pub fn ffmpeg_args(&self, fps: f64) -> Vec<String> {
+        let mut args = vec!["-vcodec", self.ffmpeg_name()];
+        if self.codec == VideoCodec::Hevc {
+            // plays in quicktime and safari
+            args.extend(["-tag:v", "hvc1"]);
+        }
+        let pixel_format = match self.backend {
+            EncoderBackend::Software if self.codec == VideoCodec::Av1 => {
+                args.extend(["-preset", "8", "-crf", "35"]);
+                "yuv420p"
+            }
+            EncoderBackend::Software => {
+                args.extend(["-preset", "ultrafast", "-crf", "23"]);
+                "yuv420p"
+            }
+            EncoderBackend::VideoToolbox => {
+                args.extend(["-q:v", "65", "-realtime", "1"]);
+                "yuv420p"
+            }
+            EncoderBackend::Nvenc => {
+                args.extend(["-preset", "p1", "-rc", "vbr", "-cq", "28", "-b:v", "0"]);
+                "yuv420p"
+            }
+            EncoderBackend::QuickSync => {
+                args.extend(["-preset", "veryfast", "-global_quality", "25"]);
+                "nv12"
+            }
+        };
+        let mut args: Vec<String> = args.into_iter().map(String::from).collect();
+        if self.backend != EncoderBackend::Software {
+            // no b-frames and bounded gops, like the software presets, so seeking stays cheap
+            let keyframe_interval = (fps * KEYFRAME_INTERVAL_SECS).ceil().max(1.0);
+            args.extend([
+                "-bf".to_string(),
+                "0".to_string(),
+                "-g".to_string(),
+                keyframe_interval.to_string(),
+            ]);
+        }
+        args.extend(["-pix_fmt".to_string(), pixel_format.to_string()]);
+        args
+    }
+
+    /// Whether ffmpeg can encode with it here. Hardware encoders are listed by builds of ffmpeg
+    /// supporting them even on machines without the hardware
+    pub async fn works(&self) -> bool {
+        if !self.exists() {
+            return false;
+        }
+        // libx264 and libx265 are in every build screenpipe ships with, svt-av1 isn't
+        if self.backend == EncoderBackend::Software && self.codec != VideoCodec::Av1 {
+            return true;
+        }
+        let Some(ffmpeg) = find_ffmpeg_path() else {
+            return false;
+        };
+        let mut args: Vec<String> = [
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-f",
+            "lavfi",
+            "-i",
+            "color=c=black:s=256x256",
+            "-frames:v",
+            "1",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        args.extend(self.ffmpeg_args(1.0));
+        args.extend(["-f", "null", "-"].into_iter().map(String::from));
+        Command::new(ffmpeg)
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .is_ok_and(|status| status.success())
+    }
+}
+
+impl fmt::Display for VideoEncoder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.ffmpeg_name())
+    }
+}
+
+/// Hardware encoders tried by --video-encoder auto, the most common first
+fn platform_backends() -> &'static [EncoderBackend] {
+    if cfg!(target_os = "macos") {
+        &[EncoderBackend::VideoToolbox]
+    } else {
+        &[EncoderBackend::Nvenc, EncoderBackend::QuickSync]
+    }
+}
+
+/// The encoder of `codec` to use, falling back to software when the one asked for doesn't work.
+/// None when no encoder of `codec` works, ffmpeg is built without svt-av1 and the gpu has no
+/// av1 encoder
+pub async fn resolve(codec: VideoCodec, preference: EncoderPreference) -> Option<VideoEncoder> {
+    let mut candidates: Vec<EncoderBackend> = match preference {
+        EncoderPreference::Auto => platform_backends().to_vec(),
+        EncoderPreference::Backend(backend) => vec![backend],
+    };
+    if !candidates.contains(&EncoderBackend::Software) {
+        candidates.push(EncoderBackend::Software);
+    }
+    for backend in candidates {
+        let candidate = VideoEncoder::new(codec, backend);
+        if candidate.works().await {
+            return Some(candidate);
+        }
+        if preference == EncoderPreference::Backend(backend) {
+            warn!("{} doesn't work on this machine", candidate);
+        }
+    }
+    None
+}
+
+/// Picks the encoder the video chunks are written with, hevc in software when no encoder of
+/// `codec` works. Chunks are encoded with libx265 until it is called
+pub async fn init(codec: VideoCodec, preference: EncoderPreference) -> VideoEncoder {
+    let encoder = match resolve(codec, preference).await {
+        Some(encoder) => encoder,
+        None => {
+            warn!(
+                "no {:?} encoder works on this machine, encoding video with hevc",
+                codec
+            );
+            VideoEncoder::default()
+        }
+    };
+    info!("encoding video with {}", encoder);
+    let _ = ENCODER.set(encoder);
+    encoder
+}
+
+pub fn current() -> VideoEncoder {
+    ENCODER.get().copied().unwrap_or_default()
+}
@@ -216,6 +216,36 @@ pub async fn extract_frames_from_video(
     video_path: &std::path::Path,
     output_path: Option<PathBuf>,
 ) -> Result<Vec<DynamicImage>> {
+    let extracted = extract_frame_files(video_path).await?;
+
+    let mut frames = Vec::with_capacity(extracted.paths.len());
+    for path in &extracted.paths {
+        let frame_data = tokio::fs::read(path).await?;
+        let img = image::load_from_memory(&frame_data)?;
+
+        if let (Some(out_dir), Some(frame_name)) = (&output_path, path.file_name()) {
+            let dest_path = out_dir.join(frame_name);
+            debug!("saving frame to disk: {}", dest_path.display());
+            img.save(&dest_path)?;
+        }
+
+        frames.push(img);
+    }
+
+    debug!("extracted {} frames", frames.len());
+    Ok(frames)
+}
+
+/// Frames of a video written as jpegs to a temporary directory, removed when dropped
+pub struct ExtractedFrames {
+    _dir: tempfile::TempDir,
+    /// In playback order
+    pub paths: Vec<PathBuf>,
+}
+
+/// Extracts the frames of a video to disk, at 1 fps for high frame rate videos, so a long
+/// recording can be read a frame at a time instead of holding every frame in memory
+pub async fn extract_frame_files(video_path: &std::path::Path) -> Result<ExtractedFrames> {
     let ffmpeg_path = find_ffmpeg_path().expect("failed to find ffmpeg path");
     let temp_dir = tempfile::tempdir()?;
     let output_pattern = temp_dir.path().join("frame%d.jpg");
@@ -278,31 +308,30 @@ pub async fn extract_frames_from_video(
         return Err(anyhow::anyhow!("ffmpeg failed: {}", stderr));
     }
 
-    // Collect all frames into a vector
+    // the directory lists frame10.jpg before frame2.jpg, ordered by their number instead
     let mut frames = Vec::new();
     let mut entries = tokio::fs::read_dir(&temp_dir.path()).await?;
-
     while let Some(entry) = entries.next_entry().await? {
         let path = entry.path();
-        let frame_data = tokio::fs::read(&path).await?;
-        let img = image::load_from_memory(&frame_data)?;
-
-        if let Some(out_dir) = &output_path {
-            let frame_name = entry.file_name();
-            let dest_path = out_dir.join(frame_name);
-            debug!("saving frame to disk: {}", dest_path.display());
-            img.save(&dest_path)?;
+        let number = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix("frame"))
+            .and_then(|n| n.parse::<u64>().ok());
+        if let Some(number) = number {
+            frames.push((number, path));
         }
-
-        frames.push(img);
     }
+    frames.sort_by_key(|(number, _)| *number);
 
     if frames.is_empty() {
         return Err(anyhow::anyhow!("no frames were extracted"));
     }
 
-    debug!("extracted {} frames", frames.len());
-    Ok(frames)
+    Ok(ExtractedFrames {
+        _dir: temp_dir,
+        paths: frames.into_iter().map(|(_, path)| path).collect(),
+    })
 }
 
 async fn get_video_fps(ffmpeg_path: &PathBuf, video_path: &str) -> Result<f64> {
@@ -0,0 +1,427 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use image::DynamicImage;
+use screenpipe_audio::{
+    pcm_stream, AudioDevice, AudioInput, CaptureTiming, DeviceType, FILE_AUDIO_HOST,
+};
+use screenpipe_core::Language;
+use screenpipe_vision::utils::compare_with_previous_image;
+use screenpipe_vision::OcrEngine;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, error, info, warn};
+use walkdir::WalkDir;
+
+#[cfg(target_os = "macos")]
+use screenpipe_vision::perform_ocr_apple;
+#[cfg(target_os = "windows")]
+use screenpipe_vision::perform_ocr_windows;
+use screenpipe_vision::{perform_ocr_custom, perform_ocr_paddle, perform_ocr_tesseract};
+
+use crate::redaction::{self, redact};
+use crate::video_utils::{extract_frame_files, get_video_metadata, VideoMetadata};
+use crate::DatabaseManager;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Files modified more recently than this are probably still being written (e.g. a running OBS recording)
+const SETTLE_TIME: Duration = Duration::from_secs(5);
+/// Audio is fed to the transcription pipeline in pieces the size of a live recording chunk
+const AUDIO_PIECE: Duration = Duration::from_secs(30);
+
+/// A user-designated folder whose new screenshots and recordings end up in the timeline.
+///
+/// Parsed from `path` or `label=path`, the label (folder name by default) is what the
+/// ingested content is attributed to: app name of the OCR text, device of the transcripts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchFolder {
+    pub path: PathBuf,
+    pub source: String,
+}
+
+impl std::str::FromStr for WatchFolder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (source, path) = match s.split_once('=') {
+            Some((label, path)) if !label.trim().is_empty() => {
+                (Some(label.trim().to_string()), path.trim())
+            }
+            _ => (None, s.trim()),
+        };
+        if path.is_empty() {
+            return Err("watch folder path is empty".to_string());
+        }
+        let path = PathBuf::from(path);
+        let source = source.unwrap_or_else(|| {
+            path.file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.to_string_lossy().into_owned())
+        });
+        Ok(WatchFolder { path, source })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IngestKind {
+    Image,
+    Video,
+    Audio,
+}
+
+impl IngestKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "png" | "jpg" | "jpeg" | "bmp" | "tiff" | "webp" => Some(IngestKind::Image),
+            "mp4" | "mov" | "mkv" | "webm" | "avi" => Some(IngestKind::Video),
+            "mp3" | "wav" | "m4a" | "flac" | "ogg" | "oga" | "opus" | "weba" => {
+                Some(IngestKind::Audio)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Polls watched folders and runs new files through OCR (images, video frames) and
+/// transcription (audio, video soundtracks). Files are referenced in place, not copied.
+///
+/// Files already in a folder the first time it is watched are recorded as skipped,
+/// use `screenpipe add` to backfill them.
+pub struct FolderWatcher {
+    db: Arc<DatabaseManager>,
+    folders: Vec<WatchFolder>,
+    ocr_engine: Arc<OcrEngine>,
+    languages: Vec<Language>,
+    use_pii_removal: bool,
+    /// None when audio is disabled, audio files and soundtracks are then ignored
+    whisper_sender: Option<crossbeam::channel::Sender<AudioInput>>,
+}
+
+impl FolderWatcher {
+    pub fn new(
+        db: Arc<DatabaseManager>,
+        folders: Vec<WatchFolder>,
+        ocr_engine: Arc<OcrEngine>,
+        languages: Vec<Language>,
+        use_pii_removal: bool,
+        whisper_sender: Option<crossbeam::channel::Sender<AudioInput>>,
+    ) -> Self {
+        Self {
+            db,
+            folders,
+            ocr_engine,
+            languages,
+            use_pii_removal,
+            whisper_sender,
+        }
+    }
+
+    pub async fn run(self) {
+        for folder in &self.folders {
+            info!(
+                "watching {} for new captures (source: {})",
+                folder.path.display(),
+                folder.source
+            );
+        }
+        loop {
+            for folder in &self.folders {
+                if let Err(e) = self.scan(folder).await {
+                    warn!(
+                        "failed to scan watch folder {}: {}",
+                        folder.path.display(),
+                        e
+                    );
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Ingests every settled, supported file of the folder that hasn't been seen yet.
+    /// Returns how many files were ingested.
+    pub async fn scan(&self, folder: &WatchFolder) -> Result<usize> {
+        // the folder itself is recorded once its existing content has been marked as skipped
+        let folder_path = folder.path.to_string_lossy().into_owned();
+        let first_scan = !self.db.is_file_ingested(&folder_path).await?;
+        let mut ingested = 0;
+
+        for (path, kind, modified) in find_candidates(&folder.path) {
+            let file_path = path.to_string_lossy().into_owned();
+            if self.db.is_file_ingested(&file_path).await? {
+                continue;
+            }
+            if first_scan {
+                self.db
+                    .mark_file_ingested(&file_path, &folder.source, "skipped")
+                    .await?;
+                continue;
+            }
+            if SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default()
+                < SETTLE_TIME
+            {
+                continue;
+            }
+
+            info!("ingesting {:?} {} from {}", kind, file_path, folder.source);
+            let status = match self.ingest(&path, kind, modified, &folder.source).await {
+                Ok(()) => {
+                    ingested += 1;
+                    "ingested"
+                }
+                Err(e) => {
+                    error!("failed to ingest {}: {}", file_path, e);
+                    "failed"
+                }
+            };
+            self.db
+                .mark_file_ingested(&file_path, &folder.source, status)
+                .await?;
+        }
+
+        if first_scan {
+            self.db
+                .mark_file_ingested(&folder_path, &folder.source, "watched")
+                .await?;
+        }
+
+        Ok(ingested)
+    }
+
+    async fn ingest(
+        &self,
+        path: &Path,
+        kind: IngestKind,
+        modified: SystemTime,
+        source: &str,
+    ) -> Result<()> {
+        match kind {
+            IngestKind::Image => self.ingest_image(path, modified, source).await,
+            IngestKind::Video => {
+                let metadata = get_video_metadata(&path.to_string_lossy()).await?;
+                let started_at = metadata.creation_time;
+                self.ingest_video(path, metadata, source).await?;
+                // screen recordings often have no soundtrack, that's fine
+                if let Err(e) = self.ingest_audio(path, Some(started_at), source).await {
+                    debug!("no audio ingested from {}: {}", path.display(), e);
+                }
+                Ok(())
+            }
+            // no reliable start time in audio files, derived from when the file stopped being written
+            IngestKind::Audio => self.ingest_audio(path, None, source).await,
+        }
+    }
+
+    async fn ingest_image(&self, path: &Path, modified: SystemTime, source: &str) -> Result<()> {
+        let image = image::open(path)?;
+        let metadata = VideoMetadata {
+            creation_time: modified.into(),
+            fps: 1.0,
+            duration: 0.0,
+            device_name: Some(device_name(source)),
+            name: Some(path.to_string_lossy().into_owned()),
+        };
+        let frame_ids = self
+            .db
+            .create_video_with_frames(&path.to_string_lossy(), 1, metadata)
+            .await?;
+        self.insert_ocr(frame_ids[0], &image, path, source).await
+    }
+
+    async fn ingest_video(
+        &self,
+        path: &Path,
+        mut metadata: VideoMetadata,
+        source: &str,
+    ) -> Result<()> {
+        // read back a frame at a time, hours of recording never fit in memory at once
+        let frames = extract_frame_files(path).await?;
+        // frames are extracted at 1 fps from high frame rate videos
+        if metadata.fps > 10.0 {
+            metadata.fps = 1.0;
+        }
+        metadata.device_name = Some(device_name(source));
+        let frame_ids = self
+            .db
+            .create_video_with_frames(&path.to_string_lossy(), frames.paths.len(), metadata)
+            .await?;
+
+        let mut previous_image: Option<DynamicImage> = None;
+        for (idx, frame_path) in frames.paths.iter().enumerate() {
+            let frame = image::open(frame_path)?;
+            if let Some(prev) = &previous_image {
+                let diff = compare_with_previous_image(
+                    Some(prev),
+                    &frame,
+                    &mut None,
+                    idx as u64,
+                    &mut 0.0,
+                )
+                .await?;
+                // same threshold as the live capture
+                if diff < 0.006 {
+                    continue;
+                }
+            }
+            self.insert_ocr(frame_ids[idx], &frame, path, source)
+                .await?;
+            previous_image = Some(frame);
+        }
+        Ok(())
+    }
+
+    async fn insert_ocr(
+        &self,
+        frame_id: i64,
+        image: &DynamicImage,
+        path: &Path,
+        source: &str,
+    ) -> Result<()> {
+        let (text, text_json, _) = self.perform_ocr(image).await?;
+        let (text, text_json) = if self.use_pii_removal {
+            let redacted = redaction::off_runtime(move || {
+                let redacted = redact(&text);
+                // the words would give away what was masked
+                let text_json = if redacted == text {
+                    text_json
+                } else {
+                    "[]".to_string()
+                };
+                (redacted, text_json)
+            })
+            .await?;
+            redacted
+        } else {
+            (text, text_json)
+        };
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.db
+            .insert_ocr_text(
+                frame_id,
+                &text,
+                &text_json,
+                source,
+                &file_name,
+                Arc::clone(&self.ocr_engine),
+                true,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn perform_ocr(&self, image: &DynamicImage) -> Result<(String, String, Option<f64>)> {
+        Ok(match self.ocr_engine.as_ref() {
+            #[cfg(target_os = "macos")]
+            OcrEngine::AppleNative => perform_ocr_apple(image, &self.languages),
+            #[cfg(target_os = "windows")]
+            OcrEngine::WindowsNative => perform_ocr_windows(image).await?,
+            OcrEngine::PaddleOcr => perform_ocr_paddle(image, self.languages.clone()).await?,
+            OcrEngine::Custom(config) => {
+                perform_ocr_custom(image, self.languages.clone(), config).await?
+            }
+            _ => perform_ocr_tesseract(image, self.languages.clone()),
+        })
+    }
+
+    /// Decodes the file's first audio track a piece at a time and queues it for transcription,
+    /// timestamped from `started_at` (or modification time minus duration) so transcripts land
+    /// where the recording happened in the timeline
+    async fn ingest_audio(
+        &self,
+        path: &Path,
+        started_at: Option<DateTime<Utc>>,
+        source: &str,
+    ) -> Result<()> {
+        let Some(sender) = self.whisper_sender.clone() else {
+            return Ok(());
+        };
+
+        let started_at: SystemTime = match started_at {
+            Some(started_at) => started_at.into(),
+            None => {
+                let duration = audio_duration(path).await?;
+                std::fs::metadata(path)?.modified()? - duration
+            }
+        };
+
+        let device = Arc::new(
+            AudioDevice::new(source.to_string(), DeviceType::Input)
+                .with_host(Some(FILE_AUDIO_HOST.to_string())),
+        );
+        let decode_path = path.to_path_buf();
+        // symphonia panics on some codecs and the channel blocks while its queue is full, both
+        // kept off the watcher task
+        let pieces = tokio::task::spawn_blocking(move || -> Result<usize> {
+            let stream = pcm_stream(&decode_path, AUDIO_PIECE)?;
+            let sample_rate = stream.sample_rate();
+            let mut pieces = 0;
+            for (i, piece) in stream.enumerate() {
+                let mut capture_timing = CaptureTiming::new(started_at, sample_rate);
+                capture_timing.push_block(0, AUDIO_PIECE * i as u32);
+                sender.send(AudioInput {
+                    data: Arc::new(piece?),
+                    sample_rate,
+                    channels: 1,
+                    device: Arc::clone(&device),
+                    capture_timing: Some(capture_timing),
+                })?;
+                pieces += 1;
+            }
+            Ok(pieces)
+        })
+        .await
+        .map_err(|e| anyhow!("audio decoding panicked: {}", e))??;
+        if pieces == 0 {
+            return Err(anyhow!("no audio samples"));
+        }
+        Ok(())
+    }
+}
+
+/// Length of an audio file from its container, or by decoding it when the container doesn't say
+async fn audio_duration(path: &Path) -> Result<Duration> {
+    if let Ok(metadata) = get_video_metadata(&path.to_string_lossy()).await {
+        if metadata.duration > 0.0 {
+            return Ok(Duration::from_secs_f64(metadata.duration));
+        }
+    }
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<Duration> {
+        let stream = pcm_stream(&path, AUDIO_PIECE)?;
+        let sample_rate = stream.sample_rate();
+        let mut samples = 0;
+        for piece in stream {
+            samples += piece?.len();
+        }
+        Ok(Duration::from_secs_f64(samples as f64 / sample_rate as f64))
+    })
+    .await
+    .map_err(|e| anyhow!("audio decoding panicked: {}", e))?
+}
+
+fn device_name(source: &str) -> String {
+    format!("watch_folder:{}", source)
+}
+
+/// Supported files under `root` with their modification time, oldest first
+fn find_candidates(root: &Path) -> Vec<(PathBuf, IngestKind, SystemTime)> {
+    let mut candidates: Vec<_> = WalkDir::new(root)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let kind = IngestKind::from_path(e.path())?;
+            let modified = e.metadata().ok()?.modified().ok()?;
+            Some((e.into_path(), kind, modified))
+        })
+        .collect();
+    candidates.sort_by_key(|(_, _, modified)| *modified);
+    candidates
+}
@@ -1,13 +1,22 @@
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::sync::Arc;
 
     use chrono::Utc;
-    use screenpipe_audio::{AudioDevice, DeviceType};
+    use screenpipe_audio::{AudioDevice, DetectedLanguage, DeviceType};
     use screenpipe_server::{
-        db_types::{ContentType, SearchResult},
+        db_types::{ContentType, FrameDetails, SearchResult, TranscriptionDetails},
         DatabaseManager,
     };
+    use screenpipe_vision::accessibility::{AccessibilityTree, Bounds, UiElement};
+    use screenpipe_vision::active_window::ActiveApp;
+    use screenpipe_vision::browser_tab::BrowserTab;
+    use screenpipe_vision::cursor::CursorOverlay;
+    use screenpipe_vision::input_events::{InputActivity, InputEvent};
+    use screenpipe_vision::monitor::MonitorData;
+    use screenpipe_vision::ocr_layout::{LayoutBox, OcrLayout};
+    use screenpipe_vision::ocr_structure::{OcrStructure, StructuredContent};
     use screenpipe_vision::OcrEngine;
 
     async fn setup_test_db() -> DatabaseManager {
@@ -48,6 +57,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -91,6 +108,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -110,6 +135,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -160,6 +193,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -179,6 +220,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -242,6 +291,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -261,6 +318,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -365,14 +430,14 @@ mod tests {
 
         // After inserting both audio transcriptions, let's check all audio entries
         let all_audio = db
-            .search_audio("", 100, 0, None, None, None, None, None)
+            .search_audio("", 100, 0, None, None, None, None, None, None, None, None)
             .await
             .unwrap();
         println!("All audio entries: {:?}", all_audio);
 
         // Then try specific search
         let audio_results = db
-            .search_audio("2", 100, 0, None, None, None, None, None)
+            .search_audio("2", 100, 0, None, None, None, None, None, None, None, None)
             .await
             .unwrap();
         println!("Audio results for '2': {:?}", audio_results);
@@ -394,6 +459,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -415,6 +488,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -440,6 +521,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -460,6 +549,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -557,6 +654,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -581,6 +686,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -608,6 +721,30 @@ mod tests {
         assert_eq!(speaker.unwrap().id, 1);
     }
 
+    #[tokio::test]
+    async fn test_enroll_speaker() {
+        let db = setup_test_db().await;
+
+        let unknown = db.insert_speaker(&vec![-0.1; 512]).await.unwrap();
+        let alice = db.enroll_speaker("alice", &vec![0.1; 512]).await.unwrap();
+        assert_eq!(alice.name, "alice");
+        assert_ne!(alice.id, unknown.id);
+
+        // enrolling again adds a sample to the same speaker
+        let mut second_sample = vec![0.1; 512];
+        second_sample[0] = 0.5;
+        let again = db.enroll_speaker("alice", &second_sample).await.unwrap();
+        assert_eq!(again.id, alice.id);
+
+        let matched = db
+            .get_speaker_from_embedding(&vec![0.2; 512])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(matched.id, alice.id);
+        assert_eq!(matched.name, "alice");
+    }
+
     #[tokio::test]
     async fn test_update_speaker_metadata() {
         let db = setup_test_db().await;
@@ -962,6 +1099,14 @@ mod tests {
                 None,
                 None,
                 Some("test_video"),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -987,6 +1132,14 @@ mod tests {
                 None,
                 None,
                 Some("non_existent"),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -1012,6 +1165,14 @@ mod tests {
                 None,
                 None,
                 Some("test_video"),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
@@ -1035,10 +1196,1486 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )
             .await
             .unwrap();
 
         assert_eq!(count, 2, "Should count both matching frames");
     }
+
+    #[tokio::test]
+    async fn test_search_audio_by_language() {
+        let db = setup_test_db().await;
+        let device = AudioDevice::new("test".to_string(), DeviceType::Input);
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription_with_details(
+            audio_chunk_id,
+            "hello there",
+            0,
+            "",
+            &device,
+            None,
+            None,
+            None,
+            &TranscriptionDetails {
+                language: Some(DetectedLanguage {
+                    code: "en".to_string(),
+                    probability: 0.9,
+                }),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        db.insert_audio_transcription_with_details(
+            audio_chunk_id,
+            "bonjour",
+            1,
+            "",
+            &device,
+            None,
+            None,
+            None,
+            &TranscriptionDetails {
+                language: Some(DetectedLanguage::forced("fr")),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let results = db
+            .search_audio(
+                "",
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some("fr"),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].transcription, "bonjour");
+        assert_eq!(
+            results[0].language,
+            Some(DetectedLanguage::forced("fr")),
+            "language should be returned with the result"
+        );
+
+        let count = db
+            .count_search_results(
+                "",
+                ContentType::Audio,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some("en"),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        let results = db
+            .search_audio("", 100, 0, None, None, None, None, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_search_audio_by_engine_and_model_version() {
+        let db = setup_test_db().await;
+        let device = AudioDevice::new("test".to_string(), DeviceType::Input);
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        let captured_until = Utc::now();
+        db.insert_audio_transcription_with_details(
+            audio_chunk_id,
+            "ship it on friday",
+            0,
+            "WhisperLargeV3Turbo",
+            &device,
+            None,
+            Some(0.0),
+            Some(2.5),
+            &TranscriptionDetails {
+                captured_until: Some(captured_until),
+                audio_duration: Some(2.5),
+                latency: Some(0.8),
+                model_version: Some("openai/whisper-large-v3-turbo".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        db.insert_audio_transcription(
+            audio_chunk_id,
+            "ship it on monday",
+            1,
+            "AzureSpeech",
+            &device,
+            None,
+            Some(2.5),
+            Some(5.0),
+        )
+        .await
+        .unwrap();
+
+        let results = db
+            .search_audio(
+                "",
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some("openai/whisper-large-v3-turbo"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let result = &results[0];
+        assert_eq!(result.transcription, "ship it on friday");
+        assert_eq!(result.transcription_engine, "WhisperLargeV3Turbo");
+        assert_eq!(result.audio_duration, Some(2.5));
+        assert_eq!(result.latency, Some(0.8));
+        assert_eq!(result.captured_until, Some(captured_until));
+
+        let results = db
+            .search_audio(
+                "",
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some("AzureSpeech"),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].transcription, "ship it on monday");
+        assert_eq!(
+            results[0].model_version, None,
+            "cloud engines not reporting their model have none"
+        );
+
+        let count = db
+            .count_search_results(
+                "ship",
+                ContentType::All,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some("WhisperLargeV3Turbo"),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_explain_search_by_engine_and_model_version() {
+        let db = setup_test_db().await;
+        let device = AudioDevice::new("test".to_string(), DeviceType::Input);
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription_with_details(
+            audio_chunk_id,
+            "ship it on friday",
+            0,
+            "WhisperLargeV3Turbo",
+            &device,
+            None,
+            Some(0.0),
+            Some(2.5),
+            &TranscriptionDetails {
+                model_version: Some("openai/whisper-large-v3-turbo".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        db.insert_audio_transcription(
+            audio_chunk_id,
+            "ship it on monday",
+            1,
+            "AzureSpeech",
+            &device,
+            None,
+            Some(2.5),
+            Some(5.0),
+        )
+        .await
+        .unwrap();
+
+        for (engine, model_version) in [
+            (Some("AzureSpeech"), None),
+            (None, Some("openai/whisper-large-v3-turbo")),
+            (Some("AzureSpeech"), Some("openai/whisper-large-v3-turbo")),
+        ] {
+            let results = db
+                .search(
+                    "ship",
+                    ContentType::Audio,
+                    100,
+                    0,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    engine,
+                    model_version,
+                )
+                .await
+                .unwrap();
+            let report = db
+                .explain_search(
+                    "ship",
+                    ContentType::Audio,
+                    100,
+                    0,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    engine,
+                    model_version,
+                )
+                .await
+                .unwrap();
+
+            // explain reports the rows of the search it explains
+            let rows: Vec<(&str, usize)> = report
+                .stages
+                .iter()
+                .map(|s| (s.stage.as_str(), s.rows_returned))
+                .collect();
+            assert_eq!(
+                rows,
+                vec![("audio", results.len()), ("count", results.len())],
+                "engine {:?}, model version {:?}",
+                engine,
+                model_version
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_duplicate_frame() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        db.insert_frame("test_device", None).await.unwrap();
+        let frame_id = db.insert_frame("test_device", None).await.unwrap();
+        let duplicate_id = db
+            .insert_duplicate_frame(frame_id, None, &FrameDetails::default())
+            .await
+            .unwrap();
+        let duplicate_of_duplicate_id = db
+            .insert_duplicate_frame(duplicate_id, None, &FrameDetails::default())
+            .await
+            .unwrap();
+
+        // duplicates show the video frame of the original, and always refer to it
+        let frames: Vec<(i64, i64, Option<i64>)> =
+            sqlx::query_as("SELECT id, offset_index, duplicate_of FROM frames ORDER BY id")
+                .fetch_all(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            frames[1..],
+            [
+                (frame_id, 1, None),
+                (duplicate_id, 1, Some(frame_id)),
+                (duplicate_of_duplicate_id, 1, Some(frame_id)),
+            ]
+        );
+        assert_eq!(
+            db.insert_frame("test_device", None).await.unwrap(),
+            duplicate_of_duplicate_id + 1
+        );
+        let offset_index: i64 =
+            sqlx::query_scalar("SELECT offset_index FROM frames ORDER BY id DESC LIMIT 1")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        assert_eq!(offset_index, 2);
+    }
+
+    #[tokio::test]
+    async fn test_search_by_browser_url() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let github_frame = db.insert_frame("test_device", None).await.unwrap();
+        let editor_frame = db.insert_frame("test_device", None).await.unwrap();
+        db.set_frame_browser_tab(
+            github_frame,
+            &BrowserTab {
+                url: "https://github.com/mediar-ai/screenpipe/pulls".to_string(),
+                title: "Pull requests".to_string(),
+                incognito: false,
+            },
+        )
+        .await
+        .unwrap();
+        for frame_id in [github_frame, editor_frame] {
+            db.insert_ocr_text(
+                frame_id,
+                "screenpipe",
+                "",
+                "",
+                "",
+                Arc::new(OcrEngine::Tesseract),
+                true,
+            )
+            .await
+            .unwrap();
+        }
+
+        let results = db
+            .search(
+                "screenpipe",
+                ContentType::All,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some("GitHub.com"),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        if let SearchResult::OCR(ocr_result) = &results[0] {
+            assert_eq!(ocr_result.frame_id, github_frame);
+            assert_eq!(
+                ocr_result.browser_url.as_deref(),
+                Some("https://github.com/mediar-ai/screenpipe/pulls")
+            );
+        } else {
+            panic!("Expected OCR result");
+        }
+
+        let count = db
+            .count_search_results(
+                "screenpipe",
+                ContentType::OCR,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some("github.com"),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_by_focused_app_and_call() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let zoom_frame = db.insert_frame("test_device", None).await.unwrap();
+        let editor_frame = db.insert_frame("test_device", None).await.unwrap();
+        let zoom = ActiveApp {
+            name: "zoom.us".to_string(),
+            window_title: "Zoom Meeting".to_string(),
+            ..Default::default()
+        };
+        db.set_frame_focus(zoom_frame, Some(&zoom), true)
+            .await
+            .unwrap();
+        db.set_frame_focus(editor_frame, None, false).await.unwrap();
+        // the text of a window on another monitor than the focused app
+        for frame_id in [zoom_frame, editor_frame] {
+            db.insert_ocr_text(
+                frame_id,
+                "quarterly planning",
+                "",
+                "Notes",
+                "Planning",
+                Arc::new(OcrEngine::Tesseract),
+                false,
+            )
+            .await
+            .unwrap();
+        }
+
+        let search = |focused_app_name: Option<&'static str>, in_call: Option<bool>| {
+            db.search(
+                "planning",
+                ContentType::OCR,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                focused_app_name,
+                in_call,
+                None,
+                None,
+                None,
+                None,
+            )
+        };
+        let results = search(Some("zoom"), None).await.unwrap();
+        assert_eq!(results.len(), 1);
+        if let SearchResult::OCR(ocr_result) = &results[0] {
+            assert_eq!(ocr_result.frame_id, zoom_frame);
+            assert_eq!(ocr_result.app_name, "Notes");
+            assert_eq!(ocr_result.focused_app_name.as_deref(), Some("zoom.us"));
+            assert_eq!(
+                ocr_result.focused_window_name.as_deref(),
+                Some("Zoom Meeting")
+            );
+            assert!(ocr_result.in_call);
+        } else {
+            panic!("Expected OCR result");
+        }
+        assert_eq!(search(None, Some(true)).await.unwrap().len(), 1);
+        assert_eq!(search(None, Some(false)).await.unwrap().len(), 1);
+        assert_eq!(search(None, None).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_insert_frame_with_details() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let cursor = CursorOverlay {
+            cursor_x: Some(0.25),
+            cursor_y: Some(0.5),
+            selected_text: Some("quarterly revenue".to_string()),
+            selection: Some(LayoutBox {
+                left: 0.1,
+                top: 0.4,
+                width: 0.3,
+                height: 0.05,
+            }),
+        };
+        let details = FrameDetails {
+            monitor: Some(MonitorData {
+                width: 2560,
+                height: 1440,
+                x: -2560,
+                y: 0,
+                name: "DELL U2720Q".to_string(),
+                is_primary: false,
+            }),
+            active_app: Some(ActiveApp {
+                name: "Google Chrome".to_string(),
+                window_title: "Pull requests".to_string(),
+                ..Default::default()
+            }),
+            in_call: true,
+            browser_tab: Some(BrowserTab {
+                url: "https://github.com/pulls".to_string(),
+                title: "Pull requests".to_string(),
+                incognito: false,
+            }),
+            virtual_desktop: Some(2),
+            cursor: Some(cursor.clone()),
+        };
+        let frame_id = db
+            .insert_frame_with_details("test_device", None, &details)
+            .await
+            .unwrap();
+        // the pointer moved and the window is sensitive now, the monitor is the original's
+        let duplicate_details = FrameDetails {
+            active_app: Some(ActiveApp {
+                name: "Google Chrome".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let duplicate_id = db
+            .insert_duplicate_frame(frame_id, None, &duplicate_details)
+            .await
+            .unwrap();
+
+        type Row = (
+            Option<String>,
+            Option<i64>,
+            Option<i64>,
+            Option<String>,
+            Option<String>,
+            bool,
+            Option<String>,
+            Option<String>,
+            Option<i64>,
+        );
+        let rows: Vec<Row> = sqlx::query_as(
+            "SELECT monitor_name, monitor_width, monitor_x, focused_app_name, focused_window_name, in_call, browser_url, tab_title, virtual_desktop FROM frames ORDER BY id",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            rows,
+            [
+                (
+                    Some("DELL U2720Q".to_string()),
+                    Some(2560),
+                    Some(-2560),
+                    Some("Google Chrome".to_string()),
+                    Some("Pull requests".to_string()),
+                    true,
+                    Some("https://github.com/pulls".to_string()),
+                    Some("Pull requests".to_string()),
+                    Some(2),
+                ),
+                (
+                    Some("DELL U2720Q".to_string()),
+                    Some(2560),
+                    Some(-2560),
+                    Some("Google Chrome".to_string()),
+                    None,
+                    false,
+                    None,
+                    None,
+                    None,
+                ),
+            ]
+        );
+        assert_eq!(db.get_frame_cursor(frame_id).await.unwrap(), Some(cursor));
+        assert_eq!(
+            db.get_frame_cursor(duplicate_id).await.unwrap(),
+            Some(CursorOverlay::default())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_frame_cursor() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let selected_frame = db.insert_frame("test_device", None).await.unwrap();
+        let other_frame = db.insert_frame("test_device", None).await.unwrap();
+        let cursor = CursorOverlay {
+            cursor_x: Some(0.25),
+            cursor_y: Some(0.5),
+            selected_text: Some("quarterly revenue grew 12%".to_string()),
+            selection: Some(LayoutBox {
+                left: 0.1,
+                top: 0.4,
+                width: 0.3,
+                height: 0.05,
+            }),
+        };
+        db.set_frame_cursor(selected_frame, &cursor).await.unwrap();
+        for frame_id in [selected_frame, other_frame] {
+            db.insert_ocr_text(
+                frame_id,
+                "quarterly revenue grew 12% year over year",
+                "",
+                "Preview",
+                "report.pdf",
+                Arc::new(OcrEngine::Tesseract),
+                false,
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            db.get_frame_cursor(selected_frame).await.unwrap(),
+            Some(cursor)
+        );
+        // stored without a cursor
+        assert_eq!(
+            db.get_frame_cursor(other_frame).await.unwrap(),
+            Some(CursorOverlay::default())
+        );
+        assert_eq!(db.get_frame_cursor(other_frame + 1).await.unwrap(), None);
+
+        let results = db
+            .search(
+                "revenue",
+                ContentType::OCR,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some("REVENUE grew"),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        if let SearchResult::OCR(ocr_result) = &results[0] {
+            assert_eq!(ocr_result.frame_id, selected_frame);
+            assert_eq!(
+                ocr_result.selected_text.as_deref(),
+                Some("quarterly revenue grew 12%")
+            );
+        } else {
+            panic!("Expected OCR result");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_by_virtual_desktop() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let first_desktop = db.insert_frame("test_device", None).await.unwrap();
+        let second_desktop = db.insert_frame("test_device", None).await.unwrap();
+        // the os didn't tell
+        let unknown_desktop = db.insert_frame("test_device", None).await.unwrap();
+        db.set_frame_virtual_desktop(first_desktop, 1)
+            .await
+            .unwrap();
+        db.set_frame_virtual_desktop(second_desktop, 2)
+            .await
+            .unwrap();
+        for frame_id in [first_desktop, second_desktop, unknown_desktop] {
+            db.insert_ocr_text(
+                frame_id,
+                "deploy logs",
+                "",
+                "Terminal",
+                "zsh",
+                Arc::new(OcrEngine::Tesseract),
+                false,
+            )
+            .await
+            .unwrap();
+        }
+
+        let search = |virtual_desktop: Option<u32>| {
+            db.search(
+                "deploy",
+                ContentType::All,
+                100,
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                virtual_desktop,
+                None,
+                None,
+                None,
+            )
+        };
+        let results = search(Some(2)).await.unwrap();
+        assert_eq!(results.len(), 1);
+        if let SearchResult::OCR(ocr_result) = &results[0] {
+            assert_eq!(ocr_result.frame_id, second_desktop);
+            assert_eq!(ocr_result.virtual_desktop, Some(2));
+        } else {
+            panic!("Expected OCR result");
+        }
+        assert_eq!(search(None).await.unwrap().len(), 3);
+
+        let count = db
+            .count_search_results(
+                "deploy",
+                ContentType::OCR,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(1),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_search_ui_elements() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let frame_id = db.insert_frame("test_device", None).await.unwrap();
+        let tree = AccessibilityTree {
+            app_name: "Slack".to_string(),
+            window_name: "general - mediar".to_string(),
+            elements: vec![
+                UiElement {
+                    role: "button".to_string(),
+                    label: "Send now".to_string(),
+                    depth: 12,
+                    bounds: Some(Bounds {
+                        x: 1204.0,
+                        y: 860.0,
+                        width: 28.0,
+                        height: 28.0,
+                    }),
+                    ..Default::default()
+                },
+                UiElement {
+                    role: "text field".to_string(),
+                    label: "Message #general".to_string(),
+                    text: "send the deck".to_string(),
+                    depth: 11,
+                    bounds: None,
+                },
+            ],
+        };
+        db.insert_ui_elements(frame_id, &tree).await.unwrap();
+
+        let results = db
+            .search_ui_elements("send", None, None, None, None, 100, 0)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        let buttons = db
+            .search_ui_elements("send", Some("slack"), Some("button"), None, None, 100, 0)
+            .await
+            .unwrap();
+        assert_eq!(buttons.len(), 1);
+        assert_eq!(buttons[0].frame_id, frame_id);
+        assert_eq!(buttons[0].label, "Send now");
+        assert_eq!(buttons[0].window_name, "general - mediar");
+        assert_eq!(buttons[0].x, Some(1204.0));
+
+        let other_app = db
+            .search_ui_elements("", Some("Notion"), None, None, None, 100, 0)
+            .await
+            .unwrap();
+        assert!(other_app.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_search_ocr_lines() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let frame_id = db.insert_frame("test_device", None).await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "File Edit View\nrevenue grew 12%",
+            "",
+            "Numbers",
+            "q3.numbers",
+            Arc::new(OcrEngine::Tesseract),
+            false,
+        )
+        .await
+        .unwrap();
+        let word = |text: &str, line: &str, left: u32, top: u32| -> HashMap<String, String> {
+            [
+                ("block_num", "1".to_string()),
+                ("par_num", "1".to_string()),
+                ("line_num", line.to_string()),
+                ("text", text.to_string()),
+                ("conf", "95".to_string()),
+                ("left", left.to_string()),
+                ("top", top.to_string()),
+                ("width", "80".to_string()),
+                ("height", "20".to_string()),
+            ]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect()
+        };
+        let layout = OcrLayout::from_words(
+            &[
+                word("File", "1", 10, 5),
+                word("Edit", "1", 100, 5),
+                word("revenue", "2", 400, 500),
+                word("grew", "2", 490, 500),
+            ],
+            1000,
+            1000,
+            false,
+        );
+        db.insert_ocr_layout(frame_id, &layout).await.unwrap();
+
+        let stored = db.get_frame_layout(frame_id).await.unwrap().unwrap();
+        assert_eq!(stored.text(), layout.text());
+        assert_eq!(stored.lines().count(), 2);
+        assert!(db.get_frame_layout(frame_id + 1).await.unwrap().is_none());
+
+        let top_of_window = LayoutBox {
+            left: 0.0,
+            top: 0.0,
+            width: 1.0,
+            height: 1.0 / 6.0,
+        };
+        let lines = db
+            .search_ocr_lines("", Some(top_of_window), None, None, None, 100, 0)
+            .await
+            .unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text, "File Edit");
+        assert_eq!(lines[0].app_name, "Numbers");
+
+        let revenue = db
+            .search_ocr_lines("revenue", None, Some("numbers"), None, None, 100, 0)
+            .await
+            .unwrap();
+        assert_eq!(revenue.len(), 1);
+        assert_eq!(revenue[0].frame_id, frame_id);
+        assert!((revenue[0].y - 0.5).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_get_ocr_structures() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let frame_id = db.insert_frame("test_device", None).await.unwrap();
+        let structures = vec![
+            OcrStructure {
+                bbox: LayoutBox {
+                    left: 0.1,
+                    top: 0.1,
+                    width: 0.5,
+                    height: 0.1,
+                },
+                content: StructuredContent::Table {
+                    rows: vec![
+                        vec!["region".to_string(), "q3".to_string()],
+                        vec!["emea".to_string(), "1,530".to_string()],
+                    ],
+                },
+            },
+            OcrStructure {
+                bbox: LayoutBox {
+                    left: 0.1,
+                    top: 0.5,
+                    width: 0.3,
+                    height: 0.1,
+                },
+                content: StructuredContent::Code {
+                    text: "if ready {\n    run();\n}".to_string(),
+                },
+            },
+        ];
+        db.insert_ocr_structures(frame_id, &structures)
+            .await
+            .unwrap();
+
+        let stored = db.get_frame_structures(frame_id).await.unwrap();
+        assert_eq!(stored, structures);
+        assert_eq!(stored[0].text(), "region\tq3\nemea\t1,530");
+        assert!(db
+            .get_frame_structures(frame_id + 1)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_frames_by_embedding() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let chart_frame = db.insert_frame("test_device", None).await.unwrap();
+        db.insert_ocr_text(
+            chart_frame,
+            "revenue by region",
+            "",
+            "Numbers",
+            "q3.numbers",
+            Arc::new(OcrEngine::Tesseract),
+            false,
+        )
+        .await
+        .unwrap();
+        // no text was read in the dialog
+        let dialog_frame = db.insert_frame("test_device", None).await.unwrap();
+
+        let axis = |i: usize| {
+            let mut embedding = vec![0.0f32; 512];
+            embedding[i] = 1.0;
+            embedding
+        };
+        db.insert_frame_embedding(chart_frame, &axis(0))
+            .await
+            .unwrap();
+        db.insert_frame_embedding(dialog_frame, &axis(1))
+            .await
+            .unwrap();
+
+        let mut query = axis(1);
+        query[0] = 0.5;
+        let results = db
+            .search_frames_by_embedding(&query, None, None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].frame_id, dialog_frame);
+        assert_eq!(results[0].ocr_text, None);
+        assert_eq!(results[1].frame_id, chart_frame);
+        assert_eq!(results[1].app_name.as_deref(), Some("Numbers"));
+
+        let close = db
+            .search_frames_by_embedding(&query, Some(0.5), None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(close.len(), 1);
+        assert_eq!(close[0].frame_id, dialog_frame);
+    }
+
+    #[tokio::test]
+    async fn test_text_embeddings() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let frame_id = db.insert_frame("test_device", None).await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "pricing page",
+            "",
+            "Arc",
+            "pricing",
+            Arc::new(OcrEngine::Tesseract),
+            false,
+        )
+        .await
+        .unwrap();
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        let device = AudioDevice::new("test".to_string(), DeviceType::Input);
+        let mut transcriptions = Vec::new();
+        for (offset, text) in ["charge per seat", "lunch at noon", "see you"]
+            .into_iter()
+            .enumerate()
+        {
+            transcriptions.push(
+                db.insert_audio_transcription(
+                    audio_chunk_id,
+                    text,
+                    offset as i64,
+                    "",
+                    &device,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap(),
+            );
+        }
+
+        assert_eq!(
+            db.ocr_text_without_embedding(10).await.unwrap(),
+            vec![(frame_id, "pricing page".to_string())]
+        );
+        let pending = db.transcriptions_without_embedding(10).await.unwrap();
+        assert_eq!(
+            pending.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            transcriptions.iter().rev().copied().collect::<Vec<_>>()
+        );
+
+        let axis = |i: usize, length: usize| {
+            let mut embedding = vec![0.0f32; length];
+            embedding[i] = 1.0;
+            embedding
+        };
+        db.insert_embeddings(frame_id, serde_json::to_string(&axis(0, 8)).unwrap())
+            .await
+            .unwrap();
+        db.insert_transcription_embedding(transcriptions[0], &axis(1, 8))
+            .await
+            .unwrap();
+        db.insert_transcription_embedding(transcriptions[1], &axis(2, 8))
+            .await
+            .unwrap();
+        // of another model
+        db.insert_transcription_embedding(transcriptions[2], &axis(1, 4))
+            .await
+            .unwrap();
+        assert!(db.ocr_text_without_embedding(10).await.unwrap().is_empty());
+        assert!(db
+            .transcriptions_without_embedding(10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let mut query = axis(1, 8);
+        query[0] = 0.5;
+        let audio = db
+            .search_transcription_embeddings(&query, None, None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(audio.len(), 2);
+        assert_eq!(audio[0].0.transcription, "charge per seat");
+        assert_eq!(audio[1].0.transcription, "lunch at noon");
+        assert!(audio[0].1 < audio[1].1);
+        let close = db
+            .search_transcription_embeddings(&query, Some(0.5), None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(close.len(), 1);
+
+        let ocr = db
+            .search_ocr_text_embeddings(&query, None, None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(ocr.len(), 1);
+        assert_eq!(ocr[0].0.frame_id, frame_id);
+        assert_eq!(ocr[0].0.ocr_text, "pricing page");
+
+        // a corrected transcription is embedded again
+        db.set_audio_transcription_text(transcriptions[0], "charge per workspace")
+            .await
+            .unwrap();
+        assert_eq!(
+            db.transcriptions_without_embedding(10).await.unwrap(),
+            vec![(transcriptions[0], "charge per workspace".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capture_pauses() {
+        let db = setup_test_db().await;
+        let start = Utc::now() - chrono::Duration::hours(2);
+        let idle = db.insert_capture_pause("idle", start).await.unwrap();
+        db.end_capture_pauses(Some(idle), start + chrono::Duration::minutes(30))
+            .await
+            .unwrap();
+        let locked = db
+            .insert_capture_pause("locked", start + chrono::Duration::hours(1))
+            .await
+            .unwrap();
+
+        let pauses = db.list_capture_pauses(None, None, 10, 0).await.unwrap();
+        assert_eq!(pauses.len(), 2);
+        assert_eq!(pauses[0].id, locked);
+        assert_eq!(pauses[0].reason, "locked");
+        assert!(pauses[0].end_time.is_none());
+        assert_eq!(pauses[1].reason, "idle");
+        assert!(pauses[1].end_time.is_some());
+
+        // the pause going on overlaps any range after its start
+        let recent = db
+            .list_capture_pauses(Some(start + chrono::Duration::minutes(45)), None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].id, locked);
+
+        // ends the pauses left open, the other one keeps its end
+        assert_eq!(db.end_capture_pauses(None, Utc::now()).await.unwrap(), 1);
+        let pauses = db.list_capture_pauses(None, None, 10, 0).await.unwrap();
+        assert!(pauses.iter().all(|pause| pause.end_time.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_meeting_sessions() {
+        let db = setup_test_db().await;
+        let start = Utc::now() - chrono::Duration::hours(3);
+        let standup = db
+            .insert_meeting_session("zoom.us", "Zoom Meeting", start)
+            .await
+            .unwrap();
+        db.end_meeting_sessions(Some(standup), start + chrono::Duration::minutes(15))
+            .await
+            .unwrap();
+        let review = db
+            .insert_meeting_session(
+                "Google Chrome",
+                "Meet - abc-defg-hij",
+                start + chrono::Duration::hours(2),
+            )
+            .await
+            .unwrap();
+
+        let meetings = db.list_meeting_sessions(None, None, 10, 0).await.unwrap();
+        assert_eq!(meetings.len(), 2);
+        assert_eq!(meetings[0].id, review);
+        assert!(meetings[0].end_time.is_none());
+        assert_eq!(meetings[1].title, "Zoom Meeting");
+
+        let after_standup = db
+            .list_meeting_sessions(Some(start + chrono::Duration::hours(1)), None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(after_standup.len(), 1);
+        assert_eq!(after_standup[0].id, review);
+
+        let standup = db.get_meeting_session(standup).await.unwrap().unwrap();
+        assert_eq!(
+            standup.end_time,
+            Some(start + chrono::Duration::minutes(15))
+        );
+        assert!(db.get_meeting_session(review + 1).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_aligned_frames() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let said_at = Utc::now() - chrono::Duration::minutes(5);
+        let mut frames = Vec::new();
+        for secs in [-90, -20, 1, 3, 30] {
+            frames.push(
+                db.insert_frame(
+                    "test_device",
+                    Some(said_at + chrono::Duration::seconds(secs)),
+                )
+                .await
+                .unwrap(),
+            );
+        }
+
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        let id = db
+            .insert_audio_transcription_with_details(
+                audio_chunk_id,
+                "this chart shows the revenue",
+                0,
+                "",
+                &AudioDevice::new("test".to_string(), DeviceType::Input),
+                None,
+                Some(10.0),
+                Some(14.0),
+                &TranscriptionDetails {
+                    captured_at: Some(said_at),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let segment = db.get_transcript_segment(id).await.unwrap().unwrap();
+        assert_eq!(segment.timestamp, said_at);
+        assert_eq!(segment.end(), said_at + chrono::Duration::seconds(4));
+
+        let aligned = db
+            .get_aligned_frames(
+                segment.timestamp,
+                segment.end(),
+                segment.timestamp - chrono::Duration::minutes(1),
+                100,
+            )
+            .await
+            .unwrap();
+        // the frame on screen when it started, and the ones during it
+        let ids: Vec<i64> = aligned.iter().map(|frame| frame.frame_id).collect();
+        assert_eq!(ids, vec![frames[1], frames[2], frames[3]]);
+        assert_eq!(aligned[0].file_path, "test_video.mp4");
+        assert!(db.get_transcript_segment(id + 1).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_input_events_are_linked_to_frames() {
+        let db = setup_test_db().await;
+        db.insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let start = Utc::now() - chrono::Duration::minutes(1);
+        let frame_id = db.insert_frame("test_device", None).await.unwrap();
+        let click = InputEvent {
+            timestamp: std::time::SystemTime::now(),
+            activity: InputActivity::Click {
+                x: 1218.0,
+                y: 874.0,
+                button: "left".to_string(),
+                element: Some(UiElement {
+                    role: "button".to_string(),
+                    label: "Send now".to_string(),
+                    ..Default::default()
+                }),
+            },
+            app_name: Some("Slack".to_string()),
+            window_name: Some("general - mediar".to_string()),
+        };
+        let keys = InputEvent {
+            activity: InputActivity::Keys { count: 42 },
+            ..click.clone()
+        };
+        db.insert_input_event(&click).await.unwrap();
+        db.insert_input_event(&keys).await.unwrap();
+
+        let clicks = db
+            .search_input_events(Some("click"), Some("slack"), None, None, 100, 0)
+            .await
+            .unwrap();
+        assert_eq!(clicks.len(), 1);
+        assert_eq!(clicks[0].frame_id, Some(frame_id));
+        assert_eq!(clicks[0].element_label.as_deref(), Some("Send now"));
+        assert_eq!(clicks[0].button.as_deref(), Some("left"));
+
+        let activity = db
+            .input_activity(start, Utc::now() + chrono::Duration::minutes(1))
+            .await
+            .unwrap();
+        assert_eq!(activity.iter().map(|bucket| bucket.clicks).sum::<i64>(), 1);
+        assert_eq!(
+            activity.iter().map(|bucket| bucket.keypresses).sum::<i64>(),
+            42
+        );
+        assert_eq!(activity.iter().map(|bucket| bucket.scrolls).sum::<i64>(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_video_chunks_to_archive() {
+        let db = setup_test_db().await;
+        let old_chunk = db
+            .insert_video_chunk("old_video.mp4", "test_device")
+            .await
+            .unwrap();
+        db.insert_frame("test_device", Some(Utc::now() - chrono::Duration::days(40)))
+            .await
+            .unwrap();
+        db.insert_video_chunk("recent_video.mp4", "test_device")
+            .await
+            .unwrap();
+        db.insert_frame("test_device", None).await.unwrap();
+
+        let before = Utc::now() - chrono::Duration::days(30);
+        let chunks = db.video_chunks_to_archive(before, "av1", 10).await.unwrap();
+        assert_eq!(chunks, vec![(old_chunk, "old_video.mp4".to_string())]);
+
+        db.set_video_chunk_codec(old_chunk, "av1").await.unwrap();
+        assert!(db
+            .video_chunks_to_archive(before, "av1", 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    async fn search_all(db: &DatabaseManager, query: &str) -> Vec<SearchResult> {
+        db.search(
+            query,
+            ContentType::All,
+            100,
+            0,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_search_query_syntax() {
+        let db = setup_test_db().await;
+        let _ = db
+            .insert_video_chunk("test_video.mp4", "test_device")
+            .await
+            .unwrap();
+        let frame_id = db.insert_frame("test_device", None).await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "standup notes",
+            "",
+            "app",
+            "Slack",
+            Arc::new(OcrEngine::Tesseract),
+            false,
+        )
+        .await
+        .unwrap();
+        let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(
+            audio_chunk_id,
+            "standup at ten",
+            0,
+            "",
+            &AudioDevice::new("test".to_string(), DeviceType::Output),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(search_all(&db, "standup").await.len(), 2);
+        assert_eq!(search_all(&db, "stand*").await.len(), 2);
+        assert_eq!(search_all(&db, "notes OR ten").await.len(), 2);
+
+        let window = search_all(&db, "window:slack standup").await;
+        assert_eq!(window.len(), 1);
+        assert!(matches!(window[0], SearchResult::OCR(_)));
+
+        let transcript = search_all(&db, "transcript:standup").await;
+        assert_eq!(transcript.len(), 1);
+        assert!(matches!(transcript[0], SearchResult::Audio(_)));
+        assert_eq!(search_all(&db, "\"standup at\"").await.len(), 1);
+
+        // the exclusion only applies to transcripts
+        let excluded = search_all(&db, "standup -transcript:ten").await;
+        assert_eq!(excluded.len(), 1);
+        assert!(matches!(excluded[0], SearchResult::OCR(_)));
+
+        // fts syntax errors are searched as text
+        assert_eq!(search_all(&db, "standup ( AND \"notes").await.len(), 1);
+
+        let count = db
+            .count_search_results(
+                "transcript:standup",
+                ContentType::All,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
 }
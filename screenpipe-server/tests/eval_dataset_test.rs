@@ -0,0 +1,45 @@
+use screenpipe_server::eval_dataset::EvalDataset;
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn test_eval_dataset_roundtrip() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("chunk.mp4");
+    tokio::fs::write(&source, b"fake audio").await.unwrap();
+    let frame = dir.path().join("frame.jpg");
+    tokio::fs::write(&frame, b"fake frame").await.unwrap();
+
+    let dataset = EvalDataset::new(dir.path());
+    assert!(dataset.audio_samples().await.unwrap().is_empty());
+
+    let audio = dataset
+        .add_audio_sample(source.to_str().unwrap(), 7, "helo wrld", "hello world")
+        .await
+        .unwrap();
+    dataset
+        .add_ocr_sample(frame.to_str().unwrap(), 3, "Scr33npipe", "Screenpipe")
+        .await
+        .unwrap();
+
+    let audio_samples = dataset.audio_samples().await.unwrap();
+    assert_eq!(audio_samples.len(), 1);
+    assert_eq!(audio_samples[0].reference, "hello world");
+    assert_eq!(audio_samples[0].hypothesis, "helo wrld");
+    assert!(dataset.root().join(&audio.audio_path).exists());
+
+    let ocr_samples = dataset.ocr_samples().await.unwrap();
+    assert_eq!(ocr_samples.len(), 1);
+    assert_eq!(ocr_samples[0].frame_id, 3);
+    assert!(dataset.root().join(&ocr_samples[0].frame_path).exists());
+}
+
+#[tokio::test]
+async fn test_eval_dataset_rejects_missing_source() {
+    let dir = tempdir().unwrap();
+    let dataset = EvalDataset::new(dir.path());
+    let result = dataset
+        .add_audio_sample("/nonexistent/chunk.mp4", 1, "", "hello")
+        .await;
+    assert!(result.is_err());
+    assert!(dataset.audio_samples().await.unwrap().is_empty());
+}
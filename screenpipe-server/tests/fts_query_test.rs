@@ -0,0 +1,114 @@
+use screenpipe_server::fts_query::{FtsQuery, FtsTable};
+
+fn render(query: &str, table: FtsTable) -> Option<String> {
+    FtsQuery::parse(query).for_table(table)
+}
+
+fn ocr(query: &str) -> Option<String> {
+    render(query, FtsTable::Ocr)
+}
+
+#[test]
+fn test_words_and_phrases() {
+    assert_eq!(
+        ocr("budget review").as_deref(),
+        Some("\"budget\" AND \"review\"")
+    );
+    assert_eq!(
+        ocr("\"quarterly budget\" review").as_deref(),
+        Some("\"quarterly budget\" AND \"review\"")
+    );
+    assert_eq!(ocr("").as_deref(), Some(""));
+    assert_eq!(ocr("   ").as_deref(), Some(""));
+}
+
+#[test]
+fn test_boolean_operators() {
+    assert_eq!(
+        ocr("budget OR forecast").as_deref(),
+        Some("\"budget\" OR \"forecast\"")
+    );
+    assert_eq!(
+        ocr("(budget OR forecast) AND 2024").as_deref(),
+        Some("(\"budget\" OR \"forecast\") AND \"2024\"")
+    );
+    assert_eq!(
+        ocr("budget NOT draft").as_deref(),
+        Some("\"budget\" NOT \"draft\"")
+    );
+    assert_eq!(
+        ocr("budget -draft -old").as_deref(),
+        Some("\"budget\" NOT (\"draft\" OR \"old\")")
+    );
+    // lowercase words aren't operators
+    assert_eq!(
+        ocr("black or white").as_deref(),
+        Some("\"black\" AND \"or\" AND \"white\"")
+    );
+    // nothing to leave matches out of
+    assert_eq!(ocr("-draft").as_deref(), Some(""));
+}
+
+#[test]
+fn test_prefixes() {
+    assert_eq!(ocr("budg*").as_deref(), Some("\"budg\"*"));
+    assert_eq!(
+        ocr("\"quarterly bud\"*").as_deref(),
+        Some("\"quarterly bud\"")
+    );
+    assert_eq!(ocr("* budget").as_deref(), Some("\"budget\""));
+}
+
+#[test]
+fn test_fields() {
+    assert_eq!(
+        ocr("window:slack standup").as_deref(),
+        Some("window_name : \"slack\" AND \"standup\"")
+    );
+    assert_eq!(
+        render("window:slack standup", FtsTable::Ui).as_deref(),
+        Some("window : \"slack\" AND \"standup\"")
+    );
+    assert_eq!(
+        ocr("title:(slack OR teams)").as_deref(),
+        Some("window_name : \"slack\" OR window_name : \"teams\"")
+    );
+    // audio has no window, nothing in it can match
+    assert_eq!(render("window:slack standup", FtsTable::Audio), None);
+    assert_eq!(
+        render("transcript:\"sounds good\"", FtsTable::Audio).as_deref(),
+        Some("transcription : \"sounds good\"")
+    );
+    assert_eq!(ocr("transcript:\"sounds good\""), None);
+    // a field only one table has in an OR leaves the other branches to the others
+    assert_eq!(
+        ocr("transcript:invoice OR text:invoice").as_deref(),
+        Some("text : \"invoice\"")
+    );
+    // excluding a field a table doesn't have excludes nothing from it
+    assert_eq!(
+        ocr("invoice -transcript:draft").as_deref(),
+        Some("\"invoice\"")
+    );
+    // unknown fields are text
+    assert_eq!(
+        ocr("http://example.com").as_deref(),
+        Some("\"http://example.com\"")
+    );
+}
+
+#[test]
+fn test_any_text_is_safe() {
+    assert_eq!(
+        ocr("c++ \"unterminated").as_deref(),
+        Some("\"c++\" AND \"unterminated\"")
+    );
+    assert_eq!(ocr("foo.bar").as_deref(), Some("\"foo.bar\""));
+    assert_eq!(ocr("say \"\"hi").as_deref(), Some("\"say\" AND \"hi\""));
+    assert_eq!(ocr("a)b(c").as_deref(), Some("\"a\" AND (\"b\" AND \"c\")"));
+    assert_eq!(ocr("OR AND NOT ( )").as_deref(), Some(""));
+    assert_eq!(
+        ocr("col:umn ^start").as_deref(),
+        Some("\"col:umn\" AND \"^start\"")
+    );
+}
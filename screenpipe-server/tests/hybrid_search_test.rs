@@ -0,0 +1,33 @@
+use screenpipe_server::hybrid_search::{reciprocal_rank_fusion, RRF_K};
+
+#[test]
+fn test_reciprocal_rank_fusion() {
+    let fts = vec!["invoice", "receipt", "refund"];
+    let semantic = vec!["refund", "invoice", "chargeback"];
+    let fused = reciprocal_rank_fusion(&[fts, semantic], RRF_K);
+
+    let order: Vec<&str> = fused.iter().map(|(key, _)| *key).collect();
+    // in both rankings first, then the ones ranked the highest
+    assert_eq!(order, vec!["invoice", "refund", "receipt", "chargeback"]);
+    let score = |rank: f64| 1.0 / (RRF_K + rank);
+    assert!((fused[0].1 - (score(1.0) + score(2.0))).abs() < 1e-12);
+    assert!((fused[1].1 - (score(3.0) + score(1.0))).abs() < 1e-12);
+    assert!((fused[2].1 - score(2.0)).abs() < 1e-12);
+}
+
+#[test]
+fn test_reciprocal_rank_fusion_ties_and_duplicates() {
+    // items ranked the same keep the order they first appear in
+    let fused = reciprocal_rank_fusion(&[vec![1, 2], vec![3, 4]], RRF_K);
+    assert_eq!(
+        fused.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+        vec![1, 3, 2, 4]
+    );
+
+    // an item twice in a ranking counts at its best rank
+    let fused = reciprocal_rank_fusion(&[vec![7, 8, 7]], RRF_K);
+    assert_eq!(fused.len(), 2);
+    assert!((fused[0].1 - 1.0 / (RRF_K + 1.0)).abs() < 1e-12);
+
+    assert!(reciprocal_rank_fusion::<i64>(&[], RRF_K).is_empty());
+}
@@ -0,0 +1,38 @@
+use screenpipe_server::db_types::Speaker;
+use screenpipe_server::now::{
+    current_context, last_sentence, update_focused_window, update_transcript,
+};
+
+#[test]
+fn test_last_sentence_picks_trailing_sentence() {
+    assert_eq!(
+        last_sentence("hello there. how are you doing today?"),
+        Some("how are you doing today?".to_string())
+    );
+    assert_eq!(
+        last_sentence("no punctuation at all"),
+        Some("no punctuation at all".to_string())
+    );
+    assert_eq!(last_sentence("   "), None);
+}
+
+#[test]
+fn test_current_context_reflects_updates() {
+    update_focused_window("Code", "now.rs - screenpipe");
+    let speaker = Speaker {
+        id: 42,
+        name: "louis".to_string(),
+        metadata: String::new(),
+    };
+    update_transcript(
+        "MacBook Pro Microphone (input)",
+        "first. second one",
+        Some(&speaker),
+    );
+
+    let ctx = current_context();
+    assert_eq!(ctx.app_name.as_deref(), Some("Code"));
+    assert_eq!(ctx.window_name.as_deref(), Some("now.rs - screenpipe"));
+    assert_eq!(ctx.last_transcript.as_deref(), Some("second one"));
+    assert!(ctx.active_speakers.iter().any(|s| s.id == 42));
+}
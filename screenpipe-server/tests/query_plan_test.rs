@@ -0,0 +1,89 @@
+use screenpipe_server::db_types::ContentType;
+use screenpipe_server::query_plan::{parse_plan, suggestions, SearchShape, StageReport, TableScan};
+use screenpipe_server::DatabaseManager;
+
+#[test]
+fn test_parse_plan() {
+    let plan = vec![
+        "SCAN ocr_text_fts VIRTUAL TABLE INDEX 0:M1".to_string(),
+        "SEARCH ocr_text USING INDEX idx_ocr_text_frame_id (frame_id=?)".to_string(),
+        "SEARCH frames USING INTEGER PRIMARY KEY (rowid=?)".to_string(),
+        "SCAN video_chunks".to_string(),
+        "SCAN json_each VIRTUAL TABLE INDEX 1:".to_string(),
+        "USE TEMP B-TREE FOR ORDER BY".to_string(),
+    ];
+    let (indexes, scanned) = parse_plan(&plan);
+    assert_eq!(
+        indexes,
+        vec![
+            "ocr_text_fts (fts)",
+            "idx_ocr_text_frame_id",
+            "frames (primary key)"
+        ]
+    );
+    assert_eq!(scanned, vec!["video_chunks"]);
+}
+
+#[test]
+fn test_suggestions_for_unfiltered_full_scan() {
+    let stage = StageReport {
+        stage: "ocr".to_string(),
+        elapsed_ms: 900.0,
+        rows_returned: 20,
+        plan: vec!["SCAN ocr_text".to_string()],
+        indexes_used: vec![],
+        full_scans: vec![TableScan {
+            table: "ocr_text".to_string(),
+            rows: 2_000_000,
+        }],
+    };
+    let result = suggestions(&SearchShape::default(), &[stage]);
+    assert!(result.iter().any(|s| s.contains("text query")));
+    assert!(result.iter().any(|s| s.contains("start_time/end_time")));
+    assert!(result.iter().any(|s| s.contains("ocr stage took")));
+
+    let narrowed = SearchShape {
+        has_text_query: true,
+        has_time_range: true,
+        ..Default::default()
+    };
+    assert!(suggestions(&narrowed, &[]).is_empty());
+}
+
+#[tokio::test]
+async fn test_explain_search() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let report = db
+        .explain_search(
+            "hello",
+            ContentType::All,
+            20,
+            0,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+    let stages: Vec<&str> = report.stages.iter().map(|s| s.stage.as_str()).collect();
+    assert_eq!(stages, vec!["ocr", "audio", "ui", "count"]);
+    assert!(report.stages[0]
+        .indexes_used
+        .iter()
+        .any(|i| i == "ocr_text_fts (fts)"));
+    assert!(report.stages.iter().all(|s| s.rows_returned == 0));
+}
@@ -0,0 +1,192 @@
+use screenpipe_server::fts_query::FtsTable;
+use screenpipe_server::search_analyzer::{QueryAnalyzer, SearchAnalyzer};
+use screenpipe_server::{DatabaseManager, Language};
+use screenpipe_vision::OcrEngine;
+use std::sync::Arc;
+
+#[test]
+fn test_analyzer_for_languages() {
+    assert_eq!(SearchAnalyzer::for_languages(&[]), SearchAnalyzer::Folded);
+    assert_eq!(
+        SearchAnalyzer::for_languages(&[Language::French, Language::English]),
+        SearchAnalyzer::Stemmed
+    );
+    assert_eq!(
+        SearchAnalyzer::for_languages(&[Language::English, Language::Japanese]),
+        SearchAnalyzer::Cjk
+    );
+}
+
+#[test]
+fn test_stemmed_query_rewrite() {
+    let analyzer = QueryAnalyzer::new(SearchAnalyzer::Stemmed, vec![Language::English]);
+    let rewrite = |query: &str| analyzer.parse_query(query).for_table(FtsTable::Ocr);
+    assert_eq!(rewrite("the meetings").as_deref(), Some("\"meet\"*"));
+    // phrases are searched as typed
+    assert_eq!(
+        rewrite("\"the meetings\"").as_deref(),
+        Some("\"the meetings\"")
+    );
+    // only stop words, keep them
+    assert_eq!(rewrite("the").as_deref(), Some("\"the\""));
+    // operators and fields survive stemming
+    assert_eq!(
+        rewrite("meetings -window:calls").as_deref(),
+        Some("\"meet\"* NOT window_name : \"call\"*")
+    );
+}
+
+#[test]
+fn test_basic_query_untouched() {
+    let analyzer = QueryAnalyzer::default();
+    assert_eq!(
+        analyzer
+            .parse_query("réunion")
+            .for_table(FtsTable::Ocr)
+            .as_deref(),
+        Some("\"réunion\"")
+    );
+}
+
+#[test]
+fn test_cjk_query_is_a_phrase() {
+    let analyzer = QueryAnalyzer::new(SearchAnalyzer::Cjk, vec![Language::Japanese]);
+    assert_eq!(
+        analyzer
+            .parse_query("会議 資料")
+            .for_table(FtsTable::Audio)
+            .as_deref(),
+        Some("\"会議 資料\"")
+    );
+}
+
+async fn count_fts_matches(db: &DatabaseManager, query: &str) -> i64 {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM ocr_text_fts WHERE ocr_text_fts MATCH ?1")
+        .bind(query)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_rebuild_search_index_folds_diacritics() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let _ = db
+        .insert_video_chunk("test_video.mp4", "test_device")
+        .await
+        .unwrap();
+    let frame_id = db.insert_frame("test_device", None).await.unwrap();
+    db.insert_ocr_text(
+        frame_id,
+        "reunion budget",
+        "",
+        "",
+        "",
+        Arc::new(OcrEngine::Tesseract),
+        false,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(count_fts_matches(&db, "réunion").await, 0);
+    assert!(db
+        .rebuild_search_index(SearchAnalyzer::Folded)
+        .await
+        .unwrap());
+    assert_eq!(count_fts_matches(&db, "réunion").await, 1);
+    assert_eq!(
+        db.get_index_analyzer().await.unwrap(),
+        SearchAnalyzer::Folded
+    );
+    // same analyzer, nothing to do
+    assert!(!db
+        .rebuild_search_index(SearchAnalyzer::Folded)
+        .await
+        .unwrap());
+}
+
+#[test]
+fn test_short_cjk_query_is_a_substring() {
+    let analyzer = QueryAnalyzer::new(
+        SearchAnalyzer::Cjk,
+        vec![Language::English, Language::Japanese],
+    );
+    assert_eq!(analyzer.substring_query(" 東京 ").as_deref(), Some("東京"));
+    assert_eq!(analyzer.substring_query("AI").as_deref(), Some("AI"));
+    // long enough for the trigram index
+    assert_eq!(analyzer.substring_query("会議室"), None);
+    assert_eq!(analyzer.substring_query(""), None);
+    // other analyzers index words, short ones included
+    let folded = QueryAnalyzer::new(SearchAnalyzer::Folded, vec![Language::English]);
+    assert_eq!(folded.substring_query("AI"), None);
+}
+
+#[tokio::test]
+async fn test_search_short_cjk_words() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let _ = db
+        .insert_video_chunk("test_video.mp4", "test_device")
+        .await
+        .unwrap();
+    for text in ["東京で会議", "AI roadmap"] {
+        let frame_id = db.insert_frame("test_device", None).await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            text,
+            "",
+            "",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+            false,
+        )
+        .await
+        .unwrap();
+    }
+    db.rebuild_search_index(SearchAnalyzer::Cjk).await.unwrap();
+    db.set_query_analyzer(QueryAnalyzer::new(
+        SearchAnalyzer::Cjk,
+        vec![Language::English, Language::Japanese],
+    ));
+
+    // trigrams never match 2 characters
+    assert_eq!(count_fts_matches(&db, "\"東京\"").await, 0);
+    for (query, expected) in [
+        ("東京", "東京で会議"),
+        ("会議", "東京で会議"),
+        ("AI", "AI roadmap"),
+    ] {
+        let results = db
+            .search_ocr(
+                query, 10, 0, None, None, None, None, None, None, None, None, None, None, None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1, "{}", query);
+        assert_eq!(results[0].ocr_text, expected);
+    }
+    let count = db
+        .count_search_results(
+            "東京",
+            screenpipe_server::db_types::ContentType::OCR,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
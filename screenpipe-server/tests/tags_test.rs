@@ -39,6 +39,8 @@ async fn setup_test_app() -> (Router, Arc<AppState>) {
         frame_image_cache: Some(Arc::new(Mutex::new(LruCache::new(
             NonZeroUsize::new(100).unwrap(),
         )))),
+        eval_dataset: None,
+        screenshot_options: None,
     });
 
     let app = create_router().with_state(app_state.clone());
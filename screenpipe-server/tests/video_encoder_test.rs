@@ -0,0 +1,82 @@
+use screenpipe_server::video_encoder::{
+    current, EncoderBackend, EncoderPreference, VideoCodec, VideoEncoder,
+};
+
+fn arg_after<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+#[test]
+fn test_software_hevc_keeps_the_previous_encoding() {
+    let args = VideoEncoder::default().ffmpeg_args(0.5);
+    assert_eq!(arg_after(&args, "-vcodec"), Some("libx265"));
+    assert_eq!(arg_after(&args, "-tag:v"), Some("hvc1"));
+    assert_eq!(arg_after(&args, "-crf"), Some("23"));
+    assert_eq!(arg_after(&args, "-pix_fmt"), Some("yuv420p"));
+    assert_eq!(arg_after(&args, "-bf"), None);
+}
+
+#[test]
+fn test_hardware_encoders_have_no_b_frames_and_bounded_gops() {
+    for backend in [
+        EncoderBackend::VideoToolbox,
+        EncoderBackend::Nvenc,
+        EncoderBackend::QuickSync,
+    ] {
+        let args = VideoEncoder::new(VideoCodec::H264, backend).ffmpeg_args(0.5);
+        assert_eq!(arg_after(&args, "-bf"), Some("0"), "{:?}", backend);
+        assert_eq!(arg_after(&args, "-g"), Some("5"), "{:?}", backend);
+        assert_eq!(arg_after(&args, "-tag:v"), None, "{:?}", backend);
+        assert_eq!(
+            args.last().map(String::as_str),
+            Some(match backend {
+                EncoderBackend::QuickSync => "nv12",
+                _ => "yuv420p",
+            })
+        );
+    }
+}
+
+#[test]
+fn test_av1_encoders() {
+    let args = VideoEncoder::new(VideoCodec::Av1, EncoderBackend::Software).ffmpeg_args(0.5);
+    assert_eq!(arg_after(&args, "-vcodec"), Some("libsvtav1"));
+    assert_eq!(arg_after(&args, "-crf"), Some("35"));
+    assert_eq!(arg_after(&args, "-tag:v"), None);
+    assert!(!VideoEncoder::new(VideoCodec::Av1, EncoderBackend::VideoToolbox).exists());
+    assert_eq!(
+        VideoEncoder::new(VideoCodec::Av1, EncoderBackend::Nvenc).to_string(),
+        "av1_nvenc"
+    );
+}
+
+#[test]
+fn test_encoder_names() {
+    assert_eq!(
+        VideoEncoder::new(VideoCodec::Hevc, EncoderBackend::VideoToolbox).to_string(),
+        "hevc_videotoolbox"
+    );
+    assert_eq!(
+        VideoEncoder::new(VideoCodec::H264, EncoderBackend::Nvenc).to_string(),
+        "h264_nvenc"
+    );
+    assert_eq!(
+        VideoEncoder::new(VideoCodec::H264, EncoderBackend::Software).to_string(),
+        "libx264"
+    );
+}
+
+#[tokio::test]
+async fn test_software_encoder_is_used_as_asked() {
+    assert_eq!(current(), VideoEncoder::default());
+    let encoder = screenpipe_server::video_encoder::init(
+        VideoCodec::H264,
+        EncoderPreference::Backend(EncoderBackend::Software),
+    )
+    .await;
+    assert_eq!(encoder.backend, EncoderBackend::Software);
+    assert_eq!(current(), encoder);
+}
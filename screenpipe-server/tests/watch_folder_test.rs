@@ -0,0 +1,64 @@
+use screenpipe_server::watch_folder::{FolderWatcher, IngestKind, WatchFolder};
+use screenpipe_server::DatabaseManager;
+use screenpipe_vision::OcrEngine;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::tempdir;
+
+#[test]
+fn test_parse_watch_folder() {
+    let folder: WatchFolder = "OBS=/home/me/Videos/OBS".parse().unwrap();
+    assert_eq!(folder.source, "OBS");
+    assert_eq!(folder.path, PathBuf::from("/home/me/Videos/OBS"));
+
+    let folder: WatchFolder = "/home/me/Desktop".parse().unwrap();
+    assert_eq!(folder.source, "Desktop");
+
+    assert!("OBS=".parse::<WatchFolder>().is_err());
+}
+
+#[test]
+fn test_ingest_kind_from_path() {
+    assert_eq!(
+        IngestKind::from_path(Path::new("Screenshot 2025-02-07.PNG")),
+        Some(IngestKind::Image)
+    );
+    assert_eq!(
+        IngestKind::from_path(Path::new("2025-02-07 10-00-00.mkv")),
+        Some(IngestKind::Video)
+    );
+    assert_eq!(
+        IngestKind::from_path(Path::new("memo.m4a")),
+        Some(IngestKind::Audio)
+    );
+    assert_eq!(IngestKind::from_path(Path::new("notes.txt")), None);
+}
+
+#[tokio::test]
+async fn test_existing_files_are_skipped_on_first_scan() {
+    let dir = tempdir().unwrap();
+    let screenshot = dir.path().join("old.png");
+    image::RgbImage::new(8, 8).save(&screenshot).unwrap();
+
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let folder = WatchFolder {
+        path: dir.path().to_path_buf(),
+        source: "Desktop".to_string(),
+    };
+    let watcher = FolderWatcher::new(
+        db.clone(),
+        vec![folder.clone()],
+        Arc::new(OcrEngine::Tesseract),
+        vec![],
+        false,
+        None,
+    );
+
+    assert_eq!(watcher.scan(&folder).await.unwrap(), 0);
+    assert!(db
+        .is_file_ingested(&screenshot.to_string_lossy())
+        .await
+        .unwrap());
+    // a second scan doesn't pick it up either
+    assert_eq!(watcher.scan(&folder).await.unwrap(), 0);
+}
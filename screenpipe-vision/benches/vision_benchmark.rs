@@ -7,6 +7,7 @@ use std::sync::Arc;
 
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 use screenpipe_vision::capture_screenshot_by_window::WindowFilters;
+use screenpipe_vision::frame_scale::FrameScale;
 use screenpipe_vision::monitor::get_default_monitor;
 use screenpipe_vision::{continuous_capture, OcrEngine};
 use tokio::sync::mpsc;
@@ -25,6 +26,10 @@ async fn benchmark_continuous_capture(duration_secs: u64) -> f64 {
             window_filters,
             vec![],
             false,
+            None,
+            true,
+            false,
+            FrameScale::Full,
         )
         .await;
     });
@@ -4,6 +4,7 @@ use clap::Parser;
 use futures_util::{SinkExt, StreamExt};
 use image::ImageEncoder;
 use screenpipe_vision::capture_screenshot_by_window::WindowFilters;
+use screenpipe_vision::frame_scale::FrameScale;
 use screenpipe_vision::{
     continuous_capture, monitor::get_default_monitor, CaptureResult, OcrEngine,
 };
@@ -102,6 +103,10 @@ async fn main() -> Result<()> {
             window_filters,
             vec![],
             false,
+            None,
+            true,
+            false,
+            FrameScale::Full,
         )
         .await
     });
@@ -0,0 +1,519 @@
+use crate::active_window::ActiveApp;
+use serde::{Deserialize, Serialize};
+
+/// Elements read from a window at most, the tree of a browser can have tens of thousands
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+const MAX_ELEMENTS: usize = 2000;
+/// Deepest level of the tree read
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+const MAX_DEPTH: u32 = 40;
+
+/// Where an element is on the screen, in points
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// An element of the accessibility tree of a window, e.g. a button or a text field
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UiElement {
+    /// Kind of element, the same on every platform, e.g. "button", "text field", "link"
+    pub role: String,
+    /// Name of the element, e.g. the title of a button
+    pub label: String,
+    /// Text the element holds, e.g. the content of a text field. Never the one of a password
+    /// field
+    pub text: String,
+    /// Level in the tree, the window is at 0
+    pub depth: u32,
+    pub bounds: Option<Bounds>,
+}
+
+/// Text selected in the focused element, e.g. highlighted in a document
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Selection {
+    pub text: String,
+    /// Where it is highlighted on the screen, in points, when the os tells
+    pub bounds: Option<Bounds>,
+}
+
+/// Elements of the focused window, in the order of the tree
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccessibilityTree {
+    pub app_name: String,
+    pub window_name: String,
+    pub elements: Vec<UiElement>,
+}
+
+/// Reads the tree of the focused window of `app`, without the elements having neither label nor
+/// text. None when the window can't be read, e.g. without the accessibility permission on
+/// macOS, or on Linux where AT-SPI isn't supported yet. Blocks
+pub fn focused_window_tree(app: &ActiveApp) -> Option<AccessibilityTree> {
+    let elements = platform::read_focused_window(app)?;
+    Some(AccessibilityTree {
+        app_name: app.name.clone(),
+        window_name: app.window_title.clone(),
+        elements,
+    })
+}
+
+/// The element at `x`, `y` on the screen, in points, with its text left out. None where it can't
+/// be read, like [`focused_window_tree`]. Blocks
+pub fn element_at(x: f64, y: f64) -> Option<UiElement> {
+    let element = platform::element_at(x, y)?;
+    Some(UiElement {
+        text: String::new(),
+        ..element
+    })
+}
+
+/// The text selected in the focused element. None when nothing is, it's in a password field or
+/// it can't be read, like [`focused_window_tree`]. Blocks
+pub fn focused_selection() -> Option<Selection> {
+    platform::focused_selection().filter(|selection| !selection.text.trim().is_empty())
+}
+
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+fn push_element(elements: &mut Vec<UiElement>, element: UiElement) {
+    if !element.label.trim().is_empty() || !element.text.trim().is_empty() {
+        elements.push(element);
+    }
+}
+
+/// "AXStaticText" to "static text"
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn role_words(ax_role: &str) -> String {
+    let role = ax_role.strip_prefix("AX").unwrap_or(ax_role);
+    let mut words = String::with_capacity(role.len() + 4);
+    for (i, c) in role.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            words.push(' ');
+        }
+        words.extend(c.to_lowercase());
+    }
+    match words.as_str() {
+        "static text" => "text".to_string(),
+        "secure text field" => "text field".to_string(),
+        _ => words,
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{push_element, role_words, Bounds, Selection, UiElement, MAX_DEPTH, MAX_ELEMENTS};
+    use crate::active_window::ActiveApp;
+    use accessibility_sys::{
+        kAXBoundsForRangeParameterizedAttribute, kAXChildrenAttribute, kAXDescriptionAttribute,
+        kAXErrorSuccess, kAXFocusedUIElementAttribute, kAXFocusedWindowAttribute,
+        kAXPositionAttribute, kAXRoleAttribute, kAXSelectedTextAttribute,
+        kAXSelectedTextRangeAttribute, kAXSizeAttribute, kAXTitleAttribute, kAXValueAttribute,
+        kAXValueTypeCGPoint, kAXValueTypeCGRect, kAXValueTypeCGSize, AXUIElementCopyAttributeValue,
+        AXUIElementCopyElementAtPosition, AXUIElementCopyParameterizedAttributeValue,
+        AXUIElementCreateApplication, AXUIElementCreateSystemWide, AXUIElementRef, AXValueGetValue,
+        AXValueRef,
+    };
+    use core_foundation::array::CFArray;
+    use core_foundation::base::{CFType, CFTypeRef, TCFType};
+    use core_foundation::string::CFString;
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct CGSize {
+        width: f64,
+        height: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct CGRect {
+        origin: CGPoint,
+        size: CGSize,
+    }
+
+    pub fn read_focused_window(app: &ActiveApp) -> Option<Vec<UiElement>> {
+        let application = unsafe {
+            CFType::wrap_under_create_rule(
+                AXUIElementCreateApplication(app.process_id as i32) as CFTypeRef
+            )
+        };
+        let window = attribute(
+            application.as_CFTypeRef() as AXUIElementRef,
+            kAXFocusedWindowAttribute,
+        )?;
+        let mut elements = Vec::new();
+        walk(window.as_CFTypeRef() as AXUIElementRef, 0, &mut elements);
+        Some(elements)
+    }
+
+    pub fn element_at(x: f64, y: f64) -> Option<UiElement> {
+        let system =
+            unsafe { CFType::wrap_under_create_rule(AXUIElementCreateSystemWide() as CFTypeRef) };
+        let mut element: AXUIElementRef = std::ptr::null_mut();
+        let error = unsafe {
+            AXUIElementCopyElementAtPosition(
+                system.as_CFTypeRef() as AXUIElementRef,
+                x as f32,
+                y as f32,
+                &mut element,
+            )
+        };
+        if error != kAXErrorSuccess || element.is_null() {
+            return None;
+        }
+        let element = unsafe { CFType::wrap_under_create_rule(element as CFTypeRef) };
+        Some(read_element(element.as_CFTypeRef() as AXUIElementRef, 0))
+    }
+
+    pub fn focused_selection() -> Option<Selection> {
+        let system =
+            unsafe { CFType::wrap_under_create_rule(AXUIElementCreateSystemWide() as CFTypeRef) };
+        let element = attribute(
+            system.as_CFTypeRef() as AXUIElementRef,
+            kAXFocusedUIElementAttribute,
+        )?;
+        let element = element.as_CFTypeRef() as AXUIElementRef;
+        if string_attribute(element, kAXRoleAttribute) == "AXSecureTextField" {
+            return None;
+        }
+        let text = string_attribute(element, kAXSelectedTextAttribute);
+        let bounds = attribute(element, kAXSelectedTextRangeAttribute)
+            .and_then(|range| selection_bounds(element, &range));
+        Some(Selection { text, bounds })
+    }
+
+    /// Where the text of `range` is on the screen
+    fn selection_bounds(element: AXUIElementRef, range: &CFType) -> Option<Bounds> {
+        let name = CFString::new(kAXBoundsForRangeParameterizedAttribute);
+        let mut value: CFTypeRef = std::ptr::null();
+        let error = unsafe {
+            AXUIElementCopyParameterizedAttributeValue(
+                element,
+                name.as_concrete_TypeRef(),
+                range.as_CFTypeRef(),
+                &mut value,
+            )
+        };
+        if error != kAXErrorSuccess || value.is_null() {
+            return None;
+        }
+        let value = unsafe { CFType::wrap_under_create_rule(value) };
+        let mut rect = CGRect::default();
+        let read = unsafe {
+            AXValueGetValue(
+                value.as_CFTypeRef() as AXValueRef,
+                kAXValueTypeCGRect,
+                &mut rect as *mut CGRect as *mut c_void,
+            )
+        };
+        read.then_some(Bounds {
+            x: rect.origin.x,
+            y: rect.origin.y,
+            width: rect.size.width,
+            height: rect.size.height,
+        })
+    }
+
+    fn walk(element: AXUIElementRef, depth: u32, elements: &mut Vec<UiElement>) {
+        if elements.len() >= MAX_ELEMENTS || depth > MAX_DEPTH {
+            return;
+        }
+        push_element(elements, read_element(element, depth));
+
+        let Some(children) =
+            attribute(element, kAXChildrenAttribute).and_then(|value| value.downcast::<CFArray>())
+        else {
+            return;
+        };
+        for child in children.iter() {
+            if elements.len() >= MAX_ELEMENTS {
+                break;
+            }
+            walk(*child as AXUIElementRef, depth + 1, elements);
+        }
+    }
+
+    fn read_element(element: AXUIElementRef, depth: u32) -> UiElement {
+        let ax_role = string_attribute(element, kAXRoleAttribute);
+        let label = Some(string_attribute(element, kAXTitleAttribute))
+            .filter(|title| !title.is_empty())
+            .unwrap_or_else(|| string_attribute(element, kAXDescriptionAttribute));
+        let text = if ax_role == "AXSecureTextField" {
+            String::new()
+        } else {
+            string_attribute(element, kAXValueAttribute)
+        };
+        UiElement {
+            role: role_words(&ax_role),
+            label,
+            text,
+            depth,
+            bounds: bounds(element),
+        }
+    }
+
+    fn attribute(element: AXUIElementRef, name: &str) -> Option<CFType> {
+        let name = CFString::new(name);
+        let mut value: CFTypeRef = std::ptr::null();
+        let error = unsafe {
+            AXUIElementCopyAttributeValue(element, name.as_concrete_TypeRef(), &mut value)
+        };
+        (error == kAXErrorSuccess && !value.is_null())
+            .then(|| unsafe { CFType::wrap_under_create_rule(value) })
+    }
+
+    /// The attribute when it is a string, values of sliders or checkboxes are numbers
+    fn string_attribute(element: AXUIElementRef, name: &str) -> String {
+        attribute(element, name)
+            .and_then(|value| value.downcast::<CFString>())
+            .map(|value| value.to_string())
+            .unwrap_or_default()
+    }
+
+    fn bounds(element: AXUIElementRef) -> Option<Bounds> {
+        let position = attribute(element, kAXPositionAttribute)?;
+        let size = attribute(element, kAXSizeAttribute)?;
+        let mut point = CGPoint::default();
+        let mut extent = CGSize::default();
+        let read = unsafe {
+            AXValueGetValue(
+                position.as_CFTypeRef() as AXValueRef,
+                kAXValueTypeCGPoint,
+                &mut point as *mut CGPoint as *mut c_void,
+            ) && AXValueGetValue(
+                size.as_CFTypeRef() as AXValueRef,
+                kAXValueTypeCGSize,
+                &mut extent as *mut CGSize as *mut c_void,
+            )
+        };
+        read.then_some(Bounds {
+            x: point.x,
+            y: point.y,
+            width: extent.width,
+            height: extent.height,
+        })
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::{push_element, Bounds, Selection, UiElement, MAX_DEPTH, MAX_ELEMENTS};
+    use crate::active_window::ActiveApp;
+    use windows::Win32::Foundation::POINT;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED, SAFEARRAY,
+    };
+    use windows::Win32::System::Ole::SafeArrayDestroy;
+    use windows::Win32::UI::Accessibility::*;
+    use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+    /// Control types by the role the same element has on macOS
+    const ROLES: &[(UIA_CONTROLTYPE_ID, &str)] = &[
+        (UIA_ButtonControlTypeId, "button"),
+        (UIA_CheckBoxControlTypeId, "check box"),
+        (UIA_ComboBoxControlTypeId, "combo box"),
+        (UIA_DocumentControlTypeId, "text area"),
+        (UIA_EditControlTypeId, "text field"),
+        (UIA_HyperlinkControlTypeId, "link"),
+        (UIA_ImageControlTypeId, "image"),
+        (UIA_ListItemControlTypeId, "cell"),
+        (UIA_MenuItemControlTypeId, "menu item"),
+        (UIA_RadioButtonControlTypeId, "radio button"),
+        (UIA_TabItemControlTypeId, "tab"),
+        (UIA_TextControlTypeId, "text"),
+        (UIA_TreeItemControlTypeId, "row"),
+        (UIA_WindowControlTypeId, "window"),
+    ];
+
+    unsafe fn automation() -> Option<IUIAutomation> {
+        // fails harmlessly when the thread already is initialized
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+        CoCreateInstance(&CUIAutomation, None, CLSCTX_INPROC_SERVER).ok()
+    }
+
+    pub fn read_focused_window(_app: &ActiveApp) -> Option<Vec<UiElement>> {
+        unsafe {
+            let automation = automation()?;
+            let window = automation.ElementFromHandle(GetForegroundWindow()).ok()?;
+            let walker = automation.ControlViewWalker().ok()?;
+            let mut elements = Vec::new();
+            walk(&walker, &window, 0, &mut elements);
+            Some(elements)
+        }
+    }
+
+    pub fn element_at(x: f64, y: f64) -> Option<UiElement> {
+        unsafe {
+            let point = POINT {
+                x: x as i32,
+                y: y as i32,
+            };
+            let element = automation()?.ElementFromPoint(point).ok()?;
+            Some(read_element(&element, 0))
+        }
+    }
+
+    pub fn focused_selection() -> Option<Selection> {
+        unsafe {
+            let element = automation()?.GetFocusedElement().ok()?;
+            if element
+                .CurrentIsPassword()
+                .map(|is_password| is_password.as_bool())
+                .unwrap_or(true)
+            {
+                return None;
+            }
+            let ranges = element
+                .GetCurrentPatternAs::<IUIAutomationTextPattern>(UIA_TextPatternId)
+                .ok()?
+                .GetSelection()
+                .ok()?;
+            let mut selection = Selection::default();
+            for i in 0..ranges.Length().ok()? {
+                let range = ranges.GetElement(i).ok()?;
+                selection.text.push_str(
+                    &range
+                        .GetText(-1)
+                        .map(|text| text.to_string())
+                        .unwrap_or_default(),
+                );
+                let Ok(rectangles) = range.GetBoundingRectangles() else {
+                    continue;
+                };
+                // left, top, width and height of each line of the range
+                for rectangle in doubles(rectangles).chunks_exact(4) {
+                    let bounds = Bounds {
+                        x: rectangle[0],
+                        y: rectangle[1],
+                        width: rectangle[2],
+                        height: rectangle[3],
+                    };
+                    selection.bounds = Some(match selection.bounds {
+                        Some(union) => {
+                            let (x, y) = (union.x.min(bounds.x), union.y.min(bounds.y));
+                            Bounds {
+                                x,
+                                y,
+                                width: (union.x + union.width).max(bounds.x + bounds.width) - x,
+                                height: (union.y + union.height).max(bounds.y + bounds.height) - y,
+                            }
+                        }
+                        None => bounds,
+                    });
+                }
+            }
+            Some(selection)
+        }
+    }
+
+    /// The doubles of a one dimension safe array, which is destroyed
+    unsafe fn doubles(array: *mut SAFEARRAY) -> Vec<f64> {
+        if array.is_null() {
+            return Vec::new();
+        }
+        let count = (*array).rgsabound[0].cElements as usize;
+        let values = if (*array).pvData.is_null() {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts((*array).pvData as *const f64, count).to_vec()
+        };
+        let _ = SafeArrayDestroy(array);
+        values
+    }
+
+    unsafe fn walk(
+        walker: &IUIAutomationTreeWalker,
+        element: &IUIAutomationElement,
+        depth: u32,
+        elements: &mut Vec<UiElement>,
+    ) {
+        if elements.len() >= MAX_ELEMENTS || depth > MAX_DEPTH {
+            return;
+        }
+        push_element(elements, read_element(element, depth));
+
+        let mut child = walker.GetFirstChildElement(element).ok();
+        while let Some(current) = child {
+            if elements.len() >= MAX_ELEMENTS {
+                break;
+            }
+            walk(walker, &current, depth + 1, elements);
+            child = walker.GetNextSiblingElement(&current).ok();
+        }
+    }
+
+    unsafe fn read_element(element: &IUIAutomationElement, depth: u32) -> UiElement {
+        let role = match element.CurrentControlType() {
+            Ok(control_type) => ROLES
+                .iter()
+                .find(|(id, _)| *id == control_type)
+                .map(|(_, role)| role.to_string())
+                .or_else(|| {
+                    element
+                        .CurrentLocalizedControlType()
+                        .ok()
+                        .map(|role| role.to_string().to_lowercase())
+                })
+                .unwrap_or_default(),
+            Err(_) => String::new(),
+        };
+        let label = element
+            .CurrentName()
+            .map(|name| name.to_string())
+            .unwrap_or_default();
+        let is_password = element
+            .CurrentIsPassword()
+            .map(|is_password| is_password.as_bool())
+            .unwrap_or(true);
+        let text = if is_password {
+            String::new()
+        } else {
+            element
+                .GetCurrentPatternAs::<IUIAutomationValuePattern>(UIA_ValuePatternId)
+                .and_then(|pattern| pattern.CurrentValue())
+                .map(|value| value.to_string())
+                .unwrap_or_default()
+        };
+        let bounds = element.CurrentBoundingRectangle().ok().map(|rect| Bounds {
+            x: rect.left as f64,
+            y: rect.top as f64,
+            width: (rect.right - rect.left) as f64,
+            height: (rect.bottom - rect.top) as f64,
+        });
+        UiElement {
+            role,
+            label,
+            text,
+            depth,
+            bounds,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{Selection, UiElement};
+    use crate::active_window::ActiveApp;
+
+    pub fn read_focused_window(_app: &ActiveApp) -> Option<Vec<UiElement>> {
+        None
+    }
+
+    pub fn focused_selection() -> Option<Selection> {
+        None
+    }
+
+    pub fn element_at(_x: f64, _y: f64) -> Option<UiElement> {
+        None
+    }
+}
@@ -0,0 +1,130 @@
+use active_win_pos_rs::get_active_window;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// The app of the focused window
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActiveApp {
+    /// Name the app shows, e.g. "Google Chrome"
+    pub name: String,
+    /// File name of the executable without extension, e.g. "chrome"
+    pub process_name: String,
+    /// macOS bundle identifier, e.g. "com.google.Chrome"
+    pub bundle_id: Option<String>,
+    pub window_title: String,
+    pub process_id: u64,
+}
+
+impl ActiveApp {
+    /// Whether `identifier` (lowercase) is the bundle id, the process name or the name of the app
+    fn is(&self, identifier: &str) -> bool {
+        self.name.to_lowercase() == identifier
+            || self.process_name.to_lowercase() == identifier
+            || self
+                .bundle_id
+                .as_ref()
+                .is_some_and(|bundle_id| bundle_id.to_lowercase() == identifier)
+    }
+}
+
+/// The app of the focused window, None when it can't be told (no window is focused, or a Wayland
+/// session)
+pub fn get_active_app() -> Option<ActiveApp> {
+    let window = get_active_window().ok()?;
+    let process_name = window
+        .process_path
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Some(ActiveApp {
+        name: window.app_name,
+        process_name,
+        bundle_id: bundle_id(&window.process_path),
+        window_title: window.title,
+        process_id: window.process_id,
+    })
+}
+
+/// Bundle identifier of the .app the executable at `process_path` is in
+#[cfg(target_os = "macos")]
+fn bundle_id(process_path: &Path) -> Option<String> {
+    use once_cell::sync::Lazy;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    // reading Info.plist for each frame would be wasteful, an app keeps its bundle id
+    static BUNDLE_IDS: Lazy<Mutex<HashMap<PathBuf, Option<String>>>> =
+        Lazy::new(|| Mutex::new(HashMap::new()));
+
+    let mut bundle_ids = BUNDLE_IDS.lock().unwrap();
+    bundle_ids
+        .entry(process_path.to_path_buf())
+        .or_insert_with(|| {
+            let app = process_path
+                .ancestors()
+                .find(|path| path.extension().is_some_and(|ext| ext == "app"))?;
+            let info = plist::Value::from_file(app.join("Contents/Info.plist")).ok()?;
+            info.as_dictionary()?
+                .get("CFBundleIdentifier")?
+                .as_string()
+                .map(str::to_string)
+        })
+        .clone()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn bundle_id(_process_path: &Path) -> Option<String> {
+    None
+}
+
+/// Apps to record or not, by bundle id, process name or name, whichever is given
+#[derive(Debug, Clone, Default)]
+pub struct AppFilters {
+    ignore_set: HashSet<String>,
+    include_set: HashSet<String>,
+}
+
+impl AppFilters {
+    pub fn new(ignore_list: &[String], include_list: &[String]) -> Self {
+        Self {
+            ignore_set: ignore_list
+                .iter()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_lowercase())
+                .collect(),
+            include_set: include_list
+                .iter()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_lowercase())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ignore_set.is_empty() && self.include_set.is_empty()
+    }
+
+    /// Whether the screen may be captured while `app` is focused. Ignored apps never are, and
+    /// when apps are included only they are. An unknown app is only captured when no app is
+    /// included
+    pub fn is_allowed(&self, app: Option<&ActiveApp>) -> bool {
+        let Some(app) = app else {
+            return self.include_set.is_empty();
+        };
+        if self.ignore_set.iter().any(|ignore| app.is(ignore)) {
+            return false;
+        }
+        self.include_set.is_empty() || self.include_set.iter().any(|include| app.is(include))
+    }
+
+    /// Whether a window of the app named `app_name` may be captured, windows only know the name
+    /// of their app
+    pub fn is_app_name_allowed(&self, app_name: &str) -> bool {
+        let app = ActiveApp {
+            name: app_name.to_string(),
+            ..Default::default()
+        };
+        !self.ignore_set.iter().any(|ignore| app.is(ignore))
+    }
+}
@@ -0,0 +1,83 @@
+use log::debug;
+use std::time::Duration;
+
+/// How the capture of a monitor slows down while its screen doesn't change
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveFpsConfig {
+    /// Slowest the screen is captured at
+    pub min_fps: f64,
+    /// Difference from the previous frame, 0 to 1, under which a frame is still
+    pub still_threshold: f64,
+    /// Difference over which the screen changed and is captured at full rate again. Higher than
+    /// `still_threshold`, so a cursor blinking or a clock ticking between both doesn't make the
+    /// rate go up and down
+    pub change_threshold: f64,
+    /// Still frames in a row before the rate is halved, again after as many more, down to
+    /// `min_fps`
+    pub still_frames: u32,
+}
+
+impl Default for AdaptiveFpsConfig {
+    fn default() -> Self {
+        Self {
+            min_fps: 0.2,
+            still_threshold: 0.006,
+            change_threshold: 0.02,
+            still_frames: 5,
+        }
+    }
+}
+
+/// Frame rate of the capture of a monitor, following how much its screen changes
+pub struct AdaptiveFps {
+    config: AdaptiveFpsConfig,
+    fastest: Duration,
+    slowest: Duration,
+    interval: Duration,
+    still_frames: u32,
+}
+
+impl AdaptiveFps {
+    /// Captures at `fps` until the screen stops changing
+    pub fn new(fps: f64, config: AdaptiveFpsConfig) -> Self {
+        let fastest = Duration::from_secs_f64(1.0 / fps);
+        let slowest = Duration::from_secs_f64(1.0 / config.min_fps.min(fps));
+        Self {
+            config,
+            fastest,
+            slowest,
+            interval: fastest,
+            still_frames: 0,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Takes the difference of the last frame from the previous one into account, returning how
+    /// long to wait before the next frame
+    pub fn update(&mut self, difference: f64) -> Duration {
+        if difference > self.config.change_threshold {
+            if self.interval != self.fastest {
+                debug!("screen changed, back to {:?} between frames", self.fastest);
+            }
+            self.interval = self.fastest;
+            self.still_frames = 0;
+        } else if difference < self.config.still_threshold {
+            self.still_frames += 1;
+            if self.still_frames >= self.config.still_frames && self.interval < self.slowest {
+                self.interval = (self.interval * 2).min(self.slowest);
+                self.still_frames = 0;
+                debug!(
+                    "screen still, slowing down to {:?} between frames",
+                    self.interval
+                );
+            }
+        } else {
+            // small changes neither slow down nor speed up
+            self.still_frames = 0;
+        }
+        self.interval
+    }
+}
@@ -1,4 +1,6 @@
 #[cfg(target_os = "macos")]
+use crate::ocr_languages;
+#[cfg(target_os = "macos")]
 use cidre::{
     cv::{PixelBuf, PixelFormat},
     ns,
@@ -75,7 +77,8 @@ pub fn perform_ocr_apple(
 ) -> (String, String, Option<f64>) {
     cidre::objc::ar_pool(|| {
         // Convert languages to Apple format and create ns::Array
-        let apple_languages = get_apple_languages(languages);
+        let options = ocr_languages::current();
+        let apple_languages = get_apple_languages(&options.languages_or(languages));
         let mut languages_array = ns::ArrayMut::<ns::String>::with_capacity(apple_languages.len());
         apple_languages.iter().for_each(|language| {
             languages_array.push(&ns::String::with_str(language));
@@ -118,6 +121,8 @@ pub fn perform_ocr_apple(
         let mut request = RecognizeTextRequest::new();
         request.set_recognition_langs(&languages_array);
         request.set_uses_lang_correction(false);
+        // the languages are tried in order otherwise, text in a later one reads poorly
+        request.set_automatically_detects_lang(options.auto_detect);
         let requests = ns::Array::<vn::Request>::from_slice(&[&request]);
         let result = handler.perform(&requests);
 
@@ -129,36 +134,43 @@ pub fn perform_ocr_apple(
             if !results.is_empty() {
                 let mut ocr_results_vec: Vec<serde_json::Value> = Vec::new();
                 let mut ocr_text: String = String::new();
-                results.iter().for_each(|result| {
+                results.iter().enumerate().for_each(|(line_num, result)| {
                     let observation_result = result.top_candidates(1).get(0).unwrap();
                     let text = observation_result.string();
                     let confidence = observation_result.confidence() as f64;
-                    let bbox = observation_result
-                        .bounding_box_for_range(ns::Range::new(0, text.len()))
-                        .unwrap()
-                        .bounding_box();
-                    let x = bbox.origin.x;
-                    let y = bbox.origin.y;
-                    let height = bbox.size.height;
-                    let width = bbox.size.width;
-
-                    ocr_results_vec.push(serde_json::json!({
-                        "level": "0",
-                        "page_num": "0",
-                        "block_num": "0",
-                        "par_num": "0",
-                        "line_num": "0",
-                        "word_num": "0",
-                        "left": x.to_string(),
-                        "top": y.to_string(),
-                        "width": width.to_string(),
-                        "height": height.to_string(),
-                        "conf": confidence.to_string(),
-                        "text": text.to_string(),
-                    }));
+                    let line = text.to_string();
+
+                    // vision reads lines, a box per word so it can be filtered out on its own.
+                    // Ranges are in utf-16 code units
+                    let mut offset = 0;
+                    for (word_num, word) in line.split(' ').enumerate() {
+                        let len = word.encode_utf16().count();
+                        if len > 0 {
+                            if let Ok(rect) = observation_result
+                                .bounding_box_for_range(ns::Range::new(offset, len))
+                            {
+                                let bbox = rect.bounding_box();
+                                ocr_results_vec.push(serde_json::json!({
+                                    "level": "5",
+                                    "page_num": "1",
+                                    "block_num": "0",
+                                    "par_num": "0",
+                                    "line_num": line_num.to_string(),
+                                    "word_num": word_num.to_string(),
+                                    "left": bbox.origin.x.to_string(),
+                                    "top": bbox.origin.y.to_string(),
+                                    "width": bbox.size.width.to_string(),
+                                    "height": bbox.size.height.to_string(),
+                                    "conf": confidence.to_string(),
+                                    "text": word,
+                                }));
+                            }
+                        }
+                        offset += len + 1;
+                    }
 
                     overall_confidence += confidence;
-                    ocr_text.push_str(text.to_string().as_str());
+                    ocr_text.push_str(&line);
                 });
 
                 let json_output_string =
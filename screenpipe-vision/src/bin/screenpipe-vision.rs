@@ -3,6 +3,7 @@ use screenpipe_core::Language;
 use screenpipe_vision::{
     capture_screenshot_by_window::WindowFilters, 
     continuous_capture,
+    frame_scale::FrameScale,
     OcrEngine,
 };
 use std::{sync::Arc, time::Duration};
@@ -57,6 +58,10 @@ async fn main() {
         window_filters,
         languages.clone(),
         false,
+        None,
+        true,
+        false,
+        FrameScale::Full,
     )
     .await;
 
@@ -0,0 +1,262 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Browsers, matched in the lowercase name of the app
+const BROWSERS: &[&str] = &[
+    "google chrome",
+    "chrome",
+    "chromium",
+    "firefox",
+    "safari",
+    "microsoft edge",
+    "msedge",
+    "brave",
+    "opera",
+    "vivaldi",
+    "zen browser",
+];
+
+/// What browsers add to the title of their private windows, lowercase: "(Incognito)" for
+/// chrome, "Private Browsing" for firefox and the start page of safari, "InPrivate" for edge,
+/// "(Private)" for brave and vivaldi
+const PRIVATE_TITLE_MARKERS: &[&str] = &[
+    "incognito",
+    "private browsing",
+    "inprivate",
+    "(private)",
+    "private window",
+];
+
+/// How long the titles of the private windows a browser was asked for are reused for, every
+/// window of every frame is checked
+#[cfg(target_os = "macos")]
+const PRIVATE_WINDOWS_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long the url a browser was asked for is reused for while its window keeps its title,
+/// asking runs a script on macOS and searches the window's UI tree on Windows
+const URL_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The tab a companion browser extension reported as active last, for the browsers the url
+/// can't be read from natively, and on Linux
+static REPORTED_TAB: Lazy<Mutex<Option<BrowserTab>>> = Lazy::new(|| Mutex::new(None));
+
+/// The active tab of a browser
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BrowserTab {
+    pub url: String,
+    pub title: String,
+    /// In a private window, only the extension tells
+    #[serde(default)]
+    pub incognito: bool,
+}
+
+pub fn is_browser(app_name: &str) -> bool {
+    let app_name = app_name.to_lowercase();
+    // "arc" alone, as a substring it is in too many names
+    app_name == "arc" || BROWSERS.iter().any(|browser| app_name.contains(browser))
+}
+
+/// Records the tab a companion extension reports as active
+pub fn report_tab(tab: BrowserTab) {
+    *REPORTED_TAB.lock().unwrap() = Some(tab);
+}
+
+/// The active tab of the focused window of browser `app_name`, titled `window_title`. Read from
+/// the browser when it can be, otherwise the tab reported by the companion extension if it is
+/// the one shown in the window. Blocks, it may run a script
+pub fn get_browser_tab(app_name: &str, window_title: &str) -> Option<BrowserTab> {
+    if !is_browser(app_name) {
+        return None;
+    }
+    if let Some(url) = cached_native_url(app_name, window_title) {
+        return Some(BrowserTab {
+            url: normalize_url(&url),
+            title: tab_title(app_name, window_title),
+            incognito: false,
+        });
+    }
+    let reported = REPORTED_TAB.lock().unwrap().clone()?;
+    (!reported.incognito && !reported.title.is_empty() && window_title.contains(&reported.title))
+        .then_some(reported)
+}
+
+/// Whether the window of browser `app_name` titled `window_title` is a private (incognito)
+/// one, from its title, the tab the companion extension reported, or the browser itself where
+/// it tells. Blocks, it may run a script
+pub fn is_private_window(app_name: &str, window_title: &str) -> bool {
+    if !is_browser(app_name) {
+        return false;
+    }
+    let title = window_title.to_lowercase();
+    if PRIVATE_TITLE_MARKERS
+        .iter()
+        .any(|marker| title.contains(marker))
+    {
+        return true;
+    }
+    let reported_private = REPORTED_TAB.lock().unwrap().as_ref().is_some_and(|tab| {
+        tab.incognito && !tab.title.is_empty() && window_title.contains(&tab.title)
+    });
+    reported_private || native_private_titles(app_name).contains(&window_title.to_string())
+}
+
+/// `window_title` without the name of the browser some add at the end, e.g. "Inbox - Google
+/// Chrome"
+pub fn tab_title(app_name: &str, window_title: &str) -> String {
+    [" - ", " — ", " – "]
+        .iter()
+        .find_map(|separator| window_title.strip_suffix(&format!("{}{}", separator, app_name)))
+        .unwrap_or(window_title)
+        .to_string()
+}
+
+/// `native_url` of the window last asked for, when it is the same app and title and was asked
+/// for recently
+fn cached_native_url(app_name: &str, window_title: &str) -> Option<String> {
+    type Check = ((String, String), Instant, Option<String>);
+    static LAST_CHECK: Lazy<Mutex<Option<Check>>> = Lazy::new(Default::default);
+
+    let window = (app_name.to_string(), window_title.to_string());
+    if let Some((checked_window, checked_at, url)) = LAST_CHECK.lock().unwrap().as_ref() {
+        if *checked_window == window && checked_at.elapsed() < URL_CHECK_INTERVAL {
+            return url.clone();
+        }
+    }
+    let url = native_url(app_name);
+    *LAST_CHECK.lock().unwrap() = Some((window, Instant::now(), url.clone()));
+    url
+}
+
+/// Address bars show urls without their scheme
+fn normalize_url(url: &str) -> String {
+    let url = url.trim();
+    if url.contains("://") || url.starts_with("about:") {
+        url.to_string()
+    } else {
+        format!("https://{}", url)
+    }
+}
+
+/// Asks the browser its url with AppleScript, Firefox doesn't support it
+#[cfg(target_os = "macos")]
+fn native_url(app_name: &str) -> Option<String> {
+    let app = app_name.replace('"', "");
+    let script = if app.to_lowercase().contains("safari") {
+        format!(
+            r#"tell application "{}" to return URL of front document"#,
+            app
+        )
+    } else if app.to_lowercase().contains("firefox") {
+        return None;
+    } else {
+        // chromium based browsers and arc
+        format!(
+            r#"tell application "{}" to return URL of active tab of front window"#,
+            app
+        )
+    };
+    let output = std::process::Command::new("osascript")
+        .args(["-e", &script])
+        .output()
+        .ok()?;
+    let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !url.is_empty() && url != "missing value").then_some(url)
+}
+
+/// Titles of the incognito windows of a chromium based browser, asked with AppleScript. Safari
+/// and Firefox don't tell
+#[cfg(target_os = "macos")]
+fn native_private_titles(app_name: &str) -> Vec<String> {
+    use std::collections::HashMap;
+
+    static CHECKS: Lazy<Mutex<HashMap<String, (Instant, Vec<String>)>>> =
+        Lazy::new(Default::default);
+
+    let lowercase = app_name.to_lowercase();
+    if lowercase.contains("safari") || lowercase.contains("firefox") {
+        return Vec::new();
+    }
+    if let Some((checked_at, titles)) = CHECKS.lock().unwrap().get(app_name) {
+        if checked_at.elapsed() < PRIVATE_WINDOWS_CHECK_INTERVAL {
+            return titles.clone();
+        }
+    }
+    let script = format!(
+        r#"tell application "{}"
+            set titles to ""
+            repeat with w in (every window whose mode is "incognito")
+                set titles to titles & (title of w) & linefeed
+            end repeat
+            return titles
+        end tell"#,
+        app_name.replace('"', "")
+    );
+    let titles = std::process::Command::new("osascript")
+        .args(["-e", &script])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter(|title| !title.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    CHECKS
+        .lock()
+        .unwrap()
+        .insert(app_name.to_string(), (Instant::now(), titles.clone()));
+    titles
+}
+
+/// The titles tell on windows and linux
+#[cfg(not(target_os = "macos"))]
+fn native_private_titles(_app_name: &str) -> Vec<String> {
+    Vec::new()
+}
+
+/// Reads the address bar, the first edit control, of the foreground window with UI Automation
+#[cfg(target_os = "windows")]
+fn native_url(_app_name: &str) -> Option<String> {
+    use windows::core::VARIANT;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED,
+    };
+    use windows::Win32::UI::Accessibility::{
+        CUIAutomation, IUIAutomation, IUIAutomationValuePattern, TreeScope_Descendants,
+        UIA_ControlTypePropertyId, UIA_EditControlTypeId, UIA_ValuePatternId,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+    unsafe {
+        // fails harmlessly when the thread already is initialized
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+        let automation: IUIAutomation =
+            CoCreateInstance(&CUIAutomation, None, CLSCTX_INPROC_SERVER).ok()?;
+        let window = automation.ElementFromHandle(GetForegroundWindow()).ok()?;
+        let condition = automation
+            .CreatePropertyCondition(
+                UIA_ControlTypePropertyId,
+                &VARIANT::from(UIA_EditControlTypeId.0),
+            )
+            .ok()?;
+        let address_bar = window.FindFirst(TreeScope_Descendants, &condition).ok()?;
+        let value = address_bar
+            .GetCurrentPatternAs::<IUIAutomationValuePattern>(UIA_ValuePatternId)
+            .ok()?
+            .CurrentValue()
+            .ok()?
+            .to_string();
+        (!value.is_empty()).then_some(value)
+    }
+}
+
+/// Browsers can't be asked their url on Linux, only the companion extension tells it
+#[cfg(target_os = "linux")]
+fn native_url(_app_name: &str) -> Option<String> {
+    None
+}
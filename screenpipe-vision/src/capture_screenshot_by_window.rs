@@ -7,7 +7,9 @@ use std::fmt;
 
 use xcap::{Window, XCapError};
 
+use crate::active_window::AppFilters;
 use crate::monitor::SafeMonitor;
+use crate::sensitive_windows::SensitiveWindows;
 
 #[derive(Debug)]
 enum CaptureError {
@@ -142,9 +144,24 @@ pub struct CapturedWindow {
     pub is_focused: bool,
 }
 
+/// A single window, captured with where it is on the virtual screen, in the coordinates of the
+/// monitors
+#[derive(Debug, Clone)]
+pub struct SingleWindow {
+    pub window: CapturedWindow,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone)]
 pub struct WindowFilters {
     ignore_set: HashSet<String>,
     include_set: HashSet<String>,
+    single_window: Option<String>,
+    app_filters: AppFilters,
+    sensitive_windows: SensitiveWindows,
 }
 
 impl WindowFilters {
@@ -152,9 +169,43 @@ impl WindowFilters {
         Self {
             ignore_set: ignore_list.iter().map(|s| s.to_lowercase()).collect(),
             include_set: include_list.iter().map(|s| s.to_lowercase()).collect(),
+            single_window: None,
+            app_filters: AppFilters::default(),
+            sensitive_windows: SensitiveWindows::default(),
         }
     }
 
+    /// Captures only the window whose app name or title contains `window` instead of the whole
+    /// screen, none captures everything
+    pub fn with_single_window(mut self, window: Option<&str>) -> Self {
+        self.single_window = window.map(str::to_lowercase);
+        self
+    }
+
+    pub fn single_window(&self) -> Option<&str> {
+        self.single_window.as_deref()
+    }
+
+    /// Doesn't capture the screen while an ignored app, or one not included, is focused
+    pub fn with_app_filters(mut self, app_filters: AppFilters) -> Self {
+        self.app_filters = app_filters;
+        self
+    }
+
+    pub fn app_filters(&self) -> &AppFilters {
+        &self.app_filters
+    }
+
+    /// Blanks the sensitive windows in the frames captured and doesn't OCR them
+    pub fn with_sensitive_windows(mut self, sensitive_windows: SensitiveWindows) -> Self {
+        self.sensitive_windows = sensitive_windows;
+        self
+    }
+
+    pub fn sensitive_windows(&self) -> &SensitiveWindows {
+        &self.sensitive_windows
+    }
+
     // O(n) - we could figure out a better way to do this
     pub fn is_valid(&self, app_name: &str, title: &str) -> bool {
         let app_name_lower = app_name.to_lowercase();
@@ -228,7 +279,11 @@ pub async fn capture_all_visible_windows(
         let is_valid = !SKIP_APPS.contains(app_name.as_str())
             && !SKIP_TITLES.contains(window_name.as_str())
             && (capture_unfocused_windows || (is_focused && monitor.id() == monitor.id()))
-            && window_filters.is_valid(&app_name, &window_name);
+            && window_filters.is_valid(&app_name, &window_name)
+            && window_filters.app_filters.is_app_name_allowed(&app_name)
+            && !window_filters
+                .sensitive_windows
+                .is_sensitive(&app_name, &window_name);
 
         if is_valid {
             all_captured_images.push(CapturedWindow {
@@ -242,3 +297,64 @@ pub async fn capture_all_visible_windows(
 
     Ok(all_captured_images)
 }
+
+/// Captures the window whose app name or title contains `target` (lowercase), the focused one
+/// when several do, then the largest. None when no such window is shown on `monitor`, so a window
+/// moved to another monitor is captured by the capture of that monitor
+pub async fn capture_single_window(
+    monitor: &SafeMonitor,
+    target: &str,
+) -> Result<Option<SingleWindow>, Box<dyn Error>> {
+    let mut windows = Window::all()?
+        .into_iter()
+        .filter(|window| {
+            !window.is_minimized()
+                && window.current_monitor().id() == monitor.id()
+                && (window.app_name().to_lowercase().contains(target)
+                    || window.title().to_lowercase().contains(target))
+        })
+        .collect::<Vec<_>>();
+    windows.sort_by_key(|window| {
+        (
+            !window.is_focused(),
+            std::cmp::Reverse(window.width() as u64 * window.height() as u64),
+        )
+    });
+    let Some(window) = windows.into_iter().next() else {
+        return Ok(None);
+    };
+
+    let buffer = window.capture_image()?;
+    let image = DynamicImage::ImageRgba8(
+        image::ImageBuffer::from_raw(buffer.width(), buffer.height(), buffer.into_raw()).unwrap(),
+    );
+    Ok(Some(SingleWindow {
+        window: CapturedWindow {
+            image,
+            app_name: window.app_name().to_string(),
+            window_name: window.title().to_string(),
+            is_focused: window.is_focused(),
+        },
+        x: window.x(),
+        y: window.y(),
+        width: window.width(),
+        height: window.height(),
+    }))
+}
+
+/// Where the sensitive windows shown on `monitor` are, as x, y, width and height on the virtual
+/// screen
+pub async fn sensitive_window_bounds(
+    monitor: &SafeMonitor,
+    sensitive_windows: &SensitiveWindows,
+) -> Result<Vec<(i32, i32, u32, u32)>, Box<dyn Error>> {
+    Ok(Window::all()?
+        .into_iter()
+        .filter(|window| {
+            !window.is_minimized()
+                && window.current_monitor().id() == monitor.id()
+                && sensitive_windows.is_sensitive(window.app_name(), window.title())
+        })
+        .map(|window| (window.x(), window.y(), window.width(), window.height()))
+        .collect())
+}
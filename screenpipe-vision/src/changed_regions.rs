@@ -0,0 +1,240 @@
+use image::DynamicImage;
+use once_cell::sync::{Lazy, OnceCell};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Rows of pixels hashed together, a line of text spans 1 or 2 bands
+const BAND_HEIGHT: u32 = 16;
+/// Changed regions closer than this are OCRed together, each call to the engine has a cost
+const MERGE_GAP: u32 = 32;
+/// Pixels added above and below a changed region, for the letters reaching out of it
+const MARGIN: u32 = 4;
+/// Windows changed more than this share of their height are OCRed whole
+pub const MAX_CHANGED_SHARE: f64 = 0.5;
+/// Windows whose last OCR results are kept
+const MAX_WINDOWS: usize = 32;
+
+static ENABLED: OnceCell<bool> = OnceCell::new();
+static PREVIOUS: Lazy<Mutex<HashMap<(String, String), PreviousOcr>>> = Lazy::new(Default::default);
+
+/// Only OCR the rows of windows that changed since they were last OCRed, off until enabled
+pub fn init(enabled: bool) {
+    let _ = ENABLED.set(enabled);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.get().copied().unwrap_or(false)
+}
+
+/// What was read in a window the last time it was OCRed
+#[derive(Clone, Debug)]
+pub struct PreviousOcr {
+    pub engine: String,
+    pub width: u32,
+    pub height: u32,
+    pub bands: Vec<u64>,
+    pub text: String,
+    pub words: Vec<HashMap<String, String>>,
+    pub confidence: Option<f64>,
+    /// Times the words were merged, to keep apart the lines read in each merge
+    pub merges: u64,
+    last_used: Instant,
+}
+
+impl PreviousOcr {
+    pub fn new(
+        engine: String,
+        image: &DynamicImage,
+        text: String,
+        words: Vec<HashMap<String, String>>,
+        confidence: Option<f64>,
+        merges: u64,
+    ) -> Self {
+        Self {
+            engine,
+            width: image.width(),
+            height: image.height(),
+            bands: band_hashes(image),
+            text,
+            words,
+            confidence,
+            merges,
+            last_used: Instant::now(),
+        }
+    }
+}
+
+/// Last OCR of the window of `app_name` titled `window_name`
+pub fn previous(app_name: &str, window_name: &str) -> Option<PreviousOcr> {
+    let previous = PREVIOUS.lock().unwrap_or_else(|e| e.into_inner());
+    previous
+        .get(&(app_name.to_string(), window_name.to_string()))
+        .cloned()
+}
+
+pub fn save(app_name: &str, window_name: &str, ocr: PreviousOcr) {
+    let mut previous = PREVIOUS.lock().unwrap_or_else(|e| e.into_inner());
+    if previous.len() >= MAX_WINDOWS {
+        if let Some(oldest) = previous
+            .iter()
+            .min_by_key(|(_, ocr)| ocr.last_used)
+            .map(|(key, _)| key.clone())
+        {
+            previous.remove(&oldest);
+        }
+    }
+    previous.insert((app_name.to_string(), window_name.to_string()), ocr);
+}
+
+/// Hash of each band of rows of `image`, screenshots are lossless so unchanged rows hash the same
+pub fn band_hashes(image: &DynamicImage) -> Vec<u64> {
+    let bytes = image.as_bytes();
+    let row_size = bytes.len() / image.height().max(1) as usize;
+    bytes
+        .chunks((row_size * BAND_HEIGHT as usize).max(1))
+        .map(|band| {
+            let mut hasher = DefaultHasher::new();
+            band.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
+
+/// Rows from the top of a window of `height` pixels, of the bands that differ, close ones merged
+pub fn changed_rows(previous: &[u64], bands: &[u64], height: u32) -> Vec<(u32, u32)> {
+    let mut rows: Vec<(u32, u32)> = Vec::new();
+    for (i, _) in bands
+        .iter()
+        .enumerate()
+        .filter(|(i, band)| previous.get(*i) != Some(band))
+    {
+        let top = i as u32 * BAND_HEIGHT;
+        let bottom = (top + BAND_HEIGHT).min(height);
+        match rows.last_mut() {
+            Some(last) if top <= last.1 + MERGE_GAP => last.1 = bottom,
+            _ => rows.push((top, bottom)),
+        }
+    }
+    rows
+}
+
+/// Top and bottom of `word` in pixels from the top of a window of `height`, apple native's boxes
+/// are fractions from the bottom left corner
+fn word_rows(
+    word: &HashMap<String, String>,
+    height: u32,
+    normalized_bottom_left: bool,
+) -> Option<(f64, f64)> {
+    let value = |key: &str| word.get(key)?.parse::<f64>().ok();
+    let (top, word_height) = (value("top")?, value("height")?);
+    if normalized_bottom_left {
+        let height = height as f64;
+        Some((height - (top + word_height) * height, height - top * height))
+    } else {
+        Some((top, top + word_height))
+    }
+}
+
+fn has_text(word: &HashMap<String, String>) -> bool {
+    word.get("text").is_some_and(|text| !text.trim().is_empty())
+}
+
+/// Rows of a window of `height` to OCR again, the `changed` ones grown to the whole words of
+/// `previous_words` they cut through. None when too much changed or a word can't be located, the
+/// window is better OCRed whole
+pub fn rows_to_ocr(
+    changed: &[(u32, u32)],
+    previous_words: &[HashMap<String, String>],
+    height: u32,
+    normalized_bottom_left: bool,
+) -> Option<Vec<(u32, u32)>> {
+    let mut words = Vec::new();
+    for word in previous_words.iter().filter(|word| has_text(word)) {
+        words.push(word_rows(word, height, normalized_bottom_left)?);
+    }
+
+    let mut rows: Vec<(u32, u32)> = Vec::new();
+    for &(top, bottom) in changed {
+        let (mut top, mut bottom) = (top.saturating_sub(MARGIN), (bottom + MARGIN).min(height));
+        loop {
+            let (grown_top, grown_bottom) = words
+                .iter()
+                .filter(|(word_top, word_bottom)| {
+                    *word_top < bottom as f64 && *word_bottom > top as f64
+                })
+                .fold((top, bottom), |(top, bottom), (word_top, word_bottom)| {
+                    (
+                        top.min(word_top.floor().max(0.0) as u32),
+                        bottom.max((word_bottom.ceil() as u32).min(height)),
+                    )
+                });
+            if (grown_top, grown_bottom) == (top, bottom) {
+                break;
+            }
+            (top, bottom) = (grown_top, grown_bottom);
+        }
+        match rows.last_mut() {
+            Some(last) if top <= last.1 => last.1 = last.1.max(bottom),
+            _ => rows.push((top, bottom)),
+        }
+    }
+
+    let changed_height: u32 = rows.iter().map(|(top, bottom)| bottom - top).sum();
+    (changed_height as f64 <= height as f64 * MAX_CHANGED_SHARE).then_some(rows)
+}
+
+/// `previous_words` outside of `rows`, with the words read in each of `rows` moved to where the
+/// rows are in the window of `height`. The words of a row are read in a crop of it as wide as
+/// the window, only their vertical position changes
+pub fn merge_words(
+    previous_words: &[HashMap<String, String>],
+    rows: &[(u32, u32)],
+    rows_words: Vec<Vec<HashMap<String, String>>>,
+    height: u32,
+    normalized_bottom_left: bool,
+    merge: u64,
+) -> Vec<HashMap<String, String>> {
+    let in_rows = |word: &HashMap<String, String>| {
+        word_rows(word, height, normalized_bottom_left).is_some_and(|(word_top, word_bottom)| {
+            rows.iter()
+                .any(|&(top, bottom)| word_top < bottom as f64 && word_bottom > top as f64)
+        })
+    };
+    let mut words: Vec<HashMap<String, String>> = previous_words
+        .iter()
+        .filter(|word| has_text(word) && !in_rows(word))
+        .cloned()
+        .collect();
+
+    for (i, (&(top, bottom), row_words)) in rows.iter().zip(rows_words).enumerate() {
+        let row_height = (bottom - top) as f64;
+        for mut word in row_words {
+            let value = |key: &str| word.get(key)?.parse::<f64>().ok();
+            let (Some(word_top), Some(word_height)) = (value("top"), value("height")) else {
+                continue;
+            };
+            let (word_top, word_height) = if normalized_bottom_left {
+                let height = height as f64;
+                (
+                    (word_top * row_height + (height - bottom as f64)) / height,
+                    word_height * row_height / height,
+                )
+            } else {
+                (word_top + top as f64, word_height)
+            };
+            word.insert("top".to_string(), word_top.to_string());
+            word.insert("height".to_string(), word_height.to_string());
+            // lines are told apart by their numbers, which restart in each row
+            let block = word.get("block_num").cloned().unwrap_or_default();
+            word.insert(
+                "block_num".to_string(),
+                format!("{}.{}.{}", merge, i, block),
+            );
+            words.push(word);
+        }
+    }
+    words
+}
@@ -0,0 +1,170 @@
+use anyhow::{anyhow, Context, Result};
+use image::{imageops, imageops::FilterType, DynamicImage, RgbImage};
+use ndarray::{Array2, Array4};
+use once_cell::sync::Lazy;
+use ort::{GraphOptimizationLevel, Session};
+use screenpipe_core::models::{self, ModelFile};
+use std::sync::{Arc, Mutex};
+use tokenizers::Tokenizer;
+use tracing::debug;
+
+/// CLIP ViT-B/32 converted to onnx, its image and text encoders embed in the same space
+const MODEL_REPO: &str = "Xenova/clip-vit-base-patch32";
+const IMAGE_MODEL: &str = "onnx/vision_model_quantized.onnx";
+const TEXT_MODEL: &str = "onnx/text_model_quantized.onnx";
+const TOKENIZER: &str = "tokenizer.json";
+
+pub const EMBEDDING_SIZE: usize = 512;
+const IMAGE_SIZE: u32 = 224;
+const MEAN: [f32; 3] = [0.481_454_66, 0.457_827_5, 0.408_210_73];
+const STD: [f32; 3] = [0.268_629_54, 0.261_302_6, 0.275_777_1];
+/// Context of the text encoder, longer texts are cut
+const MAX_TOKENS: usize = 77;
+
+static MODEL: Lazy<Mutex<Option<Arc<Clip>>>> = Lazy::new(Default::default);
+
+struct Clip {
+    image_encoder: Session,
+    text_encoder: Session,
+    tokenizer: Tokenizer,
+}
+
+/// Embedding of `image`, of unit length, close to the embeddings of the texts describing it. The
+/// models are downloaded on first use
+pub async fn image_embedding(image: &DynamicImage) -> Result<Vec<f32>> {
+    let image = image.to_rgb8();
+    tokio::task::spawn_blocking(move || model()?.embed_image(&image)).await?
+}
+
+/// Embedding of `text`, of unit length, to find the images it describes, like "a bar chart" or
+/// "an error dialog"
+pub async fn text_embedding(text: &str) -> Result<Vec<f32>> {
+    let text = text.to_string();
+    tokio::task::spawn_blocking(move || model()?.embed_text(&text)).await?
+}
+
+fn model() -> Result<Arc<Clip>> {
+    // held while loading, so the models are downloaded once
+    let mut model = MODEL
+        .lock()
+        .map_err(|_| anyhow!("clip model lock poisoned"))?;
+    if let Some(model) = model.as_ref() {
+        return Ok(Arc::clone(model));
+    }
+    let clip = Arc::new(Clip::load()?);
+    *model = Some(Arc::clone(&clip));
+    Ok(clip)
+}
+
+impl Clip {
+    fn load() -> Result<Self> {
+        debug!("loading clip models");
+        let image_path =
+            models::get_or_download_with_events(&ModelFile::huggingface(MODEL_REPO, IMAGE_MODEL))?;
+        let text_path =
+            models::get_or_download_with_events(&ModelFile::huggingface(MODEL_REPO, TEXT_MODEL))?;
+        let tokenizer_path =
+            models::get_or_download_with_events(&ModelFile::huggingface(MODEL_REPO, TOKENIZER))?;
+        Ok(Self {
+            image_encoder: session(image_path)?,
+            text_encoder: session(text_path)?,
+            tokenizer: Tokenizer::from_file(tokenizer_path)
+                .map_err(|e| anyhow!("failed to load the clip tokenizer: {}", e))?,
+        })
+    }
+
+    fn embed_image(&self, image: &RgbImage) -> Result<Vec<f32>> {
+        let outputs = self
+            .image_encoder
+            .run(ort::inputs!["pixel_values" => pixel_values(image)]?)?;
+        let embedding = outputs
+            .get("image_embeds")
+            .context("clip image embedding output not found")?
+            .try_extract_tensor::<f32>()?;
+        normalized(embedding.iter().copied().collect())
+    }
+
+    fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .map_err(|e| anyhow!("failed to tokenize {:?}: {}", text, e))?;
+        let mut ids: Vec<i64> = encoding.get_ids().iter().map(|&id| id as i64).collect();
+        if ids.len() > MAX_TOKENS {
+            // the end of text token is where the text encoder pools from
+            let end = ids[ids.len() - 1];
+            ids.truncate(MAX_TOKENS - 1);
+            ids.push(end);
+        }
+        let input_ids = Array2::from_shape_vec((1, ids.len()), ids)?;
+        let attention_mask = Array2::<i64>::ones(input_ids.raw_dim());
+
+        // some exports take the attention mask, others infer it
+        let outputs = if self
+            .text_encoder
+            .inputs
+            .iter()
+            .any(|input| input.name == "attention_mask")
+        {
+            self.text_encoder.run(ort::inputs![
+                "input_ids" => input_ids,
+                "attention_mask" => attention_mask
+            ]?)?
+        } else {
+            self.text_encoder
+                .run(ort::inputs!["input_ids" => input_ids]?)?
+        };
+        let embedding = outputs
+            .get("text_embeds")
+            .context("clip text embedding output not found")?
+            .try_extract_tensor::<f32>()?;
+        normalized(embedding.iter().copied().collect())
+    }
+}
+
+/// `image` fit in a square and normalized, 1 x 3 x 224 x 224. Screenshots are wide, a crop of
+/// their middle would lose their sides, so they are padded instead
+fn pixel_values(image: &RgbImage) -> Array4<f32> {
+    let (width, height) = image.dimensions();
+    let scale = IMAGE_SIZE as f32 / width.max(height).max(1) as f32;
+    let resized = imageops::resize(
+        image,
+        ((width as f32 * scale).round() as u32).clamp(1, IMAGE_SIZE),
+        ((height as f32 * scale).round() as u32).clamp(1, IMAGE_SIZE),
+        FilterType::CatmullRom,
+    );
+    let left = (IMAGE_SIZE - resized.width()) / 2;
+    let top = (IMAGE_SIZE - resized.height()) / 2;
+
+    // the padding is zero, the mean color once normalized
+    let size = IMAGE_SIZE as usize;
+    let mut input = Array4::<f32>::zeros((1, 3, size, size));
+    for (x, y, pixel) in resized.enumerate_pixels() {
+        for channel in 0..3 {
+            input[[0, channel, (top + y) as usize, (left + x) as usize]] =
+                (pixel[channel] as f32 / 255.0 - MEAN[channel]) / STD[channel];
+        }
+    }
+    input
+}
+
+fn normalized(mut embedding: Vec<f32>) -> Result<Vec<f32>> {
+    if embedding.len() != EMBEDDING_SIZE {
+        return Err(anyhow!(
+            "clip embedding of {} dimensions instead of {}",
+            embedding.len(),
+            EMBEDDING_SIZE
+        ));
+    }
+    let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        embedding.iter_mut().for_each(|x| *x /= norm);
+    }
+    Ok(embedding)
+}
+
+fn session(path: std::path::PathBuf) -> Result<Session> {
+    Ok(Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .commit_from_file(path)?)
+}
@@ -1,20 +1,34 @@
+use crate::accessibility::{focused_window_tree, AccessibilityTree};
+use crate::active_window::{get_active_app, ActiveApp};
+use crate::adaptive_fps::{AdaptiveFps, AdaptiveFpsConfig};
 #[cfg(target_os = "macos")]
 use crate::apple::perform_ocr_apple;
+use crate::browser_tab::{get_browser_tab, is_browser, BrowserTab};
 use crate::capture_screenshot_by_window::CapturedWindow;
+use crate::changed_regions::{self, PreviousOcr};
+use crate::cursor::{self, CursorOverlay};
 use crate::capture_screenshot_by_window::WindowFilters;
 use crate::custom_ocr::perform_ocr_custom;
+use crate::frame_hash::FrameHash;
+use crate::frame_scale::{resized, FrameScale};
 #[cfg(target_os = "windows")]
 use crate::microsoft::perform_ocr_windows;
-use crate::monitor::get_monitor_by_id;
+use crate::monitor::{get_monitor_by_id, MonitorData, SafeMonitor};
+use crate::ocr_filter;
+use crate::ocr_layout::OcrLayout;
+use crate::ocr_preprocess;
+use crate::paddle_ocr::perform_ocr_paddle;
 use crate::tesseract::perform_ocr_tesseract;
 use crate::utils::OcrEngine;
 use crate::utils::{capture_screenshot, compare_with_previous_image};
+use crate::video_call::{capture_interval, is_in_call};
+use crate::virtual_desktop::current_desktop;
 use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose, Engine as _};
 use image::codecs::jpeg::JpegEncoder;
 use image::DynamicImage;
 use log::{debug, error};
-use screenpipe_core::Language;
+use screenpipe_core::{capture_pause, Language};
 use screenpipe_integrations::unstructured_ocr::perform_ocr_cloud;
 use serde::Deserialize;
 use serde::Deserializer;
@@ -105,7 +119,23 @@ pub struct CaptureResult {
     pub image: DynamicImage,
     pub frame_number: u64,
     pub timestamp: Instant,
+    /// Monitor the frame was captured on, as it was when captured
+    pub monitor: MonitorData,
     pub window_ocr_results: Vec<WindowOcrResult>,
+    /// Same as the previous frame sent, not OCRed
+    pub duplicate: bool,
+    /// Active tab of the browser focused on the monitor, when one is
+    pub browser_tab: Option<BrowserTab>,
+    /// App focused when the frame was captured, on any monitor
+    pub active_app: Option<ActiveApp>,
+    /// Whether a video call was going on when the frame was captured
+    pub in_call: bool,
+    /// Virtual desktop shown on the monitor, from 1, when the os tells
+    pub virtual_desktop: Option<u32>,
+    /// Pointer and selection on the frame, when reading them is enabled
+    pub cursor: Option<CursorOverlay>,
+    /// UI elements of the focused window, when reading them is enabled
+    pub accessibility_tree: Option<AccessibilityTree>,
 }
 
 pub struct WindowOcrResult {
@@ -114,6 +144,8 @@ pub struct WindowOcrResult {
     pub app_name: String,
     pub text: String,
     pub text_json: Vec<HashMap<String, String>>, // Change this line
+    /// The words of `text_json` in blocks and lines, with their boxes
+    pub layout: OcrLayout,
     pub focused: bool,
     pub confidence: f64,
 }
@@ -123,6 +155,13 @@ pub struct OcrTaskData {
     pub window_images: Vec<CapturedWindow>,
     pub frame_number: u64,
     pub timestamp: Instant,
+    pub monitor: MonitorData,
+    pub browser_tab: Option<BrowserTab>,
+    pub active_app: Option<ActiveApp>,
+    pub in_call: bool,
+    pub virtual_desktop: Option<u32>,
+    pub cursor: Option<CursorOverlay>,
+    pub accessibility_tree: Option<AccessibilityTree>,
     pub result_tx: Sender<CaptureResult>,
 }
 
@@ -134,11 +173,21 @@ pub async fn continuous_capture(
     window_filters: Arc<WindowFilters>,
     languages: Vec<Language>,
     capture_unfocused_windows: bool,
+    adaptive_fps: Option<AdaptiveFpsConfig>,
+    frame_dedup: bool,
+    accessibility_tree: bool,
+    ocr_scale: FrameScale,
 ) {
+    let mut adaptive_fps =
+        adaptive_fps.map(|config| AdaptiveFps::new(1.0 / interval.as_secs_f64(), config));
+    let mut interval = interval;
     let mut frame_counter: u64 = 0;
     let mut previous_image: Option<DynamicImage> = None;
     let mut max_average: Option<MaxAverageFrame> = None;
     let mut max_avg_value = 0.0;
+    // of the last frame OCRed
+    let mut previous_hash: Option<FrameHash> = None;
+    let mut previous_browser_tab: Option<BrowserTab> = None;
 
     debug!(
         "continuous_capture: Starting using monitor: {:?}",
@@ -146,6 +195,27 @@ pub async fn continuous_capture(
     );
 
     loop {
+        // idle or locked, the same frame would be stored until the user is back
+        if capture_pause::is_paused() {
+            tokio::time::sleep(interval).await;
+            continue;
+        }
+
+        let active_app = tokio::task::spawn_blocking(get_active_app)
+            .await
+            .ok()
+            .flatten();
+        if !window_filters.app_filters().is_allowed(active_app.as_ref()) {
+            debug!(
+                "Skipping frame {}, {:?} is not recorded",
+                frame_counter,
+                active_app.map(|app| app.name)
+            );
+            tokio::time::sleep(capture_interval(interval)).await;
+            continue;
+        }
+        let (active_app, focus_sensitive) = hide_sensitive_title(&window_filters, active_app).await;
+
         let monitor = match get_monitor_by_id(monitor_id).await {
             Some(m) => m,
             None => {
@@ -155,13 +225,17 @@ pub async fn continuous_capture(
         };
         let capture_result =
             match capture_screenshot(&monitor, &window_filters, capture_unfocused_windows).await {
-                Ok((image, window_images, image_hash, _capture_duration)) => {
+                Ok(Some((image, window_images, image_hash, _capture_duration))) => {
                     debug!(
                         "Captured screenshot on monitor {} with hash: {}",
                         monitor_id, image_hash
                     );
                     Some((image, window_images, image_hash))
                 }
+                Ok(None) => {
+                    debug!("Captured window is not on monitor {}", monitor_id);
+                    None
+                }
                 Err(e) => {
                     error!("Failed to capture screenshot: {}", e);
                     None
@@ -169,6 +243,50 @@ pub async fn continuous_capture(
             };
 
         if let Some((image, window_images, image_hash)) = capture_result {
+            let in_call = tokio::task::spawn_blocking(is_in_call).await.unwrap_or(false);
+            let virtual_desktop = tokio::task::spawn_blocking(move || current_desktop(monitor_id))
+                .await
+                .ok()
+                .flatten();
+            let cursor = read_cursor(&monitor, focus_sensitive).await;
+            let frame_hash = frame_dedup.then(|| FrameHash::new(&image));
+            if let (Some(frame_hash), Some(previous_hash)) = (&frame_hash, &previous_hash) {
+                if frame_hash.is_same_as(previous_hash) {
+                    debug!(
+                        "Frame {} is the same as the last one OCRed, sending it as a duplicate",
+                        frame_counter
+                    );
+                    if let Some(adaptive_fps) = adaptive_fps.as_mut() {
+                        interval = adaptive_fps.update(0.0);
+                    }
+                    let duplicate = CaptureResult {
+                        image,
+                        frame_number: frame_counter,
+                        timestamp: Instant::now(),
+                        monitor: monitor.get_info(),
+                        window_ocr_results: Vec::new(),
+                        duplicate: true,
+                        // the same pixels, the same address bar
+                        browser_tab: if focus_sensitive {
+                            None
+                        } else {
+                            previous_browser_tab.clone()
+                        },
+                        active_app,
+                        in_call,
+                        virtual_desktop,
+                        cursor,
+                        accessibility_tree: None,
+                    };
+                    if let Err(e) = result_tx.send(duplicate).await {
+                        error!("Failed to send duplicate frame: {}", e);
+                    }
+                    frame_counter += 1;
+                    tokio::time::sleep(capture_interval(interval)).await;
+                    continue;
+                }
+            }
+
             let current_average = match compare_with_previous_image(
                 previous_image.as_ref(),
                 &image,
@@ -191,6 +309,9 @@ pub async fn continuous_capture(
             } else {
                 current_average
             };
+            if let Some(adaptive_fps) = adaptive_fps.as_mut() {
+                interval = adaptive_fps.update(current_average);
+            }
 
             if current_average < 0.006 {
                 debug!(
@@ -198,17 +319,35 @@ pub async fn continuous_capture(
                     frame_counter, current_average
                 );
                 frame_counter += 1;
-                tokio::time::sleep(interval).await;
+                tokio::time::sleep(capture_interval(interval)).await;
                 continue;
             }
 
             if current_average > max_avg_value {
+                // asking the browser is slow, only for the frames that are stored
+                let browser_tab = if focus_sensitive {
+                    None
+                } else {
+                    focused_browser_tab(&window_images).await
+                };
+                let accessibility_tree = if accessibility_tree {
+                    read_accessibility_tree(&window_images, active_app.clone()).await
+                } else {
+                    None
+                };
                 max_average = Some(MaxAverageFrame {
                     image: image.clone(),
                     window_images: window_images.clone(),
                     image_hash,
                     frame_number: frame_counter,
                     timestamp: Instant::now(),
+                    monitor: monitor.get_info(),
+                    browser_tab,
+                    active_app,
+                    in_call,
+                    virtual_desktop,
+                    cursor,
+                    accessibility_tree,
                     result_tx: result_tx.clone(),
                     average: current_average,
                 });
@@ -218,11 +357,31 @@ pub async fn continuous_capture(
             previous_image = Some(image);
 
             if let Some(max_avg_frame) = max_average.take() {
+                // windows shrunk as much as the frame, for their text to be read at the same size
+                let factor =
+                    ocr_scale.factor(max_avg_frame.image.width(), max_avg_frame.image.height());
+                let window_images = max_avg_frame
+                    .window_images
+                    .into_iter()
+                    .map(|mut window| {
+                        if let Some(image) = resized(&window.image, factor) {
+                            window.image = image;
+                        }
+                        window
+                    })
+                    .collect();
                 let ocr_task_data = OcrTaskData {
                     image: max_avg_frame.image,
-                    window_images: max_avg_frame.window_images,
+                    window_images,
                     frame_number: max_avg_frame.frame_number,
                     timestamp: max_avg_frame.timestamp,
+                    monitor: max_avg_frame.monitor,
+                    browser_tab: max_avg_frame.browser_tab.clone(),
+                    active_app: max_avg_frame.active_app,
+                    in_call: max_avg_frame.in_call,
+                    virtual_desktop: max_avg_frame.virtual_desktop,
+                    cursor: max_avg_frame.cursor,
+                    accessibility_tree: max_avg_frame.accessibility_tree,
                     result_tx: max_avg_frame.result_tx,
                 };
 
@@ -231,6 +390,8 @@ pub async fn continuous_capture(
                 {
                     error!("Error processing OCR task: {}", e);
                 }
+                previous_hash = frame_hash;
+                previous_browser_tab = max_avg_frame.browser_tab;
 
                 frame_counter = 0;
                 max_avg_value = 0.0;
@@ -240,8 +401,132 @@ pub async fn continuous_capture(
         }
 
         frame_counter += 1;
-        tokio::time::sleep(interval).await;
+        // shorter during meetings
+        tokio::time::sleep(capture_interval(interval)).await;
+    }
+}
+
+/// Captures `monitor` and OCRs it right away, for what is on screen now rather than in the next
+/// frame recorded. None when the window of `window_filters` isn't on it or the focused app isn't
+/// recorded
+pub async fn capture_frame(
+    monitor: &SafeMonitor,
+    window_filters: &WindowFilters,
+    ocr_engine: &OcrEngine,
+    languages: Vec<Language>,
+    capture_unfocused_windows: bool,
+) -> Result<Option<CaptureResult>> {
+    let active_app = tokio::task::spawn_blocking(get_active_app)
+        .await
+        .ok()
+        .flatten();
+    if !window_filters.app_filters().is_allowed(active_app.as_ref()) {
+        return Ok(None);
+    }
+    let (active_app, focus_sensitive) = hide_sensitive_title(window_filters, active_app).await;
+    let Some((image, window_images, _, _)) =
+        capture_screenshot(monitor, window_filters, capture_unfocused_windows).await?
+    else {
+        return Ok(None);
+    };
+    let browser_tab = if focus_sensitive {
+        None
+    } else {
+        focused_browser_tab(&window_images).await
+    };
+    let in_call = tokio::task::spawn_blocking(is_in_call).await.unwrap_or(false);
+    let monitor_id = monitor.id();
+    let virtual_desktop = tokio::task::spawn_blocking(move || current_desktop(monitor_id))
+        .await
+        .ok()
+        .flatten();
+    let cursor = read_cursor(monitor, focus_sensitive).await;
+
+    let (result_tx, mut result_rx) = tokio::sync::mpsc::channel(1);
+    let ocr_task_data = OcrTaskData {
+        image,
+        window_images,
+        frame_number: 0,
+        timestamp: Instant::now(),
+        monitor: monitor.get_info(),
+        browser_tab,
+        active_app,
+        in_call,
+        virtual_desktop,
+        cursor,
+        accessibility_tree: None,
+        result_tx,
+    };
+    process_ocr_task(ocr_task_data, ocr_engine, languages).await?;
+    Ok(result_rx.recv().await)
+}
+
+/// `active_app` without its window title when the window is sensitive, and whether it is
+async fn hide_sensitive_title(
+    window_filters: &WindowFilters,
+    active_app: Option<ActiveApp>,
+) -> (Option<ActiveApp>, bool) {
+    let Some(mut app) = active_app else {
+        return (None, false);
+    };
+    let sensitive_windows = window_filters.sensitive_windows().clone();
+    if sensitive_windows.is_empty() {
+        return (Some(app), false);
+    }
+    match tokio::task::spawn_blocking(move || {
+        let sensitive = sensitive_windows.hide_title(&mut app);
+        (app, sensitive)
+    })
+    .await
+    {
+        Ok((app, sensitive)) => (Some(app), sensitive),
+        // whether it is sensitive is unknown, nothing of the window is kept
+        Err(_) => (None, true),
+    }
+}
+
+/// Active tab of the focused window of `window_images`, when it is a browser's
+async fn focused_browser_tab(window_images: &[CapturedWindow]) -> Option<BrowserTab> {
+    let focused = window_images.iter().find(|window| window.is_focused)?;
+    if !is_browser(&focused.app_name) {
+        return None;
+    }
+    let (app_name, window_name) = (focused.app_name.clone(), focused.window_name.clone());
+    tokio::task::spawn_blocking(move || get_browser_tab(&app_name, &window_name))
+        .await
+        .ok()
+        .flatten()
+}
+
+/// UI elements of the focused window, when it is one of `window_images`
+async fn read_accessibility_tree(
+    window_images: &[CapturedWindow],
+    active_app: Option<ActiveApp>,
+) -> Option<AccessibilityTree> {
+    let active_app = active_app?;
+    let focused = window_images
+        .iter()
+        .any(|window| window.is_focused && window.app_name == active_app.name);
+    if !focused {
+        return None;
     }
+    tokio::task::spawn_blocking(move || focused_window_tree(&active_app))
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Pointer and selection on `monitor`, when reading them is enabled. The selection of a sensitive
+/// focused window isn't read
+async fn read_cursor(monitor: &SafeMonitor, focus_sensitive: bool) -> Option<CursorOverlay> {
+    if !cursor::is_enabled() {
+        return None;
+    }
+    let monitor = monitor.get_info();
+    tokio::task::spawn_blocking(move || cursor::current_overlay(&monitor, !focus_sensitive))
+        .await
+        .ok()
+        .flatten()
 }
 
 pub struct MaxAverageFrame {
@@ -250,6 +535,13 @@ pub struct MaxAverageFrame {
     pub image_hash: u64,
     pub frame_number: u64,
     pub timestamp: Instant,
+    pub monitor: MonitorData,
+    pub browser_tab: Option<BrowserTab>,
+    pub active_app: Option<ActiveApp>,
+    pub in_call: bool,
+    pub virtual_desktop: Option<u32>,
+    pub cursor: Option<CursorOverlay>,
+    pub accessibility_tree: Option<AccessibilityTree>,
     pub result_tx: Sender<CaptureResult>,
     pub average: f64,
 }
@@ -264,6 +556,13 @@ pub async fn process_ocr_task(
         window_images,
         frame_number,
         timestamp,
+        monitor,
+        browser_tab,
+        active_app,
+        in_call,
+        virtual_desktop,
+        cursor,
+        accessibility_tree,
         result_tx,
     } = ocr_task_data;
 
@@ -278,43 +577,27 @@ pub async fn process_ocr_task(
     let mut window_count = 0;
 
     for captured_window in window_images {
-        let (window_text, window_json_output, confidence) = match ocr_engine {
-            OcrEngine::Unstructured => perform_ocr_cloud(&captured_window.image, languages.clone())
-                .await
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
-            OcrEngine::Tesseract => {
-                perform_ocr_tesseract(&captured_window.image, languages.clone())
-            }
-            #[cfg(target_os = "windows")]
-            OcrEngine::WindowsNative => perform_ocr_windows(&captured_window.image)
-                .await
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
-            #[cfg(target_os = "macos")]
-            OcrEngine::AppleNative => perform_ocr_apple(&captured_window.image, &languages),
-            OcrEngine::Custom(config) => {
-                perform_ocr_custom(&captured_window.image, languages.clone(), config)
-                    .await
-                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
-            }
-            _ => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Unsupported OCR engine",
-                ))
-            }
-        };
+        let (window_text, text_json, confidence) =
+            ocr_window(&captured_window, ocr_engine, &languages).await?;
 
         if let Some(conf) = confidence {
             total_confidence += conf;
             window_count += 1;
         }
+        let layout = OcrLayout::from_words(
+            &text_json,
+            captured_window.image.width(),
+            captured_window.image.height(),
+            matches!(ocr_engine, OcrEngine::AppleNative),
+        );
 
         window_ocr_results.push(WindowOcrResult {
             image: captured_window.image,
             window_name: captured_window.window_name,
             app_name: captured_window.app_name,
             text: window_text,
-            text_json: parse_json_output(&window_json_output),
+            text_json,
+            layout,
             focused: captured_window.is_focused,
             confidence: confidence.unwrap_or(0.0),
         });
@@ -324,7 +607,15 @@ pub async fn process_ocr_task(
         image,
         frame_number,
         timestamp,
+        monitor,
         window_ocr_results,
+        duplicate: false,
+        browser_tab,
+        active_app,
+        in_call,
+        virtual_desktop,
+        cursor,
+        accessibility_tree,
     };
 
     if let Err(e) = result_tx.send(capture_result).await {
@@ -355,6 +646,141 @@ pub async fn process_ocr_task(
     Ok(())
 }
 
+/// Text, words and confidence read in `window`, filtered. Only the rows that changed since the
+/// window was last OCRed are read again when changed regions are enabled, the words of the rest
+/// are the ones read then
+async fn ocr_window(
+    window: &CapturedWindow,
+    ocr_engine: &OcrEngine,
+    languages: &[Language],
+) -> Result<(String, Vec<HashMap<String, String>>, Option<f64>), std::io::Error> {
+    let normalized = matches!(ocr_engine, OcrEngine::AppleNative);
+    let engine = format!("{:?}", ocr_engine);
+    let (width, height) = (window.image.width(), window.image.height());
+    let previous = changed_regions::is_enabled()
+        .then(|| changed_regions::previous(&window.app_name, &window.window_name))
+        .flatten()
+        .filter(|previous| {
+            previous.engine == engine && (previous.width, previous.height) == (width, height)
+        });
+
+    if let Some(previous) = previous {
+        let bands = changed_regions::band_hashes(&window.image);
+        let changed = changed_regions::changed_rows(&previous.bands, &bands, height);
+        if let Some(rows) =
+            changed_regions::rows_to_ocr(&changed, &previous.words, height, normalized)
+        {
+            let mut rows_words = Vec::with_capacity(rows.len());
+            let mut confidences = Vec::new();
+            for &(top, bottom) in &rows {
+                let row = window.image.crop_imm(0, top, width, bottom - top);
+                let (text, json_output, confidence) =
+                    ocr_image(&row, ocr_engine, languages).await?;
+                let (_, words) = ocr_filter::filter_words(
+                    &ocr_filter::current(),
+                    text,
+                    parse_json_output(&json_output),
+                );
+                rows_words.push(words);
+                confidences.extend(confidence);
+            }
+            let merges = previous.merges + 1;
+            let words = changed_regions::merge_words(
+                &previous.words,
+                &rows,
+                rows_words,
+                height,
+                normalized,
+                merges,
+            );
+            let text = if rows.is_empty() {
+                previous.text.clone()
+            } else {
+                OcrLayout::from_words(&words, width, height, normalized).text()
+            };
+            let confidence = if confidences.is_empty() {
+                previous.confidence
+            } else {
+                Some(confidences.iter().sum::<f64>() / confidences.len() as f64)
+            };
+            debug!(
+                "OCRed {} changed rows of {} instead of the whole window",
+                rows.len(),
+                window.app_name
+            );
+            changed_regions::save(
+                &window.app_name,
+                &window.window_name,
+                PreviousOcr::new(
+                    engine,
+                    &window.image,
+                    text.clone(),
+                    words.clone(),
+                    confidence,
+                    merges,
+                ),
+            );
+            return Ok((text, words, confidence));
+        }
+    }
+
+    let (text, json_output, confidence) = ocr_image(&window.image, ocr_engine, languages).await?;
+    let (text, words) = ocr_filter::filter_words(
+        &ocr_filter::current(),
+        text,
+        parse_json_output(&json_output),
+    );
+    if changed_regions::is_enabled() {
+        changed_regions::save(
+            &window.app_name,
+            &window.window_name,
+            PreviousOcr::new(
+                engine,
+                &window.image,
+                text.clone(),
+                words.clone(),
+                confidence,
+                0,
+            ),
+        );
+    }
+    Ok((text, words, confidence))
+}
+
+/// Text, json output of the words and confidence read in `image` by `ocr_engine`
+async fn ocr_image(
+    image: &DynamicImage,
+    ocr_engine: &OcrEngine,
+    languages: &[Language],
+) -> Result<(String, String, Option<f64>), std::io::Error> {
+    let preprocessed = ocr_preprocess::preprocess(image, ocr_preprocess::current());
+    let image = preprocessed.as_ref().unwrap_or(image);
+    Ok(match ocr_engine {
+        OcrEngine::Unstructured => perform_ocr_cloud(image, languages.to_vec())
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
+        OcrEngine::Tesseract => perform_ocr_tesseract(image, languages.to_vec()),
+        #[cfg(target_os = "windows")]
+        OcrEngine::WindowsNative => perform_ocr_windows(image)
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
+        #[cfg(target_os = "macos")]
+        OcrEngine::AppleNative => perform_ocr_apple(image, languages),
+        OcrEngine::PaddleOcr => perform_ocr_paddle(image, languages.to_vec())
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
+        OcrEngine::Custom(config) => perform_ocr_custom(image, languages.to_vec(), config)
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "Unsupported OCR engine",
+            ))
+        }
+    })
+}
+
 fn parse_json_output(json_output: &str) -> Vec<HashMap<String, String>> {
     let parsed_output: Vec<HashMap<String, String>> = serde_json::from_str(json_output)
         .unwrap_or_else(|e| {
@@ -0,0 +1,182 @@
+use crate::accessibility::{focused_selection, Bounds, Selection};
+use crate::monitor::MonitorData;
+use crate::ocr_layout::LayoutBox;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+
+static ENABLED: OnceCell<bool> = OnceCell::new();
+
+/// Read where the pointer and the selection are with every frame, off until enabled
+pub fn init(enabled: bool) {
+    let _ = ENABLED.set(enabled);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.get().copied().unwrap_or(false)
+}
+
+/// Where the attention of the user was on a frame, in fractions of its width and height from its
+/// top left corner so it applies to the frame at any scale. Kept beside the frame, never drawn
+/// on it
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CursorOverlay {
+    /// Pointer, None when it was on another monitor
+    pub cursor_x: Option<f64>,
+    pub cursor_y: Option<f64>,
+    /// Text selected in the focused window
+    pub selected_text: Option<String>,
+    /// Where the selection is highlighted, when the os tells
+    pub selection: Option<LayoutBox>,
+}
+
+impl CursorOverlay {
+    /// The pointer at `position` and `selection`, in points on the virtual screen, on a frame of
+    /// `monitor`. A selection the os doesn't tell the position of goes with the frames of the
+    /// monitor the pointer is on. None when neither is on the monitor
+    pub fn on_monitor(
+        position: Option<(f64, f64)>,
+        selection: Option<Selection>,
+        monitor: &MonitorData,
+    ) -> Option<Self> {
+        let cursor = position.and_then(|(x, y)| {
+            fraction_of(
+                monitor,
+                &Bounds {
+                    x,
+                    y,
+                    width: 0.0,
+                    height: 0.0,
+                },
+            )
+        });
+        let (selected_text, selection) = match selection {
+            Some(Selection {
+                text,
+                bounds: Some(bounds),
+            }) => match fraction_of(monitor, &bounds) {
+                Some(selection) => (Some(text), Some(selection)),
+                None => (None, None),
+            },
+            Some(Selection { text, bounds: None }) if cursor.is_some() => (Some(text), None),
+            _ => (None, None),
+        };
+        if cursor.is_none() && selected_text.is_none() {
+            return None;
+        }
+        Some(Self {
+            cursor_x: cursor.map(|cursor| cursor.left),
+            cursor_y: cursor.map(|cursor| cursor.top),
+            selected_text,
+            selection,
+        })
+    }
+}
+
+/// `bounds` in fractions of `monitor`, None when its top left corner isn't on it
+fn fraction_of(monitor: &MonitorData, bounds: &Bounds) -> Option<LayoutBox> {
+    let (width, height) = (monitor.width.max(1) as f64, monitor.height.max(1) as f64);
+    let left = (bounds.x - monitor.x as f64) / width;
+    let top = (bounds.y - monitor.y as f64) / height;
+    ((0.0..1.0).contains(&left) && (0.0..1.0).contains(&top)).then(|| LayoutBox {
+        left,
+        top,
+        width: (bounds.width / width).min(1.0 - left),
+        height: (bounds.height / height).min(1.0 - top),
+    })
+}
+
+/// Pointer and selection on `monitor` now, the selection left out when `read_selection` is
+/// false, e.g. in sensitive windows. Blocks, it may run a command on linux
+pub fn current_overlay(monitor: &MonitorData, read_selection: bool) -> Option<CursorOverlay> {
+    let selection = if read_selection {
+        focused_selection()
+    } else {
+        None
+    };
+    CursorOverlay::on_monitor(cursor_position(), selection, monitor)
+}
+
+/// Position of the pointer on the virtual screen spanning all monitors, in points
+pub fn cursor_position() -> Option<(f64, f64)> {
+    platform::cursor_position()
+}
+
+/// Position in the output of `xdotool getmouselocation --shell`, like "X=812\nY=440\nSCREEN=0"
+pub fn parse_xdotool_location(output: &str) -> Option<(f64, f64)> {
+    let value = |key: &str| {
+        output
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(key)?.strip_prefix('='))?
+            .parse::<f64>()
+            .ok()
+    };
+    Some((value("X")?, value("Y")?))
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use core_foundation::base::CFRelease;
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventCreate(source: *const c_void) -> *const c_void;
+        fn CGEventGetLocation(event: *const c_void) -> CGPoint;
+    }
+
+    /// From an empty event, which gets the location of the pointer
+    pub fn cursor_position() -> Option<(f64, f64)> {
+        unsafe {
+            let event = CGEventCreate(std::ptr::null());
+            if event.is_null() {
+                return None;
+            }
+            let location = CGEventGetLocation(event);
+            CFRelease(event);
+            Some((location.x, location.y))
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use windows::Win32::Foundation::POINT;
+    use windows::Win32::UI::WindowsAndMessaging::GetCursorPos;
+
+    pub fn cursor_position() -> Option<(f64, f64)> {
+        let mut point = POINT::default();
+        unsafe { GetCursorPos(&mut point) }.ok()?;
+        Some((point.x as f64, point.y as f64))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::parse_xdotool_location;
+    use std::process::Command;
+
+    /// From `xdotool`, on x11
+    pub fn cursor_position() -> Option<(f64, f64)> {
+        let output = Command::new("xdotool")
+            .args(["getmouselocation", "--shell"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_xdotool_location(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod platform {
+    pub fn cursor_position() -> Option<(f64, f64)> {
+        None
+    }
+}
@@ -0,0 +1,41 @@
+use image::DynamicImage;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Rows of pixels hashed together, so a hash tells which band of the frame changed
+const BAND_ROWS: usize = 16;
+
+/// Hash of the exact pixels of a frame, by bands of rows. Screenshots are lossless, so frames
+/// looking the same have the same pixels, while any downsampled or tolerant hash lets a few typed
+/// characters through as a duplicate and their text would never be OCRed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameHash {
+    width: u32,
+    height: u32,
+    bands: Vec<u64>,
+}
+
+impl FrameHash {
+    pub fn new(image: &DynamicImage) -> Self {
+        let bytes = image.as_bytes();
+        let row_len = bytes.len() / (image.height().max(1) as usize);
+        let bands = bytes
+            .chunks(row_len.max(1) * BAND_ROWS)
+            .map(|band| {
+                let mut hasher = DefaultHasher::new();
+                band.hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect();
+        Self {
+            width: image.width(),
+            height: image.height(),
+            bands,
+        }
+    }
+
+    /// Whether the frames have the same pixels
+    pub fn is_same_as(&self, other: &FrameHash) -> bool {
+        self == other
+    }
+}
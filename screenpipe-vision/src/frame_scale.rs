@@ -0,0 +1,73 @@
+use image::{imageops::FilterType, DynamicImage, GenericImageView};
+use std::str::FromStr;
+
+/// How much frames are shrunk before being OCRed or stored, large displays make frames bigger
+/// than OCR or the video need
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FrameScale {
+    /// As captured
+    #[default]
+    Full,
+    /// Width and height multiplied by a factor between 0 and 1
+    Factor(f64),
+    /// Shrunk for the longest side to be at most this many pixels
+    MaxDimension(u32),
+}
+
+impl FrameScale {
+    /// Factor a frame of `width` x `height` is resized by, at most 1
+    pub fn factor(&self, width: u32, height: u32) -> f64 {
+        match *self {
+            FrameScale::Full => 1.0,
+            FrameScale::Factor(factor) => factor.min(1.0),
+            FrameScale::MaxDimension(max) => {
+                (max as f64 / width.max(height).max(1) as f64).min(1.0)
+            }
+        }
+    }
+
+    /// `image` scaled, None when it keeps its size
+    pub fn apply(&self, image: &DynamicImage) -> Option<DynamicImage> {
+        resized(image, self.factor(image.width(), image.height()))
+    }
+}
+
+/// `image` resized by `factor`, None when it keeps its size
+pub fn resized(image: &DynamicImage, factor: f64) -> Option<DynamicImage> {
+    if factor >= 1.0 {
+        return None;
+    }
+    let width = ((image.width() as f64 * factor).round() as u32).max(1);
+    let height = ((image.height() as f64 * factor).round() as u32).max(1);
+    if (width, height) == image.dimensions() {
+        return None;
+    }
+    Some(image.resize_exact(width, height, FilterType::Triangle))
+}
+
+/// "full", a factor like "0.5" or a max dimension like "1920px"
+impl FromStr for FrameScale {
+    type Err = String;
+
+    fn from_str(scale: &str) -> Result<Self, Self::Err> {
+        let scale = scale.trim();
+        if scale.eq_ignore_ascii_case("full") {
+            return Ok(FrameScale::Full);
+        }
+        if let Some(max) = scale.strip_suffix("px") {
+            return match max.trim().parse::<u32>() {
+                Ok(max) if max > 0 => Ok(FrameScale::MaxDimension(max)),
+                _ => Err(format!("invalid max dimension {:?}", scale)),
+            };
+        }
+        match scale.parse::<f64>() {
+            Ok(factor) if factor == 1.0 => Ok(FrameScale::Full),
+            Ok(factor) if factor > 0.0 && factor < 1.0 => Ok(FrameScale::Factor(factor)),
+            _ => Err(format!(
+                "invalid scale {:?}, expected full, a factor between 0 and 1 or a max \
+                 dimension like 1920px",
+                scale
+            )),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+/// Time since the last keypress or mouse move, None when the os doesn't tell. Blocks, it may
+/// run a command on linux
+pub fn idle_time() -> Option<Duration> {
+    platform::idle_time()
+}
+
+/// Whether the screen is locked or the screensaver is on. Blocks, it may run a command
+pub fn is_screen_locked() -> bool {
+    platform::is_screen_locked()
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use core_foundation::base::{CFType, TCFType};
+    use core_foundation::boolean::CFBoolean;
+    use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+    use core_foundation::string::CFString;
+    use std::process::Command;
+    use std::time::Duration;
+
+    const HID_SYSTEM_STATE: i32 = 1;
+    const ANY_INPUT_EVENT: u32 = u32::MAX;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventSourceSecondsSinceLastEventType(state: i32, event_type: u32) -> f64;
+        fn CGSessionCopyCurrentDictionary() -> CFDictionaryRef;
+    }
+
+    pub fn idle_time() -> Option<Duration> {
+        let seconds =
+            unsafe { CGEventSourceSecondsSinceLastEventType(HID_SYSTEM_STATE, ANY_INPUT_EVENT) };
+        (seconds.is_finite() && seconds >= 0.0).then(|| Duration::from_secs_f64(seconds))
+    }
+
+    pub fn is_screen_locked() -> bool {
+        let session = unsafe { CGSessionCopyCurrentDictionary() };
+        if session.is_null() {
+            // no session when the user is switched out
+            return true;
+        }
+        let session: CFDictionary<CFString, CFType> =
+            unsafe { CFDictionary::wrap_under_create_rule(session) };
+        let locked = session
+            .find(CFString::from_static_string("CGSSessionScreenIsLocked"))
+            .and_then(|value| value.downcast::<CFBoolean>())
+            .map(bool::from)
+            .unwrap_or(false);
+        locked
+            || Command::new("pgrep")
+                .args(["-x", "ScreenSaverEngine"])
+                .output()
+                .map(|output| output.status.success())
+                .unwrap_or(false)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::time::Duration;
+    use windows::Win32::System::StationsAndDesktops::{
+        CloseDesktop, OpenInputDesktop, DESKTOP_CONTROL_FLAGS, DESKTOP_SWITCHDESKTOP,
+    };
+    use windows::Win32::System::SystemInformation::GetTickCount;
+    use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SystemParametersInfoW, SPI_GETSCREENSAVERRUNNING, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+    };
+
+    pub fn idle_time() -> Option<Duration> {
+        let mut info = LASTINPUTINFO {
+            cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+            dwTime: 0,
+        };
+        unsafe {
+            if !GetLastInputInfo(&mut info).as_bool() {
+                return None;
+            }
+            // both wrap after 49 days
+            Some(Duration::from_millis(
+                GetTickCount().wrapping_sub(info.dwTime) as u64,
+            ))
+        }
+    }
+
+    pub fn is_screen_locked() -> bool {
+        // the desktop taking input is the secure one while locked, it can't be opened
+        let locked = match unsafe {
+            OpenInputDesktop(DESKTOP_CONTROL_FLAGS(0), false, DESKTOP_SWITCHDESKTOP)
+        } {
+            Ok(desktop) => {
+                let _ = unsafe { CloseDesktop(desktop) };
+                false
+            }
+            Err(_) => true,
+        };
+        let mut screensaver = windows::Win32::Foundation::BOOL(0);
+        let _ = unsafe {
+            SystemParametersInfoW(
+                SPI_GETSCREENSAVERRUNNING,
+                0,
+                Some(&mut screensaver as *mut _ as *mut _),
+                SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+            )
+        };
+        locked || screensaver.as_bool()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::process::Command;
+    use std::time::Duration;
+
+    /// From `xprintidle`, on x11
+    pub fn idle_time() -> Option<Duration> {
+        let output = Command::new("xprintidle").output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .ok()
+            .map(Duration::from_millis)
+    }
+
+    /// From logind, which the lock screens of the desktops tell
+    pub fn is_screen_locked() -> bool {
+        let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".to_string());
+        Command::new("loginctl")
+            .args(["show-session", &session, "-p", "LockedHint", "--value"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "yes")
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod platform {
+    use std::time::Duration;
+
+    pub fn idle_time() -> Option<Duration> {
+        None
+    }
+
+    pub fn is_screen_locked() -> bool {
+        false
+    }
+}
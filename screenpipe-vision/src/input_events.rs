@@ -0,0 +1,221 @@
+use crate::accessibility::{element_at, UiElement};
+use crate::active_window::get_active_app;
+use crate::sensitive_windows::SensitiveWindows;
+use rdev::{listen, Button, EventType};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc::Sender;
+use tracing::{debug, error};
+
+/// How long keypresses and scrolls are counted for before being sent as a single event
+const COUNT_WINDOW: Duration = Duration::from_secs(5);
+
+/// What the user did, never the keys typed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InputActivity {
+    /// A mouse button pressed at `x`, `y` on the screen, over `element`
+    Click {
+        x: f64,
+        y: f64,
+        button: String,
+        element: Option<UiElement>,
+    },
+    /// Keys pressed since the event
+    Keys { count: u32 },
+    /// Wheel ticks since the event, and the distance scrolled
+    Scroll {
+        count: u32,
+        delta_x: i64,
+        delta_y: i64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InputEvent {
+    pub timestamp: SystemTime,
+    pub activity: InputActivity,
+    /// App and window focused, at the first keypress or scroll of the ones counted
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+}
+
+/// What the listener passes on, the key pressed is dropped right away
+enum RawInput {
+    Click {
+        timestamp: SystemTime,
+        x: f64,
+        y: f64,
+        button: Button,
+    },
+    Key(SystemTime),
+    Wheel {
+        timestamp: SystemTime,
+        delta_x: i64,
+        delta_y: i64,
+    },
+}
+
+/// Counts in progress
+struct Counts {
+    started_at: Instant,
+    event: InputEvent,
+}
+
+/// Listens to the mouse and the keyboard and sends what was done on `tx`, until it is closed.
+/// The element clicked isn't read in sensitive windows. Needs the accessibility and input
+/// monitoring permissions on macOS
+pub fn start_input_capture(sensitive_windows: SensitiveWindows, tx: Sender<InputEvent>) {
+    let (raw_tx, raw_rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let mut position = (0.0, 0.0);
+        // the os waits for the callback before delivering the event, it only passes it on
+        let result = listen(move |event| {
+            let raw = match event.event_type {
+                EventType::MouseMove { x, y } => {
+                    position = (x, y);
+                    return;
+                }
+                EventType::ButtonPress(button) => RawInput::Click {
+                    timestamp: event.time,
+                    x: position.0,
+                    y: position.1,
+                    button,
+                },
+                EventType::KeyPress(_) => RawInput::Key(event.time),
+                EventType::Wheel { delta_x, delta_y } => RawInput::Wheel {
+                    timestamp: event.time,
+                    delta_x,
+                    delta_y,
+                },
+                _ => return,
+            };
+            let _ = raw_tx.send(raw);
+        });
+        if let Err(e) = result {
+            error!("failed to listen to input events: {:?}", e);
+        }
+    });
+
+    std::thread::spawn(move || {
+        let mut keys: Option<Counts> = None;
+        let mut scroll: Option<Counts> = None;
+        loop {
+            let raw = match raw_rx.recv_timeout(COUNT_WINDOW / 5) {
+                Ok(raw) => Some(raw),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            match raw {
+                Some(RawInput::Click {
+                    timestamp,
+                    x,
+                    y,
+                    button,
+                }) => {
+                    let (app_name, window_name) = focused_window();
+                    let sensitive = match (&app_name, &window_name) {
+                        (Some(app_name), Some(window_name)) => {
+                            sensitive_windows.is_sensitive(app_name, window_name)
+                        }
+                        _ => false,
+                    };
+                    let element = if sensitive { None } else { element_at(x, y) };
+                    let click = InputEvent {
+                        timestamp,
+                        activity: InputActivity::Click {
+                            x,
+                            y,
+                            button: button_name(button),
+                            element,
+                        },
+                        app_name,
+                        window_name,
+                    };
+                    if tx.blocking_send(click).is_err() {
+                        break;
+                    }
+                }
+                Some(RawInput::Key(timestamp)) => {
+                    let counts = keys.get_or_insert_with(|| {
+                        start_counts(timestamp, InputActivity::Keys { count: 0 })
+                    });
+                    if let InputActivity::Keys { count } = &mut counts.event.activity {
+                        *count += 1;
+                    }
+                }
+                Some(RawInput::Wheel {
+                    timestamp,
+                    delta_x,
+                    delta_y,
+                }) => {
+                    let counts = scroll.get_or_insert_with(|| {
+                        start_counts(
+                            timestamp,
+                            InputActivity::Scroll {
+                                count: 0,
+                                delta_x: 0,
+                                delta_y: 0,
+                            },
+                        )
+                    });
+                    if let InputActivity::Scroll {
+                        count,
+                        delta_x: total_x,
+                        delta_y: total_y,
+                    } = &mut counts.event.activity
+                    {
+                        *count += 1;
+                        *total_x += delta_x;
+                        *total_y += delta_y;
+                    }
+                }
+                None => {}
+            }
+
+            for counts in [&mut keys, &mut scroll] {
+                if counts
+                    .as_ref()
+                    .is_some_and(|counts| counts.started_at.elapsed() >= COUNT_WINDOW)
+                {
+                    let event = counts.take().unwrap().event;
+                    debug!("input activity: {:?}", event.activity);
+                    if tx.blocking_send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+}
+
+fn start_counts(timestamp: SystemTime, activity: InputActivity) -> Counts {
+    let (app_name, window_name) = focused_window();
+    Counts {
+        started_at: Instant::now(),
+        event: InputEvent {
+            timestamp,
+            activity,
+            app_name,
+            window_name,
+        },
+    }
+}
+
+fn focused_window() -> (Option<String>, Option<String>) {
+    match get_active_app() {
+        Some(app) => (Some(app.name), Some(app.window_title)),
+        None => (None, None),
+    }
+}
+
+fn button_name(button: Button) -> String {
+    match button {
+        Button::Left => "left".to_string(),
+        Button::Right => "right".to_string(),
+        Button::Middle => "middle".to_string(),
+        Button::Unknown(code) => format!("button {}", code),
+    }
+}
@@ -1,23 +1,49 @@
+pub mod accessibility;
+pub mod active_window;
+pub mod adaptive_fps;
 #[cfg(target_os = "macos")]
 pub mod apple;
+pub mod browser_tab;
+pub mod changed_regions;
+pub mod clip;
 pub mod core;
+pub mod cursor;
 pub mod custom_ocr;
+pub mod frame_hash;
+pub mod frame_scale;
+pub mod idle;
+pub mod input_events;
 #[cfg(target_os = "windows")]
 pub mod microsoft;
 pub mod monitor;
+pub mod ocr_filter;
+pub mod ocr_languages;
+pub mod ocr_layout;
+pub mod ocr_preprocess;
+pub mod ocr_structure;
+pub mod paddle_ocr;
 #[cfg(target_os = "macos")]
 pub mod run_ui_monitoring_macos;
+pub mod sensitive_windows;
 pub mod tesseract;
 pub mod utils;
+pub mod video_call;
+pub mod virtual_desktop;
+#[cfg(all(target_os = "linux", feature = "pipewire"))]
+pub mod wayland;
 #[cfg(target_os = "macos")]
 pub use apple::perform_ocr_apple;
-pub use core::{continuous_capture, process_ocr_task, CaptureResult, RealtimeVisionEvent, UIFrame};
+pub use core::{
+    capture_frame, continuous_capture, process_ocr_task, CaptureResult, RealtimeVisionEvent,
+    UIFrame,
+};
 // pub use types::CaptureResult;
 pub use utils::OcrEngine;
 pub mod capture_screenshot_by_window;
 pub use custom_ocr::perform_ocr_custom;
 #[cfg(target_os = "windows")]
 pub use microsoft::perform_ocr_windows;
+pub use paddle_ocr::perform_ocr_paddle;
 #[cfg(target_os = "macos")]
 pub use run_ui_monitoring_macos::run_ui;
 pub use tesseract::perform_ocr_tesseract;
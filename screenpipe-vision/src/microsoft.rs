@@ -37,13 +37,33 @@ pub async fn perform_ocr_windows(image: &DynamicImage) -> Result<(String, String
     let engine = WindowsOcrEngine::TryCreateFromUserProfileLanguages()?;
     let result = engine.RecognizeAsync(&bitmap)?.get()?;
 
-    let text = result.Text()?.to_string();
+    // one entry per word with its box in pixels, in the shape of tesseract's tsv records
+    let mut words: Vec<serde_json::Value> = Vec::new();
+    let mut lines: Vec<String> = Vec::new();
+    for (line_num, line) in result.Lines()?.into_iter().enumerate() {
+        for (word_num, word) in line.Words()?.into_iter().enumerate() {
+            let rect = word.BoundingRect()?;
+            words.push(serde_json::json!({
+                "level": "5",
+                "page_num": "1",
+                "block_num": "0",
+                "par_num": "0",
+                "line_num": line_num.to_string(),
+                "word_num": word_num.to_string(),
+                "left": rect.X.to_string(),
+                "top": rect.Y.to_string(),
+                "width": rect.Width.to_string(),
+                "height": rect.Height.to_string(),
+                // windows ocr doesn't give a confidence
+                "conf": "1.0",
+                "text": word.Text()?.to_string(),
+            }));
+        }
+        lines.push(line.Text()?.to_string());
+    }
+    let text = lines.join("\n");
 
-    let json_output = serde_json::json!([{
-        "text": text,
-        "confidence": "1.0" // Windows OCR doesn't provide confidence scores
-    }])
-    .to_string();
+    let json_output = serde_json::to_string(&words)?;
 
     Ok((text, json_output, Some(1.0)))
 }
@@ -3,26 +3,45 @@ use image::DynamicImage;
 use std::sync::Arc;
 use xcap::Monitor;
 
+use crate::frame_scale::FrameScale;
+use crate::utils::OcrEngine;
+
 #[derive(Clone)]
 pub struct SafeMonitor {
     monitor_id: u32,
     monitor_data: Arc<MonitorData>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct MonitorData {
     pub width: u32,
     pub height: u32,
+    /// Position of the top left corner in the virtual screen spanning all monitors
+    pub x: i32,
+    pub y: i32,
     pub name: String,
     pub is_primary: bool,
 }
 
+/// How a single monitor is recorded, unset fields fall back to the settings of all monitors
+#[derive(Clone, Debug, Default)]
+pub struct MonitorConfig {
+    /// Don't record the monitor at all
+    pub disabled: bool,
+    pub fps: Option<f64>,
+    pub ocr_engine: Option<OcrEngine>,
+    pub ocr_scale: Option<FrameScale>,
+    pub video_scale: Option<FrameScale>,
+}
+
 impl SafeMonitor {
     pub fn new(monitor: Monitor) -> Self {
         let monitor_id = monitor.id();
         let monitor_data = Arc::new(MonitorData {
             width: monitor.width(),
             height: monitor.height(),
+            x: monitor.x(),
+            y: monitor.y(),
             name: monitor.name().to_string(),
             is_primary: monitor.is_primary(),
         });
@@ -34,6 +53,12 @@ impl SafeMonitor {
     }
 
     pub async fn capture_image(&self) -> Result<DynamicImage> {
+        // x11 capture only sees the x11 apps on wayland, the screencast portal sees everything
+        #[cfg(all(target_os = "linux", feature = "pipewire"))]
+        if crate::wayland::is_wayland() {
+            return crate::wayland::capture_monitor(&self.monitor_data).await;
+        }
+
         let monitor_id = self.monitor_id;
         
         let image = std::thread::spawn(move || -> Result<DynamicImage> {
@@ -77,6 +102,10 @@ impl SafeMonitor {
         self.monitor_data.height
     }
 
+    pub fn position(&self) -> (i32, i32) {
+        (self.monitor_data.x, self.monitor_data.y)
+    }
+
     pub fn is_primary(&self) -> bool {
         self.monitor_data.is_primary
     }
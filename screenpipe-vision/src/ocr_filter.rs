@@ -0,0 +1,139 @@
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+
+/// Words of at least 3 characters with more symbols than this are junk, like "|=~" or "-.-'"
+const MAX_SYMBOL_RATIO: f64 = 0.5;
+/// Latin words this long without a vowel are junk, like "tflrrk", while "html" isn't
+const MIN_VOWELLESS_LETTERS: usize = 6;
+
+static CONFIG: OnceCell<OcrFilterConfig> = OnceCell::new();
+
+/// Words dropped from the OCR results before they are stored
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OcrFilterConfig {
+    /// Words the engine is less confident about, between 0 and 1
+    pub min_confidence: f64,
+    /// Drop the strings read in icons, gradients and anti-aliased edges, see `is_junk`
+    pub junk: bool,
+}
+
+impl Default for OcrFilterConfig {
+    fn default() -> Self {
+        Self {
+            min_confidence: 0.0,
+            junk: true,
+        }
+    }
+}
+
+pub fn init(config: OcrFilterConfig) {
+    let _ = CONFIG.set(config);
+}
+
+pub fn current() -> OcrFilterConfig {
+    CONFIG.get().copied().unwrap_or_default()
+}
+
+/// Whether `word` is something OCR made up from what isn't text: only or mostly symbols, a
+/// character repeated, a long latin word without vowels or with the case flipping back and forth
+pub fn is_junk(word: &str) -> bool {
+    let chars: Vec<char> = word.chars().filter(|c| !c.is_whitespace()).collect();
+    let alphanumeric = chars.iter().filter(|c| c.is_alphanumeric()).count();
+    if alphanumeric == 0 {
+        return true;
+    }
+    let symbols = chars.len() - alphanumeric;
+    if chars.len() >= 3 && symbols as f64 / chars.len() as f64 > MAX_SYMBOL_RATIO {
+        return true;
+    }
+    // "0000" is a number, "lllll" a border
+    if chars
+        .windows(4)
+        .any(|w| !w[0].is_numeric() && w.iter().all(|c| *c == w[0]))
+    {
+        return true;
+    }
+
+    let letters: Vec<char> = chars
+        .iter()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if letters.len() >= MIN_VOWELLESS_LETTERS
+        && letters.len() == alphanumeric
+        && !letters.iter().any(|c| "aeiouy".contains(*c))
+    {
+        return true;
+    }
+    // "lIlIl" or "tHiSiS", while "iPhone" and "McDonald" switch case once or twice
+    let case_switches = chars
+        .windows(2)
+        .filter(|w| {
+            w[0].is_alphabetic()
+                && w[1].is_alphabetic()
+                && w[0].is_uppercase() != w[1].is_uppercase()
+        })
+        .count();
+    letters.len() >= 5 && case_switches * 2 > letters.len()
+}
+
+/// Confidence of an entry of the OCR json output between 0 and 1, tesseract's is a percentage
+pub(crate) fn word_confidence(word: &HashMap<String, String>) -> Option<f64> {
+    let confidence: f64 = word
+        .get("conf")
+        .or_else(|| word.get("confidence"))?
+        .parse()
+        .ok()?;
+    Some(if confidence > 1.0 {
+        confidence / 100.0
+    } else {
+        confidence
+    })
+}
+
+/// Drops the words of `words` below `config.min_confidence` or junk. When some are dropped the
+/// text is rebuilt from the words left, a line per line of the engine. Results of engines
+/// without per word confidences are kept as they are
+pub fn filter_words(
+    config: &OcrFilterConfig,
+    text: String,
+    words: Vec<HashMap<String, String>>,
+) -> (String, Vec<HashMap<String, String>>) {
+    if words
+        .iter()
+        .any(|word| !word.contains_key("text") || word_confidence(word).is_none())
+    {
+        return (text, words);
+    }
+
+    let count = words.len();
+    let kept: Vec<HashMap<String, String>> = words
+        .into_iter()
+        .filter(|word| {
+            word_confidence(word).is_some_and(|confidence| confidence >= config.min_confidence)
+                && !(config.junk && is_junk(&word["text"]))
+        })
+        .collect();
+    if kept.len() == count {
+        return (text, kept);
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut current_line: Option<(&str, &str, &str)> = None;
+    for word in &kept {
+        let line = (
+            word.get("block_num").map_or("", String::as_str),
+            word.get("par_num").map_or("", String::as_str),
+            word.get("line_num").map_or("", String::as_str),
+        );
+        match lines.last_mut() {
+            Some(text) if current_line == Some(line) => {
+                text.push(' ');
+                text.push_str(&word["text"]);
+            }
+            _ => lines.push(word["text"].clone()),
+        }
+        current_line = Some(line);
+    }
+    (lines.join("\n"), kept)
+}
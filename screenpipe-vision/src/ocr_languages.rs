@@ -0,0 +1,75 @@
+use once_cell::sync::OnceCell;
+use screenpipe_core::{Language, TESSERACT_LANGUAGES};
+use tracing::{info, warn};
+
+static OPTIONS: OnceCell<OcrLanguageOptions> = OnceCell::new();
+
+/// The languages text on screen is read in
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OcrLanguageOptions {
+    /// Most likely first, the languages given to the OCR engines otherwise
+    pub languages: Vec<Language>,
+    /// Let the engine detect the language or script of the text, among `languages` when there
+    /// are some. Apple Vision detects it itself, tesseract loads every language installed
+    pub auto_detect: bool,
+}
+
+impl OcrLanguageOptions {
+    /// `self.languages` when there are some, `languages` otherwise
+    pub fn languages_or(&self, languages: &[Language]) -> Vec<Language> {
+        if self.languages.is_empty() {
+            languages.to_vec()
+        } else {
+            self.languages.clone()
+        }
+    }
+}
+
+pub fn init(options: OcrLanguageOptions) {
+    if !options.languages.is_empty() || options.auto_detect {
+        info!(
+            "ocr languages: {:?}, detection {}",
+            options.languages,
+            if options.auto_detect { "on" } else { "off" }
+        );
+    }
+    let _ = OPTIONS.set(options);
+}
+
+pub fn current() -> OcrLanguageOptions {
+    OPTIONS.get().cloned().unwrap_or_default()
+}
+
+/// The `-l` argument of tesseract, in the order of `languages` as the first one is tried first
+pub fn tesseract_languages(languages: &[Language], auto_detect: bool) -> String {
+    let mut codes: Vec<&str> = Vec::new();
+    for language in languages {
+        match TESSERACT_LANGUAGES
+            .iter()
+            .find(|(_, val)| *language == *val)
+        {
+            Some((code, _)) if !codes.contains(code) => codes.push(code),
+            Some(_) => {}
+            None => warn!("tesseract doesn't read {:?}", language),
+        }
+    }
+    if codes.is_empty() && auto_detect {
+        match rusty_tesseract::get_tesseract_langs() {
+            Ok(installed) => {
+                let installed: Vec<String> = installed
+                    .into_iter()
+                    // orientation and script detection only, not a language
+                    .filter(|code| code != "osd")
+                    .collect();
+                if !installed.is_empty() {
+                    return installed.join("+");
+                }
+            }
+            Err(e) => warn!("failed to list the languages of tesseract: {:?}", e),
+        }
+    }
+    if codes.is_empty() {
+        return "eng".to_string();
+    }
+    codes.join("+")
+}
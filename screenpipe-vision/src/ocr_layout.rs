@@ -0,0 +1,219 @@
+use crate::ocr_filter::word_confidence;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Lines further below a block than this many line heights start a new block
+const MAX_LINE_GAP: f64 = 1.0;
+
+/// A rectangle of a window, in fractions of its width and height from its top left corner
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LayoutBox {
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl LayoutBox {
+    pub fn right(&self) -> f64 {
+        self.left + self.width
+    }
+
+    pub fn bottom(&self) -> f64 {
+        self.top + self.height
+    }
+
+    pub fn union(&self, other: &LayoutBox) -> LayoutBox {
+        let left = self.left.min(other.left);
+        let top = self.top.min(other.top);
+        LayoutBox {
+            left,
+            top,
+            width: self.right().max(other.right()) - left,
+            height: self.bottom().max(other.bottom()) - top,
+        }
+    }
+
+    /// Whether `other` is entirely inside it
+    pub fn contains(&self, other: &LayoutBox) -> bool {
+        other.left >= self.left
+            && other.top >= self.top
+            && other.right() <= self.right()
+            && other.bottom() <= self.bottom()
+    }
+
+    fn overlaps_horizontally(&self, other: &LayoutBox) -> bool {
+        self.left < other.right() && other.left < self.right()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayoutWord {
+    pub text: String,
+    pub bbox: LayoutBox,
+    /// Between 0 and 1
+    pub confidence: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayoutLine {
+    /// The words, left to right
+    pub text: String,
+    pub bbox: LayoutBox,
+    pub words: Vec<LayoutWord>,
+}
+
+/// Lines read one after the other, a paragraph, a menu or a column
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayoutBlock {
+    pub bbox: LayoutBox,
+    pub lines: Vec<LayoutLine>,
+}
+
+/// The text read in a window, blocks in reading order
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OcrLayout {
+    pub blocks: Vec<LayoutBlock>,
+}
+
+impl OcrLayout {
+    /// From the per word json output of the OCR engines, boxes in pixels of the `width` by
+    /// `height` image. Apple native's are fractions of the image from its bottom left corner,
+    /// `normalized_bottom_left`. Entries without a box are left out
+    pub fn from_words(
+        words: &[HashMap<String, String>],
+        width: u32,
+        height: u32,
+        normalized_bottom_left: bool,
+    ) -> Self {
+        // words of a line of the engine, in the order lines were read
+        let mut lines: Vec<Vec<LayoutWord>> = Vec::new();
+        let mut line_indices: HashMap<(&str, &str, &str), usize> = HashMap::new();
+        for word in words {
+            let Some(text) = word.get("text").filter(|text| !text.trim().is_empty()) else {
+                continue;
+            };
+            let Some(bbox) = word_box(word, width, height, normalized_bottom_left) else {
+                continue;
+            };
+            let key = (
+                word.get("block_num").map_or("", String::as_str),
+                word.get("par_num").map_or("", String::as_str),
+                word.get("line_num").map_or("", String::as_str),
+            );
+            let index = *line_indices.entry(key).or_insert_with(|| {
+                lines.push(Vec::new());
+                lines.len() - 1
+            });
+            lines[index].push(LayoutWord {
+                text: text.clone(),
+                bbox,
+                confidence: word_confidence(word).unwrap_or(0.0),
+            });
+        }
+
+        let mut lines: Vec<LayoutLine> = lines
+            .into_iter()
+            .filter_map(|mut words| {
+                words.sort_by(|a, b| a.bbox.left.total_cmp(&b.bbox.left));
+                let bbox = words
+                    .iter()
+                    .skip(1)
+                    .fold(words.first()?.bbox, |bbox, word| bbox.union(&word.bbox));
+                Some(LayoutLine {
+                    text: words
+                        .iter()
+                        .map(|word| word.text.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    bbox,
+                    words,
+                })
+            })
+            .collect();
+        lines.sort_by(|a, b| a.bbox.top.total_cmp(&b.bbox.top));
+
+        // a line joins the block it is right below, blocks side by side are columns. it only
+        // needs to be under the block, as the closing brace under an indented line of code
+        let mut blocks: Vec<LayoutBlock> = Vec::new();
+        for line in lines {
+            let block = blocks.iter_mut().find(|block| {
+                let last = &block.lines[block.lines.len() - 1].bbox;
+                let gap = line.bbox.top - last.bottom();
+                gap < line.bbox.height.max(last.height) * MAX_LINE_GAP
+                    && block.bbox.overlaps_horizontally(&line.bbox)
+            });
+            match block {
+                Some(block) => {
+                    block.bbox = block.bbox.union(&line.bbox);
+                    block.lines.push(line);
+                }
+                None => blocks.push(LayoutBlock {
+                    bbox: line.bbox,
+                    lines: vec![line],
+                }),
+            }
+        }
+        // top to bottom, left to right for blocks starting at the same height
+        blocks.sort_by(|a, b| {
+            a.bbox
+                .top
+                .total_cmp(&b.bbox.top)
+                .then(a.bbox.left.total_cmp(&b.bbox.left))
+        });
+
+        Self { blocks }
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &LayoutLine> {
+        self.blocks.iter().flat_map(|block| block.lines.iter())
+    }
+
+    /// In reading order, a line per line and an empty line between blocks
+    pub fn text(&self) -> String {
+        self.blocks
+            .iter()
+            .map(|block| {
+                block
+                    .lines
+                    .iter()
+                    .map(|line| line.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+fn word_box(
+    word: &HashMap<String, String>,
+    width: u32,
+    height: u32,
+    normalized_bottom_left: bool,
+) -> Option<LayoutBox> {
+    let value = |key: &str| word.get(key)?.parse::<f64>().ok();
+    let (left, top, box_width, box_height) = (
+        value("left")?,
+        value("top")?,
+        value("width")?,
+        value("height")?,
+    );
+    if normalized_bottom_left {
+        return Some(LayoutBox {
+            left,
+            top: 1.0 - top - box_height,
+            width: box_width,
+            height: box_height,
+        });
+    }
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some(LayoutBox {
+        left: left / width as f64,
+        top: top / height as f64,
+        width: box_width / width as f64,
+        height: box_height / height as f64,
+    })
+}
@@ -0,0 +1,138 @@
+use image::{DynamicImage, GrayImage};
+use once_cell::sync::OnceCell;
+
+/// Images whose median luma is below this have light text on a dark background
+const DARK_MEDIAN: u8 = 128;
+/// Share of the darkest and lightest pixels ignored when stretching contrast, a cursor or an icon
+/// shouldn't keep a dull window dull
+const CLIP_SHARE: f64 = 0.01;
+/// Contrast is stretched when the lightest and darkest pixels are closer than this
+const MIN_RANGE: u8 = 200;
+
+static MODE: OnceCell<OcrPreprocessing> = OnceCell::new();
+
+/// What is done to images before they are OCRed. Engines are trained on dark text on a light
+/// background and read dark themed IDEs and terminals noticeably worse
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OcrPreprocessing {
+    /// Images are OCRed as captured
+    Off,
+    /// Dark images are inverted and dull ones get their contrast stretched
+    #[default]
+    Auto,
+    /// Like auto, then made black and white
+    Binarize,
+}
+
+pub fn init(mode: OcrPreprocessing) {
+    let _ = MODE.set(mode);
+}
+
+pub fn current() -> OcrPreprocessing {
+    MODE.get().copied().unwrap_or_default()
+}
+
+/// Number of pixels of each luma in `image`
+pub fn histogram(image: &GrayImage) -> [u64; 256] {
+    let mut histogram = [0; 256];
+    for &luma in image.as_raw() {
+        histogram[luma as usize] += 1;
+    }
+    histogram
+}
+
+/// Luma below which `share` of the pixels are
+fn percentile(histogram: &[u64; 256], share: f64) -> u8 {
+    let total: u64 = histogram.iter().sum();
+    let target = (total as f64 * share) as u64;
+    let mut seen = 0;
+    for (luma, count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen > target {
+            return luma as u8;
+        }
+    }
+    255
+}
+
+/// Whether most of the image is dark, text is then light
+pub fn is_dark(histogram: &[u64; 256]) -> bool {
+    percentile(histogram, 0.5) < DARK_MEDIAN
+}
+
+/// Luma splitting the pixels in the 2 groups of least variance, Otsu's method
+pub fn otsu_threshold(histogram: &[u64; 256]) -> u8 {
+    let total: u64 = histogram.iter().sum();
+    let sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(luma, &count)| luma as f64 * count as f64)
+        .sum();
+    let (mut below, mut below_sum) = (0u64, 0.0);
+    let (mut threshold, mut max_variance) = (0, -1.0);
+    for (luma, &count) in histogram.iter().enumerate() {
+        below += count;
+        below_sum += luma as f64 * count as f64;
+        let above = total - below;
+        if below == 0 || above == 0 {
+            continue;
+        }
+        let below_mean = below_sum / below as f64;
+        let above_mean = (sum - below_sum) / above as f64;
+        let variance = below as f64 * above as f64 * (below_mean - above_mean).powi(2);
+        if variance > max_variance {
+            (threshold, max_variance) = (luma as u8, variance);
+        }
+    }
+    threshold
+}
+
+/// `image` made easier to read by OCR engines, in grayscale and the same size so the boxes of the
+/// words read still apply to the captured image. None when it's better OCRed as is
+pub fn preprocess(image: &DynamicImage, mode: OcrPreprocessing) -> Option<DynamicImage> {
+    if mode == OcrPreprocessing::Off || image.width() == 0 || image.height() == 0 {
+        return None;
+    }
+    let mut luma = image.to_luma8();
+    let histogram = histogram(&luma);
+
+    // every step maps each luma to another, done at once with a table
+    let mut table: [u8; 256] = std::array::from_fn(|luma| luma as u8);
+    let dark = is_dark(&histogram);
+    if dark {
+        table.iter_mut().for_each(|luma| *luma = 255 - *luma);
+    }
+
+    let mut mapped = [0u64; 256];
+    for (luma, &count) in histogram.iter().enumerate() {
+        mapped[table[luma] as usize] += count;
+    }
+    let (low, high) = (
+        percentile(&mapped, CLIP_SHARE),
+        percentile(&mapped, 1.0 - CLIP_SHARE),
+    );
+    let stretch = high > low && high - low < MIN_RANGE;
+    if stretch {
+        let range = (high - low) as f64;
+        table.iter_mut().for_each(|luma| {
+            *luma = ((*luma as f64 - low as f64) / range * 255.0).clamp(0.0, 255.0) as u8
+        });
+    }
+
+    if mode == OcrPreprocessing::Binarize {
+        let mut mapped = [0u64; 256];
+        for (luma, &count) in histogram.iter().enumerate() {
+            mapped[table[luma] as usize] += count;
+        }
+        let threshold = otsu_threshold(&mapped);
+        table
+            .iter_mut()
+            .for_each(|luma| *luma = if *luma > threshold { 255 } else { 0 });
+    } else if !dark && !stretch {
+        return None;
+    }
+
+    luma.iter_mut()
+        .for_each(|pixel| *pixel = table[*pixel as usize]);
+    Some(DynamicImage::ImageLuma8(luma))
+}
@@ -0,0 +1,311 @@
+use crate::ocr_layout::{LayoutBlock, LayoutBox, LayoutLine, OcrLayout};
+use serde::{Deserialize, Serialize};
+
+/// Words further apart than this many characters are in different cells of a table
+const MIN_CELL_GAP: f64 = 2.0;
+/// Rows further apart than this many row heights are in different tables
+const MAX_ROW_GAP: f64 = 1.5;
+const MIN_TABLE_ROWS: usize = 2;
+/// Longer cells on average are lines of text next to each other, like a paragraph and a sidebar
+const MAX_MEAN_CELL_CHARS: f64 = 30.0;
+const MIN_CODE_LINES: usize = 2;
+/// Blocks with at least this share of their lines looking like code are code
+const MIN_CODE_SHARE: f64 = 0.5;
+
+const CODE_TOKENS: [&str; 10] = ["=>", "->", "::", "==", "!=", "&&", "||", "//", " = ", "();"];
+const CODE_KEYWORDS: [&str; 15] = [
+    "fn ",
+    "pub ",
+    "use ",
+    "let ",
+    "const ",
+    "var ",
+    "function ",
+    "def ",
+    "class ",
+    "import ",
+    "return",
+    "#include",
+    "if (",
+    "for (",
+    "while (",
+];
+const CODE_LINE_ENDINGS: [char; 5] = ['{', '}', ';', '(', '['];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StructuredContent {
+    /// Cells row by row, empty where a row has nothing in a column
+    Table { rows: Vec<Vec<String>> },
+    /// Lines with their indentation and the spacing between their words, in spaces
+    Code { text: String },
+}
+
+/// A table or a block of code read in a window
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OcrStructure {
+    pub bbox: LayoutBox,
+    pub content: StructuredContent,
+}
+
+impl OcrStructure {
+    pub fn kind(&self) -> &'static str {
+        match self.content {
+            StructuredContent::Table { .. } => "table",
+            StructuredContent::Code { .. } => "code",
+        }
+    }
+
+    /// Rows of tab separated cells for tables, as spreadsheets paste them, the code as it was
+    pub fn text(&self) -> String {
+        match &self.content {
+            StructuredContent::Table { rows } => rows
+                .iter()
+                .map(|row| row.join("\t"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            StructuredContent::Code { text } => text.clone(),
+        }
+    }
+}
+
+/// The tables and blocks of code of `layout`, top to bottom. Blocks mostly made of lines looking
+/// like code are code, then lines side by side with their words in the same columns are tables
+pub fn extract_structures(layout: &OcrLayout) -> Vec<OcrStructure> {
+    let mut structures = Vec::new();
+    let mut lines = Vec::new();
+    for block in &layout.blocks {
+        match code(block) {
+            Some(code) => structures.push(code),
+            None => lines.extend(block.lines.iter()),
+        }
+    }
+    structures.extend(tables(&rows(lines)));
+    structures.sort_by(|a, b| {
+        a.bbox
+            .top
+            .total_cmp(&b.bbox.top)
+            .then(a.bbox.left.total_cmp(&b.bbox.left))
+    });
+    structures
+}
+
+/// Average width of a character of the words of `line`
+fn char_width(line: &LayoutLine) -> f64 {
+    if line.words.is_empty() {
+        return line.bbox.width / line.text.chars().count().max(1) as f64;
+    }
+    let (width, chars) = line.words.iter().fold((0.0, 0), |(width, chars), word| {
+        (width + word.bbox.width, chars + word.text.chars().count())
+    });
+    width / chars.max(1) as f64
+}
+
+fn is_code_line(text: &str) -> bool {
+    let text = text.trim();
+    CODE_TOKENS.iter().any(|token| text.contains(token))
+        || CODE_KEYWORDS
+            .iter()
+            .any(|keyword| text.starts_with(keyword))
+        || text.ends_with(&CODE_LINE_ENDINGS[..])
+}
+
+fn code(block: &LayoutBlock) -> Option<OcrStructure> {
+    if block.lines.len() < MIN_CODE_LINES {
+        return None;
+    }
+    let code_lines = block
+        .lines
+        .iter()
+        .filter(|line| is_code_line(&line.text))
+        .count();
+    if (code_lines as f64) < block.lines.len() as f64 * MIN_CODE_SHARE {
+        return None;
+    }
+
+    // code is in monospace fonts, a character is as wide on every line
+    let mut widths: Vec<f64> = block
+        .lines
+        .iter()
+        .map(char_width)
+        .filter(|width| *width > 0.0)
+        .collect();
+    widths.sort_by(f64::total_cmp);
+    let char_width = *widths.get(widths.len() / 2)?;
+    let spaces = |gap: f64| (gap / char_width).round().max(0.0) as usize;
+
+    let text = block
+        .lines
+        .iter()
+        .map(|line| {
+            let mut text = " ".repeat(spaces(line.bbox.left - block.bbox.left));
+            if line.words.is_empty() {
+                text.push_str(&line.text);
+                return text;
+            }
+            for (i, word) in line.words.iter().enumerate() {
+                if i > 0 {
+                    let gap = word.bbox.left - line.words[i - 1].bbox.right();
+                    text.push_str(&" ".repeat(spaces(gap).max(1)));
+                }
+                text.push_str(&word.text);
+            }
+            text
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(OcrStructure {
+        bbox: block.bbox,
+        content: StructuredContent::Code { text },
+    })
+}
+
+struct Cell {
+    text: String,
+    bbox: LayoutBox,
+}
+
+/// Lines side by side and the cells of their words
+struct Row {
+    bbox: LayoutBox,
+    cells: Vec<Cell>,
+}
+
+/// The words of `line` in cells, split where they are far apart
+fn cells(line: &LayoutLine) -> Vec<Cell> {
+    if line.words.is_empty() {
+        return vec![Cell {
+            text: line.text.clone(),
+            bbox: line.bbox,
+        }];
+    }
+    let min_gap = char_width(line) * MIN_CELL_GAP;
+    let mut cells: Vec<Cell> = Vec::new();
+    for word in &line.words {
+        match cells.last_mut() {
+            Some(cell) if word.bbox.left - cell.bbox.right() < min_gap => {
+                cell.text.push(' ');
+                cell.text.push_str(&word.text);
+                cell.bbox = cell.bbox.union(&word.bbox);
+            }
+            _ => cells.push(Cell {
+                text: word.text.clone(),
+                bbox: word.bbox,
+            }),
+        }
+    }
+    cells
+}
+
+/// `lines` in rows, a line is in a row when its middle is within it
+fn rows(mut lines: Vec<&LayoutLine>) -> Vec<Row> {
+    lines.sort_by(|a, b| a.bbox.top.total_cmp(&b.bbox.top));
+    let mut rows: Vec<Row> = Vec::new();
+    for line in lines {
+        let middle = line.bbox.top + line.bbox.height / 2.0;
+        match rows.last_mut() {
+            Some(row) if middle > row.bbox.top && middle < row.bbox.bottom() => {
+                row.bbox = row.bbox.union(&line.bbox);
+                row.cells.extend(cells(line));
+            }
+            _ => rows.push(Row {
+                bbox: line.bbox,
+                cells: cells(line),
+            }),
+        }
+    }
+    for row in &mut rows {
+        row.cells
+            .sort_by(|a, b| a.bbox.left.total_cmp(&b.bbox.left));
+    }
+    rows
+}
+
+/// Runs of rows of several cells close to each other, the ones lining up in columns
+fn tables(rows: &[Row]) -> Vec<OcrStructure> {
+    let mut tables = Vec::new();
+    let mut start = 0;
+    while start < rows.len() {
+        let mut end = start;
+        while end < rows.len()
+            && rows[end].cells.len() >= 2
+            && (end == start || {
+                let (previous, row) = (&rows[end - 1].bbox, &rows[end].bbox);
+                row.top - previous.bottom() < previous.height.max(row.height) * MAX_ROW_GAP
+            })
+        {
+            end += 1;
+        }
+        tables.extend(table(&rows[start..end]));
+        start = end.max(start + 1);
+    }
+    tables
+}
+
+/// The cells of `rows` in columns, None when they don't make a table
+fn table(rows: &[Row]) -> Option<OcrStructure> {
+    if rows.len() < MIN_TABLE_ROWS {
+        return None;
+    }
+    let cells: Vec<&Cell> = rows.iter().flat_map(|row| row.cells.iter()).collect();
+    let mean_chars = cells
+        .iter()
+        .map(|cell| cell.text.chars().count())
+        .sum::<usize>() as f64
+        / cells.len() as f64;
+    if mean_chars > MAX_MEAN_CELL_CHARS {
+        return None;
+    }
+
+    // a column spans the cells overlapping it, whether they are aligned left, right or centered
+    let mut spans: Vec<(f64, f64)> = cells
+        .iter()
+        .map(|cell| (cell.bbox.left, cell.bbox.right()))
+        .collect();
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut columns: Vec<(f64, f64)> = Vec::new();
+    for (left, right) in spans {
+        match columns.last_mut() {
+            Some(column) if left < column.1 => column.1 = column.1.max(right),
+            _ => columns.push((left, right)),
+        }
+    }
+    if columns.len() < 2 {
+        return None;
+    }
+
+    let table: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            let mut cells = vec![String::new(); columns.len()];
+            for cell in &row.cells {
+                let column = columns
+                    .iter()
+                    .position(|(_, right)| cell.bbox.left < *right)
+                    .unwrap_or(columns.len() - 1);
+                if !cells[column].is_empty() {
+                    cells[column].push(' ');
+                }
+                cells[column].push_str(&cell.text);
+            }
+            cells
+        })
+        .collect();
+    // rows of a single column are a line of text wrapping around another
+    if table
+        .iter()
+        .filter(|cells| cells.iter().filter(|cell| !cell.is_empty()).count() >= 2)
+        .count()
+        < MIN_TABLE_ROWS
+    {
+        return None;
+    }
+
+    Some(OcrStructure {
+        bbox: rows
+            .iter()
+            .skip(1)
+            .fold(rows[0].bbox, |bbox, row| bbox.union(&row.bbox)),
+        content: StructuredContent::Table { rows: table },
+    })
+}
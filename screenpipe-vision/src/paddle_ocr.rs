@@ -0,0 +1,388 @@
+use crate::ocr_languages;
+use anyhow::{anyhow, Context, Result};
+use image::{imageops, imageops::FilterType, DynamicImage, RgbImage};
+use ndarray::Array4;
+use once_cell::sync::Lazy;
+use ort::{GraphOptimizationLevel, Session};
+use screenpipe_core::models::{self, ModelFile};
+use screenpipe_core::Language;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// PaddleOCR models converted to onnx by RapidOCR
+const MODEL_REPO: &str = "SWHL/RapidOCR";
+const DETECTION_MODEL: &str = "PP-OCRv4/ch_PP-OCRv4_det_infer.onnx";
+const DICTIONARY_URL: &str =
+    "https://raw.githubusercontent.com/PaddlePaddle/PaddleOCR/release/2.7/ppocr/utils";
+
+// detection: DBNet on images of at most 960px, sides multiple of 32
+const DETECTION_MAX_SIDE: u32 = 960;
+const DETECTION_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const DETECTION_STD: [f32; 3] = [0.229, 0.224, 0.225];
+/// Probability from which a pixel is text
+const PIXEL_THRESHOLD: f32 = 0.3;
+/// Mean probability of the pixels of a box kept
+const BOX_THRESHOLD: f32 = 0.5;
+/// The model shrinks text regions, boxes are grown by their area times this over their perimeter
+const UNCLIP_RATIO: f32 = 1.5;
+/// Boxes thinner than this on the probability map are noise
+const MIN_BOX_SIDE: usize = 3;
+
+// recognition: CRNN reading a line at a time, 48px high
+const LINE_HEIGHT: u32 = 48;
+/// Longer lines are squeezed, the model reads them poorly past it anyway
+const MAX_LINE_WIDTH: u32 = 1600;
+
+static ENGINES: Lazy<Mutex<HashMap<Script, Arc<PaddleOcr>>>> = Lazy::new(Default::default);
+
+/// The recognition model used, the detection model is the same for all
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Script {
+    /// Reads english too
+    Chinese,
+    Japanese,
+    Korean,
+}
+
+impl Script {
+    /// Of the first chinese, japanese or korean language of `languages`
+    fn for_languages(languages: &[Language]) -> Self {
+        languages
+            .iter()
+            .find_map(|language| match language {
+                Language::Chinese => Some(Script::Chinese),
+                Language::Japanese => Some(Script::Japanese),
+                Language::Korean => Some(Script::Korean),
+                _ => None,
+            })
+            .unwrap_or(Script::Chinese)
+    }
+
+    fn recognition_model(&self) -> ModelFile {
+        let file = match self {
+            Script::Chinese => "PP-OCRv4/ch_PP-OCRv4_rec_infer.onnx",
+            Script::Japanese => "PP-OCRv3/japan_PP-OCRv3_rec_infer.onnx",
+            Script::Korean => "PP-OCRv3/korean_PP-OCRv3_rec_infer.onnx",
+        };
+        ModelFile::huggingface(MODEL_REPO, file)
+    }
+
+    /// Characters of the classes of the recognition model, one per line
+    fn dictionary(&self) -> ModelFile {
+        let file = match self {
+            Script::Chinese => "ppocr_keys_v1.txt",
+            Script::Japanese => "dict/japan_dict.txt",
+            Script::Korean => "dict/korean_dict.txt",
+        };
+        ModelFile::new(
+            format!("paddleocr/{}", file),
+            format!("{}/{}", DICTIONARY_URL, file),
+        )
+    }
+}
+
+/// A region of text, in pixels of the image
+#[derive(Clone, Copy, Debug)]
+struct TextBox {
+    left: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Text detection and recognition models of PaddleOCR
+struct PaddleOcr {
+    detection: Session,
+    detection_output: String,
+    recognition: Session,
+    recognition_output: String,
+    /// Character of each class of the recognition model, after the ctc blank
+    characters: Vec<String>,
+}
+
+/// OCR with PaddleOCR, far more accurate than tesseract on chinese, japanese and korean. The
+/// models are downloaded on first use
+pub async fn perform_ocr_paddle(
+    image: &DynamicImage,
+    languages: Vec<Language>,
+) -> Result<(String, String, Option<f64>)> {
+    let script = Script::for_languages(&ocr_languages::current().languages_or(&languages));
+    let image = image.to_rgb8();
+    tokio::task::spawn_blocking(move || engine(script)?.ocr(&image)).await?
+}
+
+fn engine(script: Script) -> Result<Arc<PaddleOcr>> {
+    // held while loading, so the models are downloaded once
+    let mut engines = ENGINES
+        .lock()
+        .map_err(|_| anyhow!("paddleocr engines lock poisoned"))?;
+    if let Some(engine) = engines.get(&script) {
+        return Ok(Arc::clone(engine));
+    }
+    let engine = Arc::new(PaddleOcr::load(script)?);
+    engines.insert(script, Arc::clone(&engine));
+    Ok(engine)
+}
+
+impl PaddleOcr {
+    fn load(script: Script) -> Result<Self> {
+        debug!("loading paddleocr models for {:?}", script);
+        let detection_path = models::get_or_download_with_events(&ModelFile::huggingface(
+            MODEL_REPO,
+            DETECTION_MODEL,
+        ))?;
+        let recognition_path = models::get_or_download_with_events(&script.recognition_model())?;
+        let dictionary_path = models::get_or_download_with_events(&script.dictionary())?;
+
+        let mut characters: Vec<String> = std::fs::read_to_string(dictionary_path)?
+            .lines()
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .collect();
+        if characters.is_empty() {
+            return Err(anyhow!("empty paddleocr dictionary"));
+        }
+        // the models are trained with spaces, the last class
+        characters.push(" ".to_string());
+
+        let (detection, detection_output) = session(detection_path)?;
+        let (recognition, recognition_output) = session(recognition_path)?;
+        Ok(Self {
+            detection,
+            detection_output,
+            recognition,
+            recognition_output,
+            characters,
+        })
+    }
+
+    fn ocr(&self, image: &RgbImage) -> Result<(String, String, Option<f64>)> {
+        let mut lines: Vec<Vec<(TextBox, String, f32)>> = Vec::new();
+        // top to bottom, then boxes overlapping the middle of the line left to right
+        let mut boxes = self.detect(image)?;
+        boxes.sort_by_key(|text_box| text_box.top);
+        for text_box in boxes {
+            let (text, confidence) = self.recognize(image, &text_box)?;
+            if text.trim().is_empty() {
+                continue;
+            }
+            let middle = text_box.top + text_box.height / 2;
+            match lines.last_mut() {
+                Some(line)
+                    if line.iter().any(|(other, _, _)| {
+                        other.top <= middle && middle < other.top + other.height
+                    }) =>
+                {
+                    line.push((text_box, text, confidence))
+                }
+                _ => lines.push(vec![(text_box, text, confidence)]),
+            }
+        }
+
+        let mut words: Vec<serde_json::Value> = Vec::new();
+        let mut text_lines: Vec<String> = Vec::new();
+        let mut total_confidence = 0.0;
+        for (line_num, line) in lines.iter_mut().enumerate() {
+            line.sort_by_key(|(text_box, _, _)| text_box.left);
+            for (word_num, (text_box, text, confidence)) in line.iter().enumerate() {
+                words.push(serde_json::json!({
+                    "level": "5",
+                    "page_num": "1",
+                    "block_num": "0",
+                    "par_num": "0",
+                    "line_num": line_num.to_string(),
+                    "word_num": word_num.to_string(),
+                    "left": text_box.left.to_string(),
+                    "top": text_box.top.to_string(),
+                    "width": text_box.width.to_string(),
+                    "height": text_box.height.to_string(),
+                    "conf": format!("{:.2}", confidence),
+                    "text": text,
+                }));
+                total_confidence += *confidence as f64;
+            }
+            text_lines.push(
+                line.iter()
+                    .map(|(_, text, _)| text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+        }
+
+        let confidence = if words.is_empty() {
+            0.0
+        } else {
+            total_confidence / words.len() as f64
+        };
+        Ok((
+            text_lines.join("\n"),
+            serde_json::to_string(&words)?,
+            Some(confidence),
+        ))
+    }
+
+    /// Regions of text of `image`
+    fn detect(&self, image: &RgbImage) -> Result<Vec<TextBox>> {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Ok(Vec::new());
+        }
+        let scale = (DETECTION_MAX_SIDE as f32 / width.max(height) as f32).min(1.0);
+        let input_width = (((width as f32 * scale) / 32.0).round() as u32 * 32).max(32);
+        let input_height = (((height as f32 * scale) / 32.0).round() as u32 * 32).max(32);
+        let resized = imageops::resize(image, input_width, input_height, FilterType::Triangle);
+
+        let mut input = Array4::<f32>::zeros((1, 3, input_height as usize, input_width as usize));
+        for (x, y, pixel) in resized.enumerate_pixels() {
+            for c in 0..3 {
+                input[[0, c, y as usize, x as usize]] =
+                    (pixel[c] as f32 / 255.0 - DETECTION_MEAN[c]) / DETECTION_STD[c];
+            }
+        }
+        let outputs = self.detection.run(ort::inputs![input]?)?;
+        let output = outputs
+            .get(self.detection_output.as_str())
+            .context("paddleocr detection output not found")?
+            .try_extract_tensor::<f32>()?;
+        // probability of each pixel to be text, 1 x 1 x height x width
+        let shape = output.shape().to_vec();
+        let (map_height, map_width) = match shape.as_slice() {
+            [.., h, w] => (*h, *w),
+            _ => return Err(anyhow!("unexpected paddleocr detection output {:?}", shape)),
+        };
+        let probabilities: Vec<f32> = output.iter().copied().collect();
+        let scale_x = width as f32 / map_width as f32;
+        let scale_y = height as f32 / map_height as f32;
+
+        // connected regions of text pixels, screen text is horizontal so their bounding box is
+        // the text box
+        let mut seen = vec![false; map_width * map_height];
+        let mut boxes = Vec::new();
+        for start in 0..probabilities.len() {
+            if seen[start] || probabilities[start] < PIXEL_THRESHOLD {
+                continue;
+            }
+            seen[start] = true;
+            let mut stack = vec![start];
+            let (mut min_x, mut min_y, mut max_x, mut max_y) = (map_width, map_height, 0, 0);
+            let mut sum = 0.0;
+            let mut count = 0;
+            while let Some(i) = stack.pop() {
+                let (x, y) = (i % map_width, i / map_width);
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+                sum += probabilities[i];
+                count += 1;
+                let neighbours = [
+                    (x > 0).then(|| i - 1),
+                    (x + 1 < map_width).then_some(i + 1),
+                    (y > 0).then(|| i - map_width),
+                    (y + 1 < map_height).then_some(i + map_width),
+                ];
+                for j in neighbours.into_iter().flatten() {
+                    if !seen[j] && probabilities[j] >= PIXEL_THRESHOLD {
+                        seen[j] = true;
+                        stack.push(j);
+                    }
+                }
+            }
+
+            let box_width = max_x - min_x + 1;
+            let box_height = max_y - min_y + 1;
+            if box_width.min(box_height) < MIN_BOX_SIDE || sum / (count as f32) < BOX_THRESHOLD {
+                continue;
+            }
+            let distance = (box_width * box_height) as f32 * UNCLIP_RATIO
+                / (2 * (box_width + box_height)) as f32;
+            let left = ((min_x as f32 - distance) * scale_x).max(0.0) as u32;
+            let top = ((min_y as f32 - distance) * scale_y).max(0.0) as u32;
+            let right = (((max_x + 1) as f32 + distance) * scale_x).min(width as f32) as u32;
+            let bottom = (((max_y + 1) as f32 + distance) * scale_y).min(height as f32) as u32;
+            if right <= left || bottom <= top {
+                continue;
+            }
+            boxes.push(TextBox {
+                left,
+                top,
+                width: right - left,
+                height: bottom - top,
+            });
+        }
+        Ok(boxes)
+    }
+
+    /// Text of `text_box` and the mean probability of its characters
+    fn recognize(&self, image: &RgbImage, text_box: &TextBox) -> Result<(String, f32)> {
+        let crop = imageops::crop_imm(
+            image,
+            text_box.left,
+            text_box.top,
+            text_box.width,
+            text_box.height,
+        )
+        .to_image();
+        let line_width = ((LINE_HEIGHT as f32 * text_box.width as f32 / text_box.height as f32)
+            .ceil() as u32)
+            .clamp(1, MAX_LINE_WIDTH);
+        let resized = imageops::resize(&crop, line_width, LINE_HEIGHT, FilterType::Triangle);
+
+        let mut input = Array4::<f32>::zeros((1, 3, LINE_HEIGHT as usize, line_width as usize));
+        for (x, y, pixel) in resized.enumerate_pixels() {
+            for c in 0..3 {
+                input[[0, c, y as usize, x as usize]] = (pixel[c] as f32 / 255.0 - 0.5) / 0.5;
+            }
+        }
+        let outputs = self.recognition.run(ort::inputs![input]?)?;
+        let output = outputs
+            .get(self.recognition_output.as_str())
+            .context("paddleocr recognition output not found")?
+            .try_extract_tensor::<f32>()?;
+        // probability of each class at each step, 1 x steps x classes
+        let classes = *output
+            .shape()
+            .last()
+            .context("empty paddleocr recognition output")?;
+        let probabilities: Vec<f32> = output.iter().copied().collect();
+
+        // greedy ctc decoding: repeated classes are one character, class 0 separates them
+        let mut text = String::new();
+        let mut confidences: Vec<f32> = Vec::new();
+        let mut previous = 0;
+        for step in probabilities.chunks(classes.max(1)) {
+            let (class, probability) = step
+                .iter()
+                .copied()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap_or((0, 0.0));
+            if class != 0 && class != previous {
+                if let Some(character) = self.characters.get(class - 1) {
+                    text.push_str(character);
+                    confidences.push(probability);
+                }
+            }
+            previous = class;
+        }
+
+        let confidence = if confidences.is_empty() {
+            0.0
+        } else {
+            confidences.iter().sum::<f32>() / confidences.len() as f32
+        };
+        Ok((text.trim().to_string(), confidence))
+    }
+}
+
+fn session(path: std::path::PathBuf) -> Result<(Session, String)> {
+    let session = Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .commit_from_file(path)?;
+    let output = session
+        .outputs
+        .first()
+        .map(|output| output.name.clone())
+        .context("paddleocr model has no output")?;
+    Ok((session, output))
+}
@@ -0,0 +1,143 @@
+use crate::active_window::ActiveApp;
+use crate::browser_tab::is_private_window;
+use image::{DynamicImage, Rgba};
+
+/// Password managers, matched in the name of the app
+const PASSWORD_MANAGERS: &[&str] = &[
+    "1password",
+    "bitwarden",
+    "dashlane",
+    "enpass",
+    "keeper",
+    "keepass",
+    "keychain access",
+    "lastpass",
+    "nordpass",
+    "proton pass",
+    "roboform",
+];
+
+/// Banks and payment sites, matched as whole words in the title of the window, the tab of a
+/// browser
+const BANKING_KEYWORDS: &[&str] = &[
+    "bank",
+    "banking",
+    "paypal",
+    "revolut",
+    "monzo",
+    "n26",
+    "chase",
+    "wells fargo",
+    "capital one",
+    "american express",
+    "schwab",
+    "fidelity",
+    "vanguard",
+    "coinbase",
+];
+
+/// Windows whose region is blanked in the frames captured, and which are never OCRed
+#[derive(Clone, Debug, Default)]
+pub struct SensitiveWindows {
+    /// Password managers and banking sites
+    builtin: bool,
+    /// Matched in the app name or the title, like the ignored windows
+    custom: Vec<String>,
+    /// Private (incognito) windows of browsers
+    private_windows: bool,
+}
+
+impl SensitiveWindows {
+    pub fn new(builtin: bool, custom: &[String]) -> Self {
+        Self {
+            builtin,
+            custom: custom
+                .iter()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_lowercase())
+                .collect(),
+            private_windows: false,
+        }
+    }
+
+    /// Also blanks the private windows of browsers and doesn't OCR them
+    pub fn with_private_windows(mut self, private_windows: bool) -> Self {
+        self.private_windows = private_windows;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.builtin && self.custom.is_empty() && !self.private_windows
+    }
+
+    /// Blocks, asking a browser whether its window is private may run a script
+    pub fn is_sensitive(&self, app_name: &str, title: &str) -> bool {
+        let app_name_lower = app_name.to_lowercase();
+        let title_lower = title.to_lowercase();
+        if self
+            .custom
+            .iter()
+            .any(|custom| app_name_lower.contains(custom) || title_lower.contains(custom))
+        {
+            return true;
+        }
+        if self.builtin
+            && (PASSWORD_MANAGERS
+                .iter()
+                .any(|manager| app_name_lower.contains(manager))
+                || BANKING_KEYWORDS
+                    .iter()
+                    .any(|keyword| contains_words(&title_lower, keyword)))
+        {
+            return true;
+        }
+        self.private_windows && is_private_window(app_name, title)
+    }
+
+    /// Clears the window title of `app` when its window is sensitive, so the title of e.g. a
+    /// private tab isn't stored with the frame. Returns whether it was, blocks like `is_sensitive`
+    pub fn hide_title(&self, app: &mut ActiveApp) -> bool {
+        let sensitive = self.is_sensitive(&app.name, &app.window_title);
+        if sensitive {
+            app.window_title.clear();
+        }
+        sensitive
+    }
+}
+
+/// Whether `words` is in `text` between non alphanumeric characters, "bank" is in "My Bank -
+/// Accounts" and not in "Embankment"
+fn contains_words(text: &str, words: &str) -> bool {
+    text.match_indices(words).any(|(start, _)| {
+        let end = start + words.len();
+        !text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric)
+            && !text[end..]
+                .chars()
+                .next()
+                .is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Paints the rectangle at `x`, `y` of `width` by `height` pixels of `image` black, the part of
+/// it outside the image is ignored
+pub fn blank_region(image: &mut DynamicImage, x: i64, y: i64, width: u32, height: u32) {
+    let (image_width, image_height) = (image.width() as i64, image.height() as i64);
+    let (left, top) = (x.clamp(0, image_width), y.clamp(0, image_height));
+    let right = (x + width as i64).clamp(0, image_width);
+    let bottom = (y + height as i64).clamp(0, image_height);
+    if left >= right || top >= bottom {
+        return;
+    }
+    if image.as_rgba8().is_none() {
+        *image = DynamicImage::ImageRgba8(image.to_rgba8());
+    }
+    let buffer = image.as_mut_rgba8().unwrap();
+    for py in top..bottom {
+        for px in left..right {
+            buffer.put_pixel(px as u32, py as u32, Rgba([0, 0, 0, 255]));
+        }
+    }
+}
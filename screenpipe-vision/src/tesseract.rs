@@ -1,26 +1,16 @@
+use crate::ocr_languages;
 use image::DynamicImage;
 use rusty_tesseract::{Args, DataOutput, Image};
-use screenpipe_core::{Language, TESSERACT_LANGUAGES};
+use screenpipe_core::Language;
 use std::collections::HashMap;
 
 pub fn perform_ocr_tesseract(
     image: &DynamicImage,
     languages: Vec<Language>,
 ) -> (String, String, Option<f64>) {
-    let language_string = match languages.is_empty() {
-        true => "eng".to_string(),
-        _ => TESSERACT_LANGUAGES
-            .iter()
-            .filter_map(|(key, val)| {
-                if languages.iter().any(|l| l == val) {
-                    Some(key.to_string())
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<String>>()
-            .join("+"),
-    };
+    let options = ocr_languages::current();
+    let language_string =
+        ocr_languages::tesseract_languages(&options.languages_or(&languages), options.auto_detect);
     let args = Args {
         lang: language_string,
         config_variables: HashMap::from([("tessedit_create_tsv".into(), "1".into())]),
@@ -57,54 +47,32 @@ fn data_output_to_text(data_output: &DataOutput) -> String {
     text
 }
 
+/// One entry per word, with its box and confidence
 fn data_output_to_json(data_output: &DataOutput) -> String {
-    let mut lines: Vec<HashMap<String, String>> = Vec::new();
-    let mut current_line = String::new();
-    let mut current_conf = 0.0;
-    let mut word_count = 0;
-    let mut last_word_num = 0;
+    let mut words: Vec<HashMap<String, String>> = Vec::new();
 
     for record in &data_output.data {
-        if record.word_num == 0 && !current_line.is_empty() {
-            let avg_conf = current_conf / word_count as f32;
-            let mut line_data = HashMap::new();
-            line_data.insert("text".to_string(), current_line.clone());
-            line_data.insert("confidence".to_string(), format!("{:.2}", avg_conf));
-            line_data.insert(
-                "line_position".to_string(),
-                format!(
-                    "level{}page_num{}block_num{}par_num{}line_num{}",
-                    record.level,
-                    record.page_num,
-                    record.block_num,
-                    record.par_num,
-                    record.line_num
-                ),
-            );
-            lines.push(line_data);
-            current_line.clear();
-            current_conf = 0.0;
-            word_count = 0;
-        }
-        if record.word_num > last_word_num {
-            if !current_line.is_empty() {
-                current_line.push(' ');
-            }
-            current_line.push_str(&record.text);
-            current_conf += record.conf;
-            word_count += 1;
+        // records of pages, blocks, paragraphs and lines have no text and a confidence of -1
+        if record.text.trim().is_empty() || record.conf < 0.0 {
+            continue;
         }
-        last_word_num = record.word_num;
-    }
-    if !current_line.is_empty() {
-        let avg_conf = current_conf / word_count as f32;
-        let mut line_data = HashMap::new();
-        line_data.insert("text".to_string(), current_line);
-        line_data.insert("confidence".to_string(), format!("{:.2}", avg_conf));
-        lines.push(line_data);
+        let mut word = HashMap::new();
+        word.insert("level".to_string(), record.level.to_string());
+        word.insert("page_num".to_string(), record.page_num.to_string());
+        word.insert("block_num".to_string(), record.block_num.to_string());
+        word.insert("par_num".to_string(), record.par_num.to_string());
+        word.insert("line_num".to_string(), record.line_num.to_string());
+        word.insert("word_num".to_string(), record.word_num.to_string());
+        word.insert("left".to_string(), record.left.to_string());
+        word.insert("top".to_string(), record.top.to_string());
+        word.insert("width".to_string(), record.width.to_string());
+        word.insert("height".to_string(), record.height.to_string());
+        word.insert("conf".to_string(), format!("{:.2}", record.conf));
+        word.insert("text".to_string(), record.text.clone());
+        words.push(word);
     }
 
-    serde_json::to_string_pretty(&lines).unwrap()
+    serde_json::to_string_pretty(&words).unwrap()
 }
 
 fn calculate_overall_confidence(data_output: &DataOutput) -> f64 {
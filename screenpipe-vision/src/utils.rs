@@ -1,9 +1,11 @@
 use crate::capture_screenshot_by_window::{
-    capture_all_visible_windows, CapturedWindow, WindowFilters,
+    capture_all_visible_windows, capture_single_window, sensitive_window_bounds, CapturedWindow,
+    WindowFilters,
 };
 use crate::core::MaxAverageFrame;
 use crate::custom_ocr::CustomOcrConfig;
 use crate::monitor::SafeMonitor;
+use crate::sensitive_windows::blank_region;
 use image::DynamicImage;
 use image_compare::{Algorithm, Metric, Similarity};
 use log::{debug, error, warn};
@@ -17,6 +19,8 @@ pub enum OcrEngine {
     Tesseract,
     WindowsNative,
     AppleNative,
+    /// PaddleOCR models run with onnxruntime
+    PaddleOcr,
     Custom(CustomOcrConfig),
 }
 
@@ -45,17 +49,83 @@ pub fn compare_images_ssim(image1: &DynamicImage, image2: &DynamicImage) -> f64
     result.score
 }
 
+/// Captures `monitor` and its windows. In single window mode, the window alone drawn where it is
+/// on the monitor, None when it isn't on the monitor
 pub async fn capture_screenshot(
     monitor: &SafeMonitor,
     window_filters: &WindowFilters,
     capture_unfocused_windows: bool,
-) -> Result<(DynamicImage, Vec<CapturedWindow>, u64, Duration), anyhow::Error> {
+) -> Result<Option<(DynamicImage, Vec<CapturedWindow>, u64, Duration)>, anyhow::Error> {
     // info!("Starting screenshot capture for monitor: {:?}", monitor);
     let capture_start = Instant::now();
-    let image = monitor.capture_image().await.map_err(|e| {
+    if let Some(target) = window_filters.single_window() {
+        let single_window = capture_single_window(monitor, target).await.map_err(|e| {
+            error!("Failed to capture window {}: {}", target, e);
+            anyhow::anyhow!("Window capture failed")
+        })?;
+        let Some(single_window) = single_window.filter(|single_window| {
+            !window_filters.sensitive_windows().is_sensitive(
+                &single_window.window.app_name,
+                &single_window.window.window_name,
+            )
+        }) else {
+            return Ok(None);
+        };
+        // the rest of the monitor is left black, the video keeps the size of the monitor
+        // whatever the size of the window
+        let mut image = DynamicImage::new_rgba8(monitor.width(), monitor.height());
+        let window_image = single_window.window.image.resize_exact(
+            single_window.width,
+            single_window.height,
+            image::imageops::FilterType::Triangle,
+        );
+        let (monitor_x, monitor_y) = monitor.position();
+        image::imageops::overlay(
+            &mut image,
+            &window_image,
+            (single_window.x - monitor_x) as i64,
+            (single_window.y - monitor_y) as i64,
+        );
+        let image_hash = calculate_hash(&image);
+        return Ok(Some((
+            image,
+            vec![single_window.window],
+            image_hash,
+            capture_start.elapsed(),
+        )));
+    }
+
+    let mut image = monitor.capture_image().await.map_err(|e| {
         error!("Failed to capture monitor image: {}", e);
         anyhow::anyhow!("Monitor capture failed")
     })?;
+    if !window_filters.sensitive_windows().is_empty() {
+        // blanked before anything reads the frame, a window that can't be located blanks it all
+        match sensitive_window_bounds(monitor, window_filters.sensitive_windows()).await {
+            Ok(bounds) => {
+                // windows are placed in points, the frame may have more pixels (retina)
+                let scale = image.width() as f64 / monitor.width().max(1) as f64;
+                let (monitor_x, monitor_y) = monitor.position();
+                for (x, y, width, height) in bounds {
+                    blank_region(
+                        &mut image,
+                        ((x - monitor_x) as f64 * scale).floor() as i64,
+                        ((y - monitor_y) as f64 * scale).floor() as i64,
+                        (width as f64 * scale).ceil() as u32,
+                        (height as f64 * scale).ceil() as u32,
+                    );
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to locate sensitive windows, blanking the frame: {}",
+                    e
+                );
+                let (width, height) = (image.width(), image.height());
+                blank_region(&mut image, 0, 0, width, height);
+            }
+        }
+    }
     let image_hash = calculate_hash(&image);
     let capture_duration = capture_start.elapsed();
 
@@ -72,7 +142,7 @@ pub async fn capture_screenshot(
             }
         };
 
-    Ok((image, window_images, image_hash, capture_duration))
+    Ok(Some((image, window_images, image_hash, capture_duration)))
 }
 
 pub async fn compare_with_previous_image(
@@ -0,0 +1,94 @@
+use once_cell::sync::{Lazy, OnceCell};
+use screenpipe_core::meeting;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::info;
+use xcap::Window;
+
+/// How long whether a call is going on is reused for, listing the windows each frame of each
+/// monitor would be wasteful
+const CALL_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Apps for calls, matched in the lowercase name of the app. Their windows don't always tell a
+/// call is going on, e.g. teams calls in the main window
+const MEETING_APPS: &[&str] = &[
+    "zoom", "teams", "webex", "slack", "facetime", "discord", "skype",
+];
+
+static LAST_CALL_CHECK: Lazy<Mutex<Option<(Instant, bool)>>> = Lazy::new(|| Mutex::new(None));
+
+/// Frames per second captured at least during meetings
+static MEETING_FPS: OnceCell<f64> = OnceCell::new();
+
+/// Whether the window of `app_name` titled `title` is the one of a video call, in the app or in
+/// a browser. The windows of the apps outside of calls (chats, lobbies) have other titles
+pub fn is_call_window(app_name: &str, title: &str) -> bool {
+    let app_name = app_name.to_lowercase();
+    let title = title.to_lowercase();
+
+    // zoom names its call window the same in the app and on the web
+    title.contains("zoom meeting")
+        || title.contains("zoom webinar")
+        // "Meeting with Ana | Microsoft Teams", "Call with Ana | Microsoft Teams"
+        || ((app_name.contains("teams") || title.contains("microsoft teams"))
+            && (title.contains("meeting") || title.contains("call with")))
+        // "Meet - abc-defg-hij", the landing page is "Google Meet"
+        || title.starts_with("meet - ")
+        || title.starts_with("meet – ")
+        || (app_name.contains("webex") && title.contains("meeting"))
+        || (app_name.contains("slack") && title.contains("huddle"))
+}
+
+pub fn is_meeting_app(app_name: &str) -> bool {
+    let app_name = app_name.to_lowercase();
+    MEETING_APPS.iter().any(|app| app_name.contains(app))
+}
+
+/// App name and title of the window of a video call, focused or not. Blocks, it lists the
+/// windows
+pub fn call_window() -> Option<(String, String)> {
+    find_window(is_call_window)
+}
+
+/// App name and title of a window of an app for calls, in a call or not. Blocks, it lists the
+/// windows
+pub fn meeting_app_window() -> Option<(String, String)> {
+    find_window(|app_name, _| is_meeting_app(app_name))
+}
+
+fn find_window(matches: impl Fn(&str, &str) -> bool) -> Option<(String, String)> {
+    Window::all()
+        .ok()?
+        .iter()
+        .find(|window| matches(window.app_name(), window.title()))
+        .map(|window| (window.app_name().to_string(), window.title().to_string()))
+}
+
+/// Captures at least `fps` frames per second while a meeting goes on
+pub fn init_meeting_fps(fps: f64) {
+    if fps.is_finite() && fps > 0.0 {
+        info!("capturing {} frames per second during meetings", fps);
+        let _ = MEETING_FPS.set(fps);
+    }
+}
+
+/// `interval`, shortened to the one of the meeting fps while a meeting goes on
+pub fn capture_interval(interval: Duration) -> Duration {
+    match MEETING_FPS.get() {
+        Some(fps) if meeting::in_meeting() => interval.min(Duration::from_secs_f64(1.0 / fps)),
+        _ => interval,
+    }
+}
+
+/// Whether a window of a video call is open, focused or not. Blocks, it lists the windows
+pub fn is_in_call() -> bool {
+    let mut last_check = LAST_CALL_CHECK.lock().unwrap();
+    if let Some((checked_at, in_call)) = *last_check {
+        if checked_at.elapsed() < CALL_CHECK_INTERVAL {
+            return in_call;
+        }
+    }
+    let in_call = call_window().is_some();
+    *last_check = Some((Instant::now(), in_call));
+    in_call
+}
@@ -0,0 +1,205 @@
+/// Number of the virtual desktop (space on macos, workspace on linux) shown on the monitor of
+/// `monitor_id`, from 1 in the order the os lists them. None when the os doesn't tell. Blocks, it
+/// may run a command on linux
+pub fn current_desktop(monitor_id: u32) -> Option<u32> {
+    platform::current_desktop(monitor_id)
+}
+
+/// Desktop number in the output of `xprop -root -notype _NET_CURRENT_DESKTOP`, like
+/// "_NET_CURRENT_DESKTOP = 1" for the second desktop
+pub fn parse_xprop_desktop(output: &str) -> Option<u32> {
+    let (_, index) = output.trim().split_once('=')?;
+    index.trim().parse::<u32>().ok().map(|index| index + 1)
+}
+
+/// Number of the desktop whose 16 bytes id is `current` in `ids`, the ids of all the desktops
+/// one after the other as windows stores them
+pub fn desktop_number(ids: &[u8], current: &[u8]) -> Option<u32> {
+    ids.chunks_exact(16)
+        .position(|id| id == current)
+        .map(|index| index as u32 + 1)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use core_foundation::array::{CFArray, CFArrayRef};
+    use core_foundation::base::{CFType, CFTypeRef, TCFType};
+    use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+    use core_foundation::number::CFNumber;
+    use core_foundation::string::{CFString, CFStringRef};
+    use std::ffi::c_void;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGSMainConnectionID() -> i32;
+        fn CGSCopyManagedDisplaySpaces(connection: i32) -> CFArrayRef;
+    }
+
+    #[link(name = "ColorSync", kind = "framework")]
+    extern "C" {
+        fn CGDisplayCreateUUIDFromDisplayID(display: u32) -> CFTypeRef;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFUUIDCreateString(allocator: *const c_void, uuid: CFTypeRef) -> CFStringRef;
+    }
+
+    type Dictionary = CFDictionary<CFString, CFType>;
+
+    fn value(dictionary: &Dictionary, key: &'static str) -> Option<CFType> {
+        dictionary
+            .find(CFString::from_static_string(key))
+            .map(|value| (*value).clone())
+    }
+
+    fn dictionary(value: CFType) -> Option<Dictionary> {
+        value.instance_of::<CFDictionary>().then(|| unsafe {
+            Dictionary::wrap_under_get_rule(value.as_CFTypeRef() as CFDictionaryRef)
+        })
+    }
+
+    fn array(value: CFType) -> Option<CFArray<CFType>> {
+        value
+            .instance_of::<CFArray>()
+            .then(|| unsafe { CFArray::wrap_under_get_rule(value.as_CFTypeRef() as CFArrayRef) })
+    }
+
+    fn space_id(space: &Dictionary) -> Option<i64> {
+        value(space, "ManagedSpaceID")?
+            .downcast::<CFNumber>()?
+            .to_i64()
+    }
+
+    fn display_uuid(display: u32) -> Option<String> {
+        let uuid = unsafe { CGDisplayCreateUUIDFromDisplayID(display) };
+        if uuid.is_null() {
+            return None;
+        }
+        let uuid = unsafe { CFType::wrap_under_create_rule(uuid) };
+        let string = unsafe { CFUUIDCreateString(std::ptr::null(), uuid.as_CFTypeRef()) };
+        if string.is_null() {
+            return None;
+        }
+        Some(unsafe { CFString::wrap_under_create_rule(string) }.to_string())
+    }
+
+    /// From the spaces mission control manages for each display
+    pub fn current_desktop(monitor_id: u32) -> Option<u32> {
+        let displays = unsafe { CGSCopyManagedDisplaySpaces(CGSMainConnectionID()) };
+        if displays.is_null() {
+            return None;
+        }
+        let displays: CFArray<CFType> = unsafe { CFArray::wrap_under_create_rule(displays) };
+        let displays: Vec<Dictionary> = displays
+            .iter()
+            .filter_map(|display| dictionary((*display).clone()))
+            .collect();
+        let uuid = display_uuid(monitor_id);
+        // when displays don't have separate spaces a single "Main" display has them all
+        let display = displays
+            .iter()
+            .find(|display| {
+                value(display, "Display Identifier")
+                    .and_then(|id| id.downcast::<CFString>())
+                    .map(|id| id.to_string())
+                    == uuid
+            })
+            .or_else(|| displays.first().filter(|_| displays.len() == 1))?;
+
+        let current = space_id(&dictionary(value(display, "Current Space")?)?)?;
+        array(value(display, "Spaces")?)?
+            .iter()
+            .filter_map(|space| dictionary((*space).clone()))
+            .position(|space| space_id(&space) == Some(current))
+            .map(|index| index as u32 + 1)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::desktop_number;
+    use windows::core::HSTRING;
+    use windows::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_BINARY};
+    use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+
+    const VIRTUAL_DESKTOPS: &str =
+        r"Software\Microsoft\Windows\CurrentVersion\Explorer\VirtualDesktops";
+
+    fn binary_value(key: &str, value: &str) -> Option<Vec<u8>> {
+        let (key, value) = (HSTRING::from(key), HSTRING::from(value));
+        let mut size = 0u32;
+        unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                &key,
+                &value,
+                RRF_RT_REG_BINARY,
+                None,
+                None,
+                Some(&mut size as *mut u32),
+            )
+        }
+        .ok()
+        .ok()?;
+        let mut data = vec![0u8; size as usize];
+        unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                &key,
+                &value,
+                RRF_RT_REG_BINARY,
+                None,
+                Some(data.as_mut_ptr() as *mut _),
+                Some(&mut size as *mut u32),
+            )
+        }
+        .ok()
+        .ok()?;
+        data.truncate(size as usize);
+        Some(data)
+    }
+
+    /// From the registry, where explorer keeps the ids of the desktops in order and the current
+    /// one, under the session on windows 10
+    pub fn current_desktop(_monitor_id: u32) -> Option<u32> {
+        let ids = binary_value(VIRTUAL_DESKTOPS, "VirtualDesktopIDs")?;
+        let current = binary_value(VIRTUAL_DESKTOPS, "CurrentVirtualDesktop").or_else(|| {
+            let mut session = 0u32;
+            unsafe { ProcessIdToSessionId(std::process::id(), &mut session) }.ok()?;
+            binary_value(
+                &format!(
+                    r"Software\Microsoft\Windows\CurrentVersion\Explorer\SessionInfo\{}\VirtualDesktops",
+                    session
+                ),
+                "CurrentVirtualDesktop",
+            )
+        })?;
+        desktop_number(&ids, &current)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::parse_xprop_desktop;
+    use std::process::Command;
+
+    /// From the window manager through `xprop`, on x11
+    pub fn current_desktop(_monitor_id: u32) -> Option<u32> {
+        let output = Command::new("xprop")
+            .args(["-root", "-notype", "_NET_CURRENT_DESKTOP"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_xprop_desktop(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod platform {
+    pub fn current_desktop(_monitor_id: u32) -> Option<u32> {
+        None
+    }
+}
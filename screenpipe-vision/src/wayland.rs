@@ -0,0 +1,393 @@
+use anyhow::{anyhow, Context, Result};
+use ashpd::desktop::screencast::{CursorMode, Screencast, SourceType};
+use ashpd::desktop::{PersistMode, Session};
+use image::{DynamicImage, RgbaImage};
+use once_cell::sync::{Lazy, OnceCell};
+use pipewire as pw;
+use pw::spa;
+use std::os::fd::OwnedFd;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::monitor::MonitorData;
+
+/// Name of the file in the data dir keeping the token of the screencast permission, so the portal
+/// doesn't ask again after a restart
+const RESTORE_TOKEN_FILE: &str = "wayland_restore_token";
+/// Time to wait for the first frame of a stream
+const FIRST_FRAME_TIMEOUT: Duration = Duration::from_secs(3);
+/// Time before asking the portal again when it was refused or failed, not to prompt in a loop
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+static DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+static SESSION: Lazy<tokio::sync::Mutex<PortalState>> = Lazy::new(Default::default);
+
+#[derive(Default)]
+struct PortalState {
+    session: Option<PortalSession>,
+    failed_at: Option<Instant>,
+}
+
+/// A screencast of every monitor, with the connection to the portal keeping it open
+struct PortalSession {
+    _proxy: Screencast<'static>,
+    _session: Session<'static, Screencast<'static>>,
+    streams: Vec<PortalStream>,
+    alive: Arc<AtomicBool>,
+}
+
+struct PortalStream {
+    /// Where the monitor is in the virtual screen and its size, when the compositor tells
+    position: Option<(i32, i32)>,
+    size: Option<(i32, i32)>,
+    frame: Arc<Mutex<Option<RgbaImage>>>,
+}
+
+/// Whether the session is a wayland one, where x11 capture sees nothing but the x11 apps
+pub fn is_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland")
+}
+
+/// Keeps the screencast permission in `data_dir`, defaults to ~/.screenpipe
+pub fn init(data_dir: PathBuf) {
+    let _ = DATA_DIR.set(data_dir);
+}
+
+fn restore_token_path() -> Option<PathBuf> {
+    DATA_DIR
+        .get()
+        .cloned()
+        .or_else(|| dirs::home_dir().map(|home| home.join(".screenpipe")))
+        .map(|dir| dir.join(RESTORE_TOKEN_FILE))
+}
+
+/// Latest frame of `monitor`, through the screencast portal. The first call starts the session,
+/// which prompts the user unless a permission was given before
+pub async fn capture_monitor(monitor: &MonitorData) -> Result<DynamicImage> {
+    let frame = {
+        let mut state = SESSION.lock().await;
+        if state
+            .session
+            .as_ref()
+            .is_some_and(|session| !session.alive.load(Ordering::Relaxed))
+        {
+            warn!("wayland screencast ended, starting a new one");
+            state.session = None;
+        }
+        if state.session.is_none() {
+            if let Some(failed_at) = state.failed_at {
+                if failed_at.elapsed() < RETRY_INTERVAL {
+                    return Err(anyhow!("wayland screencast unavailable"));
+                }
+            }
+            match PortalSession::start().await {
+                Ok(session) => state.session = Some(session),
+                Err(e) => {
+                    state.failed_at = Some(Instant::now());
+                    return Err(e.context("failed to start the wayland screencast"));
+                }
+            }
+        }
+        let session = state.session.as_ref().unwrap();
+        Arc::clone(&session.stream_for(monitor)?.frame)
+    };
+
+    let start = Instant::now();
+    loop {
+        if let Some(image) = frame.lock().unwrap().clone() {
+            return Ok(DynamicImage::ImageRgba8(image));
+        }
+        if start.elapsed() > FIRST_FRAME_TIMEOUT {
+            return Err(anyhow!(
+                "no frame from the wayland screencast of {}",
+                monitor.name
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+impl PortalSession {
+    async fn start() -> Result<Self> {
+        let token_path = restore_token_path();
+        let restore_token = token_path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty());
+        debug!(
+            "starting the wayland screencast, {} restore token",
+            if restore_token.is_some() {
+                "with a"
+            } else {
+                "without"
+            }
+        );
+
+        let proxy = Screencast::new().await?;
+        let session = proxy.create_session().await?;
+        proxy
+            .select_sources(
+                &session,
+                CursorMode::Hidden,
+                SourceType::Monitor.into(),
+                true,
+                restore_token.as_deref(),
+                PersistMode::ExplicitlyRevoked,
+            )
+            .await?;
+        let response = proxy.start(&session, None).await?.response()?;
+        if response.streams().is_empty() {
+            return Err(anyhow!("no monitor shared"));
+        }
+
+        // the token is single use, the portal gives a new one every time
+        if let (Some(path), Some(token)) = (token_path, response.restore_token()) {
+            if let Err(e) = std::fs::write(&path, token) {
+                warn!(
+                    "failed to save the wayland restore token to {:?}: {}",
+                    path, e
+                );
+            }
+        }
+
+        let fd = proxy.open_pipe_wire_remote(&session).await?;
+        let streams: Vec<PortalStream> = response
+            .streams()
+            .iter()
+            .map(|stream| PortalStream {
+                position: stream.position(),
+                size: stream.size(),
+                frame: Default::default(),
+            })
+            .collect();
+        let nodes = response
+            .streams()
+            .iter()
+            .zip(&streams)
+            .map(|(stream, portal_stream)| {
+                (stream.pipe_wire_node_id(), Arc::clone(&portal_stream.frame))
+            })
+            .collect();
+        let alive = Arc::new(AtomicBool::new(true));
+        let thread_alive = Arc::clone(&alive);
+        std::thread::Builder::new()
+            .name("wayland-screencast".to_string())
+            .spawn(move || {
+                if let Err(e) = receive_frames(fd, nodes, &thread_alive) {
+                    warn!("wayland screencast failed: {}", e);
+                }
+                thread_alive.store(false, Ordering::Relaxed);
+            })?;
+        info!(
+            "capturing {} monitors through the wayland screencast portal",
+            streams.len()
+        );
+
+        Ok(Self {
+            _proxy: proxy,
+            _session: session,
+            streams,
+            alive,
+        })
+    }
+
+    /// The stream of `monitor`, found by its position, then its size
+    fn stream_for(&self, monitor: &MonitorData) -> Result<&PortalStream> {
+        let size = (monitor.width as i32, monitor.height as i32);
+        self.streams
+            .iter()
+            .find(|stream| stream.position == Some((monitor.x, monitor.y)))
+            .or_else(|| self.streams.iter().find(|stream| stream.size == Some(size)))
+            .or_else(|| match self.streams.as_slice() {
+                [stream] => Some(stream),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("monitor {} not shared by the screencast", monitor.name))
+    }
+}
+
+struct StreamData {
+    format: spa::param::video::VideoInfoRaw,
+    frame: Arc<Mutex<Option<RgbaImage>>>,
+}
+
+/// Runs the pipewire loop receiving the frames of the `nodes` until a stream fails
+fn receive_frames(
+    fd: OwnedFd,
+    nodes: Vec<(u32, Arc<Mutex<Option<RgbaImage>>>)>,
+    alive: &Arc<AtomicBool>,
+) -> Result<()> {
+    pw::init();
+    let mainloop = pw::main_loop::MainLoop::new(None)?;
+    let context = pw::context::Context::new(&mainloop)?;
+    let core = context.connect_fd(fd, None)?;
+
+    let format = format_param()?;
+    let mut streams = Vec::new();
+    for (node_id, frame) in nodes {
+        let stream = pw::stream::Stream::new(
+            &core,
+            "screenpipe",
+            pw::properties::properties! {
+                *pw::keys::MEDIA_TYPE => "Video",
+                *pw::keys::MEDIA_CATEGORY => "Capture",
+                *pw::keys::MEDIA_ROLE => "Screen",
+            },
+        )?;
+        let data = StreamData {
+            format: Default::default(),
+            frame,
+        };
+        let state_mainloop = mainloop.clone();
+        let state_alive = Arc::clone(alive);
+        let listener = stream
+            .add_local_listener_with_user_data(data)
+            .state_changed(move |_, _, _, state| {
+                if let pw::stream::StreamState::Error(e) = state {
+                    warn!("wayland screencast stream {} failed: {}", node_id, e);
+                    state_alive.store(false, Ordering::Relaxed);
+                    state_mainloop.quit();
+                }
+            })
+            .param_changed(|_, data, id, param| {
+                let Some(param) = param else {
+                    return;
+                };
+                if id != spa::param::ParamType::Format.as_raw() {
+                    return;
+                }
+                if let Err(e) = data.format.parse(param) {
+                    warn!("unexpected wayland screencast format: {:?}", e);
+                }
+            })
+            .process(|stream, data| {
+                let Some(mut buffer) = stream.dequeue_buffer() else {
+                    return;
+                };
+                let Some(buffer_data) = buffer.datas_mut().first_mut() else {
+                    return;
+                };
+                let stride = buffer_data.chunk().stride() as usize;
+                let size = data.format.size();
+                let bgr = matches!(
+                    data.format.format(),
+                    spa::param::video::VideoFormat::BGRx | spa::param::video::VideoFormat::BGRA
+                );
+                let Some(bytes) = buffer_data.data() else {
+                    return;
+                };
+                if let Some(image) = frame_image(bytes, size.width, size.height, stride, bgr) {
+                    *data.frame.lock().unwrap() = Some(image);
+                }
+            })
+            .register()?;
+
+        let mut params =
+            [spa::pod::Pod::from_bytes(&format).context("invalid wayland screencast format")?];
+        stream.connect(
+            spa::utils::Direction::Input,
+            Some(node_id),
+            pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+            &mut params,
+        )?;
+        streams.push((stream, listener));
+    }
+
+    mainloop.run();
+    Ok(())
+}
+
+/// The formats of frames asked to the compositor, 4 bytes per pixel in memory
+fn format_param() -> Result<Vec<u8>> {
+    use spa::param::format::{FormatProperties, MediaSubtype, MediaType};
+    use spa::param::video::VideoFormat;
+    use spa::utils::{Fraction, Rectangle};
+
+    let object = spa::pod::object!(
+        spa::utils::SpaTypes::ObjectParamFormat,
+        spa::param::ParamType::EnumFormat,
+        spa::pod::property!(FormatProperties::MediaType, Id, MediaType::Video),
+        spa::pod::property!(FormatProperties::MediaSubtype, Id, MediaSubtype::Raw),
+        spa::pod::property!(
+            FormatProperties::VideoFormat,
+            Choice,
+            Enum,
+            Id,
+            VideoFormat::BGRx,
+            VideoFormat::BGRx,
+            VideoFormat::BGRA,
+            VideoFormat::RGBx,
+            VideoFormat::RGBA
+        ),
+        spa::pod::property!(
+            FormatProperties::VideoSize,
+            Choice,
+            Range,
+            Rectangle,
+            Rectangle {
+                width: 1920,
+                height: 1080
+            },
+            Rectangle {
+                width: 1,
+                height: 1
+            },
+            Rectangle {
+                width: 8192,
+                height: 8192
+            }
+        ),
+        spa::pod::property!(
+            FormatProperties::VideoFramerate,
+            Choice,
+            Range,
+            Fraction,
+            Fraction { num: 30, denom: 1 },
+            Fraction { num: 0, denom: 1 },
+            Fraction { num: 240, denom: 1 }
+        ),
+    );
+    Ok(spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &spa::pod::Value::Object(object),
+    )
+    .map_err(|e| anyhow!("failed to serialize the wayland screencast format: {:?}", e))?
+    .0
+    .into_inner())
+}
+
+/// Image of a frame of `width` x `height` pixels of 4 bytes, in rows `stride` bytes apart. The
+/// 4th byte is padding in the x formats, so the image is opaque
+pub fn frame_image(
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    bgr: bool,
+) -> Option<RgbaImage> {
+    let row_size = width as usize * 4;
+    let stride = if stride == 0 { row_size } else { stride };
+    if width == 0
+        || height == 0
+        || stride < row_size
+        || bytes.len() < stride * (height as usize - 1) + row_size
+    {
+        return None;
+    }
+    let mut pixels = Vec::with_capacity(row_size * height as usize);
+    for row in bytes.chunks(stride).take(height as usize) {
+        for pixel in row[..row_size].chunks_exact(4) {
+            if bgr {
+                pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+            } else {
+                pixels.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+            }
+        }
+    }
+    RgbaImage::from_raw(width, height, pixels)
+}
@@ -0,0 +1,31 @@
+use screenpipe_vision::adaptive_fps::{AdaptiveFps, AdaptiveFpsConfig};
+use std::time::Duration;
+
+#[test]
+fn test_adaptive_fps() {
+    let mut fps = AdaptiveFps::new(1.0, AdaptiveFpsConfig::default());
+    assert_eq!(fps.interval(), Duration::from_secs(1));
+
+    // halved after every 5 still frames, down to 0.2 fps
+    let intervals: Vec<Duration> = (0..20).map(|_| fps.update(0.001)).collect();
+    assert_eq!(intervals[3], Duration::from_secs(1));
+    assert_eq!(intervals[4], Duration::from_secs(2));
+    assert_eq!(intervals[9], Duration::from_secs(4));
+    assert_eq!(intervals[14], Duration::from_secs(5));
+    assert_eq!(intervals[19], Duration::from_secs(5));
+
+    // small changes keep the rate, and start counting still frames over
+    let mut fps = AdaptiveFps::new(1.0, AdaptiveFpsConfig::default());
+    for _ in 0..4 {
+        fps.update(0.001);
+    }
+    assert_eq!(fps.update(0.01), Duration::from_secs(1));
+    for _ in 0..4 {
+        assert_eq!(fps.update(0.001), Duration::from_secs(1));
+    }
+    assert_eq!(fps.update(0.001), Duration::from_secs(2));
+    assert_eq!(fps.update(0.01), Duration::from_secs(2));
+
+    // a change is captured at full rate right away
+    assert_eq!(fps.update(0.3), Duration::from_secs(1));
+}
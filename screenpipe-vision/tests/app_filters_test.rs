@@ -0,0 +1,42 @@
+use screenpipe_vision::active_window::{ActiveApp, AppFilters};
+
+#[test]
+fn test_app_filters() {
+    let keepass = ActiveApp {
+        name: "KeePassXC".to_string(),
+        process_name: "keepassxc".to_string(),
+        bundle_id: Some("org.keepassxc.keepassxc".to_string()),
+        window_title: "Passwords.kdbx".to_string(),
+        process_id: 412,
+    };
+    let chrome = ActiveApp {
+        name: "Google Chrome".to_string(),
+        process_name: "chrome".to_string(),
+        bundle_id: Some("com.google.Chrome".to_string()),
+        window_title: "screenpipe".to_string(),
+        process_id: 580,
+    };
+
+    // matched by bundle id, process name or name, whole and without case
+    let filters = AppFilters::new(&["org.keepassxc.KeePassXC".to_string()], &[]);
+    assert!(!filters.is_allowed(Some(&keepass)));
+    assert!(filters.is_allowed(Some(&chrome)));
+    assert!(filters.is_allowed(None));
+    assert!(filters.is_app_name_allowed("Google Chrome"));
+    let filters = AppFilters::new(&["keepassxc".to_string()], &[]);
+    assert!(!filters.is_allowed(Some(&keepass)));
+    assert!(!filters.is_app_name_allowed("KeePassXC"));
+    let filters = AppFilters::new(&["Chrom".to_string()], &[]);
+    assert!(filters.is_allowed(Some(&chrome)));
+
+    // only included apps are recorded, never an unknown one
+    let filters = AppFilters::new(&[], &["chrome".to_string()]);
+    assert!(filters.is_allowed(Some(&chrome)));
+    assert!(!filters.is_allowed(Some(&keepass)));
+    assert!(!filters.is_allowed(None));
+
+    // ignoring wins over including
+    let filters = AppFilters::new(&["Google Chrome".to_string()], &["chrome".to_string()]);
+    assert!(!filters.is_allowed(Some(&chrome)));
+    assert!(AppFilters::new(&[], &[]).is_empty());
+}
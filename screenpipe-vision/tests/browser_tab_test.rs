@@ -0,0 +1,70 @@
+use screenpipe_vision::browser_tab::{is_browser, is_private_window, tab_title};
+
+#[test]
+fn test_is_browser() {
+    assert!(is_browser("Google Chrome"));
+    assert!(is_browser("firefox"));
+    assert!(is_browser("Arc"));
+    assert!(!is_browser("Archive Utility"));
+    assert!(!is_browser("Code"));
+}
+
+#[test]
+fn test_tab_title() {
+    assert_eq!(tab_title("Google Chrome", "Inbox - Google Chrome"), "Inbox");
+    assert_eq!(tab_title("Firefox", "Issues — Firefox"), "Issues");
+    // macOS titles have no browser name
+    assert_eq!(tab_title("Safari", "Pull requests"), "Pull requests");
+}
+
+#[test]
+fn test_is_private_window() {
+    assert!(is_private_window(
+        "Google Chrome",
+        "New Tab - Google Chrome (Incognito)"
+    ));
+    assert!(is_private_window(
+        "firefox",
+        "Inbox — Mozilla Firefox Private Browsing"
+    ));
+    assert!(is_private_window(
+        "Microsoft Edge",
+        "[InPrivate] Bing - Microsoft Edge"
+    ));
+    assert!(!is_private_window("firefox", "Inbox — Mozilla Firefox"));
+    // only browsers have private windows
+    assert!(!is_private_window("Code", "incognito.rs"));
+}
+
+// browsers are only known through the companion extension on linux
+#[cfg(target_os = "linux")]
+#[test]
+fn test_reported_tab() {
+    use screenpipe_vision::browser_tab::{get_browser_tab, report_tab, BrowserTab};
+
+    let tab = BrowserTab {
+        url: "https://github.com/mediar-ai/screenpipe".to_string(),
+        title: "mediar-ai/screenpipe".to_string(),
+        incognito: false,
+    };
+    report_tab(tab.clone());
+    assert_eq!(
+        get_browser_tab("firefox", "mediar-ai/screenpipe — Mozilla Firefox"),
+        Some(tab)
+    );
+    // another tab is shown than the one reported
+    assert_eq!(get_browser_tab("firefox", "Inbox — Mozilla Firefox"), None);
+    assert_eq!(get_browser_tab("Code", "mediar-ai/screenpipe"), None);
+
+    // the url of a private tab isn't kept, its window is private
+    report_tab(BrowserTab {
+        url: "https://example.com/gift".to_string(),
+        title: "Gift ideas".to_string(),
+        incognito: true,
+    });
+    assert_eq!(
+        get_browser_tab("firefox", "Gift ideas — Mozilla Firefox"),
+        None
+    );
+    assert!(is_private_window("firefox", "Gift ideas — Mozilla Firefox"));
+}
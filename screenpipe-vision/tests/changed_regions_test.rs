@@ -0,0 +1,83 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use screenpipe_vision::changed_regions::{band_hashes, changed_rows, merge_words, rows_to_ocr};
+use std::collections::HashMap;
+
+fn word(text: &str, top: f64, height: f64, line: &str) -> HashMap<String, String> {
+    HashMap::from([
+        ("text".to_string(), text.to_string()),
+        ("left".to_string(), "10".to_string()),
+        ("top".to_string(), top.to_string()),
+        ("width".to_string(), "50".to_string()),
+        ("height".to_string(), height.to_string()),
+        ("block_num".to_string(), "1".to_string()),
+        ("line_num".to_string(), line.to_string()),
+    ])
+}
+
+#[test]
+fn test_changed_rows() {
+    let mut image = RgbaImage::new(200, 400);
+    let previous = band_hashes(&DynamicImage::ImageRgba8(image.clone()));
+    assert!(changed_rows(&previous, &previous, 400).is_empty());
+
+    // a character typed on a line
+    image.put_pixel(50, 100, Rgba([255, 255, 255, 255]));
+    let bands = band_hashes(&DynamicImage::ImageRgba8(image.clone()));
+    assert_eq!(changed_rows(&previous, &bands, 400), vec![(96, 112)]);
+
+    // and another far below
+    image.put_pixel(50, 300, Rgba([255, 255, 255, 255]));
+    let bands = band_hashes(&DynamicImage::ImageRgba8(image));
+    assert_eq!(
+        changed_rows(&previous, &bands, 400),
+        vec![(96, 112), (288, 304)]
+    );
+}
+
+#[test]
+fn test_rows_to_ocr() {
+    let words = vec![
+        word("hello", 90.0, 20.0, "1"),
+        word("world", 200.0, 20.0, "2"),
+    ];
+    // grown to the whole line cut through
+    assert_eq!(
+        rows_to_ocr(&[(96, 112)], &words, 400, false),
+        Some(vec![(90, 116)])
+    );
+    // too much changed
+    assert_eq!(rows_to_ocr(&[(0, 300)], &words, 400, false), None);
+    // words that can't be located
+    let mut unlocated = word("?", 0.0, 0.0, "3");
+    unlocated.remove("top");
+    assert_eq!(rows_to_ocr(&[(96, 112)], &[unlocated], 400, false), None);
+}
+
+#[test]
+fn test_merge_words() {
+    let previous = vec![
+        word("hello", 90.0, 20.0, "1"),
+        word("world", 200.0, 20.0, "2"),
+    ];
+    // read in the crop of rows 90 to 116
+    let read = vec![word("goodbye", 2.0, 20.0, "1")];
+    let words = merge_words(&previous, &[(90, 116)], vec![read], 400, false, 1);
+
+    let texts: Vec<&str> = words.iter().map(|word| word["text"].as_str()).collect();
+    assert_eq!(texts, vec!["world", "goodbye"]);
+    assert_eq!(words[1]["top"], "92");
+    assert_eq!(words[1]["block_num"], "1.0.1");
+}
+
+#[test]
+fn test_merge_normalized_words() {
+    // apple native's boxes are fractions from the bottom left corner
+    let previous = vec![word("hello", 0.5, 0.1, "1")];
+    // the bottom half of a window of 100 rows
+    let read = vec![word("goodbye", 0.2, 0.2, "1")];
+    let words = merge_words(&previous, &[(50, 100)], vec![read], 100, true, 1);
+
+    assert_eq!(words.len(), 2);
+    assert_eq!(words[1]["top"], "0.1");
+    assert_eq!(words[1]["height"], "0.1");
+}
@@ -0,0 +1,73 @@
+use screenpipe_vision::accessibility::{Bounds, Selection};
+use screenpipe_vision::cursor::{parse_xdotool_location, CursorOverlay};
+use screenpipe_vision::monitor::MonitorData;
+
+/// A 1920x1080 monitor right of the primary one
+fn monitor() -> MonitorData {
+    MonitorData {
+        width: 1920,
+        height: 1080,
+        x: 2560,
+        y: 0,
+        name: "DELL U2720Q".to_string(),
+        is_primary: false,
+    }
+}
+
+#[test]
+fn test_cursor_on_monitor() {
+    let overlay =
+        CursorOverlay::on_monitor(Some((2560.0 + 960.0, 270.0)), None, &monitor()).unwrap();
+    assert_eq!(overlay.cursor_x, Some(0.5));
+    assert_eq!(overlay.cursor_y, Some(0.25));
+    assert_eq!(overlay.selected_text, None);
+
+    // on the primary monitor
+    assert!(CursorOverlay::on_monitor(Some((100.0, 100.0)), None, &monitor()).is_none());
+    assert!(CursorOverlay::on_monitor(None, None, &monitor()).is_none());
+}
+
+#[test]
+fn test_selection_on_monitor() {
+    let selection = Selection {
+        text: "quarterly revenue".to_string(),
+        bounds: Some(Bounds {
+            x: 2560.0 + 192.0,
+            y: 540.0,
+            width: 384.0,
+            height: 27.0,
+        }),
+    };
+    // the pointer on another monitor than the selection
+    let overlay =
+        CursorOverlay::on_monitor(Some((100.0, 100.0)), Some(selection.clone()), &monitor())
+            .unwrap();
+    assert_eq!(overlay.cursor_x, None);
+    assert_eq!(overlay.selected_text.as_deref(), Some("quarterly revenue"));
+    let bounds = overlay.selection.unwrap();
+    assert_eq!((bounds.left, bounds.top), (0.1, 0.5));
+    assert_eq!((bounds.width, bounds.height), (0.2, 0.025));
+
+    // without its position, the selection goes with the monitor of the pointer
+    let selection = Selection {
+        bounds: None,
+        ..selection
+    };
+    assert!(
+        CursorOverlay::on_monitor(Some((100.0, 100.0)), Some(selection.clone()), &monitor())
+            .is_none()
+    );
+    let overlay =
+        CursorOverlay::on_monitor(Some((2600.0, 100.0)), Some(selection), &monitor()).unwrap();
+    assert_eq!(overlay.selected_text.as_deref(), Some("quarterly revenue"));
+    assert_eq!(overlay.selection, None);
+}
+
+#[test]
+fn test_parse_xdotool_location() {
+    assert_eq!(
+        parse_xdotool_location("X=812\nY=440\nSCREEN=0\nWINDOW=62914567\n"),
+        Some((812.0, 440.0))
+    );
+    assert_eq!(parse_xdotool_location("SCREEN=0\n"), None);
+}
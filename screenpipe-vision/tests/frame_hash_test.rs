@@ -0,0 +1,60 @@
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use screenpipe_vision::frame_hash::FrameHash;
+use std::path::PathBuf;
+
+fn screenshot() -> DynamicImage {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("testing_OCR.png");
+    image::open(path).unwrap()
+}
+
+/// First bright pixel below the menu bar, part of a character of the dark screenshot
+fn first_character(image: &DynamicImage) -> (u32, u32) {
+    image
+        .pixels()
+        .find(|(_, y, Rgba([r, g, b, _]))| *y >= 100 && (*r as u32 + *g as u32 + *b as u32) > 500)
+        .map(|(x, y, _)| (x, y))
+        .unwrap()
+}
+
+#[test]
+fn test_same_frame() {
+    let image = screenshot();
+    assert!(FrameHash::new(&image).is_same_as(&FrameHash::new(&image.clone())));
+}
+
+#[test]
+fn test_deleted_character_is_not_a_duplicate() {
+    let image = screenshot();
+    let background = image.get_pixel(0, 0);
+    let (x, y) = first_character(&image);
+
+    // the character erased with the background, as after a backspace
+    let mut edited = image.clone();
+    for dy in 0..12 {
+        for dx in 0..7 {
+            edited.put_pixel(x + dx, y + dy, background);
+        }
+    }
+    assert!(!FrameHash::new(&edited).is_same_as(&FrameHash::new(&image)));
+}
+
+#[test]
+fn test_typed_characters_are_not_a_duplicate() {
+    let image = screenshot();
+    let (x, y) = first_character(&image);
+
+    // three characters copied over the ones after them
+    let mut edited = image.clone();
+    let glyphs = image.crop_imm(x, y, 21, 12);
+    edited.copy_from(&glyphs, x + 21, y).unwrap();
+    assert_ne!(edited.as_bytes(), image.as_bytes());
+    assert!(!FrameHash::new(&edited).is_same_as(&FrameHash::new(&image)));
+
+    // down to a single pixel
+    let mut edited = image.clone();
+    let Rgba([r, g, b, a]) = image.get_pixel(x, y);
+    edited.put_pixel(x, y, Rgba([r ^ 1, g, b, a]));
+    assert!(!FrameHash::new(&edited).is_same_as(&FrameHash::new(&image)));
+}
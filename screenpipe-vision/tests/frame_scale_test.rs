@@ -0,0 +1,38 @@
+use image::{DynamicImage, GenericImageView};
+use screenpipe_vision::frame_scale::FrameScale;
+
+#[test]
+fn test_parse_frame_scale() {
+    assert_eq!("full".parse(), Ok(FrameScale::Full));
+    assert_eq!("1".parse(), Ok(FrameScale::Full));
+    assert_eq!("0.5".parse(), Ok(FrameScale::Factor(0.5)));
+    assert_eq!("1920px".parse(), Ok(FrameScale::MaxDimension(1920)));
+    assert!("2".parse::<FrameScale>().is_err());
+    assert!("0".parse::<FrameScale>().is_err());
+    assert!("0px".parse::<FrameScale>().is_err());
+    assert!("half".parse::<FrameScale>().is_err());
+}
+
+#[test]
+fn test_frame_scale() {
+    // a 5K display
+    let image = DynamicImage::new_rgba8(5120, 2880);
+    assert!(FrameScale::Full.apply(&image).is_none());
+    assert_eq!(
+        FrameScale::Factor(0.5).apply(&image).unwrap().dimensions(),
+        (2560, 1440)
+    );
+    assert_eq!(
+        FrameScale::MaxDimension(1920)
+            .apply(&image)
+            .unwrap()
+            .dimensions(),
+        (1920, 1080)
+    );
+
+    // smaller frames are left as they are
+    assert_eq!(FrameScale::MaxDimension(1920).factor(1280, 720), 1.0);
+    assert!(FrameScale::MaxDimension(1920)
+        .apply(&DynamicImage::new_rgba8(1280, 720))
+        .is_none());
+}
@@ -0,0 +1,66 @@
+use screenpipe_vision::ocr_filter::{filter_words, is_junk, OcrFilterConfig};
+use std::collections::HashMap;
+
+fn word(line_num: &str, conf: &str, text: &str) -> HashMap<String, String> {
+    HashMap::from([
+        ("block_num".to_string(), "1".to_string()),
+        ("par_num".to_string(), "1".to_string()),
+        ("line_num".to_string(), line_num.to_string()),
+        ("conf".to_string(), conf.to_string()),
+        ("text".to_string(), text.to_string()),
+    ])
+}
+
+#[test]
+fn test_is_junk() {
+    for junk in [
+        "|", "—", "|=~", "-.-'", "lllll", "tflrrk", "lIlIlI", "tHiSiS",
+    ] {
+        assert!(is_junk(junk), "{:?} should be junk", junk);
+    }
+    for word in [
+        "screenpipe",
+        "html",
+        "iPhone",
+        "McDonald",
+        "2025",
+        "0000",
+        "e.g.",
+        "管理分支",
+        "naïve",
+        "I",
+        "rhythm",
+    ] {
+        assert!(!is_junk(word), "{:?} shouldn't be junk", word);
+    }
+}
+
+#[test]
+fn test_filter_words() {
+    let words = vec![
+        word("1", "96.0", "hello"),
+        word("1", "91.5", "|||"),
+        word("1", "95.0", "world"),
+        word("2", "30.0", "gradient"),
+        word("2", "88.0", "screenpipe"),
+    ];
+    let config = OcrFilterConfig {
+        min_confidence: 0.5,
+        junk: true,
+    };
+
+    let (text, kept) = filter_words(&config, "hello ||| world gradient screenpipe".into(), words);
+
+    assert_eq!(text, "hello world\nscreenpipe");
+    assert_eq!(kept.len(), 3);
+}
+
+#[test]
+fn test_filter_words_keeps_results_without_confidences() {
+    let words = vec![HashMap::from([("text".to_string(), "|||".to_string())])];
+
+    let (text, kept) = filter_words(&OcrFilterConfig::default(), "|||".into(), words);
+
+    assert_eq!(text, "|||");
+    assert_eq!(kept.len(), 1);
+}
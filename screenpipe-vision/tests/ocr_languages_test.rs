@@ -0,0 +1,36 @@
+use screenpipe_core::Language;
+use screenpipe_vision::ocr_languages::{tesseract_languages, OcrLanguageOptions};
+
+#[test]
+fn test_tesseract_languages_keep_their_order() {
+    assert_eq!(
+        tesseract_languages(&[Language::Japanese, Language::English], false),
+        "jpn+eng"
+    );
+    assert_eq!(
+        tesseract_languages(
+            &[Language::German, Language::Japanese, Language::German],
+            false
+        ),
+        "deu+jpn"
+    );
+    assert_eq!(tesseract_languages(&[], false), "eng");
+}
+
+#[test]
+fn test_ocr_languages_fall_back_to_the_languages_given() {
+    let options = OcrLanguageOptions::default();
+    assert_eq!(
+        options.languages_or(&[Language::French]),
+        vec![Language::French]
+    );
+
+    let options = OcrLanguageOptions {
+        languages: vec![Language::Korean, Language::English],
+        auto_detect: true,
+    };
+    assert_eq!(
+        options.languages_or(&[Language::French]),
+        vec![Language::Korean, Language::English]
+    );
+}
@@ -0,0 +1,70 @@
+use screenpipe_vision::ocr_layout::OcrLayout;
+use std::collections::HashMap;
+
+fn word(line_num: &str, text: &str, left: f64, top: f64, width: f64) -> HashMap<String, String> {
+    HashMap::from([
+        ("block_num".to_string(), "1".to_string()),
+        ("par_num".to_string(), "1".to_string()),
+        ("line_num".to_string(), line_num.to_string()),
+        ("text".to_string(), text.to_string()),
+        ("conf".to_string(), "90".to_string()),
+        ("left".to_string(), left.to_string()),
+        ("top".to_string(), top.to_string()),
+        ("width".to_string(), width.to_string()),
+        ("height".to_string(), "20".to_string()),
+    ])
+}
+
+#[test]
+fn test_layout_groups_lines_into_blocks_in_reading_order() {
+    // two columns read line by line by the engine, the right one first
+    let words = vec![
+        word("1", "inbox", 600.0, 100.0, 100.0),
+        word("2", "archive", 600.0, 125.0, 140.0),
+        word("3", "hello", 100.0, 100.0, 100.0),
+        word("3", "world", 210.0, 100.0, 100.0),
+        word("4", "second", 100.0, 125.0, 120.0),
+        word("5", "footer", 100.0, 800.0, 120.0),
+    ];
+    let layout = OcrLayout::from_words(&words, 1000, 1000, false);
+
+    assert_eq!(layout.blocks.len(), 3);
+    assert_eq!(
+        layout.text(),
+        "hello world\nsecond\n\ninbox\narchive\n\nfooter"
+    );
+    let first = &layout.blocks[0].lines[0];
+    assert!((first.bbox.left - 0.1).abs() < 1e-9);
+    assert!((first.bbox.width - 0.21).abs() < 1e-9);
+    assert!((first.words[0].confidence - 0.9).abs() < 1e-9);
+}
+
+#[test]
+fn test_layout_flips_apple_boxes() {
+    let mut words = vec![
+        word("0", "bottom", 0.1, 0.1, 0.2),
+        word("1", "top", 0.1, 0.8, 0.2),
+    ];
+    for word in &mut words {
+        word.insert("height".to_string(), "0.05".to_string());
+    }
+    let layout = OcrLayout::from_words(&words, 1000, 1000, true);
+
+    let lines: Vec<_> = layout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].text, "top");
+    assert!((lines[0].bbox.top - 0.15).abs() < 1e-9);
+    assert_eq!(lines[1].text, "bottom");
+}
+
+#[test]
+fn test_layout_skips_words_without_boxes() {
+    let mut words = vec![word("1", "kept", 10.0, 10.0, 40.0)];
+    words.push(HashMap::from([
+        ("text".to_string(), "no box".to_string()),
+        ("conf".to_string(), "1.0".to_string()),
+    ]));
+    let layout = OcrLayout::from_words(&words, 100, 100, false);
+
+    assert_eq!(layout.text(), "kept");
+}
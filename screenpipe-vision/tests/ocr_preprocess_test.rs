@@ -0,0 +1,68 @@
+use image::{DynamicImage, GrayImage, Luma};
+use screenpipe_vision::ocr_preprocess::{
+    histogram, is_dark, otsu_threshold, preprocess, OcrPreprocessing,
+};
+
+/// A line of `text` luma on a `background`, like a line of code
+fn text_image(background: u8, text: u8) -> DynamicImage {
+    let mut image = GrayImage::from_pixel(200, 40, Luma([background]));
+    for x in 20..180 {
+        for y in 15..25 {
+            if x % 3 != 0 {
+                image.put_pixel(x, y, Luma([text]));
+            }
+        }
+    }
+    DynamicImage::ImageLuma8(image)
+}
+
+#[test]
+fn test_dark_theme_is_inverted() {
+    // light text on a dark editor background
+    let image = text_image(30, 220);
+    assert!(is_dark(&histogram(&image.to_luma8())));
+
+    let preprocessed = preprocess(&image, OcrPreprocessing::Auto).unwrap();
+    assert_eq!(preprocessed.width(), 200);
+    assert_eq!(preprocessed.height(), 40);
+    let luma = preprocessed.to_luma8();
+    // background light, text dark
+    assert!(luma.get_pixel(0, 0)[0] > 200);
+    assert!(luma.get_pixel(21, 20)[0] < 50);
+}
+
+#[test]
+fn test_light_image_is_kept() {
+    let image = text_image(255, 0);
+    assert!(!is_dark(&histogram(&image.to_luma8())));
+    assert!(preprocess(&image, OcrPreprocessing::Auto).is_none());
+    assert!(preprocess(&text_image(30, 220), OcrPreprocessing::Off).is_none());
+}
+
+#[test]
+fn test_dull_image_is_stretched() {
+    // grey text on a slightly lighter grey
+    let image = text_image(180, 120);
+    let luma = preprocess(&image, OcrPreprocessing::Auto)
+        .unwrap()
+        .to_luma8();
+    assert_eq!(luma.get_pixel(0, 0)[0], 255);
+    assert_eq!(luma.get_pixel(21, 20)[0], 0);
+}
+
+#[test]
+fn test_binarize() {
+    let mut histogram = [0; 256];
+    histogram[40] = 100;
+    histogram[50] = 100;
+    histogram[200] = 300;
+    let threshold = otsu_threshold(&histogram);
+    assert!((50..200).contains(&threshold));
+
+    let luma = preprocess(&text_image(40, 200), OcrPreprocessing::Binarize)
+        .unwrap()
+        .to_luma8();
+    assert!(luma.pixels().all(|pixel| pixel[0] == 0 || pixel[0] == 255));
+    assert_eq!(luma.get_pixel(0, 0)[0], 255);
+    assert_eq!(luma.get_pixel(21, 20)[0], 0);
+}
@@ -0,0 +1,101 @@
+use screenpipe_vision::ocr_layout::OcrLayout;
+use screenpipe_vision::ocr_structure::{extract_structures, StructuredContent};
+use std::collections::HashMap;
+
+// 10 pixels a character, 20 a line
+fn word(line_num: &str, text: &str, left: u32, top: u32) -> HashMap<String, String> {
+    HashMap::from([
+        ("block_num".to_string(), "1".to_string()),
+        ("par_num".to_string(), "1".to_string()),
+        ("line_num".to_string(), line_num.to_string()),
+        ("text".to_string(), text.to_string()),
+        ("conf".to_string(), "90".to_string()),
+        ("left".to_string(), left.to_string()),
+        ("top".to_string(), top.to_string()),
+        ("width".to_string(), (text.chars().count() * 10).to_string()),
+        ("height".to_string(), "20".to_string()),
+    ])
+}
+
+#[test]
+fn test_extracts_table_from_cells_read_apart() {
+    // the engine reads each cell of the spreadsheet on its own, numbers aligned right
+    let words = vec![
+        word("1", "region", 100, 100),
+        word("2", "emea", 100, 125),
+        word("3", "apac", 100, 150),
+        word("4", "q2", 430, 100),
+        word("5", "1,204", 400, 125),
+        word("6", "980", 420, 150),
+        word("7", "q3", 630, 100),
+        word("8", "1,530", 600, 125),
+        word("9", "1,012", 600, 150),
+    ];
+    let layout = OcrLayout::from_words(&words, 1000, 1000, false);
+    let structures = extract_structures(&layout);
+
+    assert_eq!(structures.len(), 1);
+    assert_eq!(structures[0].kind(), "table");
+    assert_eq!(
+        structures[0].text(),
+        "region\tq2\tq3\nemea\t1,204\t1,530\napac\t980\t1,012"
+    );
+    assert!((structures[0].bbox.top - 0.1).abs() < 1e-9);
+}
+
+#[test]
+fn test_extracts_table_from_spaced_out_terminal_lines() {
+    let words = vec![
+        word("1", "PID", 100, 100),
+        word("1", "COMMAND", 200, 100),
+        word("2", "412", 100, 125),
+        word("2", "screenpipe", 200, 125),
+    ];
+    let layout = OcrLayout::from_words(&words, 1000, 1000, false);
+    let structures = extract_structures(&layout);
+
+    assert_eq!(structures.len(), 1);
+    assert_eq!(
+        structures[0].content,
+        StructuredContent::Table {
+            rows: vec![
+                vec!["PID".to_string(), "COMMAND".to_string()],
+                vec!["412".to_string(), "screenpipe".to_string()],
+            ]
+        }
+    );
+}
+
+#[test]
+fn test_extracts_code_with_its_indentation() {
+    let words = vec![
+        word("1", "fn", 100, 100),
+        word("1", "main()", 130, 100),
+        word("1", "{", 200, 100),
+        word("2", "let", 140, 125),
+        word("2", "x", 180, 125),
+        word("2", "=", 200, 125),
+        word("2", "1;", 220, 125),
+        word("3", "}", 100, 150),
+    ];
+    let layout = OcrLayout::from_words(&words, 1000, 1000, false);
+    let structures = extract_structures(&layout);
+
+    assert_eq!(structures.len(), 1);
+    assert_eq!(structures[0].kind(), "code");
+    assert_eq!(structures[0].text(), "fn main() {\n    let x = 1;\n}");
+}
+
+#[test]
+fn test_prose_has_no_structure() {
+    let words = vec![
+        word("1", "screenpipe", 100, 100),
+        word("1", "records", 210, 100),
+        word("1", "your", 290, 100),
+        word("2", "screen", 100, 125),
+        word("2", "locally", 170, 125),
+    ];
+    let layout = OcrLayout::from_words(&words, 1000, 1000, false);
+
+    assert!(extract_structures(&layout).is_empty());
+}
@@ -0,0 +1,26 @@
+use screenpipe_core::Language;
+use screenpipe_vision::perform_ocr_paddle;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[tokio::test]
+#[ignore] // downloads the paddleocr models
+async fn test_paddle_ocr_chinese() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.push("testing_OCR_chinese.png");
+    let image = image::open(&path).expect("Failed to open Chinese test image");
+
+    let (text, json_output, confidence) = perform_ocr_paddle(&image, vec![Language::Chinese])
+        .await
+        .unwrap();
+
+    assert!(
+        text.contains("管理分支"),
+        "OCR failed to recognize Chinese text: {:?}",
+        text
+    );
+    assert!(confidence.unwrap() > 0.5);
+    let words: Vec<HashMap<String, String>> = serde_json::from_str(&json_output).unwrap();
+    assert!(words.iter().any(|word| word["text"].contains("管理分支")));
+}
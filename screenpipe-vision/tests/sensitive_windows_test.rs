@@ -0,0 +1,65 @@
+use image::{DynamicImage, GenericImageView, Rgba};
+use screenpipe_vision::active_window::ActiveApp;
+use screenpipe_vision::sensitive_windows::{blank_region, SensitiveWindows};
+
+#[test]
+fn test_sensitive_windows() {
+    let builtin = SensitiveWindows::new(true, &[]);
+    assert!(builtin.is_sensitive("1Password 7", "Vault"));
+    assert!(builtin.is_sensitive("Google Chrome", "Chase Bank - Accounts"));
+    assert!(builtin.is_sensitive("firefox", "PayPal: Summary"));
+    // banks are matched as whole words only
+    assert!(!builtin.is_sensitive("Google Chrome", "Thames Embankment - Wikipedia"));
+    assert!(!builtin.is_sensitive("Code", "bankruptcy.rs"));
+
+    let custom = SensitiveWindows::new(false, &["Signal".to_string()]);
+    assert!(custom.is_sensitive("Signal", "Chats"));
+    assert!(!custom.is_sensitive("1Password 7", "Vault"));
+    assert!(SensitiveWindows::new(false, &[]).is_empty());
+
+    let private = SensitiveWindows::new(false, &[]).with_private_windows(true);
+    assert!(!private.is_empty());
+    assert!(private.is_sensitive("Google Chrome", "Inbox - Google Chrome (Incognito)"));
+    assert!(!private.is_sensitive("Code", "incognito.rs"));
+}
+
+#[test]
+fn test_blank_region() {
+    let mut image = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+        10,
+        10,
+        Rgba([255, 255, 255, 255]),
+    ));
+    // clipped to the image
+    blank_region(&mut image, -2, 5, 5, 10);
+    assert_eq!(image.get_pixel(0, 5), Rgba([0, 0, 0, 255]));
+    assert_eq!(image.get_pixel(2, 9), Rgba([0, 0, 0, 255]));
+    assert_eq!(image.get_pixel(3, 9), Rgba([255, 255, 255, 255]));
+    assert_eq!(image.get_pixel(0, 4), Rgba([255, 255, 255, 255]));
+    blank_region(&mut image, 20, 20, 5, 5);
+}
+
+#[test]
+fn test_hide_title_of_sensitive_focused_window() {
+    let sensitive_windows = SensitiveWindows::new(false, &[]).with_private_windows(true);
+    let mut private = ActiveApp {
+        name: "Google Chrome".to_string(),
+        process_name: "chrome".to_string(),
+        bundle_id: None,
+        window_title: "Inbox - Google Chrome (Incognito)".to_string(),
+        process_id: 42,
+    };
+    assert!(sensitive_windows.hide_title(&mut private));
+    assert_eq!(private.window_title, "");
+    assert_eq!(private.name, "Google Chrome");
+
+    let mut code = ActiveApp {
+        name: "Code".to_string(),
+        process_name: "code".to_string(),
+        bundle_id: None,
+        window_title: "incognito.rs".to_string(),
+        process_id: 43,
+    };
+    assert!(!sensitive_windows.hide_title(&mut code));
+    assert_eq!(code.window_title, "incognito.rs");
+}
@@ -0,0 +1,27 @@
+use screenpipe_vision::video_call::is_call_window;
+
+#[test]
+fn test_is_call_window() {
+    assert!(is_call_window("zoom.us", "Zoom Meeting"));
+    assert!(is_call_window(
+        "Microsoft Teams",
+        "Meeting with Ana | Microsoft Teams"
+    ));
+    assert!(is_call_window(
+        "Google Chrome",
+        "Meet - abc-defg-hij - Google Chrome"
+    ));
+    assert!(is_call_window("Slack", "Huddle: #general"));
+
+    // the apps outside of calls
+    assert!(!is_call_window("zoom.us", "Zoom Workplace"));
+    assert!(!is_call_window(
+        "Microsoft Teams",
+        "Chat | Ana | Microsoft Teams"
+    ));
+    assert!(!is_call_window(
+        "Google Chrome",
+        "Google Meet - Google Chrome"
+    ));
+    assert!(!is_call_window("Code", "meeting_notes.md"));
+}
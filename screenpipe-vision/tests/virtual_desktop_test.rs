@@ -0,0 +1,22 @@
+use screenpipe_vision::virtual_desktop::{desktop_number, parse_xprop_desktop};
+
+#[test]
+fn test_parse_xprop_desktop() {
+    assert_eq!(parse_xprop_desktop("_NET_CURRENT_DESKTOP = 0\n"), Some(1));
+    assert_eq!(parse_xprop_desktop("_NET_CURRENT_DESKTOP = 3\n"), Some(4));
+    // window managers without desktops
+    assert_eq!(
+        parse_xprop_desktop("_NET_CURRENT_DESKTOP:  not found.\n"),
+        None
+    );
+    assert_eq!(parse_xprop_desktop(""), None);
+}
+
+#[test]
+fn test_desktop_number() {
+    let ids: Vec<u8> = [[1u8; 16], [2; 16], [3; 16]].concat();
+    assert_eq!(desktop_number(&ids, &[2; 16]), Some(2));
+    assert_eq!(desktop_number(&ids, &[3; 16]), Some(3));
+    // a desktop closed since
+    assert_eq!(desktop_number(&ids, &[4; 16]), None);
+}
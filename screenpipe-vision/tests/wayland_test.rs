@@ -0,0 +1,24 @@
+#[cfg(all(target_os = "linux", feature = "pipewire"))]
+mod tests {
+    use screenpipe_vision::wayland::frame_image;
+
+    #[test]
+    fn test_frame_image() {
+        // 2 x 2 pixels, rows padded to 12 bytes
+        let bytes = [
+            1, 2, 3, 0, 4, 5, 6, 0, 9, 9, 9, 9, //
+            7, 8, 9, 0, 10, 11, 12, 0,
+        ];
+        let image = frame_image(&bytes, 2, 2, 12, true).unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [3, 2, 1, 255]);
+        assert_eq!(image.get_pixel(1, 1).0, [12, 11, 10, 255]);
+
+        let image = frame_image(&bytes, 2, 2, 12, false).unwrap();
+        assert_eq!(image.get_pixel(1, 0).0, [4, 5, 6, 255]);
+        assert_eq!(image.get_pixel(0, 1).0, [7, 8, 9, 255]);
+
+        // too short for its size
+        assert!(frame_image(&bytes[..16], 2, 2, 12, true).is_none());
+        assert!(frame_image(&bytes, 2, 2, 4, true).is_none());
+    }
+}
@@ -3,7 +3,8 @@
 mod tests {
     use screenpipe_vision::core::OcrTaskData;
     use screenpipe_vision::monitor::get_default_monitor;
-    use screenpipe_vision::{process_ocr_task, OcrEngine};
+    use screenpipe_vision::{perform_ocr_windows, process_ocr_task, OcrEngine};
+    use std::collections::HashMap;
     use std::{path::PathBuf, time::Instant};
     use tokio::sync::mpsc;
 
@@ -11,6 +12,27 @@ mod tests {
     use std::time::Duration;
     use tokio::time::timeout;
 
+    #[tokio::test]
+    async fn test_windows_ocr_returns_word_boxes() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests");
+        path.push("testing_OCR.png");
+        let image = image::open(&path).expect("Failed to open image");
+
+        let (text, json_output, _) = perform_ocr_windows(&image).await.unwrap();
+        let words: Vec<HashMap<String, String>> = serde_json::from_str(&json_output).unwrap();
+
+        assert!(!text.is_empty());
+        assert!(!words.is_empty());
+        for word in &words {
+            for key in ["text", "left", "top", "width", "height", "conf"] {
+                assert!(word.contains_key(key), "missing {} in {:?}", key, word);
+            }
+            assert!(word["width"].parse::<f32>().unwrap() > 0.0);
+            assert!(text.contains(&word["text"]));
+        }
+    }
+
     #[cfg(target_os = "windows")]
     #[tokio::test]
     async fn test_process_ocr_task_windows() {
@@ -40,6 +62,7 @@ mod tests {
                 window_images,
                 frame_number,
                 timestamp,
+                monitor: get_default_monitor().await.get_info(),
                 result_tx: tx,
             },
             false,